@@ -0,0 +1,399 @@
+// src/alarm.rs
+use rand::Rng;
+use vehicle_sim_core::driver;
+use vehicle_sim_core::events::Event;
+use vehicle_sim_core::locale;
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::sim_log;
+
+pub const STEP: f64 = 0.1; // s
+// Siren and hazard lights per alarm, and how many alarms one arming may
+// raise before the siren stays silent (ECE R116)
+pub const SIREN_TIME: f64 = 30.0; // s
+pub const MAX_ALARMS: u32 = 10;
+// Pressing lock twice within this time arms without the interior and tilt
+// sensors, for a pet left inside or a car on a ferry
+const DOUBLE_LOCK_TIME: f64 = 3.0; // s
+
+// Ultrasonic interior sensor: the disturbance of the echo, 0 for a still
+// cabin and 1 for someone climbing in, must stay above the threshold this
+// long so a jolt or a gust does not set it off
+const MOTION_THRESHOLD: f64 = 0.5;
+const MOTION_CONFIRM: f64 = 0.5; // s
+const MOTION_NOISE: f64 = 0.1;
+// Tilt sensor: a change of the body angle by more than the threshold from
+// the reference taken at arming is a tow or a jack. The reference follows
+// slow changes (a tyre losing air, the car settling) at up to DRIFT_RATE.
+const TILT_THRESHOLD: f64 = 1.0; // °
+const DRIFT_RATE: f64 = 0.01; // °/s
+const TILT_NOISE: f64 = 0.05; // °
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sensor {
+    Door,
+    Motion,
+    Tilt,
+}
+
+impl Sensor {
+    pub fn name(self) -> &'static str {
+        match self {
+            Sensor::Door => "door contact",
+            Sensor::Motion => "interior motion sensor",
+            Sensor::Tilt => "tilt sensor",
+        }
+    }
+
+    fn report(self) -> &'static str {
+        match self {
+            Sensor::Door => "break-in: door opened while armed",
+            Sensor::Motion => "break-in: movement inside the cabin",
+            Sensor::Tilt => "theft: vehicle being towed or lifted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Lock,
+    Unlock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlarmState {
+    Disarmed,
+    Armed,
+    // Siren and hazard lights on, the time left
+    Sounding(f64),
+}
+
+// What the telematics unit sends to the owner and the monitoring center
+#[derive(Debug, Clone, PartialEq)]
+pub struct TheftReport {
+    pub time: f64, // s
+    pub sensor: Sensor,
+    pub message: String,
+}
+
+// The alarm in the body module. It reads the door contacts and the two
+// sensors every step and is armed and disarmed by a paired key fob only.
+pub struct Alarm {
+    pub state: AlarmState,
+    // Interior and tilt sensors, off after a double lock
+    pub monitoring: bool,
+    last_lock: Option<f64>,
+    door_open: bool,
+    motion_time: f64,
+    tilt_reference: f64,
+    pub alarms: u32,
+    pub siren_time: f64, // s
+    pub time: f64,
+    pub events: Vec<(f64, String)>,
+    pub reports: Vec<TheftReport>,
+}
+
+impl Alarm {
+    pub fn new() -> Self {
+        Alarm {
+            state: AlarmState::Disarmed,
+            monitoring: true,
+            last_lock: None,
+            door_open: false,
+            motion_time: 0.0,
+            tilt_reference: 0.0,
+            alarms: 0,
+            siren_time: 0.0,
+            time: 0.0,
+            events: Vec::new(),
+            reports: Vec::new(),
+        }
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.state != AlarmState::Disarmed
+    }
+
+    // Returns whether the fob was accepted; `tilt` is the body angle now,
+    // the reference for the tilt sensor
+    pub fn fob(&mut self, key_fob_id: &str, button: Button, tilt: f64) -> bool {
+        if driver::find_profile(key_fob_id).is_none() {
+            self.log(format!("key fob {} is not paired, ignored", key_fob_id));
+            return false;
+        }
+        match button {
+            Button::Lock if self.door_open => {
+                self.log("door open, not armed".to_string());
+                return false;
+            }
+            Button::Lock => {
+                let double = self.last_lock.is_some_and(|at| self.time - at <= DOUBLE_LOCK_TIME);
+                if double && self.state == AlarmState::Armed {
+                    self.monitoring = false;
+                    self.log("armed without interior and tilt monitoring".to_string());
+                } else if !self.is_armed() {
+                    self.state = AlarmState::Armed;
+                    self.monitoring = true;
+                    self.alarms = 0;
+                    self.motion_time = 0.0;
+                    self.tilt_reference = tilt;
+                    self.log(format!("armed with {}", key_fob_id));
+                }
+                self.last_lock = Some(self.time);
+            }
+            Button::Unlock => {
+                if self.is_armed() {
+                    self.log(format!("disarmed with {}", key_fob_id));
+                }
+                self.state = AlarmState::Disarmed;
+                self.last_lock = None;
+            }
+        }
+        true
+    }
+
+    // One step with the door contacts, the echo disturbance and the body
+    // angle as the sensors measure them
+    pub fn step(&mut self, door_open: bool, motion: f64, tilt: f64) {
+        self.time += STEP;
+        let door_opened = door_open && !self.door_open;
+        self.door_open = door_open;
+
+        self.motion_time = if motion > MOTION_THRESHOLD { self.motion_time + STEP } else { 0.0 };
+        let drift = (tilt - self.tilt_reference).clamp(-DRIFT_RATE * STEP, DRIFT_RATE * STEP);
+        self.tilt_reference += drift;
+
+        if let AlarmState::Sounding(left) = self.state {
+            self.siren_time += STEP;
+            let left = left - STEP;
+            self.state = if left > 0.0 {
+                AlarmState::Sounding(left)
+            } else {
+                self.log("siren off, armed again".to_string());
+                AlarmState::Armed
+            };
+            return;
+        }
+        if self.state != AlarmState::Armed {
+            return;
+        }
+        let triggered = if door_opened {
+            Some(Sensor::Door)
+        } else if self.monitoring && self.motion_time >= MOTION_CONFIRM {
+            Some(Sensor::Motion)
+        } else if self.monitoring && (tilt - self.tilt_reference).abs() > TILT_THRESHOLD {
+            Some(Sensor::Tilt)
+        } else {
+            None
+        };
+        if let Some(sensor) = triggered {
+            self.trigger(sensor, tilt);
+        }
+    }
+
+    fn trigger(&mut self, sensor: Sensor, tilt: f64) {
+        // Only the first alarms of an arming sound, the report goes out anyway
+        self.alarms += 1;
+        if self.alarms <= MAX_ALARMS {
+            self.state = AlarmState::Sounding(SIREN_TIME);
+            self.log(format!("{} triggered, siren and hazard lights on", sensor.name()));
+        } else {
+            self.log(format!("{} triggered, siren limit reached", sensor.name()));
+        }
+        // A tow keeps going; re-reference so it is reported once per alarm
+        self.tilt_reference = tilt;
+        self.motion_time = 0.0;
+
+        let report = TheftReport {
+            time: self.time,
+            sensor,
+            message: sensor.report().to_string(),
+        };
+        sim_log::event(
+            None,
+            &Event::WarningRaised {
+                source: "alarm".to_string(),
+                message: report.message.clone(),
+            },
+        );
+        self.reports.push(report);
+    }
+
+    fn log(&mut self, message: String) {
+        self.events.push((self.time, message));
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    Fob(&'static str, Button),
+    Door(bool),
+    // Disturbance of the interior sensor's echo from now on
+    Motion(f64),
+    // How fast the body angle changes from now on, °/s
+    TiltRate(f64),
+}
+
+pub struct AlarmScenario {
+    pub name: &'static str,
+    pub duration: f64, // s
+    pub actions: &'static [(f64, Action)],
+}
+
+pub const SCENARIOS: &[AlarmScenario] = &[
+    AlarmScenario {
+        name: "Owner locks and comes back",
+        duration: 120.0,
+        actions: &[
+            (1.0, Action::Fob("FOB-1", Button::Lock)),
+            (90.0, Action::Fob("FOB-1", Button::Unlock)),
+            (92.0, Action::Door(true)),
+            (95.0, Action::Motion(0.9)),
+        ],
+    },
+    AlarmScenario {
+        name: "Door forced open",
+        duration: 120.0,
+        actions: &[
+            (1.0, Action::Fob("FOB-2", Button::Lock)),
+            (20.0, Action::Door(true)),
+            (25.0, Action::Door(false)),
+            (100.0, Action::Fob("FOB-2", Button::Unlock)),
+        ],
+    },
+    AlarmScenario {
+        name: "Unknown key fob, then a smashed window",
+        duration: 120.0,
+        actions: &[
+            (1.0, Action::Fob("FOB-1", Button::Lock)),
+            (10.0, Action::Fob("FOB-9", Button::Unlock)),
+            // The glass breaking jolts the sensor, then someone reaches in
+            (20.0, Action::Motion(0.8)),
+            (20.2, Action::Motion(0.0)),
+            (24.0, Action::Motion(0.9)),
+            (30.0, Action::Motion(0.0)),
+        ],
+    },
+    AlarmScenario {
+        name: "Towed away",
+        duration: 120.0,
+        actions: &[
+            (1.0, Action::Fob("FOB-1", Button::Lock)),
+            (60.0, Action::TiltRate(0.4)),
+            (70.0, Action::TiltRate(0.0)),
+        ],
+    },
+    AlarmScenario {
+        name: "Tyre losing air overnight",
+        duration: 7200.0,
+        actions: &[
+            (1.0, Action::Fob("FOB-3", Button::Lock)),
+            (10.0, Action::TiltRate(3.0 / 3600.0)),
+        ],
+    },
+    AlarmScenario {
+        name: "Dog left inside, locked twice",
+        duration: 120.0,
+        actions: &[
+            (1.0, Action::Fob("FOB-1", Button::Lock)),
+            (2.0, Action::Fob("FOB-1", Button::Lock)),
+            (10.0, Action::Motion(0.9)),
+        ],
+    },
+];
+
+pub struct ScenarioRun {
+    pub name: &'static str,
+    pub state: AlarmState,
+    pub alarms: u32,
+    pub siren_time: f64,
+    pub events: Vec<(f64, String)>,
+    pub reports: Vec<TheftReport>,
+}
+
+pub fn run(scenario: &AlarmScenario, rng: &mut SimRng) -> ScenarioRun {
+    let mut alarm = Alarm::new();
+    let (mut door_open, mut motion, mut tilt_rate, mut tilt) = (false, 0.0, 0.0, 0.0);
+    let mut actions = scenario.actions.iter().peekable();
+    while alarm.time < scenario.duration {
+        while let Some((_, action)) = actions.next_if(|(at, _)| *at <= alarm.time + 1e-9) {
+            match *action {
+                Action::Fob(key_fob_id, button) => {
+                    alarm.fob(key_fob_id, button, tilt);
+                }
+                Action::Door(open) => door_open = open,
+                Action::Motion(level) => motion = level,
+                Action::TiltRate(rate) => tilt_rate = rate,
+            }
+        }
+        tilt += tilt_rate * STEP;
+        let measured_motion = motion + rng.gen_range(0.0..MOTION_NOISE);
+        let measured_tilt = tilt + rng.gen_range(-TILT_NOISE..TILT_NOISE);
+        alarm.step(door_open, measured_motion, measured_tilt);
+    }
+
+    ScenarioRun {
+        name: scenario.name,
+        state: alarm.state,
+        alarms: alarm.alarms,
+        siren_time: alarm.siren_time,
+        events: alarm.events,
+        reports: alarm.reports,
+    }
+}
+
+pub fn print_report(runs: &[ScenarioRun]) {
+    let locale = locale::current();
+    for run in runs {
+        println!("--- {} ---", run.name);
+        for (time, event) in &run.events {
+            println!("  {:>7} s  {}", locale.number(*time, 1), event);
+        }
+        for report in &run.reports {
+            println!("  {:>7} s  reported: {}", locale.number(report.time, 1), report.message);
+        }
+        let state = match run.state {
+            AlarmState::Disarmed => "disarmed",
+            AlarmState::Armed => "armed",
+            AlarmState::Sounding(_) => "sounding",
+        };
+        println!(
+            "  {} alarm(s), siren on for {} s, {} at the end",
+            run.alarms,
+            locale.number(run.siren_time, 0),
+            state
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forced_door_sounds_the_siren_and_reports_a_break_in() {
+        let run = run(&SCENARIOS[1], &mut SimRng::from_seed(1));
+        assert_eq!(run.alarms, 1);
+        assert!((run.siren_time - SIREN_TIME).abs() < STEP + 1e-9);
+        assert_eq!(run.reports.len(), 1);
+        assert_eq!(run.reports[0].sensor, Sensor::Door);
+        assert_eq!(run.state, AlarmState::Disarmed);
+
+        // Neither the owner nor a dog behind a double lock sets it off
+        for scenario in [&SCENARIOS[0], &SCENARIOS[5]] {
+            assert!(run_quiet(scenario));
+        }
+    }
+
+    #[test]
+    fn tilt_sensor_catches_a_tow_but_follows_a_deflating_tyre() {
+        let towed = run(&SCENARIOS[3], &mut SimRng::from_seed(2));
+        assert_eq!(towed.reports.first().map(|report| report.sensor), Some(Sensor::Tilt));
+        // 1° at 0.4 °/s, within a few steps of noise
+        assert!(towed.reports[0].time < 60.0 + TILT_THRESHOLD / 0.4 + 1.0);
+        assert!(run_quiet(&SCENARIOS[4]));
+    }
+
+    fn run_quiet(scenario: &AlarmScenario) -> bool {
+        (0..5).all(|seed| run(scenario, &mut SimRng::from_seed(seed)).reports.is_empty())
+    }
+}
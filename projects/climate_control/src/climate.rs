@@ -1,48 +1,559 @@
 // src/climate.rs
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::dtc::{Debounce, DtcConfig, DtcManager, FaultReport};
+use vehicle_sim_core::locale;
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::units::{Celsius, Temperature};
 
-pub struct ClimateControlSystem {
+use crate::defog::{DefogSystem, HumidityState};
+
+// Cabin plant model: heat capacity of the cabin air and interior, and the
+// heat flow through the body per degree of difference to the outside
+const CABIN_HEAT_CAPACITY: f32 = 30_000.0; // J/K
+const CABIN_HEAT_LOSS: f32 = 40.0; // W/K
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PidConfig {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    // HVAC power limits in watts, negative values cool the cabin
+    pub output_min: f32,
+    pub output_max: f32,
+}
+
+impl Default for PidConfig {
+    fn default() -> Self {
+        PidConfig {
+            kp: 3000.0,
+            ki: 50.0,
+            kd: 0.0,
+            output_min: -4000.0,
+            output_max: 5000.0,
+        }
+    }
+}
+
+impl PidConfig {
+    // Gains and limits for a plant `factor` times the size of the cabin
+    pub fn scaled(self, factor: f32) -> Self {
+        PidConfig {
+            kp: self.kp * factor,
+            ki: self.ki * factor,
+            kd: self.kd * factor,
+            output_min: self.output_min * factor,
+            output_max: self.output_max * factor,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PidController {
+    config: PidConfig,
+    integral: f32,
+    previous_error: Option<f32>,
+}
+
+impl PidController {
+    pub fn new(config: PidConfig) -> Self {
+        PidController {
+            config,
+            integral: 0.0,
+            previous_error: None,
+        }
+    }
+
+    // Retunes the controller, keeping its integral so the output does not jump
+    pub fn set_config(&mut self, config: PidConfig) {
+        self.config = config;
+    }
+
+    pub fn update(&mut self, error: f32, dt: f32) -> f32 {
+        let config = &self.config;
+        let derivative = self.previous_error.map_or(0.0, |previous| (error - previous) / dt);
+        self.previous_error = Some(error);
+
+        let unclamped = config.kp * error + config.ki * (self.integral + error * dt) + config.kd * derivative;
+        let output = unclamped.clamp(config.output_min, config.output_max);
+
+        // Anti-windup: only integrate while the output is not saturated, or
+        // when the error drives it back out of saturation
+        let saturated_high = unclamped > config.output_max && error > 0.0;
+        let saturated_low = unclamped < config.output_min && error < 0.0;
+        if !saturated_high && !saturated_low {
+            self.integral += error * dt;
+        }
+
+        output
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Zone {
+    Driver,
+    Passenger,
+    Rear,
+}
+
+impl Zone {
+    pub const ALL: [Zone; 3] = [Zone::Driver, Zone::Passenger, Zone::Rear];
+
+    // Share of the cabin volume (and body surface) served by the zone
+    fn cabin_share(self) -> f32 {
+        match self {
+            Zone::Driver | Zone::Passenger => 0.3,
+            Zone::Rear => 0.4,
+        }
+    }
+
+    // Zone performance codes: B1A20 driver, B1A21 passenger, B1A22 rear
+    pub fn fault_code(self) -> String {
+        let index = Zone::ALL.iter().position(|&zone| zone == self).unwrap_or_default();
+        format!("B1A2{}", index)
+    }
+}
+
+// Relative cabin humidity when the simulation starts
+pub const DEFAULT_CABIN_HUMIDITY: f32 = 0.5;
+
+// A zone counts as settled within this distance of its setpoint
+const STABILIZED_TOLERANCE: f32 = 0.1; // °C
+
+// Heat flow between two neighbouring zones per degree of difference
+const ZONE_COUPLING: f32 = 15.0; // W/K
+
+// Zone performance monitor: a zone that has held its setpoint with the HVAC
+// running for this long has to be within ZONE_ERROR_LIMIT of it
+const ZONE_RESPONSE_TIME: f32 = 600.0; // s
+const ZONE_ERROR_LIMIT: f32 = 2.0; // °C
+
+// The monitor runs every 1 s step: 30 s off the setpoint set the fault, 10 s
+// back within the limit reset it. A comfort fault does not light a lamp.
+pub const DTC_CONFIG: DtcConfig = DtcConfig {
+    debounce: Debounce::counting(30, 10),
+    confirmation_cycles: 2,
+    healing_cycles: 3,
+    aging_cycles: 40,
+    warning_indicator: false,
+};
+
+// What the zone looked like when its performance fault was first detected
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ZoneFreezeFrame {
+    pub zone: Zone,
+    pub current_temperature: Celsius,
+    pub desired_temperature: Celsius,
+    pub external_temperature: Celsius,
+    pub hvac_power: f32, // W
+}
+
+pub type DtcStore = DtcManager<ZoneFreezeFrame>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ZoneState {
+    pub zone: Zone,
     pub current_temperature: f32,
     pub desired_temperature: f32,
+    pub hvac_power: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClimateState {
+    pub zones: Vec<ZoneState>,
     pub external_temperature: f32,
+    pub zone_sync: bool,
+    pub humidity: HumidityState,
+}
+
+impl ZoneState {
+    pub fn is_stabilized(&self) -> bool {
+        (self.current_temperature - self.desired_temperature).abs() < STABILIZED_TOLERANCE
+    }
+}
+
+// Temperature control of a single zone
+#[derive(Serialize, Deserialize)]
+pub struct ClimateControlSystem {
+    current_temperature: Celsius,
+    desired_temperature: Celsius,
+    external_temperature: Celsius,
+    hvac_power: f32, // W
+    controller: PidController,
+    heat_capacity: f32,
+    heat_loss: f32,
 }
 
 impl ClimateControlSystem {
-    pub fn new(initial_temperature: f32, external_temperature: f32) -> Self {
+    // `cabin_share` scales the plant model to the part of the cabin this system serves
+    pub fn new(initial_temperature: Temperature, external_temperature: Temperature, pid: PidConfig, cabin_share: f32) -> Self {
         ClimateControlSystem {
-            current_temperature: initial_temperature,
-            desired_temperature: initial_temperature,
-            external_temperature,
+            current_temperature: initial_temperature.celsius(),
+            desired_temperature: initial_temperature.celsius(),
+            external_temperature: external_temperature.celsius(),
+            hvac_power: 0.0,
+            controller: PidController::new(pid.scaled(cabin_share)),
+            heat_capacity: CABIN_HEAT_CAPACITY * cabin_share,
+            heat_loss: CABIN_HEAT_LOSS * cabin_share,
         }
     }
 
-    pub fn adjust_temperature(&mut self) {
-        use std::cmp::Ordering;
+    pub fn is_stabilized(&self) -> bool {
+        (self.current_temperature - self.desired_temperature).abs() < STABILIZED_TOLERANCE
+    }
+
+    // The PID controller sets the HVAC power, the zone then exchanges heat
+    // with the HVAC and with the outside air
+    pub fn adjust_temperature(&mut self, dt: f32) {
+        let error = self.desired_temperature - self.current_temperature;
+        self.hvac_power = self.controller.update(error, dt);
 
-        match self.current_temperature.partial_cmp(&self.desired_temperature).unwrap() {
-            Ordering::Less => {
-                self.current_temperature += 0.5;
-                println!("Heating up. Current temperature: {:.1}°C", self.current_temperature);
+        let heat_flow = self.hvac_power + self.heat_loss * (self.external_temperature - self.current_temperature);
+        self.add_heat(heat_flow * dt);
+    }
+
+    // With the HVAC switched off the zone only exchanges heat with the outside
+    pub fn coast(&mut self, dt: f32) {
+        self.hvac_power = 0.0;
+        self.add_heat(self.heat_loss * (self.external_temperature - self.current_temperature) * dt);
+    }
+
+    // Gains and limits for the whole cabin, scaled to this zone like at startup
+    pub fn set_pid(&mut self, pid: PidConfig) {
+        self.controller.set_config(pid.scaled(self.heat_capacity / CABIN_HEAT_CAPACITY));
+    }
+
+    fn add_heat(&mut self, joules: f32) {
+        self.current_temperature += joules / self.heat_capacity;
+    }
+
+    pub fn set_desired_temperature(&mut self, temperature: Temperature) {
+        self.desired_temperature = temperature.celsius();
+    }
+
+    pub fn set_external_temperature(&mut self, temperature: Temperature) {
+        self.external_temperature = temperature.celsius();
+    }
+}
+
+// Driver, passenger and rear zones with their own setpoints. The zones share
+// one cabin, so heat flows between neighbouring zones. In sync mode all zones
+// follow the driver setpoint.
+#[derive(Serialize, Deserialize)]
+pub struct MultiZoneClimate {
+    zones: Vec<(Zone, ClimateControlSystem)>,
+    zone_sync: bool,
+    defog: DefogSystem,
+    #[serde(default)]
+    hvac_off: bool,
+    // Per fitted zone, seconds the setpoint has been held with the HVAC
+    // running and that setpoint
+    #[serde(default)]
+    settling: Vec<(f32, Celsius)>,
+    #[serde(default)]
+    dtcs: DtcStore,
+}
+
+impl MultiZoneClimate {
+    // `zones` are the fitted zones; without a rear zone the front zones
+    // serve the whole cabin
+    pub fn new(initial_temperature: Temperature, external_temperature: Temperature, pid: PidConfig, zones: &[Zone]) -> Self {
+        let fitted_share: f32 = zones.iter().map(|zone| zone.cabin_share()).sum();
+        let zones = zones
+            .iter()
+            .map(|&zone| {
+                let cabin_share = zone.cabin_share() / fitted_share;
+                let system = ClimateControlSystem::new(initial_temperature, external_temperature, pid, cabin_share);
+                (zone, system)
+            })
+            .collect();
+
+        MultiZoneClimate {
+            zones,
+            zone_sync: false,
+            defog: DefogSystem::new(initial_temperature.celsius(), DEFAULT_CABIN_HUMIDITY),
+            hvac_off: false,
+            settling: Vec::new(),
+            dtcs: DtcStore::new(DTC_CONFIG),
+        }
+    }
+
+    pub fn set_cabin_humidity(&mut self, relative_humidity: f32) {
+        let automatic = self.defog.is_automatic();
+        self.defog = DefogSystem::new(self.average_temperature(), relative_humidity);
+        self.defog.set_automatic(automatic);
+    }
+
+    fn average_temperature(&self) -> f32 {
+        let total = self.zones.iter().fold(0.0, |total, (_, system)| total + system.current_temperature);
+        total / self.zones.len() as f32
+    }
+
+    pub fn has_zone(&self, zone: Zone) -> bool {
+        self.zones.iter().any(|(z, _)| *z == zone)
+    }
+
+    pub fn zone_mut(&mut self, zone: Zone) -> Option<&mut ClimateControlSystem> {
+        self.zones.iter_mut().find(|(z, _)| *z == zone).map(|(_, system)| system)
+    }
+
+    // Setpoints of zones that are not fitted are ignored
+    pub fn set_desired_temperature(&mut self, zone: Zone, temperature: Temperature) {
+        if self.zone_sync {
+            for (_, system) in &mut self.zones {
+                system.set_desired_temperature(temperature);
             }
-            Ordering::Greater => {
-                self.current_temperature -= 0.5;
-                println!("Cooling down. Current temperature: {:.1}°C", self.current_temperature);
+        } else if let Some(system) = self.zone_mut(zone) {
+            system.set_desired_temperature(temperature);
+        }
+    }
+
+    pub fn set_zone_sync(&mut self, zone_sync: bool) {
+        self.zone_sync = zone_sync;
+        if zone_sync {
+            let driver = self.zones[0].1.desired_temperature;
+            self.set_desired_temperature(Zone::Driver, Temperature::from_celsius(driver));
+        }
+    }
+
+    pub fn set_pid(&mut self, pid: PidConfig) {
+        for (_, system) in &mut self.zones {
+            system.set_pid(pid);
+        }
+    }
+
+    pub fn set_hvac_enabled(&mut self, enabled: bool) {
+        self.hvac_off = !enabled;
+    }
+
+    pub fn is_hvac_enabled(&self) -> bool {
+        !self.hvac_off
+    }
+
+    pub fn dtcs(&self) -> &DtcStore {
+        &self.dtcs
+    }
+
+    // Fault memory kept from earlier runs
+    pub fn set_dtcs(&mut self, dtcs: DtcStore) {
+        self.dtcs = dtcs;
+    }
+
+    // The DTC configuration is not checkpointed; a resumed run sets it again
+    pub fn set_dtc_config(&mut self, config: DtcConfig) {
+        self.dtcs.set_config(config);
+    }
+
+    pub fn clear_dtcs(&mut self) {
+        self.dtcs.clear();
+    }
+
+    // Tests each zone that has had time to reach its setpoint; a setpoint
+    // change or the HVAC switching off starts the wait over
+    pub fn run_monitors(&mut self, timestamp: f64, dt: f32) {
+        let state = self.state();
+        self.settling.resize(self.zones.len(), (0.0, f32::NAN));
+        for (zone, (held, setpoint)) in state.zones.iter().zip(&mut self.settling) {
+            *held = if self.hvac_off || zone.desired_temperature != *setpoint { 0.0 } else { *held + dt };
+            *setpoint = zone.desired_temperature;
+            if *held < ZONE_RESPONSE_TIME {
+                continue;
             }
-            Ordering::Equal => {
-                println!("Desired temperature reached: {:.1}°C", self.current_temperature);
+            let fault = FaultReport {
+                component: format!("{:?} zone", zone.zone),
+                code: zone.zone.fault_code(),
+                description: format!("{:?} zone does not reach its setpoint", zone.zone),
+            };
+            let error = zone.current_temperature - zone.desired_temperature;
+            self.dtcs.report(timestamp, &fault, error.abs() > ZONE_ERROR_LIMIT, || ZoneFreezeFrame {
+                zone: zone.zone,
+                current_temperature: zone.current_temperature,
+                desired_temperature: zone.desired_temperature,
+                external_temperature: state.external_temperature,
+                hvac_power: zone.hvac_power,
+            });
+        }
+    }
+
+    pub fn set_auto_defog(&mut self, automatic: bool) {
+        self.defog.set_automatic(automatic);
+    }
+
+    pub fn state(&self) -> ClimateState {
+        ClimateState {
+            zones: self
+                .zones
+                .iter()
+                .map(|(zone, system)| ZoneState {
+                    zone: *zone,
+                    current_temperature: system.current_temperature,
+                    desired_temperature: system.desired_temperature,
+                    hvac_power: system.hvac_power,
+                })
+                .collect(),
+            external_temperature: self.zones[0].1.external_temperature,
+            zone_sync: self.zone_sync,
+            humidity: self.defog.state(),
+        }
+    }
+
+    pub fn is_stabilized(&self) -> bool {
+        self.zones.iter().all(|(_, system)| system.is_stabilized())
+    }
+
+    pub fn adjust_temperature(&mut self, dt: f32) {
+        for (_, system) in &mut self.zones {
+            if self.hvac_off {
+                system.coast(dt);
+            } else {
+                system.adjust_temperature(dt);
             }
         }
+
+        // Every zone borders the other two
+        let temperatures: Vec<f32> = self.zones.iter().map(|(_, system)| system.current_temperature).collect();
+        for (i, (_, system)) in self.zones.iter_mut().enumerate() {
+            let exchange: f32 = temperatures
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, neighbour)| ZONE_COUPLING * (neighbour - temperatures[i]))
+                .fold(0.0, |total, flow| total + flow);
+            system.add_heat(exchange * dt);
+        }
+
+        let external_temperature = self.zones[0].1.external_temperature;
+        self.defog.update(dt, self.average_temperature(), external_temperature);
+    }
+
+    pub fn set_external_temperature(&mut self, temperature: Temperature) {
+        for (_, system) in &mut self.zones {
+            system.set_external_temperature(temperature);
+        }
     }
 
-    pub fn simulate_external_conditions(&mut self) {
-        let mut rng = rand::thread_rng();
+    pub fn simulate_external_conditions(&mut self, rng: &mut impl Rng) {
+        // An occupant picks a new temperature for their zone
+        let zone = self.zones[rng.gen_range(0..self.zones.len())].0;
+        let temperature = rng.gen_range(18.0..26.0);
+        self.set_desired_temperature(zone, Temperature::from_celsius(temperature));
+        sim_log::info(
+            "climate",
+            &format!(
+                "New desired temperature for the {:?} zone set to: {}",
+                zone,
+                locale::current().temperature(temperature, 1)
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn settle(system: &mut MultiZoneClimate, seconds: u32) {
+        for _ in 0..seconds {
+            system.adjust_temperature(1.0);
+        }
+    }
 
-        // Randomly adjust external temperature
-        self.external_temperature += rng.gen_range(-0.5..0.5);
-        println!("External temperature changed to: {:.1}°C", self.external_temperature);
+    #[test]
+    fn a_switched_off_hvac_drifts_to_the_outside_temperature() {
+        let mut climate = MultiZoneClimate::new(
+            Temperature::from_celsius(22.0),
+            Temperature::from_celsius(5.0),
+            PidConfig::default(),
+            &Zone::ALL,
+        );
+        climate.set_hvac_enabled(false);
+        settle(&mut climate, 10_000);
 
-        // Randomly set a new desired temperature
-        self.desired_temperature = rng.gen_range(18.0..26.0);
-        println!("New desired temperature set to: {:.1}°C", self.desired_temperature);
+        assert!(climate.state().zones.iter().all(|zone| zone.hvac_power == 0.0));
+        assert!(climate.state().zones.iter().all(|zone| (zone.current_temperature - 5.0).abs() < 1.0));
+    }
+
+    #[test]
+    fn a_zone_that_cannot_reach_its_setpoint_sets_its_performance_code() {
+        let run = |pid: PidConfig| {
+            let mut climate = MultiZoneClimate::new(
+                Temperature::from_celsius(5.0),
+                Temperature::from_celsius(-10.0),
+                pid,
+                &[Zone::Driver, Zone::Passenger],
+            );
+            climate.set_desired_temperature(Zone::Driver, Temperature::from_celsius(22.0));
+            for second in 0..900 {
+                climate.adjust_temperature(1.0);
+                climate.run_monitors(second as f64, 1.0);
+            }
+            climate
+        };
+
+        assert_eq!(run(PidConfig::default()).dtcs().records().count(), 0);
+
+        // A heater too weak to hold 22 °C against -10 °C outside
+        let climate = run(PidConfig {
+            output_max: 500.0,
+            ..PidConfig::default()
+        });
+        // The passenger zone was left at 5 °C, which it holds
+        assert_eq!(climate.dtcs().active_codes(), vec!["B1A20".to_string()]);
+        let (component, record) = climate.dtcs().records().next().unwrap();
+        assert_eq!(component, "Driver zone");
+        assert!(record.freeze_frame.current_temperature < 20.0);
+        assert!(climate.dtcs().entries()[0].status & vehicle_sim_core::uds::TEST_FAILED != 0);
+    }
+
+    proptest! {
+        #[test]
+        fn pid_output_stays_within_the_hvac_limits(errors in prop::collection::vec(-50.0f32..50.0, 1..200)) {
+            let config = PidConfig::default();
+            let mut controller = PidController::new(config);
+            for error in errors {
+                let output = controller.update(error, 1.0);
+                prop_assert!(output >= config.output_min && output <= config.output_max);
+            }
+        }
+
+        #[test]
+        fn zone_converges_to_its_setpoint(
+            initial in -10.0f32..50.0,
+            external in -20.0f32..40.0,
+            desired in 16.0f32..28.0,
+        ) {
+            let mut system = ClimateControlSystem::new(
+                Temperature::from_celsius(initial),
+                Temperature::from_celsius(external),
+                PidConfig::default(),
+                1.0,
+            );
+            system.set_desired_temperature(Temperature::from_celsius(desired));
+            for _ in 0..3600 {
+                system.adjust_temperature(1.0);
+            }
+
+            prop_assert!(system.is_stabilized(), "{} °C instead of {} °C", system.current_temperature, desired);
+        }
+
+        #[test]
+        fn coupled_zones_converge_to_their_own_setpoints(
+            external in -20.0f32..40.0,
+            setpoints in prop::array::uniform3(16.0f32..28.0),
+        ) {
+            let mut climate = MultiZoneClimate::new(
+                Temperature::from_celsius(20.0),
+                Temperature::from_celsius(external),
+                PidConfig::default(),
+                &Zone::ALL,
+            );
+            for (zone, setpoint) in Zone::ALL.into_iter().zip(setpoints) {
+                climate.set_desired_temperature(zone, Temperature::from_celsius(setpoint));
+            }
+            settle(&mut climate, 3600);
+
+            prop_assert!(climate.is_stabilized(), "{:?}", climate.state().zones);
+        }
     }
 }
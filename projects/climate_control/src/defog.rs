@@ -0,0 +1,197 @@
+// src/defog.rs
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::locale;
+use vehicle_sim_core::sim_log;
+
+// Moisture exhaled by the occupants, as vapour pressure gain per second
+const OCCUPANT_MOISTURE: f32 = 0.015; // hPa/s
+// Share of the cabin air replaced with outside air per second at full blower
+const AIR_EXCHANGE_RATE: f32 = 0.01;
+// Share of the excess moisture the A/C evaporator removes per second
+const AC_DRYING_RATE: f32 = 0.02;
+const AC_COIL_TEMPERATURE: f32 = 3.0;
+const OUTSIDE_RELATIVE_HUMIDITY: f32 = 0.8;
+
+// Fog risk starts this close to the windshield temperature; defogging
+// stops once the dew point is clearly below it again
+const FOG_RISK_MARGIN: f32 = 2.0;
+const FOG_CLEAR_MARGIN: f32 = 4.0;
+
+const BASE_BLOWER: f32 = 0.3;
+const BLOWER_RAMP: f32 = 0.1; // per second
+
+// Saturation vapour pressure over water in hPa (Magnus formula)
+pub fn saturation_vapour_pressure(temperature: f32) -> f32 {
+    6.112 * (17.62 * temperature / (243.12 + temperature)).exp()
+}
+
+pub fn dew_point(vapour_pressure: f32) -> f32 {
+    let gamma = (vapour_pressure / 6.112).ln();
+    243.12 * gamma / (17.62 - gamma)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HumidityState {
+    pub relative_humidity: f32,
+    pub dew_point: f32,
+    pub windshield_temperature: f32,
+    pub blower: f32,
+    // The A/C compressor runs while defogging
+    pub defog_active: bool,
+}
+
+impl HumidityState {
+    // Moisture condenses on a windshield at or below the dew point and
+    // blocks the driver's view
+    pub fn is_fogged(&self) -> bool {
+        self.windshield_temperature <= self.dew_point
+    }
+}
+
+// Cabin humidity with an automatic defog mode that ramps up the blower and
+// runs the A/C compressor while the windshield is at risk of fogging
+#[derive(Serialize, Deserialize)]
+pub struct DefogSystem {
+    vapour_pressure: f32,
+    cabin_temperature: f32,
+    windshield_temperature: f32,
+    blower: f32,
+    defog_active: bool,
+    // Off when the vehicle is not coded for automatic defog
+    #[serde(default = "automatic_default")]
+    automatic: bool,
+}
+
+fn automatic_default() -> bool {
+    true
+}
+
+impl DefogSystem {
+    pub fn new(cabin_temperature: f32, relative_humidity: f32) -> Self {
+        DefogSystem {
+            vapour_pressure: relative_humidity * saturation_vapour_pressure(cabin_temperature),
+            cabin_temperature,
+            windshield_temperature: cabin_temperature,
+            blower: BASE_BLOWER,
+            defog_active: false,
+            automatic: true,
+        }
+    }
+
+    pub fn is_automatic(&self) -> bool {
+        self.automatic
+    }
+
+    pub fn set_automatic(&mut self, automatic: bool) {
+        self.automatic = automatic;
+        self.defog_active &= automatic;
+    }
+
+    pub fn update(&mut self, dt: f32, cabin_temperature: f32, external_temperature: f32) {
+        self.cabin_temperature = cabin_temperature;
+
+        // The glass sits between cabin and outside temperature; defrost air
+        // blown at it warms it up. Fog risk is judged on the unheated glass,
+        // otherwise switching defog off would bring the fog straight back.
+        let unheated_windshield = external_temperature + 0.3 * (cabin_temperature - external_temperature);
+        let defrost_heating = if self.defog_active { 8.0 * self.blower } else { 0.0 };
+        self.windshield_temperature = unheated_windshield + defrost_heating;
+
+        let outside_vapour = OUTSIDE_RELATIVE_HUMIDITY * saturation_vapour_pressure(external_temperature);
+        let mut change = OCCUPANT_MOISTURE + self.blower * AIR_EXCHANGE_RATE * (outside_vapour - self.vapour_pressure);
+        let coil_vapour = saturation_vapour_pressure(AC_COIL_TEMPERATURE);
+        if self.defog_active && self.vapour_pressure > coil_vapour {
+            change -= AC_DRYING_RATE * (self.vapour_pressure - coil_vapour);
+        }
+        self.vapour_pressure = (self.vapour_pressure + change * dt)
+            .clamp(0.1, saturation_vapour_pressure(cabin_temperature));
+
+        let dew_point = dew_point(self.vapour_pressure);
+        let locale = locale::current();
+        if self.automatic && !self.defog_active && dew_point > unheated_windshield - FOG_RISK_MARGIN {
+            self.defog_active = true;
+            sim_log::debug(
+                "defog",
+                &format!(
+                    "Fog risk detected: dew point {} vs. windshield {}, defog mode on",
+                    locale.temperature(dew_point, 1),
+                    locale.temperature(unheated_windshield, 1)
+                ),
+            );
+        } else if self.defog_active && dew_point < unheated_windshield - FOG_CLEAR_MARGIN {
+            self.defog_active = false;
+            sim_log::debug(
+                "defog",
+                &format!(
+                    "Fog risk cleared: dew point {} vs. windshield {}, defog mode off",
+                    locale.temperature(dew_point, 1),
+                    locale.temperature(unheated_windshield, 1)
+                ),
+            );
+        }
+
+        let target_blower = if self.defog_active { 1.0 } else { BASE_BLOWER };
+        let ramp = BLOWER_RAMP * dt;
+        self.blower += (target_blower - self.blower).clamp(-ramp, ramp);
+    }
+
+    pub fn state(&self) -> HumidityState {
+        HumidityState {
+            relative_humidity: self.vapour_pressure / saturation_vapour_pressure(self.cabin_temperature),
+            dew_point: dew_point(self.vapour_pressure),
+            windshield_temperature: self.windshield_temperature,
+            blower: self.blower,
+            defog_active: self.defog_active,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cabin at 20 °C with the given dew point; 10 °C outside puts the
+    // unheated windshield at 13 °C
+    fn cabin_with_dew_point(dew_point: f32) -> DefogSystem {
+        DefogSystem::new(20.0, saturation_vapour_pressure(dew_point) / saturation_vapour_pressure(20.0))
+    }
+
+    #[test]
+    fn the_dew_point_matches_the_magnus_tables() {
+        assert!((saturation_vapour_pressure(0.0) - 6.112).abs() < 1e-4);
+        assert!((saturation_vapour_pressure(20.0) - 23.37).abs() < 0.05);
+        assert!((saturation_vapour_pressure(-10.0) - 2.86).abs() < 0.05);
+        // 20 °C at 50% and 25 °C at 60% relative humidity
+        assert!((dew_point(0.5 * saturation_vapour_pressure(20.0)) - 9.3).abs() < 0.1);
+        assert!((dew_point(0.6 * saturation_vapour_pressure(25.0)) - 16.7).abs() < 0.1);
+        for temperature in [-20.0, 0.0, 15.0, 35.0] {
+            assert!((dew_point(saturation_vapour_pressure(temperature)) - temperature).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn defog_starts_within_the_risk_margin_and_stops_past_the_clear_margin() {
+        let mut defog = cabin_with_dew_point(10.5);
+        defog.update(0.001, 20.0, 10.0);
+        assert!(!defog.state().defog_active);
+
+        let mut defog = cabin_with_dew_point(11.5);
+        defog.update(0.001, 20.0, 10.0);
+        assert!(defog.state().defog_active);
+
+        // Between both margins the mode stays on, below the clear margin it stops
+        let mut defog = cabin_with_dew_point(10.0);
+        defog.defog_active = true;
+        defog.update(0.001, 20.0, 10.0);
+        assert!(defog.state().defog_active);
+        let mut defog = cabin_with_dew_point(8.5);
+        defog.defog_active = true;
+        defog.update(0.001, 20.0, 10.0);
+        assert!(!defog.state().defog_active);
+
+        let mut defog = cabin_with_dew_point(11.5);
+        defog.set_automatic(false);
+        defog.update(0.001, 20.0, 10.0);
+        assert!(!defog.state().defog_active);
+    }
+}
@@ -0,0 +1,167 @@
+// src/diagnostics.rs
+use crate::climate::{MultiZoneClimate, PidConfig, Zone};
+use crate::simulation::ClimateSimulation;
+use vehicle_sim_core::calibration::Calibration;
+use vehicle_sim_core::description::{Address, Characteristic, DataType, Description, Measurement};
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::uds::{DiagnosticHandler, DtcEntry, ALL_GROUPS};
+
+// Physical addressing of the climate ECU
+pub const REQUEST_ID: u32 = 0x7B0;
+pub const RESPONSE_ID: u32 = 0x7B8;
+
+// Data identifiers, one per fitted zone in the order of `Zone::ALL`, in 0.1 °C
+pub const ZONE_TEMPERATURE_DID: u16 = 0x5000;
+pub const ZONE_SETPOINT_DID: u16 = 0x5100;
+pub const EXTERNAL_TEMPERATURE_DID: u16 = 0x5200;
+// Cabin relative humidity in percent
+pub const HUMIDITY_DID: u16 = 0x5201;
+
+fn tenths(temperature: f32) -> Vec<u8> {
+    ((temperature * 10.0).round() as i16).to_be_bytes().to_vec()
+}
+
+// What `--describe` lists: the bus signals, the data identifiers of the
+// fitted zones and the controller tuning in use
+pub fn describe(zones: &[Zone], pid: &PidConfig, calibration: Option<&Calibration>) -> Description {
+    let did = |did| Address::Did {
+        request_id: REQUEST_ID,
+        did,
+    };
+    let mut description = Description::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .with_calibration(calibration)
+        .with_can_messages("Climate")
+        .with_characteristic(Characteristic::number("pid_kp", pid.kp, "W/K", 0.0, 20000.0))
+        .with_characteristic(Characteristic::number("pid_ki", pid.ki, "W/(K s)", 0.0, 1000.0))
+        .with_characteristic(Characteristic::number("pid_kd", pid.kd, "W s/K", 0.0, 20000.0))
+        .with_characteristic(Characteristic::number("max_cooling_power", -pid.output_min, "W", 0.0, 10000.0))
+        .with_characteristic(Characteristic::number("max_heating_power", pid.output_max, "W", 0.0, 10000.0));
+    for (i, zone) in zones.iter().enumerate() {
+        let i = i as u16;
+        description = description
+            .with_measurement(Measurement::new(&format!("{:?}ZoneCurrentTemperature", zone), did(ZONE_TEMPERATURE_DID + i), DataType::Sword, 0.1, "°C"))
+            .with_measurement(Measurement::new(&format!("{:?}ZoneSetpoint", zone), did(ZONE_SETPOINT_DID + i), DataType::Sword, 0.1, "°C"));
+    }
+    description
+        .with_measurement(Measurement::new("ExternalTemperatureSensor", did(EXTERNAL_TEMPERATURE_DID), DataType::Sword, 0.1, "°C"))
+        .with_measurement(Measurement::new("CabinHumidity", did(HUMIDITY_DID), DataType::Ubyte, 1.0, "%").with_range(0.0, 100.0))
+}
+
+// Zone performance codes survive program runs like the TPMS fault memory
+pub const DTC_STORE_PATH: &str = "climate_dtcs.json";
+
+impl DiagnosticHandler for MultiZoneClimate {
+    fn dtcs(&self) -> Vec<DtcEntry> {
+        self.dtcs().entries()
+    }
+
+    // The fault memory has no DTC groups, so only "all groups" clears anything
+    fn clear_dtcs(&mut self, group: u32) {
+        if group == ALL_GROUPS {
+            MultiZoneClimate::clear_dtcs(self);
+        }
+    }
+
+    fn read_data(&self, identifier: u16) -> Option<Vec<u8>> {
+        let state = self.state();
+        let zone = state.zones.get((identifier & 0xFF) as usize);
+        match identifier {
+            EXTERNAL_TEMPERATURE_DID => Some(tenths(state.external_temperature)),
+            HUMIDITY_DID => Some(vec![(state.humidity.relative_humidity * 100.0).round() as u8]),
+            _ => match identifier & 0xFF00 {
+                ZONE_TEMPERATURE_DID => zone.map(|zone| tenths(zone.current_temperature)),
+                ZONE_SETPOINT_DID => zone.map(|zone| tenths(zone.desired_temperature)),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl ClimateSimulation {
+    // Controller tuning written by a calibration tool over XCP. The server
+    // holds every parameter, so one written gain retunes with the others.
+    pub fn apply_xcp_writes(&mut self) {
+        let Some(xcp) = &self.xcp else {
+            return;
+        };
+        let written = xcp.take_writes();
+        if written.is_empty() {
+            return;
+        }
+        let value = |name: &str| xcp.characteristic(name).map(|value| value as f32);
+        let (Some(kp), Some(ki), Some(kd), Some(max_cooling), Some(max_heating)) =
+            (value("pid_kp"), value("pid_ki"), value("pid_kd"), value("max_cooling_power"), value("max_heating_power"))
+        else {
+            return;
+        };
+        self.system.set_pid(PidConfig {
+            kp,
+            ki,
+            kd,
+            output_min: -max_cooling,
+            output_max: max_heating,
+        });
+        for (name, value) in written {
+            sim_log::info("xcp", &format!("Calibrated over XCP: {} = {}", name, value));
+        }
+    }
+
+    // The values behind the data identifiers of `describe`
+    pub fn publish_xcp(&self) {
+        let Some(xcp) = &self.xcp else {
+            return;
+        };
+        let state = self.system.state();
+        let mut names = Vec::new();
+        let mut values = Vec::new();
+        for zone in &state.zones {
+            names.push(format!("{:?}ZoneCurrentTemperature", zone.zone));
+            values.push(zone.current_temperature as f64);
+            names.push(format!("{:?}ZoneSetpoint", zone.zone));
+            values.push(zone.desired_temperature as f64);
+        }
+        let mut signals: Vec<(&str, f64)> = names.iter().map(String::as_str).zip(values).collect();
+        signals.push(("ExternalTemperatureSensor", state.external_temperature as f64));
+        signals.push(("CabinHumidity", state.humidity.relative_humidity as f64 * 100.0));
+        xcp.publish(&signals);
+    }
+
+    // Per zone under `<zone>/`, plus the cabin average as `cabin_temp`
+    #[cfg(feature = "mqtt")]
+    pub fn publish_mqtt(&mut self, time: f64) {
+        let Some(mqtt) = &self.mqtt else {
+            return;
+        };
+        let state = self.system.state();
+        let mut names = Vec::new();
+        let mut values = Vec::new();
+        for zone in &state.zones {
+            let level = vehicle_sim_core::mqtt::topic_level(&format!("{:?}", zone.zone));
+            names.push(format!("{}/cabin_temp", level));
+            values.push(zone.current_temperature as f64);
+            names.push(format!("{}/setpoint", level));
+            values.push(zone.desired_temperature as f64);
+            names.push(format!("{}/hvac_power", level));
+            values.push(zone.hvac_power as f64);
+        }
+        let mut signals: Vec<(&str, f64)> = names.iter().map(String::as_str).zip(values).collect();
+        let cabin = state.zones.iter().map(|zone| zone.current_temperature as f64).sum::<f64>() / state.zones.len().max(1) as f64;
+        signals.push(("cabin_temp", cabin));
+        signals.push(("external_temp", state.external_temperature as f64));
+        signals.push(("humidity", state.humidity.relative_humidity as f64 * 100.0));
+        signals.push(("defog", if state.humidity.defog_active { 1.0 } else { 0.0 }));
+        mqtt.publish(&vehicle_sim_core::privacy::filtered(self.privacy.as_mut(), time, signals));
+    }
+
+    // The averages still open when the run ends
+    #[cfg(feature = "mqtt")]
+    pub fn flush_mqtt(&mut self) {
+        let (Some(mqtt), Some(privacy)) = (&self.mqtt, &mut self.privacy) else {
+            return;
+        };
+        let pending = privacy.flush();
+        if !pending.is_empty() {
+            mqtt.publish(&pending);
+        }
+    }
+}
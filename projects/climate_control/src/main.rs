@@ -1,16 +1,310 @@
 // src/main.rs
+mod alarm;
 mod climate;
+mod defog;
+mod diagnostics;
+mod plot;
 mod simulation;
+mod windows;
 
-use climate::ClimateControlSystem;
-use simulation::run_simulation;
+use climate::{DtcStore, MultiZoneClimate, PidConfig, Zone, DTC_CONFIG};
+use simulation::{parse_switch, run_simulation, setpoint_key, ClimateSimulation, SAFE_CONFIG_KEYS};
+use std::path::Path;
+use std::process;
+use vehicle_sim_core::ambient::{AmbientModel, Daylight};
+use vehicle_sim_core::batch;
+use vehicle_sim_core::calendar::{Calendar, Date};
+use vehicle_sim_core::calibration::{self, CalibrationSet};
+use vehicle_sim_core::can_trace::{self, CanTrace};
+use vehicle_sim_core::clock;
+use vehicle_sim_core::config::{self, ConfigWatcher};
+use vehicle_sim_core::description;
+use vehicle_sim_core::driver;
+use vehicle_sim_core::locale;
+use vehicle_sim_core::privacy::{self, PrivacyFilter, PrivacyPolicy};
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::scenario;
+use vehicle_sim_core::sim_log::{self, LogOptions};
+use vehicle_sim_core::simulation::RunSummary;
+use vehicle_sim_core::snapshot::{self, Snapshot};
+use vehicle_sim_core::uds::UdsServer;
+use vehicle_sim_core::units::Temperature;
+use vehicle_sim_core::vehicle::{self, VariantCoding, VehicleIdentity};
+use vehicle_sim_core::vehicle_mode::{ModeManager, VehicleMode};
+use vehicle_sim_core::xcp::{self, XcpServer};
 
 fn main() {
-    let initial_cabin_temperature = 20.0;
-    let external_temperature = 15.0;
+    // `--batch <steps>` runs headless for CI and parameter sweeps, printing
+    // only the statistics of the run
+    let batch = batch::steps_from_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
 
-    let mut system = ClimateControlSystem::new(initial_cabin_temperature, external_temperature);
+    // `--log-level`, `--log-file` and `--log-json` or their SIM_LOG_*
+    // variables; batch runs log to files only
+    let log_options = LogOptions {
+        console: batch.is_none(),
+        ..LogOptions::from_args_and_env()
+    };
+    if let Err(e) = sim_log::init(&log_options) {
+        eprintln!("Cannot open log file: {}", e);
+        process::exit(1);
+    }
+
+    // `--windows` runs the window lift and child lock scenarios on the door
+    // LIN cluster and exits
+    if std::env::args().any(|arg| arg == "--windows") {
+        let runs: Vec<windows::ScenarioRun> = windows::SCENARIOS.iter().map(windows::run).collect();
+        windows::print_report(&runs);
+        return;
+    }
+    // `--alarm` runs the break-in and theft scenarios against the alarm,
+    // reproducible with `--seed <n>`
+    if std::env::args().any(|arg| arg == "--alarm") {
+        let mut rng = SimRng::from_args_env_or(None);
+        let runs: Vec<alarm::ScenarioRun> = alarm::SCENARIOS.iter().map(|scenario| alarm::run(scenario, &mut rng)).collect();
+        alarm::print_report(&runs);
+        return;
+    }
+
+    // Load `--config <path>` or SIM_CONFIG; only the setpoint is reloaded while running
+    let config = config::path_from_args().map(|path| {
+        ConfigWatcher::watch(&path, SAFE_CONFIG_KEYS).unwrap_or_else(|e| {
+            eprintln!("Cannot read config {}: {}", path.display(), e);
+            process::exit(1);
+        })
+    });
+    let settings = config.as_ref().map(|watcher| watcher.config());
+
+    // `--scenario <path>` or SIM_SCENARIO sets the starting conditions, run
+    // length, seed and an outside temperature timeline
+    let scenario = scenario::from_args().unwrap_or_else(|e| {
+        eprintln!("Cannot read scenario: {}", e);
+        process::exit(1);
+    });
+    if let Some(scenario) = &scenario {
+        scenario.announce(&["cabin_temperature", "latitude", "date", "cabin_humidity", "vehicle_mode"], false, true, true, false);
+    }
+    let initial = |key: &str| scenario.as_ref().and_then(|s| s.initial_f64(key));
+
+    let initial_cabin_temperature = initial("cabin_temperature").map_or(20.0, |t| t as f32);
+    let latitude = initial("latitude")
+        .or_else(|| settings.and_then(|c| c.get_f64("latitude")))
+        .unwrap_or(48.1); // Degrees north, roughly central Europe
+    let start_date = match scenario.as_ref().and_then(|s| s.initial_str("date")) {
+        Some(date) => Date::parse(date).unwrap_or_else(|| {
+            eprintln!("Invalid scenario date {}, expected YYYY-MM-DD", date);
+            process::exit(1);
+        }),
+        None => Date::new(2024, 1, 15).unwrap(),
+    };
+    let calendar = Calendar::new(start_date);
+    let ambient = AmbientModel::new(latitude);
+
+    let locale = locale::current();
+    if let (Daylight::SunriseSunset(sunrise, sunset), None) = (ambient.daylight(calendar.date()), batch) {
+        println!(
+            "{}: sunrise {}, sunset {} (solar time)",
+            locale.date(calendar.date()),
+            locale.time(sunrise),
+            locale.time(sunset)
+        );
+    }
+
+    // Controller tuning is read once at startup from `--calibration <path>`
+    // or SIM_CALIBRATION (newest version of a directory); the config overrides it
+    let calibrations = calibration::path_from_args().map(|path| {
+        CalibrationSet::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read calibration {}: {}", path.display(), e);
+            process::exit(1);
+        })
+    });
+    if let Some(calibrations) = &calibrations {
+        println!("Calibration: {}", calibrations.active());
+    }
+    let defaults = PidConfig::default();
+    let setting = |key: &str, default: f32| {
+        settings
+            .and_then(|c| c.get_f64(key))
+            .or_else(|| calibrations.as_ref().and_then(|set| set.active().get_f64(key)))
+            .map_or(default, |v| v as f32)
+    };
+    let pid = PidConfig {
+        kp: setting("pid_kp", defaults.kp),
+        ki: setting("pid_ki", defaults.ki),
+        kd: setting("pid_kd", defaults.kd),
+        output_min: -setting("max_cooling_power", -defaults.output_min),
+        output_max: setting("max_heating_power", defaults.output_max),
+    };
+
+    // `--vehicle <path>` or SIM_VEHICLE names the vehicle file shared with the
+    // other ECUs; its coding decides the rear zone and automatic defog
+    let vehicle_path = vehicle::path_from_args();
+    let trim = vehicle::trim_from_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let rng = SimRng::from_args_env_or(scenario.as_ref().and_then(|s| s.seed));
+    let identity = VehicleIdentity::load_or_create(vehicle_path.as_deref(), trim, rng.seed()).unwrap_or_else(|e| {
+        eprintln!("Cannot read the vehicle file: {}", e);
+        process::exit(1);
+    });
+    if batch.is_none() {
+        println!(
+            "Vehicle: VIN {}, {:?} trim, coded with {}",
+            identity.vin,
+            identity.trim,
+            identity.coding.describe()
+        );
+    }
+
+    let zones: Vec<Zone> = Zone::ALL
+        .into_iter()
+        .filter(|&zone| zone != Zone::Rear || identity.coding.has(VariantCoding::REAR_CLIMATE_ZONE))
+        .collect();
+    // `--describe <path>` writes what the ECU exposes and exits
+    if let Some(path) = description::path_from_args() {
+        let calibration = calibrations.as_ref().map(CalibrationSet::active);
+        if let Err(e) = diagnostics::describe(&zones, &pid, calibration).save(&path) {
+            eprintln!("Cannot write description {}: {}", path.display(), e);
+            process::exit(1);
+        }
+        println!("Description written to {}", path.display());
+        return;
+    }
+
+    let mut system = MultiZoneClimate::new(Temperature::from_celsius(initial_cabin_temperature), Temperature::ZERO, pid, &zones);
+    system.set_auto_defog(identity.coding.has(VariantCoding::AUTO_DEFOG));
+
+    // Fault codes are kept across runs; each run is one ignition cycle and a
+    // resumed run continues the cycle of its checkpoint
+    let dtc_path = Path::new(diagnostics::DTC_STORE_PATH);
+    let mut dtcs = DtcStore::load(dtc_path, DTC_CONFIG).unwrap_or_else(|e| {
+        eprintln!("Cannot read DTC store {}: {}", dtc_path.display(), e);
+        process::exit(1);
+    });
+    dtcs.start_ignition_cycle();
+    system.set_dtcs(dtcs);
+
+    // Start every zone from the climate preference of the driver whose key
+    // fob is in use, unless the config sets a zone explicitly
+    let driver = driver::active_profile();
+    for &zone in &zones {
+        let setpoint = settings
+            .and_then(|c| c.get_f64(setpoint_key(zone)))
+            .map_or(driver.preferred_temperature, |t| t as f32);
+        system.set_desired_temperature(zone, Temperature::from_celsius(setpoint));
+    }
+    if let Some(humidity) = initial("cabin_humidity").or_else(|| settings.and_then(|c| c.get_f64("cabin_humidity"))) {
+        system.set_cabin_humidity(humidity as f32);
+    }
+    if let Some(zone_sync) = settings.and_then(|c| c.get("zone_sync")).and_then(parse_switch) {
+        system.set_zone_sync(zone_sync);
+    }
+    // `preconditioning = off` keeps the HVAC off while parked
+    let preconditioning = settings.and_then(|c| c.get("preconditioning")).and_then(parse_switch).unwrap_or(true);
+    if batch.is_none() {
+        println!(
+            "Driver: {} ({}), preferred cabin temperature {}",
+            driver.name,
+            driver.key_fob_id,
+            locale.temperature(driver.preferred_temperature, 1)
+        );
+    }
 
     // Run the simulation
-    run_simulation(&mut system);
+    let mut simulation = ClimateSimulation::new(system, calendar, ambient, rng, config);
+    if let Some(scenario) = &scenario {
+        simulation.weather = scenario.weather.clone();
+        simulation.run_full_duration = scenario.duration.is_some();
+    }
+    simulation.mode = ModeManager::from_scenario(scenario.as_ref(), VehicleMode::Driving);
+
+    // `--resume <path>` continues a run checkpointed with `--save <path>`;
+    // the config file is still watched for setpoint changes
+    let mut start = RunSummary::default();
+    if let Some(path) = snapshot::resume_path_from_args() {
+        let snapshot = Snapshot::<ClimateSimulation>::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot resume from {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        println!("Resuming from {} at step {}", path.display(), snapshot.steps);
+        start = snapshot.summary();
+        let config = simulation.config.take();
+        simulation = snapshot.state;
+        simulation.config = config;
+        simulation.system.set_dtc_config(DTC_CONFIG);
+    }
+    simulation.preconditioning = preconditioning;
+    let server = UdsServer::new(simulation.can.clone(), diagnostics::REQUEST_ID, diagnostics::RESPONSE_ID);
+    simulation.uds = Some(server.with_identity(identity, vehicle_path));
+    // `--can-trace <path>` or SIM_CAN_TRACE records the climate frames
+    if let Some(path) = can_trace::path_from_args() {
+        let trace = CanTrace::create(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot write CAN trace {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        simulation.can.record_to(trace);
+    }
+    // `--xcp <address>` or SIM_XCP serves the described signals and
+    // controller tuning to measurement and calibration tools over UDP
+    if let Some(address) = xcp::address_from_args() {
+        let calibration = calibrations.as_ref().map(CalibrationSet::active);
+        let server = XcpServer::start(&address, &diagnostics::describe(&zones, &pid, calibration)).unwrap_or_else(|e| {
+            eprintln!("Cannot serve XCP on {}: {}", address, e);
+            process::exit(1);
+        });
+        server.watch_bus(&simulation.can);
+        println!("XCP on UDP {}", server.local_addr().map_or(address, |address| address.to_string()));
+        simulation.xcp = Some(server);
+    }
+    // `--mqtt <host[:port]>` or SIM_MQTT publishes every step's telemetry
+    // under vehicle/climate
+    #[cfg(feature = "mqtt")]
+    if let Some(broker) = vehicle_sim_core::mqtt::broker_from_args() {
+        let options = vehicle_sim_core::mqtt::MqttOptions::from_env(broker, "climate");
+        let publisher = vehicle_sim_core::mqtt::MqttPublisher::connect(&options).unwrap_or_else(|e| {
+            eprintln!("Cannot connect to MQTT broker {}: {}", options.broker, e);
+            process::exit(1);
+        });
+        println!("Publishing telemetry to MQTT broker {} under {}/", options.broker, options.topic_prefix);
+        simulation.mqtt = Some(publisher);
+    }
+    // `--privacy <path>` or SIM_PRIVACY filters the telemetry before it goes
+    // to the broker or the dashboard
+    if let Some(path) = privacy::path_from_args() {
+        let policy = PrivacyPolicy::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read privacy settings {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        simulation.privacy = Some(PrivacyFilter::new(policy));
+    }
+    // `--svg` writes the chart as SVG instead of PNG, `--plot-theme dark` (or
+    // SIM_PLOT_THEME) draws it on a dark background
+    let plot_path = if std::env::args().any(|arg| arg == "--svg") {
+        "climate_control.svg"
+    } else {
+        "climate_control.png"
+    };
+    // A batch run keeps going once the cabin is stabilized to show it stays there
+    let max_steps = batch.or_else(|| scenario.as_ref().and_then(|s| s.steps(1.0)));
+    simulation.run_full_duration |= batch.is_some();
+    // `--realtime-factor <factor>` or SIM_REALTIME_FACTOR decouples the run
+    // from the wall clock, 0 running as fast as possible
+    let realtime_factor = clock::realtime_factor_from_args().unwrap_or(1.0);
+    run_simulation(
+        &mut simulation,
+        plot_path,
+        start,
+        max_steps,
+        realtime_factor,
+        snapshot::save_path_from_args(),
+        batch.is_some(),
+    );
+    #[cfg(feature = "mqtt")]
+    simulation.flush_mqtt();
+    if let Err(e) = simulation.system.dtcs().save(dtc_path) {
+        eprintln!("Failed to save DTC store {}: {}", dtc_path.display(), e);
+    }
 }
@@ -0,0 +1,78 @@
+// src/plot.rs
+use std::error::Error;
+use std::path::Path;
+
+use vehicle_sim_core::sim_plot::{Figure, Panel, PlotSeries, RGBColor, Theme, BLUE, GREEN, MAGENTA, RED};
+
+use crate::climate::{ClimateState, Zone};
+
+const ZONE_COLORS: [RGBColor; 3] = [RED, BLUE, GREEN];
+
+// Time series collected while the simulation runs
+#[derive(Default)]
+pub struct ClimateRecorder {
+    time: Vec<f64>,
+    zones: Vec<Zone>,
+    cabin: Vec<Vec<f64>>,
+    setpoint: Vec<Vec<f64>>,
+    external: Vec<f64>,
+    hvac_power: Vec<f64>,
+}
+
+impl ClimateRecorder {
+    pub fn new() -> Self {
+        ClimateRecorder::default()
+    }
+
+    pub fn record(&mut self, time: f64, state: &ClimateState) {
+        self.time.push(time);
+        // One series per fitted zone, known from the first state
+        if self.zones.is_empty() {
+            self.zones = state.zones.iter().map(|zone| zone.zone).collect();
+            self.cabin = vec![Vec::new(); self.zones.len()];
+            self.setpoint = vec![Vec::new(); self.zones.len()];
+        }
+        for (i, zone) in state.zones.iter().enumerate() {
+            self.cabin[i].push(zone.current_temperature as f64);
+            self.setpoint[i].push(zone.desired_temperature as f64);
+        }
+        self.external.push(state.external_temperature as f64);
+        let total_power = state.zones.iter().fold(0.0, |total, zone| total + zone.hvac_power as f64);
+        self.hvac_power.push(total_power / 1000.0);
+    }
+
+    // Writes a PNG, or an SVG when the path ends in `.svg`
+    pub fn plot(&self, path: &str, theme: Theme) -> Result<(), Box<dyn Error>> {
+        let end_time = self.time.last().cloned().unwrap_or(1.0);
+        let over_time = |values: &[f64]| self.time.iter().cloned().zip(values.iter().cloned()).collect::<Vec<_>>();
+
+        // Cabin temperatures and setpoints per zone, and the outside temperature
+        let mut temperatures = Panel::new("Cabin Temperature Over Time")
+            .with_axes("Time (s)", "°C")
+            .with_x_range(0.0, end_time);
+        for (i, zone) in self.zones.iter().enumerate() {
+            let color = ZONE_COLORS[i];
+            temperatures = temperatures
+                .with_series(PlotSeries::new(format!("{:?} zone", zone), over_time(&self.cabin[i])).with_color(color))
+                .with_series(
+                    PlotSeries::new(format!("{:?} setpoint", zone), over_time(&self.setpoint[i]))
+                        .with_color(color)
+                        .with_opacity(0.4),
+                );
+        }
+        temperatures = temperatures.with_series(PlotSeries::new("External", over_time(&self.external)).foreground());
+
+        // Combined HVAC power of all zones, negative while cooling
+        let power = Panel::new("HVAC Power Over Time")
+            .with_axes("Time (s)", "kW")
+            .with_x_range(0.0, end_time)
+            .symmetric()
+            .with_series(PlotSeries::new("HVAC power", over_time(&self.hvac_power)).with_color(MAGENTA));
+
+        Figure::new()
+            .with_theme(theme)
+            .with_panel(temperatures)
+            .with_panel(power)
+            .save(Path::new(path))
+    }
+}
@@ -1,31 +1,510 @@
 // src/simulation.rs
-use crate::climate::ClimateControlSystem;
-use std::thread::sleep;
-use std::time::Duration;
+use crate::climate::{ClimateState, MultiZoneClimate, Zone, ZoneState};
+use crate::plot::ClimateRecorder;
+use std::path::PathBuf;
+use std::sync::Arc;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::ambient::AmbientModel;
+use vehicle_sim_core::batch::{self, Stats};
+use vehicle_sim_core::calendar::Calendar;
+use vehicle_sim_core::can_bus::{CanBus, CLIMATE_STATUS};
+use vehicle_sim_core::config::ConfigWatcher;
+use vehicle_sim_core::events::{self, Event, EventBus, EventFilter};
+use vehicle_sim_core::locale;
+use vehicle_sim_core::privacy::PrivacyFilter;
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::scenario::{self, WeatherPoint};
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::sim_plot;
+use vehicle_sim_core::simulation::{FixedStepRunner, RunSummary, Simulation};
+use vehicle_sim_core::uds::UdsServer;
+use vehicle_sim_core::units::{seconds_to_hours, Temperature};
+use vehicle_sim_core::vehicle_mode::{ModeManager, VehicleMode};
+use vehicle_sim_core::xcp::XcpServer;
 
-pub fn run_simulation(system: &mut ClimateControlSystem) {
-    loop {
-        println!("\n--- Simulating Climate Control System ---");
-        println!("Current cabin temperature: {:.1}°C", system.current_temperature);
-        println!("Desired cabin temperature: {:.1}°C", system.desired_temperature);
-        println!("External temperature: {:.1}°C", system.external_temperature);
+#[derive(Serialize, Deserialize)]
+pub struct ClimateSimulation {
+    pub system: MultiZoneClimate,
+    pub calendar: Calendar,
+    pub ambient: AmbientModel,
+    pub rng: SimRng,
+    #[serde(skip)]
+    pub config: Option<ConfigWatcher>,
+    pub steps: u64,
+    // Defog and zone sync mode changes, zones reaching their setpoint
+    #[serde(skip)]
+    pub events: Arc<EventBus>,
+    #[serde(skip)]
+    pub can: Arc<CanBus>,
+    #[serde(skip)]
+    pub uds: Option<UdsServer>,
+    // Measurement and calibration over XCP (--xcp)
+    #[serde(skip)]
+    pub xcp: Option<Arc<XcpServer>>,
+    // Telemetry to an MQTT broker (--mqtt)
+    #[cfg(feature = "mqtt")]
+    #[serde(skip)]
+    pub mqtt: Option<Arc<vehicle_sim_core::mqtt::MqttPublisher>>,
+    // Applied to the telemetry before it is exported (--privacy)
+    #[serde(skip)]
+    pub privacy: Option<PrivacyFilter>,
+    // Outside temperatures a scenario holds, overriding the ambient model
+    #[serde(default)]
+    pub weather: Vec<WeatherPoint>,
+    // A scenario with a duration runs to its end instead of stopping once
+    // the cabin is stabilized
+    #[serde(default)]
+    pub run_full_duration: bool,
+    // Vehicle mode along the scenario; the HVAC runs while driving and
+    // charging, when parked only for preconditioning, never in service
+    #[serde(default)]
+    pub mode: ModeManager,
+    #[serde(skip)]
+    pub preconditioning: bool,
+}
+
+// Config keys that may be edited while the simulation is running
+pub const SAFE_CONFIG_KEYS: &[&str] = &["desired_temperature", "passenger_temperature", "rear_temperature", "zone_sync"];
+
+// Config key holding the setpoint of each zone
+pub fn setpoint_key(zone: Zone) -> &'static str {
+    match zone {
+        Zone::Driver => "desired_temperature",
+        Zone::Passenger => "passenger_temperature",
+        Zone::Rear => "rear_temperature",
+    }
+}
+
+impl ClimateSimulation {
+    pub fn new(
+        mut system: MultiZoneClimate,
+        calendar: Calendar,
+        ambient: AmbientModel,
+        rng: SimRng,
+        config: Option<ConfigWatcher>,
+    ) -> Self {
+        // The outside temperature follows the season and time of day
+        system.set_external_temperature(Temperature::from_celsius(ambient.temperature(calendar.date(), calendar.hour_of_day())));
+
+        ClimateSimulation {
+            system,
+            calendar,
+            ambient,
+            rng,
+            config,
+            steps: 0,
+            events: Arc::new(EventBus::new()),
+            can: Arc::new(CanBus::new()),
+            uds: None,
+            xcp: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            privacy: None,
+            weather: Vec::new(),
+            run_full_duration: false,
+            mode: ModeManager::default(),
+            preconditioning: true,
+        }
+    }
+
+    fn publish_mode_change(&self, component: &str, on: bool) {
+        let mode = if on { "on" } else { "off" };
+        self.events.publish(
+            self.steps,
+            Event::ModeChanged {
+                component: component.to_string(),
+                mode: mode.to_string(),
+            },
+        );
+    }
+
+    fn hvac_allowed(&self) -> bool {
+        match self.mode.mode() {
+            VehicleMode::Driving | VehicleMode::Charging => true,
+            VehicleMode::Parked => self.preconditioning,
+            VehicleMode::Service => false,
+        }
+    }
+
+    fn transmit_status(&self, state: &ClimateState) {
+        let names: Vec<String> = state.zones.iter().map(|zone| format!("{:?}ZoneTemperature", zone.zone)).collect();
+        let mut values: Vec<(&str, f64)> = names
+            .iter()
+            .zip(&state.zones)
+            .map(|(name, zone)| (name.as_str(), zone.current_temperature as f64))
+            .collect();
+        values.push(("ExternalTemperature", state.external_temperature as f64));
+        values.push(("DefogActive", state.humidity.defog_active as u8 as f64));
+        values.push(("ZoneSync", state.zone_sync as u8 as f64));
+        self.can.transmit(&CLIMATE_STATUS, &values);
+    }
+
+    fn apply_config_updates(&mut self) {
+        let Some(config) = self.config.as_mut() else {
+            return;
+        };
+
+        for update in config.poll() {
+            let value = update.value.as_deref().unwrap_or("");
+            if update.key == "zone_sync" {
+                match parse_switch(value) {
+                    Some(zone_sync) => self.system.set_zone_sync(zone_sync),
+                    None => {
+                        sim_log::warn("config", &format!("Ignoring zone_sync = {}: expected on or off", value));
+                        continue;
+                    }
+                }
+            } else if let Some(zone) = Zone::ALL.into_iter().find(|&zone| setpoint_key(zone) == update.key) {
+                if !self.system.has_zone(zone) {
+                    sim_log::warn("config", &format!("Ignoring {}: this vehicle has no {:?} zone", update.key, zone));
+                    continue;
+                }
+                match value.parse::<f32>() {
+                    Ok(setpoint) => self.system.set_desired_temperature(zone, Temperature::from_celsius(setpoint)),
+                    Err(_) => {
+                        sim_log::warn("config", &format!("Ignoring {} change: expected a temperature in °C", update.key));
+                        continue;
+                    }
+                }
+            }
+            sim_log::info("config", &format!("Config reloaded: {} = {}", update.key, value));
+        }
+    }
+}
+
+impl Simulation for ClimateSimulation {
+    type State = ClimateState;
+
+    fn step(&mut self, dt: f64) {
+        self.steps += 1;
+        let before = self.system.state();
+        self.apply_config_updates();
+        self.apply_xcp_writes();
+        self.calendar.advance(seconds_to_hours(dt));
+        let ambient = scenario::weather_at(&self.weather, self.steps as f64 * dt)
+            .and_then(|point| point.temperature)
+            .map_or_else(|| self.ambient.temperature(self.calendar.date(), self.calendar.hour_of_day()), |t| t as f32);
+        self.system.set_external_temperature(Temperature::from_celsius(ambient));
+
+        self.mode.update(self.steps as f64 * dt);
+        self.mode.transmit(&self.can);
+        let hvac = self.hvac_allowed();
+        if hvac != self.system.is_hvac_enabled() {
+            self.system.set_hvac_enabled(hvac);
+            self.publish_mode_change("hvac", hvac);
+        }
 
         // Adjust cabin temperature
-        system.adjust_temperature();
+        self.system.adjust_temperature(dt as f32);
+        let active = self.system.dtcs().active_codes();
+        self.system.run_monitors(self.steps as f64 * dt, dt as f32);
+        for (_, record) in self.system.dtcs().records() {
+            if record.active && !active.contains(&record.code) {
+                self.events.publish(
+                    self.steps,
+                    Event::DtcSet {
+                        code: record.code.clone(),
+                        description: record.description.clone(),
+                    },
+                );
+            }
+        }
 
         // Simulate changes in external conditions every few iterations
-        if rand::thread_rng().gen_bool(0.2) {
-            system.simulate_external_conditions();
+        if self.rng.gen_bool(0.2) {
+            self.system.simulate_external_conditions(&mut self.rng);
+        }
+
+        let after = self.system.state();
+        if after.humidity.defog_active != before.humidity.defog_active {
+            self.publish_mode_change("defog", after.humidity.defog_active);
+        }
+        if after.zone_sync != before.zone_sync {
+            self.publish_mode_change("zone sync", after.zone_sync);
+        }
+        for (previous, zone) in before.zones.iter().zip(&after.zones) {
+            if zone.is_stabilized() && !previous.is_stabilized() {
+                self.events.publish(
+                    self.steps,
+                    Event::TemperatureReached {
+                        zone: format!("{:?}", zone.zone),
+                        temperature: zone.current_temperature,
+                    },
+                );
+            }
         }
+        self.transmit_status(&after);
+        if let Some(server) = self.uds.as_mut() {
+            server.poll(&mut self.system);
+        }
+        self.can.end_tick();
+        self.publish_xcp();
+        #[cfg(feature = "mqtt")]
+        self.publish_mqtt(self.steps as f64 * dt);
+    }
 
-        // Wait for a short period to simulate real-time adjustments
-        sleep(Duration::from_secs(1));
+    fn state(&self) -> ClimateState {
+        self.system.state()
+    }
 
-        // End the loop if the desired temperature is reached
-        if (system.current_temperature - system.desired_temperature).abs() < 0.1 {
-            println!("System stabilized at desired temperature.");
-            break;
+    fn report(&self) -> String {
+        let state = self.state();
+        let locale = locale::current();
+        let daylight = if self.ambient.is_daylight(self.calendar.date(), self.calendar.hour_of_day()) {
+            "day"
+        } else {
+            "night"
+        };
+
+        let mut lines = vec![
+            format!(
+                "\n--- Simulating Climate Control System ({}, {:?}, {}) ---",
+                locale.timestamp(self.calendar.date(), self.calendar.hour_of_day()),
+                self.calendar.date().season(),
+                daylight
+            ),
+            format!(
+                "External temperature: {}{}",
+                locale.temperature(state.external_temperature, 1),
+                if state.zone_sync { " (zones synced)" } else { "" }
+            ),
+        ];
+        let humidity = &state.humidity;
+        lines.push(format!(
+            "Cabin humidity: {}%, dew point {}, windshield {}, blower {}%{}",
+            locale.number(humidity.relative_humidity as f64 * 100.0, 0),
+            locale.temperature(humidity.dew_point, 1),
+            locale.temperature(humidity.windshield_temperature, 1),
+            locale.number(humidity.blower as f64 * 100.0, 0),
+            if humidity.defog_active { " (defog mode, A/C on)" } else { "" }
+        ));
+        for zone in &state.zones {
+            let action = if zone.hvac_power > 0.0 { "heating" } else { "cooling" };
+            lines.push(format!(
+                "{:?} zone: {} (desired {}), {} at {} kW",
+                zone.zone,
+                locale.temperature(zone.current_temperature, 1),
+                locale.temperature(zone.desired_temperature, 1),
+                action,
+                locale.number(zone.hvac_power.abs() as f64 / 1000.0, 2)
+            ));
+        }
+        for (_, record) in self.system.dtcs().records().filter(|(_, record)| record.active) {
+            lines.push(format!("DTC {} active: {}", record.code, record.description));
         }
+
+        lines.join("\n")
+    }
+
+    // End the loop if the desired temperature is reached
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self).ok()
+    }
+
+    fn is_finished(&self) -> bool {
+        !self.run_full_duration && self.system.is_stabilized()
+    }
+}
+
+// Per zone statistics of a batch run
+struct ZoneStatistics {
+    zone: Zone,
+    temperature: Stats,
+    stabilized_after: Option<f64>,
+}
+
+// Statistics over a batch run, timed from where it started
+struct BatchStatistics {
+    start: f64,
+    zones: Vec<ZoneStatistics>,
+    // Every zone at its setpoint for the first time
+    stabilized_after: Option<f64>,
+    fogged_steps: u64,
+    steps: u64,
+}
+
+impl BatchStatistics {
+    fn new(start: &RunSummary, state: &ClimateState) -> Self {
+        BatchStatistics {
+            start: start.simulated_seconds,
+            zones: state
+                .zones
+                .iter()
+                .map(|zone| ZoneStatistics {
+                    zone: zone.zone,
+                    temperature: Stats::default(),
+                    stabilized_after: None,
+                })
+                .collect(),
+            stabilized_after: None,
+            fogged_steps: 0,
+            steps: 0,
+        }
+    }
+
+    fn record(&mut self, summary: &RunSummary, state: &ClimateState) {
+        let elapsed = summary.simulated_seconds - self.start;
+        self.steps += 1;
+        if state.humidity.is_fogged() {
+            self.fogged_steps += 1;
+        }
+        for (statistics, zone) in self.zones.iter_mut().zip(&state.zones) {
+            statistics.temperature.record(zone.current_temperature as f64);
+            if zone.is_stabilized() && statistics.stabilized_after.is_none() {
+                statistics.stabilized_after = Some(elapsed);
+            }
+        }
+        if self.stabilized_after.is_none() && state.zones.iter().all(ZoneState::is_stabilized) {
+            self.stabilized_after = Some(elapsed);
+        }
+    }
+
+    fn print(&self, summary: &RunSummary) {
+        let locale = locale::current();
+        let stabilized = |after: Option<f64>| match after {
+            Some(seconds) => format!("stabilized after {} s", locale.number(seconds, 0)),
+            None => "not stabilized".to_string(),
+        };
+        println!("=========== Run summary ===========");
+        println!(
+            "Steps: {}, simulated time: {} s",
+            summary.steps,
+            locale.number(summary.simulated_seconds, 0)
+        );
+        println!("Cabin: {}", stabilized(self.stabilized_after));
+        for zone in &self.zones {
+            let temperature = &zone.temperature;
+            if let (Some(min), Some(mean), Some(max)) = (temperature.min(), temperature.mean(), temperature.max()) {
+                println!(
+                    "{:?} zone: {}, lowest {}, mean {}, highest {}",
+                    zone.zone,
+                    stabilized(zone.stabilized_after),
+                    locale.temperature(min as f32, 1),
+                    locale.temperature(mean as f32, 1),
+                    locale.temperature(max as f32, 1)
+                );
+            }
+        }
+        println!(
+            "Unsafe steps: {} ({}%) with the windshield fogged",
+            self.fogged_steps,
+            locale.number(batch::percent(self.fogged_steps, self.steps), 1)
+        );
+    }
+}
+
+// Runs until the cabin is stabilized (or for `max_steps`) and plots the
+// recorded temperatures and HVAC power to `plot_path` (PNG, or SVG for a
+// `.svg` path). A batch run prints its statistics instead of the reports,
+// timeline and chart.
+pub fn run_simulation(
+    simulation: &mut ClimateSimulation,
+    plot_path: &str,
+    start: RunSummary,
+    max_steps: Option<u64>,
+    realtime_factor: f64,
+    save: Option<PathBuf>,
+    batch: bool,
+) {
+    if batch {
+        let mut statistics = BatchStatistics::new(&start, &simulation.state());
+        let mut runner = FixedStepRunner::new(1.0)
+            .with_realtime_factor(0.0)
+            .quiet()
+            .resume_from(start);
+        if let Some(max_steps) = max_steps {
+            runner = runner.with_max_steps(max_steps);
+        }
+        if let Some(path) = save {
+            runner = runner.with_checkpoint(path);
+        }
+        let summary = runner.run_with(simulation, |state, summary| statistics.record(summary, &state));
+        statistics.print(&summary);
+        return;
+    }
+
+    let timeline = simulation.events.subscribe(EventFilter::all());
+    let mut recorder = ClimateRecorder::new();
+    recorder.record(start.simulated_seconds, &simulation.state());
+
+    // Bounded to five minutes of simulated time so the chart is written even
+    // if the occupants keep changing their setpoints
+    let mut runner = FixedStepRunner::new(1.0)
+        .with_realtime_factor(realtime_factor)
+        .with_max_steps(max_steps.unwrap_or(300))
+        .resume_from(start);
+    if let Some(path) = save {
+        runner = runner.with_checkpoint(path);
+    }
+
+    // With the `dashboard` feature, `--dashboard 127.0.0.1:8080` (or
+    // `--serve`) streams the zone temperatures and HVAC power
+    #[cfg(feature = "dashboard")]
+    let dashboard = vehicle_sim_core::dashboard::address_from_args().and_then(|address| {
+        let metrics = Arc::new(vehicle_sim_core::metrics::Metrics::new());
+        vehicle_sim_core::dashboard::Dashboard::start(&address, "Climate Control", Vec::new(), metrics)
+            .map_err(|e| eprintln!("Cannot start dashboard on {}: {}", address, e))
+            .ok()
+    });
+    #[cfg(feature = "dashboard")]
+    let streamed = simulation.events.subscribe(EventFilter::all());
+    #[cfg(feature = "dashboard")]
+    let mut dashboard_privacy = simulation.privacy.as_ref().map(PrivacyFilter::for_stream);
+
+    runner.run_with(simulation, |state, summary| {
+        recorder.record(summary.simulated_seconds, &state);
+        #[cfg(feature = "dashboard")]
+        if let Some(dashboard) = &dashboard {
+            for event in streamed.try_iter() {
+                dashboard.publish_event(&event);
+            }
+            let signals = dashboard_signals(&state);
+            let signals = vehicle_sim_core::privacy::filtered(dashboard_privacy.as_mut(), summary.simulated_seconds, signals);
+            dashboard.publish(&signals);
+        }
+    });
+    // The averages still open at the end go out with a last update
+    #[cfg(feature = "dashboard")]
+    if let (Some(dashboard), Some(privacy)) = (&dashboard, &mut dashboard_privacy) {
+        let pending = privacy.flush();
+        if !pending.is_empty() {
+            dashboard.publish(&pending);
+        }
+    }
+
+    events::print_timeline(&timeline);
+    if simulation.system.is_stabilized() {
+        println!("System stabilized at desired temperature.");
+    }
+
+    match recorder.plot(plot_path, sim_plot::theme_from_args()) {
+        Ok(()) => println!("Climate chart written to {}", plot_path),
+        Err(e) => eprintln!("Failed to plot climate data: {}", e),
+    }
+}
+
+#[cfg(feature = "dashboard")]
+fn dashboard_signals(state: &ClimateState) -> Vec<(&'static str, f64)> {
+    let mut signals = vec![("external_temperature_c", state.external_temperature as f64)];
+    for zone in &state.zones {
+        let (temperature, setpoint, power) = match zone.zone {
+            Zone::Driver => ("driver_temperature_c", "driver_setpoint_c", "driver_hvac_power_w"),
+            Zone::Passenger => ("passenger_temperature_c", "passenger_setpoint_c", "passenger_hvac_power_w"),
+            Zone::Rear => ("rear_temperature_c", "rear_setpoint_c", "rear_hvac_power_w"),
+        };
+        signals.push((temperature, zone.current_temperature as f64));
+        signals.push((setpoint, zone.desired_temperature as f64));
+        signals.push((power, zone.hvac_power as f64));
+    }
+    signals.push(("cabin_humidity", state.humidity.relative_humidity as f64));
+    signals
+}
+
+pub fn parse_switch(value: &str) -> Option<bool> {
+    match value {
+        "on" | "true" => Some(true),
+        "off" | "false" => Some(false),
+        _ => None,
     }
 }
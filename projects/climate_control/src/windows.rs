@@ -0,0 +1,570 @@
+// src/windows.rs
+use vehicle_sim_core::lin_bus::{LinMaster, LinNode, SLOT_TIME};
+use vehicle_sim_core::locale;
+
+// Window travel from closed to fully open, and the lift motor: free-running
+// speed, stall force and the current it draws between idle and stall
+pub const TRAVEL: f64 = 0.45; // m
+const FREE_SPEED: f64 = 0.12; // m/s
+const STALL_FORCE: f64 = 400.0; // N
+const IDLE_CURRENT: f64 = 2.0; // A
+const STALL_CURRENT: f64 = 26.0; // A
+// Drag of the seals and guides
+const FRICTION: f64 = 60.0; // N
+// A starting motor draws stall current, decaying with this time constant
+const INRUSH_TIME_CONSTANT: f64 = 0.03; // s
+
+// Anti-pinch in the door node: the current is ignored while the inrush
+// decays, after that a closing window that still moves but draws more than
+// PINCH_CURRENT is reversed by REVERSE_TRAVEL. In the last few mm the seal
+// takes over and the window closes against it.
+const INRUSH_MASK: f64 = 0.15; // s
+const PINCH_CURRENT: f64 = 7.6; // A, about 35 N over the seal drag
+const REVERSE_TRAVEL: f64 = 0.2; // m
+const SEAL_ZONE: f64 = 0.004; // m
+// A motor running without the Hall sensor seeing movement for this long is
+// switched off as stalled
+pub const STALL_TIME: f64 = 0.3; // s
+// Highest force a closing window may exert on a trapped object
+// (FMVSS 118, ECE R21)
+pub const MAX_PINCH_FORCE: f64 = 100.0; // N
+
+// LIN frames of the door cluster: the body module's window commands, two
+// bits per door, and a status frame from each door node. The schedule sends
+// the commands, then polls one door per slot.
+const COMMAND_FRAME: u8 = 0x10;
+const STATUS_FRAME: u8 = 0x20;
+const SCHEDULE: [Option<usize>; 5] = [None, Some(0), Some(1), Some(2), Some(3)];
+const POSITION_RESOLUTION: f64 = 0.002; // m
+const CURRENT_RESOLUTION: f64 = 0.2; // A
+const STATUS_MOVING: u8 = 0x01;
+const STATUS_PINCHED: u8 = 0x02;
+const STATUS_STALLED: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Door {
+    FrontLeft,
+    FrontRight,
+    RearLeft,
+    RearRight,
+}
+
+impl Door {
+    pub const ALL: [Door; 4] = [Door::FrontLeft, Door::FrontRight, Door::RearLeft, Door::RearRight];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn is_rear(self) -> bool {
+        matches!(self, Door::RearLeft | Door::RearRight)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Door::FrontLeft => "front left",
+            Door::FrontRight => "front right",
+            Door::RearLeft => "rear left",
+            Door::RearRight => "rear right",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Stop,
+    Close,
+    Open,
+}
+
+impl Motion {
+    fn encode(self) -> u8 {
+        match self {
+            Motion::Stop => 0,
+            Motion::Close => 1,
+            Motion::Open => 2,
+        }
+    }
+
+    fn decode(bits: u8) -> Motion {
+        match bits {
+            1 => Motion::Close,
+            2 => Motion::Open,
+            _ => Motion::Stop,
+        }
+    }
+}
+
+// The driver's switch block works every window, each door's own switch
+// only that one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    Driver,
+    Door,
+}
+
+// Something trapped in the opening `height` above the closed position,
+// compressed like a spring by the closing window
+#[derive(Debug, Clone, Copy)]
+pub struct Obstacle {
+    pub height: f64,    // m
+    pub stiffness: f64, // N/m
+}
+
+impl Obstacle {
+    fn force(&self, position: f64) -> f64 {
+        (self.height - position).max(0.0) * self.stiffness
+    }
+}
+
+// What a door node last reported over LIN
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WindowStatus {
+    pub position: f64, // m open
+    pub current: f64,  // A
+    pub moving: bool,
+    pub pinched: bool,
+    pub stalled: bool,
+}
+
+// The lift motor electronics in a door, a LIN slave that runs anti-pinch
+// and stall detection on its own. Commands act when they change, like a
+// switch being pressed.
+#[derive(Debug, Clone)]
+struct WindowNode {
+    door: Door,
+    position: f64,
+    command: Motion,
+    motion: Motion,
+    // Where a reversal after a pinch stops
+    reverse_to: Option<f64>,
+    running: f64,
+    // Time since the Hall sensor last saw movement
+    still: f64,
+    current: f64,
+    pinched: bool,
+    stalled: bool,
+    // Fault injection: the motor draws current but does not turn
+    stuck: bool,
+    obstacle: Option<Obstacle>,
+}
+
+impl WindowNode {
+    fn new(door: Door, position: f64) -> Self {
+        WindowNode {
+            door,
+            position,
+            command: Motion::Stop,
+            motion: Motion::Stop,
+            reverse_to: None,
+            running: 0.0,
+            still: 0.0,
+            current: 0.0,
+            pinched: false,
+            stalled: false,
+            stuck: false,
+            obstacle: None,
+        }
+    }
+
+    fn start(&mut self, motion: Motion) {
+        // A stalled motor stays off until the door is serviced
+        if self.stalled || motion == Motion::Stop {
+            self.motion = Motion::Stop;
+            return;
+        }
+        self.motion = motion;
+        self.pinched = false;
+        self.reverse_to = None;
+        self.running = 0.0;
+        self.still = 0.0;
+    }
+
+    fn force(&self) -> f64 {
+        self.obstacle.map_or(0.0, |obstacle| obstacle.force(self.position))
+    }
+
+    fn step(&mut self, dt: f64) {
+        if self.motion == Motion::Stop {
+            self.current = 0.0;
+            return;
+        }
+
+        let load = match self.motion {
+            Motion::Close => FRICTION + self.force(),
+            _ => FRICTION,
+        };
+        let share = (load / STALL_FORCE).min(1.0);
+        let speed = if self.stuck { 0.0 } else { FREE_SPEED * (1.0 - share) };
+        let current = if self.stuck { STALL_CURRENT } else { IDLE_CURRENT + (STALL_CURRENT - IDLE_CURRENT) * share };
+        self.current = current.max(STALL_CURRENT * (-self.running / INRUSH_TIME_CONSTANT).exp());
+
+        let travel = speed * dt;
+        self.position = match self.motion {
+            Motion::Close => (self.position - travel).max(0.0),
+            _ => (self.position + travel).min(TRAVEL),
+        };
+        self.running += dt;
+        self.still = if travel > 0.0 { 0.0 } else { self.still + dt };
+
+        let reversed = self.reverse_to.is_some_and(|target| self.position >= target);
+        let at_end = match self.motion {
+            Motion::Close => self.position <= 0.0,
+            _ => self.position >= TRAVEL,
+        };
+        if at_end || reversed {
+            self.motion = Motion::Stop;
+        } else if self.running > INRUSH_MASK {
+            if self.still >= STALL_TIME {
+                self.motion = Motion::Stop;
+                self.stalled = true;
+            } else if self.motion == Motion::Close && self.still == 0.0 && self.current > PINCH_CURRENT && self.position > SEAL_ZONE {
+                self.pinched = true;
+                self.motion = Motion::Open;
+                self.reverse_to = Some((self.position + REVERSE_TRAVEL).min(TRAVEL));
+                self.running = 0.0;
+            }
+        }
+    }
+}
+
+impl LinNode for WindowNode {
+    fn respond(&mut self, id: u8) -> Option<Vec<u8>> {
+        if id != STATUS_FRAME + self.door.index() as u8 {
+            return None;
+        }
+        let mut flags = 0;
+        if self.motion != Motion::Stop {
+            flags |= STATUS_MOVING;
+        }
+        if self.pinched {
+            flags |= STATUS_PINCHED;
+        }
+        if self.stalled {
+            flags |= STATUS_STALLED;
+        }
+        Some(vec![
+            (self.position / POSITION_RESOLUTION).round() as u8,
+            (self.current / CURRENT_RESOLUTION).round().min(255.0) as u8,
+            flags,
+        ])
+    }
+
+    fn receive(&mut self, id: u8, data: &[u8]) {
+        if id != COMMAND_FRAME {
+            return;
+        }
+        let command = Motion::decode(data.first().map_or(0, |bits| bits >> (2 * self.door.index()) & 0x03));
+        if command != self.command {
+            self.command = command;
+            self.start(command);
+        }
+    }
+}
+
+fn decode_status(data: &[u8]) -> WindowStatus {
+    WindowStatus {
+        position: data[0] as f64 * POSITION_RESOLUTION,
+        current: data[1] as f64 * CURRENT_RESOLUTION,
+        moving: data[2] & STATUS_MOVING != 0,
+        pinched: data[2] & STATUS_PINCHED != 0,
+        stalled: data[2] & STATUS_STALLED != 0,
+    }
+}
+
+// Window lift and child lock in the comfort ECU, the master of the door
+// nodes' LIN cluster. It sees the windows only through their status frames.
+pub struct BodyModule {
+    nodes: Vec<WindowNode>,
+    master: LinMaster,
+    commands: [Motion; 4],
+    pub child_lock: bool,
+    status: [WindowStatus; 4],
+    slot: usize,
+    pub time: f64,
+    pub events: Vec<(f64, String)>,
+}
+
+impl BodyModule {
+    // Every window `open` m open
+    pub fn new(open: f64) -> Self {
+        let position = open.clamp(0.0, TRAVEL);
+        BodyModule {
+            nodes: Door::ALL.iter().map(|&door| WindowNode::new(door, position)).collect(),
+            master: LinMaster::new(),
+            commands: [Motion::Stop; 4],
+            child_lock: false,
+            status: [WindowStatus {
+                position,
+                ..WindowStatus::default()
+            }; 4],
+            slot: 0,
+            time: 0.0,
+            events: Vec::new(),
+        }
+    }
+
+    // Returns whether the switch was accepted: the rear doors' own switches
+    // do nothing while the child lock is on
+    pub fn switch(&mut self, door: Door, panel: Panel, motion: Motion) -> bool {
+        if self.child_lock && panel == Panel::Door && door.is_rear() {
+            self.log(format!("{} window switch ignored, child lock on", door.name()));
+            return false;
+        }
+        self.commands[door.index()] = motion;
+        true
+    }
+
+    // Locking with the key fob closes every window; anti-pinch stays active
+    pub fn lock(&mut self) {
+        self.log("locked, closing all windows".to_string());
+        self.commands = [Motion::Close; 4];
+    }
+
+    pub fn status(&self, door: Door) -> WindowStatus {
+        self.status[door.index()]
+    }
+
+    // One slot of the LIN schedule
+    pub fn step(&mut self) {
+        self.time += SLOT_TIME;
+        for node in &mut self.nodes {
+            node.step(SLOT_TIME);
+        }
+
+        match SCHEDULE[self.slot] {
+            None => {
+                let bits = self.commands.iter().enumerate().fold(0, |bits, (i, motion)| bits | motion.encode() << (2 * i));
+                self.master.send(&mut self.nodes, COMMAND_FRAME, &[bits]);
+            }
+            Some(i) => match self.master.request(&mut self.nodes, STATUS_FRAME + i as u8) {
+                Ok(frame) => self.update(Door::ALL[i], decode_status(&frame.data)),
+                Err(e) => self.log(format!("{} door: {}", Door::ALL[i].name(), e)),
+            },
+        }
+        self.slot = (self.slot + 1) % SCHEDULE.len();
+    }
+
+    fn update(&mut self, door: Door, status: WindowStatus) {
+        let previous = std::mem::replace(&mut self.status[door.index()], status);
+        if status.pinched && !previous.pinched {
+            self.log(format!("{} window pinched something, reversing", door.name()));
+        }
+        if status.stalled && !previous.stalled {
+            self.log(format!("{} window motor stalled at {} m, switched off", door.name(), locale::current().number(status.position, 2)));
+        }
+        // Back to Stop once the window stopped, so the next press starts it
+        if previous.moving && !status.moving {
+            self.commands[door.index()] = Motion::Stop;
+        }
+    }
+
+    fn log(&mut self, message: String) {
+        self.events.push((self.time, message));
+    }
+}
+
+pub enum Action {
+    Switch(Door, Panel, Motion),
+    Lock,
+}
+
+pub struct WindowScenario {
+    pub name: &'static str,
+    pub open: f64,
+    pub child_lock: bool,
+    pub stuck: Option<Door>,
+    pub obstacle: Option<(Door, Obstacle)>,
+    // (s, action)
+    pub actions: &'static [(f64, Action)],
+}
+
+pub const SCENARIO_DURATION: f64 = 8.0; // s
+
+pub const SCENARIOS: &[WindowScenario] = &[
+    WindowScenario {
+        name: "Remote close-all",
+        open: TRAVEL,
+        child_lock: false,
+        stuck: None,
+        obstacle: None,
+        actions: &[(0.5, Action::Lock)],
+    },
+    WindowScenario {
+        name: "Remote close-all with a hand in the rear left window",
+        open: TRAVEL,
+        child_lock: false,
+        stuck: None,
+        // A forearm, about 10 N/mm
+        obstacle: Some((Door::RearLeft, Obstacle { height: 0.1, stiffness: 10_000.0 })),
+        actions: &[(0.5, Action::Lock)],
+    },
+    WindowScenario {
+        name: "Closing onto a stiff object",
+        open: TRAVEL,
+        child_lock: false,
+        stuck: None,
+        // The 65 N/mm test rod for small gaps
+        obstacle: Some((Door::FrontRight, Obstacle { height: 0.03, stiffness: 65_000.0 })),
+        actions: &[(0.5, Action::Switch(Door::FrontRight, Panel::Door, Motion::Close))],
+    },
+    WindowScenario {
+        name: "Child lock",
+        open: 0.0,
+        child_lock: true,
+        stuck: None,
+        obstacle: None,
+        actions: &[
+            (0.5, Action::Switch(Door::RearLeft, Panel::Door, Motion::Open)),
+            (1.0, Action::Switch(Door::RearLeft, Panel::Driver, Motion::Open)),
+            (1.0, Action::Switch(Door::FrontRight, Panel::Door, Motion::Open)),
+        ],
+    },
+    WindowScenario {
+        name: "Remote close-all with a stuck front left motor",
+        open: TRAVEL,
+        child_lock: false,
+        stuck: Some(Door::FrontLeft),
+        obstacle: None,
+        actions: &[(0.5, Action::Lock)],
+    },
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct DoorResult {
+    pub door: Door,
+    pub status: WindowStatus,
+    // Highest force on a trapped object, and when it was first touched
+    pub max_force: f64,
+    pub contact: Option<f64>,
+    // When the body module learned of a pinch or a stall over LIN
+    pub pinch_reported: Option<f64>,
+    pub stall_reported: Option<f64>,
+}
+
+pub struct ScenarioRun {
+    pub name: &'static str,
+    pub doors: Vec<DoorResult>,
+    pub events: Vec<(f64, String)>,
+}
+
+pub fn run(scenario: &WindowScenario) -> ScenarioRun {
+    let mut body = BodyModule::new(scenario.open);
+    body.child_lock = scenario.child_lock;
+    if let Some(door) = scenario.stuck {
+        body.nodes[door.index()].stuck = true;
+    }
+    if let Some((door, obstacle)) = scenario.obstacle {
+        body.nodes[door.index()].obstacle = Some(obstacle);
+    }
+
+    let mut doors: Vec<DoorResult> = Door::ALL
+        .iter()
+        .map(|&door| DoorResult {
+            door,
+            status: body.status(door),
+            max_force: 0.0,
+            contact: None,
+            pinch_reported: None,
+            stall_reported: None,
+        })
+        .collect();
+    let mut actions = scenario.actions.iter().peekable();
+    while body.time < SCENARIO_DURATION {
+        while let Some((_, action)) = actions.next_if(|(at, _)| *at <= body.time) {
+            match *action {
+                Action::Switch(door, panel, motion) => {
+                    body.switch(door, panel, motion);
+                }
+                Action::Lock => body.lock(),
+            }
+        }
+        body.step();
+
+        for (result, node) in doors.iter_mut().zip(&body.nodes) {
+            let force = node.force();
+            if force > 0.0 {
+                result.contact = result.contact.or(Some(body.time));
+            }
+            result.max_force = result.max_force.max(force);
+            result.status = body.status(result.door);
+            if result.status.pinched {
+                result.pinch_reported = result.pinch_reported.or(Some(body.time));
+            }
+            if result.status.stalled {
+                result.stall_reported = result.stall_reported.or(Some(body.time));
+            }
+        }
+    }
+
+    ScenarioRun {
+        name: scenario.name,
+        doors,
+        events: body.events,
+    }
+}
+
+pub fn print_report(runs: &[ScenarioRun]) {
+    let locale = locale::current();
+    for run in runs {
+        println!("--- {} ---", run.name);
+        for (time, event) in &run.events {
+            println!("  {:>5} s  {}", locale.number(*time, 2), event);
+        }
+        for door in &run.doors {
+            let mut line = format!("  {:<12} {} m open", door.door.name(), locale.number(door.status.position, 2));
+            if let (Some(contact), Some(reported)) = (door.contact, door.pinch_reported) {
+                let verdict = if door.max_force <= MAX_PINCH_FORCE { "within" } else { "OVER" };
+                line += &format!(
+                    ", pinched with {} N ({} the {} N limit), reported {} s after contact",
+                    locale.number(door.max_force, 0),
+                    verdict,
+                    locale.number(MAX_PINCH_FORCE, 0),
+                    locale.number(reported - contact, 3)
+                );
+            }
+            if door.status.stalled {
+                line += ", motor stalled";
+            }
+            println!("{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anti_pinch_reverses_below_the_force_limit_during_close_all() {
+        let run = run(&SCENARIOS[1]);
+        for door in &run.doors {
+            if door.door == Door::RearLeft {
+                assert!(door.pinch_reported.is_some());
+                assert!(door.max_force <= MAX_PINCH_FORCE, "{} N", door.max_force);
+                assert!(door.status.position >= 0.1 + REVERSE_TRAVEL - 0.01);
+            } else {
+                assert_eq!(door.status.position, 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn stuck_motor_stalls_in_time_and_child_lock_blocks_rear_switches() {
+        let stuck = run(&SCENARIOS[4]);
+        let front_left = stuck.doors[Door::FrontLeft.index()];
+        assert!(front_left.status.stalled && front_left.pinch_reported.is_none());
+        assert_eq!(front_left.status.position, TRAVEL);
+        // The lock at 0.5 s reaches the door within a schedule cycle, the
+        // stall report within another
+        let cycle = SCHEDULE.len() as f64 * SLOT_TIME;
+        assert!(front_left.stall_reported.unwrap() <= 0.5 + STALL_TIME + 2.0 * cycle + 1e-9);
+        assert!(stuck.doors[1..].iter().all(|door| door.status.position == 0.0));
+
+        let child_lock = run(&SCENARIOS[3]);
+        let rear_left = child_lock.doors[Door::RearLeft.index()];
+        // Opened from the driver's switch block only, half a second later
+        assert_eq!(rear_left.status.position, TRAVEL);
+        assert!(child_lock.events.iter().any(|(time, event)| *time < 1.0 && event.contains("child lock")));
+    }
+}
@@ -0,0 +1,106 @@
+use vehicle_sim_core::scenario::{self, RoutePoint};
+
+use crate::coast_down::RoadLoad;
+use crate::ev::{JOULES_PER_KWH, VEHICLE_MASS};
+
+// International Standard Atmosphere, valid through the troposphere
+const SEA_LEVEL_PRESSURE: f64 = 101_325.0; // Pa
+const SEA_LEVEL_TEMPERATURE: f64 = 288.15; // K
+const LAPSE_RATE: f64 = 0.0065; // K/m
+const GAS_CONSTANT: f64 = 287.05; // J/(kg K), dry air
+const GRAVITY: f64 = 9.81;
+pub const SEA_LEVEL_DENSITY: f64 = 1.225; // kg/m³
+
+// Wheel power of the naturally aspirated petrol engine at sea level; with
+// every breath holding less air it makes less power higher up
+pub const ENGINE_POWER: f64 = 60.0; // kW
+// No driver goes faster uphill, however much power is left
+const TOP_SPEED: f64 = 200.0; // km/h
+
+pub fn air_density(elevation: f64) -> f64 {
+    let temperature = SEA_LEVEL_TEMPERATURE - LAPSE_RATE * elevation;
+    let pressure = SEA_LEVEL_PRESSURE * (temperature / SEA_LEVEL_TEMPERATURE).powf(GRAVITY / (GAS_CONSTANT * LAPSE_RATE));
+    pressure / (GAS_CONSTANT * temperature)
+}
+
+// Share of the sea level air density; drag and engine power scale with it
+pub fn density_ratio(elevation: f64) -> f64 {
+    air_density(elevation) / SEA_LEVEL_DENSITY
+}
+
+pub fn engine_power(elevation: f64) -> f64 {
+    ENGINE_POWER * density_ratio(elevation)
+}
+
+// Highest steady speed in km/h at which `power` kW overcomes `load` against
+// `headwind` km/h plus the climb up `grade`
+pub fn hill_climb_speed(power: f64, load: RoadLoad, grade: f64, headwind: f64) -> f64 {
+    let climbing = VEHICLE_MASS * GRAVITY * grade.atan().sin(); // N
+    let needed = |speed: f64| (load.force_in_wind(speed, headwind) + climbing) * speed / 3.6 / 1000.0; // kW
+    if needed(TOP_SPEED) <= power {
+        return TOP_SPEED;
+    }
+
+    // Past its lowest point the power needed only grows with the speed
+    let (mut low, mut high) = (0.0, TOP_SPEED);
+    for _ in 0..50 {
+        let speed = (low + high) / 2.0;
+        if needed(speed) <= power {
+            low = speed;
+        } else {
+            high = speed;
+        }
+    }
+    low
+}
+
+// kWh of potential energy gained covering `distance` km up `grade`;
+// negative downhill, where gravity does the work
+pub fn climbing_energy(grade: f64, distance: f64) -> f64 {
+    VEHICLE_MASS * GRAVITY * grade.atan().sin() * distance * 1000.0 / JOULES_PER_KWH
+}
+
+// The steepest climb of a route: where it starts, its grade and the
+// elevation halfway up, where the engine has the power of
+pub struct SteepestClimb {
+    pub distance: f64, // km
+    pub grade: f64,
+    pub elevation: f64, // m
+}
+
+pub fn steepest_climb(route: &[RoutePoint]) -> Option<SteepestClimb> {
+    route
+        .windows(2)
+        .map(|pair| SteepestClimb {
+            distance: pair[0].distance,
+            grade: scenario::grade_at(route, pair[0].distance),
+            elevation: (pair[0].elevation + pair[1].elevation) / 2.0,
+        })
+        .filter(|climb| climb.grade > 0.0)
+        .max_by(|a, b| a.grade.total_cmp(&b.grade))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn air_thins_with_elevation() {
+        assert!((air_density(0.0) - SEA_LEVEL_DENSITY).abs() < 1e-3);
+        // Standard atmosphere tables give 1.007 kg/m³ at 2000 m
+        assert!((air_density(2000.0) - 1.007).abs() < 1e-3);
+        assert!(engine_power(2000.0) < 0.83 * ENGINE_POWER);
+    }
+
+    #[test]
+    fn thin_air_slows_the_engine_uphill() {
+        let grade = 0.09;
+        let sea_level = hill_climb_speed(ENGINE_POWER, RoadLoad::configured(), grade, 0.0);
+        let pass = hill_climb_speed(engine_power(2000.0), RoadLoad::configured().at_elevation(2000.0), grade, 0.0);
+
+        assert!(pass < sea_level - 5.0, "{} km/h on the pass, {} km/h at sea level", pass, sea_level);
+        // Less drag lets it go faster on the level at the same power
+        let level = hill_climb_speed(ENGINE_POWER, RoadLoad::configured(), 0.0, 0.0);
+        assert!(hill_climb_speed(ENGINE_POWER, RoadLoad::configured().at_elevation(2000.0), 0.0, 0.0) > level);
+    }
+}
@@ -0,0 +1,372 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use vehicle_sim_core::clock;
+
+use crate::consumption::{self, ConsumptionModel};
+use crate::elevation::ElevationProfile;
+use crate::ev;
+use crate::trip_computer::Trip;
+use vehicle_sim_core::config::LogLevel;
+use vehicle_sim_core::drive_cycle::DriveCycle;
+use vehicle_sim_core::driver_model::SpeedProfile;
+use vehicle_sim_core::scenario::{self, Scenario};
+use vehicle_sim_core::sim_log::{self, LogOptions};
+use vehicle_sim_core::sim_plot::Theme;
+use vehicle_sim_core::telematics::{self, Compression, NetworkSchedule};
+use vehicle_sim_core::units::{hours_to_seconds, seconds_to_hours};
+
+#[derive(Parser, Debug)]
+#[command(name = "odometer_simulation", about = "Simulates an odometer, trip meter and fuel consumption")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Simulated driving time in hours [default: 24]
+    #[arg(long)]
+    pub hours: Option<f64>,
+
+    /// Simulation step in hours [default: 0.5, 1 min with --elevation]
+    #[arg(long)]
+    pub step: Option<f64>,
+
+    /// Fuel efficiency in km per liter [default: 15]
+    #[arg(long)]
+    pub fuel_efficiency: Option<f64>,
+
+    /// Fuel consumption model: constant (the fuel efficiency at any speed),
+    /// petrol or diesel [default: constant]
+    #[arg(long)]
+    pub consumption: Option<String>,
+
+    /// Consumption models to compare over the same drive, comma separated
+    /// (e.g. petrol,diesel)
+    #[arg(long, value_delimiter = ',')]
+    pub compare: Vec<String>,
+
+    /// Simulate an electric vehicle: the battery replaces the fuel tank
+    #[arg(long)]
+    pub ev: bool,
+
+    /// Usable battery capacity of the electric vehicle in kWh
+    #[arg(long, default_value_t = ev::DEFAULT_CAPACITY)]
+    pub battery_capacity: f64,
+
+    /// Power drawn by the climate control in kW
+    #[arg(long, default_value_t = 0.5)]
+    pub climate_load: f64,
+
+    /// Range the random speed is drawn from, in km/h (e.g. 40..120)
+    #[arg(long, default_value = "40..120", value_parser = parse_speed_range)]
+    pub speed_range: (f64, f64),
+
+    /// Elevation profile of the trip, instead of the scenario's route: a CSV
+    /// file of distance_km,elevation_m or synthetic hills as
+    /// hills[:AMPLITUDE_M[:WAVELENGTH_KM]] (e.g. hills:80:5); climbs cost
+    /// fuel or charge, descents give some of it back
+    #[arg(long, value_parser = ElevationProfile::parse)]
+    pub elevation: Option<ElevationProfile>,
+
+    /// Drive a speed trace of an urban, highway or mixed cycle, with
+    /// acceleration limits and traffic lights, instead of random speeds
+    #[arg(long, value_parser = SpeedProfile::parse)]
+    pub speed_profile: Option<SpeedProfile>,
+
+    /// Drive a standard cycle (nedc, wltp, ftp75) or a CSV file of
+    /// time_s,speed_kmh over and over; use a short --step to follow it
+    #[arg(long, value_parser = DriveCycle::parse, conflicts_with = "speed_profile")]
+    pub drive_cycle: Option<DriveCycle>,
+
+    /// Send the telemetry through a telematics unit that buffers it while
+    /// the network is down: always-on, tunnel, rural, dead-zone or outages
+    /// as START+DURATION pairs (e.g. 2h+30min,6h+1h); uploads go to
+    /// telematics_uplink.jsonl
+    #[arg(long, value_parser = NetworkSchedule::parse)]
+    pub telematics: Option<NetworkSchedule>,
+
+    /// Records the telematics unit buffers before it drops the oldest
+    #[arg(long, default_value_t = telematics::DEFAULT_CAPACITY)]
+    pub telematics_buffer: usize,
+
+    /// Compress the telematics uplink: a signal is only sent, as the change
+    /// since it was last sent, once it moves further than its deadband; a
+    /// deadband for all signals and/or SIGNAL=DEADBAND pairs, e.g. 0.5,speed=2
+    #[arg(long, value_parser = Compression::parse, requires = "telematics")]
+    pub telematics_deadband: Option<Compression>,
+
+    /// Remote commands the backend sends over the telematics link, one per
+    /// line as <time> <token> <command>: precondition [duration], lock,
+    /// unlock, valet <km/h>|off or restrict valet|teen|off; the audit log
+    /// goes to remote_audit.jsonl
+    #[arg(long, requires_all = ["telematics", "remote_tokens"])]
+    pub remote_commands: Option<PathBuf>,
+
+    /// Tokens allowed to send remote commands (key = value file):
+    /// token.<holder>, and optionally allow.<holder> and expires.<holder>
+    #[arg(long, requires = "remote_commands")]
+    pub remote_tokens: Option<PathBuf>,
+
+    /// Fault injection: the power fails after this many bytes of the
+    /// end-of-run save; the next run recovers from odometer.journal
+    #[arg(long)]
+    pub power_loss: Option<u64>,
+
+    /// Privacy settings (key = value file) applied to the telemetry before
+    /// it goes to MQTT, the telematics uplink or the CSV time series:
+    /// location, timestamps and a keep, drop, round or average policy per
+    /// signal
+    #[arg(long)]
+    pub privacy: Option<PathBuf>,
+
+    /// Path of the chart: PNG, or SVG for a .svg path
+    #[arg(long, default_value = "odometer_simulation.png")]
+    pub output: PathBuf,
+
+    /// Colors of the chart: light or dark [default: SIM_PLOT_THEME, then light]
+    #[arg(long, value_parser = Theme::parse)]
+    pub plot_theme: Option<Theme>,
+
+    /// Show the chart in a window that updates as the run goes on; the run
+    /// then advances one step per second unless --realtime-factor is given
+    #[cfg(feature = "live")]
+    #[arg(long)]
+    pub live: bool,
+
+    /// Path of the CSV time series
+    #[arg(long, default_value = "odometer_simulation.csv")]
+    pub csv: PathBuf,
+
+    /// Field delimiter of the CSV time series
+    #[arg(long, default_value_t = ',')]
+    pub csv_delimiter: char,
+
+    /// Decimal places written to the CSV time series
+    #[arg(long, default_value_t = 3)]
+    pub csv_precision: usize,
+
+    /// Write a checkpoint of the run to this JSON file
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+
+    /// Continue a run from a checkpoint written with --save; --hours counts
+    /// the hours already simulated
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+
+    /// Trip computer counters to reset before driving, comma separated: a, b
+    /// or refuel (useful with --resume)
+    #[arg(long, value_delimiter = ',', value_parser = parse_trip)]
+    pub reset_trip: Vec<Trip>,
+
+    /// Random seed, to reproduce a run
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Scenario file (TOML) with the run length, step, seed, fuel efficiency,
+    /// start date and route profile; flags given as well take precedence
+    #[arg(long)]
+    pub scenario: Option<PathBuf>,
+
+    /// Key fob of the driver; FOB-4 belongs to a teen driver held to the
+    /// teen restriction profile
+    #[arg(long)]
+    pub driver: Option<String>,
+
+    /// Write every CAN frame to this file: candump log, or Vector ASC for .asc
+    #[arg(long)]
+    pub can_trace: Option<PathBuf>,
+
+    /// Address to serve an ELM327-compatible OBD-II interface on (e.g. 127.0.0.1:35000);
+    /// the run then advances one step per second unless --realtime-factor is given
+    #[arg(long)]
+    pub obd: Option<String>,
+
+    /// Simulated seconds per wall-clock second, 0 for as fast as possible
+    /// [default: 0]
+    #[arg(long, value_parser = clock::parse_realtime_factor)]
+    pub realtime_factor: Option<f64>,
+
+    /// UDP address to serve XCP measurement and calibration on (e.g. 127.0.0.1:5555)
+    #[arg(long)]
+    pub xcp: Option<String>,
+
+    /// MQTT broker to publish telemetry to (host[:port]); credentials come
+    /// from SIM_MQTT_USER and SIM_MQTT_PASSWORD
+    #[cfg(feature = "mqtt")]
+    #[arg(long)]
+    pub mqtt: Option<String>,
+
+    /// Write the signals and parameters the simulation exposes to this file
+    /// (JSON, or ASAP2 text for .a2l) and exit
+    #[arg(long)]
+    pub describe: Option<PathBuf>,
+
+    /// Most verbose log level printed: error, warn, info or debug
+    #[arg(long, value_parser = parse_log_level)]
+    pub log_level: Option<LogLevel>,
+
+    /// Also write the log as plain text to this file
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Also write the log as JSON lines to this file
+    #[arg(long)]
+    pub log_json: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Summarize the trip history per driver
+    Stats {
+        /// Only show this key fob
+        #[arg(long)]
+        driver: Option<String>,
+    },
+    /// Roll out from 120 km/h, fit the drag and rolling resistance from the
+    /// deceleration and check them against the configured road load
+    CoastDown,
+    /// Run a prescribed wheel-speed trace on a chassis dyno with simulated
+    /// road load and measure the consumption; writes the trace to --csv
+    Dyno {
+        /// Built-in cycle (urban, highway), standard cycle (nedc, wltp,
+        /// ftp75) or a CSV file of time_s,speed_kmh
+        #[arg(long, default_value = "urban")]
+        cycle: String,
+    },
+    /// Estimate the vehicle mass from drive force and acceleration while
+    /// the car is loaded and unloaded, and plot how the estimate converges
+    MassEstimate {
+        /// Chart of the estimated mass, stopping distance and range
+        #[arg(long, default_value = "mass_estimation.png")]
+        output: PathBuf,
+    },
+    /// Replay a route recorded as a GPX track, with speed and slope taken
+    /// from its fixes: consumption per kilometer and a map colored by speed
+    Gpx {
+        /// GPX file with timestamped track points
+        track: PathBuf,
+        /// Chart of the route and the consumption along it
+        #[arg(long, default_value = "gpx_route.png")]
+        output: PathBuf,
+    },
+}
+
+fn parse_trip(value: &str) -> Result<Trip, String> {
+    Trip::parse(value).ok_or_else(|| format!("unknown trip {}: expected a, b or refuel", value))
+}
+
+fn parse_log_level(value: &str) -> Result<LogLevel, String> {
+    LogLevel::parse(value).ok_or_else(|| format!("unknown log level {}: expected error, warn, info or debug", value))
+}
+
+fn parse_speed_range(value: &str) -> Result<(f64, f64), String> {
+    let (min, max) = value
+        .split_once("..")
+        .ok_or_else(|| format!("expected MIN..MAX, got '{}'", value))?;
+    let min: f64 = min.trim().parse().map_err(|_| format!("invalid minimum speed '{}'", min))?;
+    let max: f64 = max.trim().parse().map_err(|_| format!("invalid maximum speed '{}'", max))?;
+
+    if min < 0.0 || min >= max {
+        return Err(format!("speed range must satisfy 0 <= MIN < MAX, got {}..{}", min, max));
+    }
+    Ok((min, max))
+}
+
+impl Cli {
+    pub fn hours(&self) -> f64 {
+        self.hours.unwrap_or(24.0)
+    }
+
+    // An elevation profile needs steps short enough to follow its hills
+    pub fn step(&self) -> f64 {
+        self.step.unwrap_or(if self.elevation.is_some() { seconds_to_hours(60.0) } else { 0.5 })
+    }
+
+    pub fn fuel_efficiency(&self) -> f64 {
+        self.fuel_efficiency.unwrap_or(15.0)
+    }
+
+    // `None` for the constant model, which follows the (calibratable) fuel
+    // efficiency directly
+    pub fn consumption_model(&self) -> Option<Arc<dyn ConsumptionModel>> {
+        self.consumption
+            .as_deref()
+            .filter(|&name| name != "constant")
+            .and_then(|name| consumption::model(name, self.fuel_efficiency()))
+    }
+
+    pub fn compared_models(&self) -> Vec<Arc<dyn ConsumptionModel>> {
+        self.compare
+            .iter()
+            .filter_map(|name| consumption::model(name, self.fuel_efficiency()))
+            .collect()
+    }
+
+    // --realtime-factor or SIM_REALTIME_FACTOR; while an OBD tool may be
+    // watching, one step per second
+    pub fn realtime_factor(&self) -> f64 {
+        self.realtime_factor
+            .or_else(clock::realtime_factor_from_args)
+            .unwrap_or(if self.paced() { hours_to_seconds(self.step()) } else { 0.0 })
+    }
+
+    // Runs someone follows as they happen, over OBD-II or in the live chart
+    fn paced(&self) -> bool {
+        #[cfg(feature = "live")]
+        if self.live {
+            return true;
+        }
+        self.obd.is_some()
+    }
+
+    // Settings not given on the command line come from the scenario
+    pub fn apply_scenario(&mut self, scenario: &Scenario) {
+        self.hours = self.hours.or(scenario.duration.map(seconds_to_hours));
+        // A route profile needs steps short enough to follow its climbs
+        self.step = self.step.or_else(|| match scenario.initial("step").map(scenario::parse_duration) {
+            Some(Ok(seconds)) => Some(seconds_to_hours(seconds)),
+            Some(Err(e)) => {
+                sim_log::warn("scenario", &format!("Ignoring initial step: {}", e));
+                None
+            }
+            None => None,
+        });
+        self.fuel_efficiency = self.fuel_efficiency.or_else(|| scenario.initial_f64("fuel_efficiency"));
+        self.consumption = self.consumption.take().or_else(|| scenario.initial_str("consumption").map(str::to_string));
+        self.power_loss = self.power_loss.or_else(|| scenario.initial_f64("power_loss").map(|bytes| bytes as u64));
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.hours() <= 0.0 || self.step() <= 0.0 || self.step() > self.hours() {
+            return Err("--hours and --step must be positive, with --step <= --hours".to_string());
+        }
+        if self.fuel_efficiency() <= 0.0 {
+            return Err("--fuel-efficiency must be positive".to_string());
+        }
+        if self.battery_capacity <= 0.0 || self.climate_load < 0.0 {
+            return Err("--battery-capacity must be positive and --climate-load not negative".to_string());
+        }
+        for name in self.consumption.iter().chain(&self.compare) {
+            if !consumption::MODEL_NAMES.contains(&name.as_str()) {
+                return Err(format!(
+                    "unknown consumption model {}: expected {}",
+                    name,
+                    consumption::MODEL_NAMES.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Flags take precedence over the SIM_LOG_* variables
+    pub fn log_options(&self) -> LogOptions {
+        let options = LogOptions::from_env();
+        LogOptions {
+            level: self.log_level.unwrap_or(options.level),
+            file: self.log_file.clone().or(options.file),
+            json: self.log_json.clone().or(options.json),
+            ..options
+        }
+    }
+}
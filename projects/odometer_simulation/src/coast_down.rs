@@ -0,0 +1,150 @@
+use rand::Rng;
+use vehicle_sim_core::rng::SimRng;
+
+use crate::altitude;
+use crate::ev::{DRAG_CONSUMPTION, ROLLING_CONSUMPTION, VEHICLE_MASS};
+
+pub const START_SPEED: f64 = 120.0; // km/h
+// Below this the road load no longer dominates the deceleration
+pub const END_SPEED: f64 = 15.0; // km/h
+const SAMPLE_INTERVAL: f64 = 1.0; // s
+// Integration steps per sample
+const SUBSTEPS: usize = 100;
+// Speed sensor noise, uniform within ±this
+const SENSOR_NOISE: f64 = 0.05; // km/h
+// Fitted coefficients this close to the configured ones confirm the model
+pub const TOLERANCE: f64 = 0.05;
+// A road load of 1 Wh/km is a force of 3.6 N
+const NEWTONS_PER_WH_PER_KM: f64 = 3.6;
+
+// Road load F = rolling + drag * v² in N, with v in km/h
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoadLoad {
+    pub rolling: f64,
+    pub drag: f64,
+}
+
+impl RoadLoad {
+    // The coefficients the energy models drive with
+    pub fn configured() -> RoadLoad {
+        RoadLoad {
+            rolling: ROLLING_CONSUMPTION * NEWTONS_PER_WH_PER_KM,
+            drag: DRAG_CONSUMPTION * NEWTONS_PER_WH_PER_KM,
+        }
+    }
+
+    // Drag falls with the air density; rolling resistance does not
+    pub fn at_elevation(self, elevation: f64) -> RoadLoad {
+        RoadLoad {
+            drag: self.drag * altitude::density_ratio(elevation),
+            ..self
+        }
+    }
+
+    pub fn force(&self, speed: f64) -> f64 {
+        self.rolling + self.drag * speed * speed
+    }
+
+    // Drag works against the speed through the air, `headwind` km/h more
+    // than over the road
+    pub fn force_in_wind(&self, speed: f64, headwind: f64) -> f64 {
+        let airspeed = speed + headwind;
+        self.rolling + self.drag * airspeed * airspeed.abs()
+    }
+}
+
+pub struct CoastDown {
+    // (s, km/h) as read by the speed sensor
+    pub samples: Vec<(f64, f64)>,
+    pub fitted: RoadLoad,
+}
+
+impl CoastDown {
+    // Relative deviation of the fitted from the configured coefficients
+    pub fn errors(&self, configured: RoadLoad) -> (f64, f64) {
+        (
+            (self.fitted.rolling - configured.rolling) / configured.rolling,
+            (self.fitted.drag - configured.drag) / configured.drag,
+        )
+    }
+
+    pub fn is_consistent(&self, configured: RoadLoad) -> bool {
+        let (rolling, drag) = self.errors(configured);
+        rolling.abs() <= TOLERANCE && drag.abs() <= TOLERANCE
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.samples.last().map_or(0.0, |&(t, _)| t)
+    }
+}
+
+// Lets the vehicle roll out in neutral from START_SPEED to END_SPEED under
+// `load`, recording a noisy speed signal, then fits the road load back
+// from the deceleration between samples
+pub fn run(load: RoadLoad, rng: &mut SimRng) -> CoastDown {
+    let mut samples = Vec::new();
+    let (mut time, mut speed) = (0.0, START_SPEED);
+    let dt = SAMPLE_INTERVAL / SUBSTEPS as f64;
+    while speed > END_SPEED {
+        samples.push((time, speed + rng.gen_range(-SENSOR_NOISE..=SENSOR_NOISE)));
+        for _ in 0..SUBSTEPS {
+            let deceleration = load.force(speed) / VEHICLE_MASS * 3.6; // km/h per s
+            speed -= deceleration * dt;
+        }
+        time += SAMPLE_INTERVAL;
+    }
+
+    let fitted = fit(&samples);
+    CoastDown { samples, fitted }
+}
+
+// Least squares fit of F = m * a over v² at the middle of each interval
+fn fit(samples: &[(f64, f64)]) -> RoadLoad {
+    let points: Vec<(f64, f64)> = samples
+        .windows(2)
+        .map(|pair| {
+            let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+            let speed = (v0 + v1) / 2.0;
+            let force = VEHICLE_MASS * (v0 - v1) / 3.6 / (t1 - t0);
+            (speed * speed, force)
+        })
+        .collect();
+    let n = points.len() as f64;
+    if n < 2.0 {
+        return RoadLoad { rolling: 0.0, drag: 0.0 };
+    }
+    let mean_x = points.iter().map(|&(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|&(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|&(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = points.iter().map(|&(x, _)| (x - mean_x) * (x - mean_x)).sum();
+    let drag = if variance > 0.0 { covariance / variance } else { 0.0 };
+    RoadLoad {
+        rolling: mean_y - drag * mean_x,
+        drag,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_recovers_the_configured_road_load() {
+        let configured = RoadLoad::configured();
+        let coast_down = run(configured, &mut SimRng::from_seed(1));
+
+        assert!(coast_down.is_consistent(configured), "{:?}", coast_down.fitted);
+        assert!(coast_down.samples.last().unwrap().1 <= END_SPEED + 1.0);
+    }
+
+    #[test]
+    fn a_different_vehicle_is_flagged() {
+        let heavier = RoadLoad {
+            rolling: RoadLoad::configured().rolling * 1.5,
+            drag: RoadLoad::configured().drag,
+        };
+        let coast_down = run(heavier, &mut SimRng::from_seed(1));
+
+        assert!(!coast_down.is_consistent(RoadLoad::configured()));
+    }
+}
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+// Named models selectable with `--consumption` and `--compare`
+pub const MODEL_NAMES: &[&str] = &["constant", "petrol", "diesel"];
+
+// What a liter of fuel holds and emits when burned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fuel {
+    pub energy_density: f64, // kWh/l
+    pub co2_per_liter: f64,  // g/l
+}
+
+pub const PETROL: Fuel = Fuel {
+    energy_density: 8.9,
+    co2_per_liter: 2310.0,
+};
+pub const DIESEL: Fuel = Fuel {
+    energy_density: 10.0,
+    co2_per_liter: 2640.0,
+};
+
+// How much fuel an engine burns at a given speed, so different vehicles can
+// be driven over the same speed profile
+pub trait ConsumptionModel: Send + Sync {
+    fn name(&self) -> &str;
+
+    // Liters per hour at `speed` km/h, idling included
+    fn liters_per_hour(&self, speed: f64) -> f64;
+
+    fn liters(&self, speed: f64, hours: f64) -> f64 {
+        self.liters_per_hour(speed) * hours
+    }
+
+    // The fuel burned
+    fn fuel(&self) -> Fuel {
+        PETROL
+    }
+}
+
+// The same km/l at any speed, and nothing while standing
+pub struct ConstantEfficiency {
+    pub km_per_liter: f64,
+}
+
+impl ConsumptionModel for ConstantEfficiency {
+    fn name(&self) -> &str {
+        "constant"
+    }
+
+    fn liters_per_hour(&self, speed: f64) -> f64 {
+        speed / self.km_per_liter
+    }
+}
+
+// l/100 km looked up by speed, interpolated linearly between the points and
+// held flat beyond them. The engine never burns less than at idle.
+pub struct ConsumptionTable {
+    pub name: String,
+    pub fuel: Fuel,
+    pub idle_liters_per_hour: f64,
+    pub points: Vec<(f64, f64)>, // (km/h, l/100 km), ascending speed
+}
+
+impl ConsumptionTable {
+    pub fn liters_per_100km(&self, speed: f64) -> f64 {
+        let Some(&(first_speed, first)) = self.points.first() else {
+            return 0.0;
+        };
+        if speed <= first_speed {
+            return first;
+        }
+        for pair in self.points.windows(2) {
+            let ((low_speed, low), (high_speed, high)) = (pair[0], pair[1]);
+            if speed <= high_speed {
+                return low + (high - low) * (speed - low_speed) / (high_speed - low_speed);
+            }
+        }
+        self.points.last().map_or(first, |&(_, last)| last)
+    }
+}
+
+impl ConsumptionModel for ConsumptionTable {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn liters_per_hour(&self, speed: f64) -> f64 {
+        (self.liters_per_100km(speed) * speed / 100.0).max(self.idle_liters_per_hour)
+    }
+
+    fn fuel(&self) -> Fuel {
+        self.fuel
+    }
+}
+
+// l/100 km as a polynomial over speed, lowest order coefficient first
+pub struct PolynomialConsumption {
+    pub name: String,
+    pub fuel: Fuel,
+    pub idle_liters_per_hour: f64,
+    pub coefficients: Vec<f64>,
+}
+
+impl ConsumptionModel for PolynomialConsumption {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn liters_per_hour(&self, speed: f64) -> f64 {
+        let per_100km = self.coefficients.iter().rev().fold(0.0, |sum, c| sum * speed + c);
+        (per_100km.max(0.0) * speed / 100.0).max(self.idle_liters_per_hour)
+    }
+
+    fn fuel(&self) -> Fuel {
+        self.fuel
+    }
+}
+
+// Another model driven over the same speeds as the odometer, to compare
+// vehicles within one run
+pub struct Comparison {
+    pub model: Arc<dyn ConsumptionModel>,
+    pub liters: f64,
+    pub kilometers: f64,
+}
+
+impl Comparison {
+    pub fn new(model: Arc<dyn ConsumptionModel>) -> Self {
+        Comparison {
+            model,
+            liters: 0.0,
+            kilometers: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, speed: f64, hours: f64) {
+        self.liters += self.model.liters(speed, hours);
+        self.kilometers += speed * hours;
+    }
+
+    pub fn liters_per_100km(&self) -> f64 {
+        if self.kilometers > 0.0 {
+            self.liters / self.kilometers * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// "constant" keeps the configured km/l; "petrol" and "diesel" are maps of a
+// compact car with either engine
+pub fn model(name: &str, km_per_liter: f64) -> Option<Arc<dyn ConsumptionModel>> {
+    let model: Arc<dyn ConsumptionModel> = match name {
+        "constant" => Arc::new(ConstantEfficiency { km_per_liter }),
+        "petrol" => Arc::new(ConsumptionTable {
+            name: name.to_string(),
+            fuel: PETROL,
+            idle_liters_per_hour: 0.8,
+            points: vec![
+                (20.0, 8.5),
+                (50.0, 5.6),
+                (80.0, 5.1),
+                (100.0, 5.8),
+                (120.0, 7.0),
+                (140.0, 8.6),
+                (160.0, 10.5),
+            ],
+        }),
+        "diesel" => Arc::new(PolynomialConsumption {
+            name: name.to_string(),
+            fuel: DIESEL,
+            idle_liters_per_hour: 0.6,
+            coefficients: vec![6.2, -0.045, 0.00032],
+        }),
+        _ => return None,
+    };
+    Some(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> ConsumptionTable {
+        ConsumptionTable {
+            name: "test".to_string(),
+            fuel: PETROL,
+            idle_liters_per_hour: 1.0,
+            points: vec![(50.0, 6.0), (100.0, 8.0)],
+        }
+    }
+
+    #[test]
+    fn table_interpolates_between_points_and_holds_beyond_them() {
+        let table = table();
+
+        assert_eq!(table.liters_per_100km(75.0), 7.0);
+        assert_eq!(table.liters_per_100km(20.0), 6.0);
+        assert_eq!(table.liters_per_100km(150.0), 8.0);
+    }
+
+    #[test]
+    fn standing_still_burns_idle_fuel() {
+        assert_eq!(table().liters_per_hour(0.0), 1.0);
+        assert_eq!(model("diesel", 15.0).unwrap().liters(0.0, 2.0), 1.2);
+        assert_eq!(model("constant", 15.0).unwrap().liters(0.0, 2.0), 0.0);
+    }
+
+    #[test]
+    fn constant_model_matches_the_fuel_efficiency() {
+        let constant = model("constant", 15.0).unwrap();
+
+        assert_eq!(constant.liters(90.0, 1.0), 6.0);
+    }
+
+    #[test]
+    fn every_listed_model_exists() {
+        assert!(MODEL_NAMES.iter().all(|name| model(name, 15.0).is_some()));
+    }
+}
@@ -0,0 +1,203 @@
+use std::io;
+use std::sync::Arc;
+
+use crate::coast_down::RoadLoad;
+use crate::consumption::ConsumptionModel;
+use crate::ev::{kinetic_energy, EvOdometer};
+use vehicle_sim_core::drive_cycle::DriveCycle;
+
+// Cycles selectable with `dyno --cycle`: the dyno's own and the standard ones
+pub const CYCLE_NAMES: &[&str] = &["urban", "highway", "nedc", "wltp", "ftp75"];
+
+// Share of the fuel energy reaching the wheels while accelerating; the
+// consumption maps only cover driving at a steady speed
+pub const ENGINE_EFFICIENCY: f64 = 0.3;
+const SAMPLE_INTERVAL: f64 = 1.0; // s
+
+// Wheel speed traces of the dyno's own urban and highway cycles
+pub fn builtin(name: &str) -> Option<DriveCycle> {
+    let points: &[(f64, f64)] = match name {
+        // Stop-and-go city driving with idling at the lights
+        "urban" => &[
+            (0.0, 0.0),
+            (15.0, 0.0),
+            (30.0, 32.0),
+            (60.0, 32.0),
+            (75.0, 0.0),
+            (95.0, 0.0),
+            (115.0, 50.0),
+            (170.0, 50.0),
+            (190.0, 0.0),
+            (210.0, 0.0),
+            (230.0, 40.0),
+            (260.0, 40.0),
+            (275.0, 20.0),
+            (300.0, 20.0),
+            (315.0, 0.0),
+            (340.0, 0.0),
+            (360.0, 50.0),
+            (420.0, 50.0),
+            (440.0, 0.0),
+            (460.0, 0.0),
+        ],
+        "highway" => &[
+            (0.0, 0.0),
+            (20.0, 0.0),
+            (60.0, 80.0),
+            (150.0, 90.0),
+            (200.0, 110.0),
+            (300.0, 120.0),
+            (360.0, 100.0),
+            (420.0, 120.0),
+            (480.0, 90.0),
+            (540.0, 60.0),
+            (570.0, 0.0),
+            (600.0, 0.0),
+        ],
+        _ => return None,
+    };
+    DriveCycle::new(name, points.to_vec()).ok()
+}
+
+// A built-in or standard cycle name, or the path of a CSV file of
+// `time_s,speed_kmh` lines
+pub fn cycle(name_or_path: &str) -> io::Result<DriveCycle> {
+    match builtin(name_or_path) {
+        Some(trace) => Ok(trace),
+        None => DriveCycle::from_name_or_path(name_or_path),
+    }
+}
+
+// The vehicle on the dyno: burning fuel after a consumption model, or
+// drawing on the battery
+pub enum DynoVehicle {
+    Combustion(Arc<dyn ConsumptionModel>),
+    Electric(EvOdometer),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DynoResult {
+    pub duration: f64, // s
+    pub distance: f64, // km
+    // Energy the rollers absorbed to simulate the road load, in kWh
+    pub road_load_energy: f64,
+    // Combustion only
+    pub fuel: f64, // l
+    pub co2: f64,  // g
+    // Electric only, net of regeneration
+    pub energy: f64, // kWh
+    // (s, km/h, cumulative liters or kWh) for every sample
+    pub samples: Vec<(f64, f64, f64)>,
+}
+
+impl DynoResult {
+    pub fn average_speed(&self) -> f64 {
+        if self.duration > 0.0 {
+            self.distance / (self.duration / 3600.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn per_100km(&self, amount: f64) -> f64 {
+        if self.distance > 0.0 {
+            amount / self.distance * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    pub fn co2_per_km(&self) -> f64 {
+        if self.distance > 0.0 {
+            self.co2 / self.distance
+        } else {
+            0.0
+        }
+    }
+}
+
+// Drives `trace` on the rollers, which brake the wheels with `load`. No
+// driver or random input is involved, so the same vehicle and trace always
+// measure the same.
+pub fn run(trace: &DriveCycle, vehicle: &mut DynoVehicle, load: RoadLoad) -> DynoResult {
+    let mut result = DynoResult::default();
+    let hours = SAMPLE_INTERVAL / 3600.0;
+    let mut time = 0.0;
+    while time < trace.duration() {
+        let (previous, speed) = (trace.speed_at(time), trace.speed_at(time + SAMPLE_INTERVAL));
+        let average = (previous + speed) / 2.0;
+        result.road_load_energy += load.force(average) * average / 3.6 * SAMPLE_INTERVAL / 3.6e6;
+
+        match vehicle {
+            DynoVehicle::Combustion(model) => {
+                let fuel = model.fuel();
+                let acceleration = (kinetic_energy(speed) - kinetic_energy(previous)).max(0.0);
+                let liters = model.liters(average, hours) + acceleration / ENGINE_EFFICIENCY / fuel.energy_density;
+                result.distance += average * hours;
+                result.fuel += liters;
+                result.co2 += liters * fuel.co2_per_liter;
+            }
+            DynoVehicle::Electric(ev) => {
+                let (used, regenerated) = (ev.energy_used(), ev.regenerated());
+                result.distance += ev.drive(previous, speed, hours);
+                result.energy += ev.energy_used() - used - (ev.regenerated() - regenerated);
+            }
+        }
+
+        time += SAMPLE_INTERVAL;
+        let total = match vehicle {
+            DynoVehicle::Combustion(_) => result.fuel,
+            DynoVehicle::Electric(_) => result.energy,
+        };
+        result.samples.push((time, speed, total));
+    }
+    result.duration = time;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consumption;
+
+    #[test]
+    fn trace_is_followed_linearly() {
+        let trace = builtin("urban").unwrap();
+
+        assert_eq!(trace.speed_at(0.0), 0.0);
+        assert_eq!(trace.speed_at(22.5), 16.0);
+        assert_eq!(trace.speed_at(1000.0), 0.0);
+    }
+
+    #[test]
+    fn runs_repeat_exactly() {
+        let trace = builtin("highway").unwrap();
+        let measure = || {
+            let mut vehicle = DynoVehicle::Combustion(consumption::model("petrol", 15.0).unwrap());
+            run(&trace, &mut vehicle, RoadLoad::configured())
+        };
+        let (first, second) = (measure(), measure());
+
+        assert_eq!(first.fuel, second.fuel);
+        assert!(first.fuel > 0.0 && first.road_load_energy > 0.0);
+        // Stop-and-go costs more per kilometer than the highway
+        let mut vehicle = DynoVehicle::Combustion(consumption::model("petrol", 15.0).unwrap());
+        let urban = run(&builtin("urban").unwrap(), &mut vehicle, RoadLoad::configured());
+        assert!(urban.per_100km(urban.fuel) > first.per_100km(first.fuel));
+    }
+
+    #[test]
+    fn standard_cycles_match_their_published_figures() {
+        for &name in &CYCLE_NAMES[2..] {
+            let trace = cycle(name).unwrap();
+            let published = trace.published.unwrap();
+
+            assert_eq!(trace.duration(), published.duration, "{}", name);
+            assert!((trace.distance() / published.distance - 1.0).abs() < 0.02, "{}: {} km", name, trace.distance());
+            // The dyno drives the cycle's distance
+            let mut vehicle = DynoVehicle::Combustion(consumption::model("petrol", 15.0).unwrap());
+            let result = run(&trace, &mut vehicle, RoadLoad::configured());
+            assert!((result.distance - trace.distance()).abs() < 1e-6, "{}", name);
+        }
+    }
+}
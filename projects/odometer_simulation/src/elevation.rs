@@ -0,0 +1,191 @@
+use std::f64::consts::TAU;
+use std::fs;
+use std::path::PathBuf;
+
+use vehicle_sim_core::scenario::RoutePoint;
+
+// Synthetic hills unless --elevation says otherwise
+pub const DEFAULT_AMPLITUDE: f64 = 50.0; // m
+pub const DEFAULT_WAVELENGTH: f64 = 8.0; // km
+// Valley floor the synthetic hills rise from
+const BASE_ELEVATION: f64 = 200.0; // m
+// Spacing of the generated profile points
+const HILLS_SPACING: f64 = 0.2; // km
+// Shorter rises on top of the long swell, as (wavelength share, weight);
+// without them every hill would be the same
+const HARMONICS: [(f64, f64); 3] = [(1.0, 1.0), (0.37, 0.35), (0.16, 0.15)];
+
+// Where the trip's elevation profile comes from (--elevation): a CSV file of
+// distance_km,elevation_m or rolling hills of up to `amplitude` m above and
+// below the valley floor, repeating about every `wavelength` km
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElevationProfile {
+    File(PathBuf),
+    Hills { amplitude: f64, wavelength: f64 },
+}
+
+impl ElevationProfile {
+    // `hills`, `hills:<amplitude m>` or `hills:<amplitude m>:<wavelength km>`,
+    // else the path of a CSV file
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let Some(rest) = value.strip_prefix("hills") else {
+            return Ok(ElevationProfile::File(PathBuf::from(value)));
+        };
+        let mut numbers = rest.strip_prefix(':').unwrap_or(rest).split(':').filter(|part| !part.is_empty());
+        let mut number = |name: &str, default: f64| match numbers.next() {
+            Some(text) => text
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|&number| number > 0.0)
+                .ok_or_else(|| format!("invalid hills {} '{}'", name, text)),
+            None => Ok(default),
+        };
+        let amplitude = number("amplitude", DEFAULT_AMPLITUDE)?;
+        let wavelength = number("wavelength", DEFAULT_WAVELENGTH)?;
+        if numbers.next().is_some() {
+            return Err(format!("expected hills[:AMPLITUDE[:WAVELENGTH]], got '{}'", value));
+        }
+        Ok(ElevationProfile::Hills { amplitude, wavelength })
+    }
+
+    // The profile over the first `length` km of the trip; past the end of a
+    // file the road stays level
+    pub fn route(&self, length: f64) -> Result<Vec<RoutePoint>, String> {
+        match self {
+            ElevationProfile::File(path) => {
+                let text = fs::read_to_string(path).map_err(|e| format!("cannot read elevation profile {}: {}", path.display(), e))?;
+                parse_csv(&text).map_err(|e| format!("elevation profile {}: {}", path.display(), e))
+            }
+            &ElevationProfile::Hills { amplitude, wavelength } => Ok(hills(amplitude, wavelength, length)),
+        }
+    }
+}
+
+fn point(distance: f64, elevation: f64) -> RoutePoint {
+    RoutePoint {
+        distance,
+        elevation,
+        heading: None,
+        wind: None,
+    }
+}
+
+// distance_km,elevation_m per line, distances increasing; a header line and
+// # comments are skipped
+pub fn parse_csv(text: &str) -> Result<Vec<RoutePoint>, String> {
+    let mut route: Vec<RoutePoint> = Vec::new();
+    for (number, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let values: Vec<&str> = line.split(',').map(str::trim).collect();
+        let parsed = match values[..] {
+            [distance, elevation] => distance.parse::<f64>().ok().zip(elevation.parse::<f64>().ok()),
+            _ => None,
+        };
+        let Some((distance, elevation)) = parsed else {
+            if route.is_empty() && number == 1 {
+                continue;
+            }
+            return Err(format!("line {}: expected distance_km,elevation_m, got '{}'", number, line));
+        };
+        if route.last().is_some_and(|last| distance <= last.distance) {
+            return Err(format!("line {}: distance {} km does not increase", number, distance));
+        }
+        route.push(point(distance, elevation));
+    }
+    if route.len() < 2 {
+        return Err("needs at least two points".to_string());
+    }
+    Ok(route)
+}
+
+// Rolling hills over `length` km: a long swell with shorter rises on top,
+// never more than `amplitude` m from the valley floor
+pub fn hills(amplitude: f64, wavelength: f64, length: f64) -> Vec<RoutePoint> {
+    let total_weight: f64 = HARMONICS.iter().map(|&(_, weight)| weight).sum();
+    let points = (length / HILLS_SPACING).ceil().max(1.0) as usize;
+    (0..=points)
+        .map(|i| {
+            let distance = i as f64 * HILLS_SPACING;
+            let swell: f64 = HARMONICS
+                .iter()
+                .map(|&(share, weight)| weight * (TAU * distance / (wavelength * share)).sin())
+                .sum();
+            point(distance, BASE_ELEVATION + amplitude * swell / total_weight)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ev::EvOdometer;
+    use crate::odometer::Odometer;
+    use vehicle_sim_core::scenario;
+    use vehicle_sim_core::units::Speed;
+
+    #[test]
+    fn profiles_come_from_a_file_or_the_hills_generator() {
+        let route = parse_csv("distance_km,elevation_m\n0,120\n# the pass\n2.5,310\n6,180\n").unwrap();
+        assert_eq!(route.len(), 3);
+        assert_eq!(scenario::elevation_at(&route, 1.25), Some(215.0));
+        assert!(parse_csv("0,120\n0,130\n").is_err());
+        assert!(parse_csv("0,120\n1;130\n").is_err());
+        assert!(parse_csv("0,120\n").is_err());
+
+        assert_eq!(
+            ElevationProfile::parse("hills:80").unwrap(),
+            ElevationProfile::Hills {
+                amplitude: 80.0,
+                wavelength: DEFAULT_WAVELENGTH
+            }
+        );
+        assert_eq!(ElevationProfile::parse("route.csv").unwrap(), ElevationProfile::File(PathBuf::from("route.csv")));
+        assert!(ElevationProfile::parse("hills:-5").is_err());
+        assert!(ElevationProfile::parse("hills:50:8:3").is_err());
+
+        let hills = hills(50.0, 8.0, 40.0);
+        assert!(hills.last().unwrap().distance >= 40.0);
+        assert!(hills.iter().all(|point| (point.elevation - BASE_ELEVATION).abs() <= 50.0));
+        let (lowest, highest) = hills.iter().fold((f64::MAX, f64::MIN), |(low, high), point| {
+            (low.min(point.elevation), high.max(point.elevation))
+        });
+        assert!(highest - lowest > 50.0);
+        let steepest = hills.iter().map(|point| scenario::grade_at(&hills, point.distance).abs()).fold(0.0, f64::max);
+        assert!(steepest > 0.02 && steepest < 0.1, "steepest grade {}", steepest);
+    }
+
+    #[test]
+    fn a_hill_costs_more_on_the_way_up_than_it_gives_back() {
+        let (speed, hours) = (Speed::from_kmh(80.0), 0.05);
+        let fuel = |grades: &[f64]| {
+            let mut odometer = Odometer::new(15.0);
+            for &grade in grades {
+                odometer.set_grade(grade);
+                odometer.drive(speed, hours);
+            }
+            odometer.fuel_consumed().liters()
+        };
+        // Engine braking down a steep hill burns nothing
+        assert_eq!(fuel(&[-0.08]), 0.0);
+        assert!(fuel(&[0.05]) > fuel(&[0.0]));
+        assert!(fuel(&[0.05, -0.05]) > fuel(&[0.0, 0.0]));
+
+        let battery = |grades: &[f64]| {
+            let mut ev = EvOdometer::new(60.0, 0.0);
+            for &grade in grades {
+                ev.grade = grade;
+                ev.drive(80.0, 80.0, 0.05);
+            }
+            ev.battery.energy()
+        };
+        // The motor brakes the steep descent back into the pack
+        assert!(battery(&[-0.08]) > battery(&[]));
+        let flat = battery(&[0.0, 0.0]);
+        let hill = battery(&[0.05, -0.05]);
+        assert!(hill < flat);
+        assert!(flat - hill < battery(&[0.0]) - battery(&[0.05]));
+    }
+}
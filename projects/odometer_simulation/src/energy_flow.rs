@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+use crate::altitude;
+use crate::ev::{kinetic_energy, DRAG_CONSUMPTION, ROLLING_CONSUMPTION};
+use crate::wind;
+
+// Layout of the Sankey chart, in pixels
+const CHART_TOP: f64 = 40.0;
+const CHART_HEIGHT: f64 = 240.0;
+const SINK_GAP: f64 = 12.0;
+const SOURCE_X: f64 = 20.0;
+const SINK_X: f64 = 420.0;
+const BAR_WIDTH: f64 = 20.0;
+
+// Where the energy put into a trip went, in kWh. Whatever the named sinks
+// do not account for was lost in the engine (or motor) and drivetrain.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnergyFlow {
+    // Drawn from the battery rather than burned as fuel
+    pub electric: bool,
+    // km/h the drag is working against on top of the speed, set every step
+    #[serde(skip)]
+    pub headwind: f64,
+    // Rise over run of the road, set every step
+    #[serde(skip)]
+    pub grade: f64,
+    input: f64,
+    aero: f64,
+    rolling: f64,
+    braking: f64,
+    hvac: f64,
+    regenerated: f64,
+    // Potential energy gained; descents give it back and pay for some of
+    // the other sinks
+    #[serde(default)]
+    climbing: f64,
+}
+
+impl EnergyFlow {
+    // Books `input` kWh spent covering `distance` km at `speed` km/h after
+    // `previous_speed`; `hvac` of it ran the climate control and
+    // `regenerated` went back into the battery when slowing down
+    pub fn record(&mut self, input: f64, hvac: f64, regenerated: f64, previous_speed: f64, speed: f64, distance: f64) {
+        let slowing = (kinetic_energy(previous_speed) - kinetic_energy(speed)).max(0.0);
+        let drag = DRAG_CONSUMPTION * speed * speed + wind::extra_drag(speed, self.headwind);
+        self.input += input;
+        self.aero += distance * drag.max(0.0) / 1000.0;
+        self.rolling += distance * ROLLING_CONSUMPTION / 1000.0;
+        self.braking += (slowing - regenerated).max(0.0);
+        self.hvac += hvac;
+        self.regenerated += regenerated;
+        self.climbing += altitude::climbing_energy(self.grade, distance);
+    }
+
+    pub fn input(&self) -> f64 {
+        self.input
+    }
+
+    pub fn source(&self) -> &'static str {
+        if self.electric {
+            "Battery"
+        } else {
+            "Fuel"
+        }
+    }
+
+    fn losses(&self) -> f64 {
+        (self.input - self.aero - self.rolling - self.braking - self.hvac - self.regenerated - self.climbing).max(0.0)
+    }
+
+    // Sinks with their share of the input, leaving out empty ones
+    pub fn sinks(&self) -> Vec<(&'static str, f64)> {
+        let losses = if self.electric { "Drivetrain losses" } else { "Engine and drivetrain losses" };
+        [
+            ("Aerodynamic drag", self.aero),
+            ("Rolling resistance", self.rolling),
+            ("Braking", self.braking),
+            ("Climate control", self.hvac),
+            ("Climbing", self.climbing),
+            (losses, self.losses()),
+            ("Recovered by regeneration", self.regenerated),
+        ]
+        .into_iter()
+        .filter(|&(_, kwh)| kwh > 0.0)
+        .collect()
+    }
+
+    // Share of the input in percent
+    pub fn percent(&self, kwh: f64) -> f64 {
+        if self.input > 0.0 {
+            kwh / self.input * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    // Sankey-style chart: the input on the left fans out into the sinks on
+    // the right, every band as wide as its share
+    pub fn svg(&self) -> String {
+        const COLORS: [&str; 7] = ["#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#edc948", "#bab0ac", "#59a14f"];
+
+        let sinks = self.sinks();
+        let scale = if self.input > 0.0 { CHART_HEIGHT / self.input } else { 0.0 };
+        let height = CHART_TOP + CHART_HEIGHT + SINK_GAP * sinks.len().saturating_sub(1) as f64 + 20.0;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"760\" height=\"{:.0}\" font-family=\"sans-serif\" font-size=\"13\">\n",
+            height
+        );
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{:.1}\" fill=\"#555\"/>\n<text x=\"{}\" y=\"{}\">{} {:.1} kWh</text>\n",
+            SOURCE_X,
+            CHART_TOP,
+            BAR_WIDTH,
+            self.input * scale,
+            SOURCE_X,
+            CHART_TOP - 12.0,
+            self.source(),
+            self.input
+        ));
+
+        let (from_x, to_x) = (SOURCE_X + BAR_WIDTH, SINK_X);
+        let middle = (from_x + to_x) / 2.0;
+        let (mut left, mut right) = (CHART_TOP, CHART_TOP);
+        for (i, &(name, kwh)) in sinks.iter().enumerate() {
+            let width = kwh * scale;
+            let color = COLORS[i % COLORS.len()];
+            svg.push_str(&format!(
+                "<path d=\"M {fx} {l0:.1} C {m} {l0:.1}, {m} {r0:.1}, {tx} {r0:.1} L {tx} {r1:.1} C {m} {r1:.1}, {m} {l1:.1}, {fx} {l1:.1} Z\" fill=\"{c}\" fill-opacity=\"0.5\"/>\n",
+                fx = from_x,
+                tx = to_x,
+                m = middle,
+                l0 = left,
+                l1 = left + width,
+                r0 = right,
+                r1 = right + width,
+                c = color
+            ));
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{:.1}\" width=\"{}\" height=\"{:.1}\" fill=\"{}\"/>\n<text x=\"{}\" y=\"{:.1}\" dominant-baseline=\"middle\">{}: {:.1} kWh ({:.0}%)</text>\n",
+                SINK_X,
+                right,
+                BAR_WIDTH,
+                width,
+                color,
+                SINK_X + BAR_WIDTH + 8.0,
+                right + width / 2.0,
+                name,
+                kwh,
+                self.percent(kwh)
+            ));
+            left += width;
+            right += width + SINK_GAP;
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sinks_add_up_to_the_input() {
+        let mut flow = EnergyFlow {
+            electric: true,
+            ..EnergyFlow::default()
+        };
+        flow.record(20.0, 1.0, 0.0, 0.0, 100.0, 100.0);
+        flow.record(2.0, 0.1, 0.05, 100.0, 50.0, 5.0);
+
+        let total: f64 = flow.sinks().iter().map(|&(_, kwh)| kwh).sum();
+        assert!((total - flow.input()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn without_regeneration_slowing_down_ends_in_the_brakes() {
+        let mut flow = EnergyFlow::default();
+        flow.record(10.0, 0.0, 0.0, 100.0, 0.0, 0.0);
+
+        let braking = flow.sinks().into_iter().find(|&(name, _)| name == "Braking").map(|(_, kwh)| kwh);
+        assert_eq!(braking, Some(kinetic_energy(100.0)));
+        assert!(flow.svg().contains("Engine and drivetrain losses"));
+    }
+}
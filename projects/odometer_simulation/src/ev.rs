@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+
+use crate::altitude;
+use crate::wind;
+
+pub const DEFAULT_CAPACITY: f64 = 60.0; // kWh
+// Every run starts the pack at this state of charge
+pub const INITIAL_STATE_OF_CHARGE: f64 = 0.9;
+// The low battery warning comes on below this state of charge
+pub const LOW_STATE_OF_CHARGE: f64 = 0.15;
+// The driver stops at a charger below this (or when the range gets short)
+// and charges up to the target
+pub const CHARGE_BELOW: f64 = 0.1;
+pub const CHARGE_TARGET: f64 = 0.8;
+pub const CHARGER_POWER: f64 = 50.0; // kW, DC fast charger
+// Wheel power of the drive motor, which needs no air and keeps it at any
+// elevation
+pub const MOTOR_POWER: f64 = 150.0; // kW
+
+// Rolling resistance and drivetrain losses, plus aerodynamic drag growing
+// with the square of the speed
+pub const ROLLING_CONSUMPTION: f64 = 49.0; // Wh/km
+const DRIVETRAIN_CONSUMPTION: f64 = 26.0; // Wh/km
+const BASE_CONSUMPTION: f64 = ROLLING_CONSUMPTION + DRIVETRAIN_CONSUMPTION;
+pub const DRAG_CONSUMPTION: f64 = 0.0095; // Wh/km per (km/h)²
+pub const VEHICLE_MASS: f64 = 1800.0; // kg
+const DRIVE_EFFICIENCY: f64 = 0.9;
+// Share of the kinetic energy recovered when slowing down
+const REGEN_EFFICIENCY: f64 = 0.6;
+pub const JOULES_PER_KWH: f64 = 3.6e6;
+
+// Wh/km at `speed` km/h with the climate control drawing `climate_load` kW
+pub fn consumption(speed: f64, climate_load: f64) -> f64 {
+    let driving = BASE_CONSUMPTION + DRAG_CONSUMPTION * speed * speed;
+    if speed > 0.0 {
+        driving + climate_load * 1000.0 / speed
+    } else {
+        driving
+    }
+}
+
+// kWh the vehicle carries at `speed` km/h
+pub fn kinetic_energy(speed: f64) -> f64 {
+    let meters_per_second = speed / 3.6;
+    0.5 * VEHICLE_MASS * meters_per_second * meters_per_second / JOULES_PER_KWH
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChargingSession {
+    pub start_state_of_charge: f64,
+    pub end_state_of_charge: f64,
+    pub energy: f64, // kWh
+    pub hours: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Battery {
+    capacity: f64, // kWh
+    energy: f64,   // kWh
+}
+
+impl Battery {
+    pub fn new(capacity: f64, state_of_charge: f64) -> Battery {
+        Battery {
+            capacity,
+            energy: capacity * state_of_charge.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn capacity(&self) -> f64 {
+        self.capacity
+    }
+
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    pub fn state_of_charge(&self) -> f64 {
+        if self.capacity > 0.0 {
+            self.energy / self.capacity
+        } else {
+            0.0
+        }
+    }
+
+    // Draws up to `kwh`, returning what the pack could supply
+    fn discharge(&mut self, kwh: f64) -> f64 {
+        let supplied = kwh.clamp(0.0, self.energy);
+        self.energy -= supplied;
+        supplied
+    }
+
+    // Stores up to `kwh`, never beyond the capacity; returns what went in
+    fn charge(&mut self, kwh: f64) -> f64 {
+        let stored = kwh.clamp(0.0, self.capacity - self.energy);
+        self.energy += stored;
+        stored
+    }
+}
+
+// Energy counterpart of the fuel odometer for an electric vehicle: the
+// odometer still counts the distance, this tracks the pack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvOdometer {
+    pub battery: Battery,
+    // Climate control load in kW
+    pub climate_load: f64,
+    // Cabin preconditioning on top of it, set every step
+    #[serde(skip)]
+    pub preconditioning_load: f64,
+    // km/h, set from the route's wind every step
+    #[serde(skip)]
+    pub headwind: f64,
+    // Rise over run of the road, set from the route every step
+    #[serde(skip)]
+    pub grade: f64,
+    kilometers: f64,
+    energy_used: f64,  // kWh drawn from the pack
+    regenerated: f64,  // kWh recovered while slowing down
+    charging: Option<ChargingSession>,
+    pub sessions: Vec<ChargingSession>,
+}
+
+impl EvOdometer {
+    pub fn new(capacity: f64, climate_load: f64) -> EvOdometer {
+        EvOdometer {
+            battery: Battery::new(capacity, INITIAL_STATE_OF_CHARGE),
+            climate_load,
+            preconditioning_load: 0.0,
+            headwind: 0.0,
+            grade: 0.0,
+            kilometers: 0.0,
+            energy_used: 0.0,
+            regenerated: 0.0,
+            charging: None,
+            sessions: Vec::new(),
+        }
+    }
+
+    // Drives at `speed` for `hours` after `previous_speed`, crediting the
+    // energy recovered when slowing down or rolling downhill. Returns the
+    // distance covered, which ends early when the pack runs flat.
+    pub fn drive(&mut self, previous_speed: f64, speed: f64, hours: f64) -> f64 {
+        let distance = speed * hours;
+        let speed_change = kinetic_energy(speed) - kinetic_energy(previous_speed);
+        let acceleration = speed_change.max(0.0) / DRIVE_EFFICIENCY;
+        let level = distance * (consumption(speed, 0.0) + wind::extra_drag(speed, self.headwind)).max(0.0) / 1000.0;
+        // Downhill gravity keeps the car going first; what it has to spare
+        // is braked back into the pack
+        let climbing = altitude::climbing_energy(self.grade, distance);
+        let cruising = (level + climbing.max(0.0) / DRIVE_EFFICIENCY + climbing.min(0.0)).max(0.0);
+        let descent = (-climbing - level).max(0.0);
+        let regen = ((-speed_change).max(0.0) + descent) * REGEN_EFFICIENCY;
+        let needed = cruising + acceleration + self.cabin_load() * hours;
+
+        self.battery.charge(regen);
+        self.regenerated += regen;
+        let supplied = self.battery.discharge(needed);
+        self.energy_used += supplied;
+
+        let distance = if needed > 0.0 { distance * supplied / needed } else { distance };
+        self.kilometers += distance;
+        distance
+    }
+
+    pub fn is_charging(&self) -> bool {
+        self.charging.is_some()
+    }
+
+    pub fn start_charging(&mut self) {
+        self.charging = Some(ChargingSession {
+            start_state_of_charge: self.battery.state_of_charge(),
+            end_state_of_charge: self.battery.state_of_charge(),
+            energy: 0.0,
+            hours: 0.0,
+        });
+    }
+
+    // Charges for `hours` at full charger power, which a pack takes up to
+    // the target; returns the session once it reached the target. The
+    // climate control keeps running on the charger's power meanwhile.
+    pub fn charge(&mut self, hours: f64) -> Option<ChargingSession> {
+        let power = (CHARGER_POWER - self.cabin_load()).max(0.0);
+        let session = self.charging.as_mut()?;
+        let wanted = (CHARGE_TARGET * self.battery.capacity() - self.battery.energy()).max(0.0);
+        let stored = self.battery.charge((power * hours).min(wanted));
+
+        session.energy += stored;
+        session.hours += hours;
+        session.end_state_of_charge = self.battery.state_of_charge();
+        if session.end_state_of_charge >= CHARGE_TARGET - 1e-9 || stored <= 0.0 {
+            let finished = self.charging.take()?;
+            self.sessions.push(finished.clone());
+            return Some(finished);
+        }
+        None
+    }
+
+    // Climate control and preconditioning, in kW
+    pub fn cabin_load(&self) -> f64 {
+        self.climate_load + self.preconditioning_load
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.battery.state_of_charge() < LOW_STATE_OF_CHARGE
+    }
+
+    pub fn energy_used(&self) -> f64 {
+        self.energy_used
+    }
+
+    pub fn regenerated(&self) -> f64 {
+        self.regenerated
+    }
+
+    // Net energy per distance driven so far, in Wh/km
+    pub fn average_consumption(&self) -> Option<f64> {
+        (self.kilometers > 0.0).then(|| (self.energy_used - self.regenerated) / self.kilometers * 1000.0)
+    }
+
+    // Distance left at the average consumption, or at 90 km/h before any
+    pub fn range(&self) -> f64 {
+        let wh_per_km = self
+            .average_consumption()
+            .filter(|&wh| wh > 0.0)
+            .unwrap_or_else(|| consumption(90.0, self.climate_load));
+        self.battery.energy() / wh_per_km * 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faster_driving_costs_more_per_kilometer() {
+        assert!(consumption(130.0, 0.0) > consumption(90.0, 0.0));
+        // The climate control weighs most in slow traffic
+        assert!(consumption(20.0, 2.0) - consumption(20.0, 0.0) > consumption(100.0, 2.0) - consumption(100.0, 0.0));
+    }
+
+    #[test]
+    fn slowing_down_recovers_energy() {
+        let mut constant = EvOdometer::new(60.0, 0.0);
+        let mut braking = EvOdometer::new(60.0, 0.0);
+        constant.drive(60.0, 60.0, 1.0);
+        braking.drive(120.0, 60.0, 1.0);
+
+        assert!(braking.regenerated() > 0.0);
+        assert!(braking.battery.energy() > constant.battery.energy());
+    }
+
+    #[test]
+    fn a_flat_pack_ends_the_drive() {
+        let mut ev = EvOdometer::new(1.0, 0.0);
+        let distance = ev.drive(100.0, 100.0, 1.0);
+
+        assert!(distance < 100.0);
+        assert_eq!(ev.battery.energy(), 0.0);
+    }
+
+    #[test]
+    fn charging_stops_at_the_target() {
+        let mut ev = EvOdometer::new(60.0, 0.0);
+        ev.battery = Battery::new(60.0, 0.05);
+        ev.start_charging();
+
+        let mut session = None;
+        for _ in 0..10 {
+            session = session.or(ev.charge(0.5));
+        }
+        let session = session.unwrap();
+
+        assert!(!ev.is_charging());
+        assert!((session.end_state_of_charge - CHARGE_TARGET).abs() < 1e-9);
+        assert!((session.energy - 45.0).abs() < 1e-9);
+        assert_eq!(ev.sessions.len(), 1);
+    }
+}
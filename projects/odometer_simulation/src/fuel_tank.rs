@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_CAPACITY: f64 = 50.0; // l
+// The reserve warning comes on below this share of the capacity
+pub const RESERVE_FRACTION: f64 = 0.15;
+// Distance the rolling average consumption mostly reflects
+const AVERAGE_WINDOW: f64 = 100.0; // km
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuelTank {
+    capacity: f64, // l
+    level: f64,    // l
+    // Rolling average consumption in l/km, once anything was driven
+    average_consumption: Option<f64>,
+}
+
+impl Default for FuelTank {
+    fn default() -> Self {
+        FuelTank::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl FuelTank {
+    // Starts with a full tank
+    pub fn new(capacity: f64) -> FuelTank {
+        FuelTank {
+            capacity,
+            level: capacity,
+            average_consumption: None,
+        }
+    }
+
+    pub fn with_level(mut self, liters: f64) -> FuelTank {
+        self.level = liters.clamp(0.0, self.capacity);
+        self
+    }
+
+    // Draws `liters` for `distance` km; returns what the tank could supply
+    pub fn consume(&mut self, liters: f64, distance: f64) -> f64 {
+        let supplied = liters.min(self.level);
+        self.level -= supplied;
+
+        if distance > 0.0 {
+            let consumption = liters / distance;
+            let weight = (distance / AVERAGE_WINDOW).min(1.0);
+            self.average_consumption = Some(match self.average_consumption {
+                Some(average) => average + (consumption - average) * weight,
+                None => consumption,
+            });
+        }
+        supplied
+    }
+
+    // Adds up to `liters`, never beyond the capacity; returns what went in
+    pub fn refuel(&mut self, liters: f64) -> f64 {
+        let added = liters.max(0.0).min(self.capacity - self.level);
+        self.level += added;
+        added
+    }
+
+    pub fn capacity(&self) -> f64 {
+        self.capacity
+    }
+
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    pub fn fraction(&self) -> f64 {
+        if self.capacity > 0.0 {
+            self.level / self.capacity
+        } else {
+            0.0
+        }
+    }
+
+    pub fn is_in_reserve(&self) -> bool {
+        self.fraction() < RESERVE_FRACTION
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.level <= 0.0
+    }
+
+    // Distance to empty at the rolling average consumption, falling back to
+    // `default_consumption` (l/km) before anything was driven
+    pub fn range_to_empty(&self, default_consumption: f64) -> f64 {
+        let consumption = self.average_consumption.unwrap_or(default_consumption);
+        if consumption > 0.0 {
+            self.level / consumption
+        } else {
+            f64::INFINITY
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refueling_stops_at_the_capacity() {
+        let mut tank = FuelTank::new(50.0).with_level(45.0);
+
+        assert_eq!(tank.refuel(10.0), 5.0);
+        assert_eq!(tank.level(), 50.0);
+    }
+
+    #[test]
+    fn an_empty_tank_supplies_only_what_is_left() {
+        let mut tank = FuelTank::new(50.0).with_level(2.0);
+
+        assert_eq!(tank.consume(5.0, 75.0), 2.0);
+        assert!(tank.is_empty());
+    }
+
+    #[test]
+    fn reserve_starts_below_the_threshold() {
+        let mut tank = FuelTank::new(50.0).with_level(8.0);
+        assert!(!tank.is_in_reserve());
+
+        tank.consume(1.0, 15.0);
+        assert!(tank.is_in_reserve());
+    }
+
+    #[test]
+    fn range_follows_the_rolling_average() {
+        let mut tank = FuelTank::new(50.0);
+        assert_eq!(tank.range_to_empty(1.0 / 15.0), 750.0);
+
+        // 10 l/100 km for a full averaging window
+        tank.consume(10.0, 100.0);
+        assert!((tank.range_to_empty(1.0 / 15.0) - 400.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+const KM_PER_DEGREE: f64 = 111.32;
+// Where every run starts, in the centre of Munich
+const HOME: GnssPosition = GnssPosition {
+    latitude: 48.137,
+    longitude: 11.575,
+};
+// Without headings from the route the car goes round a ring road of this
+// length, so it stays near home however long the run is
+const RING_ROAD_LENGTH: f64 = 60.0; // km
+// Dead reckoning stops short of the poles
+const MAX_LATITUDE: f64 = 85.0;
+
+// The position the telematics unit reports, dead-reckoned from the distance
+// driven and the direction of travel
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GnssPosition {
+    pub latitude: f64,  // degrees
+    pub longitude: f64, // degrees
+}
+
+impl Default for GnssPosition {
+    fn default() -> Self {
+        HOME
+    }
+}
+
+impl GnssPosition {
+    // Moves `distance` km on `heading` degrees clockwise from north
+    pub fn advance(&mut self, distance: f64, heading: f64) {
+        let heading = heading.to_radians();
+        let north = distance * heading.cos();
+        let east = distance * heading.sin();
+        self.longitude += east / (KM_PER_DEGREE * self.latitude.to_radians().cos());
+        self.longitude = (self.longitude + 180.0).rem_euclid(360.0) - 180.0;
+        self.latitude = (self.latitude + north / KM_PER_DEGREE).clamp(-MAX_LATITUDE, MAX_LATITUDE);
+    }
+}
+
+// Heading `distance` km round the ring road, starting northwards
+pub fn ring_road_heading(distance: f64) -> f64 {
+    360.0 * distance.rem_euclid(RING_ROAD_LENGTH) / RING_ROAD_LENGTH
+}
@@ -0,0 +1,304 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::consumption::ConsumptionModel;
+use crate::dyno::ENGINE_EFFICIENCY;
+use crate::ev::{kinetic_energy, VEHICLE_MASS};
+use vehicle_sim_core::calendar::Date;
+
+const EARTH_RADIUS: f64 = 6_371_000.0; // m
+const GRAVITY: f64 = 9.81;
+const JOULES_PER_KWH: f64 = 3.6e6;
+// Consumption is reported per stretch of this length
+pub const SEGMENT_LENGTH: f64 = 1.0; // km
+
+// One recorded fix of a GPX track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackPoint {
+    pub latitude: f64,  // degrees
+    pub longitude: f64, // degrees
+    pub elevation: Option<f64>, // m
+    // Seconds since 1970-01-01 UTC
+    pub time: Option<f64>,
+}
+
+// The track points of a GPX file, all track segments joined in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpxTrack {
+    pub name: String,
+    pub points: Vec<TrackPoint>,
+}
+
+// One interval between consecutive track points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Leg {
+    pub start: f64,    // km along the track
+    pub distance: f64, // km
+    pub seconds: f64,
+    pub speed: f64, // km/h
+    pub climb: f64, // m, negative downhill
+    // (east, north) in km from the first point, at the leg's end
+    pub position: (f64, f64),
+}
+
+// Consumption over one stretch of the route
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SegmentConsumption {
+    pub start: f64,    // km
+    pub distance: f64, // km
+    pub seconds: f64,
+    pub climb: f64, // m
+    pub fuel: f64,  // l
+}
+
+impl SegmentConsumption {
+    pub fn average_speed(&self) -> f64 {
+        if self.seconds > 0.0 {
+            self.distance / (self.seconds / 3600.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn grade(&self) -> f64 {
+        if self.distance > 0.0 {
+            self.climb / (self.distance * 1000.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn per_100km(&self) -> f64 {
+        if self.distance > 0.0 {
+            self.fuel / self.distance * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl GpxTrack {
+    // Reads the `<trkpt lat=".." lon="..">` elements with their optional
+    // `<ele>` and `<time>`; routes and waypoints are ignored
+    pub fn parse(text: &str) -> Result<GpxTrack, String> {
+        let name = element(text.split("<trkpt").next().unwrap_or_default(), "name").unwrap_or("GPX track").trim().to_string();
+        let mut points = Vec::new();
+        for chunk in text.split("<trkpt").skip(1) {
+            let (tag, rest) = chunk.split_once('>').ok_or("unterminated <trkpt> element")?;
+            // `<trkpt .../>` has no children
+            let body = if tag.ends_with('/') { "" } else { rest.split("</trkpt>").next().unwrap_or_default() };
+            let coordinate = |name: &str| {
+                attribute(tag, name)
+                    .ok_or_else(|| format!("track point without {}", name))?
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid {} in track point", name))
+            };
+            let (latitude, longitude) = (coordinate("lat")?, coordinate("lon")?);
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                return Err(format!("track point {}, {} is off the globe", latitude, longitude));
+            }
+            let elevation = element(body, "ele")
+                .map(|value| value.trim().parse::<f64>().map_err(|_| format!("invalid elevation '{}'", value)))
+                .transpose()?;
+            let time = element(body, "time")
+                .map(|value| parse_time(value).ok_or_else(|| format!("invalid time '{}'", value)))
+                .transpose()?;
+            points.push(TrackPoint {
+                latitude,
+                longitude,
+                elevation,
+                time,
+            });
+        }
+        if points.len() < 2 {
+            return Err("a GPX track needs at least two track points".to_string());
+        }
+        Ok(GpxTrack { name, points })
+    }
+
+    pub fn load(path: &Path) -> io::Result<GpxTrack> {
+        GpxTrack::parse(&fs::read_to_string(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Speeds come from the timestamps, which must not run backwards
+    pub fn legs(&self) -> Result<Vec<Leg>, String> {
+        let origin = self.points[0];
+        let mut legs = Vec::with_capacity(self.points.len() - 1);
+        let mut start = 0.0;
+        for pair in self.points.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let (Some(t0), Some(t1)) = (from.time, to.time) else {
+                return Err("the track has points without a timestamp".to_string());
+            };
+            if t1 < t0 {
+                return Err("the track's timestamps run backwards".to_string());
+            }
+            let distance = haversine(from, to) / 1000.0;
+            let seconds = t1 - t0;
+            legs.push(Leg {
+                start,
+                distance,
+                seconds,
+                speed: if seconds > 0.0 { distance / (seconds / 3600.0) } else { 0.0 },
+                climb: match (from.elevation, to.elevation) {
+                    (Some(e0), Some(e1)) => e1 - e0,
+                    _ => 0.0,
+                },
+                position: local_position(origin, to),
+            });
+            start += distance;
+        }
+        Ok(legs)
+    }
+
+    pub fn distance(&self) -> f64 {
+        self.points.windows(2).map(|pair| haversine(pair[0], pair[1])).sum::<f64>() / 1000.0
+    }
+}
+
+// Replays the legs with `model` for driving at a steady speed plus the fuel
+// for speeding up and climbing, which nothing gets back for a combustion
+// engine. Returns the consumption per stretch of `SEGMENT_LENGTH`.
+pub fn replay(legs: &[Leg], model: &dyn ConsumptionModel) -> Vec<SegmentConsumption> {
+    let fuel = model.fuel();
+    let mut segments: Vec<SegmentConsumption> = Vec::new();
+    let mut previous_speed = 0.0;
+    for leg in legs {
+        let potential = VEHICLE_MASS * GRAVITY * leg.climb / JOULES_PER_KWH;
+        let gained = (kinetic_energy(leg.speed) - kinetic_energy(previous_speed) + potential).max(0.0);
+        previous_speed = leg.speed;
+
+        let index = (leg.start / SEGMENT_LENGTH) as usize;
+        if segments.len() <= index {
+            segments.resize_with(index + 1, SegmentConsumption::default);
+            segments[index].start = index as f64 * SEGMENT_LENGTH;
+        }
+        let segment = &mut segments[index];
+        segment.distance += leg.distance;
+        segment.seconds += leg.seconds;
+        segment.climb += leg.climb;
+        segment.fuel += model.liters(leg.speed, leg.seconds / 3600.0) + gained / ENGINE_EFFICIENCY / fuel.energy_density;
+    }
+    // Stretches the track skipped over between two fixes stay out
+    segments.retain(|segment| segment.distance > 0.0);
+    segments
+}
+
+// Text of the first `<name>...</name>` in `xml`
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", name))?;
+    Some(&xml[start..start + end])
+}
+
+// Value of `name="..."` (or single-quoted) in the attributes of a tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    tag.match_indices(name).find_map(|(i, _)| {
+        let before = tag[..i].chars().next_back();
+        let rest = tag[i + name.len()..].trim_start().strip_prefix('=')?.trim_start();
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        if before.is_some_and(|c| !c.is_whitespace()) {
+            return None;
+        }
+        rest[1..].split(quote).next()
+    })
+}
+
+// ISO 8601 UTC time as written by GPS loggers: 2024-05-04T09:30:12Z, with
+// optional fractional seconds or a numeric offset
+fn parse_time(value: &str) -> Option<f64> {
+    let (date, time) = value.trim().split_once('T')?;
+    let days = Date::new(1970, 1, 1)?.days_until(Date::parse(date)?);
+    let (time, offset) = match time.find(['Z', '+', '-']) {
+        Some(i) => (&time[..i], &time[i..]),
+        None => (time, "Z"),
+    };
+    let mut fields = time.splitn(3, ':');
+    let hours: f64 = fields.next()?.parse().ok()?;
+    let minutes: f64 = fields.next()?.parse().ok()?;
+    let seconds: f64 = fields.next()?.parse().ok()?;
+    let offset = match offset.split_at(1) {
+        ("Z", "") => 0.0,
+        (sign, zone) => {
+            let (zone_hours, zone_minutes) = zone.split_once(':').unwrap_or((zone.get(..2)?, zone.get(2..).unwrap_or("0")));
+            let offset = zone_hours.parse::<f64>().ok()? * 3600.0 + zone_minutes.parse::<f64>().ok()? * 60.0;
+            if sign == "-" {
+                -offset
+            } else {
+                offset
+            }
+        }
+    };
+    Some(days as f64 * 86_400.0 + hours * 3600.0 + minutes * 60.0 + seconds - offset)
+}
+
+// Great-circle distance in m
+fn haversine(from: TrackPoint, to: TrackPoint) -> f64 {
+    let (lat1, lat2) = (from.latitude.to_radians(), to.latitude.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (to.longitude - from.longitude).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+// (east, north) of `point` in km on a flat map centered on `origin`, close
+// enough over the length of a drive
+fn local_position(origin: TrackPoint, point: TrackPoint) -> (f64, f64) {
+    let east = (point.longitude - origin.longitude).to_radians() * origin.latitude.to_radians().cos() * EARTH_RADIUS;
+    let north = (point.latitude - origin.latitude).to_radians() * EARTH_RADIUS;
+    (east / 1000.0, north / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consumption;
+
+    const TRACK: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test">
+  <trk>
+    <name>Up the hill</name>
+    <trkseg>
+      <trkpt lat="47.0000" lon="8.0000"><ele>400</ele><time>2024-05-04T09:30:00Z</time></trkpt>
+      <trkpt lat="47.0090" lon="8.0000"><ele>420</ele><time>2024-05-04T09:30:36Z</time></trkpt>
+      <trkpt lat='47.0180' lon='8.0000'><ele>460</ele><time>2024-05-04T11:31:12+02:00</time></trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+
+    #[test]
+    fn speed_and_climb_come_from_the_track() {
+        let track = GpxTrack::parse(TRACK).unwrap();
+        let legs = track.legs().unwrap();
+
+        assert_eq!(track.name, "Up the hill");
+        assert_eq!(legs.len(), 2);
+        // 0.009 degrees of latitude are about 1 km, driven in 36 s
+        assert!((legs[0].distance - 1.0).abs() < 0.01);
+        assert!((legs[1].speed - 100.0).abs() < 1.0, "{}", legs[1].speed);
+        assert_eq!(legs[1].climb, 40.0);
+        assert!(legs[1].position.1 > 1.9 && legs[1].position.0.abs() < 1e-9);
+    }
+
+    #[test]
+    fn climbing_costs_fuel() {
+        let track = GpxTrack::parse(TRACK).unwrap();
+        let model = consumption::model("petrol", 15.0).unwrap();
+        let segments = replay(&track.legs().unwrap(), model.as_ref());
+        let level: Vec<Leg> = track.legs().unwrap().into_iter().map(|leg| Leg { climb: 0.0, ..leg }).collect();
+        let flat = replay(&level, model.as_ref());
+
+        assert_eq!(segments.len(), 2);
+        assert!((segments[1].grade() - 0.04).abs() < 0.001);
+        assert!(segments[1].fuel > flat[1].fuel);
+    }
+
+    #[test]
+    fn broken_tracks_are_rejected() {
+        assert!(GpxTrack::parse("<gpx></gpx>").is_err());
+        assert!(GpxTrack::parse(&TRACK.replace("lat=\"47.0090\"", "lat=\"north\"")).is_err());
+        let untimed = GpxTrack::parse(&TRACK.replace("<time>2024-05-04T09:30:36Z</time>", "")).unwrap();
+        assert!(untimed.legs().is_err());
+    }
+}
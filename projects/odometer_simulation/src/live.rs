@@ -0,0 +1,98 @@
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+use minifb::{Key, Window, WindowOptions};
+use vehicle_sim_core::sim_plot::Figure;
+
+const WIDTH: usize = 1280;
+const HEIGHT: usize = 480;
+const FRAMES_PER_SECOND: usize = 30;
+
+// A window showing the latest chart of the run. It lives on its own thread so
+// it keeps redrawing while the runner waits for the wall clock.
+pub struct LiveChart {
+    figures: Sender<Figure>,
+    window: JoinHandle<()>,
+}
+
+impl LiveChart {
+    pub fn open(title: &str) -> Result<Self, String> {
+        let (figures, received) = mpsc::channel::<Figure>();
+        let (opened, result) = mpsc::channel();
+        let title = title.to_string();
+
+        let window = thread::spawn(move || {
+            let options = WindowOptions {
+                resize: true,
+                ..WindowOptions::default()
+            };
+            let mut window = match Window::new(&title, WIDTH, HEIGHT, options) {
+                Ok(window) => {
+                    let _ = opened.send(Ok(()));
+                    window
+                }
+                Err(e) => {
+                    let _ = opened.send(Err(format!("Cannot open the live chart window: {}", e)));
+                    return;
+                }
+            };
+            window.set_target_fps(FRAMES_PER_SECOND);
+
+            let mut figure: Option<Figure> = None;
+            let mut frame: Vec<u32> = Vec::new();
+            let mut drawn_size = (0, 0);
+            while window.is_open() && !window.is_key_down(Key::Escape) {
+                // Only the newest chart matters; after the run the last one stays
+                let latest = received.try_iter().last();
+                let changed = latest.is_some();
+                if latest.is_some() {
+                    figure = latest;
+                }
+
+                let size = window.get_size();
+                if let Some(figure) = figure.as_ref().filter(|_| changed || size != drawn_size) {
+                    match figure.render(size.0 as u32, size.1 as u32) {
+                        Ok(rgb) => {
+                            frame = rgb
+                                .chunks_exact(3)
+                                .map(|pixel| u32::from_be_bytes([0, pixel[0], pixel[1], pixel[2]]))
+                                .collect();
+                            drawn_size = size;
+                        }
+                        Err(e) => eprintln!("Cannot draw the live chart: {}", e),
+                    }
+                }
+
+                let updated = if frame.is_empty() {
+                    window.update();
+                    Ok(())
+                } else {
+                    window.update_with_buffer(&frame, drawn_size.0, drawn_size.1)
+                };
+                if let Err(e) = updated {
+                    eprintln!("Live chart window failed: {}", e);
+                    break;
+                }
+            }
+        });
+
+        result
+            .recv()
+            .unwrap_or_else(|_| Err("The live chart window closed unexpectedly".to_string()))?;
+        Ok(LiveChart {
+            figures,
+            window,
+        })
+    }
+
+    // Replaces the chart shown; does nothing once the window is closed
+    pub fn show(&self, figure: Figure) {
+        let _ = self.figures.send(figure);
+    }
+
+    // Waits until the window is closed
+    pub fn finish(self) {
+        drop(self.figures);
+        let _ = self.window.join();
+    }
+}
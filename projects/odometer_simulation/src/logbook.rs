@@ -0,0 +1,361 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+
+use vehicle_sim_core::calendar::Date;
+
+use crate::energy_flow::EnergyFlow;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TripPurpose {
+    Business,
+    Private,
+}
+
+impl TripPurpose {
+    pub fn parse(value: &str) -> Option<TripPurpose> {
+        match value.trim().to_lowercase().as_str() {
+            "business" | "b" => Some(TripPurpose::Business),
+            "private" | "p" => Some(TripPurpose::Private),
+            _ => None,
+        }
+    }
+
+    // Scriptable via TRIP_PURPOSE, otherwise asks on an interactive terminal
+    pub fn from_env_or_prompt() -> TripPurpose {
+        if let Some(purpose) = std::env::var("TRIP_PURPOSE").ok().and_then(|v| TripPurpose::parse(&v)) {
+            return purpose;
+        }
+
+        if io::stdin().is_terminal() {
+            print!("Trip purpose [business/private] (default private): ");
+            let _ = io::stdout().flush();
+            let mut answer = String::new();
+            if io::stdin().read_line(&mut answer).is_ok() {
+                if let Some(purpose) = TripPurpose::parse(&answer) {
+                    return purpose;
+                }
+            }
+        }
+
+        TripPurpose::Private
+    }
+}
+
+impl fmt::Display for TripPurpose {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TripPurpose::Business => write!(f, "business"),
+            TripPurpose::Private => write!(f, "private"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TripEntry {
+    pub start: String,
+    pub end: String,
+    pub start_kilometers: f64,
+    pub end_kilometers: f64,
+    pub purpose: TripPurpose,
+    pub driver: String,
+    pub fuel_liters: f64,
+}
+
+// Consumption that earns an eco score of 100
+const ECO_REFERENCE_CONSUMPTION: f64 = 6.0; // liters per 100 km
+
+impl TripEntry {
+    pub fn distance(&self) -> f64 {
+        self.end_kilometers - self.start_kilometers
+    }
+
+    pub fn consumption(&self) -> f64 {
+        if self.distance() > 0.0 {
+            self.fuel_liters / self.distance() * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    // 0-100, higher is more economical
+    pub fn eco_score(&self) -> f64 {
+        if self.consumption() <= 0.0 {
+            return 100.0;
+        }
+        (100.0 * ECO_REFERENCE_CONSUMPTION / self.consumption()).clamp(0.0, 100.0)
+    }
+
+    // Date part of the "YYYY-MM-DD HH:MM" end timestamp
+    pub fn end_date(&self) -> Option<Date> {
+        Date::parse(self.end.split(' ').next()?)
+    }
+}
+
+// Persistent trip history used to produce a mileage logbook
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriverStats {
+    pub driver: String,
+    pub trips: usize,
+    pub distance: f64,
+    pub business_distance: f64,
+    pub fuel_liters: f64,
+    pub average_eco_score: f64,
+}
+
+impl DriverStats {
+    pub fn consumption(&self) -> f64 {
+        if self.distance > 0.0 {
+            self.fuel_liters / self.distance * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Logbook {
+    entries: Vec<TripEntry>,
+}
+
+const HISTORY_HEADER: &str = "start,end,start_km,end_km,purpose,driver,fuel_l";
+
+impl Logbook {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Logbook> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Logbook::default()),
+            Err(e) => return Err(e),
+        };
+
+        let entries = contents
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let fields: Vec<&str> = line.split(',').collect();
+                let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("Invalid trip history line '{}'", line));
+                // Histories written before driver attribution have 5 columns
+                let (start, end, start_km, end_km, purpose, driver, fuel) = match fields[..] {
+                    [start, end, start_km, end_km, purpose] => (start, end, start_km, end_km, purpose, "unknown", "0"),
+                    [start, end, start_km, end_km, purpose, driver, fuel] => (start, end, start_km, end_km, purpose, driver, fuel),
+                    _ => return Err(invalid()),
+                };
+
+                Ok(TripEntry {
+                    start: start.to_string(),
+                    end: end.to_string(),
+                    start_kilometers: start_km.parse().map_err(|_| invalid())?,
+                    end_kilometers: end_km.parse().map_err(|_| invalid())?,
+                    purpose: TripPurpose::parse(purpose).ok_or_else(invalid)?,
+                    driver: driver.to_string(),
+                    fuel_liters: fuel.parse().map_err(|_| invalid())?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Logbook { entries })
+    }
+
+    // The trip history file, saved together with the odometer state
+    pub fn contents(&self) -> String {
+        let mut contents = format!("{}\n", HISTORY_HEADER);
+        for entry in &self.entries {
+            contents.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                entry.start,
+                entry.end,
+                entry.start_kilometers,
+                entry.end_kilometers,
+                entry.purpose,
+                entry.driver,
+                entry.fuel_liters
+            ));
+        }
+        contents
+    }
+
+    pub fn add(&mut self, entry: TripEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn last(&self) -> Option<&TripEntry> {
+        self.entries.last()
+    }
+
+    pub fn total_distance(&self, purpose: TripPurpose) -> f64 {
+        self.entries
+            .iter()
+            .filter(|entry| entry.purpose == purpose)
+            .fold(0.0, |total, entry| total + entry.distance())
+    }
+
+    // Per-driver summary, in order of first appearance
+    pub fn driver_stats(&self) -> Vec<DriverStats> {
+        let mut stats: Vec<DriverStats> = Vec::new();
+
+        for entry in &self.entries {
+            let index = match stats.iter().position(|s| s.driver == entry.driver) {
+                Some(index) => index,
+                None => {
+                    stats.push(DriverStats {
+                        driver: entry.driver.clone(),
+                        trips: 0,
+                        distance: 0.0,
+                        business_distance: 0.0,
+                        fuel_liters: 0.0,
+                        average_eco_score: 0.0,
+                    });
+                    stats.len() - 1
+                }
+            };
+
+            let driver = &mut stats[index];
+            driver.average_eco_score =
+                (driver.average_eco_score * driver.trips as f64 + entry.eco_score()) / (driver.trips + 1) as f64;
+            driver.trips += 1;
+            driver.distance += entry.distance();
+            driver.fuel_liters += entry.fuel_liters;
+            if entry.purpose == TripPurpose::Business {
+                driver.business_distance += entry.distance();
+            }
+        }
+
+        stats
+    }
+
+    pub fn export_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut contents = String::from("Trip;Start;End;Odometer start (km);Odometer end (km);Distance (km);Purpose;Driver\n");
+        for (i, entry) in self.entries.iter().enumerate() {
+            contents.push_str(&format!(
+                "{};{};{};{:.1};{:.1};{:.1};{};{}\n",
+                i + 1,
+                entry.start,
+                entry.end,
+                entry.start_kilometers,
+                entry.end_kilometers,
+                entry.distance(),
+                entry.purpose,
+                entry.driver
+            ));
+        }
+        fs::write(path, contents)
+    }
+
+    // Plain HTML table with print styles, so it can be saved as PDF from a
+    // browser, followed by where the energy of the last trip went
+    pub fn export_html(&self, path: impl AsRef<Path>, trip_energy: Option<&EnergyFlow>) -> io::Result<()> {
+        let mut rows = String::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            rows.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td><td>{:.1}</td><td>{:.1}</td><td>{}</td><td>{}</td></tr>\n",
+                i + 1,
+                entry.start,
+                entry.end,
+                entry.start_kilometers,
+                entry.end_kilometers,
+                entry.distance(),
+                entry.purpose,
+                entry.driver
+            ));
+        }
+
+        let energy = match trip_energy.filter(|flow| flow.input() > 0.0) {
+            Some(flow) => format!("<h2>Energy flow of the last trip</h2>\n{}", flow.svg()),
+            None => String::new(),
+        };
+
+        let html = format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Mileage Logbook</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th, td {{ border: 1px solid #999; padding: 4px 8px; text-align: right; }}
+th {{ background: #eee; }}
+@media print {{ body {{ margin: 0; }} tr {{ page-break-inside: avoid; }} }}
+</style>
+</head>
+<body>
+<h1>Mileage Logbook</h1>
+<table>
+<tr><th>Trip</th><th>Start</th><th>End</th><th>Odometer start (km)</th><th>Odometer end (km)</th><th>Distance (km)</th><th>Purpose</th><th>Driver</th></tr>
+{}</table>
+<p>Business: {:.1} km &middot; Private: {:.1} km</p>
+{}</body>
+</html>
+"#,
+            rows,
+            self.total_distance(TripPurpose::Business),
+            self.total_distance(TripPurpose::Private),
+            energy
+        );
+
+        fs::write(path, html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(name: &str, contents: &str) -> io::Result<Logbook> {
+        let path = std::env::temp_dir().join(format!("{}_{}.csv", name, std::process::id()));
+        fs::write(&path, contents)?;
+        let logbook = Logbook::load(&path);
+        fs::remove_file(&path)?;
+        logbook
+    }
+
+    fn trip(start_kilometers: f64, end_kilometers: f64, purpose: TripPurpose, driver: &str) -> TripEntry {
+        TripEntry {
+            start: "2024-03-01 08:00".to_string(),
+            end: "2024-03-01 08:45".to_string(),
+            start_kilometers,
+            end_kilometers,
+            purpose,
+            driver: driver.to_string(),
+            fuel_liters: 2.5,
+        }
+    }
+
+    #[test]
+    fn the_history_survives_a_round_trip() {
+        let mut logbook = Logbook::default();
+        logbook.add(trip(1000.0, 1042.5, TripPurpose::Business, "alice"));
+        logbook.add(trip(1042.5, 1050.25, TripPurpose::Private, "bob"));
+
+        let loaded = history("logbook_round_trip", &logbook.contents()).unwrap();
+        assert_eq!(loaded.entries, logbook.entries);
+        assert_eq!(loaded.total_distance(TripPurpose::Business), 42.5);
+        assert_eq!(loaded.total_distance(TripPurpose::Private), 7.75);
+    }
+
+    #[test]
+    fn older_histories_and_malformed_rows() {
+        let old = history("logbook_old", "start,end,start_km,end_km,purpose\na,b,10,20,business\n\n").unwrap();
+        assert_eq!(old.last().map(|entry| (entry.driver.as_str(), entry.fuel_liters)), Some(("unknown", 0.0)));
+
+        for row in ["", "a", "a,b,10,20", "a,b,10,20,private,alice", "a,b,ten,20,private", "a,b,10,20,holiday", "a,b,10,20,private,alice,lots"] {
+            let contents = format!("{}\n{}\n", HISTORY_HEADER, row);
+            let error = history("logbook_malformed", &contents).map(|_| ()).err();
+            // Blank lines are skipped, everything else is rejected
+            assert_eq!(error.map(|e| e.kind()), (!row.is_empty()).then_some(io::ErrorKind::InvalidData), "{:?}", row);
+        }
+        assert!(Logbook::load(std::env::temp_dir().join(format!("logbook_missing_{}.csv", std::process::id()))).unwrap().last().is_none());
+    }
+
+    #[test]
+    fn purposes_parse_in_full_or_by_initial() {
+        for (value, purpose) in [("business", TripPurpose::Business), (" B\n", TripPurpose::Business), ("Private", TripPurpose::Private), ("p", TripPurpose::Private)] {
+            assert_eq!(TripPurpose::parse(value), Some(purpose), "{:?}", value);
+            assert_eq!(TripPurpose::parse(&purpose.to_string()), Some(purpose));
+        }
+        assert_eq!(TripPurpose::parse("holiday"), None);
+        assert_eq!(TripPurpose::parse(""), None);
+    }
+}
@@ -1,114 +1,892 @@
+mod altitude;
+mod cli;
+mod coast_down;
+mod consumption;
+mod dyno;
+mod elevation;
+mod energy_flow;
+mod ev;
+mod fuel_tank;
+mod gnss;
+mod gpx;
+#[cfg(feature = "live")]
+mod live;
+mod logbook;
+mod mass_estimation;
+mod obd;
 mod odometer;
-use odometer::Odometer;
-use rand::Rng;
-use plotters::prelude::*;
+mod persistence;
+mod remote_control;
+mod restriction_mode;
+mod simulation;
+mod trip_computer;
+mod wind;
+use clap::Parser;
+use cli::{Cli, Command};
+use coast_down::{CoastDown, RoadLoad};
+use consumption::Comparison;
+use dyno::{DynoResult, DynoVehicle};
+use energy_flow::EnergyFlow;
+use ev::EvOdometer;
+use gpx::{GpxTrack, Leg, SegmentConsumption};
+use logbook::{Logbook, TripEntry, TripPurpose};
+use mass_estimation::MassRun;
+use odometer::{Odometer, OdometerSnapshot};
+use persistence::MileageRecord;
+use simulation::DrivingSimulation;
 use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+use vehicle_sim_core::calendar::{AnnualReminder, Calendar, Date};
+use vehicle_sim_core::can_trace::{self, CanTrace};
+use vehicle_sim_core::csv_export::{write_csv, CsvOptions};
+use vehicle_sim_core::drive_cycle::{CyclePlayback, DriveCycle};
+use vehicle_sim_core::driver::{self, DriverProfile};
+use vehicle_sim_core::locale;
+use vehicle_sim_core::obd2::ObdResponder;
+use vehicle_sim_core::privacy::{PrivacyFilter, PrivacyPolicy};
+use vehicle_sim_core::remote::{self, RemoteChannel, TokenStore};
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::scenario::{self, RoutePoint, Scenario};
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::sim_plot::{Figure, Panel, PlotSeries, RGBColor, Theme, BLUE, GREEN, MAGENTA, RED};
+use vehicle_sim_core::simulation::{FixedStepRunner, RunSummary};
+use vehicle_sim_core::snapshot::Snapshot;
+use vehicle_sim_core::storage::{self, Recovery, Transaction};
+use vehicle_sim_core::telematics::{self, TelematicsUnit};
+use vehicle_sim_core::units::{hours_to_seconds, seconds_to_hours};
+use vehicle_sim_core::xcp::{self, XcpServer};
+
+const STATE_PATH: &str = "odometer_state.txt";
+const RECORD_PATH: &str = "odometer_record.txt";
+const TRIP_HISTORY_PATH: &str = "trip_history.csv";
+const TELEMATICS_UPLINK_PATH: &str = "telematics_uplink.jsonl";
+const REMOTE_AUDIT_PATH: &str = "remote_audit.jsonl";
+// State, mileage record and trip history are saved together through this
+// journal, so a power cut never leaves them out of step
+const JOURNAL_PATH: &str = "odometer.journal";
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut rng = rand::thread_rng();
+    let mut cli = Cli::parse();
+    sim_log::init(&cli.log_options())?;
+
+    // `--scenario <path>` or SIM_SCENARIO sets the run length, step, seed,
+    // fuel efficiency, consumption model, start date and route profile
+    let scenario = cli.scenario.clone().or_else(scenario::path_from_args).map(|path| Scenario::load(&path)).transpose()?;
+    if let Some(scenario) = &scenario {
+        scenario.announce(&["fuel_efficiency", "consumption", "date", "step", "power_loss"], false, false, false, true);
+        cli.apply_scenario(scenario);
+    }
+    cli.validate()?;
+
+    match storage::recover(JOURNAL_PATH)? {
+        Recovery::Clean => {}
+        Recovery::Replayed(files) => println!("Finished saving {} files interrupted by a power cut", files),
+        Recovery::Discarded => println!("The last save was cut short by a power loss, continuing from the one before"),
+    }
+
+    // `odometer_simulation stats [--driver <fob>]` summarizes the trip history
+    if let Some(Command::Stats { driver }) = &cli.command {
+        print_driver_stats(&Logbook::load(TRIP_HISTORY_PATH)?, driver.as_deref());
+        return Ok(());
+    }
+
+    // `odometer_simulation coast-down` checks the road load model
+    if let Some(Command::CoastDown) = &cli.command {
+        let configured = RoadLoad::configured();
+        let mut rng = SimRng::from_seed_env_or(cli.seed, scenario.as_ref().and_then(|s| s.seed));
+        let coast_down = coast_down::run(configured, &mut rng);
+        print_coast_down(&coast_down, configured);
+        if !coast_down.is_consistent(configured) {
+            return Err("fitted road load deviates from the configured one".into());
+        }
+        return Ok(());
+    }
+
+    // `odometer_simulation mass-estimate` follows the mass through loading
+    // events and charts what the estimate does to stopping distance and range
+    if let Some(Command::MassEstimate { output }) = &cli.command {
+        let mut rng = SimRng::from_seed_env_or(cli.seed, scenario.as_ref().and_then(|s| s.seed));
+        let run = mass_estimation::run(RoadLoad::configured(), &mut rng);
+        print_mass_estimation(&run);
+        mass_chart(cli.plot_theme.unwrap_or_else(Theme::from_env), &run).save(output)?;
+        println!("Chart written to {}", output.display());
+        return Ok(());
+    }
+
+    // `odometer_simulation gpx <track.gpx>` replays a recorded route
+    if let Some(Command::Gpx { track, output }) = &cli.command {
+        let track = GpxTrack::load(track).map_err(|e| format!("cannot read GPX track {}: {}", track.display(), e))?;
+        let legs = track.legs()?;
+        let name = cli.consumption.as_deref().unwrap_or("constant");
+        let model = consumption::model(name, cli.fuel_efficiency()).ok_or("unknown consumption model")?;
+        let segments = gpx::replay(&legs, model.as_ref());
+        print_gpx_replay(&track, model.name(), &segments);
+        gpx_chart(cli.plot_theme.unwrap_or_else(Theme::from_env), &legs, &segments).save(output)?;
+        println!("Chart written to {}", output.display());
+        return Ok(());
+    }
+
+    // `odometer_simulation [--ev] dyno --cycle <name|path>` measures the
+    // consumption over a fixed speed trace
+    if let Some(Command::Dyno { cycle }) = &cli.command {
+        let trace = dyno::cycle(cycle)
+            .map_err(|e| format!("cannot read cycle {} (built-in: {}): {}", cycle, dyno::CYCLE_NAMES.join(", "), e))?;
+        let mut vehicle = if cli.ev {
+            DynoVehicle::Electric(EvOdometer::new(cli.battery_capacity, cli.climate_load))
+        } else {
+            let name = cli.consumption.as_deref().unwrap_or("constant");
+            DynoVehicle::Combustion(consumption::model(name, cli.fuel_efficiency()).ok_or("unknown consumption model")?)
+        };
+        let result = dyno::run(&trace, &mut vehicle, RoadLoad::configured());
+        print_dyno_result(&trace, &vehicle, &result);
 
-    let mut odometer = Odometer::new(15.0);
+        let csv_options = CsvOptions {
+            path: cli.csv.clone(),
+            delimiter: cli.csv_delimiter,
+            precision: cli.csv_precision,
+        };
+        let time: Vec<f64> = result.samples.iter().map(|&(t, _, _)| t).collect();
+        let speed: Vec<f64> = result.samples.iter().map(|&(_, v, _)| v).collect();
+        let total: Vec<f64> = result.samples.iter().map(|&(_, _, total)| total).collect();
+        let total_column = if cli.ev { "energy_kwh" } else { "fuel_l" };
+        write_csv(&csv_options, &[("time_s", &time), ("speed_kmh", &speed), (total_column, &total)])?;
+        println!("Time series written to {}", csv_options.path.display());
+        return Ok(());
+    }
+
+    if let Some(path) = &cli.describe {
+        obd::describe(cli.fuel_efficiency()).save(path)?;
+        println!("Description written to {}", path.display());
+        return Ok(());
+    }
+
+    let driver = driver::profile_for(cli.driver.as_deref());
+    println!("Driver: {} ({})", driver.name, driver.key_fob_id);
+
+    let mut record = MileageRecord::load(RECORD_PATH)?;
+    let mut logbook = Logbook::load(TRIP_HISTORY_PATH)?;
+
+    // `--resume` continues an interrupted trip, random stream included
+    let (start, mut simulation) = match &cli.resume {
+        Some(path) => {
+            let snapshot = Snapshot::<DrivingSimulation>::load(path)?;
+            println!("Resuming from {} after {} steps", path.display(), snapshot.steps);
+            (snapshot.summary(), snapshot.state)
+        }
+        None => {
+            // Continue from the last saved state; the mileage record guards against rollback
+            let snapshot = OdometerSnapshot::load(STATE_PATH)?.unwrap_or_default();
+            if snapshot.total_kilometers < record.highest_kilometers() {
+                println!(
+                    "Saved state ({}) is older than the mileage record ({}), keeping the record.",
+                    locale::current().distance(snapshot.total_kilometers, 2),
+                    locale::current().distance(record.highest_kilometers(), 2)
+                );
+            }
+            let odometer = Odometer::restore(snapshot, cli.fuel_efficiency(), &mut record);
+
+            // Each run is one trip; the calendar continues the day after the
+            // last logged trip unless the scenario sets the date
+            let scenario_date = match scenario.as_ref().and_then(|s| s.initial_str("date")) {
+                Some(date) => Some(Date::parse(date).ok_or_else(|| format!("invalid scenario date {}, expected YYYY-MM-DD", date))?),
+                None => None,
+            };
+            let start_date = scenario_date.unwrap_or_else(|| {
+                logbook
+                    .last()
+                    .and_then(TripEntry::end_date)
+                    .map_or(Date::new(2024, 3, 1).unwrap(), |date| date.add_days(1))
+            });
+            let calendar = Calendar::new(start_date);
 
-    let total_hours = 24.0;
-    let step = 0.5; // Every 30 minutes
-    let mut hours_passed = 0.0;
+            let inspection = AnnualReminder::new("Annual inspection", Date::new(2023, 3, 20).unwrap(), 30);
+            // Seed with --seed <n>, SIM_SEED or the scenario's seed to reproduce a run
+            let rng = SimRng::from_seed_env_or(cli.seed, scenario.as_ref().and_then(|s| s.seed));
+            let simulation = DrivingSimulation::new(odometer, calendar, inspection, rng, cli.speed_range);
+            (RunSummary::default(), simulation)
+        }
+    };
+
+    // `--ev` drives on a battery; a resumed run keeps the one it saved
+    if cli.ev && simulation.ev.is_none() {
+        simulation.ev = Some(EvOdometer::new(cli.battery_capacity, cli.climate_load));
+    }
+    // Likewise a resumed run keeps its driver
+    if let Some(profile) = cli.speed_profile.filter(|_| simulation.driver.is_none()) {
+        simulation.set_speed_profile(profile);
+    }
+    if let Some(cycle) = cli.drive_cycle.clone().filter(|_| simulation.cycle.is_none()) {
+        simulation.set_drive_cycle(cycle);
+    }
+    // A restricted driver's key fob puts their profile on at every start
+    if let Some(profile) = driver.restriction {
+        let change = simulation.restrictions.set(Some(profile), &format!("key {}", driver.key_fob_id))?;
+        println!("Key {} drives with {}", driver.key_fob_id, change);
+    }
+    simulation.energy.electric = simulation.ev.is_some();
+    simulation.trips.electric = simulation.ev.is_some();
+    for &trip in &cli.reset_trip {
+        simulation.trips.reset(trip);
+    }
+    simulation.climate_load = cli.climate_load;
+    // Models are not part of the checkpoint, so a resumed run picks them up again
+    simulation.odometer.set_consumption(cli.consumption_model());
+    simulation.comparisons = cli.compared_models().into_iter().map(Comparison::new).collect();
+    simulation.route = match &cli.elevation {
+        // Long enough for the whole run at top speed
+        Some(profile) => profile.route(cli.hours() * simulation.max_speed())?,
+        None => scenario.as_ref().map_or_else(Vec::new, |scenario| scenario.route.clone()),
+    };
+
+    if let Some(path) = cli.can_trace.clone().or_else(can_trace::path_from_args) {
+        simulation.can.record_to(CanTrace::create(&path)?);
+    }
+    if let Some(address) = &cli.obd {
+        let responder = Arc::new(ObdResponder::new());
+        responder.update(simulation.obd_data());
+        println!("OBD-II (ELM327) interface on {}", responder.serve(address)?);
+        simulation.obd = Some(responder);
+    }
+    if let Some(address) = cli.xcp.clone().or_else(xcp::address_from_args) {
+        let server = XcpServer::start(&address, &obd::describe(cli.fuel_efficiency()))?;
+        server.watch_bus(&simulation.can);
+        println!("XCP on UDP {}", server.local_addr()?);
+        simulation.xcp = Some(server);
+    }
+    #[cfg(feature = "mqtt")]
+    if let Some(broker) = cli.mqtt.clone().or_else(vehicle_sim_core::mqtt::broker_from_args) {
+        let options = vehicle_sim_core::mqtt::MqttOptions::from_env(broker, "odometer");
+        let publisher = vehicle_sim_core::mqtt::MqttPublisher::connect(&options)
+            .map_err(|e| format!("cannot connect to MQTT broker {}: {}", options.broker, e))?;
+        println!("Publishing telemetry to MQTT broker {} under {}/", options.broker, options.topic_prefix);
+        simulation.mqtt = Some(publisher);
+    }
+    if let Some(path) = &cli.privacy {
+        let policy = PrivacyPolicy::load(path).map_err(|e| format!("cannot read privacy settings {}: {}", path.display(), e))?;
+        simulation.privacy = Some(PrivacyFilter::new(policy));
+    }
+    if let Some(schedule) = &cli.telematics {
+        let mut unit = TelematicsUnit::new(schedule.clone(), cli.telematics_buffer, telematics::DEFAULT_UPLINK_RATE)
+            .with_uplink(Path::new(TELEMATICS_UPLINK_PATH))?;
+        if let Some(compression) = &cli.telematics_deadband {
+            unit = unit.with_compression(compression.clone());
+        }
+        simulation.telematics = Some(unit);
+    }
+    if let (Some(commands), Some(tokens)) = (&cli.remote_commands, &cli.remote_tokens) {
+        let tokens = TokenStore::load(tokens).map_err(|e| format!("cannot read remote tokens {}: {}", tokens.display(), e))?;
+        let requests = remote::load_requests(commands).map_err(|e| format!("cannot read remote commands {}: {}", commands.display(), e))?;
+        simulation.remote = Some(RemoteChannel::new(tokens, requests).with_audit(Path::new(REMOTE_AUDIT_PATH))?);
+    }
+
+    let total_hours = cli.hours();
+    let step = cli.step();
+    let theme = cli.plot_theme.unwrap_or_else(Theme::from_env);
+
+    // `--live` shows the chart in a window that follows the run
+    #[cfg(feature = "live")]
+    let live = cli.live.then(|| live::LiveChart::open("Odometer Simulation")).transpose()?;
 
     let mut time_data = vec![];
     let mut distance_data = vec![];
     let mut trip_data = vec![];
     let mut fuel_data = vec![];
+    let mut tank_data = vec![];
+    let mut soc_data = vec![];
 
-    while hours_passed < total_hours {
-        let speed: f64 = rng.gen_range(40.0..120.0); // Random speed between 40 and 120 km/h
-        odometer.drive(speed, step);
+    // The elevation panel follows the route as far as it was driven
+    #[cfg(feature = "live")]
+    let (route, route_start) = (simulation.route.clone(), simulation.trip_start.kilometers);
 
-        hours_passed += step;
-        time_data.push(hours_passed);
-        distance_data.push(odometer.total_kilometers());
-        trip_data.push(odometer.trip_meter());
-        fuel_data.push(odometer.fuel_consumed());
+    let mut runner = FixedStepRunner::new(hours_to_seconds(step))
+        .with_max_steps((total_hours / step).round() as u64)
+        .with_realtime_factor(cli.realtime_factor())
+        .resume_from(start)
+        .quiet();
+    if let Some(path) = &cli.save {
+        runner = runner.with_checkpoint(path.clone());
+    }
+    runner.run_with(&mut simulation, |state, _| {
+        time_data.push(state.hours_passed);
+        distance_data.push(state.readings.total_kilometers);
+        trip_data.push(state.readings.trip_meter);
+        fuel_data.push(state.readings.fuel_consumed);
+        tank_data.push(state.readings.fuel_level.unwrap_or(0.0));
+        soc_data.push(state.state_of_charge.unwrap_or(0.0) * 100.0);
+        #[cfg(feature = "live")]
+        if let Some(live) = &live {
+            let driven = distance_data.last().map_or(0.0, |&km| km - route_start);
+            live.show(chart(theme, total_hours, &time_data, &distance_data, &trip_data, &fuel_data, (&route, driven)));
+        }
+    });
+    #[cfg(feature = "live")]
+    if let Some(live) = live {
+        println!("Close the chart window (or press Esc) to continue");
+        live.finish();
     }
 
     // Use the `display_kilometers` method to show the final readings
-    odometer.display_kilometers();
+    let today = simulation.calendar.date();
+    println!(
+        "{} ({:?}, {:?})",
+        locale::current().timestamp(today, simulation.calendar.hour_of_day()),
+        today.weekday(),
+        today.season()
+    );
+    display_readings(&simulation.odometer, simulation.ev.as_ref());
+    if let Some(ev) = &simulation.ev {
+        print_charging_sessions(ev);
+    }
+    print_comparisons(&simulation.comparisons);
+    println!("{}", simulation.trips.summary());
+    print_energy_flow(&simulation.energy);
+    print_route(&simulation.route, simulation.ev.is_some());
+    simulation.flush_privacy();
+    if let Some(telematics) = &mut simulation.telematics {
+        telematics.flush()?;
+        telematics.print_summary();
+        println!("Uploaded telemetry written to {}", TELEMATICS_UPLINK_PATH);
+    }
+    if let Some(privacy) = &simulation.privacy {
+        privacy.print_summary();
+    }
+    if let Some(remote) = &mut simulation.remote {
+        remote.flush()?;
+        remote.print_summary();
+        println!("Remote command audit log written to {}", REMOTE_AUDIT_PATH);
+    }
+    simulation.restrictions.print_report();
+    if let Some(playback) = &simulation.cycle {
+        print_drive_cycle(playback, &simulation);
+    }
+    let odometer = &mut simulation.odometer;
+
+    let trip_start = &simulation.trip_start;
+    logbook.add(TripEntry {
+        start: trip_start.timestamp.clone(),
+        end: simulation.calendar.timestamp(),
+        start_kilometers: trip_start.kilometers,
+        end_kilometers: odometer.total_distance().km(),
+        purpose: TripPurpose::from_env_or_prompt(),
+        driver: driver.key_fob_id.clone(),
+        fuel_liters: odometer.fuel_consumed().liters() - trip_start.fuel_liters,
+    });
+    logbook.export_csv("logbook.csv")?;
+    logbook.export_html("logbook.html", Some(&simulation.energy))?;
+    println!("Logbook exported to logbook.csv and logbook.html");
 
     // Reset the trip meter at the end (this is just an example of using the method)
     odometer.reset_trip_meter();
     println!("Trip meter has been reset.");
-    odometer.display_kilometers();
+    display_readings(odometer, simulation.ev.as_ref());
+
+    record.update(odometer.total_distance().km());
+    let mut save = Transaction::new(JOURNAL_PATH);
+    save.write(STATE_PATH, odometer.snapshot().contents());
+    save.write(RECORD_PATH, record.contents());
+    save.write(TRIP_HISTORY_PATH, logbook.contents());
+    // `--power-loss` cuts the power in the middle of it
+    if let Some(bytes) = cli.power_loss {
+        storage::cut_power_after(bytes);
+    }
+    save.commit().map_err(|e| format!("cannot save the odometer state: {}", e))?;
 
-    plot_data(&time_data, &distance_data, &trip_data, &fuel_data)?;
+    let driven = simulation.odometer.total_distance().km() - simulation.trip_start.kilometers;
+    chart(theme, total_hours, &time_data, &distance_data, &trip_data, &fuel_data, (&simulation.route, driven)).save(&cli.output)?;
+
+    let csv_options = CsvOptions {
+        path: cli.csv.clone(),
+        delimiter: cli.csv_delimiter,
+        precision: cli.csv_precision,
+    };
+    // Each column with the telemetry signal whose privacy policy it follows
+    let mut recorded: Vec<(&str, &str, &[f64])> = vec![
+        ("total_km", "odometer", &distance_data),
+        ("trip_km", "trip", &trip_data),
+        ("fuel_l", "fuel_consumed", &fuel_data),
+        ("tank_l", "fuel_level", &tank_data),
+    ];
+    if simulation.ev.is_some() {
+        recorded.push(("soc_pct", "state_of_charge", &soc_data));
+    }
+    let seconds: Vec<f64> = time_data.iter().map(|&hours| hours_to_seconds(hours)).collect();
+    let (times, filtered) = match &mut simulation.privacy {
+        Some(privacy) => {
+            let signals: Vec<(&str, &[f64])> = recorded.iter().map(|&(_, signal, values)| (signal, values)).collect();
+            let times = seconds.iter().map(|&time| seconds_to_hours(privacy.timestamp(time))).collect();
+            (times, privacy.apply_columns(&seconds, &signals))
+        }
+        None => (time_data.clone(), recorded.iter().map(|&(_, _, values)| Some(values.to_vec())).collect()),
+    };
+    let mut columns: Vec<(&str, &[f64])> = vec![("time_h", &times)];
+    for (&(column, _, _), values) in recorded.iter().zip(&filtered) {
+        if let Some(values) = values {
+            columns.push((column, values));
+        }
+    }
+    write_csv(&csv_options, &columns)?;
+    println!("Time series written to {}", csv_options.path.display());
 
     Ok(())
 }
 
+// Electric vehicles show the battery instead of the fuel readings
+fn display_readings(odometer: &Odometer, ev: Option<&EvOdometer>) {
+    match ev {
+        Some(ev) => {
+            odometer.display_distances();
+            print_battery(ev);
+        }
+        None => odometer.display_kilometers(),
+    }
+}
+
+fn print_battery(ev: &EvOdometer) {
+    let locale = locale::current();
+    println!(
+        "Battery: {}% ({} of {} kWh) | Used: {} kWh | Regenerated: {} kWh | Average: {} Wh/km | Range: {}",
+        locale.number(ev.battery.state_of_charge() * 100.0, 1),
+        locale.number(ev.battery.energy(), 1),
+        locale.number(ev.battery.capacity(), 0),
+        locale.number(ev.energy_used(), 2),
+        locale.number(ev.regenerated(), 2),
+        locale.number(ev.average_consumption().unwrap_or(0.0), 0),
+        locale.distance(ev.range(), 0)
+    );
+}
+
+fn print_charging_sessions(ev: &EvOdometer) {
+    let locale = locale::current();
+    for session in &ev.sessions {
+        println!(
+            "  Charged {} kWh in {} h ({}% -> {}%)",
+            locale.number(session.energy, 1),
+            locale.number(session.hours, 1),
+            locale.number(session.start_state_of_charge * 100.0, 0),
+            locale.number(session.end_state_of_charge * 100.0, 0)
+        );
+    }
+}
+
+fn print_comparisons(comparisons: &[Comparison]) {
+    if comparisons.is_empty() {
+        return;
+    }
+    let locale = locale::current();
+    println!("Compared over the same drive:");
+    for comparison in comparisons {
+        println!(
+            "  {:<8} {} ({} l/100 km)",
+            comparison.model.name(),
+            locale.volume(comparison.liters, 2),
+            locale.number(comparison.liters_per_100km(), 2)
+        );
+    }
+}
+
+fn print_energy_flow(flow: &EnergyFlow) {
+    if flow.input() <= 0.0 {
+        return;
+    }
+    let locale = locale::current();
+    println!("Energy flow ({} kWh from {}):", locale.number(flow.input(), 2), flow.source().to_lowercase());
+    for (name, kwh) in flow.sinks() {
+        println!(
+            "  {:<28} {} kWh ({}%)",
+            name,
+            locale.number(kwh, 2),
+            locale.number(flow.percent(kwh), 0)
+        );
+    }
+}
+
+// How the thinner air at the summit and on the steepest climb held the
+// vehicle back
+fn print_route(route: &[RoutePoint], electric: bool) {
+    let (Some(start), Some(summit)) = (route.first(), route.iter().max_by(|a, b| a.elevation.total_cmp(&b.elevation))) else {
+        return;
+    };
+    let locale = locale::current();
+    println!(
+        "Route: {} m -> summit {} m at {} (air density {}% of sea level)",
+        locale.number(start.elevation, 0),
+        locale.number(summit.elevation, 0),
+        locale.distance(summit.distance, 1),
+        locale.number(altitude::density_ratio(summit.elevation) * 100.0, 0)
+    );
+    let power = |elevation: f64| if electric { ev::MOTOR_POWER } else { altitude::engine_power(elevation) };
+    if electric {
+        println!("Motor power: {} kW at any elevation", locale.number(ev::MOTOR_POWER, 0));
+    } else {
+        println!(
+            "Engine power: {} kW at sea level, {} kW at the summit",
+            locale.number(altitude::ENGINE_POWER, 1),
+            locale.number(power(summit.elevation), 1)
+        );
+    }
+    if let Some(climb) = altitude::steepest_climb(route) {
+        let load = RoadLoad::configured();
+        println!(
+            "Steepest climb: {}% from {}, top speed {} at {} m ({} at sea level)",
+            locale.number(climb.grade * 100.0, 1),
+            locale.distance(climb.distance, 1),
+            locale.speed(altitude::hill_climb_speed(power(climb.elevation), load.at_elevation(climb.elevation), climb.grade, 0.0), 0),
+            locale.number(climb.elevation, 0),
+            locale.speed(altitude::hill_climb_speed(power(0.0), load, climb.grade, 0.0), 0)
+        );
+    }
+    for point in route {
+        if let Some(wind) = point.wind {
+            let (headwind, crosswind) = wind.components(scenario::heading_at(route, point.distance));
+            println!(
+                "Wind from {}: {} m/s from {}°, gusts to {} m/s ({} m/s {}, {} m/s crosswind)",
+                locale.distance(point.distance, 1),
+                locale.number(wind.speed, 1),
+                locale.number(wind.direction, 0),
+                locale.number(wind.speed + wind.gusts, 1),
+                locale.number(headwind.abs(), 1),
+                if headwind < 0.0 { "tailwind" } else { "headwind" },
+                locale.number(crosswind.abs(), 1)
+            );
+        }
+    }
+}
+
+// Consumption over whole laps of a standard cycle compares with the
+// cycle's published figures
+fn print_drive_cycle(playback: &CyclePlayback, simulation: &DrivingSimulation) {
+    let locale = locale::current();
+    let cycle = &playback.cycle;
+    let published = cycle
+        .published
+        .map_or(String::new(), |published| format!(", published {}", locale.distance(published.distance, 2)));
+    println!(
+        "Drive cycle {}: {} laps of {} s, {} per lap{}",
+        cycle.name,
+        playback.laps,
+        locale.number(cycle.duration(), 0),
+        locale.distance(cycle.distance(), 2),
+        published
+    );
+    let distance = simulation.odometer.total_distance().km() - simulation.trip_start.kilometers;
+    if simulation.ev.is_none() && distance > 0.0 {
+        let fuel = simulation.odometer.fuel_consumed().liters() - simulation.trip_start.fuel_liters;
+        println!(
+            "Consumption over the cycle: {} l/100 km",
+            locale.number(fuel / distance * 100.0, 2)
+        );
+    }
+}
+
+fn print_dyno_result(trace: &DriveCycle, vehicle: &DynoVehicle, result: &DynoResult) {
+    let locale = locale::current();
+    println!(
+        "Dyno cycle {}: {} s, {}, average {}",
+        trace.name,
+        locale.number(result.duration, 0),
+        locale.distance(result.distance, 2),
+        locale.speed(result.average_speed(), 1)
+    );
+    if let Some(published) = trace.published {
+        println!(
+            "Published: {} s, {}",
+            locale.number(published.duration, 0),
+            locale.distance(published.distance, 2)
+        );
+    }
+    println!("Road load absorbed by the rollers: {} kWh", locale.number(result.road_load_energy, 3));
+    match vehicle {
+        DynoVehicle::Combustion(model) => println!(
+            "Fuel ({}): {} ({} l/100 km), CO2 {} g/km",
+            model.name(),
+            locale.volume(result.fuel, 3),
+            locale.number(result.per_100km(result.fuel), 2),
+            locale.number(result.co2_per_km(), 0)
+        ),
+        DynoVehicle::Electric(_) => println!(
+            "Battery: {} kWh ({} kWh/100 km)",
+            locale.number(result.energy, 3),
+            locale.number(result.per_100km(result.energy), 2)
+        ),
+    }
+}
+
+fn print_coast_down(coast_down: &CoastDown, configured: RoadLoad) {
+    let locale = locale::current();
+    let (rolling_error, drag_error) = coast_down.errors(configured);
+    println!(
+        "Coast-down {} -> {} in {} s ({} samples)",
+        locale.speed(coast_down::START_SPEED, 0),
+        locale.speed(coast_down::END_SPEED, 0),
+        locale.number(coast_down.duration(), 0),
+        coast_down.samples.len()
+    );
+    println!("{:<24} {:>12} {:>12} {:>8}", "", "configured", "fitted", "error");
+    println!(
+        "{:<24} {:>12} {:>12} {:>7}%",
+        "Rolling resistance (N)",
+        locale.number(configured.rolling, 1),
+        locale.number(coast_down.fitted.rolling, 1),
+        locale.number(rolling_error * 100.0, 1)
+    );
+    println!(
+        "{:<24} {:>12} {:>12} {:>7}%",
+        "Drag (N per (km/h)²)",
+        locale.number(configured.drag, 4),
+        locale.number(coast_down.fitted.drag, 4),
+        locale.number(drag_error * 100.0, 1)
+    );
+    if coast_down.is_consistent(configured) {
+        println!("Model consistent within {}%", locale.number(coast_down::TOLERANCE * 100.0, 0));
+    }
+}
+
+fn print_gpx_replay(track: &GpxTrack, model: &str, segments: &[SegmentConsumption]) {
+    let locale = locale::current();
+    let seconds: f64 = segments.iter().map(|segment| segment.seconds).sum();
+    let fuel: f64 = segments.iter().map(|segment| segment.fuel).sum();
+    println!(
+        "GPX track {}: {} track points, {} in {} min",
+        track.name,
+        track.points.len(),
+        locale.distance(track.distance(), 2),
+        locale.number(seconds / 60.0, 0)
+    );
+    println!("{:>10} {:>12} {:>10} {:>8} {:>10} {:>10}", "from", "average", "time (s)", "grade", "fuel", "l/100 km");
+    for segment in segments {
+        println!(
+            "{:>10} {:>12} {:>10} {:>7}% {:>10} {:>10}",
+            locale.distance(segment.start, 0),
+            locale.speed(segment.average_speed(), 0),
+            locale.number(segment.seconds, 0),
+            locale.number(segment.grade() * 100.0, 1),
+            locale.volume(segment.fuel, 3),
+            locale.number(segment.per_100km(), 1)
+        );
+    }
+    let distance = track.distance();
+    println!(
+        "Fuel ({}): {} ({} l/100 km)",
+        model,
+        locale.volume(fuel, 2),
+        locale.number(if distance > 0.0 { fuel / distance * 100.0 } else { 0.0 }, 2)
+    );
+}
+
+// Speed bands the route map is colored in, up to the given km/h
+const SPEED_BANDS: [(f64, RGBColor); 4] = [(30.0, BLUE), (60.0, GREEN), (90.0, MAGENTA), (f64::INFINITY, RED)];
 
-fn plot_data(
+fn speed_band(speed: f64) -> usize {
+    SPEED_BANDS.iter().position(|&(limit, _)| speed < limit).unwrap_or(SPEED_BANDS.len() - 1)
+}
+
+fn speed_band_label(band: usize) -> String {
+    let locale = locale::current();
+    match band {
+        0 => format!("below {}", locale.speed(SPEED_BANDS[0].0, 0)),
+        _ if band == SPEED_BANDS.len() - 1 => format!("above {}", locale.speed(SPEED_BANDS[band - 1].0, 0)),
+        _ => format!("{} to {}", locale.speed(SPEED_BANDS[band - 1].0, 0), locale.speed(SPEED_BANDS[band].0, 0)),
+    }
+}
+
+// The route from above, one stretch per run of legs in the same speed
+// band, next to the consumption along the way
+fn gpx_chart(theme: Theme, legs: &[Leg], segments: &[SegmentConsumption]) -> Figure {
+    let locale = locale::current();
+    let mut map = Panel::new("Route by Speed").with_axes("East (km)", "North (km)").with_label_decimals(1, 1);
+    let mut labelled = [false; SPEED_BANDS.len()];
+    let mut run: Vec<(f64, f64)> = vec![(0.0, 0.0)];
+    let mut band = legs.first().map_or(0, |leg| speed_band(leg.speed));
+    for (i, leg) in legs.iter().enumerate() {
+        run.push(leg.position);
+        let next = legs.get(i + 1).map(|next| speed_band(next.speed));
+        if next != Some(band) {
+            let label = if labelled[band] { String::new() } else { speed_band_label(band) };
+            labelled[band] = true;
+            map = map.with_series(PlotSeries::new(label, run.drain(..)).with_color(SPEED_BANDS[band].1));
+            run.push(leg.position);
+            band = next.unwrap_or(band);
+        }
+    }
+
+    let consumption = segments
+        .iter()
+        .flat_map(|segment| {
+            let value = segment.per_100km();
+            [(segment.start, value), (segment.start + segment.distance, value)]
+        })
+        .map(|(distance, value)| (locale.distance_value(distance), value));
+    let profile = Panel::new("Consumption along the Route")
+        .with_axes(format!("Distance ({})", locale.distance_unit()), "l/100 km")
+        .from_zero()
+        .with_series(PlotSeries::new("Fuel", consumption).with_color(RED));
+
+    Figure::new().with_theme(theme).with_panel(map).with_panel(profile)
+}
+
+fn print_mass_estimation(run: &MassRun) {
+    let locale = locale::current();
+    println!(
+        "Mass estimate over {} laps of the urban cycle, starting from the curb mass of {} kg ({} samples used)",
+        locale.number(mass_estimation::LAPS as f64, 0),
+        locale.number(ev::VEHICLE_MASS, 0),
+        run.samples_used
+    );
+    let actual_at = |time: f64| run.actual.iter().rfind(|&&(t, _)| t <= time).map_or(ev::VEHICLE_MASS, |&(_, mass)| mass);
+    for (&(time, change), settled) in mass_estimation::LOADING_EVENTS.iter().zip(run.convergence_times()) {
+        let settled = match settled {
+            Some(seconds) => format!("within {}% after {} s", locale.number(mass_estimation::TOLERANCE * 100.0, 0), locale.number(seconds, 0)),
+            None => "did not converge".to_string(),
+        };
+        println!(
+            "  {:>5} s: {:>+5} kg to {} kg, {}",
+            locale.number(time, 0),
+            change,
+            locale.number(actual_at(time + 1.0), 0),
+            settled
+        );
+    }
+
+    let actual = run.actual.last().map_or(ev::VEHICLE_MASS, |&(_, mass)| mass);
+    let estimated = run.estimated.last().map_or(ev::VEHICLE_MASS, |&(_, mass)| mass);
+    println!("{:<12} {:>10} {:>18} {:>12}", "", "mass (kg)", "stopping distance", "range");
+    for (name, mass) in [("Nominal", ev::VEHICLE_MASS), ("Estimated", estimated), ("Actual", actual)] {
+        println!(
+            "{:<12} {:>10} {:>18} {:>12}",
+            name,
+            locale.number(mass, 0),
+            locale.length(mass_estimation::stopping_distance(mass, mass_estimation::REFERENCE_SPEED), 1),
+            locale.distance(mass_estimation::range(mass), 0)
+        );
+    }
+    println!("Final estimate off by {}%", locale.number(run.final_error() * 100.0, 1));
+}
+
+fn print_driver_stats(logbook: &Logbook, key_fob_id: Option<&str>) {
+    let stats: Vec<_> = logbook
+        .driver_stats()
+        .into_iter()
+        .filter(|stats| key_fob_id.is_none_or(|id| stats.driver.eq_ignore_ascii_case(id)))
+        .collect();
+
+    if stats.is_empty() {
+        println!("No trips recorded{}.", key_fob_id.map_or(String::new(), |id| format!(" for driver {}", id)));
+        return;
+    }
+
+    let locale = locale::current();
+    for stats in stats {
+        let profile = driver::find_profile(&stats.driver);
+        println!("Driver {} ({})", stats.driver, profile.as_ref().map_or("unknown", |p: &DriverProfile| p.name.as_str()));
+        println!("  Trips: {}", stats.trips);
+        println!(
+            "  Distance: {} ({} business)",
+            locale.distance(stats.distance, 1),
+            locale.distance(stats.business_distance, 1)
+        );
+        println!(
+            "  Fuel: {} ({} l/100 km)",
+            locale.volume(stats.fuel_liters, 2),
+            locale.number(stats.consumption(), 2)
+        );
+        println!("  Average eco score: {:.0}/100", stats.average_eco_score);
+        if let Some(profile) = profile {
+            println!("  Preferred cabin temperature: {}", locale.temperature(profile.preferred_temperature, 1));
+        }
+    }
+}
+
+// Readings over time, and the elevation profile of the route (if the run
+// has one) over the `driven` km of it
+fn chart(
+    theme: Theme,
+    total_hours: f64,
     time_data: &[f64],
     distance_data: &[f64],
     trip_data: &[f64],
     fuel_data: &[f64],
-) -> Result<(), Box<dyn Error>> {
-    let root = BitMapBackend::new("odometer_simulation.png", (1280, 480)).into_drawing_area();
-    root.fill(&WHITE)?;
-
-    let areas = root.split_evenly((1, 3)); // Split into a 1x3 grid
-
-    // Plot for Total Distance
-    let mut chart1 = ChartBuilder::on(&areas[0])
-        .caption("Total Distance (km) Over Time", ("sans-serif", 25))
-        .margin(10)
-        .x_label_area_size(30)
-        .y_label_area_size(40)
-        .build_cartesian_2d(0.0..24.0, 0.0..distance_data.last().cloned().unwrap_or(0.0) + 10.0)?;
-
-    chart1.configure_mesh().draw()?;
-
-    chart1.draw_series(LineSeries::new(
-        time_data.iter().zip(distance_data.iter()).map(|(&x, &y)| (x, y)),
-        &RED,
-    ))?
-    .label("Total Distance (km)")
-    .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], &RED));
-
-    // Plot for Trip Distance
-    let mut chart2 = ChartBuilder::on(&areas[1])
-        .caption("Trip Distance (km) Over Time", ("sans-serif", 25))
-        .margin(10)
-        .x_label_area_size(30)
-        .y_label_area_size(40)
-        .build_cartesian_2d(0.0..24.0, 0.0..trip_data.last().cloned().unwrap_or(0.0) + 10.0)?;
-
-    chart2.configure_mesh().draw()?;
-
-    chart2.draw_series(LineSeries::new(
-        time_data.iter().zip(trip_data.iter()).map(|(&x, &y)| (x, y)),
-        &BLUE,
-    ))?
-    .label("Trip Distance (km)")
-    .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], &BLUE));
-
-    // Plot for Fuel Consumed
-    let mut chart3 = ChartBuilder::on(&areas[2])
-        .caption("Fuel Consumed (liters) Over Time", ("sans-serif", 25))
-        .margin(10)
-        .x_label_area_size(30)
-        .y_label_area_size(40)
-        .build_cartesian_2d(0.0..24.0, 0.0..fuel_data.last().cloned().unwrap_or(0.0) + 1.0)?;
-
-    chart3.configure_mesh().draw()?;
-
-    chart3.draw_series(LineSeries::new(
-        time_data.iter().zip(fuel_data.iter()).map(|(&x, &y)| (x, y)),
-        &GREEN,
-    ))?
-    .label("Fuel Consumed (liters)")
-    .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], &GREEN));
-
-    // Display the series labels (legends)
-    chart1.configure_series_labels().background_style(&WHITE.mix(0.8)).draw()?;
-    chart2.configure_series_labels().background_style(&WHITE.mix(0.8)).draw()?;
-    chart3.configure_series_labels().background_style(&WHITE.mix(0.8)).draw()?;
+    (route, driven): (&[RoutePoint], f64),
+) -> Figure {
+    // Values are converted to the units of the active locale
+    let locale = locale::current();
+    let distance_unit = locale.distance_unit();
+    let volume_unit = locale.volume_unit();
+    let over_time = |data: &[f64], convert: &dyn Fn(f64) -> f64| {
+        time_data.iter().zip(data).map(|(&x, &y)| (x, convert(y))).collect::<Vec<_>>()
+    };
+    let panel = |title: String| {
+        Panel::new(title)
+            .with_x_range(0.0, total_hours)
+            .from_zero()
+            .with_label_decimals(0, 1)
+    };
 
-    Ok(())
+    let distance = over_time(distance_data, &|km| locale.distance_value(km));
+    let trip = over_time(trip_data, &|km| locale.distance_value(km));
+    let fuel = over_time(fuel_data, &|liters| locale.volume_value(liters));
+
+    let figure = Figure::new()
+        .with_theme(theme)
+        .with_panel(
+            panel(format!("Total Distance ({}) Over Time", distance_unit))
+                .with_series(PlotSeries::new(format!("Total Distance ({})", distance_unit), distance).with_color(RED)),
+        )
+        .with_panel(
+            panel(format!("Trip Distance ({}) Over Time", distance_unit))
+                .with_series(PlotSeries::new(format!("Trip Distance ({})", distance_unit), trip).with_color(BLUE)),
+        )
+        .with_panel(
+            panel(format!("Fuel Consumed ({}) Over Time", volume_unit))
+                .with_series(PlotSeries::new(format!("Fuel Consumed ({})", volume_unit), fuel).with_color(GREEN)),
+        );
+    let (Some(start), Some(end)) = (scenario::elevation_at(route, 0.0), scenario::elevation_at(route, driven)) else {
+        return figure;
+    };
+
+    let profile = std::iter::once((0.0, start))
+        .chain(route.iter().filter(|point| point.distance > 0.0 && point.distance < driven).map(|point| (point.distance, point.elevation)))
+        .chain((driven > 0.0).then_some((driven, end)))
+        .map(|(km, elevation)| (locale.distance_value(km), elevation))
+        .collect::<Vec<_>>();
+    // Two by two, so the profile gets a panel as wide as the readings
+    figure.with_columns(2).with_panel(
+        Panel::new("Elevation (m) Along the Route")
+            .with_axes(format!("Trip Distance ({})", distance_unit), "Elevation (m)")
+            .with_x_range(0.0, locale.distance_value(driven).max(1.0))
+            .with_label_decimals(0, 0)
+            .with_series(PlotSeries::new("Elevation (m)", profile).with_color(MAGENTA)),
+    )
+}
+
+// Estimated against actual mass, and the stopping distance and range each
+// implies next to those of the nominal mass
+fn mass_chart(theme: Theme, run: &MassRun) -> Figure {
+    let locale = locale::current();
+    let end_time = run.actual.last().map_or(1.0, |&(time, _)| time);
+    let derived = |masses: &[(f64, f64)], value: &dyn Fn(f64) -> f64| masses.iter().map(|&(time, mass)| (time, value(mass))).collect::<Vec<_>>();
+    let nominal = derived(&run.actual, &|_| ev::VEHICLE_MASS);
+    let panel = |title: String, unit: String, value: &dyn Fn(f64) -> f64| {
+        Panel::new(title)
+            .with_axes("Time (s)", unit)
+            .with_x_range(0.0, end_time)
+            .with_series(PlotSeries::new("Actual", derived(&run.actual, value)).foreground())
+            .with_series(PlotSeries::new("Estimated", derived(&run.estimated, value)).with_color(RED))
+            .with_series(PlotSeries::new("Nominal", derived(&nominal, value)).with_color(BLUE))
+    };
+
+    let reference_speed = locale.speed(mass_estimation::REFERENCE_SPEED, 0);
+    Figure::new()
+        .with_theme(theme)
+        .with_panel(panel("Vehicle Mass".to_string(), "kg".to_string(), &|mass| mass).with_label_decimals(0, 0))
+        .with_panel(panel(
+            format!("Stopping Distance from {}", reference_speed),
+            "m".to_string(),
+            &|mass| mass_estimation::stopping_distance(mass, mass_estimation::REFERENCE_SPEED),
+        ))
+        .with_panel(panel(
+            format!("Range ({})", locale.distance_unit()),
+            locale.distance_unit().to_string(),
+            &|mass| locale.distance_value(mass_estimation::range(mass)),
+        ))
 }
@@ -0,0 +1,207 @@
+use rand::Rng;
+use vehicle_sim_core::rng::SimRng;
+
+use crate::coast_down::RoadLoad;
+use crate::dyno;
+use crate::ev::{self, ROLLING_CONSUMPTION, VEHICLE_MASS};
+
+const GRAVITY: f64 = 9.81;
+const DT: f64 = 0.1; // s
+const SAMPLE_INTERVAL: f64 = 1.0; // s
+// Laps of the urban cycle driven, with the loading events at its stops
+pub const LAPS: usize = 3;
+// Passengers getting in, luggage loaded and a delivery dropped off, as
+// (s, kg); all happen while the car stands at a light
+pub const LOADING_EVENTS: &[(f64, f64)] = &[(80.0, 300.0), (555.0, 250.0), (1005.0, -400.0)];
+
+// Only clear acceleration under drive torque excites the mass: braking
+// mixes in the friction brakes, whose force is not measured
+const MIN_ACCELERATION: f64 = 0.3; // m/s²
+const MIN_SPEED: f64 = 5.0; // km/h
+// Old samples fade out with a memory of about 1 / (1 - FORGETTING) samples
+const FORGETTING: f64 = 0.995;
+// Variance of the estimate at the start and after a stop long enough to
+// load the car, in kg²
+const INITIAL_VARIANCE: f64 = 1.0e6;
+const LOADING_STOP: f64 = 10.0; // s
+// Drive force from the motor torque map, and the acceleration sensor
+const FORCE_NOISE: f64 = 0.03; // share of the force
+const ACCELERATION_NOISE: f64 = 0.05; // m/s²
+
+// An estimate within this share of the actual mass counts as converged
+pub const TOLERANCE: f64 = 0.05;
+
+// Stopping distances and range are compared at this speed
+pub const REFERENCE_SPEED: f64 = 100.0; // km/h
+const REACTION_TIME: f64 = 1.0; // s
+// The tires transfer at most this deceleration on a dry road; the brakes
+// reach it up to about the curb mass and fall short of it when loaded
+const GRIP_DECELERATION: f64 = 0.9 * GRAVITY; // m/s²
+const MAX_BRAKE_FORCE: f64 = 16000.0; // N
+const RANGE_SPEED: f64 = 90.0; // km/h
+
+// Recursive least squares fit of the drive force against the acceleration.
+// Rolling resistance grows with the mass, so the regressor is the
+// acceleration plus the rolling resistance per kg and the measured force
+// only loses the drag.
+#[derive(Debug, Clone)]
+pub struct MassEstimator {
+    pub mass: f64, // kg
+    variance: f64,
+}
+
+impl MassEstimator {
+    pub fn new(initial_mass: f64) -> Self {
+        MassEstimator {
+            mass: initial_mass,
+            variance: INITIAL_VARIANCE,
+        }
+    }
+
+    // Feeds the drive force in N and the acceleration in m/s² at `speed` km/h;
+    // returns whether the sample was used
+    pub fn update(&mut self, force: f64, acceleration: f64, speed: f64, load: RoadLoad) -> bool {
+        if acceleration < MIN_ACCELERATION || speed < MIN_SPEED {
+            return false;
+        }
+        let regressor = acceleration + load.rolling / VEHICLE_MASS;
+        let measured = force - load.drag * speed * speed;
+        let gain = self.variance * regressor / (FORGETTING + regressor * self.variance * regressor);
+        self.mass += gain * (measured - regressor * self.mass);
+        self.variance = (1.0 - gain * regressor) * self.variance / FORGETTING;
+        true
+    }
+
+    // The next drive may carry a different load; keeps the estimate but lets
+    // new samples move it quickly
+    pub fn reopen(&mut self) {
+        self.variance = self.variance.max(INITIAL_VARIANCE);
+    }
+}
+
+// Dry-road stop from `speed` km/h, limited by the tires or by the brakes
+pub fn stopping_distance(mass: f64, speed: f64) -> f64 {
+    let deceleration = GRIP_DECELERATION.min(MAX_BRAKE_FORCE / mass);
+    let meters_per_second = speed / 3.6;
+    meters_per_second * REACTION_TIME + meters_per_second * meters_per_second / (2.0 * deceleration)
+}
+
+// Range at RANGE_SPEED on a freshly charged pack; rolling resistance is
+// the part of the consumption that grows with the load
+pub fn range(mass: f64) -> f64 {
+    let energy = ev::DEFAULT_CAPACITY * ev::INITIAL_STATE_OF_CHARGE * 1000.0; // Wh
+    let wh_per_km = ev::consumption(RANGE_SPEED, 0.0) + ROLLING_CONSUMPTION * (mass / VEHICLE_MASS - 1.0);
+    energy / wh_per_km
+}
+
+#[derive(Debug, Default)]
+pub struct MassRun {
+    // (s, kg) every SAMPLE_INTERVAL
+    pub actual: Vec<(f64, f64)>,
+    pub estimated: Vec<(f64, f64)>,
+    pub samples_used: usize,
+}
+
+impl MassRun {
+    // Seconds after each loading event until the estimate stayed within the
+    // tolerance up to the next event, `None` if it never settled
+    pub fn convergence_times(&self) -> Vec<Option<f64>> {
+        let starts = LOADING_EVENTS.iter().map(|&(time, _)| time);
+        let ends = LOADING_EVENTS.iter().skip(1).map(|&(time, _)| time).chain([f64::INFINITY]);
+        starts
+            .zip(ends)
+            .map(|(start, end)| {
+                let mut settled = None;
+                for (&(time, actual), &(_, estimated)) in self.actual.iter().zip(&self.estimated) {
+                    if time < start || time >= end {
+                        continue;
+                    }
+                    if ((estimated - actual) / actual).abs() <= TOLERANCE {
+                        settled = settled.or(Some(time - start));
+                    } else {
+                        settled = None;
+                    }
+                }
+                settled
+            })
+            .collect()
+    }
+
+    pub fn final_error(&self) -> f64 {
+        match (self.actual.last(), self.estimated.last()) {
+            (Some(&(_, actual)), Some(&(_, estimated))) => (estimated - actual) / actual,
+            _ => 0.0,
+        }
+    }
+}
+
+// Drives laps of the urban cycle with the curb mass as the first guess,
+// loading and unloading the car at the stops. The drive controller knows
+// the actual mass; the estimator only sees the noisy drive force and
+// acceleration.
+pub fn run(load: RoadLoad, rng: &mut SimRng) -> MassRun {
+    let trace = dyno::builtin("urban").expect("built-in cycle");
+    let lap = trace.duration();
+    let speed_at = |time: f64| trace.speed_at(time % lap);
+
+    let mut run = MassRun::default();
+    let mut estimator = MassEstimator::new(VEHICLE_MASS);
+    let mut actual = VEHICLE_MASS;
+    let mut events = LOADING_EVENTS.iter().peekable();
+    let mut standing = 0.0;
+
+    let steps = (lap * LAPS as f64 / DT).round() as u64;
+    let sample_every = (SAMPLE_INTERVAL / DT).round() as u64;
+    for step in 0..steps {
+        let time = step as f64 * DT;
+        while let Some(&&(at, change)) = events.peek() {
+            if at > time {
+                break;
+            }
+            actual += change;
+            events.next();
+        }
+        if step % sample_every == 0 {
+            run.actual.push((time, actual));
+            run.estimated.push((time, estimator.mass));
+        }
+
+        let speed = speed_at(time);
+        let acceleration = (speed_at(time + DT) - speed) / 3.6 / DT;
+        if speed < MIN_SPEED {
+            standing += DT;
+            if standing >= LOADING_STOP {
+                estimator.reopen();
+            }
+            continue;
+        }
+        standing = 0.0;
+
+        let rolling = load.rolling * actual / VEHICLE_MASS;
+        let force = actual * acceleration + rolling + load.drag * speed * speed;
+        let measured_force = force * (1.0 + rng.gen_range(-FORCE_NOISE..=FORCE_NOISE));
+        let measured_acceleration = acceleration + rng.gen_range(-ACCELERATION_NOISE..=ACCELERATION_NOISE);
+        if estimator.update(measured_force, measured_acceleration, speed, load) {
+            run.samples_used += 1;
+        }
+    }
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_follows_the_loading_events() {
+        let run = run(RoadLoad::configured(), &mut SimRng::from_seed(1));
+
+        assert!(run.samples_used > 0);
+        assert!(run.convergence_times().iter().all(|time| time.is_some()), "{:?}", run.convergence_times());
+        assert!(run.final_error().abs() <= TOLERANCE);
+
+        // A loaded car stops later and reaches less far
+        assert!(stopping_distance(VEHICLE_MASS + 550.0, REFERENCE_SPEED) > stopping_distance(VEHICLE_MASS, REFERENCE_SPEED));
+        assert!(range(VEHICLE_MASS + 550.0) < range(VEHICLE_MASS));
+    }
+}
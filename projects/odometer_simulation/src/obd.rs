@@ -0,0 +1,204 @@
+use vehicle_sim_core::description::{Address, Characteristic, DataType, Description, Measurement};
+use vehicle_sim_core::obd2::{self, ObdData};
+use vehicle_sim_core::privacy;
+use vehicle_sim_core::sim_log;
+
+use crate::simulation::DrivingSimulation;
+
+const IDLE_RPM: f64 = 800.0;
+// Rolling circumference of a 205/55 R16 tire
+const WHEEL_CIRCUMFERENCE: f64 = 1.99; // m
+const FINAL_DRIVE: f64 = 3.9;
+const GEAR_RATIOS: [f64; 6] = [3.6, 2.1, 1.4, 1.0, 0.8, 0.65];
+// The driver shifts up as soon as the next gear keeps the engine above this
+const MIN_CRUISE_RPM: f64 = 1400.0;
+
+// The model has no engine, so the RPM follows from the speed and the gear a
+// driver would pick for it
+pub fn estimate_engine_rpm(speed: f64) -> f64 {
+    let wheel_rpm = speed * 1000.0 / 60.0 / WHEEL_CIRCUMFERENCE;
+    let rpm = GEAR_RATIOS
+        .iter()
+        .rev()
+        .map(|ratio| wheel_rpm * ratio * FINAL_DRIVE)
+        .find(|rpm| *rpm >= MIN_CRUISE_RPM)
+        .unwrap_or(wheel_rpm * GEAR_RATIOS[0] * FINAL_DRIVE);
+    rpm.max(IDLE_RPM)
+}
+
+// What `--describe` lists: the cluster message and the OBD-II PIDs
+pub fn describe(fuel_efficiency: f64) -> Description {
+    let pid = |pid| Address::ObdPid { pid };
+    Description::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .with_can_messages("Cluster")
+        .with_measurement(Measurement::new("EngineSpeed", pid(obd2::ENGINE_RPM), DataType::Uword, 0.25, "rpm"))
+        .with_measurement(Measurement::new("ObdVehicleSpeed", pid(obd2::VEHICLE_SPEED), DataType::Ubyte, 1.0, "km/h"))
+        .with_measurement(Measurement::new("FuelLevel", pid(obd2::FUEL_LEVEL), DataType::Ubyte, 100.0 / 255.0, "%"))
+        .with_measurement(Measurement::new(
+            "DistanceSinceCodesCleared",
+            pid(obd2::DISTANCE_SINCE_CODES_CLEARED),
+            DataType::Uword,
+            1.0,
+            "km",
+        ))
+        .with_measurement(Measurement::new("ObdOdometer", pid(obd2::ODOMETER), DataType::Ulong, 0.1, "km"))
+        .with_characteristic(Characteristic::number("fuel_efficiency", fuel_efficiency, "km/l", 1.0, 50.0))
+}
+
+impl DrivingSimulation {
+    pub fn obd_data(&self) -> ObdData {
+        ObdData {
+            vehicle_speed: self.speed,
+            engine_rpm: estimate_engine_rpm(self.speed),
+            // Electric vehicles report the state of charge instead
+            fuel_level: self
+                .ev
+                .as_ref()
+                .map_or(self.odometer.tank().fraction(), |ev| ev.battery.state_of_charge()),
+            distance_since_codes_cleared: self.odometer.distance_since_codes_cleared().km(),
+            odometer: self.odometer.total_distance().km(),
+        }
+    }
+
+    // Fuel efficiency written by a calibration tool over XCP
+    pub fn apply_xcp_writes(&mut self) {
+        let Some(xcp) = &self.xcp else {
+            return;
+        };
+        for (name, value) in xcp.take_writes() {
+            if name == "fuel_efficiency" {
+                self.odometer.set_fuel_efficiency(value);
+                sim_log::info("xcp", &format!("Calibrated over XCP: {} = {}", name, value));
+            }
+        }
+    }
+
+    // The values behind the PIDs of `describe`
+    pub fn publish_xcp(&self) {
+        let Some(xcp) = &self.xcp else {
+            return;
+        };
+        let data = self.obd_data();
+        xcp.publish(&[
+            ("EngineSpeed", data.engine_rpm),
+            ("ObdVehicleSpeed", data.vehicle_speed),
+            ("FuelLevel", data.fuel_level * 100.0),
+            ("DistanceSinceCodesCleared", data.distance_since_codes_cleared),
+            ("ObdOdometer", data.odometer),
+        ]);
+    }
+
+    // Signals sent to the MQTT broker and the telematics backend
+    pub fn telemetry_signals(&self) -> Vec<(&'static str, f64)> {
+        let data = self.obd_data();
+        let mut signals = vec![
+            ("speed", data.vehicle_speed),
+            ("engine_rpm", data.engine_rpm),
+            ("fuel_level", data.fuel_level * 100.0),
+            ("odometer", data.odometer),
+            ("trip", self.odometer.trip_meter().km()),
+            ("fuel_consumed", self.odometer.fuel_consumed().liters()),
+            ("latitude", self.position.latitude),
+            ("longitude", self.position.longitude),
+        ];
+        if let Some(ev) = &self.ev {
+            signals.push(("state_of_charge", ev.battery.state_of_charge() * 100.0));
+            signals.push(("range", ev.range()));
+        }
+        signals
+    }
+
+    // The telemetry of a step at `time` s as it may leave the vehicle
+    pub fn exported_signals(&mut self, time: f64) -> Vec<(&'static str, f64)> {
+        let signals = self.telemetry_signals();
+        privacy::filtered(self.privacy.as_mut(), time, signals)
+    }
+
+    #[cfg(feature = "mqtt")]
+    pub fn publish_mqtt(&self, signals: &[(&str, f64)]) {
+        let Some(mqtt) = &self.mqtt else {
+            return;
+        };
+        mqtt.publish(signals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use vehicle_sim_core::calendar::{AnnualReminder, Calendar, Date};
+    use vehicle_sim_core::config::Config;
+    use vehicle_sim_core::obd2::{self, ObdResponder};
+    use vehicle_sim_core::privacy::{PrivacyFilter, PrivacyPolicy};
+    use vehicle_sim_core::rng::SimRng;
+    use vehicle_sim_core::simulation::Simulation;
+    use vehicle_sim_core::telematics::{NetworkSchedule, TelematicsUnit};
+
+    use crate::odometer::Odometer;
+
+    fn simulation(responder: &Arc<ObdResponder>) -> DrivingSimulation {
+        let calendar = Calendar::new(Date::new(2024, 3, 1).unwrap());
+        let inspection = AnnualReminder::new("Annual inspection", Date::new(2023, 3, 20).unwrap(), 30);
+        let mut simulation =
+            DrivingSimulation::new(Odometer::new(15.0), calendar, inspection, SimRng::from_seed(7), (60.0, 60.5));
+        simulation.obd = Some(Arc::clone(responder));
+        simulation
+    }
+
+    #[test]
+    fn engine_speed_stays_in_a_plausible_band() {
+        assert_eq!(estimate_engine_rpm(0.0), IDLE_RPM);
+        for speed in [20.0, 50.0, 80.0, 130.0] {
+            let rpm = estimate_engine_rpm(speed);
+            assert!((MIN_CRUISE_RPM..3500.0).contains(&rpm), "{} rpm at {} km/h", rpm, speed);
+        }
+    }
+
+    #[test]
+    fn answers_mode_01_from_the_last_step() {
+        let responder = Arc::new(ObdResponder::new());
+        let mut simulation = simulation(&responder);
+        simulation.step(3600.0);
+
+        assert_eq!(responder.respond(&[0x01, obd2::VEHICLE_SPEED]), Some(vec![0x41, 0x0D, 60]));
+        // 60 km at 15 km/l leaves 46 of 50 l: 235/255
+        assert_eq!(responder.respond(&[0x01, obd2::FUEL_LEVEL]), Some(vec![0x41, 0x2F, 235]));
+        assert_eq!(responder.respond(&[0x01, obd2::DISTANCE_SINCE_CODES_CLEARED]), Some(vec![0x41, 0x31, 0, 60]));
+        assert_eq!(responder.respond(&[0x01, 0x05]), None);
+    }
+
+    #[test]
+    fn clearing_codes_restarts_the_distance() {
+        let responder = Arc::new(ObdResponder::new());
+        let mut simulation = simulation(&responder);
+        simulation.step(3600.0);
+
+        assert_eq!(responder.respond(&[0x04]), Some(vec![0x44]));
+        simulation.step(1800.0);
+        assert_eq!(responder.respond(&[0x01, obd2::DISTANCE_SINCE_CODES_CLEARED]), Some(vec![0x41, 0x31, 0, 30]));
+        assert_eq!(simulation.odometer.total_distance().km().round(), 90.0);
+    }
+
+    #[test]
+    fn telemetry_goes_through_the_privacy_filter_to_the_uplink() {
+        let responder = Arc::new(ObdResponder::new());
+        let mut simulation = simulation(&responder);
+        let policy = PrivacyPolicy::from_config(&Config::parse("timestamps = 15min\nsignal.fuel_level = drop").unwrap()).unwrap();
+        simulation.privacy = Some(PrivacyFilter::new(policy));
+        // Offline for the first hour, so the unit still holds every record
+        simulation.telematics = Some(TelematicsUnit::new(NetworkSchedule::parse("0s+1h").unwrap(), 20, 1.0));
+        for _ in 0..4 {
+            simulation.step(600.0);
+        }
+
+        let uploaded = simulation.telematics.as_mut().unwrap().step(3600.0, 60.0);
+        assert_eq!(uploaded.len(), 4);
+        for record in &uploaded {
+            assert_eq!(record.time % 900.0, 0.0);
+            assert!(record.values.iter().any(|(signal, _)| signal == "speed"));
+            assert!(record.values.iter().all(|(signal, _)| signal != "fuel_level"));
+        }
+    }
+}
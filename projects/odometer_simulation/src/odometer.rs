@@ -1,8 +1,46 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::altitude;
+use crate::consumption::{self, ConsumptionModel};
+use crate::fuel_tank::FuelTank;
+use crate::persistence::MileageRecord;
+use crate::wind;
+use vehicle_sim_core::locale;
+use vehicle_sim_core::units::{Distance, Hours, Speed, Volume};
+
+// Share of the fuel's energy the engine turns into work at the wheels
+const ENGINE_EFFICIENCY: f64 = 0.25;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OdometerSnapshot {
+    pub total_kilometers: f64,
+    pub trip_meter: f64,
+    pub fuel_consumed: f64,
+    pub codes_cleared_at: f64, // total kilometers when the DTCs were last cleared
+    pub fuel_level: Option<f64>, // liters in the tank; state files without it start full
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Odometer {
     total_kilometers: f64,
     trip_meter: f64,
     fuel_consumed: f64,
     fuel_efficiency: f64, // in km per liter
+    #[serde(default)]
+    codes_cleared_at: f64,
+    #[serde(default)]
+    tank: FuelTank,
+    // Speed-dependent consumption; without one the fuel efficiency applies
+    #[serde(skip)]
+    consumption: Option<Arc<dyn ConsumptionModel>>,
+    // Headwind in km/h the models were measured without
+    #[serde(skip)]
+    headwind: f64,
+    // Grade of the road ahead, which the models were measured without
+    #[serde(skip)]
+    grade: f64,
 }
 
 impl Odometer {
@@ -13,15 +51,98 @@ impl Odometer {
             trip_meter: 0.0,
             fuel_consumed: 0.0,
             fuel_efficiency,
+            codes_cleared_at: 0.0,
+            tank: FuelTank::default(),
+            consumption: None,
+            headwind: 0.0,
+            grade: 0.0,
+        }
+    }
+
+    // Restores a saved snapshot, never going below the highest recorded mileage
+    pub fn restore(snapshot: OdometerSnapshot, fuel_efficiency: f64, record: &mut MileageRecord) -> Odometer {
+        record.update(snapshot.total_kilometers);
+
+        let mut odometer = Odometer::new(fuel_efficiency);
+        odometer.total_kilometers = record.highest_kilometers();
+        odometer.trip_meter = snapshot.trip_meter;
+        odometer.fuel_consumed = snapshot.fuel_consumed;
+        odometer.codes_cleared_at = snapshot.codes_cleared_at.min(odometer.total_kilometers);
+        if let Some(liters) = snapshot.fuel_level {
+            odometer.tank = odometer.tank.with_level(liters);
+        }
+        odometer
+    }
+
+    // Captures the current readings for persistence
+    pub fn snapshot(&self) -> OdometerSnapshot {
+        OdometerSnapshot {
+            total_kilometers: self.total_kilometers,
+            trip_meter: self.trip_meter,
+            fuel_consumed: self.fuel_consumed,
+            codes_cleared_at: self.codes_cleared_at,
+            fuel_level: Some(self.tank.level()),
         }
     }
 
-    // Method to simulate driving
-    pub fn drive(&mut self, speed: f64, hours: f64) {
-        let distance = speed * hours; // Distance = Speed * Time
-        self.total_kilometers += distance;
-        self.trip_meter += distance;
-        self.fuel_consumed += distance / self.fuel_efficiency;
+    // Method to simulate driving; the car stops where the tank runs dry, so
+    // the distance actually covered is returned
+    pub fn drive(&mut self, speed: Speed, hours: Hours) -> Distance {
+        let distance = speed.distance(hours).km(); // Distance = Speed * Time
+        let still_air = match &self.consumption {
+            Some(model) => model.liters(speed.kmh(), hours),
+            None => distance / self.fuel_efficiency,
+        };
+        let wind = distance * wind::extra_drag(speed.kmh(), self.headwind) / 1000.0
+            / (self.fuel_energy_density() * ENGINE_EFFICIENCY);
+        let climbing = altitude::climbing_energy(self.grade, distance) / (self.fuel_energy_density() * ENGINE_EFFICIENCY);
+        // Rolling downhill the engine cuts the fuel, it never makes any
+        let needed = (still_air + wind + climbing).max(0.0);
+        let fuel = self.tank.consume(needed, distance);
+        let distance = Distance::from_km(if needed > 0.0 { distance * fuel / needed } else { distance });
+        self.add_distance(distance);
+        self.fuel_consumed += fuel;
+        distance
+    }
+
+    // Counts distance driven on another energy source, e.g. the EV battery
+    pub fn add_distance(&mut self, distance: Distance) {
+        self.total_kilometers += distance.km();
+        self.trip_meter += distance.km();
+    }
+
+    // Returns what fit into the tank
+    pub fn refuel(&mut self, fuel: Volume) -> Volume {
+        Volume::from_liters(self.tank.refuel(fuel.liters()))
+    }
+
+    pub fn tank(&self) -> &FuelTank {
+        &self.tank
+    }
+
+    pub fn range_to_empty(&self) -> Distance {
+        Distance::from_km(self.tank.range_to_empty(1.0 / self.fuel_efficiency))
+    }
+
+    // kWh per liter of the fuel burned; petrol unless the model says otherwise
+    pub fn fuel_energy_density(&self) -> f64 {
+        self.consumption.as_ref().map_or(consumption::PETROL, |model| model.fuel()).energy_density
+    }
+
+    pub fn set_consumption(&mut self, model: Option<Arc<dyn ConsumptionModel>>) {
+        self.consumption = model;
+    }
+
+    pub fn set_headwind(&mut self, headwind: f64) {
+        self.headwind = headwind;
+    }
+
+    pub fn set_grade(&mut self, grade: f64) {
+        self.grade = grade;
+    }
+
+    pub fn set_fuel_efficiency(&mut self, fuel_efficiency: f64) {
+        self.fuel_efficiency = fuel_efficiency;
     }
 
     // Method to reset the trip meter
@@ -29,26 +150,106 @@ impl Odometer {
         self.trip_meter = 0.0;
     }
 
-    // Getter for total kilometers
-    pub fn total_kilometers(&self) -> f64 {
-        self.total_kilometers
+    // Scan tools clearing the fault memory restart the distance since codes cleared
+    pub fn clear_codes(&mut self) {
+        self.codes_cleared_at = self.total_kilometers;
+    }
+
+    pub fn distance_since_codes_cleared(&self) -> Distance {
+        Distance::from_km(self.total_kilometers - self.codes_cleared_at)
+    }
+
+    // Getter for total distance
+    pub fn total_distance(&self) -> Distance {
+        Distance::from_km(self.total_kilometers)
     }
 
     // Getter for trip meter
-    pub fn trip_meter(&self) -> f64 {
-        self.trip_meter
+    pub fn trip_meter(&self) -> Distance {
+        Distance::from_km(self.trip_meter)
     }
 
     // Getter for fuel consumed
-    pub fn fuel_consumed(&self) -> f64 {
-        self.fuel_consumed
+    pub fn fuel_consumed(&self) -> Volume {
+        Volume::from_liters(self.fuel_consumed)
+    }
+
+    // Distances only, for vehicles that do not run on the fuel tank
+    pub fn display_distances(&self) {
+        let locale = locale::current();
+        println!(
+            "Total Distance: {} | Trip Meter: {}",
+            locale.distance(self.total_kilometers, 2),
+            locale.distance(self.trip_meter, 2)
+        );
     }
 
     // Method to display odometer readings
     pub fn display_kilometers(&self) {
+        let locale = locale::current();
         println!(
-            "Total Kilometers: {:.2} km | Trip Meter: {:.2} km | Fuel Consumed: {:.2} liters",
-            self.total_kilometers, self.trip_meter, self.fuel_consumed
+            "Total Distance: {} | Trip Meter: {} | Fuel Consumed: {} | Fuel Level: {} | Range: {}",
+            locale.distance(self.total_kilometers, 2),
+            locale.distance(self.trip_meter, 2),
+            locale.volume(self.fuel_consumed, 2),
+            locale.volume(self.tank.level(), 1),
+            locale.distance(self.range_to_empty().km(), 0)
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn the_car_stops_where_the_tank_runs_dry() {
+        let mut odometer = Odometer::new(10.0);
+        odometer.tank = FuelTank::new(50.0).with_level(2.0);
+        let distance = odometer.drive(Speed::from_kmh(60.0), 1.0);
+
+        assert!((distance.km() - 20.0).abs() < 1e-9);
+        assert_eq!(odometer.fuel_consumed().liters(), 2.0);
+        assert_eq!(odometer.tank().level(), 0.0);
+    }
+
+    proptest! {
+        #[test]
+        fn total_distance_never_decreases(
+            model in prop::sample::select(consumption::MODEL_NAMES),
+            legs in prop::collection::vec((0.0f64..200.0, 0.0f64..2.0, any::<bool>()), 1..50),
+        ) {
+            let mut odometer = Odometer::new(15.0);
+            odometer.set_consumption(consumption::model(model, 15.0));
+            for (speed, hours, reset_trip) in legs {
+                let before = odometer.total_distance();
+                let driven = odometer.drive(Speed::from_kmh(speed), hours);
+                if reset_trip {
+                    odometer.reset_trip_meter();
+                }
+
+                prop_assert!(driven.km() >= 0.0);
+                prop_assert!(odometer.total_distance() >= before);
+                prop_assert!(odometer.trip_meter() <= odometer.total_distance());
+            }
+        }
+
+        #[test]
+        fn fuel_consumed_is_never_negative(
+            model in prop::sample::select(consumption::MODEL_NAMES),
+            legs in prop::collection::vec((0.0f64..200.0, 0.0f64..2.0, 0.0f64..60.0), 1..50),
+        ) {
+            let mut odometer = Odometer::new(15.0);
+            odometer.set_consumption(consumption::model(model, 15.0));
+            for (speed, hours, refuel) in legs {
+                let before = odometer.fuel_consumed();
+                odometer.drive(Speed::from_kmh(speed), hours);
+                odometer.refuel(Volume::from_liters(refuel));
+
+                prop_assert!(odometer.fuel_consumed() >= before);
+                prop_assert!(odometer.tank().level() >= 0.0 && odometer.tank().level() <= odometer.tank().capacity());
+            }
+        }
+    }
+}
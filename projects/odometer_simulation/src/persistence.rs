@@ -0,0 +1,212 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::odometer::OdometerSnapshot;
+
+// Reads "key=value" lines, ignoring anything that is not a number
+fn read_values(path: &Path) -> io::Result<Option<Vec<(String, f64)>>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let values = contents
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .filter_map(|(key, value)| value.trim().parse().ok().map(|v| (key.trim().to_string(), v)))
+        .collect();
+
+    Ok(Some(values))
+}
+
+fn find_value(values: &[(String, f64)], key: &str) -> Option<f64> {
+    values.iter().find(|(k, _)| k == key).map(|&(_, v)| v)
+}
+
+fn value_of(values: &[(String, f64)], key: &str) -> f64 {
+    find_value(values, key).unwrap_or(0.0)
+}
+
+impl OdometerSnapshot {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Option<OdometerSnapshot>> {
+        Ok(read_values(path.as_ref())?.map(|values| OdometerSnapshot {
+            total_kilometers: value_of(&values, "total_kilometers"),
+            trip_meter: value_of(&values, "trip_meter"),
+            fuel_consumed: value_of(&values, "fuel_consumed"),
+            codes_cleared_at: value_of(&values, "codes_cleared_at"),
+            fuel_level: find_value(&values, "fuel_level"),
+        }))
+    }
+
+    pub fn contents(&self) -> String {
+        let mut contents = format!(
+            "total_kilometers={}\ntrip_meter={}\nfuel_consumed={}\ncodes_cleared_at={}\n",
+            self.total_kilometers, self.trip_meter, self.fuel_consumed, self.codes_cleared_at
+        );
+        if let Some(liters) = self.fuel_level {
+            contents.push_str(&format!("fuel_level={}\n", liters));
+        }
+        contents
+    }
+}
+
+// Highest total mileage ever seen, kept apart from the snapshots so that
+// loading an older snapshot can never roll the odometer back.
+#[derive(Debug, Default)]
+pub struct MileageRecord {
+    highest_kilometers: f64,
+}
+
+impl MileageRecord {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<MileageRecord> {
+        let highest_kilometers = read_values(path.as_ref())?
+            .map_or(0.0, |values| value_of(&values, "highest_kilometers"));
+
+        Ok(MileageRecord { highest_kilometers })
+    }
+
+    pub fn contents(&self) -> String {
+        format!("highest_kilometers={}\n", self.highest_kilometers)
+    }
+
+    // The record only ever moves forward
+    pub fn update(&mut self, total_kilometers: f64) {
+        if total_kilometers > self.highest_kilometers {
+            self.highest_kilometers = total_kilometers;
+        }
+    }
+
+    pub fn highest_kilometers(&self) -> f64 {
+        self.highest_kilometers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logbook::{Logbook, TripEntry, TripPurpose};
+    use crate::odometer::Odometer;
+    use vehicle_sim_core::storage::{self, Transaction};
+
+    fn snapshot(total_kilometers: f64) -> OdometerSnapshot {
+        OdometerSnapshot {
+            total_kilometers,
+            trip_meter: 12.0,
+            fuel_consumed: 3.0,
+            codes_cleared_at: 0.0,
+            fuel_level: None,
+        }
+    }
+
+    #[test]
+    fn loading_an_older_snapshot_keeps_the_highest_mileage() {
+        let mut record = MileageRecord::default();
+        record.update(1500.0);
+
+        let odometer = Odometer::restore(snapshot(900.0), 15.0, &mut record);
+
+        assert_eq!(odometer.total_distance().km(), 1500.0);
+        assert_eq!(record.highest_kilometers(), 1500.0);
+    }
+
+    #[test]
+    fn loading_a_newer_snapshot_advances_the_record() {
+        let mut record = MileageRecord::default();
+        record.update(1500.0);
+
+        let odometer = Odometer::restore(snapshot(2000.0), 15.0, &mut record);
+
+        assert_eq!(odometer.total_distance().km(), 2000.0);
+        assert_eq!(record.highest_kilometers(), 2000.0);
+    }
+
+    #[test]
+    fn record_never_moves_backwards() {
+        let mut record = MileageRecord::default();
+        record.update(800.0);
+        record.update(200.0);
+
+        assert_eq!(record.highest_kilometers(), 800.0);
+    }
+
+    #[test]
+    fn record_survives_save_and_load() {
+        let path = std::env::temp_dir().join(format!("odometer_record_{}.txt", std::process::id()));
+        let mut record = MileageRecord::default();
+        record.update(4321.5);
+        storage::write_atomic(&path, record.contents()).unwrap();
+
+        // A stale snapshot written after the record must not win
+        storage::write_atomic(path.with_extension("state"), snapshot(100.0).contents()).unwrap();
+        let stale = OdometerSnapshot::load(path.with_extension("state")).unwrap().unwrap();
+        let mut loaded = MileageRecord::load(&path).unwrap();
+        let odometer = Odometer::restore(stale, 15.0, &mut loaded);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("state")).unwrap();
+
+        assert_eq!(odometer.total_distance().km(), 4321.5);
+        assert_eq!(odometer.trip_meter().km(), 12.0);
+    }
+
+    // Power-loss scenario: the power fails after every few bytes of the
+    // end-of-run save. Whatever the next start recovers is consistent, and
+    // at worst the trip that was being saved is lost.
+    #[test]
+    fn power_cut_while_saving_loses_at_most_the_last_trip() {
+        let directory = std::env::temp_dir().join(format!("odometer_power_loss_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let (state, record_path, history) = (directory.join("state.txt"), directory.join("record.txt"), directory.join("history.csv"));
+        let journal = directory.join("odometer.journal");
+
+        let save = |kilometers: f64, trips: usize, cut: Option<u64>| {
+            let mut logbook = Logbook::default();
+            for trip in 0..trips {
+                logbook.add(TripEntry {
+                    start: format!("2024-03-0{} 08:00", trip + 1),
+                    end: format!("2024-03-0{} 09:00", trip + 1),
+                    start_kilometers: 1000.0 + 100.0 * trip as f64,
+                    end_kilometers: 1100.0 + 100.0 * trip as f64,
+                    purpose: TripPurpose::Private,
+                    driver: "FOB-1".to_string(),
+                    fuel_liters: 6.0,
+                });
+            }
+            let mut record = MileageRecord::default();
+            record.update(kilometers);
+            let mut transaction = Transaction::new(&journal);
+            transaction.write(&state, snapshot(kilometers).contents());
+            transaction.write(&record_path, record.contents());
+            transaction.write(&history, logbook.contents());
+            if let Some(bytes) = cut {
+                storage::cut_power_after(bytes);
+            }
+            let saved = transaction.commit();
+            storage::restore_power();
+            saved
+        };
+
+        let mut cut = 0;
+        loop {
+            save(1100.0, 1, None).unwrap();
+            let completed = save(1200.0, 2, Some(cut)).is_ok();
+            storage::recover(&journal).unwrap();
+
+            let kilometers = OdometerSnapshot::load(&state).unwrap().unwrap().total_kilometers;
+            let record = MileageRecord::load(&record_path).unwrap().highest_kilometers();
+            let logbook = Logbook::load(&history).unwrap();
+            assert_eq!(record, kilometers, "power cut after {} bytes", cut);
+            assert_eq!(logbook.last().unwrap().end_kilometers, kilometers, "power cut after {} bytes", cut);
+            assert!(kilometers == 1100.0 || kilometers == 1200.0);
+            assert!(!journal.exists());
+            if completed {
+                assert_eq!(kilometers, 1200.0);
+                break;
+            }
+            cut += 5;
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
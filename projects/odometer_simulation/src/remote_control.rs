@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::locale;
+use vehicle_sim_core::remote::RemoteCommand;
+
+// Climate control power while the cabin is preconditioned, in kW
+pub const PRECONDITIONING_LOAD: f64 = 4.0;
+
+// What remote commands changed on the vehicle; part of the checkpoint, so a
+// resumed run stays locked and limited
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RemoteState {
+    pub doors_locked: bool,
+    pub valet_limit: Option<f64>,           // km/h
+    pub preconditioning_until: Option<f64>, // s of simulated time
+}
+
+impl RemoteState {
+    // Carries out `command` at `time`, describing what it did or why it was
+    // refused; preconditioning on a low battery would strand the car
+    pub fn apply(&mut self, command: RemoteCommand, time: f64, battery_low: bool) -> Result<String, String> {
+        let locale = locale::current();
+        match command {
+            RemoteCommand::Precondition(_) if battery_low => Err("battery too low to precondition".to_string()),
+            RemoteCommand::Precondition(duration) => {
+                // Another request extends a running preconditioning
+                let start = self.preconditioning_until.filter(|&until| until > time).unwrap_or(time);
+                self.preconditioning_until = Some(start + duration);
+                Ok(format!("preconditioning the cabin for {} min", locale.number((start + duration - time) / 60.0, 0)))
+            }
+            RemoteCommand::LockDoors => {
+                self.doors_locked = true;
+                Ok("doors locked".to_string())
+            }
+            RemoteCommand::UnlockDoors => {
+                self.doors_locked = false;
+                Ok("doors unlocked".to_string())
+            }
+            RemoteCommand::ValetMode(None) if self.valet_limit.is_none() => Err("valet mode is not active".to_string()),
+            RemoteCommand::ValetMode(limit) => {
+                self.valet_limit = limit;
+                Ok(match limit {
+                    Some(limit) => format!("valet mode, speed limited to {}", locale.speed(limit, 0)),
+                    None => "valet mode ended".to_string(),
+                })
+            }
+            // The simulation hands these to its RestrictionMode instead
+            RemoteCommand::Restrict(_) => Err("restrictions are not part of the remote state".to_string()),
+        }
+    }
+
+    // Load the preconditioning adds at `time`, in kW
+    pub fn preconditioning_load(&self, time: f64) -> f64 {
+        if self.preconditioning_until.is_some_and(|until| time < until) {
+            PRECONDITIONING_LOAD
+        } else {
+            0.0
+        }
+    }
+
+    pub fn limit_speed(&self, speed: f64) -> f64 {
+        self.valet_limit.map_or(speed, |limit| speed.min(limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vehicle_sim_core::config::Config;
+    use vehicle_sim_core::remote::{RemoteChannel, RemoteRequest, TokenStore};
+
+    fn request(at: f64, token: &str, command: &str) -> RemoteRequest {
+        RemoteRequest {
+            at,
+            token: token.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn tokens_decide_who_may_send_what() {
+        let tokens = TokenStore::from_config(
+            &Config::parse("token.owner = 3f8a61c07d2e\ntoken.valet = 91b0c4e2\nallow.valet = lock, unlock\nexpires.valet = 1h").unwrap(),
+        )
+        .unwrap();
+        let requests = vec![
+            request(10.0, "3f8a61c07d2e", "valet 40"),
+            request(20.0, "91b0c4e2", "valet off"),
+            request(30.0, "91b0c4e2", "lock"),
+            request(40.0, "3f8a61c07d2f", "unlock"),
+            request(50.0, "3f8a61c07d2e", "fly"),
+            request(7200.0, "91b0c4e2", "unlock"),
+        ];
+        let mut channel = RemoteChannel::new(tokens, requests);
+
+        // Held at the backend while the vehicle is offline
+        assert!(channel.receive(100.0, false).is_empty());
+        let authorized = channel.receive(100.0, true);
+        let sent: Vec<(&str, String)> = authorized.iter().map(|c| (c.holder.as_str(), c.command.to_string())).collect();
+        assert_eq!(sent, vec![("owner", "valet 40".to_string()), ("valet", "lock".to_string())]);
+        let refused: Vec<&str> = channel.log.iter().map(|entry| entry.outcome.as_str()).collect();
+        assert_eq!(refused[0], "valet may not send valet");
+        assert_eq!(refused[1], "unknown token");
+        assert!(refused[2].starts_with("expected precondition"));
+        assert!(channel.log.iter().all(|entry| !entry.executed && entry.holder.is_none()));
+
+        // The valet's token ran out before the last request arrived
+        channel.receive(7200.0, true);
+        assert_eq!(channel.log.last().unwrap().outcome, "token of valet expired");
+        assert_eq!(channel.pending(), 0);
+
+        assert!(TokenStore::from_config(&Config::parse("token.owner = 1234").unwrap()).is_err());
+        assert!(TokenStore::from_config(&Config::parse("allow.owner = lock").unwrap()).is_err());
+    }
+
+    #[test]
+    fn remote_commands_change_the_vehicle_and_are_audited() {
+        let tokens = TokenStore::from_config(&Config::parse("token.owner = 3f8a61c07d2e").unwrap()).unwrap();
+        let mut channel = RemoteChannel::new(tokens, vec![request(0.0, "3f8a61c07d2e", "precondition 10min")]);
+        let mut state = RemoteState::default();
+
+        for command in channel.receive(0.0, true) {
+            let outcome = state.apply(command.command, 0.0, true);
+            channel.record(0.0, &command, outcome);
+        }
+        assert!(!channel.log[0].executed);
+        assert_eq!(channel.log[0].outcome, "battery too low to precondition");
+        assert_eq!(channel.log[0].holder.as_deref(), Some("owner"));
+
+        assert!(state.apply(RemoteCommand::Precondition(600.0), 0.0, false).is_ok());
+        assert!(state.apply(RemoteCommand::Precondition(600.0), 300.0, false).is_ok());
+        assert_eq!(state.preconditioning_load(1000.0), PRECONDITIONING_LOAD);
+        assert_eq!(state.preconditioning_load(1200.0), 0.0);
+
+        assert!(state.apply(RemoteCommand::ValetMode(None), 0.0, false).is_err());
+        state.apply(RemoteCommand::ValetMode(Some(40.0)), 0.0, false).unwrap();
+        assert_eq!(state.limit_speed(120.0), 40.0);
+        assert_eq!(state.limit_speed(30.0), 30.0);
+        state.apply(RemoteCommand::ValetMode(None), 0.0, false).unwrap();
+        assert_eq!(state.limit_speed(120.0), 120.0);
+
+        let before = state.clone();
+        assert!(state.apply(RemoteCommand::Restrict(None), 0.0, false).is_err());
+        assert_eq!(state, before);
+    }
+}
@@ -0,0 +1,251 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::events::Event;
+use vehicle_sim_core::locale;
+use vehicle_sim_core::restriction::{self, RestrictionProfile};
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::units::{kmh_to_ms, ms_to_kmh};
+
+// Largest change of the audio volume the driver asks for per step, in %
+const VOLUME_DRIFT: f64 = 15.0;
+// A driver hovering at the limit dips below it now and then; a violation
+// resumed within this many seconds counts as the same one
+const MERGE_GAP: f64 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Limit {
+    Speed,
+    Acceleration,
+    Audio,
+}
+
+impl Limit {
+    const ALL: [Limit; 3] = [Limit::Speed, Limit::Acceleration, Limit::Audio];
+
+    fn name(self) -> &'static str {
+        match self {
+            Limit::Speed => "speed",
+            Limit::Acceleration => "acceleration",
+            Limit::Audio => "audio volume",
+        }
+    }
+
+    fn format(self, value: f64) -> String {
+        let locale = locale::current();
+        match self {
+            Limit::Speed => locale.speed(value, 0),
+            Limit::Acceleration => format!("{} m/s²", locale.number(value, 1)),
+            Limit::Audio => format!("{}%", locale.number(value, 0)),
+        }
+    }
+}
+
+// A stretch of time during which the driver asked for more than the
+// profile allows
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    pub profile: String,
+    pub limit: Limit,
+    pub start: f64,    // s of simulated time
+    pub duration: f64, // s
+    // Most the driver asked for, and what the profile allowed
+    pub worst: f64,
+    pub allowed: f64,
+    ongoing: bool,
+}
+
+// Holds a restricted driver to the profile's speed, acceleration and audio
+// volume, and keeps a record of every violation for the owner; part of the
+// checkpoint, so a resumed run stays restricted
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RestrictionMode {
+    // Profile in force and who put it on: a token holder or a key fob
+    profile: Option<String>,
+    set_by: Option<String>,
+    // Volume the driver turns the audio to, in %
+    requested_volume: f64,
+    pub violations: Vec<Violation>,
+}
+
+impl RestrictionMode {
+    pub fn profile(&self) -> Option<RestrictionProfile> {
+        self.profile.as_deref().and_then(restriction::find)
+    }
+
+    // Puts `profile` on (or lifts the one in force), describing what changed
+    pub fn set(&mut self, profile: Option<RestrictionProfile>, set_by: &str) -> Result<String, String> {
+        if profile.is_none() && self.profile.is_none() {
+            return Err("no restriction is active".to_string());
+        }
+        for violation in &mut self.violations {
+            violation.ongoing = false;
+        }
+        self.profile = profile.map(|profile| profile.name.to_string());
+        self.set_by = Some(set_by.to_string());
+        Ok(match profile {
+            Some(profile) => format!(
+                "{} restrictions: {}, {} m/s², audio at most {}%",
+                profile.name,
+                locale::current().speed(profile.max_speed, 0),
+                profile.max_acceleration,
+                profile.max_volume
+            ),
+            None => "restrictions lifted".to_string(),
+        })
+    }
+
+    // The speed in km/h the vehicle allows when the driver asks for `speed`
+    // after `previous_speed`, `dt` s at `time` into the run
+    pub fn limit(&mut self, time: f64, dt: f64, previous_speed: f64, speed: f64, rng: &mut impl Rng) -> f64 {
+        let Some(profile) = self.profile() else {
+            return speed;
+        };
+        let acceleration = kmh_to_ms(speed - previous_speed) / dt;
+        self.track(&profile, Limit::Acceleration, acceleration, time, dt);
+        self.track(&profile, Limit::Speed, speed, time, dt);
+        self.requested_volume = (self.requested_volume + rng.gen_range(-VOLUME_DRIFT..VOLUME_DRIFT)).clamp(0.0, 100.0);
+        self.track(&profile, Limit::Audio, self.requested_volume, time, dt);
+
+        speed
+            .min(previous_speed + ms_to_kmh(profile.max_acceleration * dt))
+            .min(profile.max_speed)
+    }
+
+    // Opens a violation (and raises a warning) when the driver first asks
+    // for too much, extends it while they keep on and closes it once they
+    // stop
+    fn track(&mut self, profile: &RestrictionProfile, limit: Limit, requested: f64, time: f64, dt: f64) {
+        let allowed = match limit {
+            Limit::Speed => profile.max_speed,
+            Limit::Acceleration => profile.max_acceleration,
+            Limit::Audio => profile.max_volume,
+        };
+        let last = self
+            .violations
+            .iter_mut()
+            .rev()
+            .find(|violation| violation.limit == limit && violation.profile == profile.name)
+            .filter(|violation| violation.ongoing || time - (violation.start + violation.duration) <= MERGE_GAP);
+        match last {
+            Some(violation) if requested > allowed => {
+                violation.duration = time + dt - violation.start;
+                violation.worst = violation.worst.max(requested);
+                violation.ongoing = true;
+            }
+            Some(violation) => violation.ongoing = false,
+            None if requested > allowed => {
+                sim_log::event(
+                    None,
+                    &Event::WarningRaised {
+                        source: "restriction".to_string(),
+                        message: format!(
+                            "{} profile: {} of {} above the allowed {}",
+                            profile.name,
+                            limit.name(),
+                            limit.format(requested),
+                            limit.format(allowed)
+                        ),
+                    },
+                );
+                self.violations.push(Violation {
+                    profile: profile.name.to_string(),
+                    limit,
+                    start: time,
+                    duration: dt,
+                    worst: requested,
+                    allowed,
+                    ongoing: true,
+                });
+            }
+            None => {}
+        }
+    }
+
+    // What the owner gets to see after a restricted drive
+    pub fn print_report(&self) {
+        let Some(set_by) = &self.set_by else {
+            return;
+        };
+        let locale = locale::current();
+        println!(
+            "Restriction report for the owner ({}, last set by {}):",
+            self.profile.as_deref().unwrap_or("lifted"),
+            set_by
+        );
+        if self.violations.is_empty() {
+            println!("  No violations.");
+            return;
+        }
+        for limit in Limit::ALL {
+            let violations: Vec<&Violation> = self.violations.iter().filter(|violation| violation.limit == limit).collect();
+            let Some(worst) = violations.iter().max_by(|a, b| (a.worst - a.allowed).total_cmp(&(b.worst - b.allowed))) else {
+                continue;
+            };
+            let first = violations[0];
+            println!(
+                "  {:<13} {} times for {} min, first after {} h, worst {} against {}",
+                limit.name(),
+                violations.len(),
+                locale.number(violations.iter().map(|violation| violation.duration).sum::<f64>() / 60.0, 0),
+                locale.number(first.start / 3600.0, 1),
+                limit.format(worst.worst),
+                limit.format(worst.allowed)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vehicle_sim_core::driver;
+    use vehicle_sim_core::remote::RemoteCommand;
+    use vehicle_sim_core::rng::SimRng;
+
+    #[test]
+    fn restricted_driver_is_held_to_the_profile() {
+        let mut rng = SimRng::from_seed(3);
+        let mut mode = RestrictionMode::default();
+        assert_eq!(mode.limit(0.0, 1.0, 50.0, 150.0, &mut rng), 150.0);
+        assert!(mode.set(None, "owner").is_err());
+
+        let teen = driver::find_profile("FOB-4").and_then(|profile| profile.restriction).unwrap();
+        mode.set(Some(teen), "key FOB-4").unwrap();
+        // Flooring it from 100 km/h: 2.5 m/s² for a second is 9 km/h
+        assert!((mode.limit(0.0, 1.0, 100.0, 130.0, &mut rng) - 109.0).abs() < 1e-9);
+        assert_eq!(mode.limit(1.0, 10.0, 109.0, 130.0, &mut rng), 110.0);
+        assert_eq!(mode.limit(11.0, 10.0, 110.0, 100.0, &mut rng), 100.0);
+
+        // One speeding violation over two steps, and one from the kick-down
+        let speeding: Vec<&Violation> = mode.violations.iter().filter(|v| v.limit == Limit::Speed).collect();
+        assert_eq!(speeding.len(), 1);
+        assert_eq!(speeding[0].duration, 11.0);
+        assert_eq!(speeding[0].worst, 130.0);
+        assert_eq!(mode.violations.iter().filter(|v| v.limit == Limit::Acceleration).count(), 1);
+        assert!(mode.violations.iter().all(|v| v.limit != Limit::Speed || !v.ongoing));
+    }
+
+    #[test]
+    fn owner_switches_profiles_remotely() {
+        let Ok(RemoteCommand::Restrict(Some(valet))) = RemoteCommand::parse("restrict valet") else {
+            panic!("restrict valet did not parse");
+        };
+        assert!(RemoteCommand::parse("restrict racing").is_err());
+        assert_eq!(RemoteCommand::Restrict(None).to_string(), "restrict off");
+
+        let mut rng = SimRng::from_seed(3);
+        let mut mode = RestrictionMode::default();
+        mode.set(Some(valet), "owner").unwrap();
+        for step in 0..20 {
+            mode.limit(step as f64, 1.0, 30.0, 30.0, &mut rng);
+        }
+        // Any music at all breaks the valet profile
+        assert!(mode.violations.iter().all(|v| v.limit == Limit::Audio && v.allowed == 0.0));
+        assert!(!mode.violations.is_empty());
+
+        assert_eq!(mode.set(None, "owner").unwrap(), "restrictions lifted");
+        assert_eq!(mode.profile(), None);
+        assert_eq!(mode.limit(30.0, 1.0, 30.0, 90.0, &mut rng), 90.0);
+    }
+}
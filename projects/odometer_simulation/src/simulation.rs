@@ -0,0 +1,554 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::calendar::{AnnualReminder, Calendar, Date, ReminderStatus};
+use vehicle_sim_core::can_bus::{CanBus, CLUSTER_ODOMETER};
+use vehicle_sim_core::drive_cycle::{CyclePlayback, DriveCycle};
+use vehicle_sim_core::driver_model::{DriverModel, SpeedProfile};
+use vehicle_sim_core::events::Event;
+use vehicle_sim_core::locale;
+use vehicle_sim_core::obd2::ObdResponder;
+use vehicle_sim_core::privacy::PrivacyFilter;
+use vehicle_sim_core::remote::{RemoteChannel, RemoteCommand};
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::scenario::{self, RoutePoint};
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::simulation::Simulation;
+use vehicle_sim_core::telematics::TelematicsUnit;
+use vehicle_sim_core::units::{hours_to_seconds, seconds_to_hours, Distance, Hours, Speed, Volume};
+use vehicle_sim_core::xcp::XcpServer;
+
+use crate::altitude;
+use crate::coast_down::RoadLoad;
+use crate::consumption::Comparison;
+use crate::energy_flow::EnergyFlow;
+use crate::ev::{self, EvOdometer};
+use crate::gnss::{self, GnssPosition};
+use crate::odometer::{Odometer, OdometerSnapshot};
+use crate::remote_control::RemoteState;
+use crate::restriction_mode::RestrictionMode;
+use crate::trip_computer::{Trip, TripComputer};
+use crate::wind;
+
+// The driver stops at the next fuel station once the range drops below this
+const REFUEL_RANGE: Distance = Distance::from_km(50.0);
+
+#[derive(Debug, Clone, Copy)]
+pub struct DrivingState {
+    pub hours_passed: Hours,
+    pub readings: OdometerSnapshot,
+    // Electric vehicles only
+    pub state_of_charge: Option<f64>,
+}
+
+// Where the current trip started, so a resumed run still logs the whole trip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TripStart {
+    pub timestamp: String,
+    pub kilometers: f64,
+    pub fuel_liters: f64,
+}
+
+// Drives the odometer at a random speed (or the driver model's, or a drive
+// cycle's) every step while keeping the calendar (and the reminders
+// depending on it) in sync with simulated time.
+#[derive(Serialize, Deserialize)]
+pub struct DrivingSimulation {
+    pub odometer: Odometer,
+    pub calendar: Calendar,
+    pub inspection: AnnualReminder,
+    pub trip_start: TripStart,
+    rng: SimRng,
+    speed_range: (f64, f64),
+    hours_passed: Hours,
+    // Speed driven during the last step, in km/h
+    #[serde(default)]
+    pub speed: f64,
+    // Set for an electric vehicle, whose battery replaces the fuel tank
+    #[serde(default)]
+    pub ev: Option<EvOdometer>,
+    // Set to drive a speed trace instead of speeds drawn from the range
+    #[serde(default)]
+    pub driver: Option<DriverModel>,
+    // Set to play back a standard or recorded drive cycle
+    #[serde(default)]
+    pub cycle: Option<CyclePlayback>,
+    #[serde(default)]
+    pub position: GnssPosition,
+    #[serde(default)]
+    pub trips: TripComputer,
+    // Where the trip's energy went
+    #[serde(default)]
+    pub energy: EnergyFlow,
+    // Climate control load in kW of a fuel vehicle; an EV keeps its own
+    #[serde(skip)]
+    pub climate_load: f64,
+    // Doors, valet mode and preconditioning as set over the air
+    #[serde(default)]
+    pub remote_state: RemoteState,
+    // Speed, acceleration and audio limits of a valet or teen driver, put on
+    // by their key fob or over the air
+    #[serde(default)]
+    pub restrictions: RestrictionMode,
+    #[serde(skip)]
+    pub can: Arc<CanBus>,
+    #[serde(skip)]
+    pub obd: Option<Arc<ObdResponder>>,
+    #[serde(skip)]
+    pub xcp: Option<Arc<XcpServer>>,
+    #[cfg(feature = "mqtt")]
+    #[serde(skip)]
+    pub mqtt: Option<Arc<vehicle_sim_core::mqtt::MqttPublisher>>,
+    // Buffers the telemetry while the network is down (--telematics)
+    #[serde(skip)]
+    pub telematics: Option<TelematicsUnit>,
+    // Applied to the telemetry before any of it is exported (--privacy)
+    #[serde(skip)]
+    pub privacy: Option<PrivacyFilter>,
+    // Commands from the backend, received over the telematics link
+    // (--remote-commands)
+    #[serde(skip)]
+    pub remote: Option<RemoteChannel>,
+    // Models compared over this run's speeds; a resumed run starts them over
+    #[serde(skip)]
+    pub comparisons: Vec<Comparison>,
+    // Elevation profile and wind of the trip from the scenario, if it has one
+    #[serde(skip)]
+    pub route: Vec<RoutePoint>,
+    // km/h against the direction of travel during the last step
+    #[serde(skip)]
+    headwind: f64,
+}
+
+impl DrivingSimulation {
+    pub fn new(
+        odometer: Odometer,
+        calendar: Calendar,
+        inspection: AnnualReminder,
+        rng: SimRng,
+        speed_range: (f64, f64),
+    ) -> Self {
+        log_reminder(&inspection, calendar.date());
+
+        let trip_start = TripStart {
+            timestamp: calendar.timestamp(),
+            kilometers: odometer.total_distance().km(),
+            fuel_liters: odometer.fuel_consumed().liters(),
+        };
+
+        DrivingSimulation {
+            odometer,
+            calendar,
+            inspection,
+            trip_start,
+            rng,
+            speed_range,
+            hours_passed: 0.0,
+            speed: 0.0,
+            ev: None,
+            driver: None,
+            cycle: None,
+            position: GnssPosition::default(),
+            trips: TripComputer::default(),
+            energy: EnergyFlow::default(),
+            climate_load: 0.0,
+            remote_state: RemoteState::default(),
+            restrictions: RestrictionMode::default(),
+            can: Arc::new(CanBus::new()),
+            obd: None,
+            xcp: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            telematics: None,
+            privacy: None,
+            remote: None,
+            comparisons: Vec::new(),
+            route: Vec::new(),
+            headwind: 0.0,
+        }
+    }
+
+    pub fn set_speed_profile(&mut self, profile: SpeedProfile) {
+        self.driver = Some(DriverModel::new(profile, &mut self.rng));
+    }
+
+    pub fn set_drive_cycle(&mut self, cycle: DriveCycle) {
+        self.cycle = Some(CyclePlayback::new(cycle));
+    }
+
+    // Fastest speed a step can be driven at
+    pub fn max_speed(&self) -> f64 {
+        match &self.cycle {
+            Some(playback) => playback.cycle.max_speed(),
+            None => self.driver.as_ref().map_or(self.speed_range.1, DriverModel::max_speed),
+        }
+    }
+
+    // The averages still open when the run ends go out as a last sample
+    pub fn flush_privacy(&mut self) {
+        let time = hours_to_seconds(self.hours_passed);
+        let Some(privacy) = &mut self.privacy else {
+            return;
+        };
+        let timestamp = privacy.timestamp(time);
+        let signals = privacy.flush();
+        if signals.is_empty() {
+            return;
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.publish(&signals);
+        }
+        if let Some(telematics) = &mut self.telematics {
+            telematics.record(timestamp, &signals);
+        }
+    }
+}
+
+impl Simulation for DrivingSimulation {
+    type State = DrivingState;
+
+    fn step(&mut self, dt: f64) {
+        let hours = seconds_to_hours(dt);
+        if self.obd.as_ref().is_some_and(|obd| obd.take_clear_request()) {
+            self.odometer.clear_codes();
+        }
+        self.apply_xcp_writes();
+        if let Some(ev) = &mut self.ev {
+            ev.preconditioning_load = self.remote_state.preconditioning_load(hours_to_seconds(self.hours_passed));
+        }
+
+        let previous_speed = self.speed;
+        self.speed = match (&mut self.cycle, &mut self.driver) {
+            (Some(playback), _) => playback.advance(dt),
+            (None, Some(driver)) => driver.advance(dt, &mut self.rng),
+            (None, None) => {
+                let (min_speed, max_speed) = self.speed_range;
+                self.rng.gen_range(min_speed..max_speed)
+            }
+        };
+        self.speed = self.restrictions.limit(hours_to_seconds(self.hours_passed), dt, previous_speed, self.speed, &mut self.rng);
+        self.speed = self.remote_state.limit_speed(self.speed);
+        self.apply_wind(hours);
+        self.limit_to_hill_climb_speed();
+        self.apply_grade(hours);
+        self.advance_position(self.speed * hours);
+        if self.ev.is_some() {
+            self.drive_electric(previous_speed, hours);
+        } else {
+            self.drive(previous_speed, hours);
+        }
+
+        let previous_date = self.calendar.date();
+        self.calendar.advance(hours);
+        if self.calendar.date() != previous_date {
+            log_reminder(&self.inspection, self.calendar.date());
+        }
+
+        self.hours_passed += hours;
+        self.can.transmit(
+            &CLUSTER_ODOMETER,
+            &[
+                ("Odometer", self.odometer.total_distance().km()),
+                ("TripMeter", self.odometer.trip_meter().km()),
+                ("FuelConsumed", self.odometer.fuel_consumed().liters()),
+            ],
+        );
+        self.can.end_tick();
+        if let Some(obd) = &self.obd {
+            obd.update(self.obd_data());
+        }
+        self.publish_xcp();
+        let time = hours_to_seconds(self.hours_passed);
+        let signals = self.exported_signals(time);
+        #[cfg(feature = "mqtt")]
+        self.publish_mqtt(&signals);
+        if let Some(telematics) = &mut self.telematics {
+            let timestamp = self.privacy.as_ref().map_or(time, |privacy| privacy.timestamp(time));
+            telematics.record(timestamp, &signals);
+            telematics.step(time, dt);
+        }
+        self.receive_remote_commands(time);
+    }
+
+    fn state(&self) -> DrivingState {
+        DrivingState {
+            hours_passed: self.hours_passed,
+            readings: self.odometer.snapshot(),
+            state_of_charge: self.ev.as_ref().map(|ev| ev.battery.state_of_charge()),
+        }
+    }
+
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self).ok()
+    }
+
+    fn report(&self) -> String {
+        let locale = locale::current();
+        if let Some(ev) = &self.ev {
+            return format!(
+                "{} | Total Distance: {} | Trip Meter: {} | Battery: {}% | Range: {}{}",
+                locale.timestamp(self.calendar.date(), self.calendar.hour_of_day()),
+                locale.distance(self.odometer.total_distance().km(), 2),
+                locale.distance(self.odometer.trip_meter().km(), 2),
+                locale.number(ev.battery.state_of_charge() * 100.0, 1),
+                locale.distance(ev.range(), 0),
+                if ev.is_charging() { " | Charging" } else { "" }
+            );
+        }
+        format!(
+            "{} | Total Distance: {} | Trip Meter: {} | Fuel Consumed: {} | Range: {}",
+            locale.timestamp(self.calendar.date(), self.calendar.hour_of_day()),
+            locale.distance(self.odometer.total_distance().km(), 2),
+            locale.distance(self.odometer.trip_meter().km(), 2),
+            locale.volume(self.odometer.fuel_consumed().liters(), 2),
+            locale.distance(self.odometer.range_to_empty().km(), 0)
+        )
+    }
+}
+
+impl DrivingSimulation {
+    // Carries out what arrived over the telematics link; they take effect
+    // from the next step
+    fn receive_remote_commands(&mut self, time: f64) {
+        let online = self.telematics.as_ref().is_none_or(TelematicsUnit::is_online);
+        let Some(remote) = &mut self.remote else {
+            return;
+        };
+        let battery_low = self.ev.as_ref().is_some_and(|ev| ev.is_low() && !ev.is_charging());
+        for command in remote.receive(time, online) {
+            let outcome = match command.command {
+                RemoteCommand::Restrict(profile) => self.restrictions.set(profile, &command.holder),
+                other => self.remote_state.apply(other, time, battery_low),
+            };
+            remote.record(time, &command, outcome);
+        }
+    }
+
+    // Kilometers into the scenario's route
+    fn route_position(&self) -> f64 {
+        self.odometer.total_distance().km() - self.trip_start.kilometers
+    }
+
+    // Follows the route's headings, or the ring road if it has none, in
+    // pieces of about a kilometer so long steps still follow the bends
+    fn advance_position(&mut self, distance: f64) {
+        let start = self.route_position();
+        let has_headings = self.route.iter().any(|point| point.heading.is_some());
+        let pieces = distance.ceil().max(1.0);
+        for piece in 0..pieces as u64 {
+            let along = start + distance * (piece as f64 + 0.5) / pieces;
+            let heading = if has_headings {
+                scenario::heading_at(&self.route, along)
+            } else {
+                gnss::ring_road_heading(along)
+            };
+            self.position.advance(distance / pieces, heading);
+        }
+    }
+
+    // The route's wind pushes back on the car or helps it along, and its
+    // gusts hit the car from the side
+    fn apply_wind(&mut self, hours: Hours) {
+        let position = self.route_position();
+        let heading = scenario::heading_at(&self.route, position);
+        let wind = scenario::wind_at(&self.route, position);
+        self.headwind = wind.map_or(0.0, |wind| wind::headwind(&wind, heading));
+        self.odometer.set_headwind(self.headwind);
+        self.energy.headwind = self.headwind;
+        if let Some(ev) = &mut self.ev {
+            ev.headwind = self.headwind;
+        }
+
+        if let Some(gust) = wind.and_then(|wind| wind::gust(&wind, heading, hours, &mut self.rng)) {
+            sim_log::event(
+                None,
+                &Event::WindGust {
+                    speed: gust.speed,
+                    crosswind: gust.crosswind,
+                },
+            );
+        }
+    }
+
+    // On the route's climbs the driver cannot go faster than the powertrain
+    // allows; the engine loses power in the thinner air, the motor does not
+    fn limit_to_hill_climb_speed(&mut self) {
+        let position = self.route_position();
+        let Some(elevation) = scenario::elevation_at(&self.route, position) else {
+            return;
+        };
+        let grade = scenario::grade_at(&self.route, position);
+        let power = if self.ev.is_some() { ev::MOTOR_POWER } else { altitude::engine_power(elevation) };
+        let limit = altitude::hill_climb_speed(power, RoadLoad::configured().at_elevation(elevation), grade, self.headwind);
+        if self.speed > limit {
+            let locale = locale::current();
+            sim_log::debug(
+                "route",
+                &format!(
+                    "Climbing {}% at {} m: {} kW hold {}",
+                    locale.number(grade * 100.0, 1),
+                    locale.number(elevation, 0),
+                    locale.number(power, 1),
+                    locale.speed(limit, 0)
+                ),
+            );
+            self.speed = limit;
+        }
+    }
+
+    // Average grade over the stretch the step is about to cover, so a long
+    // step pays for the climbs it crosses and not just for the first one
+    fn apply_grade(&mut self, hours: Hours) {
+        let start = self.route_position();
+        let stretch = self.speed * hours;
+        let grade = match (scenario::elevation_at(&self.route, start), scenario::elevation_at(&self.route, start + stretch)) {
+            (Some(from), Some(to)) if stretch > 0.0 => (to - from) / (stretch * 1000.0),
+            _ => 0.0,
+        };
+        self.odometer.set_grade(grade);
+        self.energy.grade = grade;
+        if let Some(ev) = &mut self.ev {
+            ev.grade = grade;
+        }
+    }
+
+    // Warns once when the tank drops into the reserve and refuels when the
+    // range gets short
+    fn drive(&mut self, previous_speed: f64, hours: f64) {
+        let was_in_reserve = self.odometer.tank().is_in_reserve();
+        let fuel_before = self.odometer.fuel_consumed();
+        let speed = Speed::from_kmh(self.speed);
+        let distance = self.odometer.drive(speed, hours);
+        let fuel = (self.odometer.fuel_consumed() - fuel_before).liters();
+        self.energy.record(
+            fuel * self.odometer.fuel_energy_density(),
+            (self.climate_load + self.remote_state.preconditioning_load(hours_to_seconds(self.hours_passed))) * hours,
+            0.0,
+            previous_speed,
+            self.speed,
+            distance.km(),
+        );
+        self.trips.record(distance.km(), hours, fuel);
+        // Compared over the time actually driven when the tank ran dry
+        let driven_hours = distance.hours_at(speed).unwrap_or(hours);
+        for comparison in &mut self.comparisons {
+            comparison.record(self.speed, driven_hours);
+        }
+        let locale = locale::current();
+
+        if self.odometer.tank().is_empty() && distance < speed.distance(hours) {
+            sim_log::event(
+                None,
+                &Event::WarningRaised {
+                    source: "fuel".to_string(),
+                    message: format!("ran out of fuel after {}", locale.distance(distance.km(), 1)),
+                },
+            );
+        } else if self.odometer.tank().is_in_reserve() && !was_in_reserve {
+            sim_log::event(
+                None,
+                &Event::WarningRaised {
+                    source: "fuel".to_string(),
+                    message: format!(
+                        "low fuel: {} left, range {}",
+                        locale.volume(self.odometer.tank().level(), 1),
+                        locale.distance(self.odometer.range_to_empty().km(), 0)
+                    ),
+                },
+            );
+        }
+
+        // A step at top speed must not run the tank dry before the next stop
+        let max_speed = self.max_speed();
+        if self.odometer.range_to_empty() < REFUEL_RANGE.max(Speed::from_kmh(max_speed).distance(hours)) {
+            let fuel = self.odometer.refuel(Volume::from_liters(self.odometer.tank().capacity()));
+            self.trips.reset(Trip::SinceRefuel);
+            sim_log::info("fuel", &format!("Refueled {}", locale.volume(fuel.liters(), 1)));
+        }
+    }
+
+    // Drives on the battery, or stays at the charger until the session
+    // ends; warns once when the charge gets low
+    fn drive_electric(&mut self, previous_speed: f64, hours: f64) {
+        let max_speed = self.max_speed();
+        let Some(ev) = &mut self.ev else {
+            return;
+        };
+        let locale = locale::current();
+
+        if ev.is_charging() {
+            self.speed = 0.0;
+            if let Some(session) = ev.charge(hours) {
+                sim_log::info(
+                    "battery",
+                    &format!(
+                        "Charged {} kWh in {} h, {}% -> {}%",
+                        locale.number(session.energy, 1),
+                        locale.number(session.hours, 1),
+                        locale.number(session.start_state_of_charge * 100.0, 0),
+                        locale.number(session.end_state_of_charge * 100.0, 0)
+                    ),
+                );
+            }
+            return;
+        }
+
+        let was_low = ev.is_low();
+        let (used, regenerated) = (ev.energy_used(), ev.regenerated());
+        let distance = ev.drive(previous_speed, self.speed, hours);
+        self.odometer.add_distance(Distance::from_km(distance));
+        self.energy.record(
+            ev.energy_used() - used,
+            ev.cabin_load() * hours,
+            ev.regenerated() - regenerated,
+            previous_speed,
+            self.speed,
+            distance,
+        );
+        self.trips.record(distance, hours, ev.energy_used() - used - (ev.regenerated() - regenerated));
+
+        if ev.is_low() && !was_low {
+            sim_log::event(
+                None,
+                &Event::WarningRaised {
+                    source: "battery".to_string(),
+                    message: format!(
+                        "low battery: {}%, range {}",
+                        locale.number(ev.battery.state_of_charge() * 100.0, 0),
+                        locale.distance(ev.range(), 0)
+                    ),
+                },
+            );
+        }
+        // Like refueling, with margin for a step at top speed
+        if ev.battery.state_of_charge() < ev::CHARGE_BELOW || ev.range() < REFUEL_RANGE.max(Speed::from_kmh(max_speed).distance(hours)).km() {
+            sim_log::info("battery", &format!("Charging at {} kW", locale.number(ev::CHARGER_POWER, 0)));
+            ev.start_charging();
+            self.trips.reset(Trip::SinceRefuel);
+        }
+    }
+}
+
+fn log_reminder(reminder: &AnnualReminder, today: Date) {
+    let locale = locale::current();
+    let date = locale.date(today);
+    let message = match reminder.status(today) {
+        ReminderStatus::NotDue => return,
+        ReminderStatus::Upcoming(days) => {
+            let message = format!("{}: {} due in {} days ({}).", date, reminder.name, days, locale.date(reminder.due));
+            sim_log::info("reminder", &message);
+            return;
+        }
+        ReminderStatus::Due => format!("due today ({})", date),
+        ReminderStatus::Overdue(days) => format!("overdue by {} days ({})", days, date),
+    };
+    sim_log::event(
+        None,
+        &Event::WarningRaised {
+            source: reminder.name.clone(),
+            message,
+        },
+    );
+}
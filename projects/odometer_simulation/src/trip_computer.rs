@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::locale;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trip {
+    A,
+    B,
+    SinceRefuel,
+}
+
+impl Trip {
+    pub const ALL: [Trip; 3] = [Trip::A, Trip::B, Trip::SinceRefuel];
+
+    pub fn parse(name: &str) -> Option<Trip> {
+        match name.trim().to_lowercase().as_str() {
+            "a" => Some(Trip::A),
+            "b" => Some(Trip::B),
+            "refuel" | "since-refuel" => Some(Trip::SinceRefuel),
+            _ => None,
+        }
+    }
+}
+
+// One resettable counter; `consumed` is liters, or kWh for an EV
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TripCounter {
+    pub distance: f64, // km
+    pub hours: f64,
+    pub consumed: f64,
+}
+
+impl TripCounter {
+    pub fn average_speed(&self) -> f64 {
+        if self.hours > 0.0 {
+            self.distance / self.hours
+        } else {
+            0.0
+        }
+    }
+
+    // Per 100 km
+    pub fn average_consumption(&self) -> f64 {
+        if self.distance > 0.0 {
+            self.consumed / self.distance * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// Trip A, trip B and the counter since the last refuel (or charge), all fed
+// the same driving and each reset on its own
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TripComputer {
+    pub electric: bool,
+    a: TripCounter,
+    b: TripCounter,
+    since_refuel: TripCounter,
+}
+
+impl TripComputer {
+    pub fn record(&mut self, distance: f64, hours: f64, consumed: f64) {
+        for counter in [&mut self.a, &mut self.b, &mut self.since_refuel] {
+            counter.distance += distance;
+            counter.hours += hours;
+            counter.consumed += consumed;
+        }
+    }
+
+    pub fn trip(&self, trip: Trip) -> &TripCounter {
+        match trip {
+            Trip::A => &self.a,
+            Trip::B => &self.b,
+            Trip::SinceRefuel => &self.since_refuel,
+        }
+    }
+
+    pub fn reset(&mut self, trip: Trip) {
+        match trip {
+            Trip::A => self.a = TripCounter::default(),
+            Trip::B => self.b = TripCounter::default(),
+            Trip::SinceRefuel => self.since_refuel = TripCounter::default(),
+        }
+    }
+
+    fn name(&self, trip: Trip) -> &'static str {
+        match trip {
+            Trip::A => "Trip A",
+            Trip::B => "Trip B",
+            Trip::SinceRefuel if self.electric => "Since charge",
+            Trip::SinceRefuel => "Since refuel",
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let locale = locale::current();
+        let unit = if self.electric { "kWh/100 km" } else { "l/100 km" };
+        Trip::ALL
+            .iter()
+            .map(|&trip| {
+                let counter = self.trip(trip);
+                let minutes = (counter.hours * 60.0).round() as u64;
+                format!(
+                    "{:<13} {} | {}:{:02} h | {} | {} {}",
+                    self.name(trip),
+                    locale.distance(counter.distance, 1),
+                    minutes / 60,
+                    minutes % 60,
+                    locale.speed(counter.average_speed(), 1),
+                    locale.number(counter.average_consumption(), 2),
+                    unit
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_reset_independently() {
+        let mut computer = TripComputer::default();
+        computer.record(100.0, 1.0, 6.0);
+        computer.reset(Trip::A);
+        computer.record(50.0, 1.0, 4.0);
+
+        assert_eq!(computer.trip(Trip::A).distance, 50.0);
+        assert_eq!(computer.trip(Trip::B).distance, 150.0);
+        assert_eq!(computer.trip(Trip::SinceRefuel).average_speed(), 75.0);
+        assert_eq!(computer.trip(Trip::A).average_consumption(), 8.0);
+    }
+}
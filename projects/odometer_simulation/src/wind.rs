@@ -0,0 +1,65 @@
+use rand::Rng;
+use vehicle_sim_core::scenario::Wind;
+
+use crate::ev::DRAG_CONSUMPTION;
+
+// Gusts come and go within seconds; this many an hour in gusty wind
+const GUSTS_PER_HOUR: f64 = 12.0;
+const KMH_PER_MS: f64 = 3.6;
+
+// Headwind in km/h for a vehicle heading `heading` degrees
+pub fn headwind(wind: &Wind, heading: f64) -> f64 {
+    wind.components(heading).0 * KMH_PER_MS
+}
+
+// Wh/km the drag grows by at `speed` km/h against `headwind` km/h, negative
+// with the wind from behind; drag works against the speed through the air
+pub fn extra_drag(speed: f64, headwind: f64) -> f64 {
+    let airspeed = speed + headwind;
+    DRAG_CONSUMPTION * (airspeed * airspeed.abs() - speed * speed)
+}
+
+// Peak wind speed and its component across the road, in m/s
+pub struct Gust {
+    pub speed: f64,
+    pub crosswind: f64,
+}
+
+// The strongest gust during a step of `hours`, if one came
+pub fn gust(wind: &Wind, heading: f64, hours: f64, rng: &mut impl Rng) -> Option<Gust> {
+    if wind.gusts <= 0.0 || !rng.gen_bool(1.0 - (-GUSTS_PER_HOUR * hours).exp()) {
+        return None;
+    }
+    let peak = Wind {
+        speed: wind.speed + wind.gusts * rng.gen_range(0.5..=1.0),
+        ..*wind
+    };
+    Some(Gust {
+        speed: peak.speed,
+        crosswind: peak.components(heading).1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headwind_adds_drag_and_tailwind_takes_it() {
+        let wind = Wind {
+            speed: 10.0,
+            direction: 0.0,
+            gusts: 0.0,
+        };
+        // Driving north into a northerly, then south with it behind
+        let against = headwind(&wind, 0.0);
+        let behind = headwind(&wind, 180.0);
+        assert!((against - 36.0).abs() < 1e-9);
+        assert!((behind + 36.0).abs() < 1e-9);
+        assert!(extra_drag(100.0, against) > 0.0);
+        assert!(extra_drag(100.0, behind) < 0.0);
+        // Pure crosswind leaves the drag as it is
+        assert!(extra_drag(100.0, headwind(&wind, 90.0)).abs() < 1e-9);
+        assert!((wind.components(90.0).1 + 10.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+// Four steel discs of 7 kg each, in J/K
+const HEAT_CAPACITY: f32 = 4.0 * 7.0 * 460.0;
+// Heat the discs give off per kelvin above the ambient air, standing still
+// and per m/s of airflow, in W/K
+const COOLING_AT_REST: f32 = 8.0;
+const COOLING_PER_SPEED: f32 = 2.5;
+// The pads start to glaze above FADE_ONSET and are down to MIN_FADE of
+// their friction at FADE_FULL, in °C
+pub const FADE_ONSET: f32 = 400.0;
+const FADE_FULL: f32 = 700.0;
+const MIN_FADE: f32 = 0.5;
+
+// Temperature of the brake discs, heated by the energy the brakes take out
+// of the vehicle and cooled by the air flowing past
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BrakeDiscs {
+    pub temperature: f32, // °C
+}
+
+impl Default for BrakeDiscs {
+    fn default() -> Self {
+        BrakeDiscs { temperature: 20.0 }
+    }
+}
+
+impl BrakeDiscs {
+    // `energy` J went into the discs during `dt` s at `speed` m/s; they
+    // cool exponentially towards `ambient` °C
+    pub fn step(&mut self, energy: f32, speed: f32, ambient: f32, dt: f32) {
+        let heated = self.temperature + energy.max(0.0) / HEAT_CAPACITY;
+        let rate = (COOLING_AT_REST + COOLING_PER_SPEED * speed.max(0.0)) / HEAT_CAPACITY;
+        self.temperature = ambient + (heated - ambient) * (-rate * dt).exp();
+    }
+
+    // Share of their cold friction the pads still have
+    pub fn fade(&self) -> f32 {
+        let glazing = ((self.temperature - FADE_ONSET) / (FADE_FULL - FADE_ONSET)).clamp(0.0, 1.0);
+        1.0 - glazing * (1.0 - MIN_FADE)
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.temperature > FADE_ONSET
+    }
+}
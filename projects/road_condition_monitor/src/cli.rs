@@ -0,0 +1,362 @@
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use vehicle_sim_core::batch;
+use vehicle_sim_core::clock;
+use vehicle_sim_core::config::LogLevel;
+use vehicle_sim_core::drive_cycle::DriveCycle;
+use vehicle_sim_core::driver_model::SpeedProfile;
+use vehicle_sim_core::sim_plot::Theme;
+use vehicle_sim_core::sim_log::LogOptions;
+
+use crate::cruise_control;
+use crate::road_condition::RoadCondition;
+use crate::weather::WeatherTransitions;
+
+#[derive(Parser, Debug)]
+#[command(name = "road_condition_monitor", about = "Simulates road conditions, traction and braking")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Number of simulation steps to run (runs until Ctrl-C when omitted)
+    #[arg(long)]
+    pub iterations: Option<u64>,
+
+    /// Run this many steps as fast as possible without reports, logging to
+    /// the console or charts and only print the run statistics
+    #[arg(long, value_parser = batch::parse_steps, conflicts_with = "iterations")]
+    pub batch: Option<u64>,
+
+    /// Simulation step in seconds
+    #[arg(long, default_value_t = 5.0)]
+    pub interval: f64,
+
+    /// Run as fast as possible instead of following wall-clock time
+    /// (same as --realtime-factor 0)
+    #[arg(long)]
+    pub fast: bool,
+
+    /// Simulated seconds per wall-clock second, 0 for as fast as possible
+    /// [default: 1]
+    #[arg(long, value_parser = clock::parse_realtime_factor)]
+    pub realtime_factor: Option<f64>,
+
+    /// Weather Markov chain: rows for Dry, Wet and Icy, e.g. 0.9,0.08,0.02;0.1,0.85,0.05;0.02,0.18,0.8
+    #[arg(long, value_parser = WeatherTransitions::parse)]
+    pub weather_transitions: Option<WeatherTransitions>,
+
+    /// Ambient temperature at the start of the run in °C [default: 2]
+    #[arg(long, allow_negative_numbers = true)]
+    pub ambient_temperature: Option<f32>,
+
+    /// Tire inflation pressure in kPa, which sets the speed the tires start
+    /// aquaplaning at [default: 230]
+    #[arg(long)]
+    pub tire_pressure: Option<f32>,
+
+    /// Drive a speed trace of an urban, highway or mixed cycle, with
+    /// acceleration limits and traffic lights, instead of a random walk
+    #[arg(long, value_parser = SpeedProfile::parse)]
+    pub speed_profile: Option<SpeedProfile>,
+
+    /// Drive a standard cycle (nedc, wltp, ftp75) or a CSV file of
+    /// time_s,speed_kmh over and over instead of a random walk
+    #[arg(long, value_parser = DriveCycle::parse, conflicts_with = "speed_profile")]
+    pub drive_cycle: Option<DriveCycle>,
+
+    /// Hold this speed in km/h with the cruise control instead of a random
+    /// walk; how fast it gets there depends on the grip of the road
+    #[arg(long, conflicts_with_all = ["speed_profile", "drive_cycle"])]
+    pub cruise: Option<f64>,
+
+    /// Scenario file (TOML) with the starting conditions, run length, seed
+    /// and weather timeline; flags given as well take precedence
+    #[arg(long)]
+    pub scenario: Option<PathBuf>,
+
+    /// Write a checkpoint of the run to this JSON file
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+
+    /// Continue a run from a checkpoint written with --save
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+
+    /// Random seed, to reproduce a run
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Calibration file, or a directory of versions of which the newest is used
+    #[arg(long)]
+    pub calibration: Option<PathBuf>,
+
+    /// Write the signals and parameters the simulation exposes to this file
+    /// (JSON, or ASAP2 text for .a2l) and exit
+    #[arg(long)]
+    pub describe: Option<PathBuf>,
+
+    /// UDP address to serve XCP measurement and calibration on (e.g. 127.0.0.1:5555)
+    #[arg(long)]
+    pub xcp: Option<String>,
+
+    /// MQTT broker to publish telemetry to (host[:port]); credentials come
+    /// from SIM_MQTT_USER and SIM_MQTT_PASSWORD
+    #[cfg(feature = "mqtt")]
+    #[arg(long)]
+    pub mqtt: Option<String>,
+
+    /// Privacy settings the MQTT and dashboard telemetry is filtered with
+    /// before it leaves the vehicle
+    #[arg(long)]
+    pub privacy: Option<PathBuf>,
+
+    /// Write every CAN frame to this file: candump log, or Vector ASC for .asc
+    #[arg(long)]
+    pub can_trace: Option<PathBuf>,
+
+    /// Colors of the pedal map chart: light or dark [default: SIM_PLOT_THEME, then light]
+    #[arg(long, value_parser = Theme::parse)]
+    pub plot_theme: Option<Theme>,
+
+    /// Most verbose log level printed: error, warn, info or debug
+    #[arg(long, value_parser = parse_log_level)]
+    pub log_level: Option<LogLevel>,
+
+    /// Also write the log as plain text to this file
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Also write the log as JSON lines to this file
+    #[arg(long)]
+    pub log_json: Option<PathBuf>,
+
+    /// Print a report after every step instead of showing the terminal
+    /// dashboard (the default when stdout is not a terminal)
+    #[arg(long)]
+    pub plain: bool,
+
+    /// Address to serve the live dashboard on (e.g. 127.0.0.1:8080)
+    #[cfg(feature = "dashboard")]
+    #[arg(long, alias = "serve")]
+    pub dashboard: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the simulation many times in parallel, each run with its own seed,
+    /// starting speed and tire wear, and write the outcomes to a CSV file
+    Sweep {
+        /// Number of runs
+        #[arg(long, default_value_t = 200)]
+        runs: u64,
+
+        /// Simulation steps per run
+        #[arg(long, default_value_t = 500)]
+        steps: u64,
+
+        /// Range the starting speed of a run is drawn from, in km/h
+        #[arg(long, default_value = "30..130", value_parser = parse_range)]
+        speed: (f64, f64),
+
+        /// Range the tire wear of a run is drawn from, in tire condition lost
+        /// per step at most
+        #[arg(long, default_value = "0.005..0.04", value_parser = parse_range)]
+        tire_wear: (f64, f64),
+
+        /// Range the ambient temperature at the start of a run is drawn
+        /// from, in °C; defaults to --ambient-temperature for every run
+        #[arg(long, value_parser = parse_signed_range, allow_hyphen_values = true)]
+        temperature: Option<(f64, f64)>,
+
+        /// CSV file with a row per run
+        #[arg(long, default_value = "sweep.csv")]
+        csv: PathBuf,
+    },
+
+    /// Knock a towed trailer into sway and compare how it develops with and
+    /// without the sway mitigation braking, as a chart and a summary
+    Sway {
+        /// Mass of the trailer in kg
+        #[arg(long, default_value_t = 1800.0)]
+        trailer_mass: f64,
+
+        /// Speed of the combination in km/h
+        #[arg(long, default_value_t = 110.0)]
+        speed: f64,
+
+        /// Hitch angle the trailer is knocked to, in degrees
+        #[arg(long, default_value_t = 2.0)]
+        kick: f64,
+
+        /// Simulated time in seconds
+        #[arg(long, default_value_t = 30.0)]
+        duration: f64,
+
+        /// Road the combination drives on: dry, wet or icy
+        #[arg(long, default_value = "dry", value_parser = parse_condition)]
+        condition: RoadCondition,
+
+        /// Path of the chart: PNG, or SVG for a .svg path
+        #[arg(long, default_value = "trailer_sway.png")]
+        output: PathBuf,
+    },
+
+    /// Follow a car that brakes hard and pulls away again with the adaptive
+    /// cruise control, as a chart of speeds and gaps and a summary
+    Follow {
+        /// Set speed of the cruise control in km/h
+        #[arg(long, default_value_t = 120.0)]
+        set_speed: f64,
+
+        /// Cruising speed of the car ahead in km/h
+        #[arg(long, default_value_t = 90.0)]
+        lead_speed: f64,
+
+        /// Gap to keep to the car ahead, in seconds at the current speed
+        #[arg(long, default_value_t = cruise_control::DEFAULT_TIME_HEADWAY)]
+        time_headway: f64,
+
+        /// Seconds to simulate
+        #[arg(long, default_value_t = 100.0)]
+        duration: f64,
+
+        /// Road both cars drive on: dry, wet or icy
+        #[arg(long, default_value = "dry", value_parser = parse_condition)]
+        condition: RoadCondition,
+
+        /// Path of the chart: PNG, or SVG for a .svg path
+        #[arg(long, default_value = "adaptive_cruise.png")]
+        output: PathBuf,
+    },
+
+    /// Measure parking slots of random length with the ultrasonic sensors,
+    /// plan a parallel parking maneuver and back in, counting the outcomes
+    Park {
+        /// Number of parking attempts
+        #[arg(long, default_value_t = 200)]
+        runs: u64,
+
+        /// Range the slot length is drawn from, in m
+        #[arg(long, default_value = "6..9", value_parser = parse_range)]
+        slot: (f64, f64),
+
+        /// Path of the chart: PNG, or SVG for a .svg path
+        #[arg(long, default_value = "parking.png")]
+        output: PathBuf,
+    },
+}
+
+impl Cli {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval <= 0.0 {
+            return Err("--interval must be positive".to_string());
+        }
+        if self.iterations == Some(0) {
+            return Err("--iterations must be at least 1".to_string());
+        }
+        if let Some(Command::Sweep { runs: 0, .. } | Command::Sweep { steps: 0, .. }) = &self.command {
+            return Err("sweep --runs and --steps must be at least 1".to_string());
+        }
+        if let Some(Command::Park { runs: 0, .. }) = &self.command {
+            return Err("park --runs must be at least 1".to_string());
+        }
+        if let Some(Command::Sway {
+            trailer_mass,
+            speed,
+            duration,
+            ..
+        }) = &self.command
+        {
+            if *trailer_mass <= 0.0 || *duration <= 0.0 || *speed < 0.0 {
+                return Err("sway needs a positive --trailer-mass and --duration and a --speed >= 0".to_string());
+            }
+        }
+        if let Some(Command::Follow {
+            set_speed,
+            lead_speed,
+            time_headway,
+            duration,
+            ..
+        }) = &self.command
+        {
+            if *set_speed <= 0.0 || *lead_speed <= 0.0 || *time_headway <= 0.0 || *duration <= 0.0 {
+                return Err("follow needs a positive --set-speed, --lead-speed, --time-headway and --duration".to_string());
+            }
+        }
+        if self.cruise.is_some_and(|speed| speed <= 0.0) {
+            return Err("--cruise must be positive".to_string());
+        }
+        if self.tire_pressure.is_some_and(|pressure| pressure <= 0.0) {
+            return Err("--tire-pressure must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn iterations(&self) -> Option<u64> {
+        self.batch.or(self.iterations)
+    }
+
+    // --realtime-factor, --fast, then SIM_REALTIME_FACTOR; batch runs never
+    // wait for the wall clock
+    pub fn realtime_factor(&self) -> f64 {
+        match self.realtime_factor {
+            _ if self.batch.is_some() => 0.0,
+            Some(factor) => factor,
+            None if self.fast => 0.0,
+            None => clock::realtime_factor_from_args().unwrap_or(1.0),
+        }
+    }
+
+    // The terminal dashboard replaces the scrolling reports when someone
+    // is watching
+    pub fn interactive(&self) -> bool {
+        !self.plain && self.batch.is_none() && self.describe.is_none() && io::stdout().is_terminal()
+    }
+
+    // Flags take precedence over the SIM_LOG_* variables. The terminal
+    // dashboard owns the screen and batch runs and sweeps print only their
+    // statistics, so the log then only goes to files.
+    pub fn log_options(&self) -> LogOptions {
+        let options = LogOptions::from_env();
+        LogOptions {
+            level: self.log_level.unwrap_or(options.level),
+            file: self.log_file.clone().or(options.file),
+            json: self.log_json.clone().or(options.json),
+            console: !self.interactive() && self.batch.is_none() && self.command.is_none(),
+            ..options
+        }
+    }
+}
+
+fn parse_range(value: &str) -> Result<(f64, f64), String> {
+    let (min, max) = parse_signed_range(value)?;
+    if min < 0.0 {
+        return Err(format!("range must satisfy 0 <= MIN <= MAX, got {}..{}", min, max));
+    }
+    Ok((min, max))
+}
+
+// Like `parse_range`, but below zero as well, e.g. `-8..5`
+fn parse_signed_range(value: &str) -> Result<(f64, f64), String> {
+    let (min, max) = value
+        .split_once("..")
+        .ok_or_else(|| format!("expected MIN..MAX, got '{}'", value))?;
+    let min: f64 = min.trim().parse().map_err(|_| format!("invalid minimum '{}'", min))?;
+    let max: f64 = max.trim().parse().map_err(|_| format!("invalid maximum '{}'", max))?;
+
+    if min > max {
+        return Err(format!("range must satisfy MIN <= MAX, got {}..{}", min, max));
+    }
+    Ok((min, max))
+}
+
+fn parse_condition(value: &str) -> Result<RoadCondition, String> {
+    RoadCondition::parse(value).ok_or_else(|| format!("unknown road condition {}: expected dry, wet or icy", value))
+}
+
+fn parse_log_level(value: &str) -> Result<LogLevel, String> {
+    LogLevel::parse(value).ok_or_else(|| format!("unknown log level {}: expected error, warn, info or debug", value))
+}
@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+
+use vehicle_sim_core::locale;
+
+use crate::road_condition::RoadCondition;
+use crate::vehicle::Vehicle;
+
+// Most the controller accelerates and brakes on its own, in m/s^2; harder
+// braking is left to the driver
+const MAX_ACCELERATION: f64 = 2.0;
+const MAX_DECELERATION: f64 = 3.5;
+// Acceleration per m/s the car is below the set speed, in 1/s
+const SPEED_GAIN: f64 = 0.4;
+// Acceleration per m the gap is longer than desired, in 1/s^2, and per m/s
+// the lead vehicle is faster, in 1/s
+const GAP_GAIN: f64 = 0.2;
+const RELATIVE_SPEED_GAIN: f64 = 0.7;
+// Gap kept to a stopped lead vehicle, in m
+pub const STANDSTILL_GAP: f64 = 4.0;
+// Gap in seconds at the current speed unless asked for another
+pub const DEFAULT_TIME_HEADWAY: f64 = 1.8;
+// The radar does not see further, in m
+const RADAR_RANGE: f64 = 150.0;
+
+// The lead vehicle's distance from our front bumper and its speed, in m
+// and m/s
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lead {
+    pub gap: f64,
+    pub speed: f64,
+}
+
+// What the controller asks for in a step and what the tires let it have
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CruiseCommand {
+    pub requested: f64, // m/s^2, negative when braking
+    pub acceleration: f64,
+    // The road could not take what was requested
+    pub traction_limited: bool,
+    // Controlling the gap rather than the set speed
+    pub following: bool,
+    // Avoiding the lead vehicle needs more braking than the controller
+    // can deliver: the driver has to take over
+    pub takeover: bool,
+}
+
+// Holds a set speed, and with a time headway (adaptive cruise control)
+// keeps a gap to a slower vehicle ahead that grows with the speed. The
+// tires limit both ways: the driven axle spins when asked for too much and
+// braking ends at the grip of the road.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CruiseControl {
+    pub set_speed: f64,            // km/h
+    pub time_headway: Option<f64>, // s
+}
+
+impl CruiseControl {
+    pub fn new(set_speed: f64) -> Self {
+        CruiseControl {
+            set_speed,
+            time_headway: None,
+        }
+    }
+
+    pub fn adaptive(set_speed: f64, time_headway: f64) -> Self {
+        CruiseControl {
+            set_speed,
+            time_headway: Some(time_headway),
+        }
+    }
+
+    // Gap the adaptive mode keeps at `speed` m/s
+    pub fn desired_gap(&self, speed: f64) -> f64 {
+        STANDSTILL_GAP + self.time_headway.unwrap_or(0.0) * speed
+    }
+
+    // Acceleration for the car at `speed` m/s; a lead vehicle only counts in
+    // the adaptive mode and within radar range
+    pub fn command(&self, speed: f64, lead: Option<Lead>, vehicle: &Vehicle, traction: f32) -> CruiseCommand {
+        let mut requested = SPEED_GAIN * (self.set_speed / 3.6 - speed);
+        let mut following = false;
+        let mut takeover = false;
+        let braking_authority = vehicle.achieved_deceleration(MAX_DECELERATION as f32, traction) as f64;
+        if let (Some(lead), Some(_)) = (lead.filter(|lead| lead.gap <= RADAR_RANGE), self.time_headway) {
+            let gap_control = GAP_GAIN * (lead.gap - self.desired_gap(speed)) + RELATIVE_SPEED_GAIN * (lead.speed - speed);
+            if gap_control < requested {
+                requested = gap_control;
+                following = true;
+            }
+            // Constant deceleration that matches the lead vehicle's speed
+            // at the standstill gap
+            let closing = speed - lead.speed;
+            if closing > 0.0 {
+                let needed = closing * closing / (2.0 * (lead.gap - STANDSTILL_GAP).max(0.1));
+                takeover = needed > braking_authority;
+            }
+        }
+        let requested = requested.clamp(-MAX_DECELERATION, MAX_ACCELERATION);
+
+        let acceleration = if requested >= 0.0 {
+            requested.min(vehicle.drive_limit(traction) as f64)
+        } else {
+            -(vehicle.achieved_deceleration(-requested as f32, traction) as f64)
+        };
+        CruiseCommand {
+            requested,
+            acceleration,
+            traction_limited: (acceleration - requested).abs() > 1e-3,
+            following,
+            takeover,
+        }
+    }
+}
+
+// Lead vehicle of the follow scenario: starts ahead at the given speed,
+// brakes hard to a crawl, picks up speed again and finally changes lanes
+const LEAD_START_GAP: f64 = 80.0; // m
+const LEAD_BRAKES_AT: f64 = 20.0; // s
+const LEAD_DECELERATION: f32 = 5.0; // m/s^2
+// Share of its speed the lead vehicle slows down to
+const LEAD_SLOW_SHARE: f64 = 0.3;
+const LEAD_RESUMES_AT: f64 = 40.0; // s
+const LEAD_ACCELERATION: f64 = 1.5; // m/s^2
+const LEAD_LEAVES_AT: f64 = 70.0; // s
+// Integration step, in seconds
+const DT: f64 = 0.05;
+
+// Adaptive cruise control behind a lead vehicle on a road in `condition`
+pub struct FollowScenario {
+    pub set_speed: f64,  // km/h
+    pub lead_speed: f64, // km/h
+    pub time_headway: f64,
+    pub duration: f64, // s
+    pub condition: RoadCondition,
+}
+
+#[derive(Debug, Default)]
+pub struct FollowRun {
+    pub time: Vec<f64>,
+    pub speed: Vec<f64>, // km/h
+    // Speed of and gap to the lead vehicle while it is in our lane
+    pub lead_speed: Vec<Option<f64>>,
+    pub gap: Vec<Option<f64>>,
+    pub desired_gap: Vec<Option<f64>>,
+    pub acceleration: Vec<f64>, // m/s^2
+    pub min_gap: Option<f64>,
+    // Shortest gap in seconds at our speed, while moving
+    pub min_time_gap: Option<f64>,
+    pub traction_limited_seconds: f64,
+    pub takeover_requests: u64,
+    pub collided_after: Option<f64>,
+}
+
+pub fn simulate(scenario: &FollowScenario) -> FollowRun {
+    let vehicle = Vehicle::new();
+    let traction = vehicle.adjust_for_condition(scenario.condition.traction());
+    let cruise = CruiseControl::adaptive(scenario.set_speed, scenario.time_headway);
+    let lead_deceleration = vehicle.achieved_deceleration(LEAD_DECELERATION, traction) as f64;
+    let lead_cruising = scenario.lead_speed / 3.6;
+
+    let mut run = FollowRun::default();
+    let mut speed = lead_cruising;
+    let (mut lead_position, mut lead_speed) = (LEAD_START_GAP, lead_cruising);
+    let mut position = 0.0;
+    let mut takeover = false;
+
+    let steps = (scenario.duration / DT).round() as u64;
+    for step in 0..=steps {
+        let time = step as f64 * DT;
+        let lead = (time < LEAD_LEAVES_AT).then_some(Lead {
+            gap: lead_position - position,
+            speed: lead_speed,
+        });
+        if let Some(lead) = lead {
+            if lead.gap <= 0.0 {
+                run.collided_after = Some(time);
+                return run;
+            }
+            run.min_gap = Some(run.min_gap.map_or(lead.gap, |gap: f64| gap.min(lead.gap)));
+            if speed > 1.0 {
+                let time_gap = lead.gap / speed;
+                run.min_time_gap = Some(run.min_time_gap.map_or(time_gap, |gap: f64| gap.min(time_gap)));
+            }
+        }
+
+        let command = cruise.command(speed, lead, &vehicle, traction);
+        if command.takeover && !takeover {
+            run.takeover_requests += 1;
+        }
+        takeover = command.takeover;
+        if command.traction_limited {
+            run.traction_limited_seconds += DT;
+        }
+
+        run.time.push(time);
+        run.speed.push(speed * 3.6);
+        run.lead_speed.push(lead.map(|lead| lead.speed * 3.6));
+        run.gap.push(lead.map(|lead| lead.gap));
+        run.desired_gap.push(lead.map(|_| cruise.desired_gap(speed)));
+        run.acceleration.push(command.acceleration);
+
+        let (lead_acceleration, lead_target) = if time < LEAD_BRAKES_AT {
+            (0.0, lead_cruising)
+        } else if time < LEAD_RESUMES_AT {
+            (-lead_deceleration, lead_cruising * LEAD_SLOW_SHARE)
+        } else {
+            (LEAD_ACCELERATION, lead_cruising)
+        };
+        lead_speed = if lead_acceleration < 0.0 {
+            (lead_speed + lead_acceleration * DT).max(lead_target)
+        } else {
+            (lead_speed + lead_acceleration * DT).min(lead_target)
+        };
+        lead_position += lead_speed * DT;
+        speed = (speed + command.acceleration * DT).max(0.0);
+        position += speed * DT;
+    }
+    run
+}
+
+pub fn print_summary(scenario: &FollowScenario, run: &FollowRun) {
+    let locale = locale::current();
+    println!(
+        "Adaptive cruise at {} with a {} s time headway on the {} road, behind a car at {}",
+        locale.speed(scenario.set_speed, 0),
+        locale.number(scenario.time_headway, 1),
+        format!("{:?}", scenario.condition).to_lowercase(),
+        locale.speed(scenario.lead_speed, 0)
+    );
+    let gap = |gap: Option<f64>| gap.map_or("-".to_string(), |gap| locale.length(gap, 1));
+    let time_gap = run.min_time_gap.map_or("-".to_string(), |gap| format!("{} s", locale.number(gap, 2)));
+    println!("Shortest gap: {}, shortest time gap: {}", gap(run.min_gap), time_gap);
+    println!(
+        "Traction limited the controller for {} s, {} takeover requests",
+        locale.number(run.traction_limited_seconds, 1),
+        run.takeover_requests
+    );
+    match run.collided_after {
+        Some(time) => println!("Ran into the lead vehicle after {} s", locale.number(time, 1)),
+        None => println!(
+            "Ended at {} after {} s",
+            locale.speed(run.speed.last().cloned().unwrap_or(scenario.lead_speed), 0),
+            locale.number(scenario.duration, 0)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(condition: RoadCondition) -> FollowScenario {
+        FollowScenario {
+            set_speed: 120.0,
+            lead_speed: 90.0,
+            time_headway: DEFAULT_TIME_HEADWAY,
+            duration: 100.0,
+            condition,
+        }
+    }
+
+    #[test]
+    fn set_speed_is_held_on_a_free_road() {
+        let vehicle = Vehicle::new();
+        let cruise = CruiseControl::new(100.0);
+        let mut speed: f64 = 80.0 / 3.6;
+        for _ in 0..2000 {
+            speed += cruise.command(speed, None, &vehicle, 1.0).acceleration * DT;
+        }
+        assert!((speed * 3.6 - 100.0).abs() < 0.5);
+
+        // Plain cruise control does not look at the road ahead
+        let lead = Lead { gap: 10.0, speed: 0.0 };
+        assert!(!cruise.command(speed, Some(lead), &vehicle, 1.0).following);
+    }
+
+    #[test]
+    fn follows_the_lead_vehicle_and_resumes_the_set_speed() {
+        let dry = scenario(RoadCondition::Dry);
+        let run = simulate(&dry);
+        assert!(run.collided_after.is_none());
+        assert!(run.min_gap.unwrap() > STANDSTILL_GAP);
+
+        // Settled behind the lead vehicle at its speed before it brakes...
+        let before_braking = run.time.iter().position(|&time| time >= LEAD_BRAKES_AT).unwrap();
+        assert!((run.speed[before_braking] - dry.lead_speed).abs() < 2.0);
+        let gap = run.gap[before_braking].unwrap();
+        assert!((gap - run.desired_gap[before_braking].unwrap()).abs() < 3.0);
+
+        // ...and back at the set speed once it has left the lane
+        assert!((run.speed.last().unwrap() - dry.set_speed).abs() < 1.0);
+    }
+
+    #[test]
+    fn traction_caps_the_acceleration_on_ice() {
+        let dry = simulate(&scenario(RoadCondition::Dry));
+        let icy = simulate(&scenario(RoadCondition::Icy));
+        assert!(icy.collided_after.is_none());
+        assert!(icy.traction_limited_seconds > dry.traction_limited_seconds);
+
+        let vehicle = Vehicle::new();
+        let traction = vehicle.adjust_for_condition(RoadCondition::Icy.traction());
+        let limit = vehicle.drive_limit(traction) as f64;
+        assert!(icy.acceleration.iter().all(|&a| a <= limit + 1e-9));
+        assert!(icy.acceleration.iter().any(|&a| (a - limit).abs() < 1e-9));
+    }
+}
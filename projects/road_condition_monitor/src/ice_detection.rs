@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::units::Celsius;
+
+// Below this ambient temperature a slipping wheel is taken for ice, in °C
+pub const ICE_RISK_TEMPERATURE: Celsius = 3.0;
+// A brake application up to this deceleration does not slip on a dry or wet
+// road with fair tires, so slip below it points at a slippery surface, in m/s^2
+pub const SLIP_CHECK_DECELERATION: f32 = 4.0;
+// Slip events within the last `WINDOW` steps that raise the warning
+const SLIP_EVENTS_FOR_WARNING: usize = 2;
+const WINDOW: usize = 6;
+
+// Raises a road-ice warning when the air is cold enough for ice and the
+// wheels keep slipping under light braking; one slip alone may be a patch of
+// leaves or a worn tire. The warning clears once it warms up or the wheels
+// have gripped for the whole window.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IceDetector {
+    recent_slips: VecDeque<bool>,
+    warning: bool,
+}
+
+impl IceDetector {
+    // Whether the wheels slipped under a brake application that should have
+    // gripped
+    pub fn slip_event(requested_deceleration: f32, slip_detected: bool) -> bool {
+        slip_detected && requested_deceleration <= SLIP_CHECK_DECELERATION
+    }
+
+    // Feeds one step and returns whether the warning is up afterwards
+    pub fn update(&mut self, ambient_temperature: Celsius, slip_event: bool) -> bool {
+        self.recent_slips.push_back(slip_event);
+        if self.recent_slips.len() > WINDOW {
+            self.recent_slips.pop_front();
+        }
+
+        let slips = self.recent_slips.iter().filter(|&&slip| slip).count();
+        if ambient_temperature >= ICE_RISK_TEMPERATURE || slips == 0 {
+            self.warning = false;
+        } else if slips >= SLIP_EVENTS_FOR_WARNING {
+            self.warning = true;
+        }
+        self.warning
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_slip_in_the_cold_raises_the_warning_until_the_wheels_grip() {
+        let mut detector = IceDetector::default();
+        assert!(!detector.update(1.0, true));
+        assert!(detector.update(1.0, true));
+        // One step of grip does not clear it, a whole window does
+        for _ in 1..WINDOW {
+            assert!(detector.update(1.0, false));
+        }
+        assert!(!detector.update(1.0, false));
+
+        // Slipping above the ice temperature is not ice
+        let mut warm = IceDetector::default();
+        for _ in 0..WINDOW {
+            assert!(!warm.update(ICE_RISK_TEMPERATURE, true));
+        }
+
+        // Locking up under hard braking is not a slip event
+        assert!(!IceDetector::slip_event(8.0, true));
+        assert!(IceDetector::slip_event(2.0, true));
+    }
+}
@@ -1,10 +1,214 @@
+mod brakes;
+mod cli;
+mod cruise_control;
+mod ice_detection;
 mod vehicle;
 mod road_condition;
 mod simulation;
+mod stability;
+mod sweep;
+mod parking;
+mod pedal_map;
+mod plot;
+mod trailer;
+mod tui;
+mod weather;
 
+use clap::Parser;
+use cli::{Cli, Command};
+use cruise_control::FollowScenario;
+use pedal_map::{PedalCurve, PedalMap};
+use road_condition::RoadCondition;
 use simulation::run_simulation;
+use stability::StabilityThresholds;
+use sweep::SweepOptions;
+use trailer::{SwayScenario, Trailer};
+use vehicle_sim_core::calibration::{self, CalibrationSet};
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::scenario::{self, Scenario};
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::sim_plot::Theme;
 
 fn main() {
-    println!("Starting Advanced Road Condition Simulator...");
-    run_simulation();
+    let cli = Cli::parse();
+    if let Err(e) = cli.validate() {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = sim_log::init(&cli.log_options()) {
+        eprintln!("Cannot open log file: {}", e);
+        std::process::exit(1);
+    }
+
+    if cli.batch.is_none() {
+        println!("Starting Advanced Road Condition Simulator...");
+    }
+
+    // `--scenario <path>` or SIM_SCENARIO sets the starting weather, run
+    // length, seed and a weather timeline
+    let scenario = cli.scenario.clone().or_else(scenario::path_from_args).map(|path| {
+        let scenario = Scenario::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read scenario {}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        let conditions = scenario.initial_str("condition").into_iter();
+        if let Some(condition) = conditions
+            .chain(scenario.weather.iter().filter_map(|point| point.condition.as_deref()))
+            .find(|condition| RoadCondition::parse(condition).is_none())
+        {
+            eprintln!("Unknown road condition {} in {}, expected dry, wet or icy", condition, path.display());
+            std::process::exit(1);
+        }
+        scenario.announce(&["ambient_temperature", "condition"], false, true, false, false);
+        scenario
+    });
+
+    // `--calibration <path>` or SIM_CALIBRATION holds the pedal map
+    let calibrations = cli.calibration.clone().or_else(calibration::path_from_args).map(|path| {
+        CalibrationSet::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read calibration {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    let calibration = calibrations.as_ref().map(CalibrationSet::active);
+    if let Some(calibration) = calibration {
+        println!("Calibration: {}", calibration);
+    }
+
+    // Pedal feel can be overridden with PEDAL_CURVE=comfort|sport|<custom table>
+    let curve_setting = std::env::var("PEDAL_CURVE")
+        .ok()
+        .or_else(|| calibration.and_then(|c| c.get("pedal_curve")).map(str::to_string));
+    let curve = match &curve_setting {
+        Some(value) => PedalCurve::parse(value).unwrap_or_else(|e| {
+            eprintln!("{}, falling back to the comfort curve", e);
+            PedalCurve::Comfort
+        }),
+        None => PedalCurve::Comfort,
+    };
+
+    // Full pedal travel requests 1 g of deceleration unless calibrated otherwise
+    let max_deceleration = calibration.and_then(|c| c.get_f64("max_deceleration")).map_or(9.81, |d| d as f32);
+
+    // What the simulation exposes, written by `--describe <path>` (which
+    // then exits) and served over `--xcp`
+    let thresholds = StabilityThresholds::from_calibration(calibration);
+    let description = pedal_map::describe(
+        curve_setting.as_deref().filter(|value| PedalCurve::parse(value).is_ok()).unwrap_or("comfort"),
+        max_deceleration,
+        thresholds,
+        calibration,
+    );
+    if let Some(path) = &cli.describe {
+        if let Err(e) = description.save(path) {
+            eprintln!("Cannot write description {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+        println!("Description written to {}", path.display());
+        return;
+    }
+
+    let pedal_map = PedalMap::new(curve, max_deceleration);
+
+    // `sweep` runs many short simulations with sampled parameters
+    if let Some(Command::Sweep {
+        runs,
+        steps,
+        speed,
+        tire_wear,
+        temperature,
+        csv,
+    }) = &cli.command
+    {
+        let ambient_temperature = cli.ambient_temperature.unwrap_or(2.0) as f64;
+        let options = SweepOptions {
+            runs: *runs,
+            steps: *steps,
+            interval: cli.interval,
+            speed: *speed,
+            tire_wear: *tire_wear,
+            transitions: cli.weather_transitions.unwrap_or_default(),
+            ambient_temperature: temperature.unwrap_or((ambient_temperature, ambient_temperature)),
+            csv: csv.clone(),
+        };
+        let mut rng = SimRng::from_seed_env_or(cli.seed, None);
+        if let Err(e) = sweep::run(&options, &pedal_map, &mut rng) {
+            eprintln!("Cannot write {}: {}", csv.display(), e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // `sway` compares trailer sway with and without the mitigation
+    if let Some(Command::Sway {
+        trailer_mass,
+        speed,
+        kick,
+        duration,
+        condition,
+        output,
+    }) = &cli.command
+    {
+        let scenario = SwayScenario {
+            trailer: Trailer { mass: *trailer_mass },
+            speed: *speed,
+            kick: *kick,
+            duration: *duration,
+            condition: *condition,
+        };
+        let unmitigated = trailer::simulate(&scenario, false);
+        let mitigated = trailer::simulate(&scenario, true);
+        trailer::print_comparison(&scenario, &unmitigated, &mitigated);
+
+        let theme = cli.plot_theme.unwrap_or_else(Theme::from_env);
+        match plot::plot_sway(output, theme, &unmitigated, &mitigated) {
+            Ok(()) => println!("Trailer sway chart written to {}", output.display()),
+            Err(e) => eprintln!("Failed to plot trailer sway: {}", e),
+        }
+        return;
+    }
+
+    // `follow` drives with the adaptive cruise control behind a braking car
+    if let Some(Command::Follow {
+        set_speed,
+        lead_speed,
+        time_headway,
+        duration,
+        condition,
+        output,
+    }) = &cli.command
+    {
+        let scenario = FollowScenario {
+            set_speed: *set_speed,
+            lead_speed: *lead_speed,
+            time_headway: *time_headway,
+            duration: *duration,
+            condition: *condition,
+        };
+        let run = cruise_control::simulate(&scenario);
+        cruise_control::print_summary(&scenario, &run);
+
+        let theme = cli.plot_theme.unwrap_or_else(Theme::from_env);
+        match plot::plot_follow(output, theme, &run) {
+            Ok(()) => println!("Adaptive cruise chart written to {}", output.display()),
+            Err(e) => eprintln!("Failed to plot adaptive cruise: {}", e),
+        }
+        return;
+    }
+
+    // `park` tries parallel parking in slots of random length
+    if let Some(Command::Park { runs, slot, output }) = &cli.command {
+        let mut rng = SimRng::from_seed_env_or(cli.seed, None);
+        let runs = parking::run_many(*runs, *slot, &mut rng);
+        parking::print_summary(&runs);
+
+        let theme = cli.plot_theme.unwrap_or_else(Theme::from_env);
+        match plot::plot_parking(output, theme, &runs) {
+            Ok(()) => println!("Parking chart written to {}", output.display()),
+            Err(e) => eprintln!("Failed to plot parking: {}", e),
+        }
+        return;
+    }
+
+    run_simulation(pedal_map, thresholds, &cli, scenario.as_ref(), &description);
 }
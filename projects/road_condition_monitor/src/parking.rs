@@ -0,0 +1,622 @@
+use std::f64::consts::FRAC_PI_2;
+
+use rand::Rng;
+use vehicle_sim_core::locale;
+use vehicle_sim_core::rng::SimRng;
+
+// Dimensions of the parking car around its rear axle, in m
+pub const WHEELBASE: f64 = 2.7;
+pub const FRONT_OVERHANG: f64 = 0.9;
+pub const REAR_OVERHANG: f64 = 0.9;
+pub const WIDTH: f64 = 1.8;
+pub const LENGTH: f64 = REAR_OVERHANG + WHEELBASE + FRONT_OVERHANG;
+// Steering lock, giving a turning radius of about 5 m at the rear axle
+const MAX_STEERING: f64 = 0.5; // rad
+
+// Ultrasonic sensors: range, beam half angle sampled by a few rays, and
+// noise of the echo time. Curbs lower than the detection height pass under
+// the beam.
+const SENSOR_RANGE: f64 = 4.5;
+const BEAM_HALF_ANGLE: f64 = 0.2; // rad
+const BEAM_RAYS: usize = 5;
+const SENSOR_NOISE: f64 = 0.02;
+const CURB_DETECTION_HEIGHT: f64 = 0.12;
+// (x, y, direction) on the car: the side sensor on the right of the front
+// bumper scans the slot, the bumper centers watch the gaps while maneuvering
+const SIDE_SENSOR: (f64, f64, f64) = (WHEELBASE + FRONT_OVERHANG - 0.3, -WIDTH / 2.0, -FRAC_PI_2);
+const REAR_SENSOR: (f64, f64, f64) = (-REAR_OVERHANG, 0.0, std::f64::consts::PI);
+const FRONT_SENSOR: (f64, f64, f64) = (WHEELBASE + FRONT_OVERHANG, 0.0, 0.0);
+
+// The car passes the parked cars with its rear axle this far from the curb
+const SCAN_Y: f64 = 3.6;
+const SCAN_STEP: f64 = 0.05;
+const MAX_SCAN_DISTANCE: f64 = 25.0;
+// A side echo closer than this is a parked car, farther the curb or nothing
+const OPEN_THRESHOLD: f64 = 1.5;
+
+// Margins the planner keeps to what it measured; without a curb echo it
+// lines up with the parked cars, taken to be this wide and this far from
+// the curb
+const CLEARANCE: f64 = 0.2;
+const CURB_CLEARANCE: f64 = 0.25;
+const CURB_MARGIN: f64 = 0.05;
+const ASSUMED_CAR_WIDTH: f64 = 1.85;
+const ASSUMED_CURB_GAP: f64 = 0.2;
+
+// Wheel odometry is off by up to this share of the distance
+const ODOMETRY_ERROR: f64 = 0.03;
+const STEP: f64 = 0.02;
+// The maneuver stops when a bumper sensor reads less than this
+const STOP_DISTANCE: f64 = 0.2;
+// A parked car stands straight and close to the curb
+const MAX_HEADING_ERROR: f64 = 0.05; // rad, about 3°
+const MAX_CURB_GAP: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Parked,
+    // The planner found no maneuver that fits the measured slot
+    SlotTooSmall,
+    CurbContact,
+    Collision,
+    // Stopped crooked or far from the curb
+    Misaligned,
+}
+
+impl Outcome {
+    pub const ALL: [Outcome; 5] = [
+        Outcome::Parked,
+        Outcome::SlotTooSmall,
+        Outcome::CurbContact,
+        Outcome::Collision,
+        Outcome::Misaligned,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::Parked => "parked",
+            Outcome::SlotTooSmall => "slot too small",
+            Outcome::CurbContact => "curb contact",
+            Outcome::Collision => "collision",
+            Outcome::Misaligned => "misaligned",
+        }
+    }
+}
+
+// Axis-aligned box, (x, y) corners
+#[derive(Debug, Clone, Copy)]
+pub struct Rect {
+    pub min: (f64, f64),
+    pub max: (f64, f64),
+}
+
+impl Rect {
+    fn inflate(self, margin: f64) -> Rect {
+        Rect {
+            min: (self.min.0 - margin, self.min.1 - margin),
+            max: (self.max.0 + margin, self.max.1 + margin),
+        }
+    }
+
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        [self.min, (self.max.0, self.min.1), self.max, (self.min.0, self.max.1)]
+    }
+}
+
+// Rear axle center and heading; x runs along the curb at y = 0
+#[derive(Debug, Clone, Copy)]
+pub struct Pose {
+    pub x: f64,
+    pub y: f64,
+    pub heading: f64,
+}
+
+impl Pose {
+    // Kinematic bicycle: the rear axle follows an arc set by the steering,
+    // backwards for a negative distance
+    fn advance(self, distance: f64, steering: f64) -> Pose {
+        let curvature = steering.tan() / WHEELBASE;
+        let heading = self.heading + distance * curvature;
+        if curvature.abs() < 1e-9 {
+            return Pose {
+                x: self.x + distance * self.heading.cos(),
+                y: self.y + distance * self.heading.sin(),
+                heading,
+            };
+        }
+        Pose {
+            x: self.x + (heading.sin() - self.heading.sin()) / curvature,
+            y: self.y + (self.heading.cos() - heading.cos()) / curvature,
+            heading,
+        }
+    }
+
+    fn to_world(self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (sin, cos) = self.heading.sin_cos();
+        (self.x + x * cos - y * sin, self.y + x * sin + y * cos)
+    }
+
+    pub fn body(self) -> [(f64, f64); 4] {
+        let (rear, front, side) = (-REAR_OVERHANG, WHEELBASE + FRONT_OVERHANG, WIDTH / 2.0);
+        [(rear, -side), (front, -side), (front, side), (rear, side)].map(|corner| self.to_world(corner))
+    }
+
+    // Outer edges of the right tires, the ones that meet the curb
+    fn right_wheels(self) -> [(f64, f64); 2] {
+        let side = -WIDTH / 2.0 + 0.05;
+        [(0.0, side), (WHEELBASE, side)].map(|wheel| self.to_world(wheel))
+    }
+}
+
+// Separating axis test of the car body against a box
+fn overlaps(body: &[(f64, f64); 4], rect: &Rect) -> bool {
+    let corners = rect.corners();
+    let edge = |a: (f64, f64), b: (f64, f64)| (b.0 - a.0, b.1 - a.1);
+    let axes = [(1.0, 0.0), (0.0, 1.0), edge(body[0], body[1]), edge(body[1], body[2])];
+    axes.iter().all(|&(ax, ay)| {
+        let project = |points: &[(f64, f64)]| {
+            points
+                .iter()
+                .map(|&(x, y)| x * ax + y * ay)
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| (lo.min(p), hi.max(p)))
+        };
+        let (body_lo, body_hi) = project(body);
+        let (rect_lo, rect_hi) = project(&corners);
+        body_lo <= rect_hi && rect_lo <= body_hi
+    })
+}
+
+// Distance along a ray to a box, slab method
+fn ray_hit(origin: (f64, f64), direction: (f64, f64), rect: &Rect) -> Option<f64> {
+    let (mut near, mut far) = (0.0_f64, f64::INFINITY);
+    for (o, d, lo, hi) in [
+        (origin.0, direction.0, rect.min.0, rect.max.0),
+        (origin.1, direction.1, rect.min.1, rect.max.1),
+    ] {
+        if d.abs() < 1e-12 {
+            if o < lo || o > hi {
+                return None;
+            }
+        } else {
+            let (t1, t2) = ((lo - o) / d, (hi - o) / d);
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+        }
+    }
+    (near <= far).then_some(near)
+}
+
+// A gap of `length` between two parked cars of random size, each a random
+// distance from a curb of random height
+#[derive(Debug, Clone)]
+pub struct ParkingSlot {
+    pub length: f64,
+    pub rear_car: Rect,
+    pub front_car: Rect,
+    pub curb_height: f64,
+}
+
+impl ParkingSlot {
+    pub fn random(length: f64, rng: &mut SimRng) -> Self {
+        let car = |from: f64, to_front: bool, rng: &mut SimRng| {
+            let (car_length, width, offset) = (rng.gen_range(4.2..5.0), rng.gen_range(1.7..2.0), rng.gen_range(0.05..0.4));
+            let (start, end) = if to_front { (from, from + car_length) } else { (from - car_length, from) };
+            Rect {
+                min: (start, offset),
+                max: (end, offset + width),
+            }
+        };
+        ParkingSlot {
+            length,
+            rear_car: car(0.0, false, rng),
+            front_car: car(length, true, rng),
+            curb_height: rng.gen_range(0.08..0.2),
+        }
+    }
+
+    // Echo distance of a sensor mounted at `mount` on the car, `None`
+    // without an echo within range
+    fn ultrasonic(&self, pose: Pose, mount: (f64, f64, f64), rng: &mut SimRng) -> Option<f64> {
+        let origin = pose.to_world((mount.0, mount.1));
+        let mut nearest = f64::INFINITY;
+        for i in 0..BEAM_RAYS {
+            let offset = BEAM_HALF_ANGLE * (2.0 * i as f64 / (BEAM_RAYS - 1) as f64 - 1.0);
+            let (dy, dx) = (pose.heading + mount.2 + offset).sin_cos();
+            for rect in [&self.rear_car, &self.front_car] {
+                if let Some(distance) = ray_hit(origin, (dx, dy), rect) {
+                    nearest = nearest.min(distance);
+                }
+            }
+            if self.curb_height >= CURB_DETECTION_HEIGHT && dy < 0.0 {
+                nearest = nearest.min(-origin.1 / dy);
+            }
+        }
+        (nearest <= SENSOR_RANGE).then(|| (nearest + rng.gen_range(-SENSOR_NOISE..=SENSOR_NOISE)).max(0.0))
+    }
+}
+
+// What the side sensor made of the slot, in the car's odometry frame
+#[derive(Debug, Clone, Copy)]
+pub struct SlotMeasurement {
+    pub start: f64,
+    pub end: f64,
+    // Road-facing side of the parked cars
+    pub car_side: f64,
+    pub curb: Option<f64>,
+}
+
+impl SlotMeasurement {
+    pub fn length(&self) -> f64 {
+        self.end - self.start
+    }
+
+    fn obstacles(&self) -> [Rect; 2] {
+        let car = |start: f64, end: f64| Rect {
+            min: (start, -1.0),
+            max: (end, self.car_side),
+        };
+        [car(self.start - 5.0, self.start), car(self.end, self.end + 5.0)].map(|rect| rect.inflate(CLEARANCE))
+    }
+
+    // The curb as measured, or guessed from the parked cars
+    fn curb_line(&self) -> f64 {
+        self.curb.unwrap_or(self.car_side - ASSUMED_CAR_WIDTH - ASSUMED_CURB_GAP)
+    }
+}
+
+// Two reverse arcs of equal radius, first towards the curb, then back
+// straight, from `start`
+#[derive(Debug, Clone, Copy)]
+pub struct Plan {
+    pub start: Pose,
+    pub radius: f64,
+    pub arc_length: f64,
+}
+
+impl Plan {
+    fn steering(&self) -> f64 {
+        (WHEELBASE / self.radius).atan()
+    }
+
+    // Rear axle poses along the maneuver, every STEP
+    fn poses(&self) -> Vec<Pose> {
+        let mut poses = vec![self.start];
+        let mut pose = self.start;
+        for steering in [-self.steering(), self.steering()] {
+            let mut driven = 0.0;
+            while driven < self.arc_length {
+                let step = STEP.min(self.arc_length - driven);
+                pose = pose.advance(-step, steering);
+                driven += step;
+                poses.push(pose);
+            }
+        }
+        poses
+    }
+}
+
+// Smallest turning radius first, as far back in the slot as possible, then
+// a little farther from the curb if the corners would swing over it
+pub fn plan(measured: &SlotMeasurement) -> Option<Plan> {
+    let min_radius = WHEELBASE / MAX_STEERING.tan();
+    let obstacles = measured.obstacles();
+    let curb = measured.curb_line();
+    let curb_box = Rect {
+        min: (measured.start - 10.0, curb - 5.0),
+        max: (measured.end + 10.0, curb + CURB_MARGIN),
+    };
+
+    for extra_gap in [0.0, 0.1, 0.2] {
+        let final_y = curb + CURB_CLEARANCE + extra_gap + WIDTH / 2.0;
+        let offset = SCAN_Y - final_y;
+        for radius in (0..=4).map(|i| min_radius + 0.5 * i as f64) {
+            if offset > 2.0 * radius {
+                continue;
+            }
+            let angle = (1.0 - offset / (2.0 * radius)).acos();
+            let mut final_x = measured.start + REAR_OVERHANG + CLEARANCE;
+            while final_x + WHEELBASE + FRONT_OVERHANG + CLEARANCE <= measured.end {
+                let plan = Plan {
+                    start: Pose {
+                        x: final_x + 2.0 * radius * angle.sin(),
+                        y: SCAN_Y,
+                        heading: 0.0,
+                    },
+                    radius,
+                    arc_length: radius * angle,
+                };
+                let clear = plan.poses().iter().all(|pose| {
+                    let body = pose.body();
+                    !obstacles.iter().chain([&curb_box]).any(|rect| overlaps(&body, rect))
+                });
+                if clear {
+                    return Some(plan);
+                }
+                final_x += 0.1;
+            }
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone)]
+pub struct ParkingRun {
+    pub slot: ParkingSlot,
+    pub measured: Option<SlotMeasurement>,
+    pub outcome: Outcome,
+    // Rear axle positions of the maneuver
+    pub path: Vec<(f64, f64)>,
+    pub final_pose: Pose,
+}
+
+// The car as it really moves next to where its odometry thinks it is
+struct Car<'a> {
+    slot: &'a ParkingSlot,
+    actual: Pose,
+    believed: Pose,
+    // Actual distance per distance counted by the odometry
+    odometry_scale: f64,
+    path: Vec<(f64, f64)>,
+}
+
+impl Car<'_> {
+    // Moves by `distance` as counted by the odometry; fails on touching a
+    // parked car or the curb
+    fn drive(&mut self, distance: f64, steering: f64) -> Result<(), Outcome> {
+        self.believed = self.believed.advance(distance, steering);
+        self.actual = self.actual.advance(distance * self.odometry_scale, steering);
+        self.path.push((self.actual.x, self.actual.y));
+
+        let body = self.actual.body();
+        if [&self.slot.rear_car, &self.slot.front_car].iter().any(|rect| overlaps(&body, rect)) {
+            return Err(Outcome::Collision);
+        }
+        if self.actual.right_wheels().iter().any(|&(_, y)| y < 0.0) {
+            return Err(Outcome::CurbContact);
+        }
+        Ok(())
+    }
+
+    fn drive_straight(&mut self, distance: f64) -> Result<(), Outcome> {
+        let mut left = distance.abs();
+        while left > 0.0 {
+            let step = STEP.min(left);
+            self.drive(step.copysign(distance), 0.0)?;
+            left -= step;
+        }
+        Ok(())
+    }
+
+    // Passes the slot recording the side sensor until the car ahead of it
+    // shows up again
+    fn scan(&mut self, rng: &mut SimRng) -> Option<SlotMeasurement> {
+        let (mut start, mut end) = (None, None);
+        let (mut car_distances, mut curb_distances, mut open_samples) = (Vec::new(), Vec::new(), 0);
+        let mut passed_car = false;
+        let mut scanned = 0.0;
+        while end.is_none() && scanned < MAX_SCAN_DISTANCE {
+            let echo = self.slot.ultrasonic(self.actual, SIDE_SENSOR, rng);
+            let sensor = self.believed.to_world((SIDE_SENSOR.0, SIDE_SENSOR.1));
+            match echo {
+                Some(distance) if distance < OPEN_THRESHOLD => {
+                    car_distances.push(distance);
+                    if start.is_some() {
+                        end = Some(sensor.0);
+                    }
+                    passed_car = true;
+                }
+                _ if passed_car => {
+                    start = start.or(Some(sensor.0));
+                    open_samples += 1;
+                    curb_distances.extend(echo);
+                }
+                _ => {}
+            }
+            self.drive_straight(SCAN_STEP).ok()?;
+            scanned += SCAN_STEP;
+        }
+
+        // The edge of the beam echoes off the ends of the parked cars until
+        // they are farther than the threshold, so the slot shows up shorter
+        let sensor_y = self.believed.to_world((SIDE_SENSOR.0, SIDE_SENSOR.1)).1;
+        let car_distance = median(&mut car_distances);
+        let beam_spread = OPEN_THRESHOLD * BEAM_HALF_ANGLE.sin();
+        let curb = (curb_distances.len() * 2 >= open_samples).then(|| sensor_y - median(&mut curb_distances));
+        Some(SlotMeasurement {
+            start: start? - beam_spread,
+            end: end? + beam_spread,
+            car_side: sensor_y - car_distance,
+            curb,
+        })
+    }
+
+    // Backs along the plan, stopping early when the rear sensor closes in
+    fn execute(&mut self, plan: &Plan, rng: &mut SimRng) -> Result<(), Outcome> {
+        self.drive_straight(plan.start.x - self.believed.x)?;
+        for steering in [-plan.steering(), plan.steering()] {
+            let mut driven = 0.0;
+            while driven < plan.arc_length {
+                if self.slot.ultrasonic(self.actual, REAR_SENSOR, rng).is_some_and(|gap| gap < STOP_DISTANCE) {
+                    return Ok(());
+                }
+                let step = STEP.min(plan.arc_length - driven);
+                self.drive(-step, steering)?;
+                driven += step;
+            }
+        }
+
+        // Straight in the slot, centered between the bumpers
+        let front = self.slot.ultrasonic(self.actual, FRONT_SENSOR, rng);
+        let rear = self.slot.ultrasonic(self.actual, REAR_SENSOR, rng);
+        if let (Some(front), Some(rear)) = (front, rear) {
+            self.drive_straight(((front - rear) / 2.0).clamp(-1.0, 1.0))?;
+        }
+        Ok(())
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    values.get(values.len() / 2).copied().unwrap_or(f64::NAN)
+}
+
+// Drives past `slot`, measures it, plans and backs in
+pub fn park(slot: ParkingSlot, rng: &mut SimRng) -> ParkingRun {
+    let start = Pose {
+        x: -4.0 - SIDE_SENSOR.0,
+        y: SCAN_Y,
+        heading: 0.0,
+    };
+    let odometry_scale = 1.0 + rng.gen_range(-ODOMETRY_ERROR..=ODOMETRY_ERROR);
+    let mut car = Car {
+        slot: &slot,
+        actual: start,
+        believed: start,
+        odometry_scale,
+        path: Vec::new(),
+    };
+
+    let measured = car.scan(rng);
+    let outcome = match measured.as_ref().and_then(plan) {
+        None => Outcome::SlotTooSmall,
+        Some(plan) => {
+            car.path.clear();
+            match car.execute(&plan, rng) {
+                Err(outcome) => outcome,
+                Ok(()) => {
+                    let curb_gap = car.actual.right_wheels().iter().map(|&(_, y)| y).fold(f64::INFINITY, f64::min);
+                    if car.actual.heading.abs() <= MAX_HEADING_ERROR && curb_gap <= MAX_CURB_GAP {
+                        Outcome::Parked
+                    } else {
+                        Outcome::Misaligned
+                    }
+                }
+            }
+        }
+    };
+
+    let (path, final_pose) = (car.path, car.actual);
+    ParkingRun {
+        slot,
+        measured,
+        outcome,
+        path,
+        final_pose,
+    }
+}
+
+// Parks in `runs` slots with lengths drawn from `length`
+pub fn run_many(runs: u64, length: (f64, f64), rng: &mut SimRng) -> Vec<ParkingRun> {
+    (0..runs)
+        .map(|_| {
+            let slot = ParkingSlot::random(rng.gen_range(length.0..=length.1), rng);
+            park(slot, rng)
+        })
+        .collect()
+}
+
+// Width of the slot length classes of the summary and chart, in m
+pub const BIN_WIDTH: f64 = 0.5;
+
+// Share of each outcome per slot length class, as (class start, percent per
+// outcome in the order of `Outcome::ALL`)
+pub fn outcome_shares(runs: &[ParkingRun]) -> Vec<(f64, [f64; 5])> {
+    let mut bins: Vec<(f64, [u64; 5])> = Vec::new();
+    for run in runs {
+        let start = (run.slot.length / BIN_WIDTH).floor() * BIN_WIDTH;
+        let index = Outcome::ALL.iter().position(|&outcome| outcome == run.outcome).unwrap_or(0);
+        match bins.iter_mut().find(|(bin, _)| (bin - start).abs() < 1e-9) {
+            Some((_, counts)) => counts[index] += 1,
+            None => {
+                let mut counts = [0; 5];
+                counts[index] = 1;
+                bins.push((start, counts));
+            }
+        }
+    }
+    bins.sort_by(|a, b| a.0.total_cmp(&b.0));
+    bins.into_iter()
+        .map(|(start, counts)| {
+            let total = counts.iter().sum::<u64>() as f64;
+            (start, counts.map(|count| count as f64 / total * 100.0))
+        })
+        .collect()
+}
+
+pub fn print_summary(runs: &[ParkingRun]) {
+    let locale = locale::current();
+    println!(
+        "{} parking attempts, car {} m long with a {} m turning radius",
+        runs.len(),
+        locale.number(LENGTH, 1),
+        locale.number(WHEELBASE / MAX_STEERING.tan(), 1)
+    );
+    print!("{:<12}", "slot (m)");
+    for outcome in Outcome::ALL {
+        print!(" {:>15}", outcome.name());
+    }
+    println!();
+    for (start, shares) in outcome_shares(runs) {
+        print!(
+            "{:<12}",
+            format!("{}-{}", locale.number(start, 1), locale.number(start + BIN_WIDTH, 1))
+        );
+        for share in shares {
+            print!(" {:>14}%", locale.number(share, 0));
+        }
+        println!();
+    }
+
+    let parked: Vec<&ParkingRun> = runs.iter().filter(|run| run.outcome == Outcome::Parked).collect();
+    if let Some(shortest) = parked.iter().map(|run| run.slot.length).reduce(f64::min) {
+        println!(
+            "Parked in {} of {} slots, the shortest {} m ({} car lengths)",
+            parked.len(),
+            runs.len(),
+            locale.number(shortest, 2),
+            locale.number(shortest / LENGTH, 2)
+        );
+    }
+    // The beam widens, so the sensor sees the slot shorter than it is
+    let errors: Vec<f64> = runs
+        .iter()
+        .filter_map(|run| run.measured.map(|measured| measured.length() - run.slot.length))
+        .collect();
+    if !errors.is_empty() {
+        println!(
+            "Slot measured {} m off on average",
+            locale.number(errors.iter().sum::<f64>() / errors.len() as f64, 2)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(length: f64) -> ParkingSlot {
+        let car = |start: f64| Rect {
+            min: (start, 0.2),
+            max: (start + 4.5, 2.0),
+        };
+        ParkingSlot {
+            length,
+            rear_car: car(-4.5),
+            front_car: car(length),
+            curb_height: 0.15,
+        }
+    }
+
+    #[test]
+    fn parks_in_a_long_slot_and_refuses_a_short_one() {
+        let mut rng = SimRng::from_seed(1);
+        let run = park(slot(7.5), &mut rng);
+        assert_eq!(run.outcome, Outcome::Parked, "{:?}", run.final_pose);
+        let measured = run.measured.unwrap();
+        assert!(measured.length() < 7.5 && measured.length() > 7.0);
+        assert!(measured.curb.is_some());
+
+        let run = park(slot(5.0), &mut rng);
+        assert_eq!(run.outcome, Outcome::SlotTooSmall);
+    }
+}
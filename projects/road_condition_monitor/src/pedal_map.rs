@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::calibration::Calibration;
+use vehicle_sim_core::description::{Characteristic, Description};
+use crate::stability::StabilityThresholds;
+
+// Maps brake pedal position (0.0 = released, 1.0 = fully pressed) to a
+// deceleration request in m/s^2. Curves are lookup tables with linear
+// interpolation, so custom pedal feels can be supplied as well.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PedalCurve {
+    Comfort,
+    Sport,
+    Custom(Vec<(f32, f32)>),
+}
+
+impl PedalCurve {
+    // Accepts "comfort", "sport" or a custom table like "0:0,0.5:0.4,1:1"
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "comfort" => Ok(PedalCurve::Comfort),
+            "sport" => Ok(PedalCurve::Sport),
+            table => {
+                let points = table
+                    .split(',')
+                    .map(|pair| {
+                        let (x, y) = pair
+                            .split_once(':')
+                            .ok_or_else(|| format!("Invalid pedal curve point '{}'", pair))?;
+                        let x: f32 = x.trim().parse().map_err(|_| format!("Invalid pedal position '{}'", x))?;
+                        let y: f32 = y.trim().parse().map_err(|_| format!("Invalid deceleration fraction '{}'", y))?;
+                        if !x.is_finite() || !y.is_finite() {
+                            return Err(format!("Invalid pedal curve point '{}'", pair));
+                        }
+                        Ok((x, y))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+
+                if points.len() < 2 {
+                    return Err("A custom pedal curve needs at least two points".to_string());
+                }
+                Ok(PedalCurve::Custom(points))
+            }
+        }
+    }
+
+    // Points are (pedal position, fraction of maximum deceleration)
+    fn points(&self) -> Vec<(f32, f32)> {
+        match self {
+            // Soft initial bite, most of the braking power at the end of the travel
+            PedalCurve::Comfort => vec![(0.0, 0.0), (0.05, 0.0), (0.3, 0.1), (0.6, 0.35), (0.85, 0.7), (1.0, 1.0)],
+            // Sharp initial bite, nearly linear afterwards
+            PedalCurve::Sport => vec![(0.0, 0.0), (0.05, 0.0), (0.2, 0.3), (0.5, 0.65), (0.8, 0.9), (1.0, 1.0)],
+            PedalCurve::Custom(points) => points.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PedalMap {
+    pub curve: PedalCurve,
+    pub max_deceleration: f32,
+    points: Vec<(f32, f32)>,
+}
+
+impl PedalMap {
+    pub fn new(curve: PedalCurve, max_deceleration: f32) -> Self {
+        let mut points = curve.points();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        PedalMap {
+            curve,
+            max_deceleration,
+            points,
+        }
+    }
+
+    pub fn deceleration_request(&self, pedal_position: f32) -> f32 {
+        let position = pedal_position.clamp(0.0, 1.0);
+
+        let fraction = match self.points.iter().position(|&(x, _)| x >= position) {
+            None => self.points.last().map_or(0.0, |&(_, y)| y),
+            Some(0) => self.points[0].1,
+            Some(i) => {
+                let (x0, y0) = self.points[i - 1];
+                let (x1, y1) = self.points[i];
+                y0 + (y1 - y0) * (position - x0) / (x1 - x0)
+            }
+        };
+
+        fraction.clamp(0.0, 1.0) * self.max_deceleration
+    }
+}
+
+// What `--describe` lists: the chassis message and the pedal calibration in use
+pub fn describe(
+    curve: &str,
+    max_deceleration: f32,
+    thresholds: StabilityThresholds,
+    calibration: Option<&Calibration>,
+) -> Description {
+    Description::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .with_calibration(calibration)
+        .with_can_messages("Chassis")
+        .with_characteristic(Characteristic::text("pedal_curve", curve))
+        .with_characteristic(Characteristic::number("max_deceleration", max_deceleration, "m/s^2", 1.0, 15.0))
+        .with_characteristic(Characteristic::number("tc_slip_threshold", thresholds.tc_slip, "", 0.01, 1.0))
+        .with_characteristic(Characteristic::number("esc_yaw_threshold", thresholds.esc_yaw_rate, "deg/s", 0.5, 30.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_curves_run_from_released_to_full_braking() {
+        for curve in [PedalCurve::Comfort, PedalCurve::Sport] {
+            let map = PedalMap::new(curve, 10.0);
+            assert_eq!(map.deceleration_request(0.0), 0.0);
+            // The first bit of travel is dead
+            assert_eq!(map.deceleration_request(0.05), 0.0);
+            assert_eq!(map.deceleration_request(1.0), 10.0);
+        }
+        // The sport curve bites harder halfway down
+        let comfort = PedalMap::new(PedalCurve::Comfort, 10.0).deceleration_request(0.5);
+        let sport = PedalMap::new(PedalCurve::Sport, 10.0).deceleration_request(0.5);
+        assert!(sport > comfort, "{} vs {}", sport, comfort);
+    }
+
+    #[test]
+    fn custom_points_are_sorted_and_interpolated() {
+        let curve = PedalCurve::parse("1:1, 0:0, 0.5:0.2").unwrap();
+        let map = PedalMap::new(curve, 8.0);
+        assert!((map.deceleration_request(0.25) - 0.8).abs() < 1e-6);
+        assert!((map.deceleration_request(0.75) - 4.8).abs() < 1e-6);
+        // Positions outside the pedal travel are clamped to the endpoints
+        assert_eq!(map.deceleration_request(-0.5), 0.0);
+        assert_eq!(map.deceleration_request(1.5), 8.0);
+    }
+
+    #[test]
+    fn curves_between_the_table_ends_hold_the_end_values() {
+        let map = PedalMap::new(PedalCurve::Custom(vec![(0.2, 0.1), (0.8, 0.9)]), 10.0);
+        assert!((map.deceleration_request(0.0) - 1.0).abs() < 1e-6);
+        assert!((map.deceleration_request(1.0) - 9.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn malformed_or_non_finite_points_are_rejected() {
+        assert!(PedalCurve::parse("0:0").is_err());
+        assert!(PedalCurve::parse("0:0,1").is_err());
+        assert!(PedalCurve::parse("0:0,x:1").is_err());
+        assert!(PedalCurve::parse("0:0,NaN:1").is_err());
+        assert!(PedalCurve::parse("0:0,1:inf").is_err());
+        assert!(matches!(PedalCurve::parse(" Sport "), Ok(PedalCurve::Sport)));
+    }
+}
@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::path::Path;
+
+use vehicle_sim_core::sim_plot::{Figure, Panel, PlotSeries, Theme, BLUE, CYAN, GREEN, MAGENTA, RED};
+
+use crate::cruise_control::FollowRun;
+use crate::parking::{Outcome, ParkingRun, Rect};
+use crate::pedal_map::{PedalCurve, PedalMap};
+use crate::road_condition::RoadCondition;
+use crate::trailer::SwayRun;
+use crate::vehicle::Vehicle;
+
+// Plots requested vs achieved deceleration over the full pedal travel,
+// for both built-in pedal curves and every road condition.
+pub fn plot_deceleration(path: &Path, theme: Theme, pedal_map: &PedalMap, vehicle: &Vehicle) -> Result<(), Box<dyn Error>> {
+    let positions: Vec<f32> = (0..=100).map(|i| i as f32 / 100.0).collect();
+    let max_deceleration = pedal_map.max_deceleration as f64 + 1.0;
+    let over_pedal_travel =
+        |deceleration: &dyn Fn(f32) -> f32| positions.iter().map(|&x| (x as f64, deceleration(x) as f64)).collect::<Vec<_>>();
+
+    // Requested deceleration for the different pedal feels
+    let mut requested = Panel::new("Pedal Map: Requested Deceleration")
+        .with_axes("Pedal position", "m/s²")
+        .with_x_range(0.0, 1.0)
+        .with_y_range(0.0, max_deceleration);
+    for (curve, color) in [(PedalCurve::Comfort, BLUE), (PedalCurve::Sport, RED)] {
+        let label = format!("{:?}", curve);
+        let map = PedalMap::new(curve, pedal_map.max_deceleration);
+        requested = requested
+            .with_series(PlotSeries::new(label, over_pedal_travel(&|x| map.deceleration_request(x))).with_color(color));
+    }
+
+    // Achieved deceleration with the active pedal map on each road condition
+    let mut achieved = Panel::new("Achieved vs Requested Deceleration")
+        .with_axes("Pedal position", "m/s²")
+        .with_x_range(0.0, 1.0)
+        .with_y_range(0.0, max_deceleration)
+        .with_series(PlotSeries::new("Requested", over_pedal_travel(&|x| pedal_map.deceleration_request(x))).foreground());
+    for (condition, color) in [
+        (RoadCondition::Dry, GREEN),
+        (RoadCondition::Wet, BLUE),
+        (RoadCondition::Icy, CYAN),
+    ] {
+        let traction = vehicle.adjust_for_condition(condition.traction());
+        let points = over_pedal_travel(&|x| vehicle.achieved_deceleration(pedal_map.deceleration_request(x), traction));
+        achieved = achieved.with_series(PlotSeries::new(format!("Achieved ({:?})", condition), points).with_color(color));
+    }
+
+    Figure::new()
+        .with_theme(theme)
+        .with_panel(requested)
+        .with_panel(achieved)
+        .save(path)
+}
+
+// Hitch angle and speed of the same trailer sway without and with the
+// mitigation braking
+pub fn plot_sway(path: &Path, theme: Theme, unmitigated: &SwayRun, mitigated: &SwayRun) -> Result<(), Box<dyn Error>> {
+    let over_time = |run: &SwayRun, values: &[f64]| run.time.iter().cloned().zip(values.iter().cloned()).collect::<Vec<_>>();
+    let end_time = [unmitigated, mitigated].iter().filter_map(|run| run.time.last()).fold(1.0, |end, &time| time.max(end));
+
+    let angle = Panel::new("Trailer Hitch Angle")
+        .with_axes("Time (s)", "degrees")
+        .with_x_range(0.0, end_time)
+        .symmetric()
+        .with_series(PlotSeries::new("Without mitigation", over_time(unmitigated, &unmitigated.hitch_angle)).with_color(RED))
+        .with_series(PlotSeries::new("With mitigation", over_time(mitigated, &mitigated.hitch_angle)).with_color(BLUE));
+    let speed = Panel::new("Speed")
+        .with_axes("Time (s)", "km/h")
+        .with_x_range(0.0, end_time)
+        .with_series(PlotSeries::new("Without mitigation", over_time(unmitigated, &unmitigated.speed)).with_color(RED))
+        .with_series(PlotSeries::new("With mitigation", over_time(mitigated, &mitigated.speed)).with_color(BLUE));
+
+    Figure::new().with_theme(theme).with_panel(angle).with_panel(speed).save(path)
+}
+
+// Our speed against the lead vehicle's, and the gap kept against the one
+// the time headway asks for
+pub fn plot_follow(path: &Path, theme: Theme, run: &FollowRun) -> Result<(), Box<dyn Error>> {
+    let over_time = |values: &[f64]| run.time.iter().cloned().zip(values.iter().cloned()).collect::<Vec<_>>();
+    let while_ahead =
+        |values: &[Option<f64>]| run.time.iter().zip(values).filter_map(|(&time, value)| value.map(|v| (time, v))).collect::<Vec<_>>();
+    let end_time = run.time.last().cloned().unwrap_or(0.0).max(1.0);
+
+    let speed = Panel::new("Speed")
+        .with_axes("Time (s)", "km/h")
+        .with_x_range(0.0, end_time)
+        .with_series(PlotSeries::new("Own car", over_time(&run.speed)).with_color(BLUE))
+        .with_series(PlotSeries::new("Lead vehicle", while_ahead(&run.lead_speed)).with_color(RED));
+    let gap = Panel::new("Gap to the Lead Vehicle")
+        .with_axes("Time (s)", "m")
+        .with_x_range(0.0, end_time)
+        .with_series(PlotSeries::new("Gap", while_ahead(&run.gap)).with_color(BLUE))
+        .with_series(PlotSeries::new("Desired", while_ahead(&run.desired_gap)).with_color(GREEN));
+    let acceleration = Panel::new("Acceleration")
+        .with_axes("Time (s)", "m/s²")
+        .with_x_range(0.0, end_time)
+        .symmetric()
+        .with_series(PlotSeries::new("Own car", over_time(&run.acceleration)).with_color(MAGENTA));
+
+    Figure::new()
+        .with_theme(theme)
+        .with_panel(speed)
+        .with_panel(gap)
+        .with_panel(acceleration)
+        .save(path)
+}
+
+// Outcome shares over the slot length, and the maneuver into the
+// shortest slot the car parked in
+pub fn plot_parking(path: &Path, theme: Theme, runs: &[ParkingRun]) -> Result<(), Box<dyn Error>> {
+    let shares = crate::parking::outcome_shares(runs);
+    let mut outcomes = Panel::new("Parking Outcome by Slot Length")
+        .with_axes("Slot length (m)", "% of attempts")
+        .with_y_range(0.0, 100.0);
+    for (i, (outcome, color)) in Outcome::ALL.iter().zip([GREEN, BLUE, MAGENTA, RED, CYAN]).enumerate() {
+        let points = shares.iter().map(|(start, shares)| (start + crate::parking::BIN_WIDTH / 2.0, shares[i]));
+        outcomes = outcomes.with_series(PlotSeries::new(outcome.name(), points).with_color(color));
+    }
+
+    let outline = |rect: &Rect| {
+        let corners = rect.corners();
+        corners.iter().chain(&corners[..1]).copied().collect::<Vec<_>>()
+    };
+    let mut maneuver = Panel::new("Maneuver into the Shortest Slot").with_axes("x (m)", "y (m)");
+    let example = runs
+        .iter()
+        .filter(|run| run.outcome == Outcome::Parked)
+        .min_by(|a, b| a.slot.length.total_cmp(&b.slot.length));
+    if let Some(run) = example {
+        let body = run.final_pose.body();
+        let (start, end) = (run.slot.rear_car.min.0, run.slot.front_car.max.0);
+        maneuver = maneuver
+            .with_x_range(start, end.max(run.path.iter().map(|&(x, _)| x).fold(end, f64::max)))
+            .with_y_range(-0.5, 5.0)
+            .with_series(PlotSeries::new("Curb", [(start, 0.0), (end, 0.0)]).foreground())
+            .with_series(PlotSeries::new("Parked cars", outline(&run.slot.rear_car)).with_color(BLUE))
+            .with_series(PlotSeries::new("", outline(&run.slot.front_car)).with_color(BLUE))
+            .with_series(PlotSeries::new("Rear axle", run.path.clone()).with_color(RED))
+            .with_series(PlotSeries::new("Parked", body.iter().chain(&body[..1]).copied()).with_color(GREEN));
+    }
+
+    Figure::new().with_theme(theme).with_panel(outcomes).with_panel(maneuver).save(path)
+}
@@ -1,4 +1,8 @@
-#[derive(Debug)]
+pub mod aquaplaning;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum RoadCondition {
     Dry,
     Wet,
@@ -6,13 +10,30 @@ pub enum RoadCondition {
 }
 
 impl RoadCondition {
-    pub fn random() -> Self {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        match rng.gen_range(0..3) {
-            0 => RoadCondition::Dry,
-            1 => RoadCondition::Wet,
-            _ => RoadCondition::Icy,
+    pub const ALL: [RoadCondition; 3] = [RoadCondition::Dry, RoadCondition::Wet, RoadCondition::Icy];
+
+    pub fn index(&self) -> usize {
+        match self {
+            RoadCondition::Dry => 0,
+            RoadCondition::Wet => 1,
+            RoadCondition::Icy => 2,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<RoadCondition> {
+        match value.to_ascii_lowercase().as_str() {
+            "dry" => Some(RoadCondition::Dry),
+            "wet" => Some(RoadCondition::Wet),
+            "icy" => Some(RoadCondition::Icy),
+            _ => None,
+        }
+    }
+
+    pub fn traction(&self) -> f32 {
+        match self {
+            RoadCondition::Dry => 1.0,
+            RoadCondition::Wet => 0.7,
+            RoadCondition::Icy => 0.3,
         }
     }
 }
@@ -0,0 +1,110 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::tire::Tire;
+
+use super::RoadCondition;
+
+// Film a wet road starts with, and the deepest standing water in ruts and
+// puddles, in mm
+const INITIAL_WATER_FILM: f32 = 0.5;
+const MAX_WATER_FILM: f32 = 8.0;
+// Largest change of the film per step while it rains
+const WATER_FILM_DRIFT: f32 = 0.5;
+// Rate at which the water runs off a road that is no longer wet, in mm/s
+const DRAINAGE_RATE: f32 = 0.01;
+
+// Water standing on the road. While wet it follows the rain, deepening and
+// thinning at random; afterwards it drains away, and on ice it is frozen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct WaterFilm {
+    depth: f32, // mm
+}
+
+impl WaterFilm {
+    pub fn step(&mut self, condition: RoadCondition, dt: f64, rng: &mut impl Rng) {
+        self.depth = match condition {
+            RoadCondition::Wet if self.depth <= 0.0 => INITIAL_WATER_FILM,
+            RoadCondition::Wet => {
+                let drift: f32 = rng.gen_range(-WATER_FILM_DRIFT..WATER_FILM_DRIFT);
+                (self.depth + drift).clamp(0.1, MAX_WATER_FILM)
+            }
+            RoadCondition::Dry => (self.depth - DRAINAGE_RATE * dt as f32).max(0.0),
+            RoadCondition::Icy => 0.0,
+        };
+    }
+
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+}
+
+// Whether the tires ride up on the water at `speed` km/h, and the speed at
+// which they start to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AquaplaningRisk {
+    pub water_film: f32,
+    // km/h, None while the tread drains the film
+    pub onset_speed: Option<f32>,
+    pub aquaplaning: bool,
+}
+
+impl AquaplaningRisk {
+    pub fn assess(film: &WaterFilm, tire: &Tire, speed: f32) -> AquaplaningRisk {
+        let onset_speed = tire.aquaplaning_speed(film.depth());
+        AquaplaningRisk {
+            water_film: film.depth(),
+            onset_speed,
+            aquaplaning: onset_speed.is_some_and(|onset| speed > onset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use vehicle_sim_core::units::Pressure;
+
+    #[test]
+    fn onset_speed_falls_with_tread_pressure_and_water() {
+        let film = |depth| WaterFilm { depth };
+        let new = Tire::default();
+        let mut worn = new;
+        worn.set_wear(1.0);
+        let under_inflated = Tire {
+            pressure: Pressure::from_kpa(150.0),
+            ..new
+        };
+
+        // A damp road is drained by any legal tread
+        assert_eq!(AquaplaningRisk::assess(&film(0.3), &worn, 130.0).onset_speed, None);
+
+        let onset = |tire: &Tire, depth| AquaplaningRisk::assess(&film(depth), tire, 0.0).onset_speed.unwrap();
+        assert!(onset(&worn, 3.0) < onset(&new, 3.0));
+        assert!(onset(&under_inflated, 3.0) < onset(&new, 3.0));
+        assert!(onset(&new, 6.0) < onset(&new, 3.0));
+        // Deep water leaves a worn tire close to Horne's speed of a smooth one
+        assert!((onset(&worn, 8.0) - 6.36 * 230f32.sqrt()).abs() < 5.0);
+
+        let risk = AquaplaningRisk::assess(&film(6.0), &worn, 110.0);
+        assert!(risk.aquaplaning);
+        assert!(!AquaplaningRisk::assess(&film(6.0), &worn, 80.0).aquaplaning);
+    }
+
+    #[test]
+    fn water_drains_once_the_rain_stops() {
+        let mut film = WaterFilm::default();
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..20 {
+            film.step(RoadCondition::Wet, 5.0, &mut rng);
+            assert!(film.depth() > 0.0 && film.depth() <= MAX_WATER_FILM);
+        }
+
+        let depth = film.depth();
+        film.step(RoadCondition::Dry, 10.0, &mut rng);
+        assert!((film.depth() - (depth - 10.0 * DRAINAGE_RATE).max(0.0)).abs() < 1e-5);
+        film.step(RoadCondition::Dry, 1000.0, &mut rng);
+        assert_eq!(film.depth(), 0.0);
+    }
+}
@@ -1,38 +1,846 @@
-use std::thread;
-use std::time::Duration;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
 
-use crate::vehicle::Vehicle;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::batch::{self, Stats};
+use vehicle_sim_core::can_bus::{CanBus, ROAD_CONDITION};
+use vehicle_sim_core::can_trace::{self, CanTrace};
+use vehicle_sim_core::description::Description;
+use vehicle_sim_core::drive_cycle::{CyclePlayback, DriveCycle};
+use vehicle_sim_core::driver_model::{DriverModel, SpeedProfile};
+use vehicle_sim_core::events::{self, Event, EventBus, EventFilter};
+use vehicle_sim_core::locale;
+use vehicle_sim_core::privacy::{self, PrivacyFilter, PrivacyPolicy};
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::scenario::Scenario;
+use vehicle_sim_core::simulation::{FixedStepRunner, PauseControl, RunSummary, Simulation};
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::sim_plot::Theme;
+use vehicle_sim_core::snapshot::Snapshot;
+use vehicle_sim_core::units::{Pressure, Speed};
+use vehicle_sim_core::xcp::{self, XcpServer};
+
+use crate::brakes::FADE_ONSET;
+use crate::cli::Cli;
+use crate::cruise_control::CruiseControl;
+use crate::ice_detection::IceDetector;
+use crate::pedal_map::PedalMap;
+use crate::stability::{DrivingDemand, StabilityControl, StabilityState, StabilityThresholds};
+use crate::plot::plot_deceleration;
+use crate::tui::Tui;
+use crate::vehicle::{StoppingDistance, Vehicle, ABS_SLIP_THRESHOLD};
+use crate::weather::WeatherModel;
+use crate::road_condition::aquaplaning::{AquaplaningRisk, WaterFilm};
 use crate::road_condition::RoadCondition;
 
-pub fn run_simulation() {
-    let mut vehicle = Vehicle::new();
+// Step the cruise control is integrated with, in seconds
+const CRUISE_STEP: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RoadState {
+    pub road_condition: RoadCondition,
+    // Held by hand rather than following the weather
+    #[serde(default)]
+    pub forced: bool,
+    pub ambient_temperature: f32,
+    pub speed: f32,
+    pub road_slope: f32,
+    pub tire_condition: f32,
+    pub traction: f32,
+    pub stopping_distance: StoppingDistance,
+    pub pedal_position: f32,
+    pub requested_deceleration: f32,
+    pub achieved_deceleration: f32,
+    pub pedal_stopping_distance: StoppingDistance,
+    // Wheels slipped under light braking
+    #[serde(default)]
+    pub slip_event: bool,
+    #[serde(default)]
+    pub ice_warning: bool,
+    #[serde(default)]
+    pub road_curvature: f32,
+    #[serde(default)]
+    pub stability: StabilityState,
+    #[serde(default)]
+    pub aquaplaning: AquaplaningRisk,
+    // Disc temperature and what the fading brakes deliver of the grip
+    #[serde(default)]
+    pub brake_temperature: f32,
+    #[serde(default)]
+    pub braking_efficiency: f32,
+}
 
-    loop {
-        let road_condition = RoadCondition::random();
+#[derive(Serialize, Deserialize)]
+pub struct RoadSimulation {
+    pub vehicle: Vehicle,
+    pub pedal_map: PedalMap,
+    weather: WeatherModel,
+    #[serde(default)]
+    ice_detector: IceDetector,
+    #[serde(default)]
+    water_film: WaterFilm,
+    // Traction control and ESC
+    #[serde(default)]
+    pub stability: StabilityControl,
+    rng: SimRng,
+    state: RoadState,
+    steps: u64,
+    // Drives a speed trace (--speed-profile) instead of the random walk
+    #[serde(default)]
+    driver: Option<DriverModel>,
+    // Plays back a standard or recorded drive cycle (--drive-cycle)
+    #[serde(default)]
+    cycle: Option<CyclePlayback>,
+    // Holds a set speed (--cruise), as fast as the road allows
+    #[serde(default)]
+    cruise: Option<CruiseControl>,
+    #[serde(default)]
+    cruise_limited: bool,
+    // Road condition changes
+    #[serde(skip)]
+    pub events: Arc<EventBus>,
+    #[serde(skip)]
+    pub can: Arc<CanBus>,
+    // Measurement and calibration over XCP (--xcp)
+    #[serde(skip)]
+    pub xcp: Option<Arc<XcpServer>>,
+    // Telemetry to an MQTT broker (--mqtt)
+    #[cfg(feature = "mqtt")]
+    #[serde(skip)]
+    pub mqtt: Option<Arc<vehicle_sim_core::mqtt::MqttPublisher>>,
+    // Applied to the telemetry before it is exported (--privacy)
+    #[serde(skip)]
+    pub privacy: Option<PrivacyFilter>,
+    // Road conditions forced from the dashboard, `None` releasing them
+    #[serde(skip)]
+    pub overrides: Option<Receiver<Option<RoadCondition>>>,
+}
 
-        vehicle.update_speed();
-        vehicle.update_road_slope();
-        vehicle.update_tire_condition();
+impl RoadSimulation {
+    pub fn new(pedal_map: PedalMap, weather: WeatherModel, rng: SimRng) -> Self {
+        let vehicle = Vehicle::new();
+        let traction = vehicle.adjust_for_condition(weather.traction());
 
-        let traction = vehicle.adjust_for_condition(match road_condition {
-            RoadCondition::Dry => 1.0,
-            RoadCondition::Wet => 0.7,
-            RoadCondition::Icy => 0.3,
-        });
+        RoadSimulation {
+            state: RoadState {
+                road_condition: weather.condition(),
+                forced: weather.forced().is_some(),
+                ambient_temperature: weather.ambient_temperature(),
+                speed: vehicle.speed.kmh() as f32,
+                road_slope: vehicle.road_slope,
+                tire_condition: vehicle.tire_condition,
+                traction,
+                stopping_distance: vehicle.calculate_stopping_distance(traction),
+                pedal_position: 0.0,
+                requested_deceleration: 0.0,
+                achieved_deceleration: 0.0,
+                pedal_stopping_distance: vehicle.calculate_stopping_distance_for_request(0.0, traction),
+                slip_event: false,
+                ice_warning: false,
+                road_curvature: vehicle.road_curvature,
+                stability: StabilityState::default(),
+                aquaplaning: AquaplaningRisk::default(),
+                brake_temperature: vehicle.brakes.temperature,
+                braking_efficiency: vehicle.effective_braking_efficiency(),
+            },
+            vehicle,
+            pedal_map,
+            weather,
+            ice_detector: IceDetector::default(),
+            water_film: WaterFilm::default(),
+            stability: StabilityControl::default(),
+            rng,
+            steps: 0,
+            driver: None,
+            cycle: None,
+            cruise: None,
+            cruise_limited: false,
+            events: Arc::new(EventBus::new()),
+            can: Arc::new(CanBus::new()),
+            xcp: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            privacy: None,
+            overrides: None,
+        }
+    }
+}
+
+impl RoadSimulation {
+    // The averages still open when the run ends
+    #[cfg(feature = "mqtt")]
+    pub fn flush_mqtt(&mut self) {
+        let (Some(mqtt), Some(privacy)) = (&self.mqtt, &mut self.privacy) else {
+            return;
+        };
+        let pending = privacy.flush();
+        if !pending.is_empty() {
+            mqtt.publish(&pending);
+        }
+    }
 
-        let stopping_distance = vehicle.calculate_stopping_distance(traction);
+    pub fn set_speed_profile(&mut self, profile: SpeedProfile) {
+        let mut driver = DriverModel::new(profile, &mut self.rng);
+        driver.speed = self.vehicle.speed.kmh();
+        self.driver = Some(driver);
+    }
+
+    pub fn set_drive_cycle(&mut self, cycle: DriveCycle) {
+        self.cycle = Some(CyclePlayback::new(cycle));
+    }
+
+    pub fn set_cruise(&mut self, set_speed: f64) {
+        self.cruise = Some(CruiseControl::new(set_speed));
+    }
+
+    // Pedal calibration written by a tool over XCP; the pedal curve is text
+    // and cannot be written
+    fn apply_xcp_writes(&mut self) {
+        let Some(xcp) = &self.xcp else {
+            return;
+        };
+        for (name, value) in xcp.take_writes() {
+            match name.as_str() {
+                "max_deceleration" => self.pedal_map.max_deceleration = value as f32,
+                "tc_slip_threshold" => self.stability.thresholds.tc_slip = value as f32,
+                "esc_yaw_threshold" => self.stability.thresholds.esc_yaw_rate = value as f32,
+                _ => continue,
+            }
+            sim_log::info("xcp", &format!("Calibrated over XCP: {} = {}", name, value));
+        }
+    }
+
+    fn apply_overrides(&mut self) {
+        let Some(overrides) = &self.overrides else {
+            return;
+        };
+        for condition in overrides.try_iter() {
+            self.weather.force(condition);
+            match condition {
+                Some(condition) => sim_log::info("weather", &format!("Road condition forced to {:?}", condition)),
+                None => sim_log::info("weather", "Road condition follows the weather again"),
+            }
+        }
+    }
+
+    // Integrates the controller in short steps; a warning goes out when the
+    // road cannot take the acceleration it asks for
+    fn drive_cruise(&mut self, cruise: &CruiseControl, traction: f32, dt: f64) {
+        let substeps = (dt / CRUISE_STEP).ceil().max(1.0) as u64;
+        let mut speed = self.vehicle.speed.meters_per_second();
+        let mut limited = false;
+        for _ in 0..substeps {
+            let command = cruise.command(speed, None, &self.vehicle, traction);
+            limited |= command.traction_limited;
+            speed = (speed + command.acceleration * dt / substeps as f64).max(0.0);
+        }
+        self.vehicle.speed = Speed::from_kmh(speed * 3.6);
+        if limited && !self.cruise_limited {
+            self.events.publish(
+                self.steps,
+                Event::WarningRaised {
+                    source: "cruise".to_string(),
+                    message: format!(
+                        "Cruise control limited by traction {}, accelerating at {} m/s² at most",
+                        locale::current().number(traction as f64, 2),
+                        locale::current().number(self.vehicle.drive_limit(traction) as f64, 2)
+                    ),
+                },
+            );
+        }
+        self.cruise_limited = limited;
+    }
+
+    fn transmit_condition(&self) {
+        let state = &self.state;
+        let condition = match state.road_condition {
+            RoadCondition::Dry => 0.0,
+            RoadCondition::Wet => 1.0,
+            RoadCondition::Icy => 2.0,
+        };
+        self.can.transmit(
+            &ROAD_CONDITION,
+            &[
+                ("VehicleSpeed", state.speed as f64),
+                ("RoadCondition", condition),
+                ("AbsActive", state.pedal_stopping_distance.abs_active as u8 as f64),
+                ("IceWarning", state.ice_warning as u8 as f64),
+                ("Traction", state.traction as f64),
+                ("AmbientTemperature", state.ambient_temperature as f64),
+                ("StoppingDistance", state.stopping_distance.total as f64),
+            ],
+        );
+    }
+}
+
+impl Simulation for RoadSimulation {
+    type State = RoadState;
+
+    fn step(&mut self, dt: f64) {
+        self.steps += 1;
+        self.apply_xcp_writes();
+        self.apply_overrides();
+        self.weather.step(dt, &mut self.rng);
+        let road_condition = self.weather.condition();
+        self.water_film.step(road_condition, dt, &mut self.rng);
+        let previous = self.state;
+        let previous_speed = self.vehicle.speed.meters_per_second() as f32;
+
+        // The cruise control works with the grip before this step's slope
+        // and tire wear
+        let traction = self.vehicle.adjust_for_condition(self.weather.traction());
+        match (&mut self.cycle, &mut self.driver, self.cruise.clone()) {
+            (_, _, Some(cruise)) => self.drive_cruise(&cruise, traction, dt),
+            (Some(playback), _, _) => {
+                playback.advance(dt);
+                self.vehicle.speed = Speed::from_kmh(playback.speed());
+            }
+            (None, Some(driver), None) => {
+                driver.advance(dt, &mut self.rng);
+                self.vehicle.speed = Speed::from_kmh(driver.speed);
+            }
+            (None, None, None) => self.vehicle.update_speed(&mut self.rng),
+        }
+        self.vehicle.update_road_slope(&mut self.rng);
+        self.vehicle.update_road_curvature(&mut self.rng);
+        self.vehicle.update_tire_condition(&mut self.rng);
+
+        let traction = self.vehicle.adjust_for_condition(self.weather.traction());
+
+        // Traction control holds the car back when the wheels would spin
+        let demand = DrivingDemand {
+            speed: self.vehicle.speed.meters_per_second() as f32,
+            // No more than the brakes and the engine can ask for, however
+            // short the step
+            acceleration: ((self.vehicle.speed.meters_per_second() as f32 - previous_speed) / dt as f32).clamp(-10.0, 5.0),
+            curvature: self.vehicle.road_curvature,
+        };
+        let (stability, interventions) = self.stability.update(demand, traction);
+        if stability.tc_active {
+            let speed = (previous_speed + stability.acceleration * dt as f32).max(0.0);
+            self.vehicle.speed = Speed::from_kmh(speed as f64 * 3.6);
+        }
+        for event in interventions {
+            self.events.publish(self.steps, event);
+        }
+        let ambient_temperature = self.weather.ambient_temperature();
+        self.vehicle.update_brakes(previous_speed, ambient_temperature, dt as f32);
+
+        let pedal_position: f32 = self.rng.gen_range(0.2..1.0);
+        let requested_deceleration = self.pedal_map.deceleration_request(pedal_position);
+
+        let slip_detected = self.vehicle.wheel_slip(requested_deceleration, traction) > ABS_SLIP_THRESHOLD;
+        let slip_event = IceDetector::slip_event(requested_deceleration, slip_detected);
+        let ice_warning = self.ice_detector.update(ambient_temperature, slip_event);
+        let aquaplaning = AquaplaningRisk::assess(&self.water_film, &self.vehicle.tire, self.vehicle.speed.kmh() as f32);
+
+        self.state = RoadState {
+            road_condition,
+            forced: self.weather.forced().is_some(),
+            ambient_temperature,
+            speed: self.vehicle.speed.kmh() as f32,
+            road_slope: self.vehicle.road_slope,
+            tire_condition: self.vehicle.tire_condition,
+            traction,
+            stopping_distance: self.vehicle.calculate_stopping_distance(traction),
+            pedal_position,
+            requested_deceleration,
+            achieved_deceleration: self.vehicle.achieved_deceleration(requested_deceleration, traction),
+            pedal_stopping_distance: self
+                .vehicle
+                .calculate_stopping_distance_for_request(requested_deceleration, traction),
+            slip_event,
+            ice_warning,
+            road_curvature: self.vehicle.road_curvature,
+            stability,
+            aquaplaning,
+            brake_temperature: self.vehicle.brakes.temperature,
+            braking_efficiency: self.vehicle.effective_braking_efficiency(),
+        };
+
+        self.transmit_condition();
+        self.can.end_tick();
+        // Only the bus signals are measured, the server reads them off the bus
+        if let Some(xcp) = &self.xcp {
+            xcp.publish(&[]);
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(mqtt) = &self.mqtt {
+            let state = &self.state;
+            let signals = vec![
+                ("speed", state.speed as f64),
+                ("ambient_temp", state.ambient_temperature as f64),
+                ("road_slope", state.road_slope as f64),
+                ("tire_condition", state.tire_condition as f64),
+                ("traction", state.traction as f64),
+                ("stopping_distance", state.stopping_distance.total as f64),
+                ("achieved_deceleration", state.achieved_deceleration as f64),
+                ("ice_warning", if state.ice_warning { 1.0 } else { 0.0 }),
+                ("torque_cut", state.stability.torque_cut as f64),
+                ("yaw_error", state.stability.yaw_error as f64),
+                ("water_film", state.aquaplaning.water_film as f64),
+                ("aquaplaning", if state.aquaplaning.aquaplaning { 1.0 } else { 0.0 }),
+                ("brake_temperature", state.brake_temperature as f64),
+            ];
+            mqtt.publish(&vehicle_sim_core::privacy::filtered(self.privacy.as_mut(), self.steps as f64 * dt, signals));
+        }
+
+        if ice_warning != previous.ice_warning {
+            if ice_warning {
+                self.events.publish(
+                    self.steps,
+                    Event::WarningRaised {
+                        source: "ice".to_string(),
+                        message: format!(
+                            "Road ice likely: wheels slipping under light braking at {}",
+                            locale::current().temperature(ambient_temperature, 1)
+                        ),
+                    },
+                );
+            } else {
+                sim_log::info("ice", "Road ice warning cleared");
+            }
+        }
+
+        if aquaplaning.aquaplaning && !previous.aquaplaning.aquaplaning {
+            let locale = locale::current();
+            self.events.publish(
+                self.steps,
+                Event::WarningRaised {
+                    source: "aquaplaning".to_string(),
+                    message: format!(
+                        "Aquaplaning risk: {} is above the onset speed of {} on {} mm of water with {} mm of tread",
+                        locale.speed(self.state.speed as f64, 0),
+                        locale.speed(aquaplaning.onset_speed.unwrap_or_default() as f64, 0),
+                        locale.number(aquaplaning.water_film as f64, 1),
+                        locale.number(self.vehicle.tire.tread_depth as f64, 1)
+                    ),
+                },
+            );
+        }
+
+        let fading = self.vehicle.brakes.is_fading();
+        if fading != (previous.brake_temperature > FADE_ONSET) {
+            let locale = locale::current();
+            if fading {
+                self.events.publish(
+                    self.steps,
+                    Event::WarningRaised {
+                        source: "brakes".to_string(),
+                        message: format!(
+                            "Brake fade: discs at {}, braking efficiency down to {}%",
+                            locale.temperature(self.state.brake_temperature, 0),
+                            locale.number(self.state.braking_efficiency as f64 * 100.0, 0)
+                        ),
+                    },
+                );
+            } else {
+                sim_log::info("brakes", &format!("Brakes cooled down to {}", locale.temperature(self.state.brake_temperature, 0)));
+            }
+        }
+
+        if road_condition != previous.road_condition {
+            self.events.publish(
+                self.steps,
+                Event::ConditionChanged {
+                    condition: format!("{:?}", road_condition),
+                    traction,
+                },
+            );
+        }
+    }
+
+    fn state(&self) -> RoadState {
+        self.state
+    }
+
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self).ok()
+    }
+
+    fn report(&self) -> String {
+        let state = &self.state;
+        let locale = locale::current();
+        let curve = |curvature: f32| match curvature {
+            c if c > 0.0 => format!("left curve of {}", locale.length(1.0 / c as f64, 0)),
+            c if c < 0.0 => format!("right curve of {}", locale.length(-1.0 / c as f64, 0)),
+            _ => "straight road".to_string(),
+        };
+        format!(
+            "-----------------------------------\n\
+             Road condition: {:?} at {}, Speed: {}, Road Slope: {} degrees, Tire Condition: {}\n\
+             Traction: {}, Estimated stopping distance: {} (reaction {} + braking {}).\n\
+             Brake pedal ({:?}): {}% -> requested {} m/s², achieved {} m/s²{}, stopping distance {}.\n\
+             Stability: {}, wheel slip front {}% rear {}%, yaw rate error {} deg/s{}{}.\n\
+             Water film: {} mm, tread {} mm at {} ({}% grip), {}.\n\
+             Brakes: discs at {}, braking efficiency {}%{}.\n\
+             -----------------------------------",
+            state.road_condition,
+            locale.temperature(state.ambient_temperature, 1),
+            locale.speed(state.speed as f64, 1),
+            locale.number(state.road_slope as f64, 1),
+            locale.number(state.tire_condition as f64, 2),
+            locale.number(state.traction as f64, 2),
+            locale.length(state.stopping_distance.total as f64, 2),
+            locale.length(state.stopping_distance.reaction_distance as f64, 2),
+            locale.length(state.stopping_distance.braking_distance as f64, 2),
+            self.pedal_map.curve,
+            locale.number(state.pedal_position as f64 * 100.0, 0),
+            locale.number(state.requested_deceleration as f64, 2),
+            locale.number(state.achieved_deceleration as f64, 2),
+            if state.pedal_stopping_distance.abs_active { " (ABS)" } else { "" },
+            locale.length(state.pedal_stopping_distance.total as f64, 2),
+            curve(state.road_curvature),
+            locale.number(state.stability.front_slip as f64 * 100.0, 0),
+            locale.number(state.stability.rear_slip as f64 * 100.0, 0),
+            locale.number(state.stability.yaw_error as f64, 1),
+            if state.stability.tc_active {
+                format!(", TC cutting {}% torque", locale.number(state.stability.torque_cut as f64 * 100.0, 0))
+            } else {
+                String::new()
+            },
+            if state.stability.esc_active { ", ESC braking" } else { "" },
+            locale.number(state.aquaplaning.water_film as f64, 1),
+            locale.number(self.vehicle.tire.tread_depth as f64, 1),
+            locale.pressure(self.vehicle.tire.cold_pressure().psi(), 1),
+            locale.number(self.vehicle.tire.grip_factor() as f64 * 100.0, 0),
+            match state.aquaplaning.onset_speed {
+                Some(onset) if state.aquaplaning.aquaplaning => format!("AQUAPLANING above {}", locale.speed(onset as f64, 0)),
+                Some(onset) => format!("aquaplaning from {}", locale.speed(onset as f64, 0)),
+                None => "drained by the tread".to_string(),
+            },
+            locale.temperature(state.brake_temperature, 0),
+            locale.number(state.braking_efficiency as f64 * 100.0, 0),
+            if state.brake_temperature > FADE_ONSET { " (FADING)" } else { "" }
+        )
+    }
+}
 
-        println!("-----------------------------------");
+// Road ahead the driver can see; an emergency stop that does not fit into it
+// counts as unsafe
+const SIGHT_DISTANCE: f32 = 100.0; // m
+
+// Running statistics printed when the run ends or is interrupted
+#[derive(Debug, Default)]
+pub struct RunStatistics {
+    dry: u64,
+    wet: u64,
+    icy: u64,
+    speed: Stats,
+    pub traction: Stats,
+    pub stopping_distance: Stats,
+    unsafe_steps: u64,
+    // Ice warnings on a road that was not icy, and icy steps without one
+    false_ice_warnings: u64,
+    missed_ice: u64,
+    traction_control_steps: u64,
+    esc_steps: u64,
+    aquaplaning_steps: u64,
+    brake_fade_steps: u64,
+    hottest_brakes: Option<f32>,
+}
+
+impl RunStatistics {
+    pub fn record(&mut self, state: &RoadState) {
+        match state.road_condition {
+            RoadCondition::Dry => self.dry += 1,
+            RoadCondition::Wet => self.wet += 1,
+            RoadCondition::Icy => self.icy += 1,
+        }
+        self.speed.record(state.speed as f64);
+        self.traction.record(state.traction as f64);
+        self.stopping_distance.record(state.stopping_distance.total as f64);
+        if state.stopping_distance.total > SIGHT_DISTANCE {
+            self.unsafe_steps += 1;
+        }
+        self.traction_control_steps += state.stability.tc_active as u64;
+        self.esc_steps += state.stability.esc_active as u64;
+        self.aquaplaning_steps += state.aquaplaning.aquaplaning as u64;
+        self.brake_fade_steps += (state.brake_temperature > FADE_ONSET) as u64;
+        self.hottest_brakes = Some(self.hottest_brakes.map_or(state.brake_temperature, |hottest| hottest.max(state.brake_temperature)));
+        match (state.ice_warning, state.road_condition == RoadCondition::Icy) {
+            (true, false) => self.false_ice_warnings += 1,
+            (false, true) => self.missed_ice += 1,
+            _ => {}
+        }
+    }
+
+    fn print(&self, summary: &RunSummary) {
+        let locale = locale::current();
+        // A resumed run only has statistics for the steps since the resume
+        println!("=========== Run summary ===========");
         println!(
-            "Road condition: {:?}, Speed: {:.1} km/h, Road Slope: {:.1} degrees, Tire Condition: {:.2}",
-            road_condition, vehicle.speed, vehicle.road_slope, vehicle.tire_condition
+            "Steps: {}, simulated time: {} s",
+            summary.steps,
+            locale.number(summary.simulated_seconds, 0)
         );
+        println!("Road conditions: {} dry, {} wet, {} icy", self.dry, self.wet, self.icy);
+        if let Some(speed) = self.speed.mean() {
+            println!("Average speed: {}", locale.speed(speed, 1));
+        }
+        if let Some(min_traction) = self.traction.min() {
+            println!("Lowest traction: {}", locale.number(min_traction, 2));
+        }
+        if let (Some(min), Some(mean), Some(max)) =
+            (self.stopping_distance.min(), self.stopping_distance.mean(), self.stopping_distance.max())
+        {
+            println!(
+                "Stopping distance: shortest {}, mean {}, longest {}",
+                locale.length(min, 2),
+                locale.length(mean, 2),
+                locale.length(max, 2)
+            );
+        }
         println!(
-            "Estimated stopping distance: {:.2} meters.",
-            stopping_distance
+            "Unsafe steps: {} ({}%) with a stopping distance beyond the {} sight distance",
+            self.unsafe_steps,
+            locale.number(self.unsafe_percent(), 1),
+            locale.length(SIGHT_DISTANCE as f64, 0)
         );
-        println!("-----------------------------------");
+        let rate = |percent: Option<f64>| percent.map_or("-".to_string(), |percent| locale.number(percent, 1));
+        println!(
+            "Ice warnings: {}% false on roads without ice, {}% of icy steps missed",
+            rate(self.false_positive_percent()),
+            rate(self.false_negative_percent())
+        );
+        println!(
+            "Stability control: traction control in {} steps, ESC in {} steps",
+            self.traction_control_steps, self.esc_steps
+        );
+        println!("Aquaplaning risk: {} steps above the onset speed", self.aquaplaning_steps);
+        if let Some(hottest) = self.hottest_brakes {
+            println!(
+                "Brakes: hottest discs {}, fading in {} steps",
+                locale.temperature(hottest, 0),
+                self.brake_fade_steps
+            );
+        }
+    }
+
+    pub fn unsafe_percent(&self) -> f64 {
+        batch::percent(self.unsafe_steps, self.stopping_distance.count())
+    }
+
+    // Steps with an ice warning in percent of the steps without ice, `None`
+    // if the road was icy throughout
+    pub fn false_positive_percent(&self) -> Option<f64> {
+        let clear = self.dry + self.wet;
+        (clear > 0).then(|| batch::percent(self.false_ice_warnings, clear))
+    }
+
+    // Icy steps without an ice warning in percent of the icy steps, `None`
+    // if there was no ice
+    pub fn false_negative_percent(&self) -> Option<f64> {
+        (self.icy > 0).then(|| batch::percent(self.missed_ice, self.icy))
+    }
+
+    // Share of the steps on an icy road in percent
+    pub fn icy_percent(&self) -> f64 {
+        batch::percent(self.icy, self.dry + self.wet + self.icy)
+    }
+}
+
+pub fn run_simulation(
+    pedal_map: PedalMap,
+    thresholds: StabilityThresholds,
+    cli: &Cli,
+    scenario: Option<&Scenario>,
+    description: &Description,
+) {
+    // `--resume` continues a checkpointed run, random stream included
+    let (start, mut simulation) = match &cli.resume {
+        Some(path) => {
+            let snapshot = Snapshot::<RoadSimulation>::load(path).unwrap_or_else(|e| {
+                eprintln!("Cannot resume from {}: {}", path.display(), e);
+                process::exit(1);
+            });
+            println!("Resuming from {} at step {}", path.display(), snapshot.steps);
+            (snapshot.summary(), snapshot.state)
+        }
+        None => {
+            let ambient_temperature = cli
+                .ambient_temperature
+                .or_else(|| scenario.and_then(|s| s.initial_f64("ambient_temperature")).map(|t| t as f32))
+                .unwrap_or(2.0);
+            let mut weather = WeatherModel::new(cli.weather_transitions.unwrap_or_default(), ambient_temperature);
+            if let Some(scenario) = scenario {
+                if let Some(condition) = scenario.initial_str("condition").and_then(RoadCondition::parse) {
+                    weather = weather.with_condition(condition);
+                }
+                weather = weather.with_timeline(scenario.weather.clone());
+            }
+            // Seed with --seed <n>, SIM_SEED or the scenario's seed to reproduce a run
+            let rng = SimRng::from_seed_env_or(cli.seed, scenario.and_then(|s| s.seed));
+            let mut simulation = RoadSimulation::new(pedal_map, weather, rng);
+            if let Some(pressure) = cli.tire_pressure {
+                simulation.vehicle.tire.pressure = Pressure::from_kpa(pressure);
+            }
+            // A resumed run keeps the thresholds it was calibrated with
+            simulation.stability = StabilityControl::new(thresholds);
+            (RunSummary::default(), simulation)
+        }
+    };
+
+    // A resumed run keeps the driver it saved
+    if let Some(profile) = cli.speed_profile.filter(|_| simulation.driver.is_none()) {
+        simulation.set_speed_profile(profile);
+    }
+    if let Some(cycle) = cli.drive_cycle.clone().filter(|_| simulation.cycle.is_none()) {
+        simulation.set_drive_cycle(cycle);
+    }
+    if let Some(set_speed) = cli.cruise.filter(|_| simulation.cruise.is_none()) {
+        simulation.set_cruise(set_speed);
+    }
+    if let Some(path) = cli.can_trace.clone().or_else(can_trace::path_from_args) {
+        match CanTrace::create(&path) {
+            Ok(trace) => simulation.can.record_to(trace),
+            Err(e) => {
+                eprintln!("Cannot write CAN trace {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(address) = cli.xcp.clone().or_else(xcp::address_from_args) {
+        let server = XcpServer::start(&address, description).unwrap_or_else(|e| {
+            eprintln!("Cannot serve XCP on {}: {}", address, e);
+            process::exit(1);
+        });
+        server.watch_bus(&simulation.can);
+        println!("XCP on UDP {}", server.local_addr().map_or(address, |address| address.to_string()));
+        simulation.xcp = Some(server);
+    }
+    if let Some(path) = cli.privacy.clone().or_else(privacy::path_from_args) {
+        let policy = PrivacyPolicy::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read privacy settings {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        simulation.privacy = Some(PrivacyFilter::new(policy));
+    }
+    #[cfg(feature = "mqtt")]
+    if let Some(broker) = cli.mqtt.clone().or_else(vehicle_sim_core::mqtt::broker_from_args) {
+        let options = vehicle_sim_core::mqtt::MqttOptions::from_env(broker, "road");
+        let publisher = vehicle_sim_core::mqtt::MqttPublisher::connect(&options).unwrap_or_else(|e| {
+            eprintln!("Cannot connect to MQTT broker {}: {}", options.broker, e);
+            process::exit(1);
+        });
+        println!("Publishing telemetry to MQTT broker {} under {}/", options.broker, options.topic_prefix);
+        simulation.mqtt = Some(publisher);
+    }
+
+    // Batch runs leave nothing behind but their statistics
+    if cli.batch.is_none() {
+        let theme = cli.plot_theme.unwrap_or_else(Theme::from_env);
+        match plot_deceleration(Path::new("pedal_map.png"), theme, &simulation.pedal_map, &simulation.vehicle) {
+            Ok(()) => println!("Pedal map chart written to pedal_map.png"),
+            Err(e) => eprintln!("Failed to plot pedal map: {}", e),
+        }
+    }
+
+    // Ctrl-C ends the run after the current step so the summary still prints
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler_stop.store(true, Ordering::SeqCst)) {
+        eprintln!("Cannot install Ctrl-C handler: {}", e);
+    }
+
+    let mut runner = FixedStepRunner::new(cli.interval)
+        .with_realtime_factor(cli.realtime_factor())
+        .with_stop_flag(stop.clone())
+        .resume_from(start);
+    if let Some(path) = &cli.save {
+        runner = runner.with_checkpoint(path.clone());
+    }
+    if let Some(iterations) = cli.iterations().or_else(|| scenario.and_then(|s| s.steps(cli.interval))) {
+        runner = runner.with_max_steps(iterations);
+    }
+    if cli.batch.is_some() {
+        runner = runner.quiet();
+    }
+
+    let mut statistics = RunStatistics::default();
+    let timeline = simulation.events.subscribe(EventFilter::all());
+
+    // On a terminal the dashboard shows the state instead of a report per
+    // step, and its keys pause, step and force road conditions
+    let mut tui = None;
+    if cli.interactive() {
+        let pause = Arc::new(PauseControl::new());
+        let (overrides, received) = mpsc::channel();
+        simulation.overrides = Some(received);
+        runner = runner.quiet().with_pause(pause.clone());
+        tui = Some(Tui::start(pause, stop, overrides));
+    }
+    let finish = |tui: Option<Tui>| {
+        if let Some(Err(e)) = tui.map(Tui::finish) {
+            eprintln!("Terminal dashboard failed: {}", e);
+        }
+    };
+
+    // With the `dashboard` feature, `--dashboard 127.0.0.1:8080` serves the
+    // pedal map chart, a live stream of the road state and Prometheus metrics
+    #[cfg(feature = "dashboard")]
+    if let Some(address) = cli.dashboard.clone().or_else(vehicle_sim_core::dashboard::address_from_args) {
+        let streamed = simulation.events.subscribe(EventFilter::all());
+        let metrics = Arc::new(vehicle_sim_core::metrics::Metrics::new());
+        let charts = vec!["pedal_map.png".into()];
+        match vehicle_sim_core::dashboard::Dashboard::start(&address, "Road Condition Monitor", charts, metrics.clone()) {
+            Ok(dashboard) => {
+                let mut dashboard_privacy = simulation.privacy.as_ref().map(PrivacyFilter::for_stream);
+                let summary = runner.with_metrics(metrics).run_with(&mut simulation, |state, summary| {
+                    statistics.record(&state);
+                    if let Some(tui) = &tui {
+                        tui.publish(state, summary);
+                    }
+                    for event in streamed.try_iter() {
+                        dashboard.publish_event(&event);
+                    }
+                    let signals = vec![
+                        ("ambient_temperature_c", state.ambient_temperature as f64),
+                        ("speed_kmh", state.speed as f64),
+                        ("road_slope_deg", state.road_slope as f64),
+                        ("tire_condition", state.tire_condition as f64),
+                        ("traction", state.traction as f64),
+                        ("stopping_distance_m", state.stopping_distance.total as f64),
+                        ("pedal_position", state.pedal_position as f64),
+                        ("achieved_deceleration", state.achieved_deceleration as f64),
+                    ];
+                    dashboard.publish(&privacy::filtered(dashboard_privacy.as_mut(), summary.simulated_seconds, signals));
+                });
+                finish(tui);
+                // The averages still open at the end go out with a last update
+                if let Some(privacy) = &mut dashboard_privacy {
+                    let pending = privacy.flush();
+                    if !pending.is_empty() {
+                        dashboard.publish(&pending);
+                    }
+                }
+                #[cfg(feature = "mqtt")]
+                simulation.flush_mqtt();
+                if cli.batch.is_none() {
+                    events::print_timeline(&timeline);
+                }
+                statistics.print(&summary);
+                return;
+            }
+            Err(e) => eprintln!("Cannot start dashboard on {}: {}", address, e),
+        }
+    }
 
-        thread::sleep(Duration::from_secs(5));
+    let summary = runner.run_with(&mut simulation, |state, summary| {
+        statistics.record(&state);
+        if let Some(tui) = &tui {
+            tui.publish(state, summary);
+        }
+    });
+    finish(tui);
+    #[cfg(feature = "mqtt")]
+    simulation.flush_mqtt();
+    if cli.batch.is_none() {
+        events::print_timeline(&timeline);
     }
+    statistics.print(&summary);
 }
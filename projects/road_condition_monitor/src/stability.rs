@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+
+use vehicle_sim_core::calibration::Calibration;
+use vehicle_sim_core::events::Event;
+
+const GRAVITY: f32 = 9.81;
+
+// Slip of the driven axle above which traction control cuts the torque
+pub const DEFAULT_TC_SLIP_THRESHOLD: f32 = 0.12;
+// Yaw rate error above which ESC brakes a wheel, in deg/s
+pub const DEFAULT_ESC_YAW_THRESHOLD: f32 = 4.0;
+// Slip at which a tire transfers its peak friction; asked for more than it
+// can transfer, the wheel spins or locks
+const PEAK_FRICTION_SLIP: f32 = 0.15;
+// Front-wheel drive with the weight evenly on both axles at rest
+const FRONT_AXLE_SHARE: f32 = 0.5;
+// Height of the center of gravity over the wheelbase: braking moves this
+// share of the weight per g onto the front axle, accelerating onto the rear
+const LOAD_TRANSFER: f32 = 0.2;
+// Share of the braking force on the front axle
+const FRONT_BRAKE_SHARE: f32 = 0.6;
+// Curves tighter than this (1/m) are taken at walking pace only
+const MAX_CURVATURE: f32 = 1.0 / 15.0;
+
+// Thresholds of the stability control, set by calibration as
+// `tc_slip_threshold` and `esc_yaw_threshold` or over XCP
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StabilityThresholds {
+    pub tc_slip: f32,
+    pub esc_yaw_rate: f32, // deg/s
+}
+
+impl Default for StabilityThresholds {
+    fn default() -> Self {
+        StabilityThresholds {
+            tc_slip: DEFAULT_TC_SLIP_THRESHOLD,
+            esc_yaw_rate: DEFAULT_ESC_YAW_THRESHOLD,
+        }
+    }
+}
+
+impl StabilityThresholds {
+    pub fn from_calibration(calibration: Option<&Calibration>) -> Self {
+        let defaults = StabilityThresholds::default();
+        let get = |key: &str, default: f32| calibration.and_then(|c| c.get_f64(key)).map_or(default, |value| value as f32);
+        StabilityThresholds {
+            tc_slip: get("tc_slip_threshold", defaults.tc_slip),
+            esc_yaw_rate: get("esc_yaw_threshold", defaults.esc_yaw_rate),
+        }
+    }
+}
+
+// What the driver and the road ask of the tires in a step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrivingDemand {
+    pub speed: f32,        // m/s
+    pub acceleration: f32, // m/s^2, negative when braking
+    // One over the curve radius, positive to the left and zero on a straight
+    pub curvature: f32, // 1/m
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StabilityState {
+    pub front_slip: f32,
+    pub rear_slip: f32,
+    // Share of the engine torque traction control took away
+    pub torque_cut: f32,
+    // Acceleration left after the cut, m/s^2
+    pub acceleration: f32,
+    // Measured minus steered yaw rate, deg/s
+    pub yaw_error: f32,
+    pub tc_active: bool,
+    pub esc_active: bool,
+}
+
+// Estimates the slip of each axle from how much of its friction circle the
+// drive, braking and cornering forces use. Traction control cuts the engine
+// torque until the driven front axle is back at the slip threshold; ESC
+// brakes the outer front wheel against oversteer, when the rear axle runs
+// out of grip first, and the inner rear wheel against understeer.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StabilityControl {
+    pub thresholds: StabilityThresholds,
+    pub tc_interventions: u64,
+    pub esc_interventions: u64,
+    #[serde(skip)]
+    state: StabilityState,
+}
+
+impl StabilityControl {
+    pub fn new(thresholds: StabilityThresholds) -> Self {
+        StabilityControl {
+            thresholds,
+            ..StabilityControl::default()
+        }
+    }
+
+    // The state after `demand` on a road with `traction`, and the events of
+    // interventions that started in this step
+    pub fn update(&mut self, demand: DrivingDemand, traction: f32) -> (StabilityState, Vec<Event>) {
+        let grip = traction.max(0.0) * GRAVITY;
+        let curvature = demand.curvature.abs().min(MAX_CURVATURE);
+        let lateral = demand.speed * demand.speed * curvature;
+        // Forces per unit of the vehicle's mass: driving loads the front
+        // axle alone, braking both, cornering both evenly
+        let (mut front_longitudinal, rear_longitudinal) = match demand.acceleration {
+            a if a >= 0.0 => (a, 0.0),
+            a => (-a * FRONT_BRAKE_SHARE, -a * (1.0 - FRONT_BRAKE_SHARE)),
+        };
+        let lateral_per_axle = lateral / 2.0;
+        let front_share = (FRONT_AXLE_SHARE - LOAD_TRANSFER * demand.acceleration / GRAVITY).clamp(0.1, 0.9);
+        let axle_grip = |share: f32| (share * grip).max(f32::EPSILON);
+        let (front_grip, rear_grip) = (axle_grip(front_share), axle_grip(1.0 - front_share));
+        let usage = |longitudinal: f32, grip: f32| longitudinal.hypot(lateral_per_axle) / grip;
+
+        let mut torque_cut = 0.0;
+        let mut acceleration = demand.acceleration;
+        let demanded_slip = slip(usage(front_longitudinal, front_grip));
+        if demand.acceleration > 0.0 && demanded_slip > self.thresholds.tc_slip {
+            // Drive force the front axle can still put down at the threshold
+            let limit = self.thresholds.tc_slip / PEAK_FRICTION_SLIP * front_grip;
+            let allowed = (limit * limit - lateral_per_axle * lateral_per_axle).max(0.0).sqrt();
+            torque_cut = (1.0 - allowed / front_longitudinal).clamp(0.0, 1.0);
+            front_longitudinal = allowed;
+            acceleration = allowed;
+        }
+        let front_usage = usage(front_longitudinal, front_grip);
+        let rear_usage = usage(rear_longitudinal, rear_grip);
+
+        // Cornering force each axle can still hold, as a share of what the
+        // curve asks of it
+        let held = |longitudinal: f32, grip: f32| {
+            if lateral <= 0.0 {
+                return 1.0;
+            }
+            ((grip * grip - longitudinal * longitudinal).max(0.0).sqrt() / lateral_per_axle).min(1.0)
+        };
+        let front_held = held(front_longitudinal, front_grip);
+        let rear_held = held(rear_longitudinal, rear_grip);
+        let steered = (demand.speed * curvature).to_degrees();
+        let measured = if rear_held < front_held {
+            // The rear steps out and the car turns in further than steered
+            steered * (1.0 + front_held - rear_held)
+        } else {
+            steered * front_held
+        };
+        let yaw_error = measured - steered;
+
+        let tc_active = torque_cut > 0.0;
+        let esc_active = yaw_error.abs() > self.thresholds.esc_yaw_rate;
+        let mut events = Vec::new();
+        if tc_active && !self.state.tc_active {
+            self.tc_interventions += 1;
+            events.push(Event::TractionControl {
+                axle: "front".to_string(),
+                slip: demanded_slip,
+                torque_cut,
+            });
+        }
+        if esc_active && !self.state.esc_active {
+            self.esc_interventions += 1;
+            let left_turn = demand.curvature > 0.0;
+            let wheel = match (yaw_error > 0.0, left_turn) {
+                (true, true) => "front right",
+                (true, false) => "front left",
+                (false, true) => "rear left",
+                (false, false) => "rear right",
+            };
+            events.push(Event::YawCorrection {
+                yaw_error,
+                wheel: wheel.to_string(),
+            });
+        }
+
+        self.state = StabilityState {
+            front_slip: slip(front_usage),
+            rear_slip: slip(rear_usage),
+            torque_cut,
+            acceleration,
+            yaw_error,
+            tc_active,
+            esc_active,
+        };
+        (self.state, events)
+    }
+}
+
+// Slip grows with the used share of the grip up to the peak; beyond it the
+// wheel spins or locks
+fn slip(usage: f32) -> f32 {
+    if usage > 1.0 {
+        1.0
+    } else {
+        PEAK_FRICTION_SLIP * usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn demand(speed_kmh: f32, acceleration: f32, curve_radius: f32) -> DrivingDemand {
+        DrivingDemand {
+            speed: speed_kmh / 3.6,
+            acceleration,
+            curvature: 1.0 / curve_radius,
+        }
+    }
+
+    #[test]
+    fn traction_control_cuts_torque_on_ice_only() {
+        let mut stability = StabilityControl::default();
+        let (dry, events) = stability.update(demand(30.0, 2.5, f32::INFINITY), 0.9);
+        assert!(!dry.tc_active && events.is_empty());
+        assert_eq!(dry.acceleration, 2.5);
+
+        let (icy, events) = stability.update(demand(30.0, 2.5, f32::INFINITY), 0.27);
+        assert!(icy.tc_active);
+        assert!((icy.front_slip - DEFAULT_TC_SLIP_THRESHOLD).abs() < 1e-4);
+        assert!(icy.acceleration < 2.5 && icy.torque_cut > 0.0);
+        assert!(matches!(events.as_slice(), [Event::TractionControl { .. }]));
+
+        // Only the start of an intervention is reported
+        let (_, events) = stability.update(demand(30.0, 2.5, f32::INFINITY), 0.27);
+        assert!(events.is_empty());
+        assert_eq!(stability.tc_interventions, 1);
+    }
+
+    #[test]
+    fn esc_corrects_oversteer_under_braking_and_understeer_under_power() {
+        let mut stability = StabilityControl::default();
+        // A gentle curve on a dry road needs no help
+        let (state, _) = stability.update(demand(80.0, 0.0, 300.0), 0.9);
+        assert!(!state.esc_active && state.yaw_error.abs() < 1e-4);
+
+        // Braking into a left curve on a wet road the rear axle lets go first
+        let (state, events) = stability.update(demand(80.0, -6.0, 100.0), 0.63);
+        assert!(state.esc_active && state.yaw_error > 0.0);
+        assert!(matches!(events.as_slice(), [Event::YawCorrection { wheel, .. }] if wheel == "front right"));
+
+        // Accelerating out of a right curve on ice the driven front axle
+        // pushes wide
+        let mut stability = StabilityControl::new(StabilityThresholds {
+            tc_slip: 1.0,
+            ..StabilityThresholds::default()
+        });
+        let (state, events) = stability.update(demand(40.0, 1.0, -40.0), 0.27);
+        assert!(state.esc_active && state.yaw_error < 0.0);
+        assert!(matches!(events.as_slice(), [Event::YawCorrection { wheel, .. }] if wheel == "rear right"));
+    }
+}
@@ -0,0 +1,224 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use rand::Rng;
+use rayon::prelude::*;
+use vehicle_sim_core::batch;
+use vehicle_sim_core::csv_export::{self, CsvOptions};
+use vehicle_sim_core::locale;
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::simulation::FixedStepRunner;
+use vehicle_sim_core::units::Speed;
+
+use crate::pedal_map::PedalMap;
+use crate::simulation::{RoadSimulation, RunStatistics};
+use crate::weather::{WeatherModel, WeatherTransitions};
+
+pub struct SweepOptions {
+    pub runs: u64,
+    pub steps: u64,
+    pub interval: f64,
+    // Ranges the parameters of every run are drawn from
+    pub speed: (f64, f64), // km/h at the start
+    pub tire_wear: (f64, f64), // tire condition lost per step at most
+    pub ambient_temperature: (f64, f64), // °C at the start
+    pub transitions: WeatherTransitions,
+    pub csv: PathBuf,
+}
+
+// Parameters of one run; its seed drives the weather and the driving
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    seed: u64,
+    start_speed: f64,
+    tire_wear_rate: f64,
+    ambient_temperature: f64,
+}
+
+struct Outcome {
+    sample: Sample,
+    statistics: RunStatistics,
+}
+
+// Runs the simulation `options.runs` times in parallel, each with its own
+// sampled parameters, writes the outcome of every run to the CSV file and
+// prints how the outcomes are distributed. The samples are drawn up front
+// from `rng`, so the same seed gives the same sweep on any number of cores.
+pub fn run(options: &SweepOptions, pedal_map: &PedalMap, rng: &mut SimRng) -> io::Result<()> {
+    let samples: Vec<Sample> = (0..options.runs)
+        .map(|_| Sample {
+            seed: rng.fork().seed(),
+            start_speed: rng.gen_range(options.speed.0..=options.speed.1),
+            tire_wear_rate: rng.gen_range(options.tire_wear.0..=options.tire_wear.1),
+            ambient_temperature: rng.gen_range(options.ambient_temperature.0..=options.ambient_temperature.1),
+        })
+        .collect();
+
+    let started = Instant::now();
+    let outcomes: Vec<Outcome> = samples.into_par_iter().map(|sample| run_one(sample, options, pedal_map)).collect();
+    let elapsed = started.elapsed().as_secs_f64();
+
+    write_csv(&options.csv, &outcomes)?;
+    print_distribution(options, &outcomes, elapsed);
+    println!("Outcome of every run written to {}", options.csv.display());
+    Ok(())
+}
+
+fn run_one(sample: Sample, options: &SweepOptions, pedal_map: &PedalMap) -> Outcome {
+    let weather = WeatherModel::new(options.transitions, sample.ambient_temperature as f32);
+    let mut simulation = RoadSimulation::new(pedal_map.clone(), weather, SimRng::from_seed(sample.seed));
+    simulation.vehicle.speed = Speed::from_kmh(sample.start_speed);
+    simulation.vehicle.tire_wear_rate = sample.tire_wear_rate as f32;
+
+    let mut statistics = RunStatistics::default();
+    FixedStepRunner::new(options.interval)
+        .with_realtime_factor(0.0)
+        .with_max_steps(options.steps)
+        .quiet()
+        .run_with(&mut simulation, |state, _| statistics.record(&state));
+    Outcome { sample, statistics }
+}
+
+fn write_csv(path: &Path, outcomes: &[Outcome]) -> io::Result<()> {
+    let header = [
+        "run",
+        "seed",
+        "start_speed_kmh",
+        "tire_wear_rate",
+        "ambient_temperature_c",
+        "mean_stopping_distance_m",
+        "max_stopping_distance_m",
+        "unsafe_percent",
+        "icy_percent",
+        "min_traction",
+        "ice_false_positive_percent",
+        "ice_false_negative_percent",
+    ];
+    let rows: Vec<Vec<String>> = outcomes
+        .iter()
+        .enumerate()
+        .map(|(run, outcome)| {
+            let (sample, statistics) = (&outcome.sample, &outcome.statistics);
+            let value = |value: Option<f64>| value.map_or(String::new(), |value| format!("{:.3}", value));
+            vec![
+                run.to_string(),
+                sample.seed.to_string(),
+                format!("{:.3}", sample.start_speed),
+                format!("{:.5}", sample.tire_wear_rate),
+                format!("{:.2}", sample.ambient_temperature),
+                value(statistics.stopping_distance.mean()),
+                value(statistics.stopping_distance.max()),
+                format!("{:.3}", statistics.unsafe_percent()),
+                format!("{:.3}", statistics.icy_percent()),
+                value(statistics.traction.min()),
+                value(statistics.false_positive_percent()),
+                value(statistics.false_negative_percent()),
+            ]
+        })
+        .collect();
+    let options = CsvOptions {
+        path: path.to_path_buf(),
+        delimiter: ',',
+        precision: 3,
+    };
+    csv_export::write_rows(&options, &header, &rows)
+}
+
+// 5th, 50th and 95th percentile over the runs of each outcome
+fn print_distribution(options: &SweepOptions, outcomes: &[Outcome], elapsed: f64) {
+    let locale = locale::current();
+    println!(
+        "Sweep: {} runs of {} steps in {} s",
+        outcomes.len(),
+        options.steps,
+        locale.number(elapsed, 2)
+    );
+    println!("{:<28}{:>12}{:>12}{:>12}", "Over all runs", "p5", "p50", "p95");
+
+    let over_runs = |outcome: fn(&RunStatistics) -> Option<f64>| {
+        let mut values: Vec<f64> = outcomes.iter().filter_map(|o| outcome(&o.statistics)).collect();
+        values.sort_by(f64::total_cmp);
+        values
+    };
+    let length = |value: f64| locale.length(value, 1);
+    let percent = |value: f64| format!("{}%", locale.number(value, 1));
+    print_percentiles("Mean stopping distance", &over_runs(|s| s.stopping_distance.mean()), length);
+    print_percentiles("Longest stopping distance", &over_runs(|s| s.stopping_distance.max()), length);
+    print_percentiles("Unsafe steps", &over_runs(|s| Some(s.unsafe_percent())), percent);
+    print_percentiles("Lowest traction", &over_runs(|s| s.traction.min()), |value| locale.number(value, 2));
+    print_percentiles("False ice warnings", &over_runs(|s| s.false_positive_percent()), percent);
+    print_percentiles("Missed ice", &over_runs(|s| s.false_negative_percent()), percent);
+}
+
+fn print_percentiles(name: &str, sorted: &[f64], format: impl Fn(f64) -> String) {
+    let cells = percentile_cells(sorted, format);
+    println!("{:<28}{:>12}{:>12}{:>12}", name, cells[0], cells[1], cells[2]);
+}
+
+// p5, p50 and p95 of `sorted`, "-" when no run had a value
+fn percentile_cells(sorted: &[f64], format: impl Fn(f64) -> String) -> Vec<String> {
+    [5.0, 50.0, 95.0]
+        .iter()
+        .map(|&p| batch::percentile(sorted, p).map_or("-".to_string(), &format))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pedal_map::PedalCurve;
+
+    fn cells(sorted: &[f64]) -> Vec<String> {
+        percentile_cells(sorted, |value| format!("{:.2}", value))
+    }
+
+    #[test]
+    fn percentiles_interpolate_between_the_runs() {
+        assert_eq!(cells(&[]), ["-", "-", "-"]);
+        assert_eq!(cells(&[4.0]), ["4.00", "4.00", "4.00"]);
+        assert_eq!(cells(&[0.0, 10.0]), ["0.50", "5.00", "9.50"]);
+        let runs: Vec<f64> = (0..=100).map(f64::from).collect();
+        assert_eq!(cells(&runs), ["5.00", "50.00", "95.00"]);
+    }
+
+    #[test]
+    fn the_csv_has_one_row_per_run_under_the_header() {
+        let sample = Sample {
+            seed: 7,
+            start_speed: 80.0,
+            tire_wear_rate: 0.0001,
+            ambient_temperature: -2.5,
+        };
+        let options = SweepOptions {
+            runs: 1,
+            steps: 20,
+            interval: 0.5,
+            speed: (80.0, 80.0),
+            tire_wear: (0.0001, 0.0001),
+            ambient_temperature: (-2.5, -2.5),
+            transitions: WeatherTransitions::default(),
+            csv: std::env::temp_dir().join(format!("sweep_{}.csv", std::process::id())),
+        };
+        let pedal_map = PedalMap::new(PedalCurve::Comfort, 8.0);
+        let outcomes = [
+            run_one(sample, &options, &pedal_map),
+            Outcome {
+                sample,
+                statistics: RunStatistics::default(),
+            },
+        ];
+        write_csv(&options.csv, &outcomes).unwrap();
+        let written = std::fs::read_to_string(&options.csv).unwrap();
+        std::fs::remove_file(&options.csv).unwrap();
+
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("run,seed,start_speed_kmh,tire_wear_rate,ambient_temperature_c,"));
+        assert!(lines[0].ends_with(",ice_false_positive_percent,ice_false_negative_percent"));
+        assert!(lines[1].starts_with("0,7,80.000,0.00010,-2.50,"));
+        assert!(lines[1].split(',').all(|field| !field.is_empty()));
+        // A run without steps leaves its measured columns empty
+        assert_eq!(lines[2], "1,7,80.000,0.00010,-2.50,,,0.000,0.000,,,");
+    }
+}
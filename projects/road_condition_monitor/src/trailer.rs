@@ -0,0 +1,191 @@
+use std::f64::consts::PI;
+
+use vehicle_sim_core::locale;
+
+use crate::road_condition::RoadCondition;
+use crate::vehicle::Vehicle;
+
+// Mass of the towing car, in kg
+const TOW_VEHICLE_MASS: f64 = 1600.0;
+// Speed at which a trailer as heavy as the car stops damping its own sway,
+// in km/h; lighter trailers stay stable to higher speeds
+const CRITICAL_SPEED_AT_EQUAL_MASS: f64 = 100.0;
+// Damping ratio of the sway at walking pace
+const LOW_SPEED_DAMPING: f64 = 0.15;
+// Trailers sway at about this frequency whatever the speed, in Hz
+const SWAY_FREQUENCY: f64 = 0.8;
+// Sway amplitude at which the mitigation brakes, and below which it
+// releases the brakes again, in degrees
+const SWAY_THRESHOLD: f64 = 3.0;
+const RELEASE_THRESHOLD: f64 = 1.0;
+// Once the trailer sways the mitigation takes at least this much speed off,
+// as the sway would build up again at the speed it started at, in km/h
+const SPEED_REDUCTION: f64 = 20.0;
+// Deceleration the mitigation asks for, in m/s^2
+const MITIGATION_DECELERATION: f32 = 3.0;
+// Damping ratio the asymmetric braking adds when the tires transfer the
+// full deceleration
+const MITIGATION_DAMPING: f64 = 0.5;
+// Beyond this hitch angle the combination jackknifes, in degrees
+const JACKKNIFE_ANGLE: f64 = 45.0;
+// Integration step, in seconds
+const DT: f64 = 0.01;
+
+pub struct Trailer {
+    pub mass: f64, // kg
+}
+
+impl Trailer {
+    // Above this speed the sway grows instead of dying out, in km/h
+    pub fn critical_speed(&self) -> f64 {
+        CRITICAL_SPEED_AT_EQUAL_MASS * (TOW_VEHICLE_MASS / self.mass).sqrt()
+    }
+
+    // Damping of the hitch angle oscillation, negative above the critical speed
+    fn damping_ratio(&self, speed: f64) -> f64 {
+        LOW_SPEED_DAMPING * (1.0 - (speed / self.critical_speed()).powi(2))
+    }
+}
+
+// A car towing `trailer` at `speed` km/h whose trailer is knocked to a hitch
+// angle of `kick` degrees, e.g. by a gust or a steering correction
+pub struct SwayScenario {
+    pub trailer: Trailer,
+    pub speed: f64,
+    pub kick: f64,
+    pub duration: f64, // s
+    pub condition: RoadCondition,
+}
+
+#[derive(Debug, Default)]
+pub struct SwayRun {
+    pub time: Vec<f64>,
+    pub hitch_angle: Vec<f64>, // degrees
+    pub speed: Vec<f64>,       // km/h
+    pub peak_angle: f64,
+    // Since when the sway has stayed below the release threshold
+    pub settled_after: Option<f64>,
+    pub jackknifed_after: Option<f64>,
+    pub braking_seconds: f64,
+}
+
+// Integrates the hitch angle as a damped oscillator. With `mitigation` the
+// car brakes once the sway amplitude exceeds the threshold, until the sway
+// has died down and the speed is lower than where it began: the wheels on
+// the side the trailer swings towards brake harder, which yaws the car
+// against the swing and damps it, and the lower speed damps it as well.
+// How hard it can brake depends on the grip of the road.
+pub fn simulate(scenario: &SwayScenario, mitigation: bool) -> SwayRun {
+    let omega = 2.0 * PI * SWAY_FREQUENCY;
+    let vehicle = Vehicle::new();
+    let traction = vehicle.adjust_for_condition(scenario.condition.traction());
+    let deceleration = vehicle.achieved_deceleration(MITIGATION_DECELERATION, traction) as f64;
+    let added_damping = MITIGATION_DAMPING * deceleration / MITIGATION_DECELERATION as f64;
+
+    let mut run = SwayRun::default();
+    let (mut angle, mut rate) = (scenario.kick.to_radians(), 0.0);
+    let mut speed = scenario.speed / 3.6;
+    let mut braking = false;
+    let mut target_speed = speed;
+    let mut last_swaying = None;
+
+    let steps = (scenario.duration / DT).round() as u64;
+    for step in 0..=steps {
+        let time = step as f64 * DT;
+        run.time.push(time);
+        run.hitch_angle.push(angle.to_degrees());
+        run.speed.push(speed * 3.6);
+        run.peak_angle = run.peak_angle.max(angle.to_degrees().abs());
+
+        let amplitude = angle.hypot(rate / omega).to_degrees();
+        if amplitude > JACKKNIFE_ANGLE {
+            run.jackknifed_after = Some(time);
+            return run;
+        }
+        if amplitude >= RELEASE_THRESHOLD {
+            last_swaying = Some(time);
+        }
+        if mitigation {
+            if amplitude > SWAY_THRESHOLD && !braking {
+                braking = true;
+                target_speed = (speed - SPEED_REDUCTION / 3.6).max(0.0);
+            } else if amplitude < RELEASE_THRESHOLD && speed <= target_speed {
+                braking = false;
+            }
+        }
+
+        let mut damping = scenario.trailer.damping_ratio(speed * 3.6);
+        if braking {
+            damping += added_damping;
+            speed = (speed - deceleration * DT).max(0.0);
+            run.braking_seconds += DT;
+        }
+        rate -= (2.0 * damping * omega * rate + omega * omega * angle) * DT;
+        angle += rate * DT;
+    }
+
+    let settled = angle.hypot(rate / omega).to_degrees() < RELEASE_THRESHOLD;
+    run.settled_after = settled.then(|| last_swaying.map_or(0.0, |time| time + DT));
+    run
+}
+
+pub fn print_comparison(scenario: &SwayScenario, unmitigated: &SwayRun, mitigated: &SwayRun) {
+    let locale = locale::current();
+    println!(
+        "Trailer of {} kg at {} on a {} road, knocked to {}°; sway grows above {}",
+        locale.number(scenario.trailer.mass, 0),
+        locale.speed(scenario.speed, 0),
+        format!("{:?}", scenario.condition).to_lowercase(),
+        locale.number(scenario.kick, 1),
+        locale.speed(scenario.trailer.critical_speed(), 0)
+    );
+    for (name, run) in [("Without mitigation", unmitigated), ("With mitigation", mitigated)] {
+        let outcome = match (run.jackknifed_after, run.settled_after) {
+            (Some(time), _) => format!("jackknifed after {} s", locale.number(time, 1)),
+            (None, Some(time)) => format!("settled after {} s", locale.number(time, 1)),
+            (None, None) => "still swaying at the end".to_string(),
+        };
+        println!(
+            "{:<20} peak {:>6}°, {}, braked {} s, {} at the end",
+            name,
+            locale.number(run.peak_angle, 1),
+            outcome,
+            locale.number(run.braking_seconds, 1),
+            locale.speed(run.speed.last().cloned().unwrap_or(scenario.speed), 0)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(condition: RoadCondition) -> SwayScenario {
+        SwayScenario {
+            trailer: Trailer { mass: 1800.0 },
+            speed: 110.0,
+            kick: 2.0,
+            duration: 30.0,
+            condition,
+        }
+    }
+
+    #[test]
+    fn braking_stops_sway_above_the_critical_speed() {
+        let dry = scenario(RoadCondition::Dry);
+        assert!(dry.trailer.critical_speed() < dry.speed);
+
+        let unmitigated = simulate(&dry, false);
+        assert!(unmitigated.jackknifed_after.is_some());
+
+        let mitigated = simulate(&dry, true);
+        assert!(mitigated.jackknifed_after.is_none());
+        assert!(mitigated.settled_after.is_some());
+        assert!(mitigated.speed.last().unwrap() < &dry.trailer.critical_speed());
+
+        // Less grip, less braking: it takes longer to calm the trailer down
+        let icy = simulate(&scenario(RoadCondition::Icy), true);
+        assert!(icy.braking_seconds > mitigated.braking_seconds);
+        assert!(icy.settled_after.unwrap() > mitigated.settled_after.unwrap());
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Gauge, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use vehicle_sim_core::locale;
+use vehicle_sim_core::simulation::{PauseControl, RunSummary};
+
+use crate::road_condition::RoadCondition;
+use crate::simulation::RoadState;
+
+// Full scale of the gauges and sparklines
+const MAX_SPEED: f64 = 150.0; // km/h, the vehicle's top speed
+const MAX_SLOPE: f64 = 10.0; // degrees either way
+const MAX_TRACTION: f64 = 1.25; // dry road, new tires, downhill
+const MAX_STOPPING_DISTANCE: f64 = 300.0; // m
+// Steps kept for the sparklines, more than any terminal is wide
+const HISTORY: usize = 500;
+// Keys are read and the screen redrawn at least this often
+const REFRESH: Duration = Duration::from_millis(100);
+
+const HELP: &str = "space pause/resume · s step · d/w/i force dry/wet/icy · a follow the weather · q quit";
+
+// Full-screen terminal dashboard fed the state after every step. It runs on
+// its own thread so keys still work while the run is paused or waiting for
+// the wall clock.
+pub struct Tui {
+    states: Sender<(RoadState, RunSummary)>,
+    thread: JoinHandle<io::Result<()>>,
+}
+
+impl Tui {
+    // Keys pause and step the run through `pause`, end it through `stop` and
+    // force road conditions through `overrides`
+    pub fn start(pause: Arc<PauseControl>, stop: Arc<AtomicBool>, overrides: Sender<Option<RoadCondition>>) -> Tui {
+        let (states, received) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut terminal = ratatui::init();
+            let result = App::new(pause, stop, overrides).run(&mut terminal, received);
+            ratatui::restore();
+            result
+        });
+        Tui { states, thread }
+    }
+
+    pub fn publish(&self, state: RoadState, summary: &RunSummary) {
+        // Gone once the user quit
+        let _ = self.states.send((state, *summary));
+    }
+
+    // Waits for the user to leave the dashboard after the run ended
+    pub fn finish(self) -> io::Result<()> {
+        drop(self.states);
+        self.thread.join().unwrap_or_else(|_| Err(io::Error::other("the dashboard crashed")))
+    }
+}
+
+struct App {
+    pause: Arc<PauseControl>,
+    stop: Arc<AtomicBool>,
+    overrides: Sender<Option<RoadCondition>>,
+    latest: Option<(RoadState, RunSummary)>,
+    speed: VecDeque<u64>,
+    stopping_distance: VecDeque<u64>,
+    finished: bool,
+}
+
+impl App {
+    fn new(pause: Arc<PauseControl>, stop: Arc<AtomicBool>, overrides: Sender<Option<RoadCondition>>) -> App {
+        App {
+            pause,
+            stop,
+            overrides,
+            latest: None,
+            speed: VecDeque::new(),
+            stopping_distance: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal, states: Receiver<(RoadState, RunSummary)>) -> io::Result<()> {
+        loop {
+            loop {
+                match states.try_recv() {
+                    Ok((state, summary)) => self.record(state, summary),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.finished = true;
+                        break;
+                    }
+                }
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if event::poll(REFRESH)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.handle_key(key.code, key.modifiers) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, state: RoadState, summary: RunSummary) {
+        for (history, value) in [
+            (&mut self.speed, state.speed as f64),
+            (&mut self.stopping_distance, (state.stopping_distance.total as f64).min(MAX_STOPPING_DISTANCE)),
+        ] {
+            history.push_back(value.round() as u64);
+            if history.len() > HISTORY {
+                history.pop_front();
+            }
+        }
+        self.latest = Some((state, summary));
+    }
+
+    // Returns false to leave the dashboard
+    fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        // Once the run is over any key leaves
+        if self.finished {
+            return false;
+        }
+        let force = |condition| {
+            let _ = self.overrides.send(condition);
+        };
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.stop.store(true, Ordering::SeqCst);
+                return false;
+            }
+            // Raw mode turns Ctrl-C into a key press
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                self.stop.store(true, Ordering::SeqCst);
+                return false;
+            }
+            KeyCode::Char(' ') | KeyCode::Char('p') => self.pause.toggle(),
+            KeyCode::Char('s') => self.pause.step_once(),
+            KeyCode::Char('d') => force(Some(RoadCondition::Dry)),
+            KeyCode::Char('w') => force(Some(RoadCondition::Wet)),
+            KeyCode::Char('i') => force(Some(RoadCondition::Icy)),
+            KeyCode::Char('a') => force(None),
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, gauges, sparklines, footer] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Length(15),
+            Constraint::Min(6),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(self.header(), header);
+        if let Some((state, _)) = &self.latest {
+            draw_gauges(frame, gauges, state);
+        }
+
+        let [speed, stopping_distance] = Layout::horizontal([Constraint::Fill(1); 2]).areas(sparklines);
+        let sparkline = |title: &str, history: &VecDeque<u64>, max: f64, color: Color, area: Rect| {
+            // Newest on the right, as many as fit
+            let shown = history.len().min(area.width.saturating_sub(2) as usize);
+            Sparkline::default()
+                .block(Block::bordered().title(title.to_string()))
+                .data(history.iter().skip(history.len() - shown).copied().collect::<Vec<_>>())
+                .max(max as u64)
+                .style(Style::default().fg(color))
+        };
+        frame.render_widget(sparkline(" Speed ", &self.speed, MAX_SPEED, Color::Yellow, speed), speed);
+        frame.render_widget(
+            sparkline(" Stopping distance ", &self.stopping_distance, MAX_STOPPING_DISTANCE, Color::Red, stopping_distance),
+            stopping_distance,
+        );
+
+        let help = if self.finished { "Run finished, press any key to leave" } else { HELP };
+        frame.render_widget(Paragraph::new(help).style(Style::default().add_modifier(Modifier::DIM)), footer);
+    }
+
+    fn header(&self) -> Paragraph<'_> {
+        let locale = locale::current();
+        let status = if self.finished {
+            "FINISHED"
+        } else if self.pause.is_paused() {
+            "PAUSED"
+        } else {
+            "RUNNING"
+        };
+        let lines = match &self.latest {
+            Some((state, summary)) => vec![
+                format!(
+                    "{} | step {} | {} s | {:?}{} at {}",
+                    status,
+                    summary.steps,
+                    locale.number(summary.simulated_seconds, 0),
+                    state.road_condition,
+                    if state.forced { " (forced)" } else { "" },
+                    locale.temperature(state.ambient_temperature, 1)
+                ),
+                format!(
+                    "Brake pedal {}% -> requested {} m/s², achieved {} m/s²{}, stopping distance {}",
+                    locale.number(state.pedal_position as f64 * 100.0, 0),
+                    locale.number(state.requested_deceleration as f64, 2),
+                    locale.number(state.achieved_deceleration as f64, 2),
+                    if state.pedal_stopping_distance.abs_active { " (ABS)" } else { "" },
+                    locale.length(state.pedal_stopping_distance.total as f64, 1)
+                ),
+            ],
+            None => vec![format!("{} | waiting for the first step", status)],
+        };
+        Paragraph::new(lines.join("\n")).block(Block::bordered().title(" Road Condition Monitor "))
+    }
+}
+
+fn draw_gauges(frame: &mut Frame, area: Rect, state: &RoadState) {
+    let locale = locale::current();
+    let condition_color = match state.road_condition {
+        RoadCondition::Dry => Color::Green,
+        RoadCondition::Wet => Color::Blue,
+        RoadCondition::Icy => Color::Cyan,
+    };
+    let stopping_distance = state.stopping_distance;
+    let gauges = [
+        (
+            " Speed ",
+            state.speed as f64 / MAX_SPEED,
+            locale.speed(state.speed as f64, 1),
+            Color::Yellow,
+        ),
+        (
+            " Road slope ",
+            (state.road_slope as f64 + MAX_SLOPE) / (2.0 * MAX_SLOPE),
+            format!("{}°", locale.number(state.road_slope as f64, 1)),
+            Color::Magenta,
+        ),
+        (
+            " Tire condition ",
+            state.tire_condition as f64,
+            locale.number(state.tire_condition as f64, 2),
+            Color::White,
+        ),
+        (
+            " Traction ",
+            state.traction as f64 / MAX_TRACTION,
+            format!("{} ({:?})", locale.number(state.traction as f64, 2), state.road_condition),
+            condition_color,
+        ),
+        (
+            " Stopping distance ",
+            stopping_distance.total as f64 / MAX_STOPPING_DISTANCE,
+            format!(
+                "{} (reaction {} + braking {})",
+                locale.length(stopping_distance.total as f64, 1),
+                locale.length(stopping_distance.reaction_distance as f64, 1),
+                locale.length(stopping_distance.braking_distance as f64, 1)
+            ),
+            Color::Red,
+        ),
+    ];
+
+    let rows = Layout::vertical([Constraint::Length(3); 5]).split(area);
+    for ((title, ratio, label, color), row) in gauges.into_iter().zip(rows.iter()) {
+        let gauge = Gauge::default()
+            .block(Block::bordered().title(title))
+            .gauge_style(Style::default().fg(color))
+            .ratio(ratio.clamp(0.0, 1.0))
+            .label(label);
+        frame.render_widget(gauge, *row);
+    }
+}
@@ -1,20 +1,99 @@
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::tire::Tire;
+use vehicle_sim_core::units::Speed;
 
+use crate::brakes::BrakeDiscs;
+
+const GRAVITY: f32 = 9.81;
+
+// Tire condition lost per step at most; the wear of a step is drawn below it
+pub const TIRE_WEAR_RATE: f32 = 0.02;
+
+// Time from spotting the hazard to touching the brake pedal, in seconds
+pub const REACTION_TIME: f32 = 1.0;
+// Time for the brake pressure to build up to the requested level
+pub const BRAKE_RAMP_TIME: f32 = 0.3;
+// Wheel slip above which the ABS starts modulating brake pressure
+pub const ABS_SLIP_THRESHOLD: f32 = 0.2;
+// Slip at which the tire transfers its peak friction
+const PEAK_FRICTION_SLIP: f32 = 0.15;
+// Share of the peak friction the ABS keeps while cycling around the threshold
+const ABS_FRICTION_RATIO: f32 = 0.95;
+// A locked wheel slides on the lower kinetic friction
+const LOCKED_WHEEL_FRICTION_RATIO: f32 = 0.7;
+// Curves are laid out for at most this lateral acceleration at the speed
+// driven, in m/s^2; the tightest are hairpins of MIN_CURVE_RADIUS m
+const DESIGN_LATERAL_ACCELERATION: f32 = 4.0;
+const MIN_CURVE_RADIUS: f32 = 20.0;
+// Share of the weight on the driven wheels
+const DRIVEN_AXLE_SHARE: f32 = 0.5;
+// Mass of the car, and the deceleration rolling resistance, drag and the
+// engine manage without the brakes
+const VEHICLE_MASS: f32 = 1500.0; // kg
+const COAST_DECELERATION: f32 = 0.5; // m/s^2
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StoppingDistance {
+    // Travelled at full speed during the driver's reaction time
+    pub reaction_distance: f32,
+    // Travelled from the first brake application to standstill
+    pub braking_distance: f32,
+    pub total: f32,
+    pub abs_active: bool,
+}
+
+impl StoppingDistance {
+    fn new(reaction_distance: f32, braking_distance: f32, abs_active: bool) -> Self {
+        StoppingDistance {
+            reaction_distance,
+            braking_distance,
+            total: reaction_distance + braking_distance,
+            abs_active,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Vehicle {
-    pub speed: f32,
+    pub speed: Speed,
     pub braking_efficiency: f32,
     pub tire_condition: f32,
     pub road_slope: f32,
+    pub abs_enabled: bool,
+    #[serde(default = "default_tire_wear_rate")]
+    pub tire_wear_rate: f32,
+    // One over the radius of the curve driven, positive to the left
+    #[serde(default)]
+    pub road_curvature: f32, // 1/m
+    // Pressure, tread, which wears down with the tire condition, and load;
+    // an under-inflated tire transfers less of the road's grip
+    #[serde(default)]
+    pub tire: Tire,
+    // Disc temperature; hot discs fade and lower the braking efficiency
+    #[serde(default)]
+    pub brakes: BrakeDiscs,
+}
+
+fn default_tire_wear_rate() -> f32 {
+    TIRE_WEAR_RATE
 }
 
 impl Vehicle {
     pub fn new() -> Self {
-        Vehicle {
-            speed: 50.0,
+        let mut vehicle = Vehicle {
+            speed: Speed::from_kmh(50.0),
             braking_efficiency: 0.9,
             tire_condition: 0.9,
             road_slope: 0.0,
-        }
+            abs_enabled: true,
+            tire_wear_rate: TIRE_WEAR_RATE,
+            road_curvature: 0.0,
+            tire: Tire::default(),
+            brakes: BrakeDiscs::default(),
+        };
+        vehicle.wear_tread();
+        vehicle
     }
 
     pub fn adjust_for_condition(&self, traction: f32) -> f32 {
@@ -29,27 +108,268 @@ impl Vehicle {
         adjusted_traction
     }
 
-    pub fn calculate_stopping_distance(&self, traction: f32) -> f32 {
-        let velocity = self.speed / 3.6;
-        let gravity = 9.81;
-        (velocity * velocity) / (2.0 * traction * gravity * self.braking_efficiency)
+    // Emergency stop: the driver asks for more than the tires can transfer
+    pub fn calculate_stopping_distance(&self, traction: f32) -> StoppingDistance {
+        self.calculate_stopping_distance_for_request(f32::INFINITY, traction)
+    }
+
+    // What the brakes deliver of the grip, less while the discs fade
+    pub fn effective_braking_efficiency(&self) -> f32 {
+        self.braking_efficiency * self.brakes.fade()
     }
 
-    pub fn update_speed(&mut self) {
-        let mut rng = rand::thread_rng();
+    // Deceleration the tires can transfer at peak friction
+    fn grip_limit(&self, traction: f32) -> f32 {
+        traction * GRAVITY * self.effective_braking_efficiency() * self.tire.grip_factor()
+    }
+
+    // Acceleration the driven axle can put down before the wheels spin; it
+    // carries about half the weight
+    pub fn drive_limit(&self, traction: f32) -> f32 {
+        traction * GRAVITY * DRIVEN_AXLE_SHARE * self.tire.grip_factor()
+    }
+
+    // Slip grows with the requested share of the grip and the wheel locks
+    // once the request exceeds it
+    pub fn wheel_slip(&self, requested_deceleration: f32, traction: f32) -> f32 {
+        let grip = self.grip_limit(traction);
+        if grip <= 0.0 || requested_deceleration > grip {
+            return 1.0;
+        }
+        PEAK_FRICTION_SLIP * (requested_deceleration / grip).max(0.0)
+    }
+
+    pub fn abs_active(&self, requested_deceleration: f32, traction: f32) -> bool {
+        self.abs_enabled && self.wheel_slip(requested_deceleration, traction) > ABS_SLIP_THRESHOLD
+    }
+
+    // The brakes can only deliver what the tires can transfer to the road;
+    // beyond that the ABS keeps the wheels near peak friction, or they lock
+    pub fn achieved_deceleration(&self, requested_deceleration: f32, traction: f32) -> f32 {
+        let grip = self.grip_limit(traction);
+        if self.wheel_slip(requested_deceleration, traction) <= ABS_SLIP_THRESHOLD {
+            requested_deceleration.max(0.0)
+        } else if self.abs_enabled {
+            grip * ABS_FRICTION_RATIO
+        } else {
+            grip * LOCKED_WHEEL_FRICTION_RATIO
+        }
+    }
+
+    pub fn calculate_stopping_distance_for_request(&self, requested_deceleration: f32, traction: f32) -> StoppingDistance {
+        let velocity = self.speed.meters_per_second() as f32;
+        let reaction_distance = velocity * REACTION_TIME;
+        let abs_active = self.abs_active(requested_deceleration, traction);
+
+        let deceleration = self.achieved_deceleration(requested_deceleration, traction);
+        if deceleration <= 0.0 {
+            return StoppingDistance::new(reaction_distance, f32::INFINITY, abs_active);
+        }
+
+        StoppingDistance::new(reaction_distance, braking_distance(velocity, deceleration), abs_active)
+    }
+
+    pub fn update_speed(&mut self, rng: &mut impl Rng) {
         let speed_change: f32 = rng.gen_range(-10.0..10.0);
-        self.speed = (self.speed + speed_change).clamp(0.0, 150.0);
+        self.speed = Speed::from_kmh((self.speed.kmh() as f32 + speed_change).clamp(0.0, 150.0) as f64);
     }
 
-    pub fn update_road_slope(&mut self) {
-        let mut rng = rand::thread_rng();
+    pub fn update_road_slope(&mut self, rng: &mut impl Rng) {
         let slope_change: f32 = rng.gen_range(-5.0..5.0);
         self.road_slope = (self.road_slope + slope_change).clamp(-10.0, 10.0);
     }
 
-    pub fn update_tire_condition(&mut self) {
-        let mut rng = rand::thread_rng();
-        let wear: f32 = rng.gen_range(-0.02..0.0);
+    // Half the time the road runs straight; its curves are laid out for
+    // the speed driven, so they are wider the faster the car goes
+    pub fn update_road_curvature(&mut self, rng: &mut impl Rng) {
+        if rng.gen_bool(0.5) {
+            self.road_curvature = 0.0;
+            return;
+        }
+        let velocity = self.speed.meters_per_second() as f32;
+        let min_radius = (velocity * velocity / DESIGN_LATERAL_ACCELERATION).max(MIN_CURVE_RADIUS);
+        let radius = rng.gen_range(min_radius..min_radius * 5.0);
+        self.road_curvature = if rng.gen_bool(0.5) { 1.0 / radius } else { -1.0 / radius };
+    }
+
+    pub fn update_tire_condition(&mut self, rng: &mut impl Rng) {
+        if self.tire_wear_rate <= 0.0 {
+            return;
+        }
+        let wear: f32 = rng.gen_range(-self.tire_wear_rate..0.0);
         self.tire_condition = (self.tire_condition + wear).clamp(0.5, 1.0);
+        self.wear_tread();
+    }
+
+    // The brakes hold the car on descents and take off whatever it slowed
+    // down by since `previous_speed` (m/s) beyond what it would coasting;
+    // their heat goes into the discs
+    pub fn update_brakes(&mut self, previous_speed: f32, ambient_temperature: f32, dt: f32) {
+        let speed = self.speed.meters_per_second() as f32;
+        let slowing = (previous_speed - speed) / dt;
+        let downhill = GRAVITY * (-self.road_slope).to_radians().sin();
+        let braking = (slowing + downhill - COAST_DECELERATION).max(0.0);
+        let average_speed = (previous_speed + speed) / 2.0;
+        self.brakes.step(VEHICLE_MASS * braking * average_speed * dt, average_speed, ambient_temperature, dt);
+    }
+
+    // The worst tire condition leaves the legal minimum of tread
+    fn wear_tread(&mut self) {
+        self.tire.set_wear((1.0 - self.tire_condition) / 0.5);
+    }
+}
+
+// Deceleration rises linearly to its full value over BRAKE_RAMP_TIME and
+// stays constant afterwards
+fn braking_distance(velocity: f32, deceleration: f32) -> f32 {
+    let ramp = BRAKE_RAMP_TIME;
+    let velocity_after_ramp = velocity - deceleration * ramp / 2.0;
+
+    if velocity_after_ramp <= 0.0 {
+        // Stops before the brakes reach full pressure
+        let stop_time = (2.0 * velocity * ramp / deceleration).sqrt();
+        return velocity * stop_time - deceleration * stop_time.powi(3) / (6.0 * ramp);
+    }
+
+    let ramp_distance = velocity * ramp - deceleration * ramp * ramp / 6.0;
+    ramp_distance + velocity_after_ramp * velocity_after_ramp / (2.0 * deceleration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use vehicle_sim_core::tire::RATED_LOAD;
+    use vehicle_sim_core::units::{Pressure, Temperature};
+
+    fn vehicle_at(speed: f32, abs_enabled: bool) -> Vehicle {
+        Vehicle {
+            speed: Speed::from_kmh(speed as f64),
+            abs_enabled,
+            ..Vehicle::new()
+        }
+    }
+
+    #[test]
+    fn reaction_distance_is_travelled_at_full_speed() {
+        let vehicle = Vehicle::new();
+        let distance = vehicle.calculate_stopping_distance(1.0);
+
+        assert!((distance.reaction_distance - 50.0 / 3.6 * REACTION_TIME).abs() < 1e-4);
+        assert!((distance.total - distance.reaction_distance - distance.braking_distance).abs() < 1e-4);
+    }
+
+    #[test]
+    fn brake_ramp_lengthens_braking_distance() {
+        let vehicle = Vehicle::new();
+        let velocity = vehicle.speed.meters_per_second() as f32;
+        let deceleration = 3.0;
+        let instant_braking = velocity * velocity / (2.0 * deceleration);
+
+        let distance = vehicle.calculate_stopping_distance_for_request(deceleration, 1.0);
+        assert!(!distance.abs_active);
+        assert!(distance.braking_distance > instant_braking);
+    }
+
+    #[test]
+    fn abs_shortens_emergency_stop_on_ice() {
+        let mut vehicle = Vehicle::new();
+        let with_abs = vehicle.calculate_stopping_distance(0.3);
+        vehicle.abs_enabled = false;
+        let locked = vehicle.calculate_stopping_distance(0.3);
+
+        assert!(with_abs.abs_active);
+        assert!(!locked.abs_active);
+        assert!(with_abs.braking_distance < locked.braking_distance);
+    }
+
+    #[test]
+    fn under_inflation_lengthens_the_emergency_stop() {
+        let mut vehicle = Vehicle::new();
+        let inflated = vehicle.calculate_stopping_distance(0.8);
+        vehicle.tire.pressure = Pressure::from_kpa(150.0);
+        let soft = vehicle.calculate_stopping_distance(0.8);
+        assert!(soft.braking_distance > inflated.braking_distance);
+        assert_eq!(soft.reaction_distance, inflated.reaction_distance);
+
+        // A warm tire reads high but has lost no air
+        vehicle.tire.pressure = vehicle.tire.nominal_pressure;
+        vehicle.tire.set_temperature(Temperature::from_celsius(60.0));
+        assert!(vehicle.tire.pressure > vehicle.tire.nominal_pressure);
+        assert!((vehicle.tire.grip_factor() - 1.0).abs() < 1e-3);
+
+        // An overloaded tire needs more air than its nominal pressure
+        vehicle.tire.load = 2.0 * RATED_LOAD;
+        assert!(vehicle.tire.grip_factor() < 0.8);
+    }
+
+    #[test]
+    fn long_descent_fades_the_brakes() {
+        let mut vehicle = vehicle_at(80.0, true);
+        let cold = vehicle.calculate_stopping_distance(0.9);
+        let velocity = vehicle.speed.meters_per_second() as f32;
+
+        // Holding 80 km/h down a steep pass for ten minutes
+        vehicle.road_slope = -8.0;
+        for _ in 0..120 {
+            vehicle.update_brakes(velocity, 10.0, 5.0);
+        }
+        assert!(vehicle.brakes.is_fading());
+        assert!(vehicle.effective_braking_efficiency() < vehicle.braking_efficiency);
+        assert!(vehicle.calculate_stopping_distance(0.9).braking_distance > cold.braking_distance);
+
+        // A gentle descent is taken on the engine alone, and the airflow
+        // cools the discs down again
+        vehicle.road_slope = -2.0;
+        for _ in 0..240 {
+            vehicle.update_brakes(velocity, 10.0, 5.0);
+        }
+        assert!(vehicle.brakes.temperature < 15.0);
+        assert_eq!(vehicle.effective_braking_efficiency(), vehicle.braking_efficiency);
+    }
+
+    proptest! {
+        #[test]
+        fn stopping_distance_grows_with_speed(
+            speed in 1.0f32..150.0,
+            increase in 1.0f32..100.0,
+            traction in 0.05f32..1.2,
+            abs_enabled in any::<bool>(),
+        ) {
+            let slower = vehicle_at(speed, abs_enabled).calculate_stopping_distance(traction);
+            let faster = vehicle_at(speed + increase, abs_enabled).calculate_stopping_distance(traction);
+
+            prop_assert!(faster.total > slower.total);
+            prop_assert!(faster.braking_distance > slower.braking_distance);
+        }
+
+        #[test]
+        fn stopping_distance_shrinks_with_traction(
+            speed in 1.0f32..150.0,
+            traction in 0.05f32..1.0,
+            increase in 0.05f32..0.5,
+            abs_enabled in any::<bool>(),
+        ) {
+            let vehicle = vehicle_at(speed, abs_enabled);
+            let slippery = vehicle.calculate_stopping_distance(traction);
+            let grippy = vehicle.calculate_stopping_distance(traction + increase);
+
+            prop_assert!(grippy.braking_distance < slippery.braking_distance);
+            prop_assert_eq!(grippy.reaction_distance, slippery.reaction_distance);
+        }
+
+        #[test]
+        fn brakes_never_deliver_more_than_requested_or_the_grip_allows(
+            requested in 0.0f32..20.0,
+            traction in 0.05f32..1.2,
+            abs_enabled in any::<bool>(),
+        ) {
+            let vehicle = vehicle_at(50.0, abs_enabled);
+            let achieved = vehicle.achieved_deceleration(requested, traction);
+
+            prop_assert!(achieved >= 0.0);
+            prop_assert!(achieved <= requested.max(0.0) + 1e-4);
+            prop_assert!(achieved <= vehicle.grip_limit(traction) + 1e-4);
+        }
     }
 }
@@ -0,0 +1,246 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::scenario::{self, WeatherPoint};
+use vehicle_sim_core::units::Celsius;
+
+use crate::road_condition::RoadCondition;
+
+// Above this ambient temperature the road cannot freeze, in °C
+pub const ICE_MAX_TEMPERATURE: Celsius = 4.0;
+// How fast the surface traction follows a change of weather, per second
+const TRACTION_CHANGE_RATE: f32 = 0.01;
+// Largest ambient temperature change per step, in °C
+const TEMPERATURE_DRIFT: Celsius = 0.3;
+
+// Per-step Markov chain over Dry/Wet/Icy; row `i` holds the probabilities
+// of moving from condition `i` to each condition
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WeatherTransitions {
+    rows: [[f64; 3]; 3],
+}
+
+impl Default for WeatherTransitions {
+    fn default() -> Self {
+        WeatherTransitions {
+            rows: [[0.90, 0.08, 0.02], [0.10, 0.85, 0.05], [0.02, 0.18, 0.80]],
+        }
+    }
+}
+
+impl WeatherTransitions {
+    // Rows for Dry, Wet and Icy separated by `;`, e.g.
+    // `0.9,0.08,0.02;0.1,0.85,0.05;0.02,0.18,0.8`. Rows are normalized.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let rows: Vec<&str> = value.split(';').collect();
+        if rows.len() != 3 {
+            return Err(format!("expected 3 rows separated by ';', got {}", rows.len()));
+        }
+
+        let mut transitions = [[0.0; 3]; 3];
+        for (row, text) in transitions.iter_mut().zip(rows) {
+            let values = text
+                .split(',')
+                .map(|p| p.trim().parse::<f64>().map_err(|_| format!("invalid probability '{}'", p.trim())))
+                .collect::<Result<Vec<f64>, String>>()?;
+            if values.len() != 3 || values.iter().any(|p| *p < 0.0) {
+                return Err(format!("expected 3 non-negative probabilities, got '{}'", text));
+            }
+
+            let total: f64 = values.iter().sum();
+            if total <= 0.0 {
+                return Err(format!("probabilities of '{}' sum to zero", text));
+            }
+            for (p, value) in row.iter_mut().zip(values) {
+                *p = value / total;
+            }
+        }
+
+        Ok(WeatherTransitions { rows: transitions })
+    }
+
+    pub fn next(&self, from: RoadCondition, rng: &mut impl Rng) -> RoadCondition {
+        let mut draw: f64 = rng.gen();
+        for (condition, p) in RoadCondition::ALL.iter().zip(self.rows[from.index()]) {
+            if draw < p {
+                return *condition;
+            }
+            draw -= p;
+        }
+        from
+    }
+}
+
+// Weather drives the road surface: the condition follows the Markov chain,
+// ice needs freezing temperatures and traction changes gradually.
+#[derive(Serialize, Deserialize)]
+pub struct WeatherModel {
+    transitions: WeatherTransitions,
+    condition: RoadCondition,
+    ambient_temperature: Celsius,
+    traction: f32,
+    // Scenario weather held over the Markov chain, and the time into it
+    #[serde(default)]
+    timeline: Vec<WeatherPoint>,
+    #[serde(default)]
+    elapsed: f64,
+    // Held by hand until released, over the timeline too
+    #[serde(default)]
+    forced: Option<RoadCondition>,
+}
+
+impl WeatherModel {
+    pub fn new(transitions: WeatherTransitions, ambient_temperature: Celsius) -> Self {
+        WeatherModel {
+            transitions,
+            condition: RoadCondition::Dry,
+            ambient_temperature,
+            traction: RoadCondition::Dry.traction(),
+            timeline: Vec::new(),
+            elapsed: 0.0,
+            forced: None,
+        }
+    }
+
+    // Starts on `condition` with the surface already at its traction
+    pub fn with_condition(mut self, condition: RoadCondition) -> Self {
+        self.condition = condition;
+        self.traction = condition.traction();
+        self
+    }
+
+    pub fn with_timeline(mut self, timeline: Vec<WeatherPoint>) -> Self {
+        self.timeline = timeline;
+        self
+    }
+
+    pub fn step(&mut self, dt: f64, rng: &mut impl Rng) {
+        let drift: Celsius = rng.gen_range(-TEMPERATURE_DRIFT..TEMPERATURE_DRIFT);
+        self.ambient_temperature = (self.ambient_temperature + drift).clamp(-30.0, 45.0);
+
+        let mut next = self.transitions.next(self.condition, rng);
+        if next == RoadCondition::Icy && self.ambient_temperature > ICE_MAX_TEMPERATURE {
+            // Ice melts, or precipitation falls as rain
+            next = RoadCondition::Wet;
+        }
+        self.condition = next;
+
+        // Scenario weather wins, even ice above freezing
+        self.elapsed += dt;
+        if let Some(point) = scenario::weather_at(&self.timeline, self.elapsed) {
+            if let Some(condition) = point.condition.as_deref().and_then(RoadCondition::parse) {
+                self.condition = condition;
+            }
+            if let Some(temperature) = point.temperature {
+                self.ambient_temperature = temperature as Celsius;
+            }
+        }
+
+        if let Some(condition) = self.forced {
+            self.condition = condition;
+        }
+
+        let max_change = TRACTION_CHANGE_RATE * dt as f32;
+        self.traction += (self.condition.traction() - self.traction).clamp(-max_change, max_change);
+    }
+
+    // `None` hands the condition back to the weather
+    pub fn force(&mut self, condition: Option<RoadCondition>) {
+        self.forced = condition;
+    }
+
+    pub fn forced(&self) -> Option<RoadCondition> {
+        self.forced
+    }
+
+    pub fn condition(&self) -> RoadCondition {
+        self.condition
+    }
+
+    pub fn ambient_temperature(&self) -> Celsius {
+        self.ambient_temperature
+    }
+
+    // Surface traction, lagging behind the nominal traction of the condition
+    pub fn traction(&self) -> f32 {
+        self.traction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn no_ice_above_freezing_threshold() {
+        let always_icy = WeatherTransitions::parse("0,0,1;0,0,1;0,0,1").unwrap();
+        let mut weather = WeatherModel::new(always_icy, 20.0);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for _ in 0..20 {
+            weather.step(5.0, &mut rng);
+            assert_eq!(weather.condition(), RoadCondition::Wet);
+        }
+    }
+
+    #[test]
+    fn scenario_timeline_holds_the_weather() {
+        let point = |at, condition: Option<&str>, temperature| WeatherPoint {
+            at,
+            condition: condition.map(str::to_string),
+            temperature,
+        };
+        let always_dry = WeatherTransitions::parse("1,0,0;1,0,0;1,0,0").unwrap();
+        let timeline = vec![point(10.0, Some("icy"), Some(-5.0)), point(20.0, None, None)];
+        let mut weather = WeatherModel::new(always_dry, 10.0).with_timeline(timeline);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        weather.step(5.0, &mut rng);
+        assert_eq!(weather.condition(), RoadCondition::Dry);
+        for _ in 0..2 {
+            weather.step(5.0, &mut rng);
+            assert_eq!(weather.condition(), RoadCondition::Icy);
+            assert_eq!(weather.ambient_temperature(), -5.0);
+        }
+        // Released back to the Markov chain, drifting from where it was held
+        weather.step(5.0, &mut rng);
+        assert_eq!(weather.condition(), RoadCondition::Dry);
+        assert!((weather.ambient_temperature() + 5.0).abs() <= TEMPERATURE_DRIFT);
+    }
+
+    #[test]
+    fn forced_condition_wins_over_the_timeline() {
+        let always_dry = WeatherTransitions::parse("1,0,0;1,0,0;1,0,0").unwrap();
+        let timeline = vec![WeatherPoint {
+            at: 0.0,
+            condition: Some("wet".to_string()),
+            temperature: None,
+        }];
+        let mut weather = WeatherModel::new(always_dry, 20.0).with_timeline(timeline);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        weather.force(Some(RoadCondition::Icy));
+        weather.step(5.0, &mut rng);
+        assert_eq!(weather.condition(), RoadCondition::Icy);
+
+        weather.force(None);
+        weather.step(5.0, &mut rng);
+        assert_eq!(weather.condition(), RoadCondition::Wet);
+    }
+
+    #[test]
+    fn traction_changes_gradually() {
+        let always_wet = WeatherTransitions::parse("0,1,0;0,1,0;0,1,0").unwrap();
+        let mut weather = WeatherModel::new(always_wet, 10.0);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        weather.step(5.0, &mut rng);
+        assert!((weather.traction() - 0.95).abs() < 1e-4);
+
+        for _ in 0..10 {
+            weather.step(5.0, &mut rng);
+        }
+        assert!((weather.traction() - RoadCondition::Wet.traction()).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Parser, Debug)]
+#[command(name = "service_tool", about = "Reads and clears DTCs, runs actuator tests and controls a running simulation")]
+pub struct Cli {
+    /// Dashboard address of the simulation (defaults to SIM_DASHBOARD, then 127.0.0.1:8080)
+    #[arg(long)]
+    pub address: Option<String>,
+
+    /// Print the raw JSON answers
+    #[arg(long)]
+    pub json: bool,
+
+    /// Token allowed to control the run, needed to change anything (defaults to SIM_CONTROL_TOKEN)
+    #[arg(long, global = true)]
+    pub token: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List stored DTCs with their freeze frames
+    Dtcs,
+    /// Clear the fault memory
+    ClearDtcs,
+    /// Run an actuator test, e.g. sensor_wakeup
+    Actuate {
+        name: String,
+    },
+    /// Print which monitors are complete and whether the MIL is on
+    Readiness,
+    /// List the routines the simulation offers
+    Routines,
+    /// Start or stop a routine, or read its results
+    Routine {
+        /// Routine name or identifier, e.g. sensor_ping or 0x0201
+        name: String,
+        #[arg(value_enum, default_value_t = RoutineAction::Results)]
+        action: RoutineAction,
+    },
+    /// List the calibration versions the simulation loaded
+    Calibrations,
+    /// Show the parameters of a calibration version, or make it the active one
+    Calibration {
+        version: String,
+        #[arg(value_enum, default_value_t = CalibrationAction::Show)]
+        action: CalibrationAction,
+    },
+    /// Compare two calibration files; needs no running simulation
+    DiffCalibrations {
+        old: PathBuf,
+        new: PathBuf,
+    },
+    /// Send a raw UDS request to the ECU over the simulated bus, e.g. 19 02 FF
+    Uds {
+        #[arg(required = true)]
+        bytes: Vec<String>,
+    },
+    /// Start, pause or single-step the run, inject a fault or read its state
+    Control {
+        #[command(subcommand)]
+        action: ControlAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ControlAction {
+    /// Let a paused run continue
+    Start,
+    /// Hold the run between steps
+    Pause,
+    /// Let a paused run take one step
+    Step,
+    /// Inject a fault as a simulation command, e.g. leak fl 0.2
+    Fault {
+        #[arg(required = true)]
+        command: Vec<String>,
+    },
+    /// Print whether the run is paused, its step and the latest signals
+    State,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum CalibrationAction {
+    Show,
+    Activate,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum RoutineAction {
+    Start,
+    Stop,
+    Results,
+}
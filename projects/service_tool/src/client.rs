@@ -0,0 +1,52 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+use serde_json::Value;
+use vehicle_sim_core::service::SERVICE_TIMEOUT;
+
+// Sends one request to the `/service/...` interface of a simulation
+// dashboard and returns the HTTP status with the JSON answer; anything but
+// a GET needs a token that may control the run
+pub fn request(address: &str, token: Option<&str>, method: &str, path: &str) -> io::Result<(u16, Value)> {
+    send(address, method, &format!("service/{}", path), &authorization(token), "")
+}
+
+// Sends a `/control/...` request, e.g. `POST fault` with the fault command
+// as the body, with a token that may control the run
+pub fn control(address: &str, token: &str, method: &str, rpc: &str, body: &str) -> io::Result<(u16, Value)> {
+    send(address, method, &format!("control/{}", rpc), &authorization(Some(token)), body)
+}
+
+fn authorization(token: Option<&str>) -> String {
+    token.map_or(String::new(), |token| format!("Authorization: Bearer {}\r\n", token))
+}
+
+// `headers` are extra header lines, each ending in CRLF
+fn send(address: &str, method: &str, path: &str, headers: &str, body: &str) -> io::Result<(u16, Value)> {
+    let mut stream = TcpStream::connect(address)?;
+    // The simulation answers between steps
+    stream.set_read_timeout(Some(SERVICE_TIMEOUT * 2))?;
+    write!(
+        stream,
+        "{} /{} HTTP/1.1\r\nHost: {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        address,
+        headers,
+        body.len(),
+        body
+    )?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let (head, body) = response.split_once("\r\n\r\n").ok_or_else(|| invalid("malformed HTTP response"))?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| invalid("malformed HTTP status line"))?;
+    let body = serde_json::from_str(body).map_err(|e| invalid(&format!("invalid JSON answer: {}", e)))?;
+    Ok((status, body))
+}
@@ -0,0 +1,249 @@
+mod cli;
+mod client;
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+use clap::Parser;
+use cli::{CalibrationAction, Cli, Command, ControlAction, RoutineAction};
+use serde_json::Value;
+use vehicle_sim_core::calibration::{self, Calibration};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+
+fn main() {
+    let cli = Cli::parse();
+    let address = cli
+        .address
+        .clone()
+        .or_else(|| env::var("SIM_DASHBOARD").ok())
+        .unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let token = cli.token.clone().or_else(|| env::var("SIM_CONTROL_TOKEN").ok());
+
+    let (method, path) = match &cli.command {
+        Command::Dtcs => ("GET", "dtcs".to_string()),
+        Command::ClearDtcs => ("POST", "dtcs/clear".to_string()),
+        Command::Actuate { name } => ("POST", format!("actuators/{}", name)),
+        Command::Readiness => ("GET", "readiness".to_string()),
+        Command::Routines => ("GET", "routines".to_string()),
+        Command::Routine { name, action } => match action {
+            RoutineAction::Start => ("POST", format!("routines/{}/start", name)),
+            RoutineAction::Stop => ("POST", format!("routines/{}/stop", name)),
+            RoutineAction::Results => ("GET", format!("routines/{}", name)),
+        },
+        Command::Calibrations => ("GET", "calibrations".to_string()),
+        Command::Calibration { version, action } => match action {
+            CalibrationAction::Show => ("GET", format!("calibrations/{}", version)),
+            CalibrationAction::Activate => ("POST", format!("calibrations/{}/activate", version)),
+        },
+        // Works on local files, no simulation involved
+        Command::DiffCalibrations { old, new } => {
+            diff_calibrations(old, new);
+            return;
+        }
+        Command::Uds { bytes } => ("POST", format!("uds/{}", bytes.concat())),
+        Command::Control { action } => {
+            let token = token.unwrap_or_else(|| {
+                eprintln!("Controlling the run needs a token: pass --token or set SIM_CONTROL_TOKEN");
+                process::exit(2);
+            });
+            control(&address, &token, action, cli.json);
+            return;
+        }
+    };
+
+    let (status, answer) = client::request(&address, token.as_deref(), method, &path).unwrap_or_else(|e| {
+        eprintln!("Cannot reach the simulation at {}: {}", address, e);
+        process::exit(1);
+    });
+    if status != 200 {
+        let message = answer["error"].as_str().unwrap_or("request failed");
+        eprintln!("{} (HTTP {})", message, status);
+        process::exit(1);
+    }
+
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&answer).unwrap_or_default());
+        return;
+    }
+    match &cli.command {
+        Command::Dtcs => print_dtcs(&answer),
+        Command::ClearDtcs => println!("Fault memory cleared."),
+        Command::Actuate { name } => print_actuator_result(name, &answer),
+        Command::Readiness => print_readiness(&answer),
+        Command::Routines => {
+            for routine in answer["routines"].as_array().into_iter().flatten() {
+                print_routine(routine);
+            }
+        }
+        Command::Routine { name, action } => match action {
+            RoutineAction::Start => println!("Routine {} started.", name),
+            RoutineAction::Stop => println!("Routine {} stopped.", name),
+            RoutineAction::Results => print_routine(&answer),
+        },
+        Command::Calibrations => {
+            println!("Calibration {}:", answer["name"].as_str().unwrap_or("?"));
+            let active = answer["active"].as_str();
+            for version in answer["versions"].as_array().into_iter().flatten() {
+                let marker = if version.as_str() == active { "*" } else { " " };
+                println!("{} {}", marker, version.as_str().unwrap_or("?"));
+            }
+        }
+        Command::Calibration { version, action } => match action {
+            CalibrationAction::Show => {
+                for (key, value) in answer["parameters"].as_object().into_iter().flatten() {
+                    println!("{} = {}", key, value.as_str().unwrap_or("?"));
+                }
+            }
+            CalibrationAction::Activate => {
+                println!("Calibration {} active.", version);
+                for change in answer["changes"].as_array().into_iter().flatten() {
+                    println!(
+                        "  {}: {} -> {}",
+                        change["key"].as_str().unwrap_or("?"),
+                        change["old"].as_str().unwrap_or("(unset)"),
+                        change["new"].as_str().unwrap_or("(unset)")
+                    );
+                }
+            }
+        },
+        Command::DiffCalibrations { .. } | Command::Control { .. } => unreachable!("answered separately"),
+        Command::Uds { .. } => match answer["negative"].as_str() {
+            Some(code) => println!("{} ({})", answer["response"].as_str().unwrap_or("?"), code),
+            None => println!("{}", answer["response"].as_str().unwrap_or("?")),
+        },
+    }
+}
+
+fn control(address: &str, token: &str, action: &ControlAction, json: bool) {
+    let (method, rpc, body) = match action {
+        ControlAction::Start => ("POST", "start", String::new()),
+        ControlAction::Pause => ("POST", "pause", String::new()),
+        ControlAction::Step => ("POST", "step", String::new()),
+        ControlAction::Fault { command } => ("POST", "fault", command.join(" ")),
+        ControlAction::State => ("GET", "state", String::new()),
+    };
+    let (status, answer) = client::control(address, token, method, rpc, &body).unwrap_or_else(|e| {
+        eprintln!("Cannot reach the simulation at {}: {}", address, e);
+        process::exit(1);
+    });
+    if status != 200 && status != 202 {
+        let message = answer["error"].as_str().unwrap_or("request failed");
+        eprintln!("{} (HTTP {})", message, status);
+        process::exit(1);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&answer).unwrap_or_default());
+        return;
+    }
+    match action {
+        ControlAction::Fault { .. } => println!("Queued: {} (applies with the next step)", body),
+        _ => {
+            let state = if answer["paused"].as_bool() == Some(true) { "paused" } else { "running" };
+            println!(
+                "Simulation {} at step {} ({} s simulated)",
+                state,
+                answer["steps"].as_u64().unwrap_or(0),
+                answer["sim_time_seconds"].as_f64().unwrap_or(0.0)
+            );
+        }
+    }
+    if let ControlAction::State = action {
+        for (name, value) in answer["signals"].as_object().into_iter().flatten() {
+            println!("  {} = {}", name, value);
+        }
+    }
+}
+
+fn diff_calibrations(old: &Path, new: &Path) {
+    let load = |path: &Path| {
+        Calibration::load(path).unwrap_or_else(|e| {
+            eprintln!("Cannot read calibration {}: {}", path.display(), e);
+            process::exit(1);
+        })
+    };
+    let (old, new) = (load(old), load(new));
+    if old.name != new.name {
+        println!("Note: comparing different calibrations ({} and {})", old.name, new.name);
+    }
+    println!("--- {}\n+++ {}", old, new);
+    let changes = calibration::diff(&old, &new);
+    if changes.is_empty() {
+        println!("No parameter changes.");
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+}
+
+fn print_dtcs(answer: &Value) {
+    let dtcs = answer["dtcs"].as_array().cloned().unwrap_or_default();
+    if dtcs.is_empty() {
+        println!("No stored DTCs.");
+        return;
+    }
+
+    for dtc in &dtcs {
+        let record = &dtc["record"];
+        println!(
+            "{} [{}] {} {}: {} (occurrences: {}, fault-free cycles: {})",
+            dtc["component"].as_str().unwrap_or("?"),
+            record["code"].as_str().unwrap_or("?"),
+            record["status"].as_str().unwrap_or("?"),
+            if record["active"].as_bool().unwrap_or(false) { "active" } else { "stored" },
+            record["description"].as_str().unwrap_or(""),
+            record["occurrences"],
+            record["healthy_cycles"]
+        );
+        for entry in record["freeze_frame"].as_array().into_iter().flatten() {
+            print_sensor_entry(entry);
+        }
+    }
+}
+
+fn print_actuator_result(name: &str, answer: &Value) {
+    println!("Actuator test {} completed.", name);
+    for entry in answer["readings"].as_array().into_iter().flatten() {
+        print_sensor_entry(entry);
+    }
+}
+
+fn print_sensor_entry(entry: &Value) {
+    println!(
+        "    {}: {:.2} PSI at {:.1} °C",
+        entry["position"].as_str().unwrap_or("?"),
+        entry["pressure"].as_f64().unwrap_or(f64::NAN),
+        entry["temperature"].as_f64().unwrap_or(f64::NAN)
+    );
+}
+
+fn print_routine(routine: &Value) {
+    println!(
+        "0x{:04X} {}: {}",
+        routine["id"].as_u64().unwrap_or(0),
+        routine["name"].as_str().unwrap_or("?"),
+        routine["status"].as_str().unwrap_or("?")
+    );
+    for sensor in routine["results"]["sensors"].as_array().into_iter().flatten() {
+        println!(
+            "    {}: {} ({:.2} PSI at {:.1} °C)",
+            sensor["position"].as_str().unwrap_or("?"),
+            if sensor["responded"].as_bool().unwrap_or(false) { "responded" } else { "no response" },
+            sensor["pressure"].as_f64().unwrap_or(f64::NAN),
+            sensor["temperature"].as_f64().unwrap_or(f64::NAN)
+        );
+    }
+}
+
+fn print_readiness(answer: &Value) {
+    println!("Readiness monitors:");
+    for (monitor, complete) in answer["monitors"].as_object().into_iter().flatten() {
+        let state = if complete.as_bool().unwrap_or(false) { "complete" } else { "incomplete" };
+        println!("  {}: {}", monitor, state);
+    }
+    println!("MIL: {}", if answer["mil"].as_bool().unwrap_or(false) { "on" } else { "off" });
+    println!("Confirmed DTCs: {}", answer["confirmed_dtcs"]);
+    println!("Ignition cycles: {}", answer["ignition_cycles"]);
+}
@@ -0,0 +1,216 @@
+use vehicle_sim_core::command::Command;
+use vehicle_sim_core::units::{Pressure, Temperature};
+
+use crate::tpms::{Fault, TPMS};
+
+pub const COMMAND_HELP: &str = "Commands: leak <tire> <psi>, inflate <tire> <psi>, heat <tire> <°C>, \
+fault <tire> slow-leak <psi/s>|blowout|stuck, undo, redo, history, save <path>";
+
+pub enum TireAction {
+    Leak(Pressure),
+    Inflate(Pressure),
+    Heat { temperature: Temperature, previous: Temperature },
+}
+
+// Fault injection on a single tire, e.g. `leak fl 0.2`
+pub struct TireCommand {
+    tire: usize,
+    tire_name: String,
+    action: TireAction,
+}
+
+// Injects a `Fault`; undo restores the previous fault and pressure
+pub struct FaultCommand {
+    tire: usize,
+    tire_name: String,
+    fault: Fault,
+    previous: Option<(Option<Fault>, Pressure)>,
+}
+
+pub fn parse_command(tpms: &TPMS, line: &str) -> Result<Box<dyn Command<TPMS>>, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (verb, tire_name, arguments) = match words[..] {
+        [verb, tire_name, ref arguments @ ..] if !arguments.is_empty() => (verb, tire_name, arguments),
+        _ => return Err(format!("Unknown command '{}'. {}", line.trim(), COMMAND_HELP)),
+    };
+
+    let tire = tpms
+        .find_tire(tire_name)
+        .ok_or_else(|| format!("Unknown tire '{}'", tire_name))?;
+    let tire_name = tire_name.to_lowercase();
+
+    if verb == "fault" {
+        return Ok(Box::new(FaultCommand {
+            tire,
+            tire_name,
+            fault: parse_fault(arguments)?,
+            previous: None,
+        }));
+    }
+
+    let [value] = arguments[..] else {
+        return Err(format!("Unknown command '{}'. {}", line.trim(), COMMAND_HELP));
+    };
+    let value: f32 = value.parse().map_err(|_| format!("Invalid number '{}'", value))?;
+    let action = match verb {
+        "leak" => TireAction::Leak(Pressure::from_psi(value)),
+        "inflate" => TireAction::Inflate(Pressure::from_psi(value)),
+        "heat" => TireAction::Heat {
+            temperature: Temperature::from_celsius(value),
+            previous: Temperature::from_celsius(value),
+        },
+        _ => return Err(format!("Unknown command '{}'. {}", verb, COMMAND_HELP)),
+    };
+
+    Ok(Box::new(TireCommand { tire, tire_name, action }))
+}
+
+fn parse_fault(arguments: &[&str]) -> Result<Fault, String> {
+    match arguments[..] {
+        ["slow-leak", rate] => rate
+            .parse()
+            .map(|rate| Fault::SlowLeak { rate })
+            .map_err(|_| format!("Invalid leak rate '{}'", rate)),
+        ["blowout"] => Ok(Fault::Blowout),
+        ["stuck"] => Ok(Fault::SensorStuck),
+        _ => Err(format!(
+            "Unknown fault '{}', expected slow-leak <psi/s>, blowout or stuck",
+            arguments.join(" ")
+        )),
+    }
+}
+
+impl Command<TPMS> for TireCommand {
+    fn apply(&mut self, tpms: &mut TPMS) {
+        let tire = tpms.tire_mut(self.tire);
+        match &mut self.action {
+            TireAction::Leak(amount) => tire.adjust_pressure(-*amount),
+            TireAction::Inflate(amount) => tire.adjust_pressure(*amount),
+            TireAction::Heat { temperature, previous } => {
+                *previous = tire.temperature();
+                tire.set_temperature(*temperature);
+            }
+        }
+    }
+
+    fn undo(&mut self, tpms: &mut TPMS) {
+        let tire = tpms.tire_mut(self.tire);
+        match self.action {
+            TireAction::Leak(amount) => tire.adjust_pressure(amount),
+            TireAction::Inflate(amount) => tire.adjust_pressure(-amount),
+            TireAction::Heat { previous, .. } => tire.set_temperature(previous),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.action {
+            TireAction::Leak(amount) => format!("leak {} {}", self.tire_name, amount.psi()),
+            TireAction::Inflate(amount) => format!("inflate {} {}", self.tire_name, amount.psi()),
+            TireAction::Heat { temperature, .. } => format!("heat {} {}", self.tire_name, temperature.celsius()),
+        }
+    }
+}
+
+impl Command<TPMS> for FaultCommand {
+    fn apply(&mut self, tpms: &mut TPMS) {
+        let tire = tpms.tire_mut(self.tire);
+        self.previous = Some((tire.fault(), tire.pressure()));
+        tpms.inject_fault(self.tire, self.fault);
+    }
+
+    fn undo(&mut self, tpms: &mut TPMS) {
+        let Some((fault, pressure)) = self.previous else {
+            return;
+        };
+
+        match fault {
+            Some(fault) => tpms.inject_fault(self.tire, fault),
+            None => tpms.clear_fault(self.tire),
+        }
+        let tire = tpms.tire_mut(self.tire);
+        tire.adjust_pressure(pressure - tire.pressure());
+    }
+
+    fn describe(&self) -> String {
+        match self.fault {
+            Fault::SlowLeak { rate } => format!("fault {} slow-leak {}", self.tire_name, rate),
+            Fault::Blowout => format!("fault {} blowout", self.tire_name),
+            Fault::SensorStuck => format!("fault {} stuck", self.tire_name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtc::{DtcStore, DTC_CONFIG};
+    use crate::tire_config::{TireConfig, VehicleLayout};
+    use vehicle_sim_core::command::CommandBus;
+
+    fn car() -> TPMS {
+        let tires = VehicleLayout::Car
+            .tires()
+            .into_iter()
+            .map(|config| TireConfig::new(config.position, config.nominal_pressure, config.nominal_pressure))
+            .collect();
+        TPMS::new(0.94, 1.15, tires, DtcStore::new(DTC_CONFIG))
+    }
+
+    fn run(bus: &mut CommandBus<TPMS>, tpms: &mut TPMS, line: &str) {
+        let command = parse_command(tpms, line).unwrap();
+        bus.execute(0, command, tpms);
+    }
+
+    #[test]
+    fn leaks_are_undone_and_redone() {
+        let mut tpms = car();
+        let mut bus = CommandBus::new();
+        let fl = tpms.find_tire("fl").unwrap();
+        let nominal = tpms.tire_mut(fl).pressure();
+
+        run(&mut bus, &mut tpms, "leak fl 4");
+        assert!((tpms.tire_mut(fl).pressure().psi() - (nominal.psi() - 4.0)).abs() < 1e-4);
+        assert_eq!(bus.undo(&mut tpms).as_deref(), Some("leak fl 4"));
+        assert!((tpms.tire_mut(fl).pressure().psi() - nominal.psi()).abs() < 1e-4);
+        assert_eq!(bus.redo(&mut tpms).as_deref(), Some("leak fl 4"));
+        assert!((tpms.tire_mut(fl).pressure().psi() - (nominal.psi() - 4.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn a_new_command_drops_the_redo_history() {
+        let mut tpms = car();
+        let mut bus = CommandBus::new();
+        run(&mut bus, &mut tpms, "heat fr 60");
+        bus.undo(&mut tpms);
+        run(&mut bus, &mut tpms, "inflate fr 2");
+        assert_eq!(bus.redo(&mut tpms), None);
+        assert_eq!(bus.history(), vec![(0, "inflate fr 2".to_string())]);
+        // Nothing left to undo once the history is used up
+        bus.undo(&mut tpms);
+        assert_eq!(bus.undo(&mut tpms), None);
+    }
+
+    #[test]
+    fn undoing_a_fault_restores_the_tire() {
+        let mut tpms = car();
+        let mut bus = CommandBus::new();
+        let rl = tpms.find_tire("rl").unwrap();
+        let pressure = tpms.tire_mut(rl).pressure();
+
+        run(&mut bus, &mut tpms, "fault rl blowout");
+        assert_eq!(tpms.tire_mut(rl).fault(), Some(Fault::Blowout));
+        bus.undo(&mut tpms);
+        assert_eq!(tpms.tire_mut(rl).fault(), None);
+        assert!((tpms.tire_mut(rl).pressure().psi() - pressure.psi()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn malformed_commands_are_rejected() {
+        let tpms = car();
+        let error = |line: &str| parse_command(&tpms, line).err().unwrap();
+        assert!(error("leak xx 4").starts_with("Unknown tire"));
+        assert!(error("leak fl lots").starts_with("Invalid number"));
+        assert!(error("fault fl melt").starts_with("Unknown fault"));
+        assert!(error("undo").starts_with("Unknown command"));
+    }
+}
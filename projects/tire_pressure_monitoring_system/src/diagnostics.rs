@@ -0,0 +1,271 @@
+use vehicle_sim_core::calibration::Calibration;
+use vehicle_sim_core::description::{Address, Characteristic, DataType, Description, Measurement};
+use vehicle_sim_core::service::ServiceResponse;
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::uds::{self, DiagnosticHandler, DtcEntry, UdsClient, ALL_GROUPS};
+
+use serde_json::json;
+
+use crate::simulation::TpmsSimulation;
+use crate::tpms::TPMS;
+
+// Physical addressing of the TPMS ECU
+pub const REQUEST_ID: u32 = 0x7A0;
+pub const RESPONSE_ID: u32 = 0x7A8;
+
+// Data identifiers, one per tire in layout order: pressure in 0.01 psi,
+// temperature in 0.1 °C
+pub const PRESSURE_DID: u16 = 0x4000;
+pub const TEMPERATURE_DID: u16 = 0x4100;
+pub const TIRE_COUNT_DID: u16 = 0x4200;
+
+// What `--describe` lists: the bus signals, the data identifiers of `tires`
+// tires and the thresholds in use
+pub fn describe(
+    tires: usize,
+    low_pressure_ratio: f32,
+    high_pressure_ratio: f32,
+    calibration: Option<&Calibration>,
+) -> Description {
+    let did = |did| Address::Did {
+        request_id: REQUEST_ID,
+        did,
+    };
+    let mut description = Description::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .with_calibration(calibration)
+        .with_can_messages("TPMS")
+        .with_characteristic(Characteristic::number("low_pressure_ratio", low_pressure_ratio, "", 0.0, 1.0))
+        .with_characteristic(Characteristic::number("high_pressure_ratio", high_pressure_ratio, "", 1.0, 2.0));
+    for i in 0..tires as u16 {
+        description = description
+            .with_measurement(Measurement::new(&format!("TirePressure_{}", i), did(PRESSURE_DID + i), DataType::Uword, 0.01, "psi"))
+            .with_measurement(Measurement::new(&format!("TireTemperature_{}", i), did(TEMPERATURE_DID + i), DataType::Sword, 0.1, "°C"));
+    }
+    description.with_measurement(Measurement::new("TireCount", did(TIRE_COUNT_DID), DataType::Ubyte, 1.0, ""))
+}
+
+impl DiagnosticHandler for TPMS {
+    fn dtcs(&self) -> Vec<DtcEntry> {
+        self.dtc_store().entries()
+    }
+
+    // The store has no DTC groups, so only "all groups" clears anything
+    fn clear_dtcs(&mut self, group: u32) {
+        if group == ALL_GROUPS {
+            self.clear_dtcs();
+        }
+    }
+
+    fn read_data(&self, identifier: u16) -> Option<Vec<u8>> {
+        let readings = self.state().readings;
+        let index = (identifier & 0xFF) as usize;
+        match identifier & 0xFF00 {
+            PRESSURE_DID => readings
+                .get(index)
+                .map(|reading| ((reading.pressure * 100.0).round() as u16).to_be_bytes().to_vec()),
+            TEMPERATURE_DID => readings
+                .get(index)
+                .map(|reading| ((reading.temperature * 10.0).round() as i16).to_be_bytes().to_vec()),
+            TIRE_COUNT_DID if index == 0 => Some(vec![readings.len() as u8]),
+            _ => None,
+        }
+    }
+}
+
+impl TpmsSimulation {
+    // The ECU answers testers on the bus once per step
+    pub fn answer_diagnostic_requests(&mut self) {
+        if let Some(server) = self.uds.as_mut() {
+            server.poll(&mut self.tpms);
+        }
+    }
+
+    // `POST uds/<hex request>` relays a raw request to the ECU's server over
+    // the bus, so the service tool can speak UDS
+    pub fn uds_response(&mut self, request: &str) -> ServiceResponse {
+        let Some(server) = self.uds.as_mut() else {
+            return ServiceResponse::error(503, "no diagnostic server");
+        };
+        let bytes: Option<Vec<u8>> = (0..request.len())
+            .step_by(2)
+            .map(|i| request.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+            .collect();
+        let Some(bytes) = bytes.filter(|bytes| !bytes.is_empty()) else {
+            return ServiceResponse::error(400, "expected the request as hex bytes, e.g. uds/1902FF");
+        };
+
+        let mut client = UdsClient::new(self.can.clone(), REQUEST_ID, RESPONSE_ID);
+        match client.exchange(server, &mut self.tpms, &bytes) {
+            Ok(response) => ServiceResponse::ok(json!({ "response": uds::hex(&response) })),
+            Err(uds::UdsError::Negative(code)) => ServiceResponse::ok(json!({
+                "response": uds::hex(&[0x7F, bytes[0], code]),
+                "negative": uds::response_code_name(code),
+            })),
+            Err(e) => ServiceResponse::error(504, &e.to_string()),
+        }
+    }
+
+    // Thresholds written by a calibration tool over XCP; the server already
+    // checked them against the ranges of the description
+    pub fn apply_xcp_writes(&mut self) {
+        let Some(xcp) = &self.xcp else {
+            return;
+        };
+        for (name, value) in xcp.take_writes() {
+            match name.as_str() {
+                "low_pressure_ratio" if value > 0.0 => self.tpms.set_low_pressure_ratio(value as f32),
+                "high_pressure_ratio" => self.tpms.set_high_pressure_ratio(value as f32),
+                _ => {
+                    sim_log::warn("xcp", &format!("Ignoring {} = {}", name, value));
+                    continue;
+                }
+            }
+            sim_log::info("xcp", &format!("Calibrated over XCP: {} = {}", name, value));
+        }
+    }
+
+    // The values behind the data identifiers of `describe`
+    pub fn publish_xcp(&self) {
+        let Some(xcp) = &self.xcp else {
+            return;
+        };
+        let readings = self.tpms.state().readings;
+        let mut names = Vec::new();
+        let mut values = Vec::new();
+        for (i, reading) in readings.iter().enumerate() {
+            names.push(format!("TirePressure_{}", i));
+            values.push(reading.pressure as f64);
+            names.push(format!("TireTemperature_{}", i));
+            values.push(reading.temperature as f64);
+        }
+        let mut signals: Vec<(&str, f64)> = names.iter().map(String::as_str).zip(values).collect();
+        signals.push(("TireCount", readings.len() as f64));
+        xcp.publish(&signals);
+    }
+
+    // Per tire under `<position>/`, e.g. front_left/pressure
+    #[cfg(feature = "mqtt")]
+    pub fn publish_mqtt(&mut self, time: f64) {
+        let Some(mqtt) = &self.mqtt else {
+            return;
+        };
+        let mut names = Vec::new();
+        let mut values = Vec::new();
+        for reading in self.tpms.state().readings {
+            let level = vehicle_sim_core::mqtt::topic_level(&reading.position);
+            names.push(format!("{}/pressure", level));
+            values.push(reading.pressure as f64);
+            names.push(format!("{}/compensated_pressure", level));
+            values.push(reading.compensated_pressure as f64);
+            names.push(format!("{}/temperature", level));
+            values.push(reading.temperature as f64);
+            names.push(format!("{}/safe", level));
+            values.push(if reading.is_safe { 1.0 } else { 0.0 });
+        }
+        let signals: Vec<(&str, f64)> = names.iter().map(String::as_str).zip(values).collect();
+        mqtt.publish(&vehicle_sim_core::privacy::filtered(self.privacy.as_mut(), time, signals));
+    }
+
+    // The averages still open when the run ends
+    #[cfg(feature = "mqtt")]
+    pub fn flush_mqtt(&mut self) {
+        let (Some(mqtt), Some(privacy)) = (&self.mqtt, &mut self.privacy) else {
+            return;
+        };
+        let pending = privacy.flush();
+        if !pending.is_empty() {
+            mqtt.publish(&pending);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::dtc::{DtcStore, DTC_CONFIG};
+    use crate::tire_config::{TireConfig, VehicleLayout};
+    use crate::tpms::Fault;
+    use vehicle_sim_core::can_bus::CanBus;
+    use vehicle_sim_core::rng::SimRng;
+    use vehicle_sim_core::uds::{Session, UdsError, UdsServer, VARIANT_CODING_DID, VIN_DID};
+    use vehicle_sim_core::vehicle::{Trim, VariantCoding, VehicleIdentity, Vin};
+
+    fn car_with_blowouts() -> TPMS {
+        let tires = VehicleLayout::Car
+            .tires()
+            .into_iter()
+            .map(|config| TireConfig::new(config.position, config.nominal_pressure, config.nominal_pressure))
+            .collect();
+        let mut tpms = TPMS::new(0.94, 1.15, tires, DtcStore::new(DTC_CONFIG));
+        tpms.inject_fault(2, Fault::Blowout);
+        tpms.inject_fault(3, Fault::Blowout);
+        tpms.check_all_tires(0.0);
+        tpms
+    }
+
+    #[test]
+    fn reads_dtcs_and_pressures_over_the_bus() {
+        let bus = Arc::new(CanBus::new());
+        let mut server = UdsServer::new(bus.clone(), REQUEST_ID, RESPONSE_ID);
+        let mut client = UdsClient::new(bus, REQUEST_ID, RESPONSE_ID);
+        let mut tpms = car_with_blowouts();
+
+        // Two records don't fit a single frame
+        let dtcs = client.read_dtcs(&mut server, &mut tpms, 0xFF).unwrap();
+        let codes: Vec<String> = dtcs.iter().map(|entry| uds::code_from_dtc(entry.dtc)).collect();
+        assert_eq!(codes, vec!["C0752", "C0753"]);
+        assert!(dtcs.iter().all(|entry| entry.status & uds::TEST_FAILED != 0));
+
+        let pressure = client.read_data(&mut server, &mut tpms, PRESSURE_DID + 2).unwrap();
+        assert_eq!(pressure, vec![0, 0]);
+        assert_eq!(
+            client.read_data(&mut server, &mut tpms, PRESSURE_DID + 9),
+            Err(UdsError::Negative(uds::REQUEST_OUT_OF_RANGE))
+        );
+    }
+
+    #[test]
+    fn clearing_dtcs_needs_the_extended_session() {
+        let bus = Arc::new(CanBus::new());
+        let mut server = UdsServer::new(bus.clone(), REQUEST_ID, RESPONSE_ID);
+        let mut client = UdsClient::new(bus, REQUEST_ID, RESPONSE_ID);
+        let mut tpms = car_with_blowouts();
+
+        assert_eq!(
+            client.clear_dtcs(&mut server, &mut tpms, ALL_GROUPS),
+            Err(UdsError::Negative(uds::SERVICE_NOT_SUPPORTED_IN_ACTIVE_SESSION))
+        );
+        client.session_control(&mut server, &mut tpms, Session::Extended).unwrap();
+        client.clear_dtcs(&mut server, &mut tpms, ALL_GROUPS).unwrap();
+        assert!(client.read_dtcs(&mut server, &mut tpms, 0xFF).unwrap().is_empty());
+    }
+
+    #[test]
+    fn identity_writes_are_checked_for_plausibility() {
+        let bus = Arc::new(CanBus::new());
+        let identity = VehicleIdentity::generate(Trim::Base, &mut SimRng::from_seed(1));
+        assert!(Vin::parse(identity.vin.as_str()).is_ok());
+        let mut server = UdsServer::new(bus.clone(), REQUEST_ID, RESPONSE_ID).with_identity(identity.clone(), None);
+        let mut client = UdsClient::new(bus, REQUEST_ID, RESPONSE_ID);
+        let mut tpms = car_with_blowouts();
+
+        let vin = client.read_data(&mut server, &mut tpms, VIN_DID).unwrap();
+        assert_eq!(vin, identity.vin.as_str().as_bytes());
+        client.session_control(&mut server, &mut tpms, Session::Extended).unwrap();
+
+        // Wrong check digit, then a valid VIN
+        let rejected = client.write_data(&mut server, &mut tpms, VIN_DID, b"1M8GDM9A1KP042788");
+        assert_eq!(rejected, Err(UdsError::Negative(uds::REQUEST_OUT_OF_RANGE)));
+        client.write_data(&mut server, &mut tpms, VIN_DID, b"1M8GDM9AXKP042788").unwrap();
+
+        // The base trim has no rear climate zone
+        let rear_zone = VariantCoding::REAR_CLIMATE_ZONE.to_be_bytes();
+        let rejected = client.write_data(&mut server, &mut tpms, VARIANT_CODING_DID, &[0, rear_zone[0], rear_zone[1]]);
+        assert_eq!(rejected, Err(UdsError::Negative(uds::REQUEST_OUT_OF_RANGE)));
+        client.write_data(&mut server, &mut tpms, VARIANT_CODING_DID, &[1, rear_zone[0], rear_zone[1]]).unwrap();
+        let coding = client.read_data(&mut server, &mut tpms, VARIANT_CODING_DID).unwrap();
+        assert_eq!(coding, vec![1, rear_zone[0], rear_zone[1]]);
+    }
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::dtc::{Debounce, DtcConfig, DtcManager};
+use vehicle_sim_core::units::{Celsius, Psi};
+
+pub use vehicle_sim_core::dtc::{DtcStatus, FaultReport};
+
+pub const DTC_STORE_PATH: &str = "tpms_dtcs.json";
+
+// Ignition cycles a fault has to be seen in before its code is confirmed
+pub const CONFIRMATION_CYCLES: u32 = 2;
+
+// Fault-free ignition cycles after which a confirmed code is erased
+pub const AGING_CYCLES: u32 = 40;
+
+// A pressure check reads the settled sensor value, so a single failed check
+// sets the fault; the tire warning lamp stays on for one fault-free cycle
+pub const DTC_CONFIG: DtcConfig = DtcConfig {
+    debounce: Debounce::IMMEDIATE,
+    confirmation_cycles: CONFIRMATION_CYCLES,
+    healing_cycles: 1,
+    aging_cycles: AGING_CYCLES,
+    warning_indicator: true,
+};
+
+// Fault codes are numbered per tire position: C0750, C0751, ...
+pub fn tire_fault_code(tire_index: usize) -> String {
+    format!("C{:04}", 750 + tire_index)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FreezeFrameEntry {
+    pub position: String,
+    pub pressure: Psi,
+    pub temperature: Celsius,
+}
+
+// The freeze frame holds all tire pressures at the moment the fault was
+// first detected
+pub type DtcStore = DtcManager<Vec<FreezeFrameEntry>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vehicle_sim_core::uds;
+
+    #[test]
+    fn tire_fault_codes_are_valid_iso_codes() {
+        let codes: Vec<String> = (0..4).map(tire_fault_code).collect();
+        assert_eq!(codes, vec!["C0750", "C0751", "C0752", "C0753"]);
+        for code in &codes {
+            let dtc = uds::dtc_from_code(code).unwrap();
+            assert_eq!(&uds::code_from_dtc(dtc), code);
+        }
+    }
+
+    #[test]
+    fn a_single_failed_check_sets_the_fault_and_lights_the_lamp() {
+        let fault = FaultReport {
+            component: "Front-Left".to_string(),
+            code: tire_fault_code(0),
+            description: "Front-Left pressure too low".to_string(),
+        };
+        let mut store = DtcStore::new(DTC_CONFIG);
+        store.start_ignition_cycle();
+        store.report(1.0, &fault, true, Vec::new);
+        assert_eq!(store.active_codes(), vec!["C0750".to_string()]);
+        assert!(store.warning_indicator_requested());
+    }
+}
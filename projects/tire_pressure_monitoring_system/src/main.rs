@@ -1,30 +1,523 @@
+mod commands;
+mod diagnostics;
+mod dtc;
+mod routines;
+mod service;
+mod simulation;
+mod telemetry;
+mod tire_config;
 mod tpms;
 
-use std::thread;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
 use std::time::Duration;
 
+use commands::COMMAND_HELP;
+use dtc::{DtcStatus, DtcStore, DTC_CONFIG, DTC_STORE_PATH};
+use simulation::{
+    TpmsSimulation, BUDGETED_COMPONENTS, DEFAULT_HIGH_PRESSURE_RATIO, DEFAULT_LOW_PRESSURE_RATIO, SAFE_CONFIG_KEYS,
+};
+use telemetry::{OutputFormat, TelemetryWriter};
+use tire_config::VehicleLayout;
+use vehicle_sim_core::calibration::{self, CalibrationSet};
+use vehicle_sim_core::can_bus::CanBus;
+use vehicle_sim_core::can_trace::{self, CanTrace};
+use vehicle_sim_core::clock;
+use vehicle_sim_core::command::{self, CommandBus};
+use vehicle_sim_core::config::{ConfigWatcher, LogLevel};
+use vehicle_sim_core::description;
+use vehicle_sim_core::events::{self, EventBus, EventFilter};
+use vehicle_sim_core::metrics::Metrics;
+use vehicle_sim_core::privacy::{self, PrivacyFilter, PrivacyPolicy};
+use vehicle_sim_core::remote::TokenStore;
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::run_control::RunControl;
+use vehicle_sim_core::scenario::{self, Scenario};
+use vehicle_sim_core::sim_log::{self, LogOptions};
+use vehicle_sim_core::simulation::{FixedStepRunner, PauseControl, RunSummary};
+use vehicle_sim_core::snapshot::Snapshot;
+use vehicle_sim_core::uds::UdsServer;
+use vehicle_sim_core::vehicle::{self, VariantCoding, VehicleIdentity};
+use vehicle_sim_core::vehicle_mode::{ModeManager, VehicleMode};
+use vehicle_sim_core::xcp::{self, XcpServer};
+
+fn usage() -> ! {
+    eprintln!("Usage: tire_pressure_monitoring_system [--layout car|motorcycle|truck] [--output text|json] [--output-file <path>] [--config <path>] [--interactive] [--scenario <path>|<file>.toml] [--save <path>] [--resume <path>] [--dashboard|--serve <address>] [--control-tokens <path>] [--grpc <address>] [--paused] [--seed <n>] [--log-level <level>] [--log-file <path>] [--log-json <path>] [--can-trace <path>] [--vehicle <path>] [--calibration <path>] [--trim base|comfort|premium] [--describe <path>] [--realtime-factor <factor>] [--xcp <address>] [--mqtt <host[:port]>] [--privacy <path>] [--list-dtcs | --clear-dtcs]");
+    process::exit(2);
+}
+
 fn main() {
-    let safe_pressure = 30.0;
-    let tire_pressures = vec![32.0, 28.5, 31.0, 29.0];
+    let mut format = OutputFormat::Text;
+    let mut output_file = None;
+    let mut layout = None;
+    let mut config_path = None;
+    let mut dashboard_address = None;
+    let mut interactive = false;
+    let mut scenario_path = None;
+    let mut save_path = None;
+    let mut resume_path = None;
+    let mut list_dtcs = false;
+    let mut clear_dtcs = false;
+    let mut realtime_factor = None;
+    let mut paused = false;
+    let mut control_tokens = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => format = args.next().and_then(|v| OutputFormat::parse(&v)).unwrap_or_else(|| usage()),
+            "--layout" => layout = Some(args.next().and_then(|v| VehicleLayout::parse(&v)).unwrap_or_else(|| usage())),
+            "--output-file" => output_file = Some(args.next().unwrap_or_else(|| usage())),
+            "--interactive" => interactive = true,
+            "--dashboard" | "--serve" => dashboard_address = Some(args.next().unwrap_or_else(|| usage())),
+            "--scenario" => scenario_path = Some(args.next().unwrap_or_else(|| usage())),
+            "--save" => save_path = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--resume" => resume_path = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--list-dtcs" => list_dtcs = true,
+            "--clear-dtcs" => clear_dtcs = true,
+            "--paused" => paused = true,
+            "--control-tokens" => control_tokens = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--config" => config_path = Some(args.next().unwrap_or_else(|| usage())),
+            "--realtime-factor" => {
+                realtime_factor = Some(args.next().and_then(|v| clock::parse_realtime_factor(&v).ok()).unwrap_or_else(|| usage()))
+            }
+            "--seed" | "--log-level" | "--log-file" | "--log-json" | "--can-trace" | "--vehicle" | "--trim" | "--calibration" | "--describe" | "--xcp" | "--mqtt" | "--privacy" | "--grpc" => {
+                args.next();
+            }
+            _ if arg.starts_with("--seed=") => {}
+            _ => usage(),
+        }
+    }
+
+    // JSON telemetry on stdout keeps log lines off it
+    let log_options = LogOptions {
+        console_stdout: format == OutputFormat::Text || output_file.is_some(),
+        ..LogOptions::from_args_and_env()
+    };
+    if let Err(e) = sim_log::init(&log_options) {
+        eprintln!("Cannot open log file: {}", e);
+        process::exit(1);
+    }
+
+    // Fault codes are kept across runs until cleared or aged out
+    let dtc_path = Path::new(DTC_STORE_PATH);
+    let mut dtc_store = DtcStore::load(dtc_path, DTC_CONFIG).unwrap_or_else(|e| {
+        eprintln!("Cannot read DTC store {}: {}", dtc_path.display(), e);
+        process::exit(1);
+    });
+
+    if list_dtcs {
+        print_dtcs(&dtc_store);
+        return;
+    }
+    if clear_dtcs {
+        dtc_store.clear();
+        save_dtcs(&dtc_store, dtc_path);
+        println!("Stored DTCs cleared.");
+        return;
+    }
+    // Each run is one ignition cycle; a resumed run continues the cycle of
+    // its checkpoint
+    dtc_store.start_ignition_cycle();
+
+    // `--scenario <file>.toml` or SIM_SCENARIO sets the layout, run length,
+    // seed and fault schedule; other `--scenario` files replay saved commands
+    let scenario_file = scenario::path_from_args().filter(|path| scenario::is_scenario_file(path)).map(|path| {
+        Scenario::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read scenario {}: {}", path.display(), e);
+            process::exit(1);
+        })
+    });
+    if let Some(scenario) = &scenario_file {
+        scenario.announce(&["layout", "vehicle_mode"], true, false, true, false);
+    }
+
+    // Thresholds and verbosity in the config file are reloaded while running;
+    // the layout is read once at startup
+    let config = config_path.map(|path| {
+        ConfigWatcher::watch(path.as_ref(), SAFE_CONFIG_KEYS).unwrap_or_else(|e| {
+            eprintln!("Cannot read config {}: {}", path, e);
+            process::exit(1);
+        })
+    });
+    let settings = config.as_ref().map(|watcher| watcher.config());
+
+    // `--calibration <file or directory>` or SIM_CALIBRATION supplies the
+    // thresholds; the newest version is active and the config overrides it
+    let calibrations = calibration::path_from_args().map(|path| {
+        CalibrationSet::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read calibration {}: {}", path.display(), e);
+            process::exit(1);
+        })
+    });
+    if let Some(calibrations) = &calibrations {
+        sim_log::info("calibration", &format!("Calibration {} active", calibrations.active()));
+    }
+    let parameter = |key: &str| {
+        settings
+            .and_then(|c| c.get_f64(key))
+            .or_else(|| calibrations.as_ref().and_then(|set| set.active().get_f64(key)))
+    };
+
+    let layout = layout
+        .or_else(|| scenario_file.as_ref().and_then(|s| s.initial_str("layout")).and_then(VehicleLayout::parse))
+        .or_else(|| settings.and_then(|c| c.get("layout")).and_then(VehicleLayout::parse))
+        .unwrap_or(VehicleLayout::Car);
+    let low_pressure_ratio = parameter("low_pressure_ratio")
+        .map_or(DEFAULT_LOW_PRESSURE_RATIO, |ratio| ratio as f32);
+    let high_pressure_ratio = parameter("high_pressure_ratio")
+        .map_or(DEFAULT_HIGH_PRESSURE_RATIO, |ratio| ratio as f32);
+    let log_level = settings
+        .and_then(|c| c.get("log_level"))
+        .and_then(LogLevel::parse)
+        .unwrap_or(LogLevel::Info);
+
+    let metrics = Arc::new(Metrics::new());
+    for component in BUDGETED_COMPONENTS {
+        if let Some(ms) = settings.and_then(|c| c.get_f64(&format!("budget.{}", component))) {
+            metrics.set_budget(component, Duration::from_secs_f64(ms.max(0.0) / 1000.0));
+        }
+    }
+
+    // Replays commands saved with `save <path>` at the steps they ran at
+    let replay_path = scenario_path.filter(|path| !scenario::is_scenario_file(Path::new(path)));
+    let mut scenario = replay_path.map_or_else(Vec::new, |path| {
+        command::load_scenario(Path::new(&path)).unwrap_or_else(|e| {
+            eprintln!("Cannot read scenario {}: {}", path, e);
+            process::exit(1);
+        })
+    });
+    if let Some(scenario_file) = &scenario_file {
+        scenario.extend(scenario_file.command_schedule(1.0));
+    }
+    let console = interactive.then(|| {
+        eprintln!("{}", COMMAND_HELP);
+        command::spawn_stdin_reader()
+    });
+
+    // `--describe <path>` writes what the ECU exposes and exits
+    if let Some(path) = description::path_from_args() {
+        let calibration = calibrations.as_ref().map(CalibrationSet::active);
+        let description = diagnostics::describe(layout.tires().len(), low_pressure_ratio, high_pressure_ratio, calibration);
+        if let Err(e) = description.save(&path) {
+            eprintln!("Cannot write description {}: {}", path.display(), e);
+            process::exit(1);
+        }
+        println!("Description written to {}", path.display());
+        return;
+    }
+
+    let tpms = tpms::TPMS::new(low_pressure_ratio, high_pressure_ratio, layout.tires(), dtc_store);
+
+    // Seed with --seed <n> or SIM_SEED to reproduce a run
+    let mut simulation = TpmsSimulation {
+        tpms,
+        rng: SimRng::from_args_env_or(scenario_file.as_ref().and_then(|s| s.seed)),
+        log_level,
+        config,
+        commands: CommandBus::new(),
+        console,
+        scenario,
+        steps: 0,
+        metrics,
+        events: Arc::new(EventBus::new()),
+        service: None,
+        faults: None,
+        can: Arc::new(CanBus::new()),
+        routines: routines::routines(),
+        uds: None,
+        calibrations,
+        xcp: None,
+        #[cfg(feature = "mqtt")]
+        mqtt: None,
+        privacy: None,
+        // Starts driving unless the scenario sets `vehicle_mode`
+        mode: ModeManager::from_scenario(scenario_file.as_ref(), VehicleMode::Driving),
+    };
+
+    // `--resume` continues a checkpointed run: tires, DTCs, thresholds and the
+    // random stream come from the checkpoint, inputs and log level from this run
+    let mut start = RunSummary::default();
+    if let Some(path) = &resume_path {
+        let snapshot = Snapshot::<TpmsSimulation>::load(path).unwrap_or_else(|e| {
+            eprintln!("Cannot resume from {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        println!("Resuming from {} at step {}", path.display(), snapshot.steps);
+        start = snapshot.summary();
+        simulation.tpms = snapshot.state.tpms;
+        simulation.tpms.set_dtc_config(DTC_CONFIG);
+        simulation.rng = snapshot.state.rng;
+        simulation.steps = snapshot.state.steps;
+    }
+
+    // `--vehicle <path>` or SIM_VEHICLE names the vehicle file shared with the
+    // other ECUs; its coding switches temperature compensation
+    let vehicle_path = vehicle::path_from_args();
+    let trim = vehicle::trim_from_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        usage()
+    });
+    let identity = VehicleIdentity::load_or_create(vehicle_path.as_deref(), trim, simulation.rng.seed())
+        .unwrap_or_else(|e| {
+            eprintln!("Cannot read the vehicle file: {}", e);
+            process::exit(1);
+        });
+    sim_log::info(
+        "vehicle",
+        &format!("VIN {}, {:?} trim, coded with {}", identity.vin, identity.trim, identity.coding.describe()),
+    );
+    simulation
+        .tpms
+        .set_temperature_compensation(identity.coding.has(VariantCoding::TPMS_TEMPERATURE_COMPENSATION));
+    let server = UdsServer::new(simulation.can.clone(), diagnostics::REQUEST_ID, diagnostics::RESPONSE_ID);
+    simulation.uds = Some(server.with_identity(identity, vehicle_path));
+
+    // `--can-trace <path>` or SIM_CAN_TRACE records the TPMS frames
+    if let Some(path) = can_trace::path_from_args() {
+        let trace = CanTrace::create(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot write CAN trace {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        simulation.can.record_to(trace);
+    }
+
+    // `--xcp <address>` or SIM_XCP serves the described signals and
+    // thresholds to measurement and calibration tools over UDP
+    if let Some(address) = xcp::address_from_args() {
+        let description = diagnostics::describe(
+            simulation.tpms.state().readings.len(),
+            simulation.tpms.low_pressure_ratio(),
+            simulation.tpms.high_pressure_ratio(),
+            simulation.calibrations.as_ref().map(CalibrationSet::active),
+        );
+        let server = XcpServer::start(&address, &description).unwrap_or_else(|e| {
+            eprintln!("Cannot serve XCP on {}: {}", address, e);
+            process::exit(1);
+        });
+        server.watch_bus(&simulation.can);
+        println!("XCP on UDP {}", server.local_addr().map_or(address, |address| address.to_string()));
+        simulation.xcp = Some(server);
+    }
+
+    // `--mqtt <host[:port]>` or SIM_MQTT publishes every step's telemetry
+    // under vehicle/tpms
+    #[cfg(feature = "mqtt")]
+    if let Some(broker) = vehicle_sim_core::mqtt::broker_from_args() {
+        let options = vehicle_sim_core::mqtt::MqttOptions::from_env(broker, "tpms");
+        let publisher = vehicle_sim_core::mqtt::MqttPublisher::connect(&options).unwrap_or_else(|e| {
+            eprintln!("Cannot connect to MQTT broker {}: {}", options.broker, e);
+            process::exit(1);
+        });
+        println!("Publishing telemetry to MQTT broker {} under {}/", options.broker, options.topic_prefix);
+        simulation.mqtt = Some(publisher);
+    }
+
+    // `--privacy <path>` or SIM_PRIVACY filters the telemetry before it goes
+    // to the broker, the dashboard or the control interface
+    if let Some(path) = privacy::path_from_args() {
+        let policy = PrivacyPolicy::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read privacy settings {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        simulation.privacy = Some(PrivacyFilter::new(policy));
+    }
+
+    // Run the simulation for 10 iterations (or the scenario's duration) in
+    // real time, unless `--realtime-factor` or SIM_REALTIME_FACTOR says
+    // otherwise
+    let steps = scenario_file.as_ref().and_then(|s| s.steps(1.0)).unwrap_or(10);
+    let mut runner = FixedStepRunner::new(1.0)
+        .with_max_steps(start.steps + steps)
+        .with_realtime_factor(realtime_factor.or_else(clock::realtime_factor_from_args).unwrap_or(1.0))
+        .with_metrics(simulation.metrics.clone())
+        .resume_from(start);
+    if let Some(path) = save_path {
+        runner = runner.with_checkpoint(path);
+    }
+
+    // `--control-tokens <path>` lets a test framework start, pause and step
+    // the run and inject faults with the tokens allowed to `control`, over
+    // the dashboard's `/control/...` routes and, with `--grpc <address>`,
+    // the SimControl gRPC service; `--paused` waits for a start before the
+    // first step
+    let mut control = None;
+    if let Some(path) = control_tokens {
+        let tokens = TokenStore::load(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read control tokens {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        let pause = Arc::new(PauseControl::new());
+        pause.set_paused(paused);
+        let (run_control, faults) = RunControl::new(pause.clone(), tokens);
+        simulation.faults = Some(faults);
+        runner = runner.with_pause(pause);
+        control = Some(run_control);
+    }
+    if paused && control.is_none() {
+        eprintln!("--paused needs --control-tokens to start the run with, ignoring it");
+    }
+
+    // `--dashboard <address>` serves live tire pressures, Prometheus metrics,
+    // the service interface used by `service_tool` and the control routes
+    // when built with the `dashboard` feature; service requests that change
+    // the vehicle need a control token
+    #[cfg(feature = "dashboard")]
+    let dashboard = dashboard_address.and_then(|address| {
+        let metrics = simulation.metrics.clone();
+        vehicle_sim_core::dashboard::Dashboard::start(&address, "Tire Pressure Monitoring", Vec::new(), metrics)
+            .map_err(|e| eprintln!("Cannot start dashboard on {}: {}", address, e))
+            .ok()
+    });
+    #[cfg(feature = "dashboard")]
+    if let Some(dashboard) = &dashboard {
+        simulation.service = Some(dashboard.service_requests());
+        if let Some(control) = &control {
+            dashboard.control(control.clone());
+        }
+    }
+    #[cfg(not(feature = "dashboard"))]
+    if dashboard_address.is_some() {
+        eprintln!("Built without the dashboard feature, ignoring --dashboard");
+    }
 
-    let mut tpms = tpms::TPMS::new(safe_pressure, tire_pressures);
+    #[cfg(feature = "grpc")]
+    if let Some(address) = vehicle_sim_core::grpc::address_from_args() {
+        let Some(control) = &control else {
+            eprintln!("--grpc needs --control-tokens, not serving SimControl");
+            process::exit(2);
+        };
+        let served = vehicle_sim_core::grpc::serve(&address, control.clone()).unwrap_or_else(|e| {
+            eprintln!("Cannot serve SimControl on {}: {}", address, e);
+            process::exit(1);
+        });
+        println!("SimControl gRPC on {}", served);
+    }
+
+    // Collected for the timeline printed after a text-mode run
+    let timeline = simulation.events.subscribe(EventFilter::all());
+    #[cfg(feature = "dashboard")]
+    let streamed = simulation.events.subscribe(EventFilter::all());
 
-    // Simulation over time
-    for _ in 0..10 {  // Run the simulation for 10 iterations
-        tpms.check_all_tires();
-        tpms.display_warnings();
+    // The dashboard and the control interface share one stream
+    let mut stream_privacy = simulation.privacy.as_ref().map(PrivacyFilter::for_stream);
+
+    match format {
+        OutputFormat::Text => {
+            runner.run_with(&mut simulation, |state, summary| {
+                let signals: Vec<(&str, f64)> = state
+                    .readings
+                    .iter()
+                    .map(|reading| (reading.position.as_str(), reading.pressure as f64))
+                    .collect();
+                let signals = privacy::filtered(stream_privacy.as_mut(), summary.simulated_seconds, signals);
+                if let Some(control) = &control {
+                    control.update(summary.steps, summary.simulated_seconds, &signals);
+                }
+                #[cfg(feature = "dashboard")]
+                if let Some(dashboard) = &dashboard {
+                    for event in streamed.try_iter() {
+                        dashboard.publish_event(&event);
+                    }
+                    dashboard.publish(&signals);
+                }
+            });
+            // The averages still open at the end go out with a last update
+            #[cfg(feature = "dashboard")]
+            if let (Some(dashboard), Some(privacy)) = (&dashboard, &mut stream_privacy) {
+                let pending = privacy.flush();
+                if !pending.is_empty() {
+                    dashboard.publish(&pending);
+                }
+            }
+            events::print_timeline(&timeline);
+            println!("Simulation completed.");
+        }
+        OutputFormat::Json => {
+            let mut writer = TelemetryWriter::new(output_file.as_deref()).unwrap_or_else(|e| {
+                eprintln!("Cannot open telemetry output: {}", e);
+                process::exit(1);
+            });
 
-        if tpms.is_dtc_triggered() {
-            println!("DTC Triggered: One or more tires have unsafe pressure!");
-        } else {
-            println!("All tires are within the safe pressure range.");
+            runner.quiet().run_with(&mut simulation, |state, summary| {
+                if let Some(control) = &control {
+                    let signals: Vec<(&str, f64)> = state
+                        .readings
+                        .iter()
+                        .map(|reading| (reading.position.as_str(), reading.pressure as f64))
+                        .collect();
+                    let signals = privacy::filtered(stream_privacy.as_mut(), summary.simulated_seconds, signals);
+                    control.update(summary.steps, summary.simulated_seconds, &signals);
+                }
+                if let Err(e) = writer.write(&state, summary.steps, summary.simulated_seconds) {
+                    eprintln!("Failed to write telemetry: {}", e);
+                }
+            });
         }
+    }
+
+    #[cfg(feature = "mqtt")]
+    simulation.flush_mqtt();
+    // Service requests that arrive from now on are refused right away
+    simulation.service = None;
+    print_budget_overruns(&simulation.metrics);
+    save_dtcs(simulation.tpms.dtc_store(), dtc_path);
+}
+
+fn print_budget_overruns(metrics: &Metrics) {
+    let overruns = metrics.budget_overruns();
+    if overruns.is_empty() {
+        return;
+    }
+
+    eprintln!("Components over their time budget:");
+    for overrun in overruns {
+        eprintln!(
+            "  {}: {} steps over {:.3} ms, worst {:.3} ms",
+            overrun.component,
+            overrun.overruns,
+            overrun.limit.as_secs_f64() * 1000.0,
+            overrun.worst.as_secs_f64() * 1000.0
+        );
+    }
+}
 
-        tpms.simulate_pressure_change();
+fn save_dtcs(store: &DtcStore, path: &Path) {
+    if let Err(e) = store.save(path) {
+        eprintln!("Failed to save DTC store {}: {}", path.display(), e);
+    }
+}
 
-        // Wait for 1 second before the next iteration
-        thread::sleep(Duration::from_secs(1));
+fn print_dtcs(store: &DtcStore) {
+    println!("Ignition cycles: {}", store.ignition_cycles());
+    if store.records().next().is_none() {
+        println!("No stored DTCs.");
+        return;
     }
 
-    println!("Simulation completed.");
+    for (component, record) in store.records() {
+        let status = match record.status {
+            DtcStatus::Pending => "pending",
+            DtcStatus::Confirmed => "confirmed",
+        };
+        println!(
+            "{} [{}] {} {}: {} (occurrences: {}, failed in {} ignition cycles, first seen {:.0}, last seen {:.0}, fault-free cycles: {}{})",
+            component,
+            record.code,
+            status,
+            if record.active { "active" } else { "stored" },
+            record.description,
+            record.occurrences,
+            record.failed_cycles,
+            record.first_seen,
+            record.last_seen,
+            record.healthy_cycles,
+            if record.warning_indicator { ", warning lamp requested" } else { "" }
+        );
+        for entry in &record.freeze_frame {
+            println!("    {}: {:.2} PSI at {:.1} °C", entry.position, entry.pressure, entry.temperature);
+        }
+    }
 }
@@ -0,0 +1,69 @@
+use serde_json::{json, Value};
+use vehicle_sim_core::routine::{Routine, RoutineControl, RoutineProgress};
+
+use crate::dtc::FreezeFrameEntry;
+use crate::tpms::TPMS;
+
+// Routine identifiers, as used by RoutineControl requests
+pub const SENSOR_PING_ID: u16 = 0x0201;
+
+// Steps a sensor ping listens for fresh transmissions
+const PING_STEPS: u32 = 3;
+
+pub fn routines() -> RoutineControl<TPMS> {
+    let mut routines = RoutineControl::new();
+    routines.register(SENSOR_PING_ID, "sensor_ping", Box::new(SensorPing::default()));
+    routines
+}
+
+// Wakes every wheel sensor and listens for a few steps: a live sensor's
+// readings keep changing, a stuck one repeats its last frame
+#[derive(Default)]
+struct SensorPing {
+    first: Vec<FreezeFrameEntry>,
+    latest: Vec<FreezeFrameEntry>,
+    steps: u32,
+}
+
+impl SensorPing {
+    fn responded(&self) -> impl Iterator<Item = bool> + '_ {
+        self.first.iter().zip(&self.latest).map(|(first, latest)| first != latest)
+    }
+}
+
+impl Routine<TPMS> for SensorPing {
+    fn start(&mut self, tpms: &mut TPMS) {
+        self.first = tpms.wake_up_sensors();
+        self.latest = self.first.clone();
+        self.steps = 0;
+    }
+
+    fn step(&mut self, tpms: &mut TPMS) -> RoutineProgress {
+        self.steps += 1;
+        self.latest = tpms.wake_up_sensors();
+        if self.steps < PING_STEPS {
+            RoutineProgress::Running
+        } else if self.responded().all(|responded| responded) {
+            RoutineProgress::Passed
+        } else {
+            RoutineProgress::Failed
+        }
+    }
+
+    fn results(&self) -> Value {
+        let sensors: Vec<Value> = self
+            .latest
+            .iter()
+            .zip(self.responded())
+            .map(|(reading, responded)| {
+                json!({
+                    "position": reading.position,
+                    "responded": responded,
+                    "pressure": reading.pressure,
+                    "temperature": reading.temperature,
+                })
+            })
+            .collect();
+        json!({ "sensors": sensors })
+    }
+}
@@ -0,0 +1,135 @@
+use serde_json::json;
+use vehicle_sim_core::calibration;
+use vehicle_sim_core::routine::RoutineError;
+use vehicle_sim_core::service::{ServiceRequest, ServiceResponse};
+
+use crate::dtc::DtcStatus;
+use crate::simulation::TpmsSimulation;
+use crate::tpms::STUCK_SENSOR_CYCLES;
+
+// Actuator tests `POST actuators/<name>` can run
+pub const ACTUATORS: &[&str] = &["sensor_wakeup"];
+
+impl TpmsSimulation {
+    // Answers the requests of a service tool connected through the dashboard
+    pub fn answer_service_requests(&mut self) {
+        let Some(service) = &self.service else {
+            return;
+        };
+        let requests: Vec<ServiceRequest> = service.try_iter().collect();
+        for request in requests {
+            let response = self.service_response(&request.method, &request.path);
+            request.respond(response);
+        }
+    }
+
+    fn service_response(&mut self, method: &str, path: &str) -> ServiceResponse {
+        match (method, path) {
+            ("GET", "dtcs") => {
+                let records: Vec<_> = self
+                    .tpms
+                    .dtc_store()
+                    .records()
+                    .map(|(component, record)| json!({ "component": component, "record": record }))
+                    .collect();
+                ServiceResponse::ok(json!({ "dtcs": records }))
+            }
+            ("POST", "dtcs/clear") => {
+                self.tpms.clear_dtcs();
+                ServiceResponse::ok(json!({ "cleared": true }))
+            }
+            ("GET", "readiness") => ServiceResponse::ok(self.readiness()),
+            ("POST", "actuators/sensor_wakeup") => {
+                let readings = self.tpms.wake_up_sensors();
+                ServiceResponse::ok(json!({ "actuator": "sensor_wakeup", "readings": readings }))
+            }
+            ("GET", "calibrations") => self.calibrations_response(),
+            (_, _) if path.starts_with("calibrations/") => self.calibration_response(method, &path["calibrations/".len()..]),
+            ("GET", "routines") => ServiceResponse::ok(json!({ "routines": self.routines.reports() })),
+            ("POST", _) if path.starts_with("uds/") => self.uds_response(&path["uds/".len()..]),
+            (_, _) if path.starts_with("routines/") => self.routine_response(method, &path["routines/".len()..]),
+            ("POST", _) if path.starts_with("actuators/") => ServiceResponse::error(
+                404,
+                &format!("unknown actuator, available: {}", ACTUATORS.join(", ")),
+            ),
+            (_, "dtcs" | "dtcs/clear" | "readiness") => ServiceResponse::error(405, "method not allowed"),
+            _ => ServiceResponse::error(404, "unknown service"),
+        }
+    }
+
+    // `GET routines/<name>` returns the results, `POST routines/<name>/start`
+    // and `.../stop` control the routine
+    fn routine_response(&mut self, method: &str, path: &str) -> ServiceResponse {
+        let result = match (method, path.split_once('/')) {
+            ("GET", None) => self.routines.results(path).map(|report| json!(report)),
+            ("POST", Some((name, "start"))) => self.routines.start(name, &mut self.tpms).map(|_| json!({ "started": name })),
+            ("POST", Some((name, "stop"))) => self.routines.stop(name, &mut self.tpms).map(|_| json!({ "stopped": name })),
+            _ => return ServiceResponse::error(404, "unknown service"),
+        };
+        match result {
+            Ok(body) => ServiceResponse::ok(body),
+            Err(e @ RoutineError::Unknown(_)) => ServiceResponse::error(404, &e.to_string()),
+            Err(e) => ServiceResponse::error(409, &e.to_string()),
+        }
+    }
+
+    fn calibrations_response(&self) -> ServiceResponse {
+        let Some(calibrations) = &self.calibrations else {
+            return ServiceResponse::error(404, "no calibration loaded, start with --calibration <path>");
+        };
+        let versions: Vec<&str> = calibrations.versions().iter().map(|calibration| calibration.version.as_str()).collect();
+        ServiceResponse::ok(json!({
+            "name": calibrations.active().name,
+            "active": calibrations.active().version,
+            "versions": versions,
+        }))
+    }
+
+    // `GET calibrations/<version>` returns its parameters,
+    // `POST calibrations/<version>/activate` switches to it
+    fn calibration_response(&mut self, method: &str, path: &str) -> ServiceResponse {
+        let Some(calibrations) = self.calibrations.as_mut() else {
+            return ServiceResponse::error(404, "no calibration loaded, start with --calibration <path>");
+        };
+        match (method, path.split_once('/')) {
+            ("GET", None) => match calibrations.version(path) {
+                Some(calibration) => ServiceResponse::ok(json!(calibration)),
+                None => ServiceResponse::error(404, &format!("no calibration version {}", path)),
+            },
+            ("POST", Some((version, "activate"))) => {
+                let previous = calibrations.active().clone();
+                let changes = match calibrations.activate(version) {
+                    Ok(active) => calibration::diff(&previous, active),
+                    Err(e) => return ServiceResponse::error(404, &e),
+                };
+                self.apply_calibration();
+                ServiceResponse::ok(json!({ "active": version, "changes": changes }))
+            }
+            _ => ServiceResponse::error(404, "unknown service"),
+        }
+    }
+
+    // Monitors complete once enough checks ran to judge them, like the
+    // readiness flags of an OBD scan
+    fn readiness(&self) -> serde_json::Value {
+        let checks = self.tpms.checks();
+        let store = self.tpms.dtc_store();
+        let confirmed = store
+            .records()
+            .filter(|(_, record)| record.status == DtcStatus::Confirmed)
+            .count();
+        let mil = store
+            .records()
+            .any(|(_, record)| record.active && record.status == DtcStatus::Confirmed);
+
+        json!({
+            "monitors": {
+                "pressure": checks >= 1,
+                "sensor_plausibility": checks >= STUCK_SENSOR_CYCLES as u64,
+            },
+            "mil": mil,
+            "confirmed_dtcs": confirmed,
+            "ignition_cycles": store.ignition_cycles(),
+        })
+    }
+}
@@ -0,0 +1,368 @@
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use vehicle_sim_core::calibration::CalibrationSet;
+use vehicle_sim_core::can_bus::{CanBus, TPMS_PRESSURES, TPMS_STATUS};
+use vehicle_sim_core::command::CommandBus;
+use vehicle_sim_core::config::{ConfigWatcher, LogLevel};
+use vehicle_sim_core::events::{Event, EventBus};
+use vehicle_sim_core::locale;
+use vehicle_sim_core::metrics::Metrics;
+use vehicle_sim_core::privacy::PrivacyFilter;
+use vehicle_sim_core::rng::SimRng;
+use vehicle_sim_core::routine::RoutineControl;
+use vehicle_sim_core::service::ServiceRequest;
+use vehicle_sim_core::sim_log;
+use vehicle_sim_core::simulation::Simulation;
+use vehicle_sim_core::uds::UdsServer;
+use vehicle_sim_core::vehicle_mode::{ModeManager, VehicleMode};
+use vehicle_sim_core::xcp::XcpServer;
+
+use crate::commands::parse_command;
+use crate::dtc::DtcStatus;
+use crate::telemetry::unix_timestamp;
+use crate::tpms::{TireStatus, TpmsState, TPMS};
+
+// Checkpoints keep the tires, DTCs and random stream; settings, inputs and
+// metrics come from the resuming run
+#[derive(Serialize, Deserialize)]
+pub struct TpmsSimulation {
+    pub tpms: TPMS,
+    pub rng: SimRng,
+    #[serde(skip)]
+    pub log_level: LogLevel,
+    #[serde(skip)]
+    pub config: Option<ConfigWatcher>,
+    #[serde(skip)]
+    pub commands: CommandBus<TPMS>,
+    // Typed commands (--interactive) and scheduled ones (--scenario)
+    #[serde(skip)]
+    pub console: Option<Receiver<String>>,
+    #[serde(skip)]
+    pub scenario: Vec<(u64, String)>,
+    pub steps: u64,
+    #[serde(skip)]
+    pub metrics: Arc<Metrics>,
+    #[serde(skip)]
+    pub events: Arc<EventBus>,
+    // Service tool requests forwarded by the dashboard
+    #[serde(skip)]
+    pub service: Option<Receiver<ServiceRequest>>,
+    // Fault commands injected through the dashboard's control interface
+    #[serde(skip)]
+    pub faults: Option<Receiver<String>>,
+    #[serde(skip)]
+    pub can: Arc<CanBus>,
+    // Self-tests started through the service interface; a checkpoint does
+    // not keep them running
+    #[serde(skip)]
+    pub routines: RoutineControl<TPMS>,
+    #[serde(skip)]
+    pub uds: Option<UdsServer>,
+    // Threshold calibrations the service tool can switch between
+    #[serde(skip)]
+    pub calibrations: Option<CalibrationSet>,
+    // Measurement and calibration over XCP (--xcp)
+    #[serde(skip)]
+    pub xcp: Option<Arc<XcpServer>>,
+    // Telemetry to an MQTT broker (--mqtt)
+    #[cfg(feature = "mqtt")]
+    #[serde(skip)]
+    pub mqtt: Option<Arc<vehicle_sim_core::mqtt::MqttPublisher>>,
+    // Applied to the telemetry before it is exported (--privacy)
+    #[serde(skip)]
+    pub privacy: Option<PrivacyFilter>,
+    // Follows the scenario's vehicle modes; the ECU idles while charging
+    #[serde(skip)]
+    pub mode: ModeManager,
+}
+
+// Config keys that may be edited while the simulation is running
+pub const SAFE_CONFIG_KEYS: &[&str] = &["low_pressure_ratio", "high_pressure_ratio", "log_level"];
+
+// Components timed every step; `budget.<component> = <ms>` in the config
+// caps how long each may take
+pub const BUDGETED_COMPONENTS: &[&str] = &["config", "commands", "service", "diagnostics", "routines", "xcp", "pressure_model", "tire_check", "step", "report"];
+
+impl TpmsSimulation {
+    fn run_commands(&mut self) {
+        let mut lines: Vec<String> = self
+            .scenario
+            .iter()
+            .filter(|(step, _)| *step == self.steps)
+            .map(|(_, command)| command.clone())
+            .collect();
+        for command in &lines {
+            self.events.publish(
+                self.steps,
+                Event::ScenarioStepReached {
+                    command: command.clone(),
+                },
+            );
+        }
+        if let Some(console) = &self.console {
+            lines.extend(console.try_iter());
+        }
+        if let Some(faults) = &self.faults {
+            lines.extend(faults.try_iter());
+        }
+
+        for line in lines {
+            self.run_command(line.trim());
+        }
+    }
+
+    fn run_command(&mut self, line: &str) {
+        match line.split_once(' ').map_or((line, ""), |(verb, rest)| (verb, rest.trim())) {
+            ("", _) => {}
+            ("undo", _) => match self.commands.undo(&mut self.tpms) {
+                Some(command) => sim_log::info("command", &format!("Undone: {}", command)),
+                None => sim_log::info("command", "Nothing to undo."),
+            },
+            ("redo", _) => match self.commands.redo(&mut self.tpms) {
+                Some(command) => sim_log::info("command", &format!("Redone: {}", command)),
+                None => sim_log::info("command", "Nothing to redo."),
+            },
+            ("history", _) => {
+                for (step, command) in self.commands.history() {
+                    sim_log::info("command", &format!("  step {}: {}", step, command));
+                }
+            }
+            ("save", path) if !path.is_empty() => match self.commands.save_scenario(Path::new(path)) {
+                Ok(()) => sim_log::info("command", &format!("Scenario saved to {}", path)),
+                Err(e) => sim_log::warn("command", &format!("Failed to save scenario {}: {}", path, e)),
+            },
+            _ => match parse_command(&self.tpms, line) {
+                Ok(command) => {
+                    sim_log::info("command", &format!("Executing: {}", command.describe()));
+                    self.commands.execute(self.steps, command, &mut self.tpms);
+                }
+                Err(e) => sim_log::warn("command", &e.to_string()),
+            },
+        }
+    }
+
+    // Warnings when a tire leaves the safe range and DTCs that became active
+    fn publish_events(&self, before: &TpmsState) {
+        let after = self.tpms.state();
+
+        for (previous, reading) in before.readings.iter().zip(&after.readings) {
+            if reading.status != previous.status && !reading.is_safe {
+                let message = match reading.status {
+                    TireStatus::Underinflated => "pressure too low",
+                    TireStatus::Overinflated => "pressure too high",
+                    _ => "sensor not responding",
+                };
+                self.events.publish(
+                    self.steps,
+                    Event::WarningRaised {
+                        source: reading.position.clone(),
+                        message: message.to_string(),
+                    },
+                );
+            }
+        }
+
+        for (_, record) in self.tpms.dtc_store().records() {
+            if record.active && !before.active_dtcs.contains(&record.code) {
+                self.events.publish(
+                    self.steps,
+                    Event::DtcSet {
+                        code: record.code.clone(),
+                        description: record.description.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    fn transmit_frames(&self) {
+        let state = self.tpms.state();
+        let names: Vec<String> = (0..state.readings.len()).map(|index| format!("Pressure_{}", index)).collect();
+        let pressures: Vec<(&str, f64)> = names
+            .iter()
+            .zip(&state.readings)
+            .map(|(name, reading)| (name.as_str(), reading.pressure as f64))
+            .collect();
+        self.can.transmit(&TPMS_PRESSURES, &pressures);
+        self.can.transmit(
+            &TPMS_STATUS,
+            &[
+                ("WarningActive", state.dtc_triggered as u8 as f64),
+                ("ActiveDtcs", state.active_dtcs.len() as f64),
+            ],
+        );
+    }
+
+    // Applies the thresholds of the active calibration, keeping the current
+    // value of any parameter it lacks or has out of range
+    pub fn apply_calibration(&mut self) {
+        let Some(calibration) = self.calibrations.as_ref().map(CalibrationSet::active) else {
+            return;
+        };
+        match calibration.get_f64("low_pressure_ratio").map(|ratio| ratio as f32) {
+            Some(ratio) if ratio > 0.0 && ratio <= 1.0 => self.tpms.set_low_pressure_ratio(ratio),
+            Some(ratio) => sim_log::warn("calibration", &format!("Ignoring low_pressure_ratio = {}: expected a ratio in (0, 1]", ratio)),
+            None => {}
+        }
+        match calibration.get_f64("high_pressure_ratio").map(|ratio| ratio as f32) {
+            Some(ratio) if ratio >= 1.0 => self.tpms.set_high_pressure_ratio(ratio),
+            Some(ratio) => sim_log::warn("calibration", &format!("Ignoring high_pressure_ratio = {}: expected a ratio of at least 1", ratio)),
+            None => {}
+        }
+        sim_log::info("calibration", &format!("Calibration {} active", calibration));
+    }
+
+    fn apply_config_updates(&mut self) {
+        let Some(config) = self.config.as_mut() else {
+            return;
+        };
+
+        for update in config.poll() {
+            let value = update.value.as_deref().unwrap_or("<default>");
+            match update.key.as_str() {
+                "low_pressure_ratio" => match update.value.as_deref().map(str::parse::<f32>) {
+                    Some(Ok(ratio)) if ratio > 0.0 && ratio <= 1.0 => self.tpms.set_low_pressure_ratio(ratio),
+                    None => self.tpms.set_low_pressure_ratio(DEFAULT_LOW_PRESSURE_RATIO),
+                    _ => {
+                        sim_log::warn("config", &format!("Ignoring low_pressure_ratio = {}: expected a ratio in (0, 1]", value));
+                        continue;
+                    }
+                },
+                "high_pressure_ratio" => match update.value.as_deref().map(str::parse::<f32>) {
+                    Some(Ok(ratio)) if ratio >= 1.0 => self.tpms.set_high_pressure_ratio(ratio),
+                    None => self.tpms.set_high_pressure_ratio(DEFAULT_HIGH_PRESSURE_RATIO),
+                    _ => {
+                        sim_log::warn("config", &format!("Ignoring high_pressure_ratio = {}: expected a ratio of at least 1", value));
+                        continue;
+                    }
+                },
+                "log_level" => match update.value.as_deref().map(LogLevel::parse) {
+                    Some(Some(level)) => self.log_level = level,
+                    None => self.log_level = LogLevel::Info,
+                    Some(None) => {
+                        sim_log::warn("config", &format!("Ignoring log_level = {}: expected error, warn, info or debug", value));
+                        continue;
+                    }
+                },
+                _ => continue,
+            }
+            sim_log::info("config", &format!("Config reloaded: {} = {}", update.key, value));
+        }
+    }
+}
+
+// Warn when a tire drops below 94% of its nominal pressure
+pub const DEFAULT_LOW_PRESSURE_RATIO: f32 = 0.94;
+// ... and when it is inflated above 115% of it
+pub const DEFAULT_HIGH_PRESSURE_RATIO: f32 = 1.15;
+
+impl Simulation for TpmsSimulation {
+    type State = TpmsState;
+
+    fn step(&mut self, dt: f64) {
+        self.steps += 1;
+        let metrics = Arc::clone(&self.metrics);
+        metrics.time("config", || self.apply_config_updates());
+        metrics.time("commands", || self.run_commands());
+        metrics.time("service", || self.answer_service_requests());
+        metrics.time("diagnostics", || self.answer_diagnostic_requests());
+        metrics.time("routines", || self.routines.step(&mut self.tpms));
+        metrics.time("xcp", || self.apply_xcp_writes());
+        metrics.time("pressure_model", || self.tpms.simulate_pressure_change(&mut self.rng, dt));
+        // Scenario seconds, counted like the fault schedule
+        self.mode.update(self.steps as f64 * dt);
+        self.mode.transmit(&self.can);
+        // Parked at a charger the tires are left alone: no checks, no frames
+        if self.mode.mode() != VehicleMode::Charging {
+            let before = self.tpms.state();
+            metrics.time("tire_check", || self.tpms.check_all_tires(unix_timestamp()));
+            self.publish_events(&before);
+            self.transmit_frames();
+        }
+        self.can.end_tick();
+        self.publish_xcp();
+        #[cfg(feature = "mqtt")]
+        self.publish_mqtt(self.steps as f64 * dt);
+
+        for reading in self.tpms.state().readings {
+            let labels = [("tire", reading.position.as_str())];
+            metrics.set_gauge("tpms_pressure_psi", "Reported tire pressure", &labels, reading.pressure as f64);
+            metrics.set_gauge("tpms_temperature_celsius", "Reported tire temperature", &labels, reading.temperature as f64);
+            if !reading.is_safe {
+                metrics.inc_counter("tpms_warnings_total", "Check cycles with an unsafe tire", &labels, 1.0);
+            }
+        }
+    }
+
+    fn state(&self) -> TpmsState {
+        self.tpms.state()
+    }
+
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self).ok()
+    }
+
+    fn report(&self) -> String {
+        if self.mode.mode() == VehicleMode::Charging {
+            return "Vehicle charging, tire checks paused.".to_string();
+        }
+        let state = self.state();
+        let locale = locale::current();
+        let mut lines: Vec<String> = state
+            .readings
+            .iter()
+            .filter(|reading| self.log_level >= LogLevel::Info || !reading.is_safe)
+            .map(|reading| {
+                let measured = format!(
+                    "{} at {}, {} cold",
+                    locale.pressure(reading.pressure, 2),
+                    locale.temperature(reading.temperature, 1),
+                    locale.pressure(reading.compensated_pressure, 2)
+                );
+                match reading.status {
+                    TireStatus::Safe => format!("{}: Pressure is safe ({})", reading.position, measured),
+                    TireStatus::Underinflated => format!(
+                        "{}: WARNING! Pressure is too low ({}, nominal {})",
+                        reading.position,
+                        measured,
+                        locale.pressure(reading.nominal_pressure, 1)
+                    ),
+                    TireStatus::Overinflated => format!(
+                        "{}: WARNING! Pressure is too high ({}, nominal {})",
+                        reading.position,
+                        measured,
+                        locale.pressure(reading.nominal_pressure, 1)
+                    ),
+                    TireStatus::SensorFault => format!(
+                        "{}: WARNING! Sensor not responding (stuck at {})",
+                        reading.position,
+                        locale.pressure(reading.pressure, 2)
+                    ),
+                }
+            })
+            .collect();
+
+        if state.dtc_triggered {
+            lines.push("DTC Triggered: One or more tires have unsafe pressure!".to_string());
+        } else {
+            lines.push("All tires are within the safe pressure range.".to_string());
+        }
+
+        for (_, record) in self.tpms.dtc_store().records().filter(|(_, record)| record.active) {
+            let status = match record.status {
+                DtcStatus::Pending => "pending",
+                DtcStatus::Confirmed => "confirmed",
+            };
+            lines.push(format!(
+                "DTC {} active ({}): {} ({} occurrence(s))",
+                record.code, status, record.description, record.occurrences
+            ));
+        }
+
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::tpms::TpmsState;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Option<OutputFormat> {
+        match value {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TelemetryRecord<'a> {
+    timestamp: f64,
+    sim_time: f64,
+    iteration: u64,
+    #[serde(flatten)]
+    state: &'a TpmsState,
+}
+
+// Writes one JSON object per line (JSON Lines) to stdout or a file
+pub struct TelemetryWriter {
+    out: Box<dyn Write>,
+}
+
+impl TelemetryWriter {
+    pub fn new(path: Option<&str>) -> io::Result<Self> {
+        let out: Box<dyn Write> = match path {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(io::stdout()),
+        };
+
+        Ok(TelemetryWriter { out })
+    }
+
+    pub fn write(&mut self, state: &TpmsState, iteration: u64, sim_time: f64) -> io::Result<()> {
+        let record = TelemetryRecord {
+            timestamp: unix_timestamp(),
+            sim_time,
+            iteration,
+            state,
+        };
+
+        serde_json::to_writer(&mut self.out, &record)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+// Wall-clock seconds since the Unix epoch
+pub fn unix_timestamp() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |elapsed| elapsed.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtc::{DtcStore, DTC_CONFIG};
+    use crate::tire_config::VehicleLayout;
+    use crate::tpms::TPMS;
+
+    #[test]
+    fn every_line_is_a_json_record_of_one_check() {
+        let tpms = TPMS::new(0.94, 1.15, VehicleLayout::Car.tires(), DtcStore::new(DTC_CONFIG));
+        let path = std::env::temp_dir().join(format!("tpms_telemetry_{}.jsonl", std::process::id()));
+        let mut writer = TelemetryWriter::new(path.to_str()).unwrap();
+        writer.write(&tpms.state(), 1, 0.5).unwrap();
+        writer.write(&tpms.state(), 2, 1.0).unwrap();
+        drop(writer);
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let records: Vec<serde_json::Value> = written.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        for (record, (iteration, sim_time)) in records.iter().zip([(1, 0.5), (2, 1.0)]) {
+            assert_eq!(record["iteration"], iteration);
+            assert_eq!(record["sim_time"], sim_time);
+            assert!(record["timestamp"].as_f64().unwrap() > 0.0);
+            // The state is flattened into the record
+            assert!(record["dtc_triggered"].is_boolean());
+            assert!(record["active_dtcs"].is_array());
+            let readings = record["readings"].as_array().unwrap();
+            assert_eq!(readings.len(), 4);
+            assert_eq!(readings[0]["position"], "Front-Left");
+            assert_eq!(readings[0]["nominal_pressure"], 32.0);
+            for field in ["pressure", "compensated_pressure", "temperature"] {
+                assert!(readings[0][field].is_number(), "{}", field);
+            }
+            assert!(readings[0]["status"].is_string());
+            assert!(readings[0]["is_safe"].is_boolean());
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Axle {
+    Front,
+    Rear,
+    // Axles counted from the front, for vehicles with more than two
+    Numbered(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+    // Single-track vehicles like motorcycles
+    Center,
+}
+
+// Twin tires mounted on the same wheel end (dual rears on trucks)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Twin {
+    Inner,
+    Outer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TirePosition {
+    pub axle: Axle,
+    pub side: Side,
+    pub twin: Option<Twin>,
+}
+
+impl TirePosition {
+    pub fn new(axle: Axle, side: Side) -> Self {
+        TirePosition { axle, side, twin: None }
+    }
+
+    pub fn twin(axle: Axle, side: Side, twin: Twin) -> Self {
+        TirePosition {
+            axle,
+            side,
+            twin: Some(twin),
+        }
+    }
+
+    // Compact name for typed commands: "fl", "r", "3ri", ...
+    pub fn short_name(&self) -> String {
+        let mut name = match self.axle {
+            Axle::Front => "f".to_string(),
+            Axle::Rear => "r".to_string(),
+            Axle::Numbered(number) => number.to_string(),
+        };
+        match self.side {
+            Side::Left => name.push('l'),
+            Side::Right => name.push('r'),
+            Side::Center => {}
+        }
+        match self.twin {
+            Some(Twin::Inner) => name.push('i'),
+            Some(Twin::Outer) => name.push('o'),
+            None => {}
+        }
+        name
+    }
+}
+
+impl fmt::Display for TirePosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.axle {
+            Axle::Front => write!(f, "Front")?,
+            Axle::Rear => write!(f, "Rear")?,
+            Axle::Numbered(n) => write!(f, "Axle {}", n)?,
+        }
+        match self.side {
+            Side::Left => write!(f, "-Left")?,
+            Side::Right => write!(f, "-Right")?,
+            Side::Center => {}
+        }
+        match self.twin {
+            Some(Twin::Inner) => write!(f, "-Inner"),
+            Some(Twin::Outer) => write!(f, "-Outer"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TireConfig {
+    pub position: TirePosition,
+    pub initial_pressure: f32,
+    pub nominal_pressure: f32,
+}
+
+impl TireConfig {
+    pub fn new(position: TirePosition, initial_pressure: f32, nominal_pressure: f32) -> Self {
+        TireConfig {
+            position,
+            initial_pressure,
+            nominal_pressure,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VehicleLayout {
+    Motorcycle,
+    Car,
+    Truck,
+}
+
+impl VehicleLayout {
+    pub fn parse(value: &str) -> Option<VehicleLayout> {
+        match value {
+            "motorcycle" => Some(VehicleLayout::Motorcycle),
+            "car" => Some(VehicleLayout::Car),
+            "truck" => Some(VehicleLayout::Truck),
+            _ => None,
+        }
+    }
+
+    // Example tire setups, each with a couple of tires starting under-inflated
+    pub fn tires(self) -> Vec<TireConfig> {
+        match self {
+            VehicleLayout::Motorcycle => vec![
+                TireConfig::new(TirePosition::new(Axle::Front, Side::Center), 36.0, 36.0),
+                TireConfig::new(TirePosition::new(Axle::Rear, Side::Center), 38.5, 42.0),
+            ],
+            VehicleLayout::Car => vec![
+                TireConfig::new(TirePosition::new(Axle::Front, Side::Left), 32.0, 32.0),
+                TireConfig::new(TirePosition::new(Axle::Front, Side::Right), 28.5, 32.0),
+                TireConfig::new(TirePosition::new(Axle::Rear, Side::Left), 31.0, 32.0),
+                TireConfig::new(TirePosition::new(Axle::Rear, Side::Right), 29.0, 32.0),
+            ],
+            VehicleLayout::Truck => {
+                let mut tires = vec![
+                    TireConfig::new(TirePosition::new(Axle::Numbered(1), Side::Left), 110.0, 110.0),
+                    TireConfig::new(TirePosition::new(Axle::Numbered(1), Side::Right), 109.0, 110.0),
+                ];
+                for axle in 2..=3 {
+                    for side in [Side::Left, Side::Right] {
+                        for twin in [Twin::Inner, Twin::Outer] {
+                            let initial = if axle == 3 && side == Side::Right && twin == Twin::Inner { 88.0 } else { 100.0 };
+                            tires.push(TireConfig::new(TirePosition::twin(Axle::Numbered(axle), side, twin), initial, 100.0));
+                        }
+                    }
+                }
+                tires
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(layout: VehicleLayout) -> Vec<(String, String)> {
+        layout
+            .tires()
+            .iter()
+            .map(|tire| (tire.position.short_name(), tire.position.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn every_layout_names_each_of_its_tires() {
+        let motorcycle = labels(VehicleLayout::Motorcycle);
+        assert_eq!(motorcycle, [("f".into(), "Front".into()), ("r".into(), "Rear".into())]);
+
+        let car: Vec<String> = labels(VehicleLayout::Car).into_iter().map(|(short, _)| short).collect();
+        assert_eq!(car, ["fl", "fr", "rl", "rr"]);
+        assert_eq!(labels(VehicleLayout::Car)[1].1, "Front-Right");
+
+        let truck = labels(VehicleLayout::Truck);
+        assert_eq!(truck.len(), 10);
+        assert_eq!(truck[0], ("1l".into(), "Axle 1-Left".into()));
+        assert_eq!(truck[9], ("3ro".into(), "Axle 3-Right-Outer".into()));
+
+        // Short names are typed in commands, so no two tires may share one
+        for layout in [VehicleLayout::Motorcycle, VehicleLayout::Car, VehicleLayout::Truck] {
+            let mut names: Vec<String> = labels(layout).into_iter().map(|(short, _)| short).collect();
+            let count = names.len();
+            names.sort();
+            names.dedup();
+            assert_eq!(names.len(), count, "{:?}", layout);
+        }
+    }
+
+    #[test]
+    fn only_known_layouts_are_parsed() {
+        assert_eq!(VehicleLayout::parse("motorcycle"), Some(VehicleLayout::Motorcycle));
+        assert_eq!(VehicleLayout::parse("car"), Some(VehicleLayout::Car));
+        assert_eq!(VehicleLayout::parse("truck"), Some(VehicleLayout::Truck));
+        for unknown in ["bus", "Car", " car", ""] {
+            assert_eq!(VehicleLayout::parse(unknown), None, "{:?}", unknown);
+        }
+    }
+}
@@ -1,95 +1,412 @@
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use vehicle_sim_core::dtc::DtcConfig;
+use vehicle_sim_core::tire::{self, Tire as TireModel};
+use vehicle_sim_core::units::{Celsius, Pressure, Psi, Temperature};
 
-#[derive(Debug)]
+use crate::dtc::{tire_fault_code, DtcStore, FaultReport, FreezeFrameEntry};
+use crate::tire_config::{TireConfig, TirePosition};
+
+// A sensor reporting the exact same pressure this many times is considered stuck
+pub const STUCK_SENSOR_CYCLES: u32 = 5;
+
+// Failures that can be injected into a tire to script scenarios
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Fault {
+    // Loses `rate` PSI per second
+    SlowLeak { rate: Psi },
+    // Loses all pressure at once
+    Blowout,
+    // The sensor keeps reporting the values it had when the fault was injected
+    SensorStuck,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PressureLimits {
+    pub min: Pressure,
+    pub max: Pressure,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Tire {
-    pressure: f32,
-    is_safe: bool,
+    position: TirePosition,
+    // The tire itself, shared with the road condition monitor; flattened so
+    // checkpoints keep their pressure and temperature fields
+    #[serde(flatten)]
+    model: TireModel,
+    status: TireStatus,
+    fault: Option<Fault>,
+    stuck_reading: Option<(Psi, Celsius)>,
+    last_reading: Option<Psi>,
+    unchanged_readings: u32,
 }
 
 impl Tire {
-    pub fn new(pressure: f32) -> Self {
+    pub fn new(config: TireConfig) -> Self {
         Self {
-            pressure,
-            is_safe: true,
+            position: config.position,
+            model: TireModel {
+                pressure: Pressure::from_psi(config.initial_pressure),
+                ..TireModel::new(Pressure::from_psi(config.nominal_pressure))
+            },
+            status: TireStatus::Safe,
+            fault: None,
+            stuck_reading: None,
+            last_reading: None,
+            unchanged_readings: 0,
         }
     }
 
-    pub fn check_pressure(&mut self, safe_pressure: f32) {
-        if self.pressure < safe_pressure {
-            self.is_safe = false;
-        } else {
-            self.is_safe = true;
+    // What the wheel sensor reports, which is what the TPMS works with
+    pub fn sensor_reading(&self) -> (Pressure, Temperature) {
+        match self.stuck_reading {
+            Some((pressure, temperature)) => (Pressure::from_psi(pressure), Temperature::from_celsius(temperature)),
+            None => (self.model.pressure, self.model.temperature),
         }
     }
 
-    pub fn status(&self) -> TireStatus {
-        if self.is_safe {
-            TireStatus::Safe
+    // The pressure the sensor reading would have at the reference temperature
+    pub fn compensated_pressure(&self) -> Pressure {
+        let (pressure, temperature) = self.sensor_reading();
+        tire::cold_pressure(pressure, temperature)
+    }
+
+    // Without temperature compensation the raw sensor pressure is judged
+    pub fn check_pressure(&mut self, limits: PressureLimits, temperature_compensation: bool) {
+        // Real pressures always fluctuate a little, a frozen value means a dead sensor
+        let (reading, _) = self.sensor_reading();
+        if self.last_reading == Some(reading.psi()) {
+            self.unchanged_readings += 1;
         } else {
-            TireStatus::Unsafe
+            self.unchanged_readings = 0;
         }
+        self.last_reading = Some(reading.psi());
+
+        let pressure = if temperature_compensation { self.compensated_pressure() } else { reading };
+        self.status = if self.unchanged_readings >= STUCK_SENSOR_CYCLES {
+            TireStatus::SensorFault
+        } else if pressure < limits.min {
+            TireStatus::Underinflated
+        } else if pressure > limits.max {
+            TireStatus::Overinflated
+        } else {
+            TireStatus::Safe
+        };
+    }
+
+    pub fn status(&self) -> TireStatus {
+        self.status
     }
 
-    pub fn adjust_pressure(&mut self, delta: f32) {
-        self.pressure += delta;
+    pub fn temperature(&self) -> Temperature {
+        self.model.temperature
+    }
+
+    pub fn pressure(&self) -> Pressure {
+        self.model.pressure
+    }
+
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    pub fn adjust_pressure(&mut self, delta: Pressure) {
+        self.model.adjust_pressure(delta);
+    }
+
+    // A burst tire is open to the atmosphere and stays flat
+    pub fn set_temperature(&mut self, temperature: Temperature) {
+        if self.fault == Some(Fault::Blowout) {
+            self.model.temperature = temperature;
+        } else {
+            self.model.set_temperature(temperature);
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TireStatus {
     Safe,
-    Unsafe,
+    Underinflated,
+    Overinflated,
+    SensorFault,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct TireReading {
+    pub position: String,
+    pub pressure: f32,
+    pub compensated_pressure: f32,
+    pub temperature: f32,
+    pub nominal_pressure: f32,
+    pub status: TireStatus,
+    pub is_safe: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TpmsState {
+    pub readings: Vec<TireReading>,
+    pub dtc_triggered: bool,
+    pub active_dtcs: Vec<String>,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize)]
 pub struct TPMS {
     tires: Vec<Tire>,
-    safe_pressure: f32,
-    dtc_triggered: bool,
+    low_pressure_ratio: f32,
+    high_pressure_ratio: f32,
+    dtc_store: DtcStore,
+    // Checks since the start of the ignition cycle
+    #[serde(default)]
+    checks: u64,
+    // Set from the variant coding at every start
+    #[serde(skip)]
+    temperature_compensation: bool,
 }
 
 impl TPMS {
-    pub fn new(safe_pressure: f32, tire_pressures: Vec<f32>) -> Self {
-        let tires = tire_pressures
+    // A tire is unsafe when its temperature-compensated pressure is below
+    // `low_pressure_ratio` or above `high_pressure_ratio` of its nominal pressure
+    pub fn new(low_pressure_ratio: f32, high_pressure_ratio: f32, tires: Vec<TireConfig>, dtc_store: DtcStore) -> Self {
+        let tires = tires
             .into_iter()
             .map(Tire::new)
             .collect();
 
         Self {
             tires,
-            safe_pressure,
-            dtc_triggered: false,
+            low_pressure_ratio,
+            high_pressure_ratio,
+            dtc_store,
+            checks: 0,
+            temperature_compensation: true,
         }
     }
 
-    pub fn check_all_tires(&mut self) {
-        self.dtc_triggered = false;  // Reset DTC flag before checking
-        for tire in &mut self.tires {
-            tire.check_pressure(self.safe_pressure);
-            if !tire.is_safe {
-                self.dtc_triggered = true;
-            }
+    // Every check is one test per tire, passed or failed
+    pub fn check_all_tires(&mut self, timestamp: f64) {
+        self.checks += 1;
+        let mut results = Vec::new();
+        for (index, tire) in self.tires.iter_mut().enumerate() {
+            let nominal = tire.model.nominal_pressure;
+            tire.check_pressure(PressureLimits {
+                min: nominal * self.low_pressure_ratio,
+                max: nominal * self.high_pressure_ratio,
+            }, self.temperature_compensation);
+            let problem = match tire.status() {
+                TireStatus::Safe => None,
+                TireStatus::Underinflated => Some("pressure too low"),
+                TireStatus::Overinflated => Some("pressure too high"),
+                TireStatus::SensorFault => Some("sensor not responding"),
+            };
+            let fault = FaultReport {
+                component: tire.position.to_string(),
+                code: tire_fault_code(index),
+                description: format!("{} {}", tire.position, problem.unwrap_or("pressure in range")),
+            };
+            results.push((fault, problem.is_some()));
+        }
+
+        let freeze_frame = self.wake_up_sensors();
+        for (fault, failed) in &results {
+            self.dtc_store.report(timestamp, fault, *failed, || freeze_frame.clone());
         }
     }
 
-    pub fn is_dtc_triggered(&self) -> bool {
-        self.dtc_triggered
+    // Accepts the short name ("fl") or the full label ("Front-Left")
+    pub fn find_tire(&self, name: &str) -> Option<usize> {
+        self.tires.iter().position(|tire| {
+            tire.position.short_name().eq_ignore_ascii_case(name) || tire.position.to_string().eq_ignore_ascii_case(name)
+        })
     }
 
-    pub fn display_warnings(&self) {
-        for (i, tire) in self.tires.iter().enumerate() {
-            match tire.status() {
-                TireStatus::Safe => println!("Tire {}: Pressure is safe ({:.2} PSI)", i + 1, tire.pressure),
-                TireStatus::Unsafe => println!("Tire {}: WARNING! Pressure is unsafe ({:.2} PSI)", i + 1, tire.pressure),
+    pub fn inject_fault(&mut self, tire_index: usize, fault: Fault) {
+        let tire = &mut self.tires[tire_index];
+        tire.stuck_reading = match fault {
+            Fault::SensorStuck => {
+                let (pressure, temperature) = tire.sensor_reading();
+                Some((pressure.psi(), temperature.celsius()))
             }
+            _ => None,
+        };
+        if fault == Fault::Blowout {
+            tire.model.pressure = Pressure::ZERO;
         }
+        tire.fault = Some(fault);
+    }
+
+    pub fn clear_fault(&mut self, tire_index: usize) {
+        let tire = &mut self.tires[tire_index];
+        tire.fault = None;
+        tire.stuck_reading = None;
+    }
+
+    pub fn tire_mut(&mut self, index: usize) -> &mut Tire {
+        &mut self.tires[index]
+    }
+
+    pub fn dtc_store(&self) -> &DtcStore {
+        &self.dtc_store
+    }
+
+    pub fn clear_dtcs(&mut self) {
+        self.dtc_store.clear();
+    }
+
+    // The DTC configuration is not checkpointed; a resumed run sets it again
+    pub fn set_dtc_config(&mut self, config: DtcConfig) {
+        self.dtc_store.set_config(config);
+    }
+
+    pub fn checks(&self) -> u64 {
+        self.checks
+    }
+
+    // Service actuator test: every sensor transmits its current reading on
+    // request instead of waiting for its next cycle
+    pub fn wake_up_sensors(&self) -> Vec<FreezeFrameEntry> {
+        self.tires
+            .iter()
+            .map(|tire| {
+                let (pressure, temperature) = tire.sensor_reading();
+                FreezeFrameEntry {
+                    position: tire.position.to_string(),
+                    pressure: pressure.psi(),
+                    temperature: temperature.celsius(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn set_temperature_compensation(&mut self, temperature_compensation: bool) {
+        self.temperature_compensation = temperature_compensation;
+    }
+
+    pub fn low_pressure_ratio(&self) -> f32 {
+        self.low_pressure_ratio
     }
 
-    pub fn simulate_pressure_change(&mut self) {
-        let mut rng = rand::thread_rng();
+    pub fn high_pressure_ratio(&self) -> f32 {
+        self.high_pressure_ratio
+    }
+
+    pub fn set_low_pressure_ratio(&mut self, low_pressure_ratio: f32) {
+        self.low_pressure_ratio = low_pressure_ratio;
+    }
+
+    pub fn set_high_pressure_ratio(&mut self, high_pressure_ratio: f32) {
+        self.high_pressure_ratio = high_pressure_ratio;
+    }
+
+    // Some tire's check is failing
+    pub fn is_dtc_triggered(&self) -> bool {
+        self.dtc_store.any_active()
+    }
+
+    pub fn state(&self) -> TpmsState {
+        TpmsState {
+            readings: self
+                .tires
+                .iter()
+                .map(|tire| {
+                    let (pressure, temperature) = tire.sensor_reading();
+                    TireReading {
+                        position: tire.position.to_string(),
+                        pressure: pressure.psi(),
+                        compensated_pressure: tire.compensated_pressure().psi(),
+                        temperature: temperature.celsius(),
+                        nominal_pressure: tire.model.nominal_pressure.psi(),
+                        status: tire.status(),
+                        is_safe: tire.status() == TireStatus::Safe,
+                    }
+                })
+                .collect(),
+            dtc_triggered: self.is_dtc_triggered(),
+            active_dtcs: self.dtc_store.active_codes(),
+        }
+    }
 
+    pub fn simulate_pressure_change(&mut self, rng: &mut impl Rng, dt: f64) {
         for tire in &mut self.tires {
             let pressure_change: f32 = rng.gen_range(-0.5..0.5);
-            tire.adjust_pressure(pressure_change);
+            tire.adjust_pressure(Pressure::from_psi(pressure_change));
+
+            // Tires warm up while driving and cool down when parked
+            let temperature_change: f32 = rng.gen_range(-3.0..4.0);
+            tire.set_temperature(Temperature::from_celsius((tire.model.temperature.celsius() + temperature_change).clamp(-30.0, 110.0)));
+
+            match tire.fault {
+                Some(Fault::SlowLeak { rate }) => tire.adjust_pressure(Pressure::from_psi(-rate * dt as f32)),
+                Some(Fault::Blowout) => tire.model.pressure = Pressure::ZERO,
+                _ => {}
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtc::DTC_CONFIG;
+    use crate::tire_config::VehicleLayout;
+    use vehicle_sim_core::rng::SimRng;
+    use vehicle_sim_core::tire::REFERENCE_TEMPERATURE;
+
+    fn healthy_car() -> TPMS {
+        let tires = VehicleLayout::Car
+            .tires()
+            .into_iter()
+            .map(|config| TireConfig::new(config.position, config.nominal_pressure, config.nominal_pressure))
+            .collect();
+        TPMS::new(0.94, 1.15, tires, DtcStore::new(DTC_CONFIG))
+    }
+
+    fn run(tpms: &mut TPMS, steps: u32) {
+        let mut rng = SimRng::from_seed(7);
+        for step in 0..steps {
+            tpms.simulate_pressure_change(&mut rng, 1.0);
+            // Keep the tires at the reference temperature
+            for tire in &mut tpms.tires {
+                tire.set_temperature(REFERENCE_TEMPERATURE);
+            }
+            tpms.check_all_tires(step as f64);
+        }
+    }
+
+    #[test]
+    fn slow_leak_is_detected_once_below_threshold() {
+        let mut tpms = healthy_car();
+        tpms.inject_fault(0, Fault::SlowLeak { rate: 1.0 });
+        run(&mut tpms, 1);
+        assert_eq!(tpms.tires[0].status(), TireStatus::Safe);
+
+        run(&mut tpms, 10);
+        assert_eq!(tpms.tires[0].status(), TireStatus::Underinflated);
+        assert!(tpms.is_dtc_triggered());
+        assert_eq!(tpms.dtc_store().active_codes(), vec!["C0750".to_string()]);
+    }
+
+    #[test]
+    fn blowout_is_detected_immediately() {
+        let mut tpms = healthy_car();
+        tpms.inject_fault(2, Fault::Blowout);
+        run(&mut tpms, 1);
+        assert_eq!(tpms.tires[2].pressure(), Pressure::ZERO);
+        assert_eq!(tpms.tires[2].status(), TireStatus::Underinflated);
+    }
+
+    #[test]
+    fn stuck_sensor_hides_leak_until_flagged() {
+        let mut tpms = healthy_car();
+        tpms.inject_fault(1, Fault::SensorStuck);
+        tpms.tire_mut(1).adjust_pressure(Pressure::from_psi(-10.0));
+        run(&mut tpms, 2);
+        assert_eq!(tpms.tires[1].status(), TireStatus::Safe);
+
+        run(&mut tpms, STUCK_SENSOR_CYCLES);
+        assert_eq!(tpms.tires[1].status(), TireStatus::SensorFault);
+        assert!(tpms.is_dtc_triggered());
+    }
+}
@@ -0,0 +1,66 @@
+// One 10 Hz step of the longitudinal dynamics of a fleet. `array_of_structs`
+// steps every vehicle on its own, the way a Vec of vehicles would;
+// `struct_of_arrays` steps the whole Fleet in one pass over its arrays, with
+// std::simd when built with the `simd` feature.
+//
+//     cargo bench -p vehicle_sim_core --bench fleet_physics
+//     cargo +nightly bench -p vehicle_sim_core --bench fleet_physics --features simd
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use vehicle_sim_core::fleet::{Fleet, FleetVehicle, VehicleParameters};
+
+const FLEET_SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+const DT: f64 = 0.1; // s
+
+// Every vehicle a little different, with a pedal and grade of its own
+fn fleet(size: usize) -> (Vec<FleetVehicle>, Vec<f64>, Vec<f64>) {
+    let vehicles = (0..size)
+        .map(|i| {
+            let share = (i % 100) as f64 / 100.0;
+            let parameters = VehicleParameters {
+                mass: 1200.0 + 800.0 * share,
+                ..VehicleParameters::default()
+            };
+            FleetVehicle::new(parameters, 10.0 + 20.0 * share)
+        })
+        .collect();
+    let pedal = (0..size).map(|i| ((i % 7) as f64 - 3.0) / 3.0).collect();
+    let grade = (0..size).map(|i| ((i % 11) as f64 - 5.0) / 100.0).collect();
+    (vehicles, pedal, grade)
+}
+
+fn array_of_structs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("array_of_structs");
+    for size in FLEET_SIZES {
+        let (mut vehicles, pedal, grade) = fleet(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                for ((vehicle, &pedal), &grade) in vehicles.iter_mut().zip(&pedal).zip(&grade) {
+                    vehicle.step(pedal, grade, DT);
+                }
+                black_box(&vehicles);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn struct_of_arrays(c: &mut Criterion) {
+    let mut group = c.benchmark_group("struct_of_arrays");
+    for size in FLEET_SIZES {
+        let (vehicles, pedal, grade) = fleet(size);
+        let mut fleet: Fleet = vehicles.into_iter().collect();
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                fleet.step(&pedal, &grade, DT);
+                black_box(fleet.speeds());
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, array_of_structs, struct_of_arrays);
+criterion_main!(benches);
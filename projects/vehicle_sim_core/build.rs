@@ -0,0 +1,29 @@
+// Generates the SimControl gRPC server and client with the `grpc` feature.
+// The service is declared here rather than in a .proto file, so the build
+// needs no protoc; its messages are in src/grpc.rs.
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+
+        let method = |name: &str, route: &str, input: &str, output: &str| {
+            Method::builder()
+                .name(name)
+                .route_name(route)
+                .input_type(format!("crate::grpc::{}", input))
+                .output_type(format!("crate::grpc::{}", output))
+                .codec_path("tonic_prost::ProstCodec")
+                .build()
+        };
+        let service = Service::builder()
+            .name("SimControl")
+            .package("vehicle_sim.control")
+            .method(method("start", "Start", "ControlRequest", "StateReply"))
+            .method(method("pause", "Pause", "ControlRequest", "StateReply"))
+            .method(method("step", "Step", "ControlRequest", "StateReply"))
+            .method(method("inject_fault", "InjectFault", "FaultRequest", "FaultReply"))
+            .method(method("get_state", "GetState", "ControlRequest", "StateReply"))
+            .build();
+        Builder::new().compile(&[service]);
+    }
+}
@@ -0,0 +1,72 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::Date;
+use crate::units::{Celsius, Hours};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Daylight {
+    // Sunrise and sunset in local solar time (hours)
+    SunriseSunset(Hours, Hours),
+    PolarDay,
+    PolarNight,
+}
+
+// Rough climatological model: a yearly and a daily temperature cycle whose
+// mean and amplitude depend on latitude, plus the astronomical day length.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AmbientModel {
+    pub latitude: f64,
+    pub annual_mean: f64,
+    pub annual_amplitude: f64,
+    pub daily_amplitude: f64,
+}
+
+impl AmbientModel {
+    pub fn new(latitude: f64) -> Self {
+        let latitude = latitude.clamp(-90.0, 90.0);
+
+        AmbientModel {
+            latitude,
+            annual_mean: 28.0 - 0.45 * latitude.abs(),
+            annual_amplitude: 0.25 * latitude.abs(),
+            daily_amplitude: 5.0,
+        }
+    }
+
+    pub fn temperature(&self, date: Date, hour: Hours) -> Celsius {
+        // Coldest around mid January in the north, mid July in the south
+        let coldest_day = if self.latitude >= 0.0 { 15.0 } else { 197.0 };
+        let season_phase = 2.0 * PI * (date.day_of_year() as f64 - coldest_day) / 365.25;
+        let seasonal = -self.annual_amplitude * season_phase.cos();
+
+        // Coldest just before sunrise, warmest mid afternoon
+        let daily_phase = 2.0 * PI * (hour - 5.0) / 24.0;
+        let daily = -self.daily_amplitude * daily_phase.cos();
+
+        (self.annual_mean + seasonal + daily) as Celsius
+    }
+
+    pub fn daylight(&self, date: Date) -> Daylight {
+        let declination = (-23.44f64).to_radians() * (2.0 * PI * (date.day_of_year() as f64 + 10.0) / 365.0).cos();
+        let cos_hour_angle = -self.latitude.to_radians().tan() * declination.tan();
+
+        if cos_hour_angle <= -1.0 {
+            Daylight::PolarDay
+        } else if cos_hour_angle >= 1.0 {
+            Daylight::PolarNight
+        } else {
+            let half_day = cos_hour_angle.acos().to_degrees() / 15.0;
+            Daylight::SunriseSunset(12.0 - half_day, 12.0 + half_day)
+        }
+    }
+
+    pub fn is_daylight(&self, date: Date, hour: Hours) -> bool {
+        match self.daylight(date) {
+            Daylight::SunriseSunset(sunrise, sunset) => hour >= sunrise && hour < sunset,
+            Daylight::PolarDay => true,
+            Daylight::PolarNight => false,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::env;
+
+// Headless runs for CI and parameter sweeps: `--batch <steps>` runs that many
+// steps as fast as possible without reports or console logging and prints
+// statistics over the whole run at the end
+
+// Uses `--batch <steps>` from the command line
+pub fn steps_from_args() -> Result<Option<u64>, String> {
+    let args: Vec<String> = env::args().collect();
+    match args.iter().position(|arg| arg == "--batch") {
+        Some(i) => args
+            .get(i + 1)
+            .ok_or_else(|| "--batch needs a number of steps".to_string())
+            .and_then(|value| parse_steps(value))
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn parse_steps(value: &str) -> Result<u64, String> {
+    match value.trim().parse::<u64>() {
+        Ok(steps) if steps > 0 => Ok(steps),
+        _ => Err(format!("invalid batch length '{}', expected a number of steps >= 1", value)),
+    }
+}
+
+// Smallest, largest and mean of the values recorded
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    count: u64,
+    min: f64,
+    max: f64,
+    sum: f64,
+}
+
+impl Stats {
+    pub fn record(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+// `p`-th percentile (0 to 100) of `sorted`, interpolating between the two
+// nearest values
+pub fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    let last = sorted.len().checked_sub(1)?;
+    let rank = p.clamp(0.0, 100.0) / 100.0 * last as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64))
+}
+
+// Share of `count` in `total` in percent, 0 for an empty run
+pub fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
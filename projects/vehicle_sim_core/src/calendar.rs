@@ -0,0 +1,256 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+// Meteorological seasons for the northern hemisphere
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Season {
+    Winter,
+    Spring,
+    Summer,
+    Autumn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Date> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Date { year, month, day })
+    }
+
+    // Parses the "YYYY-MM-DD" format produced by Display
+    pub fn parse(value: &str) -> Option<Date> {
+        let mut parts = value.trim().splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+        Date::new(year, month, day)
+    }
+
+    // Days since 1970-01-01 (proleptic Gregorian calendar)
+    fn to_days(self) -> i64 {
+        let year = if self.month <= 2 { self.year - 1 } else { self.year } as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    fn from_days(days: i64) -> Date {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (year_of_era + era * 400) as i32 + if month <= 2 { 1 } else { 0 };
+        Date { year, month, day }
+    }
+
+    pub fn add_days(self, days: i64) -> Date {
+        Date::from_days(self.to_days() + days)
+    }
+
+    // February 29th falls back to the 28th in non-leap years
+    pub fn add_years(self, years: i32) -> Date {
+        let year = self.year + years;
+        Date {
+            year,
+            month: self.month,
+            day: self.day.min(days_in_month(year, self.month)),
+        }
+    }
+
+    pub fn days_until(self, other: Date) -> i64 {
+        other.to_days() - self.to_days()
+    }
+
+    pub fn day_of_year(self) -> u32 {
+        (Date { year: self.year, month: 1, day: 1 }.days_until(self) + 1) as u32
+    }
+
+    pub fn weekday(self) -> Weekday {
+        // 1970-01-01 was a Thursday
+        match (self.to_days() + 3).rem_euclid(7) {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+
+    pub fn season(self) -> Season {
+        match self.month {
+            12 | 1 | 2 => Season::Winter,
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            _ => Season::Autumn,
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+// Simulated wall calendar, advanced by the same amount of simulated time
+// as the rest of the simulation so accelerated runs stay consistent.
+#[derive(Serialize, Deserialize)]
+pub struct Calendar {
+    start: Date,
+    elapsed_hours: f64,
+}
+
+impl Calendar {
+    pub fn new(start: Date) -> Self {
+        Calendar {
+            start,
+            elapsed_hours: 0.0,
+        }
+    }
+
+    pub fn advance(&mut self, hours: f64) {
+        self.elapsed_hours += hours;
+    }
+
+    pub fn date(&self) -> Date {
+        self.start.add_days((self.elapsed_hours / 24.0).floor() as i64)
+    }
+
+    pub fn hour_of_day(&self) -> f64 {
+        self.elapsed_hours.rem_euclid(24.0)
+    }
+
+    pub fn timestamp(&self) -> String {
+        let hour = self.hour_of_day();
+        format!("{} {:02}:{:02}", self.date(), hour.floor() as u32, ((hour.fract() * 60.0).round() as u32).min(59))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReminderStatus {
+    NotDue,
+    Upcoming(i64),
+    Due,
+    Overdue(i64),
+}
+
+// A yearly date-based event such as the annual vehicle inspection
+#[derive(Serialize, Deserialize)]
+pub struct AnnualReminder {
+    pub name: String,
+    pub due: Date,
+    pub notice_days: i64,
+}
+
+impl AnnualReminder {
+    pub fn new(name: &str, last_done: Date, notice_days: i64) -> Self {
+        AnnualReminder {
+            name: name.to_string(),
+            due: last_done.add_years(1),
+            notice_days,
+        }
+    }
+
+    pub fn status(&self, today: Date) -> ReminderStatus {
+        match today.days_until(self.due) {
+            0 => ReminderStatus::Due,
+            days if days < 0 => ReminderStatus::Overdue(-days),
+            days if days <= self.notice_days => ReminderStatus::Upcoming(days),
+            _ => ReminderStatus::NotDue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> Date {
+        Date::parse(value).unwrap()
+    }
+
+    #[test]
+    fn dates_are_validated_and_printed() {
+        assert_eq!(Date::new(2024, 2, 29), Some(Date { year: 2024, month: 2, day: 29 }));
+        assert_eq!(Date::new(2023, 2, 29), None);
+        assert_eq!(Date::new(1900, 2, 29), None);
+        assert_eq!(Date::new(2000, 2, 29).map(|date| date.to_string()), Some("2000-02-29".to_string()));
+        assert_eq!(Date::parse("2026-13-01"), None);
+        assert_eq!(Date::parse("2026-10"), None);
+    }
+
+    #[test]
+    fn date_arithmetic_crosses_months_and_years() {
+        assert_eq!(date("2026-12-31").add_days(1), date("2027-01-01"));
+        assert_eq!(date("2024-03-01").add_days(-1), date("2024-02-29"));
+        assert_eq!(date("1970-01-01").add_days(-1), date("1969-12-31"));
+        assert_eq!(date("2024-02-29").add_years(1), date("2025-02-28"));
+        assert_eq!(date("2024-01-01").days_until(date("2025-01-01")), 366);
+        assert_eq!(date("2024-12-31").day_of_year(), 366);
+        assert_eq!(date("2026-10-16").weekday(), Weekday::Friday);
+        assert_eq!(date("2000-01-01").weekday(), Weekday::Saturday);
+        assert_eq!(date("2026-03-01").season(), Season::Spring);
+        assert_eq!(date("2026-12-01").season(), Season::Winter);
+    }
+
+    #[test]
+    fn the_calendar_follows_simulated_hours() {
+        let mut calendar = Calendar::new(date("2026-10-16"));
+        calendar.advance(23.5);
+        assert_eq!(calendar.timestamp(), "2026-10-16 23:30");
+        calendar.advance(1.0);
+        assert_eq!(calendar.date(), date("2026-10-17"));
+        assert_eq!(calendar.hour_of_day(), 0.5);
+    }
+
+    #[test]
+    fn reminders_come_due_a_year_after_they_were_done() {
+        let inspection = AnnualReminder::new("inspection", date("2025-10-20"), 30);
+        assert_eq!(inspection.status(date("2026-08-01")), ReminderStatus::NotDue);
+        assert_eq!(inspection.status(date("2026-10-16")), ReminderStatus::Upcoming(4));
+        assert_eq!(inspection.status(date("2026-10-20")), ReminderStatus::Due);
+        assert_eq!(inspection.status(date("2026-10-23")), ReminderStatus::Overdue(3));
+    }
+}
@@ -0,0 +1,279 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::config::Config;
+
+pub const CALIBRATION_ENV_VAR: &str = "SIM_CALIBRATION";
+
+// Calibration files end in `.cal`
+pub const EXTENSION: &str = "cal";
+
+const NAME_KEY: &str = "calibration.name";
+const VERSION_KEY: &str = "calibration.version";
+
+// A versioned set of tunable parameters, kept in a `key = value` file like
+// the config. `calibration.name` and `calibration.version` identify it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Calibration {
+    pub name: String,
+    pub version: String,
+    parameters: BTreeMap<String, String>,
+}
+
+impl Calibration {
+    pub fn parse(text: &str) -> Result<Calibration, String> {
+        let config = Config::parse(text)?;
+        let name = config.get(NAME_KEY).ok_or("missing calibration.name")?.to_string();
+        let version = config.get(VERSION_KEY).ok_or("missing calibration.version")?.to_string();
+        let parameters = config
+            .entries()
+            .filter(|(key, _)| *key != NAME_KEY && *key != VERSION_KEY)
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Ok(Calibration {
+            name,
+            version,
+            parameters,
+        })
+    }
+
+    pub fn load(path: &Path) -> io::Result<Calibration> {
+        let text = fs::read_to_string(path)?;
+        Calibration::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.parameters.get(key).map(String::as_str)
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(|value| value.parse().ok())
+    }
+
+    pub fn parameters(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.parameters.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+impl fmt::Display for Calibration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+// Dotted versions compare part by part, numerically where both parts are
+// numbers: 1.10 is newer than 1.9
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+    loop {
+        let ordering = match (a_parts.next(), b_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+// A parameter that differs between two calibrations
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CalibrationChange {
+    pub key: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl fmt::Display for CalibrationChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "~ {}: {} -> {}", self.key, old, new),
+            (None, Some(new)) => write!(f, "+ {} = {}", self.key, new),
+            (Some(old), None) => write!(f, "- {} = {}", self.key, old),
+            (None, None) => write!(f, "  {}", self.key),
+        }
+    }
+}
+
+pub fn diff(old: &Calibration, new: &Calibration) -> Vec<CalibrationChange> {
+    let mut keys: Vec<&String> = old.parameters.keys().chain(new.parameters.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| old.get(key) != new.get(key))
+        .map(|key| CalibrationChange {
+            key: key.clone(),
+            old: old.get(key).map(str::to_string),
+            new: new.get(key).map(str::to_string),
+        })
+        .collect()
+}
+
+// Every version of a calibration available to a run, one of them active.
+// The newest version is active at startup.
+#[derive(Debug, Clone)]
+pub struct CalibrationSet {
+    versions: Vec<Calibration>,
+    active: usize,
+}
+
+impl CalibrationSet {
+    // `path` is a single calibration file or a directory of `.cal` files,
+    // all versions of the same calibration
+    pub fn load(path: &Path) -> io::Result<CalibrationSet> {
+        let mut versions = Vec::new();
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let file = entry?.path();
+                if file.extension().and_then(|extension| extension.to_str()) == Some(EXTENSION) {
+                    versions.push(Calibration::load(&file)?);
+                }
+            }
+        } else {
+            versions.push(Calibration::load(path)?);
+        }
+
+        if versions.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no .{} files in {}", EXTENSION, path.display()),
+            ));
+        }
+        if let Some(other) = versions.iter().find(|calibration| calibration.name != versions[0].name) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} mixes calibrations {} and {}", path.display(), versions[0].name, other.name),
+            ));
+        }
+        versions.sort_by(|a, b| compare_versions(&a.version, &b.version));
+        versions.dedup_by(|a, b| a.version == b.version);
+
+        let active = versions.len() - 1;
+        Ok(CalibrationSet { versions, active })
+    }
+
+    pub fn active(&self) -> &Calibration {
+        &self.versions[self.active]
+    }
+
+    pub fn versions(&self) -> &[Calibration] {
+        &self.versions
+    }
+
+    pub fn version(&self, version: &str) -> Option<&Calibration> {
+        self.versions.iter().find(|calibration| calibration.version == version)
+    }
+
+    pub fn activate(&mut self, version: &str) -> Result<&Calibration, String> {
+        let index = self
+            .versions
+            .iter()
+            .position(|calibration| calibration.version == version)
+            .ok_or_else(|| format!("no calibration version {}", version))?;
+        self.active = index;
+        Ok(self.active())
+    }
+}
+
+// Uses `--calibration <path>` from the command line, then SIM_CALIBRATION
+pub fn path_from_args() -> Option<PathBuf> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--calibration")
+        .and_then(|i| args.get(i + 1).map(PathBuf::from))
+        .or_else(|| env::var(CALIBRATION_ENV_VAR).ok().map(PathBuf::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calibration(name: &str, version: &str, parameters: &str) -> String {
+        format!("calibration.name = {}\ncalibration.version = {}\n{}", name, version, parameters)
+    }
+
+    // A fresh directory holding the given files
+    fn directory(name: &str, files: &[(&str, String)]) -> PathBuf {
+        let directory = env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for (file, text) in files {
+            fs::write(directory.join(file), text).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn parses_the_identity_and_parameters() {
+        let calibration = Calibration::parse(&calibration("tpms", "1.2", "low_pressure_ratio = 0.9")).unwrap();
+        assert_eq!(calibration.to_string(), "tpms 1.2");
+        assert_eq!(calibration.get_f64("low_pressure_ratio"), Some(0.9));
+        assert_eq!(calibration.parameters().count(), 1);
+        assert_eq!(Calibration::parse("calibration.version = 1").unwrap_err(), "missing calibration.name");
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("2.0", "2.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn diff_lists_changed_added_and_removed_parameters() {
+        let old = Calibration::parse(&calibration("tpms", "1.0", "a = 1\nb = 2\nc = 3")).unwrap();
+        let new = Calibration::parse(&calibration("tpms", "1.1", "a = 1\nb = 5\nd = 4")).unwrap();
+        let changes: Vec<String> = diff(&old, &new).iter().map(CalibrationChange::to_string).collect();
+        assert_eq!(changes, vec!["~ b: 2 -> 5", "- c = 3", "+ d = 4"]);
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn a_directory_loads_every_version_the_newest_active() {
+        let directory = directory(
+            "calibration_set",
+            &[
+                ("a.cal", calibration("tpms", "1.9", "x = 1")),
+                ("b.cal", calibration("tpms", "1.10", "x = 2")),
+                ("notes.txt", "not a calibration".to_string()),
+            ],
+        );
+        let mut set = CalibrationSet::load(&directory).unwrap();
+        let versions: Vec<&str> = set.versions().iter().map(|calibration| calibration.version.as_str()).collect();
+        assert_eq!(versions, vec!["1.9", "1.10"]);
+        assert_eq!(set.active().version, "1.10");
+        assert_eq!(set.activate("1.9").unwrap().get("x"), Some("1"));
+        assert!(set.activate("3.0").is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn mixed_or_missing_calibrations_are_rejected() {
+        let mixed = directory(
+            "calibration_mixed",
+            &[("a.cal", calibration("tpms", "1.0", "")), ("b.cal", calibration("climate", "1.0", ""))],
+        );
+        let error = CalibrationSet::load(&mixed).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("mixes calibrations"));
+        fs::remove_dir_all(&mixed).unwrap();
+
+        let empty = directory("calibration_empty", &[]);
+        assert_eq!(CalibrationSet::load(&empty).unwrap_err().kind(), io::ErrorKind::NotFound);
+        fs::remove_dir_all(&empty).unwrap();
+    }
+}
@@ -0,0 +1,385 @@
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::can_trace::CanTrace;
+use crate::config::LogLevel;
+use crate::signal_store::{SignalSnapshot, SignalStore};
+use crate::sim_log;
+
+// A classic CAN data frame with an 11-bit identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanFrame {
+    pub id: u32,
+    pub dlc: u8,
+    pub data: [u8; 8],
+}
+
+impl CanFrame {
+    pub fn new(id: u32, payload: &[u8]) -> CanFrame {
+        let dlc = payload.len().min(8);
+        let mut data = [0; 8];
+        data[..dlc].copy_from_slice(&payload[..dlc]);
+        CanFrame {
+            id,
+            dlc: dlc as u8,
+            data,
+        }
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.data[..self.dlc as usize]
+    }
+}
+
+impl fmt::Display for CanFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X} [{}]", self.id, self.dlc)?;
+        for byte in self.payload() {
+            write!(f, " {:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+// Acceptance filter like SocketCAN's: a frame passes when its identifier
+// matches `id` in every bit set in `mask`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanFilter {
+    pub id: u32,
+    pub mask: u32,
+}
+
+impl CanFilter {
+    pub fn all() -> Self {
+        CanFilter { id: 0, mask: 0 }
+    }
+
+    pub fn id(id: u32) -> Self {
+        CanFilter { id, mask: 0x7FF }
+    }
+
+    pub fn matches(&self, frame: &CanFrame) -> bool {
+        frame.id & self.mask == self.id & self.mask
+    }
+}
+
+// A signal packed into a message payload, least significant bit first
+// (Intel byte order). Physical value = raw * factor + offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Signal {
+    pub name: &'static str,
+    pub start_bit: u8,
+    pub length: u8,
+    pub factor: f64,
+    pub offset: f64,
+    pub unit: &'static str,
+}
+
+impl Signal {
+    fn mask(&self) -> u64 {
+        if self.length >= 64 {
+            u64::MAX
+        } else {
+            (1 << self.length) - 1
+        }
+    }
+
+    // Smallest and largest physical value the signal can carry
+    pub fn range(&self) -> (f64, f64) {
+        (self.offset, self.mask() as f64 * self.factor + self.offset)
+    }
+
+    // Values outside the signal's range saturate at its limits
+    fn encode(&self, value: f64, payload: &mut u64) {
+        let raw = ((value - self.offset) / self.factor).round();
+        let raw = if raw.is_nan() { 0 } else { raw.clamp(0.0, self.mask() as f64) as u64 };
+        *payload &= !(self.mask() << self.start_bit);
+        *payload |= raw << self.start_bit;
+    }
+
+    fn decode(&self, payload: u64) -> f64 {
+        ((payload >> self.start_bit) & self.mask()) as f64 * self.factor + self.offset
+    }
+}
+
+// A periodic message of the matrix and the node that transmits it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Message {
+    pub id: u32,
+    pub name: &'static str,
+    pub transmitter: &'static str,
+    pub dlc: u8,
+    pub signals: &'static [Signal],
+}
+
+impl Message {
+    // Signals missing from `values` are sent as raw zero
+    pub fn encode(&self, values: &[(&str, f64)]) -> CanFrame {
+        let mut payload = 0;
+        for (name, value) in values {
+            if let Some(signal) = self.signal(name) {
+                signal.encode(*value, &mut payload);
+            }
+        }
+        CanFrame::new(self.id, &payload.to_le_bytes()[..self.dlc as usize])
+    }
+
+    pub fn decode(&self, frame: &CanFrame) -> Vec<(&'static str, f64)> {
+        let mut bytes = [0; 8];
+        bytes[..frame.payload().len()].copy_from_slice(frame.payload());
+        let payload = u64::from_le_bytes(bytes);
+        self.signals.iter().map(|signal| (signal.name, signal.decode(payload))).collect()
+    }
+
+    pub fn signal(&self, name: &str) -> Option<&'static Signal> {
+        self.signals.iter().find(|signal| signal.name == name)
+    }
+}
+
+const fn signal(name: &'static str, start_bit: u8, length: u8, factor: f64, offset: f64, unit: &'static str) -> Signal {
+    Signal {
+        name,
+        start_bit,
+        length,
+        factor,
+        offset,
+        unit,
+    }
+}
+
+pub const TPMS_PRESSURES: Message = Message {
+    id: 0x3A0,
+    name: "TPMS_Pressures",
+    transmitter: "TPMS",
+    dlc: 6,
+    // In the order of the tire layout, unused positions stay zero
+    signals: &[
+        signal("Pressure_0", 0, 8, 0.25, 0.0, "psi"),
+        signal("Pressure_1", 8, 8, 0.25, 0.0, "psi"),
+        signal("Pressure_2", 16, 8, 0.25, 0.0, "psi"),
+        signal("Pressure_3", 24, 8, 0.25, 0.0, "psi"),
+        signal("Pressure_4", 32, 8, 0.25, 0.0, "psi"),
+        signal("Pressure_5", 40, 8, 0.25, 0.0, "psi"),
+    ],
+};
+
+pub const TPMS_STATUS: Message = Message {
+    id: 0x3A1,
+    name: "TPMS_Status",
+    transmitter: "TPMS",
+    dlc: 1,
+    signals: &[
+        signal("WarningActive", 0, 1, 1.0, 0.0, ""),
+        signal("ActiveDtcs", 1, 7, 1.0, 0.0, ""),
+    ],
+};
+
+pub const CLIMATE_STATUS: Message = Message {
+    id: 0x3B0,
+    name: "Climate_Status",
+    transmitter: "Climate",
+    dlc: 8,
+    signals: &[
+        signal("DriverZoneTemperature", 0, 16, 0.1, -40.0, "°C"),
+        signal("PassengerZoneTemperature", 16, 16, 0.1, -40.0, "°C"),
+        signal("RearZoneTemperature", 32, 16, 0.1, -40.0, "°C"),
+        signal("ExternalTemperature", 48, 8, 0.5, -40.0, "°C"),
+        signal("DefogActive", 56, 1, 1.0, 0.0, ""),
+        signal("ZoneSync", 57, 1, 1.0, 0.0, ""),
+    ],
+};
+
+pub const CLUSTER_ODOMETER: Message = Message {
+    id: 0x3C0,
+    name: "Cluster_Odometer",
+    transmitter: "Cluster",
+    dlc: 8,
+    signals: &[
+        signal("Odometer", 0, 24, 0.1, 0.0, "km"),
+        signal("TripMeter", 24, 16, 0.1, 0.0, "km"),
+        signal("FuelConsumed", 40, 24, 0.01, 0.0, "l"),
+    ],
+};
+
+pub const ROAD_CONDITION: Message = Message {
+    id: 0x3D0,
+    name: "Road_Condition",
+    transmitter: "Chassis",
+    dlc: 8,
+    signals: &[
+        signal("VehicleSpeed", 0, 16, 0.01, 0.0, "km/h"),
+        // 0 = dry, 1 = wet, 2 = icy
+        signal("RoadCondition", 16, 2, 1.0, 0.0, ""),
+        signal("AbsActive", 18, 1, 1.0, 0.0, ""),
+        signal("Traction", 24, 8, 0.005, 0.0, ""),
+        signal("AmbientTemperature", 32, 8, 0.5, -40.0, "°C"),
+        signal("StoppingDistance", 40, 16, 0.1, 0.0, "m"),
+    ],
+};
+
+pub const VEHICLE_MODE: Message = Message {
+    id: 0x100,
+    name: "Vehicle_Mode",
+    transmitter: "Gateway",
+    dlc: 1,
+    signals: &[
+        // 0 = parked, 1 = driving, 2 = charging, 3 = service
+        signal("VehicleMode", 0, 4, 1.0, 0.0, ""),
+    ],
+};
+
+// Every message on the vehicle bus
+pub const MATRIX: &[Message] = &[VEHICLE_MODE, TPMS_PRESSURES, TPMS_STATUS, CLIMATE_STATUS, CLUSTER_ODOMETER, ROAD_CONDITION];
+
+pub fn message(id: u32) -> Option<&'static Message> {
+    MATRIX.iter().find(|message| message.id == id)
+}
+
+// In-process CAN bus: every frame sent reaches each subscriber whose filter
+// accepts it. Frames are logged decoded at debug level and written to the
+// trace file, if any. The signals of the matrix messages are decoded once,
+// into a store whose snapshots readers share, rather than by every reader
+// from its own copy of the frames.
+#[derive(Default)]
+pub struct CanBus {
+    subscribers: Mutex<Vec<(CanFilter, Sender<CanFrame>)>>,
+    trace: Mutex<Option<CanTrace>>,
+    signals: Arc<SignalStore>,
+}
+
+impl CanBus {
+    pub fn new() -> Self {
+        CanBus::default()
+    }
+
+    pub fn subscribe(&self, filter: CanFilter) -> Receiver<CanFrame> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push((filter, sender));
+        receiver
+    }
+
+    pub fn send(&self, frame: CanFrame) {
+        if sim_log::enabled(LogLevel::Debug) {
+            sim_log::debug("can", &describe(&frame));
+        }
+
+        let mut trace = self.trace.lock().unwrap();
+        if let Some(Err(e)) = trace.as_mut().map(|trace| trace.write(&frame)) {
+            sim_log::warn("can", &format!("Stopped writing the CAN trace: {}", e));
+            *trace = None;
+        }
+        drop(trace);
+
+        if let Some(message) = message(frame.id) {
+            for (name, value) in message.decode(&frame) {
+                self.signals.write(name, value);
+            }
+        }
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|(filter, subscriber)| !filter.matches(&frame) || subscriber.send(frame).is_ok());
+    }
+
+    // Publishes the signals sent during the step to the snapshot readers;
+    // call once at the end of every step
+    pub fn end_tick(&self) {
+        self.signals.commit();
+    }
+
+    // Signal values as of the last tick
+    pub fn snapshot(&self) -> Arc<SignalSnapshot> {
+        self.signals.snapshot()
+    }
+
+    // For readers that outlive a borrow of the bus
+    pub fn signals(&self) -> Arc<SignalStore> {
+        Arc::clone(&self.signals)
+    }
+
+    pub fn record_to(&self, trace: CanTrace) {
+        *self.trace.lock().unwrap() = Some(trace);
+    }
+
+    // Encodes and sends a message of the matrix
+    pub fn transmit(&self, message: &Message, values: &[(&str, f64)]) {
+        self.send(message.encode(values));
+    }
+}
+
+// `3A1 [1] 01 (TPMS_Status: WarningActive=1, ActiveDtcs=0)`
+pub fn describe(frame: &CanFrame) -> String {
+    let Some(message) = message(frame.id) else {
+        return frame.to_string();
+    };
+    let signals: Vec<String> = message
+        .decode(frame)
+        .into_iter()
+        .map(|(name, value)| {
+            let unit = message.signal(name).map_or("", |signal| signal.unit);
+            format!("{}={}{}", name, (value * 1000.0).round() / 1000.0, unit)
+        })
+        .collect();
+    format!("{} ({}: {})", frame, message.name, signals.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signals_survive_encoding_within_their_resolution() {
+        let values = [("Pressure_0", 32.4), ("Pressure_1", 28.0)];
+        let frame = TPMS_PRESSURES.encode(&values);
+        assert_eq!(frame.id, TPMS_PRESSURES.id);
+        assert_eq!(frame.dlc, TPMS_PRESSURES.dlc);
+        let decoded = TPMS_PRESSURES.decode(&frame);
+        for (name, value) in values {
+            let signal = TPMS_PRESSURES.signal(name).unwrap();
+            let (_, back) = decoded.iter().find(|(decoded, _)| *decoded == name).unwrap();
+            assert!((back - value).abs() <= signal.factor / 2.0 + 1e-9, "{} came back as {}", name, back);
+        }
+    }
+
+    #[test]
+    fn values_outside_the_range_saturate() {
+        let signal = TPMS_PRESSURES.signals[0];
+        let (min, max) = signal.range();
+        let decode = |value: f64| {
+            let frame = TPMS_PRESSURES.encode(&[(signal.name, value)]);
+            TPMS_PRESSURES.decode(&frame)[0].1
+        };
+        assert_eq!(decode(max + 1000.0), max);
+        assert_eq!(decode(min - 1000.0), min);
+        assert_eq!(decode(f64::NAN), min);
+    }
+
+    #[test]
+    fn subscribers_get_the_frames_their_filter_accepts() {
+        let bus = CanBus::new();
+        let all = bus.subscribe(CanFilter::all());
+        let one = bus.subscribe(CanFilter::id(0x123));
+        bus.send(CanFrame::new(0x123, &[1]));
+        bus.send(CanFrame::new(0x456, &[2]));
+        assert_eq!(all.try_iter().count(), 2);
+        assert_eq!(one.try_iter().map(|frame| frame.id).collect::<Vec<_>>(), vec![0x123]);
+
+        // A dropped receiver is forgotten
+        drop(one);
+        bus.send(CanFrame::new(0x123, &[3]));
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn matrix_signals_reach_the_snapshot_at_the_end_of_the_tick() {
+        let bus = CanBus::new();
+        bus.transmit(&TPMS_PRESSURES, &[("Pressure_0", 30.0)]);
+        assert_eq!(bus.snapshot().get("Pressure_0"), None);
+        bus.end_tick();
+        assert!((bus.snapshot().get("Pressure_0").unwrap() - 30.0).abs() < 0.1);
+        assert_eq!(message(TPMS_PRESSURES.id).map(|message| message.name), Some(TPMS_PRESSURES.name));
+        assert!(describe(&CanFrame::new(TPMS_PRESSURES.id, &[0; 8])).contains(TPMS_PRESSURES.name));
+        assert_eq!(describe(&CanFrame::new(0x7FF, &[0xAB])), "7FF [1] AB");
+    }
+}
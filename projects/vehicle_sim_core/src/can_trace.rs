@@ -0,0 +1,164 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::calendar::Date;
+use crate::clock;
+use crate::can_bus::CanFrame;
+
+pub const CAN_TRACE_ENV_VAR: &str = "SIM_CAN_TRACE";
+
+// Interface name written to candump logs
+pub const CHANNEL: &str = "vcan0";
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    // `candump -l` log files, replayable with canplayer
+    Candump,
+    // Vector ASCII logging format
+    Asc,
+}
+
+impl TraceFormat {
+    // `.asc` files are written as Vector ASC, anything else as a candump log
+    pub fn for_path(path: &Path) -> TraceFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("asc") => TraceFormat::Asc,
+            _ => TraceFormat::Candump,
+        }
+    }
+}
+
+// Writes every frame sent on a bus to a trace file; attach it with
+// `CanBus::record_to`. Frames are stamped with the simulated time of
+// the run, so runs faster than real time replay at their simulated pace.
+pub struct CanTrace {
+    format: TraceFormat,
+    writer: BufWriter<File>,
+    started: f64,
+}
+
+impl CanTrace {
+    pub fn create(path: &Path) -> io::Result<CanTrace> {
+        let mut trace = CanTrace {
+            format: TraceFormat::for_path(path),
+            writer: BufWriter::new(File::create(path)?),
+            started: unix_time(),
+        };
+        if trace.format == TraceFormat::Asc {
+            let date = asc_date(trace.started);
+            writeln!(trace.writer, "date {}", date)?;
+            writeln!(trace.writer, "base hex  timestamps absolute")?;
+            writeln!(trace.writer, "no internal events logged")?;
+            writeln!(trace.writer, "Begin Triggerblock {}", date)?;
+            writeln!(trace.writer, "   0.000000 Start of measurement")?;
+        }
+        Ok(trace)
+    }
+
+    pub fn write(&mut self, frame: &CanFrame) -> io::Result<()> {
+        let elapsed = clock::now();
+        match self.format {
+            TraceFormat::Candump => {
+                let data: String = frame.payload().iter().map(|byte| format!("{:02X}", byte)).collect();
+                writeln!(self.writer, "({:.6}) {} {:03X}#{}", self.started + elapsed, CHANNEL, frame.id, data)?;
+            }
+            TraceFormat::Asc => {
+                let data: Vec<String> = frame.payload().iter().map(|byte| format!("{:02X}", byte)).collect();
+                writeln!(
+                    self.writer,
+                    "{:>11.6} 1  {:<15} Rx   d {} {}",
+                    elapsed,
+                    format!("{:X}", frame.id),
+                    frame.dlc,
+                    data.join(" ")
+                )?;
+            }
+        }
+        // Keep the trace readable while the simulation is still running
+        self.writer.flush()
+    }
+}
+
+impl Drop for CanTrace {
+    fn drop(&mut self) {
+        if self.format == TraceFormat::Asc {
+            let _ = writeln!(self.writer, "End TriggerBlock");
+            let _ = self.writer.flush();
+        }
+    }
+}
+
+// Uses `--can-trace <path>` from the command line, then SIM_CAN_TRACE
+pub fn path_from_args() -> Option<PathBuf> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--can-trace")
+        .and_then(|i| args.get(i + 1).map(PathBuf::from))
+        .or_else(|| env::var(CAN_TRACE_ENV_VAR).ok().map(PathBuf::from))
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64())
+}
+
+// `Thu Oct 16 09:15:30.123 am 2026`, in UTC
+fn asc_date(unix_time: f64) -> String {
+    let days = (unix_time / 86_400.0).floor();
+    let date = Date::new(1970, 1, 1).unwrap().add_days(days as i64);
+    let seconds = unix_time - days * 86_400.0;
+    let hour = (seconds / 3600.0) as u32;
+    let (hour_12, meridiem) = match hour {
+        0 => (12, "am"),
+        1..=11 => (hour, "am"),
+        12 => (12, "pm"),
+        _ => (hour - 12, "pm"),
+    };
+
+    format!(
+        "{} {} {:02} {:02}:{:02}:{:06.3} {} {}",
+        &format!("{:?}", date.weekday())[..3],
+        MONTHS[date.month as usize - 1],
+        date.day,
+        hour_12,
+        (seconds / 60.0) as u32 % 60,
+        seconds % 60.0,
+        meridiem,
+        date.year
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asc_files_get_the_vector_format() {
+        assert_eq!(TraceFormat::for_path(Path::new("run.ASC")), TraceFormat::Asc);
+        assert_eq!(TraceFormat::for_path(Path::new("run.log")), TraceFormat::Candump);
+        assert_eq!(TraceFormat::for_path(Path::new("asc")), TraceFormat::Candump);
+    }
+
+    #[test]
+    fn asc_dates_use_the_twelve_hour_clock() {
+        assert_eq!(asc_date(0.0), "Thu Jan 01 12:00:00.000 am 1970");
+        // 2026-10-16 13:05:07.25 UTC
+        assert_eq!(asc_date(1_792_155_907.25), "Fri Oct 16 01:05:07.250 pm 2026");
+    }
+
+    #[test]
+    fn candump_lines_carry_the_channel_identifier_and_data() {
+        let path = env::temp_dir().join(format!("sim_can_trace_{}.log", std::process::id()));
+        let mut trace = CanTrace::create(&path).unwrap();
+        trace.write(&CanFrame::new(0x123, &[0xDE, 0xAD])).unwrap();
+        drop(trace);
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.trim_end().ends_with(") vcan0 123#DEAD"), "{}", text);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
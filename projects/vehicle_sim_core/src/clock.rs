@@ -0,0 +1,138 @@
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::sim_log;
+
+pub const REALTIME_FACTOR_ENV_VAR: &str = "SIM_REALTIME_FACTOR";
+
+// Simulated seconds of the running simulation, as f64 bits
+static SIMULATED_SECONDS: AtomicU64 = AtomicU64::new(0);
+
+// Simulated time of the run, decoupled from the wall clock. The real-time
+// factor is simulated seconds per wall-clock second: 1 follows the wall
+// clock, 60 runs a minute per second and 0 runs as fast as possible.
+#[derive(Debug, Clone, Copy)]
+pub struct SimClock {
+    realtime_factor: f64,
+    simulated: f64,
+    // Where the wall clock was anchored, and the simulated time at that point
+    anchor: Instant,
+    anchor_simulated: f64,
+}
+
+impl SimClock {
+    pub fn new(realtime_factor: f64) -> Self {
+        SimClock {
+            realtime_factor: realtime_factor.max(0.0),
+            simulated: 0.0,
+            anchor: Instant::now(),
+            anchor_simulated: 0.0,
+        }
+    }
+
+    pub fn as_fast_as_possible() -> Self {
+        SimClock::new(0.0)
+    }
+
+    // Continues from `seconds` of simulated time, e.g. after a resume
+    pub fn starting_at(mut self, seconds: f64) -> Self {
+        self.simulated = seconds;
+        self.anchor = Instant::now();
+        self.anchor_simulated = seconds;
+        publish(seconds);
+        self
+    }
+
+    pub fn realtime_factor(&self) -> f64 {
+        self.realtime_factor
+    }
+
+    pub fn is_realtime(&self) -> bool {
+        self.realtime_factor > 0.0
+    }
+
+    pub fn now(&self) -> f64 {
+        self.simulated
+    }
+
+    pub fn advance(&mut self, dt: f64) {
+        self.simulated += dt;
+        publish(self.simulated);
+    }
+
+    // Wall-clock instant the simulated time is due at; `None` when running
+    // as fast as possible. Measured from the anchor rather than per step, so
+    // time spent stepping does not add up as drift.
+    pub fn deadline(&self) -> Option<Instant> {
+        if !self.is_realtime() {
+            return None;
+        }
+        let wall_seconds = (self.simulated - self.anchor_simulated) / self.realtime_factor;
+        Some(self.anchor + Duration::from_secs_f64(wall_seconds.max(0.0)))
+    }
+}
+
+fn publish(seconds: f64) {
+    SIMULATED_SECONDS.store(seconds.to_bits(), Ordering::Relaxed);
+}
+
+// Simulated seconds of the current run, for modules that timestamp records
+// without access to the simulation (0 before a run starts)
+pub fn now() -> f64 {
+    f64::from_bits(SIMULATED_SECONDS.load(Ordering::Relaxed))
+}
+
+// Uses `--realtime-factor <factor>` from the command line, then
+// SIM_REALTIME_FACTOR
+pub fn realtime_factor_from_args() -> Option<f64> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--realtime-factor")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| env::var(REALTIME_FACTOR_ENV_VAR).ok())
+        .and_then(|value| match parse_realtime_factor(&value) {
+            Ok(factor) => Some(factor),
+            Err(e) => {
+                sim_log::warn("clock", &e);
+                None
+            }
+        })
+}
+
+pub fn parse_realtime_factor(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor >= 0.0 => Ok(factor),
+        _ => Err(format!("invalid real-time factor '{}', expected a number >= 0 (0 = as fast as possible)", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadlines_follow_the_realtime_factor_from_the_anchor() {
+        let mut clock = SimClock::as_fast_as_possible();
+        clock.advance(10.0);
+        assert_eq!(clock.now(), 10.0);
+        assert_eq!(clock.deadline(), None);
+
+        let mut clock = SimClock::new(2.0).starting_at(100.0);
+        let anchor = clock.anchor;
+        clock.advance(4.0);
+        assert_eq!(clock.now(), 104.0);
+        assert_eq!(clock.deadline(), Some(anchor + Duration::from_secs(2)));
+        assert!(!SimClock::new(-1.0).is_realtime());
+    }
+
+    #[test]
+    fn realtime_factors_are_finite_and_not_negative() {
+        assert_eq!(parse_realtime_factor(" 60 "), Ok(60.0));
+        assert_eq!(parse_realtime_factor("0"), Ok(0.0));
+        assert!(parse_realtime_factor("-1").is_err());
+        assert!(parse_realtime_factor("inf").is_err());
+        assert!(parse_realtime_factor("fast").is_err());
+    }
+}
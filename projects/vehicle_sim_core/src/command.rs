@@ -0,0 +1,198 @@
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// A runtime change to a simulation (fault injection, setpoint change, ...)
+// that knows how to revert itself
+pub trait Command<T> {
+    fn apply(&mut self, target: &mut T);
+    fn undo(&mut self, target: &mut T);
+    // The command line that recreates this command, used for scenarios
+    fn describe(&self) -> String;
+}
+
+struct Entry<T> {
+    step: u64,
+    command: Box<dyn Command<T>>,
+}
+
+// Executes commands and keeps the history needed for undo/redo
+pub struct CommandBus<T> {
+    done: Vec<Entry<T>>,
+    undone: Vec<Entry<T>>,
+}
+
+impl<T> Default for CommandBus<T> {
+    fn default() -> Self {
+        CommandBus::new()
+    }
+}
+
+impl<T> CommandBus<T> {
+    pub fn new() -> Self {
+        CommandBus {
+            done: Vec::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    // Running a new command discards anything that could still be redone
+    pub fn execute(&mut self, step: u64, mut command: Box<dyn Command<T>>, target: &mut T) {
+        command.apply(target);
+        self.done.push(Entry { step, command });
+        self.undone.clear();
+    }
+
+    pub fn undo(&mut self, target: &mut T) -> Option<String> {
+        let mut entry = self.done.pop()?;
+        entry.command.undo(target);
+        let description = entry.command.describe();
+        self.undone.push(entry);
+        Some(description)
+    }
+
+    pub fn redo(&mut self, target: &mut T) -> Option<String> {
+        let mut entry = self.undone.pop()?;
+        entry.command.apply(target);
+        let description = entry.command.describe();
+        self.done.push(entry);
+        Some(description)
+    }
+
+    // Commands currently in effect, oldest first, with the step they ran at
+    pub fn history(&self) -> Vec<(u64, String)> {
+        self.done
+            .iter()
+            .map(|entry| (entry.step, entry.command.describe()))
+            .collect()
+    }
+
+    // One `<step> <command>` line per command, replayable with `load_scenario`
+    pub fn save_scenario(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::new();
+        for (step, command) in self.history() {
+            text.push_str(&format!("{} {}\n", step, command));
+        }
+        fs::write(path, text)
+    }
+}
+
+pub fn load_scenario(path: &Path) -> io::Result<Vec<(u64, String)>> {
+    let text = fs::read_to_string(path)?;
+    let mut commands = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parsed = line
+            .split_once(' ')
+            .and_then(|(step, command)| Some((step.parse().ok()?, command.trim().to_string())));
+        match parsed {
+            Some(entry) => commands.push(entry),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: expected `<step> <command>`", number + 1),
+                ))
+            }
+        }
+    }
+
+    Ok(commands)
+}
+
+// Reads command lines from stdin on a background thread so a running
+// simulation can poll for them without blocking
+pub fn spawn_stdin_reader() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Add(i32);
+
+    impl Command<i32> for Add {
+        fn apply(&mut self, target: &mut i32) {
+            *target += self.0;
+        }
+
+        fn undo(&mut self, target: &mut i32) {
+            *target -= self.0;
+        }
+
+        fn describe(&self) -> String {
+            format!("add {}", self.0)
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_history() {
+        let mut bus = CommandBus::new();
+        let mut value = 0;
+        bus.execute(1, Box::new(Add(2)), &mut value);
+        bus.execute(5, Box::new(Add(3)), &mut value);
+        assert_eq!(value, 5);
+
+        assert_eq!(bus.undo(&mut value).as_deref(), Some("add 3"));
+        assert_eq!(value, 2);
+        assert_eq!(bus.redo(&mut value).as_deref(), Some("add 3"));
+        assert_eq!(value, 5);
+        assert_eq!(bus.history(), vec![(1, "add 2".to_string()), (5, "add 3".to_string())]);
+    }
+
+    #[test]
+    fn a_new_command_clears_what_could_be_redone() {
+        let mut bus = CommandBus::new();
+        let mut value = 0;
+        bus.execute(1, Box::new(Add(2)), &mut value);
+        bus.undo(&mut value);
+        bus.execute(2, Box::new(Add(10)), &mut value);
+        assert_eq!(bus.redo(&mut value), None);
+        assert_eq!(value, 10);
+    }
+
+    #[test]
+    fn undo_and_redo_on_an_empty_history_do_nothing() {
+        let mut bus: CommandBus<i32> = CommandBus::new();
+        let mut value = 7;
+        assert_eq!(bus.undo(&mut value), None);
+        assert_eq!(bus.redo(&mut value), None);
+        assert_eq!(value, 7);
+    }
+
+    #[test]
+    fn saved_scenarios_load_back() {
+        let mut bus = CommandBus::new();
+        let mut value = 0;
+        bus.execute(3, Box::new(Add(2)), &mut value);
+        bus.execute(8, Box::new(Add(-1)), &mut value);
+        let path = std::env::temp_dir().join(format!("command_scenario_{}.txt", std::process::id()));
+        bus.save_scenario(&path).unwrap();
+        assert_eq!(load_scenario(&path).unwrap(), bus.history());
+
+        fs::write(&path, "# comment\n3 add 2\nadd 4\n").unwrap();
+        let error = load_scenario(&path).unwrap_err();
+        assert!(error.to_string().starts_with("line 3:"));
+        fs::remove_file(&path).unwrap();
+    }
+}
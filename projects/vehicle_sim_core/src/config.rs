@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::sim_log;
+
+pub const CONFIG_ENV_VAR: &str = "SIM_CONFIG";
+
+// Simple `key = value` configuration file; `#` starts a comment line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    values: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut values = BTreeMap::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected key = value", number + 1))?;
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+
+        Ok(Config { values })
+    }
+
+    pub fn load(path: &Path) -> io::Result<Config> {
+        let text = fs::read_to_string(path)?;
+        Config::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(|value| value.parse().ok())
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+// How much a simulation prints, from least to most verbose
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub fn parse(value: &str) -> Option<LogLevel> {
+        match value.to_ascii_lowercase().as_str() {
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+}
+
+// A safe-to-change parameter that was edited while the simulation ran
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigUpdate {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+// Watches a config file and hands out edits of the keys listed in
+// `safe_keys`. Any other key is structural: changing it needs a restart.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    current: Config,
+    safe_keys: &'static [&'static str],
+    // Structural values already warned about, so an edit is only reported once
+    rejected: BTreeMap<String, Option<String>>,
+    events: Receiver<notify::Result<Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    pub fn watch(path: &Path, safe_keys: &'static [&'static str]) -> Result<ConfigWatcher, Box<dyn Error>> {
+        let current = Config::load(path)?;
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+
+        // Editors often replace the file instead of writing it in place,
+        // so watch the directory and filter on the file name
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+
+        Ok(ConfigWatcher {
+            path: path.to_path_buf(),
+            current,
+            safe_keys,
+            rejected: BTreeMap::new(),
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.current
+    }
+
+    // Non-blocking; returns the safe parameters that changed since the last call
+    pub fn poll(&mut self) -> Vec<ConfigUpdate> {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            if let Ok(event) = event {
+                changed |= self.concerns_file(&event);
+            }
+        }
+
+        if !changed {
+            return Vec::new();
+        }
+
+        let reloaded = match Config::load(&self.path) {
+            Ok(config) => config,
+            Err(e) => {
+                sim_log::warn(
+                    "config",
+                    &format!("Config reload of {} failed, keeping previous settings: {}", self.path.display(), e),
+                );
+                return Vec::new();
+            }
+        };
+        self.apply(reloaded)
+    }
+
+    // Takes over the safe changes in `reloaded` and warns about the others
+    fn apply(&mut self, reloaded: Config) -> Vec<ConfigUpdate> {
+        let mut keys: Vec<String> = self.current.values.keys().chain(reloaded.values.keys()).cloned().collect();
+        keys.sort();
+        keys.dedup();
+
+        let mut updates = Vec::new();
+        let mut next = self.current.clone();
+        for key in keys {
+            let new = reloaded.get(&key);
+            if self.current.get(&key) == new {
+                self.rejected.remove(&key);
+                continue;
+            }
+
+            if self.safe_keys.contains(&key.as_str()) {
+                match new {
+                    Some(value) => next.values.insert(key.clone(), value.to_string()),
+                    None => next.values.remove(&key),
+                };
+                updates.push(ConfigUpdate {
+                    key: key.clone(),
+                    value: new.map(str::to_string),
+                });
+            } else if self.newly_rejected(&key, new) {
+                sim_log::warn(
+                    "config",
+                    &format!(
+                        "Config change to '{}' in {} rejected: structural parameter, restart the simulation to apply it",
+                        key,
+                        self.path.display()
+                    ),
+                );
+            }
+        }
+
+        self.current = next;
+        updates
+    }
+
+    // Remembers the rejected value; false if it was already reported
+    fn newly_rejected(&mut self, key: &str, value: Option<&str>) -> bool {
+        let value = value.map(str::to_string);
+        if self.rejected.get(key) == Some(&value) {
+            return false;
+        }
+        self.rejected.insert(key.to_string(), value);
+        true
+    }
+
+    fn concerns_file(&self, event: &Event) -> bool {
+        matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+            && event.paths.iter().any(|path| path.file_name() == self.path.file_name())
+    }
+}
+
+// Uses `--config <path>` / `--config=<path>` from the command line, then
+// the SIM_CONFIG environment variable.
+pub fn path_from_args() -> Option<PathBuf> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| {
+            if let Some(value) = arg.strip_prefix("--config=") {
+                Some(value.to_string())
+            } else if arg == "--config" {
+                args.get(i + 1).cloned()
+            } else {
+                None
+            }
+        })
+        .or_else(|| env::var(CONFIG_ENV_VAR).ok())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAFE_KEYS: &[&str] = &["setpoint"];
+
+    fn watcher(name: &str, text: &str) -> ConfigWatcher {
+        let path = env::temp_dir().join(format!("{}_{}.conf", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let watcher = ConfigWatcher::watch(&path, SAFE_KEYS).unwrap();
+        fs::remove_file(&path).unwrap();
+        watcher
+    }
+
+    #[test]
+    fn parses_keys_values_and_comments() {
+        let config = Config::parse("# comment\n\n  setpoint = 21.5 \nlayout=car").unwrap();
+        assert_eq!(config.get("setpoint"), Some("21.5"));
+        assert_eq!(config.get_f64("setpoint"), Some(21.5));
+        assert_eq!(config.get_f64("layout"), None);
+        assert_eq!(config.entries().collect::<Vec<_>>(), vec![("layout", "car"), ("setpoint", "21.5")]);
+        assert_eq!(Config::parse("a = 1\nno value").unwrap_err(), "line 2: expected key = value");
+    }
+
+    #[test]
+    fn safe_keys_are_applied_while_running() {
+        let mut watcher = watcher("config_safe", "setpoint = 21\nlayout = car");
+        let updates = watcher.apply(Config::parse("setpoint = 23\nlayout = car").unwrap());
+        assert_eq!(updates, vec![ConfigUpdate { key: "setpoint".into(), value: Some("23".into()) }]);
+        assert_eq!(watcher.config().get("setpoint"), Some("23"));
+
+        let updates = watcher.apply(Config::parse("layout = car").unwrap());
+        assert_eq!(updates, vec![ConfigUpdate { key: "setpoint".into(), value: None }]);
+    }
+
+    #[test]
+    fn structural_keys_are_rejected_and_reported_once() {
+        let mut watcher = watcher("config_structural", "setpoint = 21\nlayout = car");
+        let truck = Config::parse("setpoint = 21\nlayout = truck").unwrap();
+        assert!(watcher.apply(truck.clone()).is_empty());
+        assert_eq!(watcher.config().get("layout"), Some("car"));
+
+        // Saving the file again does not repeat the warning, another value does
+        watcher.apply(truck);
+        assert!(!watcher.newly_rejected("layout", Some("truck")));
+        assert!(watcher.newly_rejected("layout", Some("motorcycle")));
+
+        // Putting the old value back forgets the rejected one
+        watcher.apply(Config::parse("setpoint = 21\nlayout = car").unwrap());
+        assert!(watcher.rejected.is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+pub struct CsvOptions {
+    pub path: PathBuf,
+    pub delimiter: char,
+    pub precision: usize,
+}
+
+// Writes equally long columns as a CSV file with a header row
+pub fn write_csv(options: &CsvOptions, columns: &[(&str, &[f64])]) -> io::Result<()> {
+    let rows = columns.iter().map(|(_, values)| values.len()).min().unwrap_or(0);
+    if columns.iter().any(|(_, values)| values.len() != rows) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "CSV columns must have the same length"));
+    }
+
+    let header: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    let rows: Vec<Vec<String>> = (0..rows)
+        .map(|row| {
+            columns
+                .iter()
+                .map(|(_, values)| format!("{:.*}", options.precision, values[row]))
+                .collect()
+        })
+        .collect();
+    write_rows(options, &header, &rows)
+}
+
+// Writes rows already formatted, e.g. with whole numbers that must not get
+// decimal places; the precision is up to the caller
+pub fn write_rows(options: &CsvOptions, header: &[&str], rows: &[Vec<String>]) -> io::Result<()> {
+    let delimiter = options.delimiter.to_string();
+    let mut writer = BufWriter::new(File::create(&options.path)?);
+
+    writeln!(writer, "{}", header.join(&delimiter))?;
+    for fields in rows {
+        writeln!(writer, "{}", fields.join(&delimiter))?;
+    }
+
+    writer.flush()
+}
@@ -0,0 +1,642 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Map, Value};
+
+use crate::events::TimedEvent;
+use crate::metrics::Metrics;
+use crate::run_control::{ControlError, RunControl};
+use crate::service::{ServiceRequest, ServiceResponse, SERVICE_TIMEOUT};
+use crate::sim_log;
+use crate::websocket;
+
+pub const DASHBOARD_ENV_VAR: &str = "SIM_DASHBOARD";
+
+// The simulation counts as stuck when no step finished for this long
+pub const LIVENESS_TIMEOUT: Duration = Duration::from_secs(60);
+// Longest request body read, e.g. a fault command for `/control/fault`
+const MAX_BODY: usize = 4096;
+
+// Minimal HTTP server: an index page with live charts of a simulation's key
+// signals and the charts it wrote, the signals and events as a WebSocket
+// (`/ws`) or Server-Sent-Events (`/events`) stream, Prometheus metrics
+// (`/metrics`), diagnostic requests (`/service/...`) and remote control of
+// the run (`/control/...`)
+pub struct Dashboard {
+    subscribers: Arc<Mutex<Vec<Sender<Update>>>>,
+    metrics: Arc<Metrics>,
+    site: Arc<Site>,
+}
+
+// Signal values or a discrete event as JSON, for every open stream
+#[derive(Clone)]
+enum Update {
+    Signals(String),
+    Event(String),
+}
+
+impl Update {
+    fn server_sent_event(&self) -> String {
+        match self {
+            Update::Signals(json) => format!("data: {}\n\n", json),
+            Update::Event(json) => format!("event: sim-event\ndata: {}\n\n", json),
+        }
+    }
+
+    fn websocket_message(&self) -> String {
+        match self {
+            Update::Signals(json) => format!("{{\"signals\":{}}}", json),
+            Update::Event(json) => format!("{{\"event\":{}}}", json),
+        }
+    }
+}
+
+struct Site {
+    title: String,
+    charts: Vec<PathBuf>,
+    metrics: Arc<Metrics>,
+    started: Instant,
+    service: Mutex<Option<Sender<ServiceRequest>>>,
+    control: Mutex<Option<Arc<RunControl>>>,
+}
+
+// Served on `/status`, `/healthz` (live) and `/readyz` (ready) for orchestrators
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub uptime: Duration,
+    pub sim_time: f64,
+    pub steps: u64,
+    pub components: Vec<String>,
+    pub last_tick_latency: Option<Duration>,
+    // Ready once the first step finished, live while steps keep finishing
+    pub ready: bool,
+    pub live: bool,
+}
+
+impl Status {
+    fn collect(site: &Site) -> Status {
+        let metrics = &site.metrics;
+        let steps = metrics.value("sim_steps_total").unwrap_or(0.0) as u64;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64());
+        let uptime = site.started.elapsed();
+
+        // Before the first step the timeout counts from startup
+        let idle = metrics
+            .value("sim_last_tick_timestamp_seconds")
+            .map_or(uptime.as_secs_f64(), |last_tick| now - last_tick);
+
+        Status {
+            uptime,
+            sim_time: metrics.value("sim_time_seconds").unwrap_or(0.0),
+            steps,
+            components: metrics.label_values("sim_component_duration_seconds", "component"),
+            last_tick_latency: metrics.value("sim_last_tick_latency_seconds").map(Duration::from_secs_f64),
+            ready: steps > 0,
+            live: idle < LIVENESS_TIMEOUT.as_secs_f64(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        json!({
+            "uptime_seconds": (self.uptime.as_secs_f64() * 1000.0).round() / 1000.0,
+            "sim_time_seconds": self.sim_time,
+            "steps": self.steps,
+            "components": self.components,
+            "last_tick_latency_seconds": self.last_tick_latency.map(|latency| latency.as_secs_f64()),
+            "ready": self.ready,
+            "live": self.live,
+        })
+        .to_string()
+    }
+}
+
+impl Dashboard {
+    pub fn start(address: &str, title: &str, charts: Vec<PathBuf>, metrics: Arc<Metrics>) -> io::Result<Dashboard> {
+        let listener = TcpListener::bind(address)?;
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let site = Arc::new(Site {
+            title: title.to_string(),
+            charts,
+            metrics: Arc::clone(&metrics),
+            started: Instant::now(),
+            service: Mutex::new(None),
+            control: Mutex::new(None),
+        });
+
+        let accepted = Arc::clone(&subscribers);
+        let served = Arc::clone(&site);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let site = Arc::clone(&served);
+                let subscribers = Arc::clone(&accepted);
+                thread::spawn(move || {
+                    if let Err(e) = handle(stream, &site, &subscribers) {
+                        sim_log::warn("dashboard", &format!("Dashboard request failed: {}", e));
+                    }
+                });
+            }
+        });
+
+        sim_log::info("dashboard", &format!("Dashboard running at http://{}/", address));
+        Ok(Dashboard { subscribers, metrics, site })
+    }
+
+    // Enables `/service/...`; the simulation answers the returned requests.
+    // Without it those paths are not found. Requests other than GET change
+    // the vehicle, e.g. clear DTCs, and need a control token (see `control`);
+    // without run control they are refused.
+    pub fn service_requests(&self) -> Receiver<ServiceRequest> {
+        let (sender, requests) = mpsc::channel();
+        *self.site.service.lock().unwrap() = Some(sender);
+        requests
+    }
+
+    // Enables `/control/...`: `POST start`, `pause` and `step` hold and
+    // release the run, `POST fault` queues its body as a fault command and
+    // `GET state` reports the run. Requests carry a token in an
+    // `Authorization: Bearer <token>` header. Without it those paths are
+    // not found.
+    pub fn control(&self, control: Arc<RunControl>) {
+        *self.site.control.lock().unwrap() = Some(control);
+    }
+
+    // Sends one event with the given signal values to every open stream and
+    // exports them as `sim_signal` gauges
+    pub fn publish(&self, signals: &[(&str, f64)]) {
+        for (name, value) in signals {
+            self.metrics.set_gauge("sim_signal", "Current value of a simulation signal", &[("signal", name)], *value);
+        }
+
+        // Non-finite values have no JSON number and go out as null
+        let fields: Map<String, Value> = signals.iter().map(|(name, value)| (name.to_string(), json!(value))).collect();
+        self.send(Update::Signals(Value::Object(fields).to_string()));
+    }
+
+    // Streams a discrete event next to the signals, as a `sim-event` message
+    // or an `event` object over the WebSocket
+    pub fn publish_event(&self, event: &TimedEvent) {
+        if let Ok(json) = serde_json::to_string(event) {
+            self.send(Update::Event(json));
+        }
+    }
+
+    fn send(&self, update: Update) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| subscriber.send(update.clone()).is_ok());
+    }
+}
+
+// Uses `--dashboard <address>` (or `--serve <address>`) from the command
+// line, then SIM_DASHBOARD
+pub fn address_from_args() -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--dashboard" || arg == "--serve")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| env::var(DASHBOARD_ENV_VAR).ok())
+}
+
+fn handle(mut stream: TcpStream, site: &Site, subscribers: &Mutex<Vec<Sender<Update>>>) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Read past the headers so closing the connection does not reset it
+    // before the client has read the response
+    let mut websocket_key = None;
+    let mut token = None;
+    let mut content_length = 0;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                websocket_key = Some(value.trim().to_string());
+            } else if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.trim().eq_ignore_ascii_case("authorization") {
+                token = value.trim().strip_prefix("Bearer ").map(|token| token.trim().to_string());
+            }
+        }
+        header.clear();
+    }
+    if content_length > MAX_BODY {
+        let body = ServiceResponse::error(413, &format!("request bodies are limited to {} bytes", MAX_BODY)).body;
+        return respond(&mut stream, &status_line(413), "application/json", body.to_string().as_bytes());
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("GET");
+    let path = parts.next().unwrap_or("/");
+
+    if path == "/" {
+        respond(&mut stream, "200 OK", "text/html; charset=utf-8", index_page(site).as_bytes())
+    } else if path == "/status" || path == "/healthz" || path == "/readyz" {
+        let status = Status::collect(site);
+        let ok = match path {
+            "/healthz" => status.live,
+            "/readyz" => status.ready && status.live,
+            _ => true,
+        };
+        let code = if ok { "200 OK" } else { "503 Service Unavailable" };
+        respond(&mut stream, code, "application/json", status.to_json().as_bytes())
+    } else if path == "/metrics" {
+        let body = site.metrics.render();
+        respond(&mut stream, "200 OK", "text/plain; version=0.0.4", body.as_bytes())
+    } else if let Some(service_path) = path.strip_prefix("/service/") {
+        let response = match authorize_service_write(site, method, token.as_deref()) {
+            Ok(()) => forward_service_request(site, method, service_path),
+            Err(refused) => refused,
+        };
+        let body = response.body.to_string();
+        respond(&mut stream, &status_line(response.status), "application/json", body.as_bytes())
+    } else if let Some(rpc) = path.strip_prefix("/control/") {
+        let response = control_response(site, method, rpc, token.as_deref(), &String::from_utf8_lossy(&body));
+        let body = response.body.to_string();
+        respond(&mut stream, &status_line(response.status), "application/json", body.as_bytes())
+    } else if path == "/events" {
+        let (sender, updates) = mpsc::channel();
+        subscribers.lock().unwrap().push(sender);
+        stream_events(stream, updates)
+    } else if path == "/ws" {
+        let Some(key) = websocket_key else {
+            return respond(&mut stream, "400 Bad Request", "text/plain", b"Expected a WebSocket upgrade");
+        };
+        let (sender, updates) = mpsc::channel();
+        subscribers.lock().unwrap().push(sender);
+        stream_websocket(stream, &key, updates)
+    } else if let Some(chart) = path
+        .strip_prefix("/charts/")
+        .and_then(|index| index.parse::<usize>().ok())
+        .and_then(|index| site.charts.get(index))
+    {
+        // Charts are re-read on every request so the latest version is served
+        match fs::read(chart) {
+            Ok(image) => respond(&mut stream, "200 OK", "image/png", &image),
+            Err(_) => respond(&mut stream, "404 Not Found", "text/plain", b"Chart not written yet"),
+        }
+    } else {
+        respond(&mut stream, "404 Not Found", "text/plain", b"Not found")
+    }
+}
+
+// Reads are open to anyone who can reach the dashboard, writes take the
+// token that may control the run
+fn authorize_service_write(site: &Site, method: &str, token: Option<&str>) -> Result<(), ServiceResponse> {
+    if method == "GET" {
+        return Ok(());
+    }
+    let Some(control) = site.control.lock().unwrap().clone() else {
+        return Err(ServiceResponse::error(403, "this simulation takes no service writes without control tokens"));
+    };
+    control.authorize(token).map_err(|e| ServiceResponse::error(401, &e.to_string()))
+}
+
+fn forward_service_request(site: &Site, method: &str, path: &str) -> ServiceResponse {
+    let Some(service) = site.service.lock().unwrap().clone() else {
+        return ServiceResponse::error(404, "this simulation has no service interface");
+    };
+
+    let (request, response) = ServiceRequest::new(method, path);
+    if service.send(request).is_err() {
+        return ServiceResponse::error(503, "the simulation has finished");
+    }
+    match response.recv_timeout(SERVICE_TIMEOUT) {
+        Ok(response) => response,
+        Err(RecvTimeoutError::Timeout) => ServiceResponse::error(504, "the simulation did not answer in time"),
+        Err(RecvTimeoutError::Disconnected) => ServiceResponse::error(503, "the simulation has finished"),
+    }
+}
+
+fn control_response(site: &Site, method: &str, rpc: &str, token: Option<&str>, body: &str) -> ServiceResponse {
+    let Some(control) = site.control.lock().unwrap().clone() else {
+        return ServiceResponse::error(404, "this simulation cannot be controlled remotely");
+    };
+    if let Err(e) = control.authorize(token) {
+        return ServiceResponse::error(401, &e.to_string());
+    }
+
+    match (method, rpc) {
+        ("POST", "start") => control.start(),
+        ("POST", "pause") => control.pause(),
+        ("POST", "step") => control.step(),
+        ("POST", "fault") => {
+            return match control.inject_fault(body) {
+                Ok(()) => ServiceResponse {
+                    status: 202,
+                    body: json!({ "queued": body.trim() }),
+                },
+                Err(ControlError::Finished) => ServiceResponse::error(503, "the simulation has finished"),
+                Err(_) => ServiceResponse::error(400, "expected a fault command as the request body"),
+            };
+        }
+        ("GET", "state") => {}
+        (_, "start" | "pause" | "step" | "fault" | "state") => return ServiceResponse::error(405, "method not allowed"),
+        _ => return ServiceResponse::error(404, "unknown control request"),
+    }
+    ServiceResponse::ok(json!(control.state()))
+}
+
+fn status_line(status: u16) -> String {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    };
+    format!("{} {}", status, reason)
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+fn stream_events(mut stream: TcpStream, updates: Receiver<Update>) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n"
+    )?;
+    stream.flush()?;
+
+    // Ends when the browser disconnects and the write fails
+    for update in updates {
+        write!(stream, "{}", update.server_sent_event())?;
+        stream.flush()?;
+    }
+    Ok(())
+}
+
+// Messages only go from the simulation to the browser; whatever the browser
+// sends is not read
+fn stream_websocket(mut stream: TcpStream, key: &str, updates: Receiver<Update>) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        websocket::accept_key(key)
+    )?;
+    stream.flush()?;
+
+    for update in updates {
+        stream.write_all(&websocket::text_frame(&update.websocket_message()))?;
+    }
+    // The simulation finished
+    stream.write_all(&websocket::close_frame())
+}
+
+// Text placed in the page's markup or in an attribute value
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn index_page(site: &Site) -> String {
+    let charts: String = site
+        .charts
+        .iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let alt = escape_html(&path.display().to_string());
+            format!("<figure><img src=\"/charts/{}\" alt=\"{}\"></figure>\n", index, alt)
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+td {{ border: 1px solid #ccc; padding: 0.3em 0.8em; }}
+td.value {{ text-align: right; min-width: 5em; }}
+img {{ max-width: 100%; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p id="connection">Connecting…</p>
+<table id="signals"></table>
+<ul id="timeline"></ul>
+{charts}<script>
+// Each signal gets a row with its value and a chart of its recent history
+const HISTORY = 300;
+const table = document.getElementById("signals");
+const timeline = document.getElementById("timeline");
+const connection = document.getElementById("connection");
+const rows = {{}};
+
+function row(name) {{
+  if (!rows[name]) {{
+    const tr = table.insertRow();
+    tr.insertCell().textContent = name;
+    const value = tr.insertCell();
+    value.className = "value";
+    const canvas = document.createElement("canvas");
+    canvas.width = 400;
+    canvas.height = 48;
+    tr.insertCell().appendChild(canvas);
+    rows[name] = {{ value, canvas, history: [] }};
+  }}
+  return rows[name];
+}}
+
+function draw(canvas, history) {{
+  const context = canvas.getContext("2d");
+  const values = history.filter((value) => value !== null);
+  context.clearRect(0, 0, canvas.width, canvas.height);
+  if (values.length < 2) return;
+  const min = Math.min(...values), max = Math.max(...values);
+  const span = max - min || 1;
+  context.strokeStyle = "royalblue";
+  context.beginPath();
+  history.forEach((value, i) => {{
+    if (value === null) return;
+    const x = (i / (HISTORY - 1)) * canvas.width;
+    const y = canvas.height - 2 - ((value - min) / span) * (canvas.height - 4);
+    i === 0 ? context.moveTo(x, y) : context.lineTo(x, y);
+  }});
+  context.stroke();
+}}
+
+const socket = new WebSocket(`ws://${{location.host}}/ws`);
+socket.onopen = () => (connection.textContent = "Live");
+socket.onclose = () => (connection.textContent = "Simulation finished or disconnected");
+socket.onmessage = (message) => {{
+  const update = JSON.parse(message.data);
+  if (update.event) {{
+    const item = document.createElement("li");
+    item.textContent = JSON.stringify(update.event);
+    timeline.prepend(item);
+  }}
+  for (const [name, value] of Object.entries(update.signals || {{}})) {{
+    const signal = row(name);
+    signal.value.textContent = value === null ? "-" : value.toFixed(2);
+    signal.history.push(value);
+    if (signal.history.length > HISTORY) signal.history.shift();
+    draw(signal.canvas, signal.history);
+  }}
+}};
+</script>
+</body>
+</html>
+"#,
+        title = escape_html(&site.title),
+        charts = charts
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(title: &str, charts: Vec<PathBuf>) -> Site {
+        Site {
+            title: title.to_string(),
+            charts,
+            metrics: Arc::new(Metrics::new()),
+            started: Instant::now(),
+            service: Mutex::new(None),
+            control: Mutex::new(None),
+        }
+    }
+
+    #[test]
+    fn status_json_escapes_component_names() {
+        let status = Status {
+            uptime: Duration::from_millis(1500),
+            sim_time: 12.5,
+            steps: 125,
+            components: vec!["tpms".to_string(), "odd \"name\"\\".to_string()],
+            last_tick_latency: None,
+            ready: true,
+            live: false,
+        };
+        let json: Value = serde_json::from_str(&status.to_json()).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "uptime_seconds": 1.5,
+                "sim_time_seconds": 12.5,
+                "steps": 125,
+                "components": ["tpms", "odd \"name\"\\"],
+                "last_tick_latency_seconds": null,
+                "ready": true,
+                "live": false,
+            })
+        );
+    }
+
+    #[test]
+    fn the_index_page_escapes_the_title_and_chart_names() {
+        let page = index_page(&site("<script>alert('x')</script> & co", vec![PathBuf::from("a\"b.png")]));
+        assert!(page.contains("<title>&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; co</title>"));
+        assert!(page.contains("alt=\"a&quot;b.png\""));
+        assert!(!page.contains("<script>alert"));
+    }
+
+    #[test]
+    fn control_requests_need_a_token_that_may_control() {
+        use crate::config::Config;
+        use crate::remote::TokenStore;
+        use crate::simulation::PauseControl;
+
+        let site = site("Control", Vec::new());
+        assert_eq!(control_response(&site, "POST", "pause", None, "").status, 404);
+
+        let tokens = TokenStore::from_config(&Config::parse("token.ci = 0123456789abcdef").unwrap()).unwrap();
+        let (control, faults) = RunControl::new(Arc::new(PauseControl::new()), tokens);
+        *site.control.lock().unwrap() = Some(control.clone());
+        assert_eq!(control_response(&site, "POST", "pause", None, "").status, 401);
+        assert_eq!(control_response(&site, "POST", "pause", Some("wrong token"), "").status, 401);
+        assert!(!control.state().paused);
+
+        let token = Some("0123456789abcdef");
+        let paused = control_response(&site, "POST", "pause", token, "");
+        assert_eq!((paused.status, &paused.body["paused"]), (200, &json!(true)));
+        let queued = control_response(&site, "POST", "fault", token, "leak fl 0.2\n");
+        assert_eq!((queued.status, &queued.body["queued"]), (202, &json!("leak fl 0.2")));
+        assert_eq!(faults.try_recv(), Ok("leak fl 0.2".to_string()));
+        assert_eq!(control_response(&site, "POST", "fault", token, "").status, 400);
+        assert_eq!(control_response(&site, "GET", "pause", token, "").status, 405);
+        assert_eq!(control_response(&site, "POST", "rewind", token, "").status, 404);
+    }
+
+    #[test]
+    fn service_writes_need_a_control_token() {
+        use crate::config::Config;
+        use crate::remote::TokenStore;
+        use crate::simulation::PauseControl;
+
+        let site = site("Service", Vec::new());
+        let (service, requests) = mpsc::channel();
+        *site.service.lock().unwrap() = Some(service);
+        assert_eq!(authorize_service_write(&site, "GET", None), Ok(()));
+        assert_eq!(authorize_service_write(&site, "POST", None).unwrap_err().status, 403);
+
+        let tokens = TokenStore::from_config(&Config::parse("token.ci = 0123456789abcdef").unwrap()).unwrap();
+        *site.control.lock().unwrap() = Some(RunControl::new(Arc::new(PauseControl::new()), tokens).0);
+        assert_eq!(authorize_service_write(&site, "POST", None).unwrap_err().status, 401);
+        assert_eq!(authorize_service_write(&site, "POST", Some("wrong token")).unwrap_err().status, 401);
+        assert_eq!(authorize_service_write(&site, "POST", Some("0123456789abcdef")), Ok(()));
+        assert_eq!(authorize_service_write(&site, "GET", None), Ok(()));
+        // Nothing refused reached the simulation
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[test]
+    fn bodies_over_the_limit_are_refused_whole() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let request = format!(
+                "POST /control/fault HTTP/1.1\r\nAuthorization: Bearer 0123456789abcdef\r\nContent-Length: {}\r\n\r\n",
+                MAX_BODY + 1
+            );
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        handle(stream, &site("Limits", Vec::new()), &Mutex::new(Vec::new())).unwrap();
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{}", response);
+        assert!(response.contains("limited to 4096 bytes"));
+    }
+}
@@ -0,0 +1,291 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::calibration::Calibration;
+use crate::can_bus::{self, Message, Signal};
+
+// How a measurement is stored before conversion, named as in ASAP2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DataType {
+    Ubyte,
+    Uword,
+    Sword,
+    Ulong,
+}
+
+impl DataType {
+    fn for_bits(bits: u8) -> DataType {
+        match bits {
+            0..=8 => DataType::Ubyte,
+            9..=16 => DataType::Uword,
+            _ => DataType::Ulong,
+        }
+    }
+
+    fn raw_range(self) -> (f64, f64) {
+        match self {
+            DataType::Ubyte => (0.0, u8::MAX as f64),
+            DataType::Uword => (0.0, u16::MAX as f64),
+            DataType::Sword => (i16::MIN as f64, i16::MAX as f64),
+            DataType::Ulong => (0.0, u32::MAX as f64),
+        }
+    }
+
+    fn a2l_name(self) -> &'static str {
+        match self {
+            DataType::Ubyte => "UBYTE",
+            DataType::Uword => "UWORD",
+            DataType::Sword => "SWORD",
+            DataType::Ulong => "ULONG",
+        }
+    }
+}
+
+// Where a tool finds a measurement
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Address {
+    // A signal of a message on the vehicle bus
+    Can { id: u32, message: &'static str, start_bit: u8, length: u8 },
+    // A UDS data identifier, read with service 0x22 from `request_id`
+    Did { request_id: u32, did: u16 },
+    // An OBD-II service 01 PID
+    ObdPid { pid: u8 },
+}
+
+impl Address {
+    fn ecu_address(&self) -> u32 {
+        match self {
+            Address::Can { id, .. } => *id,
+            Address::Did { did, .. } => *did as u32,
+            Address::ObdPid { pid } => *pid as u32,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Address::Can { id, message, start_bit, length } => {
+                format!("CAN 0x{:03X} {} bit {} length {}", id, message, start_bit, length)
+            }
+            Address::Did { request_id, did } => format!("UDS 0x{:03X} DID 0x{:04X}", request_id, did),
+            Address::ObdPid { pid } => format!("OBD-II service 01 PID 0x{:02X}", pid),
+        }
+    }
+}
+
+// A signal the simulation produces. Physical value = raw * factor + offset.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Measurement {
+    pub name: String,
+    pub address: Address,
+    pub data_type: DataType,
+    pub factor: f64,
+    pub offset: f64,
+    pub unit: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Measurement {
+    pub fn can(message: &Message, signal: &Signal) -> Measurement {
+        let (min, max) = signal.range();
+        Measurement {
+            name: signal.name.to_string(),
+            address: Address::Can {
+                id: message.id,
+                message: message.name,
+                start_bit: signal.start_bit,
+                length: signal.length,
+            },
+            data_type: DataType::for_bits(signal.length),
+            factor: signal.factor,
+            offset: signal.offset,
+            unit: signal.unit.to_string(),
+            min,
+            max,
+        }
+    }
+
+    // The range follows from the data type; narrow it with `with_range`
+    pub fn new(name: &str, address: Address, data_type: DataType, factor: f64, unit: &str) -> Measurement {
+        let (raw_min, raw_max) = data_type.raw_range();
+        Measurement {
+            name: name.to_string(),
+            address,
+            data_type,
+            factor,
+            offset: 0.0,
+            unit: unit.to_string(),
+            min: raw_min * factor,
+            max: raw_max * factor,
+        }
+    }
+
+    pub fn with_range(mut self, min: f64, max: f64) -> Measurement {
+        self.min = min;
+        self.max = max;
+        self
+    }
+}
+
+// A tunable parameter and the value this run uses
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Characteristic {
+    pub name: String,
+    pub value: String,
+    pub unit: String,
+    // Text parameters have no range
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Characteristic {
+    pub fn number(name: &str, value: impl ToString, unit: &str, min: f64, max: f64) -> Characteristic {
+        Characteristic {
+            name: name.to_string(),
+            value: value.to_string(),
+            unit: unit.to_string(),
+            min: Some(min),
+            max: Some(max),
+        }
+    }
+
+    pub fn text(name: &str, value: &str) -> Characteristic {
+        Characteristic {
+            name: name.to_string(),
+            value: value.to_string(),
+            unit: String::new(),
+            min: None,
+            max: None,
+        }
+    }
+}
+
+// Everything a simulation exposes to measurement and calibration tools.
+// Written as JSON, or as ASAP2 (A2L) text for `.a2l` paths; a
+// characteristic's address is its index in the list.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Description {
+    pub project: String,
+    pub version: String,
+    // Active calibration, "name version"
+    pub calibration: Option<String>,
+    pub measurements: Vec<Measurement>,
+    pub characteristics: Vec<Characteristic>,
+}
+
+impl Description {
+    pub fn new(project: &str, version: &str) -> Self {
+        Description {
+            project: project.to_string(),
+            version: version.to_string(),
+            calibration: None,
+            measurements: Vec::new(),
+            characteristics: Vec::new(),
+        }
+    }
+
+    pub fn with_calibration(mut self, calibration: Option<&Calibration>) -> Self {
+        self.calibration = calibration.map(Calibration::to_string);
+        self
+    }
+
+    // Every signal of the messages `transmitter` sends on the vehicle bus
+    pub fn with_can_messages(mut self, transmitter: &str) -> Self {
+        for message in can_bus::MATRIX.iter().filter(|message| message.transmitter == transmitter) {
+            self.measurements
+                .extend(message.signals.iter().map(|signal| Measurement::can(message, signal)));
+        }
+        self
+    }
+
+    pub fn with_measurement(mut self, measurement: Measurement) -> Self {
+        self.measurements.push(measurement);
+        self
+    }
+
+    pub fn with_characteristic(mut self, characteristic: Characteristic) -> Self {
+        self.characteristics.push(characteristic);
+        self
+    }
+
+    pub fn to_a2l(&self) -> String {
+        let mut a2l = String::from("ASAP2_VERSION 1 71\n");
+        let _ = writeln!(a2l, "/begin PROJECT {} \"Simulation {}\"", self.project, self.version);
+        let calibration = self.calibration.as_deref().unwrap_or("no calibration");
+        let _ = writeln!(a2l, "  /begin MODULE {} \"{}\"", self.project, calibration);
+
+        for (address, characteristic) in self.characteristics.iter().enumerate() {
+            let _ = writeln!(
+                a2l,
+                "    /begin CHARACTERISTIC {} \"Current value {}\"",
+                characteristic.name, characteristic.value
+            );
+            match (characteristic.min, characteristic.max) {
+                (Some(min), Some(max)) => {
+                    let _ = writeln!(a2l, "      VALUE 0x{:X} RL_FLOAT64 0 NO_COMPU_METHOD {} {}", address, min, max);
+                    let _ = writeln!(a2l, "      PHYS_UNIT \"{}\"", characteristic.unit);
+                }
+                _ => {
+                    let _ = writeln!(a2l, "      ASCII 0x{:X} RL_ASCII 0 NO_COMPU_METHOD 0 255", address);
+                    let _ = writeln!(a2l, "      NUMBER {}", characteristic.value.len().max(1));
+                }
+            }
+            a2l.push_str("    /end CHARACTERISTIC\n");
+        }
+
+        for measurement in &self.measurements {
+            let _ = writeln!(
+                a2l,
+                "    /begin MEASUREMENT {} \"{}\"",
+                measurement.name,
+                measurement.address.describe()
+            );
+            let _ = writeln!(
+                a2l,
+                "      {} CM_{} {} 0 {} {}",
+                measurement.data_type.a2l_name(),
+                measurement.name,
+                measurement.factor,
+                measurement.min,
+                measurement.max
+            );
+            let _ = writeln!(a2l, "      ECU_ADDRESS 0x{:X}", measurement.address.ecu_address());
+            a2l.push_str("    /end MEASUREMENT\n");
+            let _ = writeln!(
+                a2l,
+                "    /begin COMPU_METHOD CM_{} \"\" LINEAR \"%.3\" \"{}\"",
+                measurement.name, measurement.unit
+            );
+            let _ = writeln!(a2l, "      COEFFS_LINEAR {} {}", measurement.factor, measurement.offset);
+            a2l.push_str("    /end COMPU_METHOD\n");
+        }
+
+        a2l.push_str("  /end MODULE\n/end PROJECT\n");
+        a2l
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("a2l")) {
+            self.to_a2l()
+        } else {
+            serde_json::to_string_pretty(self)?
+        };
+        fs::write(path, text)
+    }
+}
+
+// Uses `--describe <path>` from the command line
+pub fn path_from_args() -> Option<PathBuf> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--describe")
+        .and_then(|i| args.get(i + 1).map(PathBuf::from))
+}
@@ -0,0 +1,71 @@
+use std::env;
+
+use crate::restriction::{self, RestrictionProfile};
+use crate::units::Celsius;
+
+pub const DRIVER_ENV_VAR: &str = "SIM_DRIVER";
+
+// A driver identified by the key fob used to start the vehicle
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriverProfile {
+    pub key_fob_id: String,
+    pub name: String,
+    pub preferred_temperature: Celsius,
+    // Limits the vehicle holds this driver to from the moment the fob starts it
+    pub restriction: Option<RestrictionProfile>,
+}
+
+impl DriverProfile {
+    pub fn new(key_fob_id: &str, name: &str, preferred_temperature: Celsius) -> Self {
+        DriverProfile {
+            key_fob_id: key_fob_id.to_string(),
+            name: name.to_string(),
+            preferred_temperature,
+            restriction: None,
+        }
+    }
+
+    pub fn restricted(mut self, profile: RestrictionProfile) -> Self {
+        self.restriction = Some(profile);
+        self
+    }
+}
+
+// Key fobs paired with the simulated vehicle
+pub fn paired_profiles() -> Vec<DriverProfile> {
+    vec![
+        DriverProfile::new("FOB-1", "Driver 1", 21.0),
+        DriverProfile::new("FOB-2", "Driver 2", 23.5),
+        DriverProfile::new("FOB-3", "Driver 3", 19.0),
+        DriverProfile::new("FOB-4", "Teen driver", 22.0).restricted(restriction::TEEN),
+    ]
+}
+
+pub fn find_profile(key_fob_id: &str) -> Option<DriverProfile> {
+    paired_profiles()
+        .into_iter()
+        .find(|profile| profile.key_fob_id.eq_ignore_ascii_case(key_fob_id))
+}
+
+// Uses `--driver <fob>` from the command line, then SIM_DRIVER, and falls
+// back to the first paired key fob. Unknown fobs get a guest profile.
+pub fn active_profile() -> DriverProfile {
+    let args: Vec<String> = env::args().collect();
+    let key_fob_id = args
+        .iter()
+        .position(|arg| arg == "--driver")
+        .and_then(|i| args.get(i + 1).cloned());
+
+    profile_for(key_fob_id.as_deref())
+}
+
+// For programs with their own argument parser: an explicit key fob wins,
+// then SIM_DRIVER, then the first paired key fob.
+pub fn profile_for(key_fob_id: Option<&str>) -> DriverProfile {
+    let key_fob_id = key_fob_id.map(str::to_string).or_else(|| env::var(DRIVER_ENV_VAR).ok());
+
+    match key_fob_id {
+        Some(id) => find_profile(&id).unwrap_or_else(|| DriverProfile::new(&id, "Guest", 21.0)),
+        None => paired_profiles().remove(0),
+    }
+}
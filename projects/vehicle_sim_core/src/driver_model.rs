@@ -0,0 +1,215 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::rng::SimRng;
+
+// The model drives in steps of this length, whatever the caller's step
+const SUBSTEP: f64 = 1.0; // s
+// The speed the driver wants drifts around the road's cruise speed and
+// returns to it over about this long, so consecutive speeds are correlated
+const TARGET_TIME: f64 = 60.0; // s
+// Distance to a red light the driver stops short of
+const STOP_MARGIN: f64 = 2.0; // m
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpeedProfile {
+    Urban,
+    Highway,
+    // Urban stretches between highway stretches
+    Mixed,
+}
+
+impl SpeedProfile {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "urban" => Ok(SpeedProfile::Urban),
+            "highway" => Ok(SpeedProfile::Highway),
+            "mixed" => Ok(SpeedProfile::Mixed),
+            other => Err(format!("unknown speed profile '{}', expected urban, highway or mixed", other)),
+        }
+    }
+}
+
+// How a driver behaves on one kind of road
+struct Road {
+    cruise: f64, // km/h
+    // Standard deviation of the wanted speed around the cruise speed
+    spread: f64, // km/h
+    max_speed: f64, // km/h
+    acceleration: f64, // m/s²
+    deceleration: f64, // m/s²
+    // Distance between traffic lights (none on the highway), the share
+    // showing red and how long they stay red
+    light_spacing: Option<(f64, f64)>, // m
+    red_share: f64,
+    red_time: (f64, f64), // s
+    // Length of a stretch of this road in a mixed profile
+    stretch: (f64, f64), // m
+}
+
+const URBAN: Road = Road {
+    cruise: 45.0,
+    spread: 6.0,
+    max_speed: 60.0,
+    acceleration: 1.5,
+    deceleration: 2.0,
+    light_spacing: Some((250.0, 700.0)),
+    red_share: 0.5,
+    red_time: (10.0, 60.0),
+    stretch: (3000.0, 8000.0),
+};
+
+const HIGHWAY: Road = Road {
+    cruise: 115.0,
+    spread: 10.0,
+    max_speed: 140.0,
+    acceleration: 0.8,
+    deceleration: 1.5,
+    light_spacing: None,
+    red_share: 0.0,
+    red_time: (0.0, 0.0),
+    stretch: (10_000.0, 30_000.0),
+};
+
+// A driver producing a speed trace: the wanted speed wanders around the
+// road's cruise speed, the actual speed follows it within comfortable
+// acceleration limits, and on urban roads the driver brakes for red lights
+// and waits at them. Serialized with a simulation's checkpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriverModel {
+    pub profile: SpeedProfile,
+    // The road driven now, urban or highway
+    highway: bool,
+    pub speed: f64, // km/h
+    target: f64,    // km/h
+    // Distance to the next traffic light (none on the highway), whether it
+    // shows red, and the time left waiting at one
+    to_light: Option<f64>, // m
+    red: bool,
+    waiting: f64, // s
+    // Distance left on the current stretch of a mixed profile
+    stretch_left: Option<f64>, // m
+    pub stops: u64,
+}
+
+impl DriverModel {
+    // Starts at standstill on the profile's (first) road
+    pub fn new(profile: SpeedProfile, rng: &mut SimRng) -> Self {
+        let mut model = DriverModel {
+            profile,
+            highway: profile == SpeedProfile::Highway,
+            speed: 0.0,
+            target: 0.0,
+            to_light: None,
+            red: false,
+            waiting: 0.0,
+            stretch_left: None,
+            stops: 0,
+        };
+        model.enter_road(rng);
+        model
+    }
+
+    fn road(&self) -> &'static Road {
+        if self.highway {
+            &HIGHWAY
+        } else {
+            &URBAN
+        }
+    }
+
+    // Highest speed the model drives on any of its roads
+    pub fn max_speed(&self) -> f64 {
+        match self.profile {
+            SpeedProfile::Urban => URBAN.max_speed,
+            _ => HIGHWAY.max_speed,
+        }
+    }
+
+    fn enter_road(&mut self, rng: &mut SimRng) {
+        let road = self.road();
+        self.target = road.cruise;
+        if self.profile == SpeedProfile::Mixed {
+            self.stretch_left = Some(rng.gen_range(road.stretch.0..road.stretch.1));
+        }
+        self.to_light = None;
+        self.red = false;
+        self.next_light(rng);
+    }
+
+    fn next_light(&mut self, rng: &mut SimRng) {
+        let road = self.road();
+        if let Some((min, max)) = road.light_spacing {
+            self.to_light = Some(rng.gen_range(min..max));
+            self.red = rng.gen_bool(road.red_share);
+        }
+    }
+
+    // Drives for `dt` seconds and returns the average speed in km/h
+    pub fn advance(&mut self, dt: f64, rng: &mut SimRng) -> f64 {
+        let mut distance = 0.0;
+        let mut left = dt;
+        while left > 0.0 {
+            let step = left.min(SUBSTEP);
+            distance += self.substep(step, rng);
+            left -= step;
+        }
+        if dt > 0.0 {
+            distance / dt * 3.6
+        } else {
+            self.speed
+        }
+    }
+
+    // Returns the distance driven in m
+    fn substep(&mut self, dt: f64, rng: &mut SimRng) -> f64 {
+        if self.waiting > 0.0 {
+            self.waiting -= dt;
+            if self.waiting <= 0.0 {
+                self.next_light(rng);
+            }
+            return 0.0;
+        }
+        let road = self.road();
+
+        // Unit-variance noise keeps the wanted speed spread around the cruise
+        // speed by about the road's spread
+        let noise = rng.gen_range(-1.0..1.0) * 3.0_f64.sqrt();
+        self.target += (road.cruise - self.target) * dt / TARGET_TIME + road.spread * (2.0 * dt / TARGET_TIME).sqrt() * noise;
+        self.target = self.target.clamp(road.cruise / 2.0, road.max_speed);
+
+        // Fastest speed from which the driver still stops at a red light
+        let mut wanted = self.target / 3.6;
+        let red_light = self.to_light.filter(|_| self.red);
+        if let Some(to_light) = red_light {
+            let room = (to_light - STOP_MARGIN).max(0.0);
+            wanted = wanted.min((2.0 * road.deceleration * room).sqrt());
+        }
+        let speed = self.speed / 3.6;
+        let change = (wanted - speed).clamp(-road.deceleration * dt, road.acceleration * dt);
+        // Braking for a light may need more than the comfortable rate
+        let new_speed = if red_light.is_some() && wanted < speed + change { wanted } else { speed + change }.max(0.0);
+        let travelled = (speed + new_speed) / 2.0 * dt;
+        self.speed = new_speed * 3.6;
+
+        if let Some(to_light) = self.to_light.as_mut() {
+            *to_light -= travelled;
+            if self.red && *to_light <= STOP_MARGIN + 0.5 && new_speed < 0.5 {
+                self.speed = 0.0;
+                self.waiting = rng.gen_range(road.red_time.0..road.red_time.1);
+                self.stops += 1;
+            } else if *to_light <= 0.0 {
+                self.next_light(rng);
+            }
+        }
+
+        if let Some(stretch_left) = self.stretch_left.as_mut() {
+            *stretch_left -= travelled;
+            if *stretch_left <= 0.0 {
+                self.highway = !self.highway;
+                self.enter_road(rng);
+            }
+        }
+        travelled
+    }
+}
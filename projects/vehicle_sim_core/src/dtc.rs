@@ -0,0 +1,507 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+use crate::uds::{self, DtcEntry};
+
+// Fault memory of an ECU, the way ISO 14229 describes it: monitors report
+// each test as passed or failed, a fault detection counter debounces the
+// results into testFailed, a fault failing in enough operation (ignition)
+// cycles is confirmed, and fault-free cycles first heal the warning lamp and
+// then age the code out of the memory. Every run is one operation cycle.
+
+// Fault detection counter limits: a test has failed at the top, passed at
+// the bottom (ISO 14229-1 D.2)
+pub const FAULT_DETECTION_FAILED: i16 = 127;
+pub const FAULT_DETECTION_PASSED: i16 = -128;
+
+// How far each failed or passed result moves the fault detection counter;
+// a result against the trend starts the count over from zero
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Debounce {
+    pub step_up: i16,
+    pub step_down: i16,
+}
+
+impl Debounce {
+    // A single result decides
+    pub const IMMEDIATE: Debounce = Debounce {
+        step_up: FAULT_DETECTION_FAILED,
+        step_down: -FAULT_DETECTION_PASSED,
+    };
+
+    // `failed` results in a row set testFailed and `passed` in a row reset it;
+    // fewer than one counts as one
+    pub const fn counting(failed: i16, passed: i16) -> Debounce {
+        let failed = if failed < 1 { 1 } else { failed };
+        let passed = if passed < 1 { 1 } else { passed };
+        Debounce {
+            step_up: (FAULT_DETECTION_FAILED + failed - 1) / failed,
+            step_down: (-FAULT_DETECTION_PASSED + passed - 1) / passed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DtcConfig {
+    pub debounce: Debounce,
+    // Operation cycles a fault has to fail in before its code is confirmed
+    pub confirmation_cycles: u32,
+    // Fault-free cycles after which the warning lamp request goes out
+    pub healing_cycles: u32,
+    // Fault-free cycles after which a confirmed code is erased
+    pub aging_cycles: u32,
+    // Whether a failing test asks for the warning lamp
+    pub warning_indicator: bool,
+}
+
+impl Default for DtcConfig {
+    fn default() -> Self {
+        DtcConfig {
+            debounce: Debounce::IMMEDIATE,
+            confirmation_cycles: 2,
+            healing_cycles: 3,
+            aging_cycles: 40,
+            warning_indicator: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DtcStatus {
+    // Failed in fewer than the confirmation cycles; dropped after a
+    // fault-free cycle
+    Pending,
+    Confirmed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DtcRecord<F> {
+    pub code: String,
+    pub description: String,
+    pub status: DtcStatus,
+    pub first_seen: f64,
+    pub last_seen: f64,
+    pub occurrences: u32,
+    // testFailed: the debounced result of the latest test
+    pub active: bool,
+    pub failed_this_cycle: bool,
+    pub failed_cycles: u32,
+    // Fault-free operation cycles since the fault was last seen
+    pub healthy_cycles: u32,
+    #[serde(default)]
+    pub fault_detection_counter: i16,
+    #[serde(default)]
+    pub warning_indicator: bool,
+    // What the ECU measured when the fault was first detected
+    pub freeze_frame: F,
+}
+
+impl<F> DtcRecord<F> {
+    // The ISO 14229 status byte; pending while failing in this or the last
+    // completed cycle
+    pub fn status_byte(&self) -> u8 {
+        let bits = [
+            (self.active, uds::TEST_FAILED),
+            (self.failed_this_cycle, uds::TEST_FAILED_THIS_OPERATION_CYCLE),
+            (self.failed_this_cycle || self.healthy_cycles == 0, uds::PENDING_DTC),
+            (self.status == DtcStatus::Confirmed, uds::CONFIRMED_DTC),
+            (self.warning_indicator, uds::WARNING_INDICATOR_REQUESTED),
+        ];
+        bits.iter().filter(|(set, _)| *set).fold(0, |status, (_, bit)| status | bit)
+    }
+}
+
+// The result of one test of a monitor
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultReport {
+    pub component: String,
+    pub code: String,
+    pub description: String,
+}
+
+// Fault memory that survives program runs, like the non-volatile DTC memory
+// of an ECU; records are kept per component with the freeze frame `F` the
+// ECU takes. Codes still being debounced have no record yet.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound(deserialize = "F: Deserialize<'de>"))]
+pub struct DtcManager<F> {
+    #[serde(default)]
+    ignition_cycles: u64,
+    #[serde(default)]
+    components: BTreeMap<String, Vec<DtcRecord<F>>>,
+    // Fault detection counters of codes that failed without a record yet,
+    // per component and then code
+    #[serde(default)]
+    debouncing: BTreeMap<String, BTreeMap<String, i16>>,
+    #[serde(skip)]
+    config: DtcConfig,
+}
+
+impl<F> Default for DtcManager<F> {
+    fn default() -> Self {
+        DtcManager::new(DtcConfig::default())
+    }
+}
+
+impl<F> DtcManager<F> {
+    pub fn new(config: DtcConfig) -> Self {
+        DtcManager {
+            ignition_cycles: 0,
+            components: BTreeMap::new(),
+            debouncing: BTreeMap::new(),
+            config,
+        }
+    }
+
+    // The configuration is not stored, so a loaded or resumed memory takes it
+    // from the ECU again
+    pub fn with_config(mut self, config: DtcConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn set_config(&mut self, config: DtcConfig) {
+        self.config = config;
+    }
+
+    // Closes the previous operation cycle: codes that did not fail in it heal
+    // by one cycle, pending codes are dropped, the warning lamp request goes
+    // out after the healing cycles and confirmed codes are erased after the
+    // aging cycles
+    pub fn start_ignition_cycle(&mut self) {
+        self.ignition_cycles += 1;
+        self.debouncing.clear();
+        let config = self.config;
+        for records in self.components.values_mut() {
+            for record in records.iter_mut() {
+                if !record.failed_this_cycle {
+                    record.healthy_cycles += 1;
+                }
+                if record.healthy_cycles >= config.healing_cycles {
+                    record.warning_indicator = false;
+                }
+                record.failed_this_cycle = false;
+                record.active = false;
+                record.fault_detection_counter = 0;
+            }
+            records.retain(|record| match record.status {
+                DtcStatus::Pending => record.healthy_cycles == 0,
+                DtcStatus::Confirmed => record.healthy_cycles < config.aging_cycles,
+            });
+        }
+        self.components.retain(|_, records| !records.is_empty());
+    }
+
+    // Feeds one test result through the debounce. A code counts a new
+    // occurrence each time it turns testFailed again, taking `freeze_frame`
+    // the first time, and is confirmed once it has failed in the
+    // confirmation cycles.
+    pub fn report(&mut self, timestamp: f64, fault: &FaultReport, failed: bool, freeze_frame: impl FnOnce() -> F) {
+        let config = self.config;
+        let step = if failed { config.debounce.step_up } else { -config.debounce.step_down };
+        let counter = |previous: i16| {
+            let start = if failed { previous.max(0) } else { previous.min(0) };
+            (start + step).clamp(FAULT_DETECTION_PASSED, FAULT_DETECTION_FAILED)
+        };
+
+        let recorded = |records: &Vec<DtcRecord<F>>| records.iter().any(|record| record.code == fault.code);
+        if !self.components.get(&fault.component).is_some_and(recorded) {
+            let codes = self.debouncing.entry(fault.component.clone()).or_default();
+            let previous = codes.remove(&fault.code).unwrap_or(0);
+            let fault_detection_counter = counter(previous);
+            if fault_detection_counter > 0 && fault_detection_counter < FAULT_DETECTION_FAILED {
+                codes.insert(fault.code.clone(), fault_detection_counter);
+            }
+            if codes.is_empty() {
+                self.debouncing.remove(&fault.component);
+            }
+            if fault_detection_counter < FAULT_DETECTION_FAILED {
+                return;
+            }
+            self.components.entry(fault.component.clone()).or_default().push(DtcRecord {
+                code: fault.code.clone(),
+                description: fault.description.clone(),
+                status: DtcStatus::Pending,
+                first_seen: timestamp,
+                last_seen: timestamp,
+                occurrences: 0,
+                active: false,
+                failed_this_cycle: false,
+                failed_cycles: 0,
+                healthy_cycles: 0,
+                fault_detection_counter: previous,
+                warning_indicator: false,
+                freeze_frame: freeze_frame(),
+            });
+        }
+        let record = self
+            .components
+            .get_mut(&fault.component)
+            .and_then(|records| records.iter_mut().find(|record| record.code == fault.code))
+            .expect("recorded above");
+
+        record.fault_detection_counter = counter(record.fault_detection_counter);
+        if record.fault_detection_counter == FAULT_DETECTION_PASSED {
+            record.active = false;
+        }
+        if record.fault_detection_counter < FAULT_DETECTION_FAILED {
+            return;
+        }
+
+        if !record.active {
+            record.occurrences += 1;
+            record.active = true;
+        }
+        if !record.failed_this_cycle {
+            record.failed_this_cycle = true;
+            record.failed_cycles += 1;
+        }
+        if record.failed_cycles >= config.confirmation_cycles {
+            record.status = DtcStatus::Confirmed;
+        }
+        record.warning_indicator |= config.warning_indicator;
+        record.description = fault.description.clone();
+        record.last_seen = timestamp;
+        record.healthy_cycles = 0;
+    }
+
+    pub fn ignition_cycles(&self) -> u64 {
+        self.ignition_cycles
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = (&str, &DtcRecord<F>)> {
+        self.components
+            .iter()
+            .flat_map(|(component, records)| records.iter().map(move |record| (component.as_str(), record)))
+    }
+
+    // Codes whose test is failing
+    pub fn active_codes(&self) -> Vec<String> {
+        self.records()
+            .filter(|(_, record)| record.active)
+            .map(|(_, record)| record.code.clone())
+            .collect()
+    }
+
+    pub fn any_active(&self) -> bool {
+        self.records().any(|(_, record)| record.active)
+    }
+
+    pub fn warning_indicator_requested(&self) -> bool {
+        self.records().any(|(_, record)| record.warning_indicator)
+    }
+
+    // What ReadDTCInformation reports; codes that are not in the ISO format
+    // are left out
+    pub fn entries(&self) -> Vec<DtcEntry> {
+        self.records()
+            .filter_map(|(_, record)| {
+                uds::dtc_from_code(&record.code).map(|dtc| DtcEntry {
+                    dtc,
+                    status: record.status_byte(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.components.clear();
+        self.debouncing.clear();
+    }
+}
+
+impl<F: DeserializeOwned> DtcManager<F> {
+    pub fn load(path: &Path, config: DtcConfig) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str::<Self>(&text)
+                .map(|manager| manager.with_config(config))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(DtcManager::new(config)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<F: Serialize> DtcManager<F> {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        storage::write_atomic(path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Confirmed in the second cycle, the lamp heals after one fault-free cycle
+    const CONFIG: DtcConfig = DtcConfig {
+        debounce: Debounce::IMMEDIATE,
+        confirmation_cycles: 2,
+        healing_cycles: 1,
+        aging_cycles: 40,
+        warning_indicator: true,
+    };
+
+    fn fault(component: &str, code: &str) -> FaultReport {
+        FaultReport {
+            component: component.to_string(),
+            code: code.to_string(),
+            description: format!("{} failed", component),
+        }
+    }
+
+    fn low() -> FaultReport {
+        fault("Front-Left", "C0750")
+    }
+
+    fn manager(config: DtcConfig) -> DtcManager<Vec<u8>> {
+        DtcManager::new(config)
+    }
+
+    #[test]
+    fn fault_is_confirmed_in_its_second_operation_cycle() {
+        let mut manager = manager(CONFIG);
+        manager.start_ignition_cycle();
+        manager.report(1.0, &low(), true, || vec![1]);
+        manager.report(2.0, &low(), true, || vec![2]);
+        let (_, record) = manager.records().next().unwrap();
+        assert_eq!(record.status, DtcStatus::Pending);
+        assert_eq!(record.failed_cycles, 1);
+
+        manager.start_ignition_cycle();
+        manager.report(3.0, &low(), true, || vec![3]);
+        let (component, record) = manager.records().next().unwrap();
+        assert_eq!(component, "Front-Left");
+        assert_eq!(record.status, DtcStatus::Confirmed);
+        assert_eq!(record.occurrences, 2);
+        // The freeze frame is taken when the fault is first detected
+        assert_eq!((record.first_seen, record.last_seen, &record.freeze_frame), (1.0, 3.0, &vec![1]));
+    }
+
+    #[test]
+    fn fault_free_cycles_drop_pending_and_age_out_confirmed_codes() {
+        let mut pending = manager(CONFIG);
+        pending.report(1.0, &low(), true, Vec::new);
+        pending.start_ignition_cycle();
+        assert_eq!(pending.records().count(), 1);
+        pending.start_ignition_cycle();
+        assert_eq!(pending.records().count(), 0);
+
+        let mut confirmed = manager(CONFIG);
+        for _ in 0..CONFIG.confirmation_cycles {
+            confirmed.start_ignition_cycle();
+            confirmed.report(1.0, &low(), true, Vec::new);
+        }
+        // Closes the last cycle the fault was seen in
+        confirmed.start_ignition_cycle();
+        for _ in 0..CONFIG.aging_cycles {
+            assert_eq!(confirmed.records().count(), 1);
+            confirmed.start_ignition_cycle();
+        }
+        assert_eq!(confirmed.records().count(), 0);
+    }
+
+    #[test]
+    fn status_byte_follows_the_test_results_and_the_lamp_heals() {
+        let mut manager = manager(CONFIG);
+        manager.start_ignition_cycle();
+        manager.report(1.0, &low(), true, Vec::new);
+        let status = |manager: &DtcManager<Vec<u8>>| manager.records().next().unwrap().1.status_byte();
+        assert_eq!(
+            status(&manager),
+            uds::TEST_FAILED | uds::TEST_FAILED_THIS_OPERATION_CYCLE | uds::PENDING_DTC | uds::WARNING_INDICATOR_REQUESTED
+        );
+
+        manager.report(2.0, &low(), false, Vec::new);
+        assert!(!manager.any_active());
+        assert!(manager.warning_indicator_requested());
+
+        manager.start_ignition_cycle();
+        manager.report(3.0, &low(), true, Vec::new);
+        manager.start_ignition_cycle();
+        assert_eq!(status(&manager), uds::CONFIRMED_DTC | uds::PENDING_DTC | uds::WARNING_INDICATOR_REQUESTED);
+
+        // One fault-free cycle turns the lamp off, the code stays stored
+        manager.start_ignition_cycle();
+        assert_eq!(status(&manager), uds::CONFIRMED_DTC);
+        assert_eq!(manager.entries(), vec![DtcEntry { dtc: 0x475000, status: uds::CONFIRMED_DTC }]);
+    }
+
+    #[test]
+    fn a_counting_debounce_needs_failures_in_a_row() {
+        let mut manager = manager(DtcConfig {
+            debounce: Debounce::counting(3, 2),
+            ..CONFIG
+        });
+        manager.report(1.0, &low(), true, Vec::new);
+        manager.report(2.0, &low(), true, Vec::new);
+        manager.report(3.0, &low(), false, Vec::new);
+        manager.report(4.0, &low(), true, Vec::new);
+        manager.report(5.0, &low(), true, Vec::new);
+        assert_eq!(manager.records().count(), 0);
+        manager.report(6.0, &low(), true, Vec::new);
+        assert_eq!(manager.active_codes(), vec!["C0750".to_string()]);
+
+        manager.report(7.0, &low(), false, Vec::new);
+        assert!(manager.any_active());
+        manager.report(8.0, &low(), false, Vec::new);
+        assert!(!manager.any_active());
+    }
+
+    #[test]
+    fn components_sharing_a_code_debounce_on_their_own() {
+        let mut manager = manager(DtcConfig {
+            debounce: Debounce::counting(2, 1),
+            ..CONFIG
+        });
+        let (left, right) = (fault("Front-Left", "U0100"), fault("Front-Right", "U0100"));
+        manager.report(1.0, &left, true, Vec::new);
+        manager.report(2.0, &right, true, Vec::new);
+        assert_eq!(manager.records().count(), 0);
+
+        manager.report(3.0, &left, true, Vec::new);
+        let components: Vec<&str> = manager.records().map(|(component, _)| component).collect();
+        assert_eq!(components, vec!["Front-Left"]);
+        manager.report(4.0, &right, true, Vec::new);
+        assert_eq!(manager.records().count(), 2);
+    }
+
+    #[test]
+    fn a_counting_debounce_of_zero_results_decides_at_once() {
+        assert_eq!(Debounce::counting(0, 0), Debounce::counting(1, 1));
+        assert_eq!(Debounce::counting(-3, 1), Debounce::counting(1, 1));
+        assert_eq!(Debounce::counting(1, 1), Debounce::IMMEDIATE);
+        assert_eq!(Debounce::counting(3, 2), Debounce { step_up: 43, step_down: 64 });
+    }
+
+    #[test]
+    fn saved_memory_loads_with_the_given_config() {
+        let path = std::env::temp_dir().join(format!("dtc_manager_{}.json", std::process::id()));
+        let mut manager = manager(DtcConfig {
+            debounce: Debounce::counting(2, 1),
+            ..CONFIG
+        });
+        manager.start_ignition_cycle();
+        manager.report(1.0, &low(), true, || vec![7]);
+        manager.report(2.0, &low(), true, || vec![8]);
+        manager.report(3.0, &fault("Rear-Left", "C0752"), true, Vec::new);
+        manager.save(&path).unwrap();
+
+        let mut loaded = DtcManager::<Vec<u8>>::load(&path, CONFIG).unwrap();
+        assert_eq!(loaded.ignition_cycles(), 1);
+        // Taken once the debounce set testFailed
+        assert_eq!(loaded.records().next().unwrap().1.freeze_frame, vec![8]);
+        // The loaded config decides immediately; the debounce count carried over
+        loaded.report(4.0, &fault("Rear-Left", "C0752"), true, Vec::new);
+        assert_eq!(loaded.records().count(), 2);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(DtcManager::<Vec<u8>>::load(&path, CONFIG).unwrap().records().count(), 0);
+    }
+}
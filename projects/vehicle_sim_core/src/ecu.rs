@@ -0,0 +1,440 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::can_bus::CanBus;
+use crate::events::Event;
+use crate::lifecycle::{self, LifecycleError};
+use crate::sim_log;
+use crate::simulation::Simulation;
+
+// E/E architecture domain an ECU belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Domain {
+    Body,
+    Powertrain,
+    Chassis,
+    Adas,
+}
+
+// The parts of `Simulation` an ECU needs, without the state type, so one
+// ECU can own components of different kinds. Simulations plug in as they
+// are, with nothing to do on startup or shutdown.
+pub trait Component {
+    fn step(&mut self, dt: f64);
+
+    fn report(&self) -> String;
+
+    // Called once its dependencies have started; an error aborts the startup
+    fn start(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn stop(&mut self) {}
+}
+
+struct Slot {
+    name: String,
+    requires: Vec<String>,
+    // Taken out when the component is stopped
+    component: Option<Box<dyn Component + Send>>,
+}
+
+impl<S: Simulation> Component for S {
+    fn step(&mut self, dt: f64) {
+        Simulation::step(self, dt)
+    }
+
+    fn report(&self) -> String {
+        Simulation::report(self)
+    }
+}
+
+// A named control unit stepping its components every `cycle` seconds once
+// it has booted. Components reach the bus through the connection handed to
+// them on construction, usually `ecu.bus()`.
+pub struct VirtualEcu {
+    name: String,
+    domain: Domain,
+    cycle: f64,
+    startup_order: u32,
+    startup_delay: f64,
+    bus: Arc<CanBus>,
+    components: Vec<Slot>,
+    cycles: u64,
+}
+
+impl VirtualEcu {
+    pub fn new(name: &str, domain: Domain, cycle: f64, bus: Arc<CanBus>) -> Self {
+        VirtualEcu {
+            name: name.to_string(),
+            domain,
+            cycle,
+            startup_order: 0,
+            startup_delay: 0.0,
+            bus,
+            components: Vec::new(),
+            cycles: 0,
+        }
+    }
+
+    // ECUs with a lower startup order boot first and run first when their
+    // cycles fall due at the same time
+    pub fn with_startup_order(mut self, order: u32) -> Self {
+        self.startup_order = order;
+        self
+    }
+
+    // Simulated seconds the ECU takes to boot before its first cycle
+    pub fn with_startup_delay(mut self, seconds: f64) -> Self {
+        self.startup_delay = seconds.max(0.0);
+        self
+    }
+
+    // Components run in the order they were added
+    pub fn with_component(self, name: &str, component: impl Component + Send + 'static) -> Self {
+        self.with_component_requiring(name, &[], component)
+    }
+
+    // A component that starts only after the named components, which may
+    // belong to any ECU of the network
+    pub fn with_component_requiring(
+        mut self,
+        name: &str,
+        requires: &[&str],
+        component: impl Component + Send + 'static,
+    ) -> Self {
+        self.components.push(Slot {
+            name: name.to_string(),
+            requires: requires.iter().map(|name| name.to_string()).collect(),
+            component: Some(Box::new(component)),
+        });
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn domain(&self) -> Domain {
+        self.domain
+    }
+
+    pub fn cycle(&self) -> f64 {
+        self.cycle
+    }
+
+    pub fn bus(&self) -> &Arc<CanBus> {
+        &self.bus
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // Counted from the boot so rounding does not add up over long runs
+    fn next_cycle_at(&self) -> f64 {
+        self.startup_delay + self.cycles as f64 * self.cycle
+    }
+
+    fn run_cycle(&mut self) {
+        if self.cycles == 0 {
+            sim_log::event(
+                None,
+                &Event::ModeChanged {
+                    component: self.name.clone(),
+                    mode: format!("running ({:?}, {} ms cycle)", self.domain, self.cycle * 1000.0),
+                },
+            );
+        }
+        for component in self.components.iter_mut().filter_map(|slot| slot.component.as_mut()) {
+            component.step(self.cycle);
+        }
+        self.cycles += 1;
+    }
+
+    fn report(&self) -> String {
+        let reports: Vec<String> = self
+            .components
+            .iter()
+            .filter_map(|slot| slot.component.as_ref())
+            .map(|component| component.report())
+            .collect();
+        format!("[{}] {}", self.name, reports.join(" | "))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EcuStatus {
+    pub name: String,
+    pub domain: Domain,
+    pub cycles: u64,
+}
+
+// Virtual ECUs sharing simulated time, each on its own cycle. Stepping the
+// network by `dt` runs every ECU cycle that falls due within it, earliest
+// first, so a 10 ms Chassis ECU runs ten times for each 100 ms Body cycle.
+// Networks cannot be checkpointed, their components are not serializable
+// as a whole.
+#[derive(Default)]
+pub struct EcuNetwork {
+    ecus: Vec<VirtualEcu>,
+    time: f64,
+    // (ECU, component) indices in the order `start` brought them up
+    started: Vec<(usize, usize)>,
+}
+
+impl EcuNetwork {
+    pub fn new() -> Self {
+        EcuNetwork::default()
+    }
+
+    pub fn with_ecu(mut self, ecu: VirtualEcu) -> Self {
+        self.ecus.push(ecu);
+        // Stable, so ECUs with the same order keep the order they were added in
+        self.ecus.sort_by_key(|ecu| ecu.startup_order);
+        self
+    }
+
+    pub fn ecus(&self) -> &[VirtualEcu] {
+        &self.ecus
+    }
+
+    pub fn ecu(&self, name: &str) -> Option<&VirtualEcu> {
+        self.ecus.iter().find(|ecu| ecu.name == name)
+    }
+
+    fn slots(&self) -> Vec<(usize, usize)> {
+        self.ecus
+            .iter()
+            .enumerate()
+            .flat_map(|(e, ecu)| (0..ecu.components.len()).map(move |c| (e, c)))
+            .collect()
+    }
+
+    // Starts every component after the ones it requires and returns their
+    // names in startup order. Missing or circular dependencies are reported
+    // before anything starts; when a component fails, the ones already
+    // started are stopped again.
+    pub fn start(&mut self, stop_timeout: Duration) -> Result<Vec<String>, LifecycleError> {
+        let slots = self.slots();
+        let units: Vec<(String, Vec<String>)> = slots
+            .iter()
+            .map(|&(e, c)| {
+                let slot = &self.ecus[e].components[c];
+                (slot.name.clone(), slot.requires.clone())
+            })
+            .collect();
+        let order = lifecycle::startup_order(&units)?;
+
+        for i in order.iter().copied() {
+            let (e, c) = slots[i];
+            let ecu = &mut self.ecus[e];
+            let slot = &mut ecu.components[c];
+            let Some(component) = slot.component.as_mut() else {
+                continue;
+            };
+            if let Err(message) = component.start() {
+                let unit = slot.name.clone();
+                let started = std::mem::take(&mut self.started);
+                self.stop(started, stop_timeout);
+                return Err(LifecycleError::StartFailed { unit, message });
+            }
+            sim_log::info("lifecycle", &format!("Started {} on {}", slot.name, ecu.name));
+            self.started.push((e, c));
+        }
+        Ok(order.into_iter().map(|i| units[i].0.clone()).collect())
+    }
+
+    // Stops the components in reverse startup order (reverse registration
+    // order for a network that was never started), giving each `timeout`.
+    // Returns the components that did not stop in time.
+    pub fn shutdown(&mut self, timeout: Duration) -> Vec<String> {
+        let mut order = std::mem::take(&mut self.started);
+        if order.is_empty() {
+            order = self.slots();
+        }
+        self.stop(order, timeout)
+    }
+
+    fn stop(&mut self, order: Vec<(usize, usize)>, timeout: Duration) -> Vec<String> {
+        let mut timed_out = Vec::new();
+        for (e, c) in order.into_iter().rev() {
+            let slot = &mut self.ecus[e].components[c];
+            let Some(component) = slot.component.take() else {
+                continue;
+            };
+            if lifecycle::stop_with_timeout(component, timeout, |component| component.stop()) {
+                sim_log::info("lifecycle", &format!("Stopped {}", slot.name));
+            } else {
+                sim_log::warn(
+                    "lifecycle",
+                    &format!("{} did not stop within {} ms, continuing the shutdown", slot.name, timeout.as_millis()),
+                );
+                timed_out.push(slot.name.clone());
+            }
+        }
+        timed_out
+    }
+
+    // Next ECU due before `until`; ties go to the earlier startup order
+    fn next_due(&self, until: f64) -> Option<usize> {
+        self.ecus
+            .iter()
+            .enumerate()
+            .filter(|(_, ecu)| ecu.cycle > 0.0 && ecu.next_cycle_at() < until - 1e-9)
+            .min_by(|(_, a), (_, b)| {
+                let (a, b) = (a.next_cycle_at(), b.next_cycle_at());
+                if (a - b).abs() < 1e-9 {
+                    Ordering::Equal
+                } else {
+                    a.total_cmp(&b)
+                }
+            })
+            .map(|(i, _)| i)
+    }
+}
+
+impl Simulation for EcuNetwork {
+    type State = Vec<EcuStatus>;
+
+    fn step(&mut self, dt: f64) {
+        let until = self.time + dt;
+        while let Some(i) = self.next_due(until) {
+            self.ecus[i].run_cycle();
+        }
+        self.time = until;
+    }
+
+    fn state(&self) -> Vec<EcuStatus> {
+        self.ecus
+            .iter()
+            .map(|ecu| EcuStatus {
+                name: ecu.name.clone(),
+                domain: ecu.domain,
+                cycles: ecu.cycles,
+            })
+            .collect()
+    }
+
+    // ECUs still booting are left out
+    fn report(&self) -> String {
+        let reports: Vec<String> = self.ecus.iter().filter(|ecu| ecu.cycles > 0).map(VirtualEcu::report).collect();
+        reports.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Writes what happens to it into a log shared by all probes
+    struct Probe {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        fail_start: bool,
+    }
+
+    impl Component for Probe {
+        fn step(&mut self, _dt: f64) {
+            self.log.lock().unwrap().push(format!("step {}", self.name));
+        }
+
+        fn report(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn start(&mut self) -> Result<(), String> {
+            if self.fail_start {
+                return Err("no power".to_string());
+            }
+            self.log.lock().unwrap().push(format!("start {}", self.name));
+            Ok(())
+        }
+
+        fn stop(&mut self) {
+            self.log.lock().unwrap().push(format!("stop {}", self.name));
+        }
+    }
+
+    fn probe(name: &'static str, log: &Arc<Mutex<Vec<String>>>) -> Probe {
+        Probe {
+            name,
+            log: Arc::clone(log),
+            fail_start: false,
+        }
+    }
+
+    fn ecu(name: &str, cycle: f64) -> VirtualEcu {
+        VirtualEcu::new(name, Domain::Body, cycle, Arc::new(CanBus::new()))
+    }
+
+    fn taken(log: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+        std::mem::take(&mut *log.lock().unwrap())
+    }
+
+    #[test]
+    fn components_start_across_ecus_and_stop_in_reverse() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut network = EcuNetwork::new()
+            .with_ecu(ecu("cluster", 0.1).with_component_requiring("display", &["gateway"], probe("display", &log)))
+            .with_ecu(ecu("gateway", 0.01).with_component("gateway", probe("gateway", &log)));
+
+        let order = network.start(Duration::from_secs(1)).unwrap();
+        assert_eq!(order, vec!["gateway", "display"]);
+        assert_eq!(taken(&log), vec!["start gateway", "start display"]);
+
+        assert!(network.shutdown(Duration::from_secs(1)).is_empty());
+        assert_eq!(taken(&log), vec!["stop display", "stop gateway"]);
+    }
+
+    #[test]
+    fn a_failed_start_stops_what_already_runs() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let broken = Probe {
+            fail_start: true,
+            ..probe("display", &log)
+        };
+        let mut network = EcuNetwork::new().with_ecu(
+            ecu("cluster", 0.1)
+                .with_component("power", probe("power", &log))
+                .with_component_requiring("display", &["power"], broken),
+        );
+
+        let error = network.start(Duration::from_secs(1)).unwrap_err();
+        assert_eq!(error, LifecycleError::StartFailed { unit: "display".into(), message: "no power".into() });
+        assert_eq!(taken(&log), vec!["start power", "stop power"]);
+    }
+
+    #[test]
+    fn missing_dependencies_are_found_before_anything_starts() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut network = EcuNetwork::new().with_ecu(
+            ecu("cluster", 0.1)
+                .with_component("power", probe("power", &log))
+                .with_component_requiring("display", &["gateway"], probe("display", &log)),
+        );
+        assert!(matches!(network.start(Duration::from_secs(1)), Err(LifecycleError::UnknownDependency { .. })));
+        assert!(taken(&log).is_empty());
+    }
+
+    #[test]
+    fn ecus_run_their_own_cycles_once_booted() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut network = EcuNetwork::new()
+            .with_ecu(ecu("body", 0.1).with_startup_order(1).with_component("body", probe("body", &log)))
+            .with_ecu(ecu("chassis", 0.01).with_startup_delay(0.05).with_component("chassis", probe("chassis", &log)));
+
+        Simulation::step(&mut network, 0.1);
+        let cycles: Vec<u64> = network.state().iter().map(|status| status.cycles).collect();
+        // Sorted by startup order: chassis first, booting for half the step
+        assert_eq!(network.ecus()[0].name(), "chassis");
+        assert_eq!(cycles, vec![5, 1]);
+        Simulation::step(&mut network, 0.1);
+        assert_eq!(network.ecu("chassis").unwrap().cycles(), 15);
+        assert_eq!(network.ecu("body").unwrap().cycles(), 2);
+    }
+}
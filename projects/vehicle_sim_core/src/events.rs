@@ -0,0 +1,242 @@
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::config::LogLevel;
+use crate::locale;
+use crate::sim_log;
+
+// Discrete things that happen during a run, as opposed to the periodic
+// signal values published every step
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    WarningRaised { source: String, message: String },
+    DtcSet { code: String, description: String },
+    ModeChanged { component: String, mode: String },
+    TemperatureReached { zone: String, temperature: f32 },
+    ConditionChanged { condition: String, traction: f32 },
+    // Peak wind speed and its component across the road, in m/s
+    WindGust { speed: f64, crosswind: f64 },
+    // Traction control cut the engine torque by `torque_cut` (0..1) to hold
+    // the slip of the driven axle
+    TractionControl { axle: String, slip: f32, torque_cut: f32 },
+    // ESC braked a single wheel against a yaw rate error in deg/s, positive
+    // when the car turns more than steered (oversteer)
+    YawCorrection { yaw_error: f32, wheel: String },
+    // A scheduled scenario command ran; the step is the one it was scheduled for
+    ScenarioStepReached { command: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Warning,
+    Dtc,
+    Mode,
+    Temperature,
+    Condition,
+    Stability,
+    Scenario,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::WarningRaised { .. } => EventKind::Warning,
+            Event::DtcSet { .. } => EventKind::Dtc,
+            Event::ModeChanged { .. } => EventKind::Mode,
+            Event::TemperatureReached { .. } => EventKind::Temperature,
+            Event::ConditionChanged { .. } | Event::WindGust { .. } => EventKind::Condition,
+            Event::TractionControl { .. } | Event::YawCorrection { .. } => EventKind::Stability,
+            Event::ScenarioStepReached { .. } => EventKind::Scenario,
+        }
+    }
+
+    pub fn level(&self) -> LogLevel {
+        match self {
+            Event::WarningRaised { .. } | Event::DtcSet { .. } | Event::YawCorrection { .. } => LogLevel::Warn,
+            _ => LogLevel::Info,
+        }
+    }
+
+    // Component the event is logged for
+    pub fn source(&self) -> &str {
+        match self {
+            Event::WarningRaised { source, .. } => source,
+            Event::DtcSet { .. } => "dtc",
+            Event::ModeChanged { component, .. } => component,
+            Event::TemperatureReached { zone, .. } => zone,
+            Event::ConditionChanged { .. } => "road",
+            Event::WindGust { .. } => "wind",
+            Event::TractionControl { .. } => "tc",
+            Event::YawCorrection { .. } => "esc",
+            Event::ScenarioStepReached { .. } => "scenario",
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::WarningRaised { source, message } => write!(f, "Warning from {}: {}", source, message),
+            Event::DtcSet { code, description } => write!(f, "DTC {} set: {}", code, description),
+            Event::ModeChanged { component, mode } => write!(f, "{} switched to {}", component, mode),
+            Event::TemperatureReached { zone, temperature } => {
+                write!(f, "{} reached {}", zone, locale::current().temperature(*temperature, 1))
+            }
+            Event::ConditionChanged { condition, traction } => {
+                write!(f, "Road condition changed to {} (traction {:.2})", condition, traction)
+            }
+            Event::WindGust { speed, crosswind } => {
+                let locale = locale::current();
+                write!(
+                    f,
+                    "Wind gust of {} m/s, {} m/s across the road",
+                    locale.number(*speed, 1),
+                    locale.number(crosswind.abs(), 1)
+                )
+            }
+            Event::TractionControl { axle, slip, torque_cut } => {
+                let locale = locale::current();
+                write!(
+                    f,
+                    "Traction control: {} axle slipping at {}%, engine torque cut by {}%",
+                    axle,
+                    locale.number(*slip as f64 * 100.0, 0),
+                    locale.number(*torque_cut as f64 * 100.0, 0)
+                )
+            }
+            Event::YawCorrection { yaw_error, wheel } => {
+                let locale = locale::current();
+                let behaviour = if *yaw_error > 0.0 { "oversteer" } else { "understeer" };
+                write!(
+                    f,
+                    "ESC: {} of {} deg/s, braking the {} wheel",
+                    behaviour,
+                    locale.number(yaw_error.abs() as f64, 1),
+                    wheel
+                )
+            }
+            Event::ScenarioStepReached { command } => write!(f, "Scenario step reached: {}", command),
+        }
+    }
+}
+
+// An event stamped with the simulation step it happened in
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimedEvent {
+    pub step: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+// Which events a subscriber receives; an empty filter receives everything
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    kinds: Vec<EventKind>,
+}
+
+impl EventFilter {
+    pub fn all() -> Self {
+        EventFilter::default()
+    }
+
+    pub fn kinds(kinds: &[EventKind]) -> Self {
+        EventFilter { kinds: kinds.to_vec() }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&event.kind())
+    }
+}
+
+// Fans events out to subscribers, each with its own filter, and logs them
+// through `sim_log`. Subscribers that dropped their receiver are removed
+// the next time an event passes their filter.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<(EventFilter, Sender<TimedEvent>)>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus::default()
+    }
+
+    pub fn subscribe(&self, filter: EventFilter) -> Receiver<TimedEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push((filter, sender));
+        receiver
+    }
+
+    pub fn publish(&self, step: u64, event: Event) {
+        sim_log::event(Some(step), &event);
+        let event = TimedEvent { step, event };
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|(filter, sender)| !filter.matches(&event.event) || sender.send(event.clone()).is_ok());
+    }
+}
+
+// Prints the events a subscriber collected during the run
+pub fn print_timeline(events: &Receiver<TimedEvent>) {
+    let events: Vec<TimedEvent> = events.try_iter().collect();
+    if events.is_empty() {
+        return;
+    }
+
+    println!("Event timeline:");
+    for event in events {
+        println!("  step {}: {}", event.step, event.event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warning(message: &str) -> Event {
+        Event::WarningRaised {
+            source: "test".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn dtc() -> Event {
+        Event::DtcSet {
+            code: "C0750".to_string(),
+            description: "low pressure".to_string(),
+        }
+    }
+
+    #[test]
+    fn subscribers_get_only_the_events_they_filter_for() {
+        let bus = EventBus::new();
+        let everything = bus.subscribe(EventFilter::all());
+        let dtcs = bus.subscribe(EventFilter::kinds(&[EventKind::Dtc]));
+        bus.publish(1, warning("low"));
+        bus.publish(2, dtc());
+
+        let steps: Vec<u64> = everything.try_iter().map(|event| event.step).collect();
+        assert_eq!(steps, vec![1, 2]);
+        let received: Vec<TimedEvent> = dtcs.try_iter().collect();
+        assert_eq!(received, vec![TimedEvent { step: 2, event: dtc() }]);
+    }
+
+    #[test]
+    fn dropped_receivers_are_pruned() {
+        let bus = EventBus::new();
+        let kept = bus.subscribe(EventFilter::all());
+        drop(bus.subscribe(EventFilter::all()));
+        drop(bus.subscribe(EventFilter::kinds(&[EventKind::Dtc])));
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 3);
+
+        // A warning reaches the dropped catch-all subscriber, not the DTC one
+        bus.publish(1, warning("low"));
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 2);
+        bus.publish(2, dtc());
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 1);
+        assert_eq!(kept.try_iter().count(), 2);
+    }
+}
@@ -0,0 +1,324 @@
+use crate::units::GRAVITY;
+
+// Longitudinal dynamics of a whole fleet in one pass. Each quantity of every
+// vehicle sits in its own array, so a step walks a handful of contiguous
+// slices with the same arithmetic for every vehicle, which the compiler
+// turns into vector instructions. With the `simd` feature (nightly only) the
+// step is written with std::simd instead of relying on the auto-vectorizer.
+
+// What sets a vehicle apart from the others in the fleet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VehicleParameters {
+    pub mass: f64, // kg
+    // Rolling resistance coefficient
+    pub rolling_resistance: f64,
+    // Half the air density times drag coefficient and frontal area
+    pub drag: f64, // N/(m/s)²
+    // Largest force the drivetrain puts on the road, and the brakes
+    pub max_drive_force: f64, // N
+    pub max_brake_force: f64, // N
+}
+
+impl Default for VehicleParameters {
+    // A mid-size car
+    fn default() -> Self {
+        VehicleParameters {
+            mass: 1500.0,
+            rolling_resistance: 0.012,
+            drag: 0.5 * 1.2 * 0.3 * 2.2,
+            max_drive_force: 4500.0,
+            max_brake_force: 12000.0,
+        }
+    }
+}
+
+// One vehicle of the fleet on its own, the way it goes in and comes out
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FleetVehicle {
+    pub parameters: VehicleParameters,
+    pub speed: f64, // m/s
+    pub distance: f64, // m
+    // Work the drivetrain has done at the wheels
+    pub drive_energy: f64, // J
+}
+
+impl FleetVehicle {
+    pub fn new(parameters: VehicleParameters, speed: f64) -> Self {
+        FleetVehicle {
+            parameters,
+            speed,
+            ..FleetVehicle::default()
+        }
+    }
+
+    // `pedal` runs from -1 (full brake) to 1 (full throttle), `grade` is the
+    // rise over run of the road
+    pub fn step(&mut self, pedal: f64, grade: f64, dt: f64) {
+        let p = &self.parameters;
+        let (speed, distance, energy) = advance(
+            p.mass,
+            p.rolling_resistance,
+            p.drag,
+            p.max_drive_force,
+            p.max_brake_force,
+            self.speed,
+            pedal,
+            grade,
+            dt,
+        );
+        self.speed = speed;
+        self.distance += distance;
+        self.drive_energy += energy;
+    }
+}
+
+// The step of one vehicle, without branches so that it vectorizes: the new
+// speed, and the distance covered and drive energy spent during the step.
+// A braking vehicle stops instead of rolling backwards.
+#[allow(clippy::too_many_arguments)]
+#[inline(always)]
+fn advance(
+    mass: f64,
+    rolling_resistance: f64,
+    drag: f64,
+    max_drive_force: f64,
+    max_brake_force: f64,
+    speed: f64,
+    pedal: f64,
+    grade: f64,
+    dt: f64,
+) -> (f64, f64, f64) {
+    let drive = pedal.max(0.0) * max_drive_force;
+    let brake = pedal.min(0.0) * max_brake_force;
+    let resistance = mass * GRAVITY * (rolling_resistance + grade) + drag * speed * speed;
+    let next = (speed + (drive + brake - resistance) / mass * dt).max(0.0);
+    (next, 0.5 * (speed + next) * dt, drive * speed * dt)
+}
+
+// Every vehicle of the fleet, one array per quantity
+#[derive(Debug, Clone, Default)]
+pub struct Fleet {
+    mass: Vec<f64>,
+    rolling_resistance: Vec<f64>,
+    drag: Vec<f64>,
+    max_drive_force: Vec<f64>,
+    max_brake_force: Vec<f64>,
+    speed: Vec<f64>,
+    distance: Vec<f64>,
+    drive_energy: Vec<f64>,
+}
+
+impl Fleet {
+    pub fn new() -> Self {
+        Fleet::default()
+    }
+
+    pub fn push(&mut self, vehicle: FleetVehicle) {
+        let p = vehicle.parameters;
+        self.mass.push(p.mass);
+        self.rolling_resistance.push(p.rolling_resistance);
+        self.drag.push(p.drag);
+        self.max_drive_force.push(p.max_drive_force);
+        self.max_brake_force.push(p.max_brake_force);
+        self.speed.push(vehicle.speed);
+        self.distance.push(vehicle.distance);
+        self.drive_energy.push(vehicle.drive_energy);
+    }
+
+    pub fn len(&self) -> usize {
+        self.speed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.speed.is_empty()
+    }
+
+    pub fn vehicle(&self, index: usize) -> FleetVehicle {
+        FleetVehicle {
+            parameters: VehicleParameters {
+                mass: self.mass[index],
+                rolling_resistance: self.rolling_resistance[index],
+                drag: self.drag[index],
+                max_drive_force: self.max_drive_force[index],
+                max_brake_force: self.max_brake_force[index],
+            },
+            speed: self.speed[index],
+            distance: self.distance[index],
+            drive_energy: self.drive_energy[index],
+        }
+    }
+
+    pub fn speeds(&self) -> &[f64] {
+        &self.speed
+    }
+
+    // Moves every vehicle on by `dt` s with its pedal and the grade it is on,
+    // both one per vehicle in the order they were pushed
+    pub fn step(&mut self, pedal: &[f64], grade: &[f64], dt: f64) {
+        assert!(
+            pedal.len() == self.len() && grade.len() == self.len(),
+            "one pedal and grade per vehicle"
+        );
+        #[cfg(feature = "simd")]
+        let done = self.step_simd(pedal, grade, dt);
+        #[cfg(not(feature = "simd"))]
+        let done = 0;
+        self.step_scalar(done, pedal, grade, dt);
+    }
+
+    // From vehicle `start` on; the slices are cut to the same length first so
+    // the loop carries no bounds checks
+    fn step_scalar(&mut self, start: usize, pedal: &[f64], grade: &[f64], dt: f64) {
+        let n = self.len();
+        let (mass, rolling_resistance, drag) = (&self.mass[start..n], &self.rolling_resistance[start..n], &self.drag[start..n]);
+        let (max_drive_force, max_brake_force) = (&self.max_drive_force[start..n], &self.max_brake_force[start..n]);
+        let (speed, distance, drive_energy) = (&mut self.speed[start..n], &mut self.distance[start..n], &mut self.drive_energy[start..n]);
+        let (pedal, grade) = (&pedal[start..n], &grade[start..n]);
+        for i in 0..n - start {
+            let (next, covered, energy) = advance(
+                mass[i],
+                rolling_resistance[i],
+                drag[i],
+                max_drive_force[i],
+                max_brake_force[i],
+                speed[i],
+                pedal[i],
+                grade[i],
+                dt,
+            );
+            speed[i] = next;
+            distance[i] += covered;
+            drive_energy[i] += energy;
+        }
+    }
+
+    // Whole vectors of LANES vehicles; returns how many it moved, the rest
+    // are left to the scalar loop
+    #[cfg(feature = "simd")]
+    fn step_simd(&mut self, pedal: &[f64], grade: &[f64], dt: f64) -> usize {
+        use std::simd::prelude::*;
+
+        const LANES: usize = 4;
+        type Lanes = Simd<f64, LANES>;
+
+        let load = |values: &[f64], i: usize| Lanes::from_slice(&values[i..i + LANES]);
+        let (zero, half, gravity, dt) = (Lanes::splat(0.0), Lanes::splat(0.5), Lanes::splat(GRAVITY), Lanes::splat(dt));
+        let done = self.len() / LANES * LANES;
+        for i in (0..done).step_by(LANES) {
+            let (mass, speed, pedal) = (load(&self.mass, i), load(&self.speed, i), load(pedal, i));
+            let drive = pedal.simd_max(zero) * load(&self.max_drive_force, i);
+            let brake = pedal.simd_min(zero) * load(&self.max_brake_force, i);
+            let drag = load(&self.drag, i);
+            let resistance = mass * gravity * (load(&self.rolling_resistance, i) + load(grade, i)) + drag * speed * speed;
+            let next = (speed + (drive + brake - resistance) / mass * dt).simd_max(zero);
+            next.copy_to_slice(&mut self.speed[i..i + LANES]);
+            (load(&self.distance, i) + half * (speed + next) * dt).copy_to_slice(&mut self.distance[i..i + LANES]);
+            (load(&self.drive_energy, i) + drive * speed * dt).copy_to_slice(&mut self.drive_energy[i..i + LANES]);
+        }
+        done
+    }
+}
+
+impl FromIterator<FleetVehicle> for Fleet {
+    fn from_iter<I: IntoIterator<Item = FleetVehicle>>(vehicles: I) -> Self {
+        let mut fleet = Fleet::new();
+        for vehicle in vehicles {
+            fleet.push(vehicle);
+        }
+        fleet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every vehicle a little different, so no two lanes step alike
+    fn vehicles(count: usize) -> (Vec<FleetVehicle>, Vec<f64>, Vec<f64>) {
+        let vehicles = (0..count)
+            .map(|i| {
+                let parameters = VehicleParameters {
+                    mass: 1200.0 + 37.0 * i as f64,
+                    ..VehicleParameters::default()
+                };
+                FleetVehicle::new(parameters, (i % 5) as f64 * 6.0)
+            })
+            .collect();
+        let pedal = (0..count).map(|i| ((i % 7) as f64 - 3.0) / 3.0).collect();
+        let grade = (0..count).map(|i| ((i % 11) as f64 - 5.0) / 100.0).collect();
+        (vehicles, pedal, grade)
+    }
+
+    #[test]
+    fn a_vehicle_accelerates_coasts_down_and_stops_braking() {
+        let mut vehicle = FleetVehicle::new(VehicleParameters::default(), 0.0);
+        vehicle.step(1.0, 0.0, 1.0);
+        // 4500 N less rolling resistance of 1500 kg
+        let expected = (4500.0 - 1500.0 * GRAVITY * 0.012) / 1500.0;
+        assert!((vehicle.speed - expected).abs() < 1e-12);
+        assert!((vehicle.distance - 0.5 * expected).abs() < 1e-12);
+        // No work is done from standstill within the step
+        assert_eq!(vehicle.drive_energy, 0.0);
+
+        let mut coasting = FleetVehicle::new(VehicleParameters::default(), 30.0);
+        coasting.step(0.0, 0.0, 1.0);
+        assert!(coasting.speed < 30.0 && coasting.speed > 29.0);
+        assert_eq!(coasting.drive_energy, 0.0);
+
+        let mut braking = FleetVehicle::new(VehicleParameters::default(), 5.0);
+        braking.step(-1.0, 0.0, 1.0);
+        assert_eq!(braking.speed, 0.0);
+        braking.step(-1.0, 0.1, 1.0);
+        assert_eq!((braking.speed, braking.distance), (0.0, 2.5));
+    }
+
+    #[test]
+    fn the_fleet_steps_like_its_vehicles_one_at_a_time() {
+        let (mut vehicles, pedal, grade) = vehicles(11);
+        let mut fleet: Fleet = vehicles.iter().copied().collect();
+        assert_eq!(fleet.len(), 11);
+        for _ in 0..20 {
+            fleet.step(&pedal, &grade, 0.1);
+            for ((vehicle, &pedal), &grade) in vehicles.iter_mut().zip(&pedal).zip(&grade) {
+                vehicle.step(pedal, grade, 0.1);
+            }
+        }
+        for (i, vehicle) in vehicles.iter().enumerate() {
+            let stepped = fleet.vehicle(i);
+            for (a, b) in [(stepped.speed, vehicle.speed), (stepped.distance, vehicle.distance), (stepped.drive_energy, vehicle.drive_energy)] {
+                assert!((a - b).abs() <= 1e-9 * b.abs().max(1.0), "vehicle {}: {} against {}", i, a, b);
+            }
+            assert_eq!(stepped.parameters, vehicle.parameters);
+        }
+        assert_eq!(fleet.speeds()[3], fleet.vehicle(3).speed);
+    }
+
+    #[test]
+    #[should_panic(expected = "one pedal and grade per vehicle")]
+    fn every_vehicle_needs_a_pedal_and_grade() {
+        let (vehicles, pedal, grade) = vehicles(4);
+        let mut fleet: Fleet = vehicles.into_iter().collect();
+        fleet.step(&pedal[..3], &grade, 0.1);
+    }
+
+    #[cfg(all(test, feature = "simd"))]
+    #[test]
+    fn simd_steps_match_the_scalar_ones() {
+        // Not a multiple of the lanes, so the scalar loop finishes the rest
+        let (vehicles, pedal, grade) = vehicles(103);
+        let mut simd: Fleet = vehicles.into_iter().collect();
+        let mut scalar = simd.clone();
+        for _ in 0..50 {
+            let done = simd.step_simd(&pedal, &grade, 0.1);
+            assert_eq!(done, 100);
+            simd.step_scalar(done, &pedal, &grade, 0.1);
+            scalar.step_scalar(0, &pedal, &grade, 0.1);
+        }
+        for i in 0..scalar.len() {
+            let (a, b) = (simd.vehicle(i), scalar.vehicle(i));
+            for (x, y) in [(a.speed, b.speed), (a.distance, b.distance), (a.drive_energy, b.drive_energy)] {
+                assert!((x - y).abs() <= 1e-9 * y.abs().max(1.0), "vehicle {}: {} against {}", i, x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::thread;
+
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use crate::run_control::{ControlError, RunControl, RunState};
+use crate::sim_log;
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/vehicle_sim.control.SimControl.rs"));
+}
+
+pub use generated::sim_control_client::SimControlClient;
+pub use generated::sim_control_server::{SimControl, SimControlServer};
+
+pub const GRPC_ENV_VAR: &str = "SIM_GRPC";
+
+// The messages of the vehicle_sim.control package, as a .proto file would
+// declare them:
+//
+//     service SimControl {
+//       rpc Start(ControlRequest) returns (StateReply);
+//       rpc Pause(ControlRequest) returns (StateReply);
+//       rpc Step(ControlRequest) returns (StateReply);
+//       rpc InjectFault(FaultRequest) returns (FaultReply);
+//       rpc GetState(ControlRequest) returns (StateReply);
+//     }
+//
+// Every call carries a token as `authorization: Bearer <token>` metadata.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ControlRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FaultRequest {
+    // A simulation command, e.g. `leak fl 0.2`
+    #[prost(string, tag = "1")]
+    pub command: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FaultReply {
+    #[prost(string, tag = "1")]
+    pub queued: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StateReply {
+    #[prost(bool, tag = "1")]
+    pub paused: bool,
+    #[prost(uint64, tag = "2")]
+    pub steps: u64,
+    #[prost(double, tag = "3")]
+    pub sim_time_seconds: f64,
+    #[prost(btree_map = "string, double", tag = "4")]
+    pub signals: BTreeMap<String, f64>,
+}
+
+impl From<RunState> for StateReply {
+    fn from(state: RunState) -> Self {
+        StateReply {
+            paused: state.paused,
+            steps: state.steps,
+            sim_time_seconds: state.sim_time_seconds,
+            signals: state.signals,
+        }
+    }
+}
+
+impl From<ControlError> for Status {
+    fn from(e: ControlError) -> Self {
+        match e {
+            ControlError::Unauthorized(_) => Status::unauthenticated(e.to_string()),
+            ControlError::InvalidArgument(_) => Status::invalid_argument(e.to_string()),
+            ControlError::Finished => Status::unavailable(e.to_string()),
+        }
+    }
+}
+
+// SimControl over the same run control as the dashboard's `/control/...`
+struct ControlService {
+    control: Arc<RunControl>,
+}
+
+impl ControlService {
+    fn authorize<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().strip_prefix("Bearer "))
+            .map(str::trim);
+        Ok(self.control.authorize(token)?)
+    }
+
+    fn state(&self) -> Result<Response<StateReply>, Status> {
+        Ok(Response::new(self.control.state().into()))
+    }
+}
+
+#[tonic::async_trait]
+impl SimControl for ControlService {
+    async fn start(&self, request: Request<ControlRequest>) -> Result<Response<StateReply>, Status> {
+        self.authorize(&request)?;
+        self.control.start();
+        self.state()
+    }
+
+    async fn pause(&self, request: Request<ControlRequest>) -> Result<Response<StateReply>, Status> {
+        self.authorize(&request)?;
+        self.control.pause();
+        self.state()
+    }
+
+    async fn step(&self, request: Request<ControlRequest>) -> Result<Response<StateReply>, Status> {
+        self.authorize(&request)?;
+        self.control.step();
+        self.state()
+    }
+
+    async fn inject_fault(&self, request: Request<FaultRequest>) -> Result<Response<FaultReply>, Status> {
+        self.authorize(&request)?;
+        let command = request.into_inner().command;
+        self.control.inject_fault(&command)?;
+        Ok(Response::new(FaultReply {
+            queued: command.trim().to_string(),
+        }))
+    }
+
+    async fn get_state(&self, request: Request<ControlRequest>) -> Result<Response<StateReply>, Status> {
+        self.authorize(&request)?;
+        self.state()
+    }
+}
+
+// Serves SimControl on `address` from a thread of its own, next to the
+// simulation's steps; returns the address it listens on
+pub fn serve(address: &str, control: Arc<RunControl>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    let local_address = listener.local_addr()?;
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+
+    thread::spawn(move || {
+        let served = runtime.block_on(async {
+            let incoming = TcpIncoming::from(tokio::net::TcpListener::from_std(listener)?);
+            Server::builder()
+                .add_service(SimControlServer::new(ControlService { control }))
+                .serve_with_incoming(incoming)
+                .await
+                .map_err(io::Error::other)
+        });
+        if let Err(e) = served {
+            sim_log::warn("grpc", &format!("SimControl service stopped: {}", e));
+        }
+    });
+    Ok(local_address)
+}
+
+// Uses `--grpc <address>` from the command line, then SIM_GRPC
+pub fn address_from_args() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--grpc")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| std::env::var(GRPC_ENV_VAR).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::Receiver;
+
+    use tonic::Code;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::remote::TokenStore;
+    use crate::simulation::PauseControl;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn start() -> (Arc<RunControl>, Receiver<String>, SocketAddr) {
+        let tokens = TokenStore::from_config(&Config::parse(&format!("token.ci = {}", TOKEN)).unwrap()).unwrap();
+        let (control, faults) = RunControl::new(Arc::new(PauseControl::new()), tokens);
+        let address = serve("127.0.0.1:0", control.clone()).unwrap();
+        (control, faults, address)
+    }
+
+    fn request<T>(message: T, token: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+        request
+    }
+
+    #[test]
+    fn drives_a_run_over_grpc() {
+        let (control, faults, address) = start();
+        control.update(4, 2.0, &[("front_left", 32.5)]);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let mut client = SimControlClient::connect(format!("http://{}", address)).await.unwrap();
+
+            let paused = client.pause(request(ControlRequest {}, TOKEN)).await.unwrap().into_inner();
+            assert!(paused.paused);
+            assert_eq!((paused.steps, paused.sim_time_seconds), (4, 2.0));
+            assert_eq!(paused.signals.get("front_left"), Some(&32.5));
+            assert!(client.step(request(ControlRequest {}, TOKEN)).await.unwrap().into_inner().paused);
+
+            let fault = FaultRequest {
+                command: "leak fl 0.2".to_string(),
+            };
+            let queued = client.inject_fault(request(fault, TOKEN)).await.unwrap().into_inner();
+            assert_eq!(queued.queued, "leak fl 0.2");
+            assert_eq!(faults.try_recv(), Ok("leak fl 0.2".to_string()));
+            let empty = client.inject_fault(request(FaultRequest::default(), TOKEN)).await.unwrap_err();
+            assert_eq!(empty.code(), Code::InvalidArgument);
+
+            assert!(!client.start(request(ControlRequest {}, TOKEN)).await.unwrap().into_inner().paused);
+            assert!(!client.get_state(request(ControlRequest {}, TOKEN)).await.unwrap().into_inner().paused);
+        });
+    }
+
+    #[test]
+    fn calls_without_a_valid_token_are_refused() {
+        let (control, _faults, address) = start();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let mut client = SimControlClient::connect(format!("http://{}", address)).await.unwrap();
+            let refused = client.pause(ControlRequest {}).await.unwrap_err();
+            assert_eq!(refused.code(), Code::Unauthenticated);
+            let refused = client.pause(request(ControlRequest {}, "fedcba9876543210")).await.unwrap_err();
+            assert_eq!(refused.code(), Code::Unauthenticated);
+        });
+        assert!(!control.state().paused);
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+
+use crate::can_bus::{CanBus, CanFilter, CanFrame};
+
+// Unused bytes of a frame are padded like most ECUs do
+const PADDING: u8 = 0xAA;
+
+const SINGLE_FRAME: u8 = 0x0;
+const FIRST_FRAME: u8 = 0x1;
+const CONSECUTIVE_FRAME: u8 = 0x2;
+const FLOW_CONTROL: u8 = 0x3;
+
+// Largest payload a first frame can announce
+pub const MAX_PAYLOAD: usize = 0xFFF;
+
+struct Reassembly {
+    length: usize,
+    data: Vec<u8>,
+    sequence: u8,
+}
+
+struct Transmission {
+    data: Vec<u8>,
+    offset: usize,
+    sequence: u8,
+}
+
+// ISO-TP (ISO 15765-2) transport between two CAN identifiers: segments
+// payloads longer than a single frame and reassembles the other side's.
+// There is no timing on an in-process bus, so flow control always allows
+// the whole message in one block.
+pub struct IsoTpChannel {
+    bus: Arc<CanBus>,
+    tx_id: u32,
+    frames: Receiver<CanFrame>,
+    receiving: Option<Reassembly>,
+    sending: Option<Transmission>,
+}
+
+impl IsoTpChannel {
+    pub fn open(bus: Arc<CanBus>, tx_id: u32, rx_id: u32) -> Self {
+        let frames = bus.subscribe(CanFilter::id(rx_id));
+        IsoTpChannel {
+            bus,
+            tx_id,
+            frames,
+            receiving: None,
+            sending: None,
+        }
+    }
+
+    // Payloads of up to seven bytes go out at once, longer ones after the
+    // receiver's flow control arrives in a later `poll`
+    pub fn send(&mut self, payload: &[u8]) {
+        let payload = &payload[..payload.len().min(MAX_PAYLOAD)];
+        if payload.len() <= 7 {
+            let mut frame = vec![(SINGLE_FRAME << 4) | payload.len() as u8];
+            frame.extend_from_slice(payload);
+            self.transmit(frame);
+            return;
+        }
+
+        let mut frame = vec![(FIRST_FRAME << 4) | (payload.len() >> 8) as u8, payload.len() as u8];
+        frame.extend_from_slice(&payload[..6]);
+        self.transmit(frame);
+        self.sending = Some(Transmission {
+            data: payload.to_vec(),
+            offset: 6,
+            sequence: 1,
+        });
+    }
+
+    // Handles the frames received since the last call and returns a
+    // payload once it is complete
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        while let Ok(frame) = self.frames.try_recv() {
+            let data = frame.payload();
+            let Some(&pci) = data.first() else {
+                continue;
+            };
+            match pci >> 4 {
+                SINGLE_FRAME => {
+                    let length = (pci & 0x0F) as usize;
+                    if length > 0 && length < data.len() {
+                        return Some(data[1..=length].to_vec());
+                    }
+                }
+                FIRST_FRAME if data.len() == 8 => {
+                    let length = ((pci as usize & 0x0F) << 8) | data[1] as usize;
+                    self.receiving = Some(Reassembly {
+                        length,
+                        data: data[2..].to_vec(),
+                        sequence: 1,
+                    });
+                    // Continue to send: no block size limit, no separation time
+                    self.transmit(vec![FLOW_CONTROL << 4, 0, 0]);
+                }
+                CONSECUTIVE_FRAME => {
+                    let Some(reassembly) = self.receiving.as_mut() else {
+                        continue;
+                    };
+                    if pci & 0x0F != reassembly.sequence {
+                        // Lost a frame: drop the message
+                        self.receiving = None;
+                        continue;
+                    }
+                    reassembly.sequence = (reassembly.sequence + 1) & 0x0F;
+                    let missing = reassembly.length - reassembly.data.len();
+                    reassembly.data.extend_from_slice(&data[1..data.len().min(1 + missing)]);
+                    if reassembly.data.len() == reassembly.length {
+                        return self.receiving.take().map(|reassembly| reassembly.data);
+                    }
+                }
+                FLOW_CONTROL if pci & 0x0F == 0 => self.send_consecutive_frames(),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn send_consecutive_frames(&mut self) {
+        let Some(mut transmission) = self.sending.take() else {
+            return;
+        };
+        while transmission.offset < transmission.data.len() {
+            let end = (transmission.offset + 7).min(transmission.data.len());
+            let mut frame = vec![(CONSECUTIVE_FRAME << 4) | transmission.sequence];
+            frame.extend_from_slice(&transmission.data[transmission.offset..end]);
+            self.transmit(frame);
+            transmission.offset = end;
+            transmission.sequence = (transmission.sequence + 1) & 0x0F;
+        }
+    }
+
+    fn transmit(&self, mut data: Vec<u8>) {
+        data.resize(8, PADDING);
+        self.bus.send(CanFrame::new(self.tx_id, &data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> (Arc<CanBus>, IsoTpChannel, IsoTpChannel) {
+        let bus = Arc::new(CanBus::new());
+        let tester = IsoTpChannel::open(bus.clone(), 0x7E0, 0x7E8);
+        let ecu = IsoTpChannel::open(bus.clone(), 0x7E8, 0x7E0);
+        (bus, tester, ecu)
+    }
+
+    #[test]
+    fn short_payloads_go_out_in_a_single_padded_frame() {
+        let (bus, mut tester, mut ecu) = pair();
+        let frames = bus.subscribe(CanFilter::id(0x7E0));
+        tester.send(&[0x22, 0xF1, 0x90]);
+        let frame = frames.try_recv().unwrap();
+        assert_eq!(frame.payload(), &[0x03, 0x22, 0xF1, 0x90, PADDING, PADDING, PADDING, PADDING]);
+        assert_eq!(ecu.poll(), Some(vec![0x22, 0xF1, 0x90]));
+        assert_eq!(ecu.poll(), None);
+    }
+
+    #[test]
+    fn long_payloads_are_segmented_after_flow_control() {
+        let (bus, mut tester, mut ecu) = pair();
+        let frames = bus.subscribe(CanFilter::id(0x7E8));
+        let payload: Vec<u8> = (0..40).collect();
+        ecu.send(&payload);
+        let first = frames.try_recv().unwrap();
+        assert_eq!(&first.payload()[..2], &[0x10, 40]);
+        assert!(frames.try_recv().is_err(), "waits for flow control");
+
+        // The tester answers the first frame, the ECU sends the rest
+        assert_eq!(tester.poll(), None);
+        assert_eq!(ecu.poll(), None);
+        let consecutive: Vec<CanFrame> = frames.try_iter().collect();
+        assert_eq!(consecutive.len(), 5);
+        assert_eq!(consecutive.iter().map(|frame| frame.data[0]).collect::<Vec<_>>(), vec![0x21, 0x22, 0x23, 0x24, 0x25]);
+        assert_eq!(tester.poll(), Some(payload));
+    }
+
+    #[test]
+    fn a_missing_consecutive_frame_drops_the_message() {
+        let bus = Arc::new(CanBus::new());
+        let mut receiver = IsoTpChannel::open(bus.clone(), 0x7E8, 0x7E0);
+        let send = |data: &[u8]| bus.send(CanFrame::new(0x7E0, data));
+        send(&[0x10, 20, 1, 2, 3, 4, 5, 6]);
+        send(&[0x21, 7, 8, 9, 10, 11, 12, 13]);
+        send(&[0x23, 14, 15, 16, 17, 18, 19, 20]);
+        assert_eq!(receiver.poll(), None);
+        // A single frame with an impossible length is ignored as well
+        send(&[0x07, 1, 2]);
+        assert_eq!(receiver.poll(), None);
+    }
+}
@@ -0,0 +1,57 @@
+// Shared building blocks for the vehicle simulation projects
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+pub mod ambient;
+pub mod batch;
+pub mod calendar;
+pub mod calibration;
+pub mod can_bus;
+pub mod can_trace;
+pub mod clock;
+pub mod command;
+pub mod config;
+pub mod csv_export;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+pub mod description;
+pub mod drive_cycle;
+pub mod driver;
+pub mod driver_model;
+pub mod dtc;
+pub mod ecu;
+pub mod events;
+pub mod fleet;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod isotp;
+pub mod lifecycle;
+pub mod lin_bus;
+pub mod locale;
+pub mod metrics;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod obd2;
+pub mod privacy;
+pub mod remote;
+pub mod restriction;
+pub mod rng;
+pub mod run_control;
+pub mod routine;
+pub mod scenario;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+pub mod service;
+pub mod signal_store;
+pub mod sim_log;
+pub mod sim_plot;
+pub mod simulation;
+pub mod snapshot;
+pub mod storage;
+pub mod telematics;
+pub mod tire;
+pub mod uds;
+pub mod units;
+pub mod vehicle;
+pub mod vehicle_mode;
+#[cfg(feature = "dashboard")]
+pub mod websocket;
+pub mod xcp;
@@ -0,0 +1,141 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleError {
+    DuplicateUnit(String),
+    UnknownDependency { unit: String, dependency: String },
+    // The units along the cycle, the first repeated at the end
+    Cycle(Vec<String>),
+    StartFailed { unit: String, message: String },
+}
+
+impl fmt::Display for LifecycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LifecycleError::DuplicateUnit(unit) => write!(f, "{} is registered twice", unit),
+            LifecycleError::UnknownDependency { unit, dependency } => {
+                write!(f, "{} depends on {}, which is not registered", unit, dependency)
+            }
+            LifecycleError::Cycle(units) => write!(f, "dependency cycle: {}", units.join(" -> ")),
+            LifecycleError::StartFailed { unit, message } => write!(f, "{} failed to start: {}", unit, message),
+        }
+    }
+}
+
+impl Error for LifecycleError {}
+
+// Indices of `units` (name, dependencies) in an order that starts every
+// unit after its dependencies. Among units ready at the same time the one
+// registered first goes first, so the order is stable between runs.
+pub fn startup_order(units: &[(String, Vec<String>)]) -> Result<Vec<usize>, LifecycleError> {
+    let index_of = |name: &str| units.iter().position(|(unit, _)| unit == name);
+
+    let mut dependencies = Vec::with_capacity(units.len());
+    for (i, (unit, depends_on)) in units.iter().enumerate() {
+        if index_of(unit) != Some(i) {
+            return Err(LifecycleError::DuplicateUnit(unit.clone()));
+        }
+        let indices = depends_on
+            .iter()
+            .map(|dependency| {
+                index_of(dependency).ok_or_else(|| LifecycleError::UnknownDependency {
+                    unit: unit.clone(),
+                    dependency: dependency.clone(),
+                })
+            })
+            .collect::<Result<Vec<usize>, _>>()?;
+        dependencies.push(indices);
+    }
+
+    let mut started = vec![false; units.len()];
+    let mut order = Vec::with_capacity(units.len());
+    while order.len() < units.len() {
+        let ready = (0..units.len()).find(|&i| !started[i] && dependencies[i].iter().all(|&d| started[d]));
+        match ready {
+            Some(i) => {
+                started[i] = true;
+                order.push(i);
+            }
+            None => return Err(LifecycleError::Cycle(find_cycle(units, &dependencies, &started))),
+        }
+    }
+    Ok(order)
+}
+
+// Follows unstarted dependencies until a unit repeats; every unit left over
+// waits on another one, so this always ends in a cycle
+fn find_cycle(units: &[(String, Vec<String>)], dependencies: &[Vec<usize>], started: &[bool]) -> Vec<String> {
+    let mut path: Vec<usize> = Vec::new();
+    let mut current = started.iter().position(|&s| !s).unwrap_or(0);
+    while !path.contains(&current) {
+        path.push(current);
+        current = dependencies[current].iter().copied().find(|&d| !started[d]).unwrap_or(current);
+    }
+    let first = path.iter().position(|&i| i == current).unwrap_or(0);
+    path[first..]
+        .iter()
+        .chain([&current])
+        .map(|&i| units[i].0.clone())
+        .collect()
+}
+
+// Runs `stop` on its own thread and gives up waiting after `timeout`, so a
+// hanging unit cannot block the rest of a shutdown. Returns whether it
+// finished in time; one that did not is left to finish on its own.
+pub fn stop_with_timeout<T, F>(mut unit: T, timeout: Duration, stop: F) -> bool
+where
+    T: Send + 'static,
+    F: FnOnce(&mut T) + Send + 'static,
+{
+    let (done, finished) = mpsc::channel();
+    thread::spawn(move || {
+        stop(&mut unit);
+        let _ = done.send(());
+    });
+    finished.recv_timeout(timeout).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(list: &[(&str, &[&str])]) -> Vec<(String, Vec<String>)> {
+        list.iter()
+            .map(|(unit, depends_on)| (unit.to_string(), depends_on.iter().map(|d| d.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn units_start_after_their_dependencies_in_registration_order() {
+        let units = units(&[("display", &["can", "power"]), ("can", &["power"]), ("sensor", &[]), ("power", &[])]);
+        // Of the units that are ready, the one registered first starts first
+        assert_eq!(startup_order(&units).unwrap(), vec![2, 3, 1, 0]);
+        assert_eq!(startup_order(&[]).unwrap(), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn cycles_and_missing_or_duplicate_units_are_rejected() {
+        let cycle = units(&[("a", &[]), ("b", &["c"]), ("c", &["b"])]);
+        let error = startup_order(&cycle).unwrap_err();
+        assert_eq!(error, LifecycleError::Cycle(vec!["b".into(), "c".into(), "b".into()]));
+        assert_eq!(error.to_string(), "dependency cycle: b -> c -> b");
+
+        let missing = units(&[("display", &["can"])]);
+        assert_eq!(
+            startup_order(&missing).unwrap_err(),
+            LifecycleError::UnknownDependency { unit: "display".into(), dependency: "can".into() }
+        );
+        let duplicate = units(&[("can", &[]), ("can", &[])]);
+        assert_eq!(startup_order(&duplicate).unwrap_err(), LifecycleError::DuplicateUnit("can".into()));
+    }
+
+    #[test]
+    fn a_hanging_stop_is_given_up_on() {
+        assert!(stop_with_timeout(0, Duration::from_secs(5), |_| {}));
+        assert!(!stop_with_timeout(0, Duration::from_millis(10), |_| thread::sleep(Duration::from_millis(500))));
+    }
+}
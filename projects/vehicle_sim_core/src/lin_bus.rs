@@ -0,0 +1,206 @@
+use std::fmt;
+
+use crate::config::LogLevel;
+use crate::sim_log;
+
+// Time one frame slot of a schedule takes at 19.2 kbit/s, header and up to
+// four data bytes with the usual 40 % reserve
+pub const SLOT_TIME: f64 = 0.005; // s
+
+// A LIN frame: the master's header carries the 6-bit identifier with two
+// parity bits (the protected identifier), the response up to 8 data bytes
+// from whichever node publishes the frame and the enhanced (LIN 2.x)
+// checksum over both
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinFrame {
+    pub id: u8,
+    pub data: Vec<u8>,
+    pub checksum: u8,
+}
+
+impl LinFrame {
+    pub fn new(id: u8, data: &[u8]) -> LinFrame {
+        let id = id & 0x3F;
+        let data = data[..data.len().min(8)].to_vec();
+        LinFrame {
+            id,
+            checksum: checksum(protected_id(id), &data),
+            data,
+        }
+    }
+}
+
+impl fmt::Display for LinFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02X} [{}]", protected_id(self.id), self.data.len())?;
+        for byte in &self.data {
+            write!(f, " {:02X}", byte)?;
+        }
+        write!(f, " ({:02X})", self.checksum)
+    }
+}
+
+// Identifier with P0 = ID0 ^ ID1 ^ ID2 ^ ID4 in bit 6 and
+// P1 = !(ID1 ^ ID3 ^ ID4 ^ ID5) in bit 7
+pub fn protected_id(id: u8) -> u8 {
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+    (id & 0x3F) | (p0 << 6) | (p1 << 7)
+}
+
+// Inverted sum with carry over the protected identifier and the data
+pub fn checksum(protected_id: u8, data: &[u8]) -> u8 {
+    let mut sum = protected_id as u16;
+    for &byte in data {
+        sum += byte as u16;
+        if sum > 0xFF {
+            sum -= 0xFF;
+        }
+    }
+    !(sum as u8)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinError {
+    // No node answered the header, e.g. one without power
+    NoResponse(u8),
+    // More than one node answered and the response was garbled
+    Collision(u8),
+}
+
+impl fmt::Display for LinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinError::NoResponse(id) => write!(f, "no response to frame {:02X}", id),
+            LinError::Collision(id) => write!(f, "more than one node answered frame {:02X}", id),
+        }
+    }
+}
+
+// A slave node: it answers the headers of the frames it publishes and
+// reads any frame it subscribes to
+pub trait LinNode {
+    // Response data for a frame this node publishes, `None` for the others
+    fn respond(&mut self, id: u8) -> Option<Vec<u8>>;
+
+    fn receive(&mut self, id: u8, data: &[u8]);
+}
+
+// The master of a LIN cluster. Only the master starts frames: it publishes
+// some itself and polls the slave nodes for the others, and every node
+// (the master included) sees each response.
+#[derive(Debug, Default)]
+pub struct LinMaster {
+    pub frames: u64,
+    pub errors: u64,
+}
+
+impl LinMaster {
+    pub fn new() -> Self {
+        LinMaster::default()
+    }
+
+    // Sends a frame the master publishes itself
+    pub fn send<N: LinNode>(&mut self, nodes: &mut [N], id: u8, data: &[u8]) -> LinFrame {
+        let frame = LinFrame::new(id, data);
+        self.deliver(nodes, &frame);
+        frame
+    }
+
+    // Sends the header of a frame a slave node publishes and returns its
+    // response
+    pub fn request<N: LinNode>(&mut self, nodes: &mut [N], id: u8) -> Result<LinFrame, LinError> {
+        let mut responses = nodes.iter_mut().filter_map(|node| node.respond(id & 0x3F));
+        let result = match (responses.next(), responses.next()) {
+            (Some(data), None) => Ok(LinFrame::new(id, &data)),
+            (None, _) => Err(LinError::NoResponse(id & 0x3F)),
+            (Some(_), Some(_)) => Err(LinError::Collision(id & 0x3F)),
+        };
+        match &result {
+            Ok(frame) => self.deliver(nodes, frame),
+            Err(e) => {
+                self.errors += 1;
+                sim_log::debug("lin", &e.to_string());
+            }
+        }
+        result
+    }
+
+    fn deliver<N: LinNode>(&mut self, nodes: &mut [N], frame: &LinFrame) {
+        self.frames += 1;
+        if sim_log::enabled(LogLevel::Debug) {
+            sim_log::debug("lin", &frame.to_string());
+        }
+        for node in nodes.iter_mut() {
+            node.receive(frame.id, &frame.data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Publishes `data` on frame `id` and keeps whatever it receives
+    struct Node {
+        id: u8,
+        data: Vec<u8>,
+        received: Vec<(u8, Vec<u8>)>,
+    }
+
+    impl LinNode for Node {
+        fn respond(&mut self, id: u8) -> Option<Vec<u8>> {
+            (id == self.id).then(|| self.data.clone())
+        }
+
+        fn receive(&mut self, id: u8, data: &[u8]) {
+            self.received.push((id, data.to_vec()));
+        }
+    }
+
+    fn node(id: u8, data: &[u8]) -> Node {
+        Node {
+            id,
+            data: data.to_vec(),
+            received: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn protected_identifiers_and_checksums() {
+        assert_eq!(protected_id(0x3C), 0x3C);
+        assert_eq!(protected_id(0x3D), 0x7D);
+        assert_eq!(protected_id(0x01), 0xC1);
+        assert_eq!(protected_id(0x7D), 0x7D);
+
+        // The carry wraps around, and the sum with the checksum is all ones
+        assert_eq!(checksum(0xFF, &[0x01]), !0x01);
+        let frame = LinFrame::new(0x10, &[0x4A, 0x55, 0x93, 0xE5]);
+        let sum = frame.data.iter().fold(protected_id(0x10) as u16, |sum, &byte| {
+            let sum = sum + byte as u16;
+            if sum > 0xFF { sum - 0xFF } else { sum }
+        });
+        assert_eq!(sum as u8 ^ frame.checksum, 0xFF);
+        assert_eq!(LinFrame::new(0x10, &[0; 12]).data.len(), 8);
+        assert_eq!(LinFrame::new(0x3C, &[0xAB]).to_string(), format!("3C [1] AB ({:02X})", checksum(0x3C, &[0xAB])));
+    }
+
+    #[test]
+    fn the_master_polls_the_one_node_that_publishes_a_frame() {
+        let mut master = LinMaster::new();
+        let mut nodes = [node(0x20, &[1, 2]), node(0x21, &[3])];
+        let frame = master.request(&mut nodes, 0x20).unwrap();
+        assert_eq!(frame.data, vec![1, 2]);
+        assert_eq!(nodes[1].received, vec![(0x20, vec![1, 2])]);
+
+        master.send(&mut nodes, 0x05, &[9]);
+        assert_eq!(nodes[0].received.last(), Some(&(0x05, vec![9])));
+        assert_eq!(master.request(&mut nodes, 0x22), Err(LinError::NoResponse(0x22)));
+        assert_eq!((master.frames, master.errors), (2, 1));
+
+        let mut twins = [node(0x20, &[1]), node(0x20, &[2])];
+        assert_eq!(master.request(&mut twins, 0x20), Err(LinError::Collision(0x20)));
+        assert!(twins[0].received.is_empty());
+    }
+}
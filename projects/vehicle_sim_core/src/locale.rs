@@ -0,0 +1,267 @@
+use std::env;
+use std::sync::OnceLock;
+
+use crate::calendar::Date;
+use crate::units::{Celsius, Hours, Kilometers, KilometersPerHour, Liters, Psi};
+
+pub const LOCALE_ENV_VAR: &str = "SIM_LOCALE";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnitSystem {
+    Metric,
+    Imperial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PressureUnit {
+    Psi,
+    Bar,
+    Kilopascal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeFormat {
+    TwentyFourHour,
+    TwelveHour,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateFormat {
+    Iso,
+    DayMonthYear,
+    MonthDayYear,
+}
+
+// How numbers, dates and physical quantities are shown to the user.
+// Machine readable outputs (CSV, JSON, state files) stay in SI units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale {
+    pub name: String,
+    pub decimal_separator: char,
+    pub time_format: TimeFormat,
+    pub date_format: DateFormat,
+    pub units: UnitSystem,
+    pub temperature_unit: TemperatureUnit,
+    pub pressure_unit: PressureUnit,
+}
+
+impl Locale {
+    // Matches the historic output of the simulations
+    pub fn default_locale() -> Self {
+        Locale {
+            name: "default".to_string(),
+            decimal_separator: '.',
+            time_format: TimeFormat::TwentyFourHour,
+            date_format: DateFormat::Iso,
+            units: UnitSystem::Metric,
+            temperature_unit: TemperatureUnit::Celsius,
+            pressure_unit: PressureUnit::Psi,
+        }
+    }
+
+    pub fn en_us() -> Self {
+        Locale {
+            name: "en-US".to_string(),
+            decimal_separator: '.',
+            time_format: TimeFormat::TwelveHour,
+            date_format: DateFormat::MonthDayYear,
+            units: UnitSystem::Imperial,
+            temperature_unit: TemperatureUnit::Fahrenheit,
+            pressure_unit: PressureUnit::Psi,
+        }
+    }
+
+    pub fn en_gb() -> Self {
+        Locale {
+            name: "en-GB".to_string(),
+            decimal_separator: '.',
+            time_format: TimeFormat::TwentyFourHour,
+            date_format: DateFormat::DayMonthYear,
+            units: UnitSystem::Imperial,
+            temperature_unit: TemperatureUnit::Celsius,
+            pressure_unit: PressureUnit::Psi,
+        }
+    }
+
+    pub fn de_de() -> Self {
+        Locale {
+            name: "de-DE".to_string(),
+            decimal_separator: ',',
+            time_format: TimeFormat::TwentyFourHour,
+            date_format: DateFormat::DayMonthYear,
+            units: UnitSystem::Metric,
+            temperature_unit: TemperatureUnit::Celsius,
+            pressure_unit: PressureUnit::Bar,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "default" | "c" | "posix" => Some(Locale::default_locale()),
+            "en-us" => Some(Locale::en_us()),
+            "en-gb" => Some(Locale::en_gb()),
+            "de-de" | "de" => Some(Locale::de_de()),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self, value: f64, precision: usize) -> String {
+        let formatted = format!("{:.*}", precision, value);
+        if self.decimal_separator == '.' {
+            formatted
+        } else {
+            formatted.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+
+    pub fn temperature(&self, celsius: Celsius, precision: usize) -> String {
+        match self.temperature_unit {
+            TemperatureUnit::Celsius => format!("{}°C", self.number(celsius as f64, precision)),
+            TemperatureUnit::Fahrenheit => format!("{}°F", self.number(celsius as f64 * 9.0 / 5.0 + 32.0, precision)),
+        }
+    }
+
+    pub fn distance(&self, kilometers: Kilometers, precision: usize) -> String {
+        format!("{} {}", self.number(self.distance_value(kilometers), precision), self.distance_unit())
+    }
+
+    pub fn distance_value(&self, kilometers: Kilometers) -> f64 {
+        match self.units {
+            UnitSystem::Metric => kilometers,
+            UnitSystem::Imperial => kilometers / 1.609344,
+        }
+    }
+
+    pub fn distance_unit(&self) -> &'static str {
+        match self.units {
+            UnitSystem::Metric => "km",
+            UnitSystem::Imperial => "mi",
+        }
+    }
+
+    // Short distances such as stopping distances
+    pub fn length(&self, meters: f64, precision: usize) -> String {
+        match self.units {
+            UnitSystem::Metric => format!("{} m", self.number(meters, precision)),
+            UnitSystem::Imperial => format!("{} ft", self.number(meters / 0.3048, precision)),
+        }
+    }
+
+    pub fn speed(&self, speed: KilometersPerHour, precision: usize) -> String {
+        match self.units {
+            UnitSystem::Metric => format!("{} km/h", self.number(speed, precision)),
+            UnitSystem::Imperial => format!("{} mph", self.number(speed / 1.609344, precision)),
+        }
+    }
+
+    pub fn volume(&self, liters: Liters, precision: usize) -> String {
+        format!("{} {}", self.number(self.volume_value(liters), precision), self.volume_unit())
+    }
+
+    pub fn volume_value(&self, liters: Liters) -> f64 {
+        match self.units {
+            UnitSystem::Metric => liters,
+            UnitSystem::Imperial => liters / 3.785411784,
+        }
+    }
+
+    pub fn volume_unit(&self) -> &'static str {
+        match self.units {
+            UnitSystem::Metric => "liters",
+            UnitSystem::Imperial => "gal",
+        }
+    }
+
+    pub fn pressure(&self, psi: Psi, precision: usize) -> String {
+        let psi = psi as f64;
+        match self.pressure_unit {
+            PressureUnit::Psi => format!("{} PSI", self.number(psi, precision)),
+            PressureUnit::Bar => format!("{} bar", self.number(psi * 0.0689476, precision + 1)),
+            PressureUnit::Kilopascal => format!("{} kPa", self.number(psi * 6.89476, precision)),
+        }
+    }
+
+    pub fn date(&self, date: Date) -> String {
+        match self.date_format {
+            DateFormat::Iso => date.to_string(),
+            DateFormat::DayMonthYear => format!("{:02}.{:02}.{:04}", date.day, date.month, date.year),
+            DateFormat::MonthDayYear => format!("{:02}/{:02}/{:04}", date.month, date.day, date.year),
+        }
+    }
+
+    pub fn time(&self, hour_of_day: Hours) -> String {
+        let total_minutes = (hour_of_day.rem_euclid(24.0) * 60.0).round() as u32 % (24 * 60);
+        let (hour, minute) = (total_minutes / 60, total_minutes % 60);
+
+        match self.time_format {
+            TimeFormat::TwentyFourHour => format!("{:02}:{:02}", hour, minute),
+            TimeFormat::TwelveHour => {
+                let suffix = if hour < 12 { "AM" } else { "PM" };
+                let hour = if hour % 12 == 0 { 12 } else { hour % 12 };
+                format!("{}:{:02} {}", hour, minute, suffix)
+            }
+        }
+    }
+
+    pub fn timestamp(&self, date: Date, hour_of_day: Hours) -> String {
+        format!("{} {}", self.date(date), self.time(hour_of_day))
+    }
+}
+
+static CURRENT: OnceLock<Locale> = OnceLock::new();
+
+// Process-wide locale, chosen once from SIM_LOCALE (e.g. en-US, en-GB, de-DE)
+pub fn current() -> &'static Locale {
+    CURRENT.get_or_init(|| match env::var(LOCALE_ENV_VAR) {
+        Ok(name) => Locale::parse(&name).unwrap_or_else(|| {
+            eprintln!("Unknown locale '{}', using the default locale", name);
+            Locale::default_locale()
+        }),
+        Err(_) => Locale::default_locale(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locales_are_found_by_any_spelling_of_their_name() {
+        assert_eq!(Locale::parse(" en_US "), Some(Locale::en_us()));
+        assert_eq!(Locale::parse("DE"), Some(Locale::de_de()));
+        assert_eq!(Locale::parse("POSIX"), Some(Locale::default_locale()));
+        assert_eq!(Locale::parse("fr-FR"), None);
+    }
+
+    #[test]
+    fn quantities_are_shown_in_the_units_of_the_locale() {
+        let (default, us, gb, de) = (Locale::default_locale(), Locale::en_us(), Locale::en_gb(), Locale::de_de());
+        assert_eq!(de.number(1.256, 2), "1,26");
+        assert_eq!(us.temperature(100.0, 0), "212°F");
+        assert_eq!(gb.temperature(21.5, 1), "21.5°C");
+        assert_eq!(gb.distance(16.09344, 1), "10.0 mi");
+        assert_eq!(default.distance(12.0, 0), "12 km");
+        assert_eq!(us.length(30.48, 0), "100 ft");
+        assert_eq!(de.speed(50.0, 0), "50 km/h");
+        assert_eq!(us.volume(3.785411784, 1), "1.0 gal");
+        assert_eq!(default.pressure(32.0, 1), "32.0 PSI");
+        assert_eq!(de.pressure(32.0, 1), "2,21 bar");
+    }
+
+    #[test]
+    fn dates_and_times_follow_the_locale() {
+        let date = Date::new(2026, 10, 6).unwrap();
+        assert_eq!(Locale::default_locale().timestamp(date, 13.5), "2026-10-06 13:30");
+        assert_eq!(Locale::de_de().date(date), "06.10.2026");
+        assert_eq!(Locale::en_us().timestamp(date, 0.25), "10/06/2026 12:15 AM");
+        assert_eq!(Locale::en_us().time(12.0), "12:00 PM");
+        // Rounds up to midnight instead of showing 24:00
+        assert_eq!(Locale::default_locale().time(23.999), "00:00");
+    }
+}
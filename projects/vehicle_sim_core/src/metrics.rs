@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sim_log;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Counter,
+    Gauge,
+    Summary,
+}
+
+struct Family {
+    help: String,
+    kind: Kind,
+    // Keyed by the rendered label set, e.g. `{tire="Front-Left"}`
+    samples: BTreeMap<String, Sample>,
+}
+
+#[derive(Default)]
+struct Sample {
+    value: f64,
+    count: u64,
+}
+
+struct Budget {
+    limit: Duration,
+    overruns: u64,
+    worst: Duration,
+}
+
+// A component that took longer than its per-step time budget
+#[derive(Debug, Clone)]
+pub struct BudgetOverrun {
+    pub component: String,
+    pub limit: Duration,
+    pub overruns: u64,
+    pub worst: Duration,
+}
+
+// Thread-safe metric registry rendered in the Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    families: Mutex<BTreeMap<String, Family>>,
+    budgets: Mutex<BTreeMap<String, Budget>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn inc_counter(&self, name: &str, help: &str, labels: &[(&str, &str)], by: f64) {
+        self.update(name, help, Kind::Counter, labels, |sample| sample.value += by);
+    }
+
+    pub fn set_gauge(&self, name: &str, help: &str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, help, Kind::Gauge, labels, |sample| sample.value = value);
+    }
+
+    // Summary without quantiles: exported as `<name>_sum` and `<name>_count`
+    pub fn observe(&self, name: &str, help: &str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, help, Kind::Summary, labels, |sample| {
+            sample.value += value;
+            sample.count += 1;
+        });
+    }
+
+    // Runs `f` and records how long it took as a duration of `component`
+    pub fn time<T>(&self, component: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record_duration(component, start.elapsed());
+        result
+    }
+
+    // Caps the time `component` may take per step; overruns are counted and
+    // flagged the first time they happen
+    pub fn set_budget(&self, component: &str, limit: Duration) {
+        self.budgets.lock().unwrap().insert(
+            component.to_string(),
+            Budget {
+                limit,
+                overruns: 0,
+                worst: Duration::ZERO,
+            },
+        );
+    }
+
+    pub fn record_duration(&self, component: &str, elapsed: Duration) {
+        self.observe(
+            "sim_component_duration_seconds",
+            "Time spent per simulation component",
+            &[("component", component)],
+            elapsed.as_secs_f64(),
+        );
+
+        let mut budgets = self.budgets.lock().unwrap();
+        let Some(budget) = budgets.get_mut(component).filter(|budget| elapsed > budget.limit) else {
+            return;
+        };
+        if budget.overruns == 0 {
+            sim_log::warn(
+                "metrics",
+                &format!(
+                    "Component {} exceeded its budget: {:.3} ms > {:.3} ms",
+                    component,
+                    elapsed.as_secs_f64() * 1000.0,
+                    budget.limit.as_secs_f64() * 1000.0
+                ),
+            );
+        }
+        budget.overruns += 1;
+        budget.worst = budget.worst.max(elapsed);
+        drop(budgets);
+
+        self.inc_counter(
+            "sim_component_budget_overruns_total",
+            "Steps in which a component exceeded its time budget",
+            &[("component", component)],
+            1.0,
+        );
+    }
+
+    // Components that exceeded their budget, most frequent offender first
+    pub fn budget_overruns(&self) -> Vec<BudgetOverrun> {
+        let budgets = self.budgets.lock().unwrap();
+        let mut overruns: Vec<BudgetOverrun> = budgets
+            .iter()
+            .filter(|(_, budget)| budget.overruns > 0)
+            .map(|(component, budget)| BudgetOverrun {
+                component: component.clone(),
+                limit: budget.limit,
+                overruns: budget.overruns,
+                worst: budget.worst,
+            })
+            .collect();
+        overruns.sort_by(|a, b| b.overruns.cmp(&a.overruns).then(b.worst.cmp(&a.worst)));
+        overruns
+    }
+
+    // Current value of an unlabelled gauge or counter
+    pub fn value(&self, name: &str) -> Option<f64> {
+        let families = self.families.lock().unwrap();
+        families.get(name)?.samples.get("").map(|sample| sample.value)
+    }
+
+    // All values `label` takes in the samples of `name`
+    pub fn label_values(&self, name: &str, label: &str) -> Vec<String> {
+        let families = self.families.lock().unwrap();
+        let prefix = format!("{}=\"", label);
+        families.get(name).map_or_else(Vec::new, |family| {
+            family
+                .samples
+                .keys()
+                .filter_map(|labels| {
+                    let start = labels.find(&prefix)? + prefix.len();
+                    let end = labels[start..].find('"')? + start;
+                    Some(labels[start..end].to_string())
+                })
+                .collect()
+        })
+    }
+
+    pub fn render(&self) -> String {
+        let families = self.families.lock().unwrap();
+        let mut out = String::new();
+
+        for (name, family) in families.iter() {
+            let kind = match family.kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+                Kind::Summary => "summary",
+            };
+            let _ = writeln!(out, "# HELP {} {}", name, family.help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+
+            for (labels, sample) in &family.samples {
+                if family.kind == Kind::Summary {
+                    let _ = writeln!(out, "{}_sum{} {}", name, labels, sample.value);
+                    let _ = writeln!(out, "{}_count{} {}", name, labels, sample.count);
+                } else {
+                    let _ = writeln!(out, "{}{} {}", name, labels, sample.value);
+                }
+            }
+        }
+
+        out
+    }
+
+    fn update(&self, name: &str, help: &str, kind: Kind, labels: &[(&str, &str)], f: impl FnOnce(&mut Sample)) {
+        let mut families = self.families.lock().unwrap();
+        let family = families.entry(name.to_string()).or_insert_with(|| Family {
+            help: help.to_string(),
+            kind,
+            samples: BTreeMap::new(),
+        });
+        f(family.samples.entry(render_labels(labels)).or_default());
+    }
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_prometheus_text_format() {
+        let metrics = Metrics::new();
+        metrics.set_gauge("tpms_pressure_psi", "Reported tire pressure", &[("tire", "Front-Left")], 32.5);
+        metrics.inc_counter("sim_steps_total", "Steps run", &[], 1.0);
+        metrics.inc_counter("sim_steps_total", "Steps run", &[], 2.0);
+        metrics.observe("sim_step_seconds", "Step time", &[("phase", "a\"b")], 0.25);
+        metrics.observe("sim_step_seconds", "Step time", &[("phase", "a\"b")], 0.5);
+
+        let expected = "# HELP sim_step_seconds Step time\n\
+                        # TYPE sim_step_seconds summary\n\
+                        sim_step_seconds_sum{phase=\"a\\\"b\"} 0.75\n\
+                        sim_step_seconds_count{phase=\"a\\\"b\"} 2\n\
+                        # HELP sim_steps_total Steps run\n\
+                        # TYPE sim_steps_total counter\n\
+                        sim_steps_total 3\n\
+                        # HELP tpms_pressure_psi Reported tire pressure\n\
+                        # TYPE tpms_pressure_psi gauge\n\
+                        tpms_pressure_psi{tire=\"Front-Left\"} 32.5\n";
+        assert_eq!(metrics.render(), expected);
+        assert_eq!(metrics.value("sim_steps_total"), Some(3.0));
+        assert_eq!(metrics.label_values("tpms_pressure_psi", "tire"), vec!["Front-Left"]);
+    }
+
+    #[test]
+    fn budget_overruns_are_counted_worst_offender_first() {
+        let metrics = Metrics::new();
+        metrics.set_budget("physics", Duration::from_millis(1));
+        metrics.set_budget("report", Duration::from_millis(1));
+        metrics.set_budget("idle", Duration::from_millis(1));
+        metrics.record_duration("physics", Duration::from_millis(3));
+        metrics.record_duration("physics", Duration::from_millis(5));
+        metrics.record_duration("physics", Duration::from_micros(500));
+        metrics.record_duration("report", Duration::from_millis(9));
+        metrics.record_duration("idle", Duration::from_micros(10));
+
+        let overruns = metrics.budget_overruns();
+        let summary: Vec<(&str, u64, Duration)> =
+            overruns.iter().map(|overrun| (overrun.component.as_str(), overrun.overruns, overrun.worst)).collect();
+        assert_eq!(
+            summary,
+            vec![("physics", 2, Duration::from_millis(5)), ("report", 1, Duration::from_millis(9))]
+        );
+        assert!(metrics.render().contains("sim_component_budget_overruns_total{component=\"physics\"} 2\n"));
+        assert!(metrics.render().contains("sim_component_duration_seconds_count{component=\"idle\"} 1\n"));
+    }
+}
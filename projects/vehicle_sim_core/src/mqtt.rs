@@ -0,0 +1,304 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::sim_log;
+
+pub const MQTT_ENV_VAR: &str = "SIM_MQTT";
+pub const MQTT_USER_ENV_VAR: &str = "SIM_MQTT_USER";
+pub const MQTT_PASSWORD_ENV_VAR: &str = "SIM_MQTT_PASSWORD";
+pub const MQTT_PREFIX_ENV_VAR: &str = "SIM_MQTT_PREFIX";
+
+const DEFAULT_PORT: u16 = 1883;
+// The broker drops the connection after 1.5 times this without a packet;
+// a ping goes out when a step has not published for half of it
+const KEEP_ALIVE: u16 = 60; // s
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PINGREQ: u8 = 0xC0;
+const DISCONNECT: u8 = 0xE0;
+
+const PROTOCOL_LEVEL: u8 = 4; // MQTT 3.1.1
+const CLEAN_SESSION: u8 = 0x02;
+const PASSWORD_FLAG: u8 = 0x40;
+const USERNAME_FLAG: u8 = 0x80;
+
+#[derive(Debug, Clone)]
+pub struct MqttOptions {
+    // host:port, the port defaulting to 1883
+    pub broker: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // Every topic starts with this, e.g. vehicle/tpms
+    pub topic_prefix: String,
+}
+
+impl MqttOptions {
+    // Credentials come from SIM_MQTT_USER and SIM_MQTT_PASSWORD rather than
+    // the command line, where other users could read them; SIM_MQTT_PREFIX
+    // replaces `vehicle/<simulator>`
+    pub fn from_env(broker: String, simulator: &str) -> Self {
+        MqttOptions {
+            broker,
+            client_id: format!("{}-{}", simulator, process::id()),
+            username: env::var(MQTT_USER_ENV_VAR).ok(),
+            password: env::var(MQTT_PASSWORD_ENV_VAR).ok(),
+            topic_prefix: env::var(MQTT_PREFIX_ENV_VAR).unwrap_or_else(|_| format!("vehicle/{}", simulator)),
+        }
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    last_sent: Instant,
+    failed: bool,
+}
+
+// Publishes telemetry to an MQTT broker, one topic per signal with the value
+// as plain text (e.g. `vehicle/tpms/front_left/pressure` = `32.1`), which
+// Telegraf's mqtt_consumer reads with `data_format = "value"`. Only QoS 0:
+// a lost sample is replaced by the next step's.
+pub struct MqttPublisher {
+    connection: Mutex<Connection>,
+    topic_prefix: String,
+}
+
+impl MqttPublisher {
+    // Connects and waits for the broker to accept the session
+    pub fn connect(options: &MqttOptions) -> io::Result<Arc<MqttPublisher>> {
+        let address = if options.broker.contains(':') {
+            options.broker.clone()
+        } else {
+            format!("{}:{}", options.broker, DEFAULT_PORT)
+        };
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        stream.write_all(&connect_packet(options)?)?;
+
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        let mut connack = [0u8; 4];
+        stream.read_exact(&mut connack)?;
+        if connack[0] != CONNACK || connack[1] != 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the broker did not answer with CONNACK"));
+        }
+        match connack[3] {
+            0 => {}
+            1 => return Err(io::Error::other("the broker does not speak MQTT 3.1.1")),
+            2 => return Err(io::Error::other("the broker rejected the client id")),
+            4 | 5 => return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the broker rejected the credentials")),
+            code => return Err(io::Error::other(format!("the broker refused the connection (code {})", code))),
+        }
+
+        // Nothing but ping responses comes back for QoS 0; reading them keeps
+        // the socket drained and notices when the broker goes away
+        stream.set_read_timeout(None)?;
+        let mut incoming = stream.try_clone()?;
+        thread::spawn(move || {
+            let mut buffer = [0u8; 256];
+            while matches!(incoming.read(&mut buffer), Ok(length) if length > 0) {}
+            sim_log::warn("mqtt", "MQTT broker closed the connection");
+        });
+
+        Ok(Arc::new(MqttPublisher {
+            connection: Mutex::new(Connection {
+                stream,
+                last_sent: Instant::now(),
+                failed: false,
+            }),
+            topic_prefix: options.topic_prefix.trim_end_matches('/').to_string(),
+        }))
+    }
+
+    // Publishes each value under `<prefix>/<name>`; call once per step. Stops
+    // publishing with a warning once the connection failed.
+    pub fn publish(&self, values: &[(&str, f64)]) {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.failed {
+            return;
+        }
+        let mut packets = Vec::new();
+        for (name, value) in values.iter().filter(|(_, value)| value.is_finite()) {
+            let topic = format!("{}/{}", self.topic_prefix, name);
+            match publish_packet(&topic, value.to_string().as_bytes()) {
+                Ok(packet) => packets.extend(packet),
+                Err(e) => {
+                    sim_log::warn("mqtt", &format!("MQTT topic {} cannot be published, no more telemetry: {}", name, e));
+                    connection.failed = true;
+                    return;
+                }
+            }
+        }
+        if packets.is_empty() && connection.last_sent.elapsed() < Duration::from_secs(KEEP_ALIVE as u64 / 2) {
+            return;
+        }
+        if packets.is_empty() {
+            packets.extend([PINGREQ, 0]);
+        }
+
+        match connection.stream.write_all(&packets) {
+            Ok(()) => connection.last_sent = Instant::now(),
+            Err(e) => {
+                sim_log::warn("mqtt", &format!("MQTT publish failed, no more telemetry: {}", e));
+                connection.failed = true;
+            }
+        }
+    }
+}
+
+impl Drop for MqttPublisher {
+    fn drop(&mut self) {
+        let connection = self.connection.get_mut().unwrap();
+        if !connection.failed {
+            let _ = connection.stream.write_all(&[DISCONNECT, 0]);
+        }
+    }
+}
+
+// Turns a display name like `Front-Left` into a topic level like `front_left`
+pub fn topic_level(name: &str) -> String {
+    let mut level = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            level.push(c.to_ascii_lowercase());
+        } else if !level.is_empty() && !level.ends_with('_') {
+            level.push('_');
+        }
+    }
+    level.trim_end_matches('_').to_string()
+}
+
+// Uses `--mqtt <host[:port]>` from the command line, then SIM_MQTT
+pub fn broker_from_args() -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--mqtt")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| env::var(MQTT_ENV_VAR).ok())
+}
+
+fn connect_packet(options: &MqttOptions) -> io::Result<Vec<u8>> {
+    let mut flags = CLEAN_SESSION;
+    let mut body = Vec::new();
+    put_string(&mut body, "MQTT")?;
+    body.push(PROTOCOL_LEVEL);
+    let flags_at = body.len();
+    body.push(0);
+    body.extend_from_slice(&KEEP_ALIVE.to_be_bytes());
+
+    put_string(&mut body, &options.client_id)?;
+    if let Some(username) = &options.username {
+        flags |= USERNAME_FLAG;
+        put_string(&mut body, username)?;
+        // MQTT 3.1.1 only allows a password along with a user name
+        if let Some(password) = &options.password {
+            flags |= PASSWORD_FLAG;
+            put_string(&mut body, password)?;
+        }
+    }
+    body[flags_at] = flags;
+    Ok(packet(CONNECT, &body))
+}
+
+fn publish_packet(topic: &str, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    put_string(&mut body, topic)?;
+    body.extend_from_slice(payload);
+    Ok(packet(PUBLISH, &body))
+}
+
+// Strings carry their length in 16 bits, so longer ones cannot be sent
+fn put_string(body: &mut Vec<u8>, value: &str) -> io::Result<()> {
+    let length = u16::try_from(value.len()).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("{} bytes is too long for an MQTT string", value.len()))
+    })?;
+    body.extend_from_slice(&length.to_be_bytes());
+    body.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+// Fixed header with the remaining length in 7-bit groups, low group first
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(username: Option<&str>, password: Option<&str>) -> MqttOptions {
+        MqttOptions {
+            broker: "localhost".to_string(),
+            client_id: "tpms".to_string(),
+            username: username.map(str::to_string),
+            password: password.map(str::to_string),
+            topic_prefix: "vehicle/tpms".to_string(),
+        }
+    }
+
+    #[test]
+    fn remaining_length_takes_seven_bits_per_byte() {
+        let header = |length: usize| {
+            let packet = packet(PUBLISH, &vec![0; length]);
+            packet[1..packet.len() - length].to_vec()
+        };
+        assert_eq!(header(0), vec![0x00]);
+        assert_eq!(header(127), vec![0x7F]);
+        assert_eq!(header(128), vec![0x80, 0x01]);
+        assert_eq!(header(321), vec![0xC1, 0x02]);
+        assert_eq!(header(16_383), vec![0xFF, 0x7F]);
+        assert_eq!(header(16_384), vec![0x80, 0x80, 0x01]);
+        assert_eq!(header(2_097_152), vec![0x80, 0x80, 0x80, 0x01]);
+    }
+
+    #[test]
+    fn publish_and_connect_packets() {
+        assert_eq!(publish_packet("a/b", b"1.5").unwrap(), b"\x30\x08\x00\x03a/b1.5");
+
+        let connect = connect_packet(&options(None, Some("ignored"))).unwrap();
+        assert_eq!(connect, b"\x10\x10\x00\x04MQTT\x04\x02\x00\x3c\x00\x04tpms");
+        let connect = connect_packet(&options(Some("user"), Some("secret"))).unwrap();
+        assert_eq!(connect[9], CLEAN_SESSION | USERNAME_FLAG | PASSWORD_FLAG);
+        assert!(connect.ends_with(b"\x00\x04user\x00\x06secret"));
+    }
+
+    #[test]
+    fn strings_longer_than_their_length_field_are_refused() {
+        let mut body = Vec::new();
+        put_string(&mut body, &"x".repeat(65_535)).unwrap();
+        assert_eq!(&body[..2], &[0xFF, 0xFF]);
+        let error = publish_packet(&"x".repeat(65_536), b"1").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn topic_levels_from_display_names() {
+        assert_eq!(topic_level("Front-Left"), "front_left");
+        assert_eq!(topic_level("  Tire #3 (rear)  "), "tire_3_rear");
+        assert_eq!(topic_level("pressure"), "pressure");
+        assert_eq!(topic_level("°C/ü"), "c");
+        assert_eq!(topic_level("--"), "");
+    }
+}
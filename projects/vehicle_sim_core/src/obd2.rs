@@ -0,0 +1,312 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::sim_log;
+
+// Service 01 parameter identifiers
+pub const MONITOR_STATUS: u8 = 0x01;
+pub const ENGINE_RPM: u8 = 0x0C;
+pub const VEHICLE_SPEED: u8 = 0x0D;
+pub const FUEL_LEVEL: u8 = 0x2F;
+pub const DISTANCE_SINCE_CODES_CLEARED: u8 = 0x31;
+pub const ODOMETER: u8 = 0xA6;
+
+const SHOW_CURRENT_DATA: u8 = 0x01;
+const SHOW_STORED_DTCS: u8 = 0x03;
+const CLEAR_DTCS: u8 = 0x04;
+const POSITIVE_RESPONSE_OFFSET: u8 = 0x40;
+
+// Data PIDs answered, besides the "PIDs supported" ones (0x00, 0x20, ...)
+const DATA_PIDS: [u8; 6] = [MONITOR_STATUS, ENGINE_RPM, VEHICLE_SPEED, FUEL_LEVEL, DISTANCE_SINCE_CODES_CLEARED, ODOMETER];
+
+// Identifier the engine ECU answers functional requests (0x7DF) on
+const RESPONSE_ID: &str = "7E8";
+const ELM_VERSION: &str = "ELM327 v1.5";
+
+// What the simulated engine ECU reports
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ObdData {
+    pub vehicle_speed: f64, // km/h
+    pub engine_rpm: f64,
+    pub fuel_level: f64, // 0.0 (empty) to 1.0 (full)
+    pub distance_since_codes_cleared: f64, // km
+    pub odometer: f64, // km
+}
+
+// Answers OBD-II (SAE J1979) requests from the latest data the simulation
+// published. The simulation has no stored DTCs; a clear request only
+// restarts the distance since codes were cleared, which the simulation
+// picks up with `take_clear_request`.
+#[derive(Default)]
+pub struct ObdResponder {
+    data: Mutex<ObdData>,
+    clear_requested: AtomicBool,
+}
+
+impl ObdResponder {
+    pub fn new() -> Self {
+        ObdResponder::default()
+    }
+
+    pub fn update(&self, data: ObdData) {
+        *self.data.lock().unwrap() = data;
+    }
+
+    pub fn take_clear_request(&self) -> bool {
+        self.clear_requested.swap(false, Ordering::SeqCst)
+    }
+
+    // `None` for requests the ECU does not answer ("NO DATA")
+    pub fn respond(&self, request: &[u8]) -> Option<Vec<u8>> {
+        let (&mode, pids) = request.split_first()?;
+        let mut response = vec![mode + POSITIVE_RESPONSE_OFFSET];
+        match mode {
+            SHOW_CURRENT_DATA => {
+                let data = *self.data.lock().unwrap();
+                let [pid] = pids else {
+                    return None;
+                };
+                response.push(*pid);
+                response.extend(pid_value(*pid, &data)?);
+            }
+            // No DTCs stored
+            SHOW_STORED_DTCS if pids.is_empty() => response.push(0),
+            CLEAR_DTCS if pids.is_empty() => {
+                self.clear_requested.store(true, Ordering::SeqCst);
+                sim_log::info("obd2", "Clear DTCs requested");
+            }
+            _ => return None,
+        }
+        Some(response)
+    }
+
+    // Serves an ELM327-compatible interface on `address`, so scan tools and
+    // scripts (python-OBD with `socket://host:port`, for one) can connect
+    pub fn serve(self: &Arc<Self>, address: &str) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address)?;
+        let local_address = listener.local_addr()?;
+        let responder = Arc::clone(self);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let responder = Arc::clone(&responder);
+                thread::spawn(move || {
+                    if let Err(e) = Elm327Session::new(&responder).run(stream) {
+                        sim_log::debug("obd2", &format!("OBD connection closed: {}", e));
+                    }
+                });
+            }
+        });
+        Ok(local_address)
+    }
+}
+
+fn pid_value(pid: u8, data: &ObdData) -> Option<Vec<u8>> {
+    let value = match pid {
+        // Which PIDs of the next 32 are supported, the last bit chaining on
+        0x00 | 0x20 | 0x40 | 0x60 | 0x80 | 0xA0 => supported_pids(pid).to_be_bytes().to_vec(),
+        // MIL off, no DTCs, no monitors reported
+        MONITOR_STATUS => vec![0, 0, 0, 0],
+        ENGINE_RPM => ((data.engine_rpm * 4.0).round().clamp(0.0, 65535.0) as u16).to_be_bytes().to_vec(),
+        VEHICLE_SPEED => vec![data.vehicle_speed.round().clamp(0.0, 255.0) as u8],
+        FUEL_LEVEL => vec![(data.fuel_level * 255.0).round().clamp(0.0, 255.0) as u8],
+        DISTANCE_SINCE_CODES_CLEARED => {
+            (data.distance_since_codes_cleared.round().clamp(0.0, 65535.0) as u16).to_be_bytes().to_vec()
+        }
+        ODOMETER => ((data.odometer * 10.0).round().clamp(0.0, u32::MAX as f64) as u32).to_be_bytes().to_vec(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn supported_pids(base: u8) -> u32 {
+    let mut bits = 0;
+    for pid in DATA_PIDS.iter().filter(|pid| **pid > base && **pid <= base.saturating_add(32)) {
+        bits |= 1 << (32 - (pid - base) as u32);
+    }
+    // Announce the next range as long as a data PID lies beyond it
+    if DATA_PIDS.iter().any(|pid| *pid > base.saturating_add(32)) {
+        bits |= 1;
+    }
+    bits
+}
+
+// Settings a scan tool changes with AT commands
+struct Elm327Session<'a> {
+    responder: &'a ObdResponder,
+    echo: bool,
+    headers: bool,
+    spaces: bool,
+    linefeeds: bool,
+}
+
+impl<'a> Elm327Session<'a> {
+    fn new(responder: &'a ObdResponder) -> Self {
+        Elm327Session {
+            responder,
+            echo: true,
+            headers: false,
+            spaces: true,
+            linefeeds: false,
+        }
+    }
+
+    fn run(mut self, stream: TcpStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        writer.write_all(b"\r>")?;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\r', &mut line)? == 0 {
+                return Ok(());
+            }
+            let command = String::from_utf8_lossy(&line).trim().to_string();
+            let mut output = String::new();
+            if self.echo {
+                output.push_str(&command);
+                output.push('\r');
+            }
+            for reply in self.handle(&command) {
+                output.push_str(&reply);
+                output.push('\r');
+                if self.linefeeds {
+                    output.push('\n');
+                }
+            }
+            output.push_str("\r>");
+            writer.write_all(output.as_bytes())?;
+        }
+    }
+
+    fn handle(&mut self, command: &str) -> Vec<String> {
+        let command: String = command.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+        if command.is_empty() {
+            return Vec::new();
+        }
+        if let Some(at) = command.strip_prefix("AT") {
+            return vec![self.at_command(at)];
+        }
+        self.request(&command)
+    }
+
+    fn at_command(&mut self, command: &str) -> String {
+        let ok = "OK".to_string();
+        match command {
+            "Z" | "WS" | "D" => {
+                *self = Elm327Session::new(self.responder);
+                ELM_VERSION.to_string()
+            }
+            "I" => ELM_VERSION.to_string(),
+            "E0" | "E1" => {
+                self.echo = command == "E1";
+                ok
+            }
+            "H0" | "H1" => {
+                self.headers = command == "H1";
+                ok
+            }
+            "S0" | "S1" => {
+                self.spaces = command == "S1";
+                ok
+            }
+            "L0" | "L1" => {
+                self.linefeeds = command == "L1";
+                ok
+            }
+            "DP" => "ISO 15765-4 (CAN 11/500)".to_string(),
+            "DPN" => "6".to_string(),
+            "RV" => "12.6V".to_string(),
+            // Protocol, timing and adaptive timing settings have no effect here
+            _ if command.starts_with("SP") || command.starts_with("TP") || command.starts_with("ST") || command.starts_with("AT") => ok,
+            _ => "?".to_string(),
+        }
+    }
+
+    fn request(&self, command: &str) -> Vec<String> {
+        // A trailing odd digit is the expected number of responses
+        let hex = if command.len() % 2 == 1 { &command[..command.len() - 1] } else { command };
+        let bytes: Option<Vec<u8>> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect();
+        let Some(bytes) = bytes.filter(|bytes| !bytes.is_empty()) else {
+            return vec!["?".to_string()];
+        };
+
+        let Some(response) = self.responder.respond(&bytes) else {
+            return vec!["NO DATA".to_string()];
+        };
+        let separator = if self.spaces { " " } else { "" };
+        let mut parts: Vec<String> = Vec::new();
+        if self.headers {
+            parts.push(RESPONSE_ID.to_string());
+            parts.push(format!("{:02X}", response.len()));
+        }
+        parts.extend(response.iter().map(|byte| format!("{:02X}", byte)));
+        vec![parts.join(separator)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn responder() -> ObdResponder {
+        let responder = ObdResponder::new();
+        responder.update(ObdData {
+            vehicle_speed: 87.4,
+            engine_rpm: 2500.0,
+            fuel_level: 0.5,
+            distance_since_codes_cleared: 1234.0,
+            odometer: 56789.1,
+        });
+        responder
+    }
+
+    #[test]
+    fn current_data_is_scaled_per_j1979() {
+        let responder = responder();
+        assert_eq!(responder.respond(&[0x01, VEHICLE_SPEED]), Some(vec![0x41, 0x0D, 87]));
+        assert_eq!(responder.respond(&[0x01, ENGINE_RPM]), Some(vec![0x41, 0x0C, 0x27, 0x10]));
+        assert_eq!(responder.respond(&[0x01, FUEL_LEVEL]), Some(vec![0x41, 0x2F, 128]));
+        assert_eq!(responder.respond(&[0x01, ODOMETER]), Some(vec![0x41, 0xA6, 0x00, 0x08, 0xAA, 0x53]));
+        assert_eq!(responder.respond(&[0x01, 0x05]), None);
+        assert_eq!(responder.respond(&[0x01, 0x0C, 0x0D]), None);
+        assert_eq!(responder.respond(&[0x09, 0x02]), None);
+    }
+
+    #[test]
+    fn supported_pid_ranges_chain_to_the_last_data_pid() {
+        assert_eq!(supported_pids(0x00), 0x8018_0001);
+        assert_eq!(supported_pids(0x20), 0x0002_8001);
+        assert_eq!(supported_pids(0xA0), 0x0400_0000);
+        assert_eq!(supported_pids(0xC0), 0);
+    }
+
+    #[test]
+    fn clearing_codes_is_handed_to_the_simulation() {
+        let responder = responder();
+        assert_eq!(responder.respond(&[0x03]), Some(vec![0x43, 0x00]));
+        assert!(!responder.take_clear_request());
+        assert_eq!(responder.respond(&[0x04]), Some(vec![0x44]));
+        assert!(responder.take_clear_request());
+        assert!(!responder.take_clear_request());
+    }
+
+    #[test]
+    fn the_elm327_session_follows_at_settings() {
+        let responder = responder();
+        let mut session = Elm327Session::new(&responder);
+        assert_eq!(session.handle("ATZ"), vec![ELM_VERSION]);
+        assert_eq!(session.handle("01 0d"), vec!["41 0D 57"]);
+        assert_eq!(session.handle("ATH1"), vec!["OK"]);
+        assert_eq!(session.handle("ATS0"), vec!["OK"]);
+        assert_eq!(session.handle("010D1"), vec!["7E803410D57"]);
+        assert_eq!(session.handle("0105"), vec!["NO DATA"]);
+        assert_eq!(session.handle("01G0"), vec!["?"]);
+        assert_eq!(session.handle("ATXX"), vec!["?"]);
+    }
+}
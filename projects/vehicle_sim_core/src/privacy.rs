@@ -0,0 +1,490 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::locale;
+use crate::scenario::{self, Value};
+
+pub const PRIVACY_ENV_VAR: &str = "SIM_PRIVACY";
+
+// Signals that together give away where the vehicle is
+pub const LOCATION_SIGNALS: &[&str] = &["latitude", "longitude"];
+const KM_PER_DEGREE: f64 = 111.32;
+
+// What the filter does with a signal before it leaves the vehicle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignalPolicy {
+    Keep,
+    Drop,
+    // To the nearest multiple of this step
+    Round(f64),
+    // Only the mean over windows of this many seconds, once a window is over
+    Average(f64),
+}
+
+impl SignalPolicy {
+    // keep, drop, round:<step> or average:<duration>, e.g. average:5min
+    pub fn parse(value: &str) -> Result<SignalPolicy, String> {
+        let (name, argument) = match value.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument.trim())),
+            None => (value.trim(), None),
+        };
+        match (name, argument) {
+            ("keep", None) => Ok(SignalPolicy::Keep),
+            ("drop", None) => Ok(SignalPolicy::Drop),
+            ("round", Some(step)) => match step.parse::<f64>() {
+                Ok(step) if step > 0.0 => Ok(SignalPolicy::Round(step)),
+                _ => Err(format!("round needs a positive step, got '{}'", step)),
+            },
+            ("average", Some(window)) => match scenario::parse_duration(&Value::String(window.to_string()))? {
+                window if window > 0.0 => Ok(SignalPolicy::Average(window)),
+                _ => Err("average needs a window longer than zero".to_string()),
+            },
+            _ => Err(format!("expected keep, drop, round:<step> or average:<duration>, got '{}'", value)),
+        }
+    }
+}
+
+// What the filter does with the position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocationPolicy {
+    Keep,
+    Drop,
+    // Snapped to the centre of a grid cell this many km wide
+    Grid(f64),
+}
+
+impl LocationPolicy {
+    // keep, drop or grid:<km>
+    pub fn parse(value: &str) -> Result<LocationPolicy, String> {
+        match value.trim() {
+            "keep" => Ok(LocationPolicy::Keep),
+            "drop" => Ok(LocationPolicy::Drop),
+            other => match other.strip_prefix("grid:").map(|size| size.trim().trim_end_matches("km").parse::<f64>()) {
+                Some(Ok(size)) if size > 0.0 => Ok(LocationPolicy::Grid(size)),
+                _ => Err(format!("expected keep, drop or grid:<km>, got '{}'", value)),
+            },
+        }
+    }
+}
+
+// Privacy settings of the telemetry export, kept in a `key = value` file:
+//
+//     location = grid:5
+//     timestamps = 1min
+//     default = keep
+//     signal.speed = round:10
+//     signal.engine_rpm = average:5min
+//     signal.fuel_level = drop
+//
+// Signals without a `signal.` line get the default policy, so `default =
+// drop` only lets through what is listed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrivacyPolicy {
+    pub location: LocationPolicy,
+    // Timestamps are truncated to this resolution, in seconds
+    pub timestamp_resolution: Option<f64>,
+    pub default: SignalPolicy,
+    pub signals: BTreeMap<String, SignalPolicy>,
+}
+
+impl Default for PrivacyPolicy {
+    fn default() -> Self {
+        PrivacyPolicy {
+            location: LocationPolicy::Keep,
+            timestamp_resolution: None,
+            default: SignalPolicy::Keep,
+            signals: BTreeMap::new(),
+        }
+    }
+}
+
+impl PrivacyPolicy {
+    pub fn from_config(config: &Config) -> Result<PrivacyPolicy, String> {
+        let mut policy = PrivacyPolicy::default();
+        for (key, value) in config.entries() {
+            let invalid = |e: String| format!("{}: {}", key, e);
+            match key {
+                "location" => policy.location = LocationPolicy::parse(value).map_err(invalid)?,
+                "timestamps" => {
+                    let resolution = scenario::parse_duration(&Value::String(value.to_string())).map_err(invalid)?;
+                    policy.timestamp_resolution = (resolution > 0.0).then_some(resolution);
+                }
+                "default" => policy.default = SignalPolicy::parse(value).map_err(invalid)?,
+                _ => match key.strip_prefix("signal.") {
+                    Some(signal) if LOCATION_SIGNALS.contains(&signal) => {
+                        return Err(format!("{}: the position follows the location setting", key));
+                    }
+                    Some(signal) => {
+                        policy.signals.insert(signal.to_string(), SignalPolicy::parse(value).map_err(invalid)?);
+                    }
+                    None => return Err(format!("unknown privacy setting '{}'", key)),
+                },
+            }
+        }
+        Ok(policy)
+    }
+
+    pub fn load(path: &Path) -> io::Result<PrivacyPolicy> {
+        PrivacyPolicy::from_config(&Config::load(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn signal(&self, name: &str) -> SignalPolicy {
+        self.signals.get(name).copied().unwrap_or(self.default)
+    }
+}
+
+// Values of a signal collected for its current averaging window
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    end: f64,
+    sum: f64,
+    count: u32,
+}
+
+// Values that went out unchanged, were left out or were changed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PrivacyStats {
+    pub passed: u64,
+    pub dropped: u64,
+    // Rounded, or positions snapped to the grid
+    pub coarsened: u64,
+    // Held back for an average, and averages sent
+    pub averaged: u64,
+    pub averages: u64,
+}
+
+// Applies a privacy policy to the samples of one telemetry stream. Every
+// exporter gets the filtered sample, so nothing leaves the vehicle that the
+// policy does not allow.
+pub struct PrivacyFilter {
+    pub policy: PrivacyPolicy,
+    windows: BTreeMap<String, Window>,
+    pub stats: PrivacyStats,
+}
+
+impl PrivacyFilter {
+    pub fn new(policy: PrivacyPolicy) -> Self {
+        PrivacyFilter {
+            policy,
+            windows: BTreeMap::new(),
+            stats: PrivacyStats::default(),
+        }
+    }
+
+    // A filter with the same policy for another stream, e.g. the dashboard
+    // next to MQTT, with windows of its own
+    pub fn for_stream(&self) -> PrivacyFilter {
+        PrivacyFilter::new(self.policy.clone())
+    }
+
+    // `time` truncated to the policy's resolution
+    pub fn timestamp(&self, time: f64) -> f64 {
+        match self.policy.timestamp_resolution {
+            Some(resolution) => (time / resolution).floor() * resolution,
+            None => time,
+        }
+    }
+
+    // The part of the sample taken at `time` that may be exported
+    pub fn apply<'a>(&mut self, time: f64, values: &[(&'a str, f64)]) -> Vec<(&'a str, f64)> {
+        let latitude = values.iter().find(|(name, _)| *name == "latitude").map(|&(_, value)| value);
+        let mut exported = Vec::with_capacity(values.len());
+        for &(name, value) in values {
+            if LOCATION_SIGNALS.contains(&name) {
+                match self.policy.location {
+                    LocationPolicy::Keep => {
+                        self.stats.passed += 1;
+                        exported.push((name, value));
+                    }
+                    LocationPolicy::Drop => self.stats.dropped += 1,
+                    LocationPolicy::Grid(size) => {
+                        self.stats.coarsened += 1;
+                        exported.push((name, snap_to_grid(name, value, latitude, size)));
+                    }
+                }
+                continue;
+            }
+            match self.policy.signal(name) {
+                SignalPolicy::Keep => {
+                    self.stats.passed += 1;
+                    exported.push((name, value));
+                }
+                SignalPolicy::Drop => self.stats.dropped += 1,
+                SignalPolicy::Round(step) => {
+                    self.stats.coarsened += 1;
+                    exported.push((name, (value / step).round() * step));
+                }
+                SignalPolicy::Average(length) => {
+                    self.stats.averaged += 1;
+                    if let Some(mean) = self.average(name, time, value, length) {
+                        self.stats.averages += 1;
+                        exported.push((name, mean));
+                    }
+                }
+            }
+        }
+        exported
+    }
+
+    // Adds `value` to the signal's window; returns the mean of the window
+    // that ended before `time`, if one did
+    fn average(&mut self, name: &str, time: f64, value: f64, length: f64) -> Option<f64> {
+        let next = Window {
+            end: ((time / length).floor() + 1.0) * length,
+            sum: value,
+            count: 1,
+        };
+        let window = self.windows.entry(name.to_string()).or_insert(Window {
+            sum: 0.0,
+            count: 0,
+            ..next
+        });
+        if time < window.end {
+            window.sum += value;
+            window.count += 1;
+            return None;
+        }
+        let mean = (window.count > 0).then(|| window.sum / window.count as f64);
+        *window = next;
+        mean
+    }
+
+    // Means of the windows still open at the end of a run, which would
+    // otherwise never go out
+    pub fn flush(&mut self) -> Vec<(&str, f64)> {
+        let mut pending = Vec::new();
+        for (name, window) in &mut self.windows {
+            if window.count > 0 {
+                pending.push((name.as_str(), window.sum / window.count as f64));
+                window.sum = 0.0;
+                window.count = 0;
+            }
+        }
+        self.stats.averages += pending.len() as u64;
+        pending
+    }
+
+    // A whole recorded table at once, e.g. before it is written to a CSV
+    // file, with the sample times in s. `None` for a dropped column; an
+    // averaged column holds the mean of each row's window, the last partial
+    // window included.
+    pub fn apply_columns(&mut self, times: &[f64], columns: &[(&str, &[f64])]) -> Vec<Option<Vec<f64>>> {
+        let latitude = columns.iter().find(|(name, _)| *name == "latitude").map(|&(_, values)| values);
+        let mut exported = Vec::with_capacity(columns.len());
+        for &(name, values) in columns {
+            let count = values.len() as u64;
+            if LOCATION_SIGNALS.contains(&name) {
+                exported.push(match self.policy.location {
+                    LocationPolicy::Keep => {
+                        self.stats.passed += count;
+                        Some(values.to_vec())
+                    }
+                    LocationPolicy::Drop => {
+                        self.stats.dropped += count;
+                        None
+                    }
+                    LocationPolicy::Grid(size) => {
+                        self.stats.coarsened += count;
+                        let row_latitude = |row: usize| latitude.and_then(|latitude| latitude.get(row).copied());
+                        Some(values.iter().enumerate().map(|(row, &value)| snap_to_grid(name, value, row_latitude(row), size)).collect())
+                    }
+                });
+                continue;
+            }
+            exported.push(match self.policy.signal(name) {
+                SignalPolicy::Keep => {
+                    self.stats.passed += count;
+                    Some(values.to_vec())
+                }
+                SignalPolicy::Drop => {
+                    self.stats.dropped += count;
+                    None
+                }
+                SignalPolicy::Round(step) => {
+                    self.stats.coarsened += count;
+                    Some(values.iter().map(|value| (value / step).round() * step).collect())
+                }
+                SignalPolicy::Average(length) => {
+                    self.stats.averaged += count;
+                    Some(self.window_means(times, values, length))
+                }
+            });
+        }
+        exported
+    }
+
+    // Every value replaced by the mean of its window
+    fn window_means(&mut self, times: &[f64], values: &[f64], length: f64) -> Vec<f64> {
+        let window = |row: usize| (times[row] / length).floor();
+        let mut means = Vec::with_capacity(values.len());
+        let mut start = 0;
+        while start < values.len() {
+            let end = (start..values.len()).find(|&row| window(row) != window(start)).unwrap_or(values.len());
+            let mean = values[start..end].iter().sum::<f64>() / (end - start) as f64;
+            means.extend(std::iter::repeat_n(mean, end - start));
+            self.stats.averages += 1;
+            start = end;
+        }
+        means
+    }
+
+    pub fn print_summary(&self) {
+        let stats = &self.stats;
+        let location = match self.policy.location {
+            LocationPolicy::Keep => "kept".to_string(),
+            LocationPolicy::Drop => "dropped".to_string(),
+            LocationPolicy::Grid(size) => format!("on a {} grid", locale::current().distance(size, 1)),
+        };
+        println!(
+            "Privacy filter: {} values passed, {} dropped, {} coarsened, {} averaged into {}; location {}",
+            stats.passed, stats.dropped, stats.coarsened, stats.averaged, stats.averages, location
+        );
+    }
+}
+
+// `values` as far as the filter lets them out, all of them without one
+pub fn filtered<'a>(filter: Option<&mut PrivacyFilter>, time: f64, values: Vec<(&'a str, f64)>) -> Vec<(&'a str, f64)> {
+    match filter {
+        Some(filter) => filter.apply(time, &values),
+        None => values,
+    }
+}
+
+// Uses `--privacy <path>` from the command line, then SIM_PRIVACY
+pub fn path_from_args() -> Option<PathBuf> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--privacy")
+        .and_then(|i| args.get(i + 1).map(PathBuf::from))
+        .or_else(|| env::var_os(PRIVACY_ENV_VAR).map(PathBuf::from))
+}
+
+// Centre of the grid cell the coordinate falls into. Cells are `size` km
+// high; their width in degrees of longitude grows towards the poles, taken
+// at the cell's latitude.
+fn snap_to_grid(name: &str, value: f64, latitude: Option<f64>, size: f64) -> f64 {
+    let latitude_step = size / KM_PER_DEGREE;
+    let snap = |value: f64, step: f64| ((value / step).floor() + 0.5) * step;
+    if name == "latitude" {
+        return snap(value, latitude_step);
+    }
+    let cell_latitude = latitude.map_or(0.0, |latitude| snap(latitude, latitude_step));
+    let longitude_step = size / (KM_PER_DEGREE * cell_latitude.to_radians().cos().max(0.01));
+    snap(value, longitude_step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(settings: &str) -> PrivacyFilter {
+        PrivacyFilter::new(PrivacyPolicy::from_config(&Config::parse(settings).unwrap()).unwrap())
+    }
+
+    fn policy_error(settings: &str) -> String {
+        PrivacyPolicy::from_config(&Config::parse(settings).unwrap()).unwrap_err()
+    }
+
+    #[test]
+    fn bad_settings_are_rejected() {
+        assert!(policy_error("speed = drop").contains("unknown privacy setting 'speed'"));
+        assert!(policy_error("signal.latitude = drop").contains("follows the location setting"));
+        assert!(policy_error("signal.speed = round:0").starts_with("signal.speed:"));
+        assert!(policy_error("signal.speed = average:0s").contains("longer than zero"));
+        assert!(policy_error("default = blur").starts_with("default:"));
+        assert!(policy_error("location = grid:-5").starts_with("location:"));
+
+        let policy = PrivacyPolicy::from_config(&Config::parse("default = drop\nsignal.speed = round:10").unwrap()).unwrap();
+        assert_eq!(policy.signal("speed"), SignalPolicy::Round(10.0));
+        assert_eq!(policy.signal("engine_rpm"), SignalPolicy::Drop);
+    }
+
+    #[test]
+    fn signals_are_rounded_dropped_and_averaged() {
+        let mut filter = filter("signal.speed = round:10\nsignal.fuel_level = drop\nsignal.engine_rpm = average:60s");
+        let exported = filter.apply(0.0, &[("speed", 54.0), ("fuel_level", 40.0), ("engine_rpm", 1000.0), ("trip", 3.2)]);
+        assert_eq!(exported, vec![("speed", 50.0), ("trip", 3.2)]);
+
+        // The mean of the first minute goes out with the first sample past it
+        assert!(filter.apply(30.0, &[("engine_rpm", 2000.0)]).is_empty());
+        assert_eq!(filter.apply(60.0, &[("engine_rpm", 4000.0)]), vec![("engine_rpm", 1500.0)]);
+        assert!(filter.apply(90.0, &[("engine_rpm", 2000.0)]).is_empty());
+        assert_eq!(filter.stats, PrivacyStats { passed: 1, dropped: 1, coarsened: 1, averaged: 4, averages: 1 });
+    }
+
+    #[test]
+    fn the_last_partial_window_is_flushed_once() {
+        let mut filter = filter("signal.engine_rpm = average:60s");
+        for (time, rpm) in [(0.0, 1000.0), (60.0, 2000.0), (90.0, 3000.0)] {
+            filter.apply(time, &[("engine_rpm", rpm)]);
+        }
+        assert_eq!(filter.flush(), vec![("engine_rpm", 2500.0)]);
+        assert!(filter.flush().is_empty());
+        assert_eq!(filter.stats.averages, 2);
+    }
+
+    #[test]
+    fn columns_hold_the_mean_of_each_window() {
+        let mut filter = filter("timestamps = 1min\nsignal.speed = average:60s\nsignal.fuel_level = drop");
+        let times = [0.0, 30.0, 60.0, 90.0, 120.0];
+        let speed = [10.0, 20.0, 30.0, 50.0, 70.0];
+        let columns = filter.apply_columns(&times, &[("speed", &speed), ("fuel_level", &speed), ("trip", &speed)]);
+        assert_eq!(columns[0], Some(vec![15.0, 15.0, 40.0, 40.0, 70.0]));
+        assert_eq!(columns[1], None);
+        assert_eq!(columns[2], Some(speed.to_vec()));
+        assert_eq!(filter.stats.averages, 3);
+        assert_eq!(filter.timestamp(90.0), 60.0);
+    }
+
+    #[test]
+    fn positions_snap_to_the_centre_of_their_cell() {
+        // 11.132 km is a tenth of a degree of latitude
+        let latitude = snap_to_grid("latitude", 48.1374, None, 11.132);
+        assert!((latitude - 48.15).abs() < 1e-9);
+        // At the equator a cell is as wide in longitude as it is high
+        assert!((snap_to_grid("longitude", 11.5755, None, 11.132) - 11.55).abs() < 1e-9);
+        // Further north the cells span more degrees of longitude
+        let longitude = snap_to_grid("longitude", 11.5755, Some(48.1374), 11.132);
+        let width = 0.1 / 48.15_f64.to_radians().cos();
+        assert!((longitude / width - 0.5).fract().abs() < 1e-6);
+        assert!((longitude - 11.5755).abs() <= width / 2.0);
+
+        let mut filter = filter("location = drop");
+        assert!(filter.apply(0.0, &[("latitude", 48.1), ("longitude", 11.5)]).is_empty());
+    }
+
+    #[test]
+    fn a_stream_follows_the_whole_policy() {
+        let mut filter = filter(
+            "location = grid:10\ntimestamps = 15min\nsignal.speed = round:50\n\
+             signal.engine_rpm = average:30min\nsignal.fuel_level = drop",
+        );
+        let mut averages = Vec::new();
+        for step in 1..=4 {
+            let time = step as f64 * 600.0;
+            assert_eq!(filter.timestamp(time) % 900.0, 0.0);
+            let sample = [
+                ("speed", 60.2),
+                ("engine_rpm", 1800.0 + step as f64),
+                ("fuel_level", 80.0),
+                ("latitude", 48.1374),
+                ("longitude", 11.5755),
+            ];
+            let exported = filter.apply(time, &sample);
+            let value = |name: &str| exported.iter().find(|(signal, _)| *signal == name).map(|&(_, value)| value);
+            assert_eq!(value("speed"), Some(50.0));
+            assert_eq!(value("fuel_level"), None);
+            // Snapped to the middle of a 10 km cell, away from the exact position
+            let latitude = value("latitude").unwrap();
+            assert_ne!(latitude, 48.1374);
+            assert!((latitude / (10.0 / KM_PER_DEGREE) - 0.5).fract().abs() < 1e-6);
+            averages.extend(value("engine_rpm"));
+        }
+        // The engine speed only goes out once its first half hour is over
+        assert_eq!(averages, vec![1801.5]);
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::locale;
+use crate::restriction::{self, RestrictionProfile};
+use crate::scenario::{self, Value};
+use crate::sim_log;
+
+// Names of the remote commands, as tokens are allowed them
+pub const REMOTE_COMMANDS: &[&str] = &["precondition", "lock", "unlock", "valet", "restrict"];
+// Allows driving a simulation run (start, pause, step, faults) from a test
+// framework, over the dashboard or gRPC
+pub const CONTROL: &str = "control";
+// Shorter tokens are too easy to guess
+const MIN_TOKEN_LENGTH: usize = 8;
+
+// What the backend may ask of the vehicle over the telematics link
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RemoteCommand {
+    // Runs the climate control for this many seconds
+    Precondition(f64),
+    LockDoors,
+    UnlockDoors,
+    // Holds the vehicle below this speed in km/h, or lifts the limit
+    ValetMode(Option<f64>),
+    // Puts a restriction profile on the driver, or lifts it
+    Restrict(Option<RestrictionProfile>),
+}
+
+impl RemoteCommand {
+    // precondition [duration], lock, unlock, valet <km/h>, valet off,
+    // restrict <profile> or restrict off
+    pub fn parse(line: &str) -> Result<RemoteCommand, String> {
+        let mut words = line.split_whitespace();
+        let command = match (words.next(), words.next()) {
+            (Some("precondition"), None) => RemoteCommand::Precondition(15.0 * 60.0),
+            (Some("precondition"), Some(duration)) => match scenario::parse_duration(&Value::String(duration.to_string()))? {
+                duration if duration > 0.0 => RemoteCommand::Precondition(duration),
+                _ => return Err("precondition needs a duration longer than zero".to_string()),
+            },
+            (Some("lock"), None) => RemoteCommand::LockDoors,
+            (Some("unlock"), None) => RemoteCommand::UnlockDoors,
+            (Some("valet"), Some("off")) => RemoteCommand::ValetMode(None),
+            (Some("valet"), Some(limit)) => match limit.trim_end_matches("km/h").parse::<f64>() {
+                Ok(limit) if limit > 0.0 => RemoteCommand::ValetMode(Some(limit)),
+                _ => return Err(format!("valet needs a speed limit in km/h or off, got '{}'", limit)),
+            },
+            (Some("restrict"), Some("off")) => RemoteCommand::Restrict(None),
+            (Some("restrict"), Some(name)) => match restriction::find(name) {
+                Some(profile) => RemoteCommand::Restrict(Some(profile)),
+                None => return Err(format!("unknown restriction profile '{}', expected {} or off", name, restriction::names())),
+            },
+            _ => {
+                return Err(format!(
+                    "expected precondition [duration], lock, unlock, valet <km/h>|off or restrict <profile>|off, got '{}'",
+                    line.trim()
+                ))
+            }
+        };
+        match words.next() {
+            Some(extra) => Err(format!("unexpected '{}' after the command", extra)),
+            None => Ok(command),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RemoteCommand::Precondition(_) => "precondition",
+            RemoteCommand::LockDoors => "lock",
+            RemoteCommand::UnlockDoors => "unlock",
+            RemoteCommand::ValetMode(_) => "valet",
+            RemoteCommand::Restrict(_) => "restrict",
+        }
+    }
+}
+
+impl fmt::Display for RemoteCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemoteCommand::Precondition(duration) => write!(f, "precondition {}min", duration / 60.0),
+            RemoteCommand::ValetMode(Some(limit)) => write!(f, "valet {}", limit),
+            RemoteCommand::ValetMode(None) => write!(f, "valet off"),
+            RemoteCommand::Restrict(Some(profile)) => write!(f, "restrict {}", profile.name),
+            RemoteCommand::Restrict(None) => write!(f, "restrict off"),
+            command => write!(f, "{}", command.name()),
+        }
+    }
+}
+
+// Someone allowed to send remote commands
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenHolder {
+    pub name: String,
+    token: String,
+    // Commands this holder may send, all of them when None
+    pub allowed: Option<Vec<String>>,
+    // Seconds of simulated time after which the token no longer works
+    pub expires: Option<f64>,
+}
+
+// Tokens of the remote command channel, kept in a `key = value` file:
+//
+//     token.owner = 3f8a61c07d2e
+//     token.valet = 91b0c4e2
+//     allow.valet = lock, unlock
+//     expires.valet = 3h
+//
+// A holder without an `allow.` line may send every command and control the
+// run; `control` in an `allow.` line lets the holder control it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenStore {
+    holders: Vec<TokenHolder>,
+}
+
+impl TokenStore {
+    pub fn from_config(config: &Config) -> Result<TokenStore, String> {
+        let mut tokens = BTreeMap::new();
+        let mut allowed = BTreeMap::new();
+        let mut expires = BTreeMap::new();
+        for (key, value) in config.entries() {
+            let invalid = |e: String| format!("{}: {}", key, e);
+            match key.split_once('.') {
+                Some(("token", name)) if value.len() < MIN_TOKEN_LENGTH => {
+                    return Err(format!("token of {} must have at least {} characters", name, MIN_TOKEN_LENGTH));
+                }
+                Some(("token", name)) => {
+                    tokens.insert(name, value.to_string());
+                }
+                Some(("allow", name)) => {
+                    let commands: Vec<String> = value.split(',').map(|command| command.trim().to_string()).collect();
+                    let known = |command: &str| REMOTE_COMMANDS.contains(&command) || command == CONTROL;
+                    if let Some(unknown) = commands.iter().find(|command| !known(command)) {
+                        return Err(invalid(format!(
+                            "unknown command '{}', expected {} or {}",
+                            unknown,
+                            REMOTE_COMMANDS.join(", "),
+                            CONTROL
+                        )));
+                    }
+                    allowed.insert(name, commands);
+                }
+                Some(("expires", name)) => {
+                    expires.insert(name, scenario::parse_duration(&Value::String(value.to_string())).map_err(invalid)?);
+                }
+                _ => return Err(format!("unknown token setting '{}'", key)),
+            }
+        }
+        if let Some(name) = allowed.keys().chain(expires.keys()).find(|name| !tokens.contains_key(*name)) {
+            return Err(format!("{} has settings but no token", name));
+        }
+        let holders = tokens
+            .into_iter()
+            .map(|(name, token)| TokenHolder {
+                name: name.to_string(),
+                token,
+                allowed: allowed.remove(name),
+                expires: expires.remove(name),
+            })
+            .collect();
+        Ok(TokenStore { holders })
+    }
+
+    pub fn load(path: &Path) -> io::Result<TokenStore> {
+        TokenStore::from_config(&Config::load(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // The holder of `token` if it may send `command` at `time`, or why not.
+    // Tokens are compared in constant time so a wrong one gives nothing away
+    // about the right one.
+    pub fn authorize(&self, token: &str, command: &RemoteCommand, time: f64) -> Result<&TokenHolder, String> {
+        self.authorize_name(token, command.name(), time)
+    }
+
+    // Like `authorize`, for a command by name, e.g. CONTROL
+    pub fn authorize_name(&self, token: &str, name: &str, time: f64) -> Result<&TokenHolder, String> {
+        // Every holder is compared, so the time taken does not tell which
+        // one matched
+        let holder = self
+            .holders
+            .iter()
+            .fold(None, |found, holder| match constant_time_eq(holder.token.as_bytes(), token.as_bytes()) {
+                true => Some(holder),
+                false => found,
+            })
+            .ok_or_else(|| "unknown token".to_string())?;
+        if holder.expires.is_some_and(|expires| time >= expires) {
+            return Err(format!("token of {} expired", holder.name));
+        }
+        if holder.allowed.as_ref().is_some_and(|allowed| !allowed.iter().any(|allowed| allowed == name)) {
+            return Err(format!("{} may not send {}", holder.name, name));
+        }
+        Ok(holder)
+    }
+}
+
+// Takes as long for every `expected` token of any length: the length
+// difference goes into the result instead of ending the comparison early
+fn constant_time_eq(expected: &[u8], given: &[u8]) -> bool {
+    let difference = given.iter().enumerate().fold(expected.len() ^ given.len(), |difference, (i, byte)| {
+        difference | (expected.get(i).copied().unwrap_or(0) ^ byte) as usize
+    });
+    difference == 0
+}
+
+// A command the backend sends at `at` seconds into the run
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteRequest {
+    pub at: f64,
+    pub token: String,
+    pub command: String,
+}
+
+// Requests as sent to the backend, one per line as `<time> <token>
+// <command>`, e.g. `2h 3f8a61c07d2e valet 40`; `#` starts a comment
+pub fn load_requests(path: &Path) -> io::Result<Vec<RemoteRequest>> {
+    let invalid = |number: usize, message: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number, message));
+    let mut requests = Vec::new();
+    for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let (Some(at), Some(token)) = (words.next(), words.next()) else {
+            return Err(invalid(index + 1, "expected <time> <token> <command>".to_string()));
+        };
+        let command = words.collect::<Vec<_>>().join(" ");
+        if command.is_empty() {
+            return Err(invalid(index + 1, "expected <time> <token> <command>".to_string()));
+        }
+        requests.push(RemoteRequest {
+            at: scenario::parse_duration(&Value::String(at.to_string())).map_err(|e| invalid(index + 1, e))?,
+            token: token.to_string(),
+            command,
+        });
+    }
+    requests.sort_by(|a, b| a.at.total_cmp(&b.at));
+    Ok(requests)
+}
+
+// One line of the audit log. Every request that reached the vehicle is
+// logged, whether it ran or not; tokens never are.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEntry {
+    pub time: f64, // s of simulated time
+    pub sent: f64,
+    pub holder: Option<String>,
+    pub command: String,
+    pub executed: bool,
+    pub outcome: String,
+}
+
+// A command that passed the token check, for the vehicle to carry out
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizedCommand {
+    pub holder: String,
+    pub command: RemoteCommand,
+    pub sent: f64,
+}
+
+// The vehicle's end of the remote command channel: requests wait at the
+// backend until their time has come and the network is up, are checked
+// against the tokens on arrival, and everything that arrives goes to the
+// audit log.
+pub struct RemoteChannel {
+    tokens: TokenStore,
+    pending: VecDeque<RemoteRequest>,
+    audit: Option<BufWriter<File>>,
+    pub log: Vec<AuditEntry>,
+}
+
+impl RemoteChannel {
+    pub fn new(tokens: TokenStore, requests: Vec<RemoteRequest>) -> Self {
+        RemoteChannel {
+            tokens,
+            pending: requests.into(),
+            audit: None,
+            log: Vec::new(),
+        }
+    }
+
+    // The audit log goes to this file as JSON lines, after what earlier
+    // runs logged there
+    pub fn with_audit(mut self, path: &Path) -> io::Result<Self> {
+        self.audit = Some(BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?));
+        Ok(self)
+    }
+
+    // Requests delivered by `time` that may run; refused ones are logged
+    // here. Nothing arrives while the vehicle is offline.
+    pub fn receive(&mut self, time: f64, online: bool) -> Vec<AuthorizedCommand> {
+        let mut authorized = Vec::new();
+        while online && self.pending.front().is_some_and(|request| request.at <= time) {
+            let Some(request) = self.pending.pop_front() else {
+                break;
+            };
+            let checked = RemoteCommand::parse(&request.command)
+                .and_then(|command| self.tokens.authorize(&request.token, &command, time).map(|holder| (holder.name.clone(), command)));
+            match checked {
+                Ok((holder, command)) => authorized.push(AuthorizedCommand {
+                    holder,
+                    command,
+                    sent: request.at,
+                }),
+                Err(reason) => {
+                    sim_log::warn("remote", &format!("Refused remote command '{}': {}", request.command, reason));
+                    self.audit(AuditEntry {
+                        time,
+                        sent: request.at,
+                        holder: None,
+                        command: request.command,
+                        executed: false,
+                        outcome: reason,
+                    });
+                }
+            }
+        }
+        authorized
+    }
+
+    // Logs what became of an authorized command: what it did, or why the
+    // vehicle would not do it
+    pub fn record(&mut self, time: f64, command: &AuthorizedCommand, outcome: Result<String, String>) {
+        let executed = outcome.is_ok();
+        let outcome = outcome.unwrap_or_else(|reason| reason);
+        let message = format!("{} from {}: {}", command.command, command.holder, outcome);
+        if executed {
+            sim_log::info("remote", &message);
+        } else {
+            sim_log::warn("remote", &message);
+        }
+        self.audit(AuditEntry {
+            time,
+            sent: command.sent,
+            holder: Some(command.holder.clone()),
+            command: command.command.to_string(),
+            executed,
+            outcome,
+        });
+    }
+
+    fn audit(&mut self, entry: AuditEntry) {
+        if let Some(audit) = &mut self.audit {
+            let written = serde_json::to_writer(&mut *audit, &entry).map_err(io::Error::from).and_then(|_| writeln!(audit));
+            if let Err(e) = written {
+                sim_log::warn("remote", &format!("Cannot write the audit log, no more entries: {}", e));
+                self.audit = None;
+            }
+        }
+        self.log.push(entry);
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.audit {
+            Some(audit) => audit.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn print_summary(&self) {
+        let executed = self.log.iter().filter(|entry| entry.executed).count();
+        let longest = self.log.iter().map(|entry| entry.time - entry.sent).fold(0.0, f64::max);
+        println!(
+            "Remote commands: {} executed, {} refused, {} not delivered; longest delay {} s",
+            executed,
+            self.log.len() - executed,
+            self.pending.len(),
+            locale::current().number(longest, 0)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKENS: &str = "token.owner = 3f8a61c07d2e\n\
+                          token.valet = 91b0c4e2\nallow.valet = lock, unlock\nexpires.valet = 3h";
+
+    fn tokens() -> TokenStore {
+        TokenStore::from_config(&Config::parse(TOKENS).unwrap()).unwrap()
+    }
+
+    fn request(at: f64, token: &str, command: &str) -> RemoteRequest {
+        RemoteRequest {
+            at,
+            token: token.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_the_remote_commands() {
+        assert_eq!(RemoteCommand::parse("precondition"), Ok(RemoteCommand::Precondition(900.0)));
+        assert_eq!(RemoteCommand::parse("precondition 30min"), Ok(RemoteCommand::Precondition(1800.0)));
+        assert_eq!(RemoteCommand::parse(" lock "), Ok(RemoteCommand::LockDoors));
+        assert_eq!(RemoteCommand::parse("valet 40km/h"), Ok(RemoteCommand::ValetMode(Some(40.0))));
+        assert_eq!(RemoteCommand::parse("valet off"), Ok(RemoteCommand::ValetMode(None)));
+        assert_eq!(RemoteCommand::parse("restrict teen"), Ok(RemoteCommand::Restrict(restriction::find("teen"))));
+        assert_eq!(RemoteCommand::parse("restrict off"), Ok(RemoteCommand::Restrict(None)));
+
+        assert!(RemoteCommand::parse("precondition 0s").is_err());
+        assert!(RemoteCommand::parse("valet -5").is_err());
+        assert!(RemoteCommand::parse("restrict racing").is_err());
+        assert!(RemoteCommand::parse("lock now").is_err());
+        assert!(RemoteCommand::parse("honk").is_err());
+        assert_eq!(RemoteCommand::ValetMode(Some(40.0)).to_string(), "valet 40");
+    }
+
+    #[test]
+    fn token_files_are_checked() {
+        let parse = |text: &str| TokenStore::from_config(&Config::parse(text).unwrap());
+        assert!(parse("token.short = 1234567").is_err());
+        assert!(parse("token.valet = 91b0c4e2\nallow.valet = lock, honk").is_err());
+        assert!(parse("allow.nobody = lock").is_err());
+        assert!(parse("secret.owner = 3f8a61c07d2e").is_err());
+        assert!(parse("token.ci = 91b0c4e2\nallow.ci = control").is_ok());
+    }
+
+    #[test]
+    fn tokens_are_allowed_their_commands_until_they_expire() {
+        let tokens = tokens();
+        assert_eq!(tokens.authorize("3f8a61c07d2e", &RemoteCommand::ValetMode(None), 0.0).unwrap().name, "owner");
+        assert_eq!(tokens.authorize_name("3f8a61c07d2e", CONTROL, 1e9).unwrap().name, "owner");
+        assert_eq!(tokens.authorize("91b0c4e2", &RemoteCommand::LockDoors, 0.0).unwrap().name, "valet");
+
+        assert_eq!(tokens.authorize("91b0c4e3", &RemoteCommand::LockDoors, 0.0), Err("unknown token".to_string()));
+        assert!(tokens.authorize("91b0c4e2", &RemoteCommand::ValetMode(None), 0.0).is_err());
+        assert!(tokens.authorize_name("91b0c4e2", CONTROL, 0.0).is_err());
+        assert_eq!(tokens.authorize("91b0c4e2", &RemoteCommand::LockDoors, 3.0 * 3600.0), Err("token of valet expired".to_string()));
+    }
+
+    #[test]
+    fn tokens_of_another_length_never_match() {
+        assert!(constant_time_eq(b"91b0c4e2", b"91b0c4e2"));
+        assert!(!constant_time_eq(b"91b0c4e2", b"91b0c4e3"));
+        assert!(!constant_time_eq(b"91b0c4e2", b"91b0c4e"));
+        assert!(!constant_time_eq(b"91b0c4e2", b"91b0c4e2\0"));
+        assert!(!constant_time_eq(b"91b0c4e2", b""));
+    }
+
+    #[test]
+    fn loads_requests_in_time_order() {
+        let path = std::env::temp_dir().join(format!("remote_requests_{}.txt", std::process::id()));
+        fs::write(&path, "# backend queue\n2h 91b0c4e2 lock\n\n30min 3f8a61c07d2e valet 40  # parking\n").unwrap();
+        let requests = load_requests(&path).unwrap();
+        assert_eq!(requests, vec![request(1800.0, "3f8a61c07d2e", "valet 40"), request(7200.0, "91b0c4e2", "lock")]);
+
+        fs::write(&path, "2h 91b0c4e2\n").unwrap();
+        assert!(load_requests(&path).unwrap_err().to_string().starts_with("line 1:"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn requests_arrive_once_due_and_online_and_everything_is_audited() {
+        let path = std::env::temp_dir().join(format!("remote_audit_{}.jsonl", std::process::id()));
+        fs::write(&path, "{\"earlier\":\"run\"}\n").unwrap();
+        let requests = vec![
+            request(10.0, "3f8a61c07d2e", "unlock"),
+            request(20.0, "91b0c4e2", "valet 40"),
+            request(30.0, "00000000", "lock"),
+        ];
+        let mut channel = RemoteChannel::new(tokens(), requests).with_audit(&path).unwrap();
+
+        assert!(channel.receive(5.0, true).is_empty());
+        assert!(channel.receive(25.0, false).is_empty());
+        assert_eq!(channel.pending(), 3);
+        let arrived = channel.receive(25.0, true);
+        assert_eq!(arrived.len(), 1);
+        assert_eq!((arrived[0].holder.as_str(), arrived[0].command, arrived[0].sent), ("owner", RemoteCommand::UnlockDoors, 10.0));
+        channel.record(25.0, &arrived[0], Ok("doors unlocked".to_string()));
+        assert!(channel.receive(30.0, true).is_empty());
+        assert_eq!(channel.pending(), 0);
+        channel.flush().unwrap();
+
+        let executed: Vec<_> = channel.log.iter().map(|entry| (entry.command.as_str(), entry.executed)).collect();
+        assert_eq!(executed, vec![("valet 40", false), ("unlock", true), ("lock", false)]);
+        assert_eq!(channel.log[2].outcome, "unknown token");
+
+        // Earlier runs stay in the audit log, and tokens never go into it
+        let audit = fs::read_to_string(&path).unwrap();
+        assert_eq!(audit.lines().count(), 4);
+        assert!(audit.starts_with("{\"earlier\":\"run\"}"));
+        assert!(!audit.contains("3f8a61c07d2e") && !audit.contains("91b0c4e2"));
+        fs::remove_file(&path).unwrap();
+    }
+}
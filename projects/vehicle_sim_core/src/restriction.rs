@@ -0,0 +1,34 @@
+// Limits the owner puts on someone else driving the vehicle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestrictionProfile {
+    pub name: &'static str,
+    pub max_speed: f64,        // km/h
+    pub max_acceleration: f64, // m/s^2
+    pub max_volume: f64,       // % of the audio system's full volume
+}
+
+// Parking the car around the block: slow, gentle and quiet
+pub const VALET: RestrictionProfile = RestrictionProfile {
+    name: "valet",
+    max_speed: 40.0,
+    max_acceleration: 1.5,
+    max_volume: 0.0,
+};
+
+// A new driver on the family car
+pub const TEEN: RestrictionProfile = RestrictionProfile {
+    name: "teen",
+    max_speed: 110.0,
+    max_acceleration: 2.5,
+    max_volume: 50.0,
+};
+
+pub const PROFILES: &[RestrictionProfile] = &[VALET, TEEN];
+
+pub fn find(name: &str) -> Option<RestrictionProfile> {
+    PROFILES.iter().copied().find(|profile| profile.name.eq_ignore_ascii_case(name))
+}
+
+pub fn names() -> String {
+    PROFILES.iter().map(|profile| profile.name).collect::<Vec<_>>().join(", ")
+}
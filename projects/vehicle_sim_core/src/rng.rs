@@ -0,0 +1,146 @@
+use rand::{Error, RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+pub const SEED_ENV_VAR: &str = "SIM_SEED";
+
+// Seedable random source shared by all simulations. The seed is always
+// known, so any run (including failing ones) can be reproduced exactly.
+// ChaCha12 is the generator behind `StdRng`; using it directly lets the
+// stream position be saved in checkpoints.
+#[derive(Serialize, Deserialize)]
+pub struct SimRng {
+    seed: u64,
+    rng: ChaCha12Rng,
+}
+
+impl SimRng {
+    pub fn from_seed(seed: u64) -> Self {
+        SimRng {
+            seed,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+        }
+    }
+
+    // Picks a fresh seed when the run does not ask for a specific one
+    pub fn from_entropy() -> Self {
+        SimRng::from_seed(rand::random())
+    }
+
+    // Uses `--seed <n>` / `--seed=<n>` from the command line, then the
+    // SIM_SEED environment variable, and falls back to a random seed.
+    pub fn from_args_or_env() -> Self {
+        SimRng::from_seed_or_env(seed_from_args(env::args()))
+    }
+
+    // Like `from_args_or_env`, trying `fallback` (a scenario's seed) before
+    // picking a random seed
+    pub fn from_args_env_or(fallback: Option<u64>) -> Self {
+        SimRng::from_seed_env_or(seed_from_args(env::args()), fallback)
+    }
+
+    // For programs with their own argument parser: an explicit seed wins,
+    // then SIM_SEED, then a random seed.
+    pub fn from_seed_or_env(seed: Option<u64>) -> Self {
+        SimRng::from_seed_env_or(seed, None)
+    }
+
+    pub fn from_seed_env_or(seed: Option<u64>, fallback: Option<u64>) -> Self {
+        let rng = match seed.or_else(|| seed_from_env(env::var(SEED_ENV_VAR).ok())).or(fallback) {
+            Some(seed) => SimRng::from_seed(seed),
+            None => SimRng::from_entropy(),
+        };
+
+        eprintln!("Random seed: {} (rerun with --seed {} to reproduce)", rng.seed, rng.seed);
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Independent, still reproducible stream for a sub-component
+    pub fn fork(&mut self) -> SimRng {
+        SimRng::from_seed(self.rng.next_u64())
+    }
+}
+
+fn seed_from_args(args: impl Iterator<Item = String>) -> Option<u64> {
+    let args: Vec<String> = args.collect();
+
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if let Some(value) = arg.strip_prefix("--seed=") {
+            value.parse().ok()
+        } else if arg == "--seed" {
+            args.get(i + 1).and_then(|value| value.parse().ok())
+        } else {
+            None
+        }
+    })
+}
+
+fn seed_from_env(value: Option<String>) -> Option<u64> {
+    value.and_then(|value| value.trim().parse().ok())
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn draws(rng: &mut SimRng) -> Vec<u64> {
+        (0..8).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_stream() {
+        assert_eq!(draws(&mut SimRng::from_seed(42)), draws(&mut SimRng::from_seed(42)));
+        assert_ne!(draws(&mut SimRng::from_seed(42)), draws(&mut SimRng::from_seed(43)));
+
+        let mut parent = SimRng::from_seed(7);
+        let mut fork = parent.fork();
+        assert_ne!(fork.seed(), parent.seed());
+        assert_eq!(draws(&mut fork), draws(&mut SimRng::from_seed(fork.seed())));
+    }
+
+    #[test]
+    fn a_checkpointed_rng_continues_where_it_stopped() {
+        let mut rng = SimRng::from_seed(3);
+        draws(&mut rng);
+        let saved = serde_json::to_string(&rng).unwrap();
+        let mut restored: SimRng = serde_json::from_str(&saved).unwrap();
+        assert_eq!(restored.seed(), 3);
+        assert_eq!(draws(&mut restored), draws(&mut rng));
+    }
+
+    #[test]
+    fn seeds_come_from_either_form_of_the_flag_or_the_environment() {
+        let args = |args: &[&str]| seed_from_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&["sim", "--seed", "12"]), Some(12));
+        assert_eq!(args(&["sim", "--seed=13"]), Some(13));
+        assert_eq!(args(&["sim", "--seed"]), None);
+        assert_eq!(args(&["sim", "--seed", "abc"]), None);
+        assert_eq!(seed_from_env(Some(" 99 ".to_string())), Some(99));
+        assert_eq!(seed_from_env(Some("x".to_string())), None);
+        assert_eq!(seed_from_env(None), None);
+    }
+}
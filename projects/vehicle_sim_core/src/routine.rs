@@ -0,0 +1,151 @@
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+// Outcome of one step of a running routine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutineProgress {
+    Running,
+    Passed,
+    Failed,
+}
+
+// A self-test or service procedure a component registers, started and
+// stopped on request and advanced once per simulation step
+pub trait Routine<T> {
+    fn start(&mut self, target: &mut T);
+    fn step(&mut self, target: &mut T) -> RoutineProgress;
+    // Puts the component back into normal operation when stopped early
+    fn stop(&mut self, _target: &mut T) {}
+    fn results(&self) -> Value;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutineStatus {
+    Idle,
+    Running,
+    Passed,
+    Failed,
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RoutineReport {
+    pub id: u16,
+    pub name: &'static str,
+    pub status: RoutineStatus,
+    pub results: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoutineError {
+    Unknown(String),
+    AlreadyRunning(&'static str),
+    NotRunning(&'static str),
+}
+
+impl fmt::Display for RoutineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoutineError::Unknown(name) => write!(f, "unknown routine {}", name),
+            RoutineError::AlreadyRunning(name) => write!(f, "routine {} is already running", name),
+            RoutineError::NotRunning(name) => write!(f, "routine {} is not running", name),
+        }
+    }
+}
+
+struct Entry<T> {
+    id: u16,
+    name: &'static str,
+    routine: Box<dyn Routine<T>>,
+    status: RoutineStatus,
+}
+
+// Start/stop/get-results control of the routines registered for `T`, in the
+// spirit of UDS RoutineControl. Routines are addressed by name or by their
+// identifier (`0x0201`).
+pub struct RoutineControl<T> {
+    routines: Vec<Entry<T>>,
+}
+
+impl<T> Default for RoutineControl<T> {
+    fn default() -> Self {
+        RoutineControl::new()
+    }
+}
+
+impl<T> RoutineControl<T> {
+    pub fn new() -> Self {
+        RoutineControl { routines: Vec::new() }
+    }
+
+    pub fn register(&mut self, id: u16, name: &'static str, routine: Box<dyn Routine<T>>) {
+        self.routines.push(Entry {
+            id,
+            name,
+            routine,
+            status: RoutineStatus::Idle,
+        });
+    }
+
+    pub fn start(&mut self, routine: &str, target: &mut T) -> Result<(), RoutineError> {
+        let entry = self.find(routine)?;
+        if entry.status == RoutineStatus::Running {
+            return Err(RoutineError::AlreadyRunning(entry.name));
+        }
+        entry.routine.start(target);
+        entry.status = RoutineStatus::Running;
+        Ok(())
+    }
+
+    pub fn stop(&mut self, routine: &str, target: &mut T) -> Result<(), RoutineError> {
+        let entry = self.find(routine)?;
+        if entry.status != RoutineStatus::Running {
+            return Err(RoutineError::NotRunning(entry.name));
+        }
+        entry.routine.stop(target);
+        entry.status = RoutineStatus::Stopped;
+        Ok(())
+    }
+
+    pub fn results(&mut self, routine: &str) -> Result<RoutineReport, RoutineError> {
+        let entry = self.find(routine)?;
+        Ok(report(entry))
+    }
+
+    pub fn reports(&self) -> Vec<RoutineReport> {
+        self.routines.iter().map(report).collect()
+    }
+
+    // Advances every running routine by one step
+    pub fn step(&mut self, target: &mut T) {
+        for entry in self.routines.iter_mut().filter(|entry| entry.status == RoutineStatus::Running) {
+            entry.status = match entry.routine.step(target) {
+                RoutineProgress::Running => RoutineStatus::Running,
+                RoutineProgress::Passed => RoutineStatus::Passed,
+                RoutineProgress::Failed => RoutineStatus::Failed,
+            };
+        }
+    }
+
+    fn find(&mut self, routine: &str) -> Result<&mut Entry<T>, RoutineError> {
+        let id = routine
+            .strip_prefix("0x")
+            .and_then(|hex| u16::from_str_radix(hex, 16).ok());
+        self.routines
+            .iter_mut()
+            .find(|entry| entry.name == routine || Some(entry.id) == id)
+            .ok_or_else(|| RoutineError::Unknown(routine.to_string()))
+    }
+}
+
+fn report<T>(entry: &Entry<T>) -> RoutineReport {
+    RoutineReport {
+        id: entry.id,
+        name: entry.name,
+        status: entry.status,
+        results: entry.routine.results(),
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::remote::{TokenStore, CONTROL};
+use crate::simulation::PauseControl;
+
+// Where a run stands, as reported to whoever controls it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunState {
+    pub paused: bool,
+    pub steps: u64,
+    pub sim_time_seconds: f64,
+    // Latest published signals; non-finite values go out as null
+    pub signals: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlError {
+    // No token, or one that may not control the run
+    Unauthorized(String),
+    InvalidArgument(String),
+    // The simulation no longer takes commands
+    Finished,
+}
+
+impl fmt::Display for ControlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ControlError::Unauthorized(reason) => write!(f, "not allowed to control the run: {}", reason),
+            ControlError::InvalidArgument(reason) => write!(f, "{}", reason),
+            ControlError::Finished => write!(f, "the simulation has finished"),
+        }
+    }
+}
+
+// Lets a test framework drive a run from outside, over the dashboard's
+// `/control/...` routes or the gRPC SimControl service: start, pause and
+// single steps go to the runner's pause control, fault commands to the
+// simulation. Every request needs a token of `tokens` that may `control`.
+pub struct RunControl {
+    pause: Arc<PauseControl>,
+    faults: Mutex<Sender<String>>,
+    tokens: TokenStore,
+    state: Mutex<RunState>,
+}
+
+impl RunControl {
+    // The simulation takes the queued fault commands from the receiver;
+    // they apply with the next step, so a paused run needs a step to show
+    // them
+    pub fn new(pause: Arc<PauseControl>, tokens: TokenStore) -> (Arc<RunControl>, Receiver<String>) {
+        let (faults, commands) = mpsc::channel();
+        let control = RunControl {
+            pause,
+            faults: Mutex::new(faults),
+            tokens,
+            state: Mutex::new(RunState::default()),
+        };
+        (Arc::new(control), commands)
+    }
+
+    // Tokens expire in simulated time, like those of the remote commands
+    pub fn authorize(&self, token: Option<&str>) -> Result<(), ControlError> {
+        let token = token.ok_or_else(|| ControlError::Unauthorized("no token".to_string()))?;
+        let time = self.state.lock().unwrap().sim_time_seconds;
+        self.tokens
+            .authorize_name(token, CONTROL, time)
+            .map(|_| ())
+            .map_err(ControlError::Unauthorized)
+    }
+
+    pub fn start(&self) {
+        self.pause.set_paused(false);
+    }
+
+    pub fn pause(&self) {
+        self.pause.set_paused(true);
+    }
+
+    pub fn step(&self) {
+        self.pause.step_once();
+    }
+
+    pub fn inject_fault(&self, command: &str) -> Result<(), ControlError> {
+        let command = command.trim();
+        if command.is_empty() {
+            return Err(ControlError::InvalidArgument("expected a fault command".to_string()));
+        }
+        self.faults
+            .lock()
+            .unwrap()
+            .send(command.to_string())
+            .map_err(|_| ControlError::Finished)
+    }
+
+    // Call after every step with the signals worth reporting
+    pub fn update(&self, steps: u64, sim_time: f64, signals: &[(&str, f64)]) {
+        let mut state = self.state.lock().unwrap();
+        state.steps = steps;
+        state.sim_time_seconds = sim_time;
+        for (name, value) in signals {
+            state.signals.insert(name.to_string(), *value);
+        }
+    }
+
+    pub fn state(&self) -> RunState {
+        RunState {
+            paused: self.pause.is_paused(),
+            ..self.state.lock().unwrap().clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn control(tokens: &str) -> (Arc<RunControl>, Receiver<String>) {
+        let tokens = TokenStore::from_config(&Config::parse(tokens).unwrap()).unwrap();
+        RunControl::new(Arc::new(PauseControl::new()), tokens)
+    }
+
+    #[test]
+    fn only_tokens_allowed_to_control_get_through() {
+        let (control, _) = control(
+            "token.ci = 0123456789abcdef\n\
+             token.valet = 91b0c4e2\nallow.valet = lock, unlock\n\
+             token.nightly = 5e1f7a9c33\nallow.nightly = control\nexpires.nightly = 1h",
+        );
+        assert_eq!(control.authorize(Some("0123456789abcdef")), Ok(()));
+        assert_eq!(control.authorize(Some("5e1f7a9c33")), Ok(()));
+        assert!(matches!(control.authorize(None), Err(ControlError::Unauthorized(_))));
+        assert!(matches!(control.authorize(Some("0123456789abcdeX")), Err(ControlError::Unauthorized(_))));
+        assert!(matches!(control.authorize(Some("91b0c4e2")), Err(ControlError::Unauthorized(_))));
+
+        control.update(3600, 3600.0, &[]);
+        assert!(matches!(control.authorize(Some("5e1f7a9c33")), Err(ControlError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn drives_the_pause_control_and_queues_faults() {
+        let (control, faults) = control("token.ci = 0123456789abcdef");
+        control.pause();
+        assert!(control.state().paused);
+        control.step();
+        assert!(control.state().paused);
+        control.start();
+        assert!(!control.state().paused);
+
+        control.inject_fault(" leak fl 0.2 ").unwrap();
+        assert_eq!(faults.try_recv(), Ok("leak fl 0.2".to_string()));
+        assert!(matches!(control.inject_fault("  "), Err(ControlError::InvalidArgument(_))));
+        drop(faults);
+        assert_eq!(control.inject_fault("leak fl 0.2"), Err(ControlError::Finished));
+    }
+
+    #[test]
+    fn reports_the_latest_signals() {
+        let (control, _) = control("token.ci = 0123456789abcdef");
+        control.update(1, 0.5, &[("front_left", 32.0), ("front_right", 31.0)]);
+        control.update(2, 1.0, &[("front_left", 31.5)]);
+        let state = control.state();
+        assert_eq!((state.steps, state.sim_time_seconds), (2, 1.0));
+        assert_eq!(state.signals["front_left"], 31.5);
+        assert_eq!(state.signals["front_right"], 31.0);
+    }
+}
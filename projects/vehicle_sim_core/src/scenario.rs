@@ -0,0 +1,608 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sim_log;
+use crate::vehicle_mode::{ModeChange, VehicleMode};
+
+pub const SCENARIO_ENV_VAR: &str = "SIM_SCENARIO";
+
+// Scenario files are TOML
+pub const EXTENSION: &str = "toml";
+
+// An initial condition or a duration: strings, numbers, booleans and arrays
+// of those
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+type Table = BTreeMap<String, Value>;
+
+// The scenario file as written, before its values are checked
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    #[serde(default)]
+    name: String,
+    duration: Option<Value>,
+    seed: Option<u64>,
+    #[serde(default)]
+    initial: Table,
+    #[serde(default)]
+    fault: Vec<FaultEntry>,
+    #[serde(default)]
+    weather: Vec<WeatherEntry>,
+    #[serde(default)]
+    mode: Vec<ModeEntry>,
+    #[serde(default)]
+    route: Vec<RouteEntry>,
+}
+
+#[derive(Deserialize)]
+struct FaultEntry {
+    at: Value,
+    command: String,
+}
+
+#[derive(Deserialize)]
+struct WeatherEntry {
+    at: Value,
+    condition: Option<String>,
+    temperature: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ModeEntry {
+    at: Value,
+    mode: String,
+}
+
+#[derive(Deserialize)]
+struct RouteEntry {
+    distance: f64,
+    elevation: f64,
+    heading: Option<f64>,
+    wind_speed: Option<f64>,
+    wind_direction: Option<f64>,
+    gusts: Option<f64>,
+}
+
+// Durations are seconds, or strings with a unit: "90s", "15min", "2h", "3d"
+pub fn parse_duration(value: &Value) -> Result<f64, String> {
+    let seconds = match value {
+        Value::Number(seconds) => *seconds,
+        Value::String(text) => {
+            let text = text.trim();
+            let split = text.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(text.len());
+            let (number, unit) = text.split_at(split);
+            let number: f64 = number.trim().parse().map_err(|_| format!("invalid duration '{}'", text))?;
+            let scale = match unit {
+                "" | "s" => 1.0,
+                "min" => 60.0,
+                "h" => 3600.0,
+                "d" => 86400.0,
+                _ => return Err(format!("unknown unit '{}' in duration '{}', expected s, min, h or d", unit, text)),
+            };
+            number * scale
+        }
+        _ => return Err("expected a duration".to_string()),
+    };
+    if seconds < 0.0 || !seconds.is_finite() {
+        return Err(format!("duration must not be negative, got {}", seconds));
+    }
+    Ok(seconds)
+}
+
+// A command run `at` seconds into the scenario, e.g. a fault injection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledCommand {
+    pub at: f64,
+    pub command: String,
+}
+
+// From `at` seconds into the scenario until the next point, the weather is
+// held at these values; values left out follow the simulation's own model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeatherPoint {
+    pub at: f64,
+    pub condition: Option<String>,
+    pub temperature: Option<f64>, // °C
+}
+
+// Weather in effect `seconds` into the scenario
+pub fn weather_at(timeline: &[WeatherPoint], seconds: f64) -> Option<&WeatherPoint> {
+    timeline.iter().rev().find(|point| point.at <= seconds)
+}
+
+// Road elevation `distance` km along the route; between two points the
+// road climbs or descends evenly. The heading and wind hold from the point
+// until the next one that gives them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RoutePoint {
+    pub distance: f64, // km
+    pub elevation: f64, // m above sea level
+    // Direction of travel, degrees clockwise from north
+    #[serde(default)]
+    pub heading: Option<f64>,
+    #[serde(default)]
+    pub wind: Option<Wind>,
+}
+
+// Wind blowing from `direction` degrees (0 north, 90 east) at a mean
+// `speed`, with gusts up to `gusts` above it; speeds in m/s
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Wind {
+    pub speed: f64,
+    pub direction: f64,
+    pub gusts: f64,
+}
+
+impl Wind {
+    // Headwind and crosswind for a vehicle heading `heading` degrees. A
+    // tailwind is a negative headwind, wind from the left a negative
+    // crosswind.
+    pub fn components(&self, heading: f64) -> (f64, f64) {
+        let angle = (self.direction - heading).to_radians();
+        (self.speed * angle.cos(), self.speed * angle.sin())
+    }
+}
+
+// Segment of the route `distance` km along it, if the route has one there
+fn route_segment(route: &[RoutePoint], distance: f64) -> Option<(&RoutePoint, &RoutePoint)> {
+    route
+        .windows(2)
+        .find(|pair| distance >= pair[0].distance && distance < pair[1].distance)
+        .map(|pair| (&pair[0], &pair[1]))
+}
+
+// Elevation `distance` km along the route; before the first and past the
+// last point the road stays level
+pub fn elevation_at(route: &[RoutePoint], distance: f64) -> Option<f64> {
+    let (first, last) = (route.first()?, route.last()?);
+    Some(match route_segment(route, distance) {
+        Some((start, end)) => {
+            start.elevation + (end.elevation - start.elevation) * (distance - start.distance) / (end.distance - start.distance)
+        }
+        None if distance < first.distance => first.elevation,
+        None => last.elevation,
+    })
+}
+
+// Last point at or before `distance` km that gives a value, else the first
+// point that does
+fn held_at<T>(route: &[RoutePoint], distance: f64, value: impl Fn(&RoutePoint) -> Option<T>) -> Option<T> {
+    route
+        .iter()
+        .rev()
+        .filter(|point| point.distance <= distance)
+        .find_map(&value)
+        .or_else(|| route.iter().find_map(&value))
+}
+
+// Direction of travel `distance` km along the route, north if not given
+pub fn heading_at(route: &[RoutePoint], distance: f64) -> f64 {
+    held_at(route, distance, |point| point.heading).unwrap_or(0.0)
+}
+
+pub fn wind_at(route: &[RoutePoint], distance: f64) -> Option<Wind> {
+    held_at(route, distance, |point| point.wind)
+}
+
+// Rise over run `distance` km along the route, e.g. 0.08 for an 8% climb
+pub fn grade_at(route: &[RoutePoint], distance: f64) -> f64 {
+    route_segment(route, distance).map_or(0.0, |(start, end)| {
+        (end.elevation - start.elevation) / ((end.distance - start.distance) * 1000.0)
+    })
+}
+
+// Initial conditions, run length, fault schedule, weather timeline, vehicle
+// mode changes and route profile of a run, kept in a TOML file:
+//
+//     name = "Black ice"
+//     duration = "30min"
+//     seed = 7
+//
+//     [initial]
+//     ambient_temperature = 1.5
+//
+//     [[fault]]
+//     at = "5min"
+//     command = "leak 0 2"
+//
+//     [[weather]]
+//     at = "10min"
+//     condition = "icy"
+//     temperature = -3
+//
+//     [[mode]]
+//     at = "20min"
+//     mode = "parked"
+//
+//     [[route]]
+//     distance = 12
+//     elevation = 850
+//     heading = 270
+//     wind_speed = 8
+//     wind_direction = 240
+//     gusts = 6
+//
+// Each simulation reads the initial conditions it knows; `unsupported`
+// lists what it would ignore.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "ScenarioFile")]
+pub struct Scenario {
+    pub name: String,
+    pub duration: Option<f64>, // s
+    pub seed: Option<u64>,
+    initial: Table,
+    pub faults: Vec<ScheduledCommand>,
+    pub weather: Vec<WeatherPoint>,
+    pub modes: Vec<ModeChange>,
+    pub route: Vec<RoutePoint>,
+}
+
+impl TryFrom<ScenarioFile> for Scenario {
+    type Error = String;
+
+    fn try_from(file: ScenarioFile) -> Result<Scenario, String> {
+        let duration = file.duration.as_ref().map(parse_duration).transpose()?;
+        let at = |at: &Value, section: &str| parse_duration(at).map_err(|e| format!("[[{}]] at: {}", section, e));
+
+        let mut faults = Vec::new();
+        for fault in file.fault {
+            faults.push(ScheduledCommand {
+                at: at(&fault.at, "fault")?,
+                command: fault.command,
+            });
+        }
+        let mut weather = Vec::new();
+        for point in file.weather {
+            weather.push(WeatherPoint {
+                at: at(&point.at, "weather")?,
+                condition: point.condition.map(|condition| condition.to_ascii_lowercase()),
+                temperature: point.temperature,
+            });
+        }
+        let mut modes = Vec::new();
+        for change in file.mode {
+            let mode = VehicleMode::parse(&change.mode).ok_or_else(|| {
+                format!("[[mode]] unknown mode {}, expected parked, driving, charging or service", change.mode)
+            })?;
+            modes.push(ModeChange {
+                at: at(&change.at, "mode")?,
+                mode,
+            });
+        }
+        let mut route = Vec::new();
+        for point in file.route {
+            let numbers = [Some(point.distance), Some(point.elevation), point.heading, point.wind_speed, point.wind_direction, point.gusts];
+            if numbers.into_iter().flatten().any(|number| !number.is_finite()) {
+                return Err("[[route]] values must be finite numbers".to_string());
+            }
+            if point.distance < 0.0 {
+                return Err(format!("[[route]] distance must not be negative, got {}", point.distance));
+            }
+            let wind = match point.wind_speed {
+                Some(speed) => {
+                    let gusts = point.gusts.unwrap_or(0.0);
+                    if speed < 0.0 || gusts < 0.0 {
+                        return Err("[[route]] wind_speed and gusts must not be negative".to_string());
+                    }
+                    Some(Wind {
+                        speed,
+                        direction: point.wind_direction.ok_or("[[route]] needs a number wind_direction")?,
+                        gusts,
+                    })
+                }
+                None if point.wind_direction.is_some() || point.gusts.is_some() => {
+                    return Err("[[route]] wind_direction and gusts need a wind_speed".to_string());
+                }
+                None => None,
+            };
+            route.push(RoutePoint {
+                distance: point.distance,
+                elevation: point.elevation,
+                heading: point.heading,
+                wind,
+            });
+        }
+        route.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        if let Some(pair) = route.windows(2).find(|pair| pair[0].distance == pair[1].distance) {
+            return Err(format!("[[route]] distance {} is given twice", pair[0].distance));
+        }
+        faults.sort_by(|a, b| a.at.total_cmp(&b.at));
+        weather.sort_by(|a, b| a.at.total_cmp(&b.at));
+        modes.sort_by(|a, b| a.at.total_cmp(&b.at));
+
+        Ok(Scenario {
+            name: file.name,
+            duration,
+            seed: file.seed,
+            initial: file.initial,
+            faults,
+            weather,
+            modes,
+            route,
+        })
+    }
+}
+
+impl Scenario {
+    pub fn parse(text: &str) -> Result<Scenario, String> {
+        toml::from_str(text).map_err(|e| e.to_string().trim_end().to_string())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Scenario> {
+        let text = fs::read_to_string(path)?;
+        let mut scenario =
+            Scenario::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+        if scenario.name.is_empty() {
+            scenario.name = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        }
+        Ok(scenario)
+    }
+
+    pub fn initial(&self, key: &str) -> Option<&Value> {
+        self.initial.get(key)
+    }
+
+    // A numeric initial condition; any other value is ignored with a warning
+    pub fn initial_f64(&self, key: &str) -> Option<f64> {
+        let value = self.initial.get(key)?;
+        if value.as_f64().is_none() {
+            sim_log::warn("scenario", &format!("Ignoring initial {}: expected a number", key));
+        }
+        value.as_f64()
+    }
+
+    // A text initial condition; any other value is ignored with a warning
+    pub fn initial_str(&self, key: &str) -> Option<&str> {
+        let value = self.initial.get(key)?;
+        if value.as_str().is_none() {
+            sim_log::warn("scenario", &format!("Ignoring initial {}: expected a string", key));
+        }
+        value.as_str()
+    }
+
+    // Number of `dt` steps the scenario runs for
+    pub fn steps(&self, dt: f64) -> Option<u64> {
+        self.duration.map(|duration| ((duration / dt).round() as u64).max(1))
+    }
+
+    // Fault commands keyed by the step they run in, counting from 1
+    pub fn command_schedule(&self, dt: f64) -> Vec<(u64, String)> {
+        self.faults
+            .iter()
+            .map(|fault| (((fault.at / dt).round() as u64).max(1), fault.command.clone()))
+            .collect()
+    }
+
+    // What a simulation that knows `initial_keys`, and handles faults,
+    // weather, vehicle modes and the route as given, would ignore in this
+    // scenario
+    pub fn unsupported(&self, initial_keys: &[&str], faults: bool, weather: bool, modes: bool, route: bool) -> Vec<String> {
+        let mut ignored: Vec<String> = self
+            .initial
+            .keys()
+            .filter(|key| !initial_keys.contains(&key.as_str()))
+            .map(|key| format!("initial {}", key))
+            .collect();
+        if !faults && !self.faults.is_empty() {
+            ignored.push("the fault schedule".to_string());
+        }
+        if !weather && !self.weather.is_empty() {
+            ignored.push("the weather timeline".to_string());
+        }
+        if !modes && !self.modes.is_empty() {
+            ignored.push("the vehicle mode changes".to_string());
+        }
+        if !route && !self.route.is_empty() {
+            ignored.push("the route profile".to_string());
+        }
+        ignored
+    }
+
+    // Logs the scenario and warns about everything the simulation ignores
+    pub fn announce(&self, initial_keys: &[&str], faults: bool, weather: bool, modes: bool, route: bool) {
+        sim_log::info(
+            "scenario",
+            &format!(
+                "Scenario {}: {} faults, {} weather changes, {} mode changes, {} route points",
+                self.name,
+                self.faults.len(),
+                self.weather.len(),
+                self.modes.len(),
+                self.route.len()
+            ),
+        );
+        for ignored in self.unsupported(initial_keys, faults, weather, modes, route) {
+            sim_log::warn("scenario", &format!("This simulation ignores {}", ignored));
+        }
+    }
+}
+
+pub fn is_scenario_file(path: &Path) -> bool {
+    path.extension().and_then(|extension| extension.to_str()) == Some(EXTENSION)
+}
+
+// Uses `--scenario <path>` from the command line, then SIM_SCENARIO
+pub fn path_from_args() -> Option<PathBuf> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--scenario")
+        .and_then(|i| args.get(i + 1).map(PathBuf::from))
+        .or_else(|| env::var(SCENARIO_ENV_VAR).ok().map(PathBuf::from))
+}
+
+// Loads the scenario named on the command line or in SIM_SCENARIO, if any
+pub fn from_args() -> io::Result<Option<Scenario>> {
+    path_from_args().map(|path| Scenario::load(&path)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_section_of_a_scenario() {
+        let scenario = Scenario::parse(
+            r#"
+# Comments and blank lines are skipped
+name = "Black \"ice\"\tnight" # after a value too
+duration = "30min"
+seed = 7
+
+[initial]
+ambient_temperature = 1.5
+condition = 'icy'
+pressures = [32, 31.5, "low", true]
+
+[[fault]]
+at = "5min"
+command = "leak 0 2"
+
+[[fault]]
+at = 60
+command = "sensor 1 off"
+
+[[weather]]
+at = "10min"
+condition = "ICY"
+temperature = -3
+
+[[mode]]
+at = "20min"
+mode = "parked"
+
+[[route]]
+distance = 12
+elevation = 850
+heading = 270
+wind_speed = 8
+wind_direction = 240
+
+[[route]]
+distance = 0
+elevation = 1_000
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.name, "Black \"ice\"\tnight");
+        assert_eq!(scenario.duration, Some(1800.0));
+        assert_eq!(scenario.seed, Some(7));
+        assert_eq!(scenario.initial_f64("ambient_temperature"), Some(1.5));
+        assert_eq!(scenario.initial_str("condition"), Some("icy"));
+        assert_eq!(
+            scenario.initial("pressures"),
+            Some(&Value::Array(vec![
+                Value::Number(32.0),
+                Value::Number(31.5),
+                Value::String("low".to_string()),
+                Value::Bool(true)
+            ]))
+        );
+        // Sorted by time and distance
+        assert_eq!(scenario.command_schedule(0.5), vec![(120, "sensor 1 off".to_string()), (600, "leak 0 2".to_string())]);
+        assert_eq!(scenario.weather[0].condition.as_deref(), Some("icy"));
+        assert_eq!(scenario.weather[0].temperature, Some(-3.0));
+        assert_eq!(scenario.modes, vec![ModeChange { at: 1200.0, mode: VehicleMode::Parked }]);
+        assert_eq!(scenario.route[0].elevation, 1000.0);
+        assert_eq!(scenario.route[1].wind, Some(Wind { speed: 8.0, direction: 240.0, gusts: 0.0 }));
+        assert_eq!(scenario.steps(0.5), Some(3600));
+        assert_eq!(
+            scenario.unsupported(&["ambient_temperature"], true, false, true, true),
+            vec!["initial condition", "initial pressures", "the weather timeline"]
+        );
+    }
+
+    #[test]
+    fn durations_take_a_unit() {
+        let duration = |text: &str| parse_duration(&Value::String(text.to_string()));
+        assert_eq!(duration("90s"), Ok(90.0));
+        assert_eq!(duration("1.5h"), Ok(5400.0));
+        assert_eq!(duration("2d"), Ok(172800.0));
+        assert_eq!(parse_duration(&Value::Number(12.0)), Ok(12.0));
+        assert!(duration("5 weeks").is_err());
+        assert!(duration("min").is_err());
+        assert!(parse_duration(&Value::Number(-1.0)).is_err());
+        assert!(parse_duration(&Value::Bool(true)).is_err());
+    }
+
+    #[test]
+    fn malformed_scenarios_are_rejected() {
+        let rejected = [
+            // Not TOML
+            "name = \"unterminated",
+            "name = \"bad \\q escape\"",
+            "pressures = [1, 2",
+            "[initial\nx = 1",
+            "seed = 7 8",
+            "name = \"a\"\nname = \"b\"",
+            "[initial]\n[initial]",
+            // Not a scenario
+            "speed = 5",
+            "[vehicle]\nmass = 1500",
+            "[[stop]]\nat = 5",
+            "name = 5",
+            "seed = -1",
+            "seed = 1.5",
+            "duration = \"5 weeks\"",
+            "[[fault]]\ncommand = \"leak 0 2\"",
+            "[[fault]]\nat = 5",
+            "[[weather]]\nat = 5\ntemperature = \"cold\"",
+            "[[mode]]\nat = 5\nmode = \"flying\"",
+            "[[route]]\ndistance = -1\nelevation = 0",
+            "[[route]]\ndistance = 1\nelevation = nan",
+            "[[route]]\ndistance = 1\nelevation = 0\ngusts = 3",
+            "[[route]]\ndistance = 1\nelevation = 0\nwind_speed = 3",
+            "[[route]]\ndistance = 1\nelevation = 0\n[[route]]\ndistance = 1\nelevation = 5",
+        ];
+        for text in rejected {
+            assert!(Scenario::parse(text).is_err(), "accepted {:?}", text);
+        }
+    }
+
+    #[test]
+    fn the_scenarios_of_the_simulations_load() {
+        let projects = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        let mut loaded = 0;
+        for project in fs::read_dir(projects).unwrap().flatten() {
+            let Ok(files) = fs::read_dir(project.path().join("scenarios")) else {
+                continue;
+            };
+            for path in files.flatten().map(|file| file.path()).filter(|path| is_scenario_file(path)) {
+                let scenario = Scenario::load(&path).unwrap_or_else(|e| panic!("{}", e));
+                assert!(!scenario.name.is_empty());
+                loaded += 1;
+            }
+        }
+        assert!(loaded > 0);
+    }
+}
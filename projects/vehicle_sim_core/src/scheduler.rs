@@ -0,0 +1,328 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Instant;
+
+use crate::sim_log;
+use crate::simulation::{RunSummary, Simulation};
+
+// Tick times closer than this are the same instant
+const TIME_EPSILON: f64 = 1e-9;
+
+type Task = Pin<Box<dyn Future<Output = ()> + Send>>;
+// Publishes a subsystem's new state once every subsystem due at the same
+// instant has stepped
+type Publish = Box<dyn FnOnce() + Send>;
+
+struct Tick {
+    dt: f64,
+    done: oneshot::Sender<(Publish, bool)>,
+}
+
+struct Subsystem {
+    name: String,
+    dt: f64,
+    summary: RunSummary,
+    finished: bool,
+    ticks: mpsc::Sender<Tick>,
+}
+
+impl Subsystem {
+    fn next_tick(&self) -> f64 {
+        (self.summary.steps + 1) as f64 * self.dt
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleSummary {
+    pub simulated_seconds: f64,
+    // Per subsystem, in the order they were added
+    pub subsystems: Vec<(String, RunSummary)>,
+}
+
+// Runs several simulations side by side on a tokio runtime, each as a task
+// stepping at a rate of its own. The scheduler keeps one simulated clock and
+// ticks every subsystem due at its next instant together, so subsystems
+// with the same rate step concurrently. Pacing to the wall clock awaits a
+// timer instead of blocking a thread.
+//
+// Subsystems talk through channels: `add` returns a watch receiver with the
+// subsystem's latest state, which another subsystem can read from its
+// `step`. New states are published once every subsystem due at the same
+// instant has stepped, so a reader always sees the state from before that
+// instant, whichever task ran first.
+pub struct Scheduler {
+    // Simulated seconds per wall-clock second; 0 runs as fast as possible
+    pub realtime_factor: f64,
+    // Simulated seconds to run for; runs until every subsystem finished or
+    // a stop request otherwise
+    pub duration: Option<f64>,
+    pub stop: Option<Arc<AtomicBool>>,
+    subsystems: Vec<Subsystem>,
+    tasks: Vec<Task>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            realtime_factor: 0.0,
+            duration: None,
+            stop: None,
+            subsystems: Vec::new(),
+            tasks: Vec::new(),
+        }
+    }
+
+    pub fn with_realtime_factor(mut self, realtime_factor: f64) -> Self {
+        self.realtime_factor = realtime_factor;
+        self
+    }
+
+    pub fn with_duration(mut self, seconds: f64) -> Self {
+        self.duration = Some(seconds);
+        self
+    }
+
+    // Ends the run after the current instant once the flag is raised
+    pub fn with_stop_flag(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    // Steps `simulation` every `dt` simulated seconds
+    pub fn add<S>(&mut self, name: &str, dt: f64, mut simulation: S) -> watch::Receiver<S::State>
+    where
+        S: Simulation + Send + 'static,
+        S::State: Send + Sync + 'static,
+    {
+        assert!(dt > 0.0, "a subsystem needs a positive time step");
+        let (ticks, mut received) = mpsc::channel::<Tick>(1);
+        let (states, state) = watch::channel(simulation.state());
+
+        self.tasks.push(Box::pin(async move {
+            while let Some(tick) = received.recv().await {
+                simulation.step(tick.dt);
+                let (next, states) = (simulation.state(), states.clone());
+                let publish: Publish = Box::new(move || {
+                    states.send_replace(next);
+                });
+                if tick.done.send((publish, simulation.is_finished())).is_err() {
+                    break;
+                }
+            }
+        }));
+        self.subsystems.push(Subsystem {
+            name: name.to_string(),
+            dt,
+            summary: RunSummary::default(),
+            finished: false,
+            ticks,
+        });
+        state
+    }
+
+    fn stop_requested(&self) -> bool {
+        self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+
+    // Drives every subsystem from within a tokio runtime
+    pub async fn run(mut self) -> ScheduleSummary {
+        let tasks: Vec<_> = self.tasks.drain(..).map(tokio::spawn).collect();
+        let started = Instant::now();
+        let mut now = 0.0;
+
+        while !self.stop_requested() {
+            let Some(next) = self
+                .subsystems
+                .iter()
+                .filter(|subsystem| !subsystem.finished)
+                .map(Subsystem::next_tick)
+                .min_by(f64::total_cmp)
+            else {
+                break;
+            };
+            if self.duration.is_some_and(|duration| next > duration + TIME_EPSILON) {
+                break;
+            }
+            if self.realtime_factor > 0.0 {
+                self.sleep_until(started + Duration::from_secs_f64(next / self.realtime_factor)).await;
+                if self.stop_requested() {
+                    break;
+                }
+            }
+            now = next;
+            self.tick(now).await;
+        }
+
+        // Closing the tick channels ends the tasks
+        let subsystems: Vec<_> = self.subsystems.drain(..).map(|s| (s.name, s.summary)).collect();
+        for task in tasks {
+            if let Err(e) = task.await {
+                sim_log::warn("scheduler", &format!("A subsystem task failed: {}", e));
+            }
+        }
+        ScheduleSummary {
+            simulated_seconds: now,
+            subsystems,
+        }
+    }
+
+    // Builds a multi-threaded runtime and runs on it until done
+    pub fn run_blocking(self) -> io::Result<ScheduleSummary> {
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_time().build()?;
+        Ok(runtime.block_on(self.run()))
+    }
+
+    // Steps every subsystem due at `now`, then publishes their states
+    async fn tick(&mut self, now: f64) {
+        let mut pending = Vec::new();
+        for (index, subsystem) in self.subsystems.iter().enumerate() {
+            if subsystem.finished || subsystem.next_tick() > now + TIME_EPSILON {
+                continue;
+            }
+            let (done, reply) = oneshot::channel();
+            let tick = Tick { dt: subsystem.dt, done };
+            if subsystem.ticks.send(tick).await.is_ok() {
+                pending.push((index, reply));
+            }
+        }
+
+        let mut published = Vec::new();
+        for (index, reply) in pending {
+            let subsystem = &mut self.subsystems[index];
+            match reply.await {
+                Ok((publish, finished)) => {
+                    subsystem.summary.steps += 1;
+                    subsystem.summary.simulated_seconds = now;
+                    subsystem.finished = finished;
+                    published.push(publish);
+                }
+                // The task panicked; the others carry on without it
+                Err(_) => {
+                    sim_log::warn("scheduler", &format!("Subsystem {} stopped", subsystem.name));
+                    subsystem.finished = true;
+                }
+            }
+        }
+        for publish in published {
+            publish();
+        }
+    }
+
+    // Waits in short slices so a stop request does not wait for a long delay
+    async fn sleep_until(&self, deadline: Instant) {
+        while !self.stop_requested() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            tokio::time::sleep(remaining.min(Duration::from_millis(100))).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // Counts its steps, or follows another subsystem's state
+    struct Counter {
+        steps: u64,
+        finish_at: Option<u64>,
+        follows: Option<watch::Receiver<u64>>,
+        seen: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Counter {
+        fn new() -> Self {
+            Counter {
+                steps: 0,
+                finish_at: None,
+                follows: None,
+                seen: Arc::default(),
+            }
+        }
+    }
+
+    impl Simulation for Counter {
+        type State = u64;
+
+        fn step(&mut self, _dt: f64) {
+            self.steps += 1;
+            if let Some(follows) = &self.follows {
+                self.seen.lock().unwrap().push(*follows.borrow());
+            }
+        }
+
+        fn state(&self) -> u64 {
+            self.steps
+        }
+
+        fn report(&self) -> String {
+            format!("step {}", self.steps)
+        }
+
+        fn is_finished(&self) -> bool {
+            self.finish_at.is_some_and(|finish_at| self.steps >= finish_at)
+        }
+    }
+
+    #[test]
+    fn subsystems_step_at_their_own_rates() {
+        let mut scheduler = Scheduler::new().with_duration(2.0);
+        let slow = scheduler.add("climate", 1.0, Counter::new());
+        let fast = scheduler.add("dynamics", 0.1, Counter::new());
+        let summary = scheduler.run_blocking().unwrap();
+
+        assert_eq!(summary.simulated_seconds, 2.0);
+        let steps: Vec<_> = summary.subsystems.iter().map(|(name, run)| (name.as_str(), run.steps)).collect();
+        assert_eq!(steps, vec![("climate", 2), ("dynamics", 20)]);
+        assert_eq!((*slow.borrow(), *fast.borrow()), (2, 20));
+    }
+
+    #[test]
+    fn a_reader_sees_the_state_from_before_the_instant() {
+        let mut scheduler = Scheduler::new().with_duration(1.0);
+        let tpms = scheduler.add("tpms", 0.25, Counter::new());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let display = Counter {
+            follows: Some(tpms),
+            seen: seen.clone(),
+            ..Counter::new()
+        };
+        scheduler.add("display", 0.5, display);
+        scheduler.run_blocking().unwrap();
+
+        // Both step at 0.5 s and 1.0 s, the display seeing 1 and 3 steps
+        assert_eq!(*seen.lock().unwrap(), vec![1, 3]);
+    }
+
+    #[test]
+    fn the_run_ends_once_every_subsystem_finished_or_on_a_stop_request() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add("short", 1.0, Counter { finish_at: Some(2), ..Counter::new() });
+        scheduler.add("long", 0.5, Counter { finish_at: Some(7), ..Counter::new() });
+        let summary = scheduler.run_blocking().unwrap();
+        assert_eq!(summary.simulated_seconds, 3.5);
+        assert_eq!(summary.subsystems[0].1.steps, 2);
+        assert_eq!(summary.subsystems[1].1.steps, 7);
+
+        let stop = Arc::new(AtomicBool::new(true));
+        let mut scheduler = Scheduler::new().with_realtime_factor(1.0).with_stop_flag(stop);
+        scheduler.add("paced", 60.0, Counter::new());
+        assert_eq!(scheduler.run_blocking().unwrap().subsystems[0].1.steps, 0);
+    }
+}
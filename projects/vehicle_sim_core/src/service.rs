@@ -0,0 +1,55 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+// How long a service client waits for the simulation to pick up a request;
+// requests are answered between steps
+pub const SERVICE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// A diagnostic request from a service tool, e.g. `GET dtcs` or
+// `POST actuators/sensor_wakeup`, answered by the simulation between steps
+#[derive(Debug)]
+pub struct ServiceRequest {
+    pub method: String,
+    pub path: String,
+    reply: Sender<ServiceResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ServiceResponse {
+    pub fn ok(body: Value) -> Self {
+        ServiceResponse { status: 200, body }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        ServiceResponse {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+}
+
+impl ServiceRequest {
+    // Returns the request to hand to the simulation and the receiver its
+    // answer arrives on
+    pub fn new(method: &str, path: &str) -> (ServiceRequest, Receiver<ServiceResponse>) {
+        let (reply, response) = mpsc::channel();
+        let request = ServiceRequest {
+            method: method.to_string(),
+            path: path.trim_matches('/').to_string(),
+            reply,
+        };
+        (request, response)
+    }
+
+    pub fn respond(self, response: ServiceResponse) {
+        // The client may have timed out and gone away
+        let _ = self.reply.send(response);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// Latest value of every signal sent on a bus, for readers that want the
+// state of the vehicle rather than every frame. The signals sent during a
+// tick go into the back buffer; the end of the tick copies it into a
+// snapshot that all readers share, so a reader costs one reference count
+// instead of a copy of every frame. A snapshot a reader still holds is never
+// written to again: the next tick gets a fresh one, and the buffer of one
+// nobody holds any more is reused.
+
+// Names in the order their signals were first sent; shared by the snapshots
+// until a new signal shows up
+#[derive(Debug, Clone, Default)]
+struct Layout {
+    names: Vec<&'static str>,
+    index: HashMap<&'static str, usize>,
+}
+
+// The signals as they stood at the end of a tick
+#[derive(Debug, Clone, Default)]
+pub struct SignalSnapshot {
+    tick: u64,
+    layout: Arc<Layout>,
+    values: Vec<f64>,
+}
+
+impl SignalSnapshot {
+    // Ticks committed before this one; 0 before the first
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.layout.index.get(name).map(|&i| self.values[i])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, f64)> + '_ {
+        self.layout.names.iter().copied().zip(self.values.iter().copied())
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct Arena {
+    layout: Arc<Layout>,
+    back: Vec<f64>,
+    tick: u64,
+    // The snapshot before the current one, whose buffer the next commit
+    // reuses when no reader holds it any more
+    spare: Option<Arc<SignalSnapshot>>,
+}
+
+#[derive(Default)]
+pub struct SignalStore {
+    arena: Mutex<Arena>,
+    front: Mutex<Arc<SignalSnapshot>>,
+}
+
+impl SignalStore {
+    pub fn new() -> Self {
+        SignalStore::default()
+    }
+
+    // Takes effect with the next commit
+    pub fn write(&self, name: &'static str, value: f64) {
+        let mut arena = self.arena.lock().unwrap();
+        let index = match arena.layout.index.get(name) {
+            Some(&index) => index,
+            None => {
+                let layout = Arc::make_mut(&mut arena.layout);
+                layout.index.insert(name, layout.names.len());
+                layout.names.push(name);
+                arena.back.push(f64::NAN);
+                arena.back.len() - 1
+            }
+        };
+        arena.back[index] = value;
+    }
+
+    // Publishes what was written during the tick; call once at its end
+    pub fn commit(&self) {
+        let mut arena = self.arena.lock().unwrap();
+        arena.tick += 1;
+        let mut next = arena.spare.take().filter(|spare| Arc::strong_count(spare) == 1).unwrap_or_default();
+        let snapshot = Arc::get_mut(&mut next).expect("a snapshot no reader holds");
+        snapshot.tick = arena.tick;
+        if !Arc::ptr_eq(&snapshot.layout, &arena.layout) {
+            snapshot.layout = Arc::clone(&arena.layout);
+        }
+        snapshot.values.clone_from(&arena.back);
+
+        let previous = std::mem::replace(&mut *self.front.lock().unwrap(), next);
+        arena.spare = Some(previous);
+    }
+
+    // The signals as of the last commit; cheap enough to take every step
+    pub fn snapshot(&self) -> Arc<SignalSnapshot> {
+        Arc::clone(&self.front.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_reader_keeps_its_snapshot_across_commits() {
+        let store = SignalStore::new();
+        assert_eq!(store.snapshot().tick(), 0);
+        assert!(store.snapshot().is_empty());
+
+        store.write("speed", 10.0);
+        store.write("rpm", 900.0);
+        store.commit();
+        let held = store.snapshot();
+
+        for tick in 2..=4 {
+            store.write("speed", 10.0 * tick as f64);
+            store.commit();
+        }
+        assert_eq!((held.tick(), held.get("speed"), held.get("rpm")), (1, Some(10.0), Some(900.0)));
+        let latest = store.snapshot();
+        assert_eq!((latest.tick(), latest.get("speed"), latest.get("rpm")), (4, Some(40.0), Some(900.0)));
+    }
+
+    #[test]
+    fn writes_show_only_after_the_commit() {
+        let store = SignalStore::new();
+        store.write("speed", 10.0);
+        store.commit();
+        store.write("speed", 20.0);
+        store.write("speed", 30.0);
+        assert_eq!(store.snapshot().get("speed"), Some(10.0));
+        store.commit();
+        assert_eq!(store.snapshot().get("speed"), Some(30.0));
+    }
+
+    #[test]
+    fn the_spare_buffer_is_reused_once_no_reader_holds_it() {
+        let store = SignalStore::new();
+        store.write("speed", 1.0);
+        store.commit();
+        let first = Arc::as_ptr(&store.snapshot());
+        store.write("speed", 2.0);
+        store.commit();
+        // The first snapshot is the spare now, and nobody holds it
+        store.write("speed", 3.0);
+        store.commit();
+        let third = store.snapshot();
+        assert_eq!(Arc::as_ptr(&third), first);
+        assert_eq!((third.tick(), third.get("speed")), (3, Some(3.0)));
+
+        // A held spare is left alone and the commit takes a fresh snapshot
+        let second_spare = Arc::as_ptr(&store.arena.lock().unwrap().spare.clone().unwrap());
+        let held = store.snapshot();
+        store.commit();
+        store.commit();
+        assert_ne!(Arc::as_ptr(&store.snapshot()), Arc::as_ptr(&held));
+        assert_ne!(Arc::as_ptr(&store.snapshot()), second_spare);
+        assert_eq!((held.tick(), held.get("speed")), (3, Some(3.0)));
+    }
+
+    #[test]
+    fn the_layout_grows_when_a_signal_is_added_mid_run() {
+        let store = SignalStore::new();
+        store.write("speed", 10.0);
+        store.commit();
+        store.write("speed", 11.0);
+        store.commit();
+        let before = store.snapshot();
+
+        store.write("brake", 0.5);
+        store.commit();
+        let after = store.snapshot();
+        let signals: Vec<_> = after.iter().collect();
+        assert_eq!(signals, vec![("speed", 11.0), ("brake", 0.5)]);
+        assert!(!Arc::ptr_eq(&before.layout, &after.layout));
+        // The older snapshot keeps the layout it was taken with
+        assert_eq!((before.len(), before.get("brake")), (1, None));
+
+        // Ticks without a new signal share the layout
+        store.write("brake", 0.0);
+        store.commit();
+        assert!(Arc::ptr_eq(&after.layout, &store.snapshot().layout));
+    }
+}
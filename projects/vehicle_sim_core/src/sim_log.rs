@@ -0,0 +1,206 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::clock;
+use crate::config::LogLevel;
+use crate::events::Event;
+
+pub const LOG_LEVEL_ENV_VAR: &str = "SIM_LOG_LEVEL";
+pub const LOG_FILE_ENV_VAR: &str = "SIM_LOG_FILE";
+pub const LOG_JSON_ENV_VAR: &str = "SIM_LOG_JSON";
+
+// Where log records go besides the console. The console prints the plain
+// message: info and debug on stdout, warnings and errors on stderr unless
+// `console_stdout` is off (e.g. while stdout carries telemetry). Without
+// `console` nothing is printed (e.g. while a full-screen UI owns the
+// terminal).
+#[derive(Debug, Clone)]
+pub struct LogOptions {
+    pub level: LogLevel,
+    pub file: Option<PathBuf>,
+    pub json: Option<PathBuf>,
+    pub console: bool,
+    pub console_stdout: bool,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            level: LogLevel::Info,
+            file: None,
+            json: None,
+            console: true,
+            console_stdout: true,
+        }
+    }
+}
+
+impl LogOptions {
+    // SIM_LOG_LEVEL, SIM_LOG_FILE and SIM_LOG_JSON
+    pub fn from_env() -> Self {
+        let defaults = LogOptions::default();
+        LogOptions {
+            level: env::var(LOG_LEVEL_ENV_VAR)
+                .ok()
+                .and_then(|value| LogLevel::parse(&value))
+                .unwrap_or(defaults.level),
+            file: env::var(LOG_FILE_ENV_VAR).ok().map(PathBuf::from),
+            json: env::var(LOG_JSON_ENV_VAR).ok().map(PathBuf::from),
+            ..defaults
+        }
+    }
+
+    // `--log-level`, `--log-file` and `--log-json` from the command line,
+    // then the environment
+    pub fn from_args_and_env() -> Self {
+        let args: Vec<String> = env::args().collect();
+        let value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1));
+
+        let mut options = LogOptions::from_env();
+        if let Some(level) = value("--log-level").and_then(|value| LogLevel::parse(value)) {
+            options.level = level;
+        }
+        if let Some(path) = value("--log-file") {
+            options.file = Some(PathBuf::from(path));
+        }
+        if let Some(path) = value("--log-json") {
+            options.json = Some(PathBuf::from(path));
+        }
+        options
+    }
+}
+
+enum Sink {
+    Console { stdout: bool },
+    Text(BufWriter<File>),
+    JsonLines(BufWriter<File>),
+}
+
+struct Logger {
+    level: LogLevel,
+    sinks: Vec<Sink>,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: f64,
+    // Simulated seconds into the run
+    sim_time: f64,
+    level: &'static str,
+    source: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step: Option<u64>,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'a Event>,
+}
+
+static LOGGER: OnceLock<Mutex<Logger>> = OnceLock::new();
+
+fn logger() -> &'static Mutex<Logger> {
+    LOGGER.get_or_init(|| {
+        Mutex::new(Logger {
+            level: LogLevel::Info,
+            sinks: vec![Sink::Console { stdout: true }],
+        })
+    })
+}
+
+// Replaces the sinks of the shared logger; without a call to `init` records
+// only go to the console
+pub fn init(options: &LogOptions) -> io::Result<()> {
+    let mut sinks = Vec::new();
+    if options.console {
+        sinks.push(Sink::Console {
+            stdout: options.console_stdout,
+        });
+    }
+    if let Some(path) = &options.file {
+        sinks.push(Sink::Text(create(path)?));
+    }
+    if let Some(path) = &options.json {
+        sinks.push(Sink::JsonLines(create(path)?));
+    }
+
+    let mut logger = logger().lock().unwrap();
+    logger.level = options.level;
+    logger.sinks = sinks;
+    Ok(())
+}
+
+fn create(path: &Path) -> io::Result<BufWriter<File>> {
+    File::create(path).map(BufWriter::new)
+}
+
+// For callers that would otherwise build messages nobody reads
+pub fn enabled(level: LogLevel) -> bool {
+    level <= logger().lock().unwrap().level
+}
+
+pub fn log(level: LogLevel, source: &str, message: &str) {
+    write(level, source, None, message, None);
+}
+
+pub fn info(source: &str, message: &str) {
+    log(LogLevel::Info, source, message);
+}
+
+pub fn warn(source: &str, message: &str) {
+    log(LogLevel::Warn, source, message);
+}
+
+pub fn debug(source: &str, message: &str) {
+    log(LogLevel::Debug, source, message);
+}
+
+// Structured event, logged with its own level and source
+pub fn event(step: Option<u64>, event: &Event) {
+    write(event.level(), event.source(), step, &event.to_string(), Some(event));
+}
+
+fn write(level: LogLevel, source: &str, step: Option<u64>, message: &str, event: Option<&Event>) {
+    let mut logger = logger().lock().unwrap();
+    if level > logger.level {
+        return;
+    }
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64());
+    let sim_time = clock::now();
+    let record = Record {
+        timestamp,
+        sim_time,
+        level: level.as_str(),
+        source,
+        step,
+        message,
+        event,
+    };
+
+    for sink in &mut logger.sinks {
+        // A failing log file must not take the simulation down
+        let _ = match sink {
+            Sink::Console { stdout } => {
+                if *stdout && level >= LogLevel::Info {
+                    println!("{}", message);
+                } else {
+                    eprintln!("{}", message);
+                }
+                Ok(())
+            }
+            Sink::Text(file) => {
+                let step = step.map_or(String::new(), |step| format!(" step {}", step));
+                writeln!(file, "{:.3} {:<5} [{}] sim {:.1}s{} {}", timestamp, level.as_str(), source, sim_time, step, message).and_then(|_| file.flush())
+            }
+            Sink::JsonLines(file) => serde_json::to_string(&record)
+                .map_err(io::Error::other)
+                .and_then(|json| writeln!(file, "{}", json))
+                .and_then(|_| file.flush()),
+        };
+    }
+}
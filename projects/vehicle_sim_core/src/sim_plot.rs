@@ -0,0 +1,409 @@
+use std::env;
+use std::error::Error;
+use std::path::Path;
+
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::locale;
+use crate::sim_log;
+
+// Colors for series that keep their meaning across charts, e.g. one per
+// road condition
+pub use plotters::style::{RGBColor, BLACK, BLUE, CYAN, GREEN, MAGENTA, RED};
+
+pub const PLOT_THEME_ENV_VAR: &str = "SIM_PLOT_THEME";
+
+// Colors of the background, the text and grid, and the series drawn
+// without a color of their own
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "light" => Ok(Theme::Light),
+            "dark" => Ok(Theme::Dark),
+            other => Err(format!("unknown plot theme '{}', expected light or dark", other)),
+        }
+    }
+
+    // SIM_PLOT_THEME, light if unset or invalid
+    pub fn from_env() -> Self {
+        match env::var(PLOT_THEME_ENV_VAR) {
+            Ok(value) => Theme::parse(&value).unwrap_or_else(|e| {
+                sim_log::warn("plot", &e);
+                Theme::Light
+            }),
+            Err(_) => Theme::Light,
+        }
+    }
+
+    fn background(&self) -> RGBColor {
+        match self {
+            Theme::Light => WHITE,
+            Theme::Dark => RGBColor(24, 26, 31),
+        }
+    }
+
+    fn foreground(&self) -> RGBColor {
+        match self {
+            Theme::Light => BLACK,
+            Theme::Dark => RGBColor(220, 220, 220),
+        }
+    }
+
+    fn grid(&self) -> RGBColor {
+        match self {
+            Theme::Light => RGBColor(210, 210, 210),
+            Theme::Dark => RGBColor(60, 63, 72),
+        }
+    }
+
+    // Pure colors like BLUE are too dark to read on the dark background and
+    // are lightened there
+    fn adapt(&self, color: RGBColor) -> RGBColor {
+        match self {
+            Theme::Light => color,
+            Theme::Dark => {
+                let lighten = |channel: u8| channel + ((255 - channel) as f64 * 0.4) as u8;
+                RGBColor(lighten(color.0), lighten(color.1), lighten(color.2))
+            }
+        }
+    }
+
+    fn palette(&self) -> &'static [RGBColor] {
+        match self {
+            Theme::Light => &[RED, BLUE, GREEN, MAGENTA, CYAN, RGBColor(255, 140, 0)],
+            Theme::Dark => &[
+                RGBColor(255, 105, 97),
+                RGBColor(100, 160, 255),
+                RGBColor(110, 220, 110),
+                RGBColor(230, 120, 230),
+                RGBColor(90, 220, 220),
+                RGBColor(255, 180, 60),
+            ],
+        }
+    }
+}
+
+// Uses `--plot-theme <light|dark>` from the command line, then SIM_PLOT_THEME
+pub fn theme_from_args() -> Theme {
+    let args: Vec<String> = env::args().collect();
+    match args.iter().position(|arg| arg == "--plot-theme").and_then(|i| args.get(i + 1)) {
+        Some(value) => Theme::parse(value).unwrap_or_else(|e| {
+            sim_log::warn("plot", &e);
+            Theme::from_env()
+        }),
+        None => Theme::from_env(),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SeriesColor {
+    Palette,
+    Foreground,
+    Fixed(RGBColor),
+}
+
+// One labelled line of a panel
+#[derive(Debug, Clone)]
+pub struct PlotSeries {
+    label: String,
+    points: Vec<(f64, f64)>,
+    color: SeriesColor,
+    opacity: f64,
+}
+
+impl PlotSeries {
+    pub fn new(label: impl Into<String>, points: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        PlotSeries {
+            label: label.into(),
+            points: points.into_iter().collect(),
+            color: SeriesColor::Palette,
+            opacity: 1.0,
+        }
+    }
+
+    pub fn with_color(mut self, color: RGBColor) -> Self {
+        self.color = SeriesColor::Fixed(color);
+        self
+    }
+
+    // Drawn in the text color of the theme, for references like a setpoint
+    // the other series are measured against
+    pub fn foreground(mut self) -> Self {
+        self.color = SeriesColor::Foreground;
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: f64) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    // The `index`-th series of its panel takes the `index`-th palette color
+    fn style(&self, theme: Theme, index: usize) -> RGBAColor {
+        let palette = theme.palette();
+        let color = match self.color {
+            SeriesColor::Palette => palette[index % palette.len()],
+            SeriesColor::Foreground => theme.foreground(),
+            SeriesColor::Fixed(color) => theme.adapt(color),
+        };
+        color.mix(self.opacity)
+    }
+}
+
+// One chart of a figure. Axes not given a range fit the data of all series.
+#[derive(Debug, Clone)]
+pub struct Panel {
+    title: String,
+    x_label: String,
+    y_label: String,
+    series: Vec<PlotSeries>,
+    x_range: Option<(f64, f64)>,
+    y_range: Option<(f64, f64)>,
+    y_from_zero: bool,
+    y_symmetric: bool,
+    label_decimals: Option<(usize, usize)>,
+}
+
+impl Panel {
+    pub fn new(title: impl Into<String>) -> Self {
+        Panel {
+            title: title.into(),
+            x_label: String::new(),
+            y_label: String::new(),
+            series: Vec::new(),
+            x_range: None,
+            y_range: None,
+            y_from_zero: false,
+            y_symmetric: false,
+            label_decimals: None,
+        }
+    }
+
+    pub fn with_axes(mut self, x_label: impl Into<String>, y_label: impl Into<String>) -> Self {
+        self.x_label = x_label.into();
+        self.y_label = y_label.into();
+        self
+    }
+
+    pub fn with_series(mut self, series: PlotSeries) -> Self {
+        self.series.push(series);
+        self
+    }
+
+    pub fn with_x_range(mut self, min: f64, max: f64) -> Self {
+        self.x_range = Some((min, max));
+        self
+    }
+
+    pub fn with_y_range(mut self, min: f64, max: f64) -> Self {
+        self.y_range = Some((min, max));
+        self
+    }
+
+    // The fitted y axis starts at zero, for quantities that only grow
+    pub fn from_zero(mut self) -> Self {
+        self.y_from_zero = true;
+        self
+    }
+
+    // The fitted y axis is centered on zero, for values of either sign
+    pub fn symmetric(mut self) -> Self {
+        self.y_symmetric = true;
+        self
+    }
+
+    // Axis labels with this many decimals in the active locale
+    pub fn with_label_decimals(mut self, x: usize, y: usize) -> Self {
+        self.label_decimals = Some((x, y));
+        self
+    }
+
+    fn x_axis(&self) -> (f64, f64) {
+        self.x_range.unwrap_or_else(|| {
+            let (min, max) = extent(self.series.iter().flat_map(|s| s.points.iter().map(|&(x, _)| x)));
+            if min < max {
+                (min, max)
+            } else {
+                (min, min + 1.0)
+            }
+        })
+    }
+
+    fn y_axis(&self) -> (f64, f64) {
+        if let Some(range) = self.y_range {
+            return range;
+        }
+        let (mut min, mut max) = extent(self.series.iter().flat_map(|s| s.points.iter().map(|&(_, y)| y)));
+        if self.y_symmetric {
+            let largest = min.abs().max(max.abs());
+            (min, max) = (-largest, largest);
+        } else if self.y_from_zero {
+            (min, max) = (min.min(0.0), max.max(0.0));
+        }
+
+        // A margin above and below so lines do not run along the frame,
+        // except below a zero the axis was asked to start at
+        let margin = if max > min { (max - min) * 0.05 } else { 1.0 };
+        let bottom = if self.y_from_zero && min == 0.0 { 0.0 } else { min - margin };
+        (bottom, max + margin)
+    }
+}
+
+// Smallest and largest finite value, 0 to 1 without any
+fn extent(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values
+        .filter(|value| value.is_finite())
+        .fold((f64::MAX, f64::MIN), |(min, max), value| (min.min(value), max.max(value)));
+    if min > max {
+        (0.0, 1.0)
+    } else {
+        (min, max)
+    }
+}
+
+// Panels laid out in a grid and written as PNG, or SVG for a `.svg` path
+#[derive(Debug, Clone)]
+pub struct Figure {
+    panels: Vec<Panel>,
+    columns: Option<usize>,
+    size: Option<(u32, u32)>,
+    theme: Theme,
+}
+
+impl Default for Figure {
+    fn default() -> Self {
+        Figure::new()
+    }
+}
+
+impl Figure {
+    pub fn new() -> Self {
+        Figure {
+            panels: Vec::new(),
+            columns: None,
+            size: None,
+            theme: Theme::Light,
+        }
+    }
+
+    pub fn with_panel(mut self, panel: Panel) -> Self {
+        self.panels.push(panel);
+        self
+    }
+
+    // Panels per row, all in one row by default
+    pub fn with_columns(mut self, columns: usize) -> Self {
+        self.columns = Some(columns.max(1));
+        self
+    }
+
+    // 1280 pixels wide and 480 per row by default
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    fn grid(&self) -> (usize, usize) {
+        let columns = self.columns.unwrap_or(self.panels.len()).max(1);
+        (self.panels.len().div_ceil(columns).max(1), columns)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let (rows, _) = self.grid();
+        let size = self.size.unwrap_or((1280, 480 * rows as u32));
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("svg")) {
+            self.draw(SVGBackend::new(path, size).into_drawing_area())
+        } else {
+            self.draw(BitMapBackend::new(path, size).into_drawing_area())
+        }
+    }
+
+    // Draws into an RGB buffer of `width` x `height` pixels, for a window
+    // showing the figure while a run goes on
+    pub fn render(&self, width: u32, height: u32) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut buffer = vec![0; width as usize * height as usize * 3];
+        self.draw(BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area())?;
+        Ok(buffer)
+    }
+
+    fn draw<DB: DrawingBackend>(&self, root: DrawingArea<DB, Shift>) -> Result<(), Box<dyn Error>>
+    where
+        DB::ErrorType: 'static,
+    {
+        root.fill(&self.theme.background())?;
+        let areas = root.split_evenly(self.grid());
+        for (panel, area) in self.panels.iter().zip(&areas) {
+            self.draw_panel(panel, area)?;
+        }
+        root.present()?;
+        Ok(())
+    }
+
+    fn draw_panel<DB: DrawingBackend>(&self, panel: &Panel, area: &DrawingArea<DB, Shift>) -> Result<(), Box<dyn Error>>
+    where
+        DB::ErrorType: 'static,
+    {
+        let theme = self.theme;
+        let foreground = theme.foreground();
+        let (x_min, x_max) = panel.x_axis();
+        let (y_min, y_max) = panel.y_axis();
+
+        let mut chart = ChartBuilder::on(area)
+            .caption(&panel.title, ("sans-serif", 25).into_font().color(&foreground))
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(40)
+            .build_cartesian_2d(x_min..x_max, y_min..y_max)?;
+
+        let locale = locale::current();
+        let decimals = panel.label_decimals.unwrap_or_default();
+        let x_formatter = |x: &f64| locale.number(*x, decimals.0);
+        let y_formatter = |y: &f64| locale.number(*y, decimals.1);
+        let mut mesh = chart.configure_mesh();
+        mesh.x_desc(panel.x_label.as_str())
+            .y_desc(panel.y_label.as_str())
+            .axis_style(foreground)
+            .label_style(("sans-serif", 12).into_font().color(&foreground))
+            .bold_line_style(theme.grid())
+            .light_line_style(theme.grid().mix(0.4));
+        if panel.label_decimals.is_some() {
+            mesh.x_label_formatter(&x_formatter).y_label_formatter(&y_formatter);
+        }
+        mesh.draw()?;
+
+        // A series without a label stays out of the legend, e.g. the second
+        // outline of a shape that is already listed
+        for (i, series) in panel.series.iter().enumerate() {
+            let color = series.style(theme, i);
+            let drawn = chart.draw_series(LineSeries::new(series.points.iter().copied(), color))?;
+            if !series.label.is_empty() {
+                drawn
+                    .label(series.label.as_str())
+                    .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color));
+            }
+        }
+
+        if panel.series.iter().any(|series| !series.label.is_empty()) {
+            chart
+                .configure_series_labels()
+                .background_style(theme.background().mix(0.8))
+                .border_style(theme.grid())
+                .label_font(("sans-serif", 15).into_font().color(&foreground))
+                .draw()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,390 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::clock::SimClock;
+use crate::metrics::Metrics;
+use crate::sim_log;
+use crate::snapshot::{Snapshot, CHECKPOINT_INTERVAL};
+
+// Common interface every simulated component plugs into the runner with
+pub trait Simulation {
+    type State;
+
+    // Advances the simulation by `dt` seconds of simulated time
+    fn step(&mut self, dt: f64);
+
+    fn state(&self) -> Self::State;
+
+    // Human readable status printed by the runner after each step
+    fn report(&self) -> String;
+
+    // Lets a simulation end the run on its own (e.g. once stabilized)
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    // State written to checkpoints; `None` when the simulation cannot resume
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        None
+    }
+}
+
+// Holds a run between steps, e.g. from a key press in an interactive UI;
+// while paused, `step_once` lets single steps through
+#[derive(Debug, Default)]
+pub struct PauseControl {
+    paused: AtomicBool,
+    single_steps: AtomicU64,
+}
+
+impl PauseControl {
+    pub fn new() -> Self {
+        PauseControl::default()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+        if !paused {
+            self.single_steps.store(0, Ordering::SeqCst);
+        }
+    }
+
+    pub fn toggle(&self) {
+        self.set_paused(!self.is_paused());
+    }
+
+    // Pauses a running simulation, or lets a paused one take one more step
+    pub fn step_once(&self) {
+        if self.paused.swap(true, Ordering::SeqCst) {
+            self.single_steps.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn take_step(&self) -> bool {
+        self.single_steps
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |steps| steps.checked_sub(1))
+            .is_ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunSummary {
+    pub steps: u64,
+    pub simulated_seconds: f64,
+}
+
+// Steps a simulation with a constant time step, optionally bounded and
+// optionally paced to follow the wall clock at a real-time factor.
+pub struct FixedStepRunner {
+    pub dt: f64,
+    pub max_steps: Option<u64>,
+    // Simulated seconds per wall-clock second; 0 runs as fast as possible
+    pub realtime_factor: f64,
+    pub print_reports: bool,
+    pub metrics: Option<Arc<Metrics>>,
+    pub stop: Option<Arc<AtomicBool>>,
+    pub pause: Option<Arc<PauseControl>>,
+    pub checkpoint: Option<PathBuf>,
+    pub start: RunSummary,
+}
+
+impl FixedStepRunner {
+    pub fn new(dt: f64) -> Self {
+        FixedStepRunner {
+            dt,
+            max_steps: None,
+            realtime_factor: 0.0,
+            print_reports: true,
+            metrics: None,
+            stop: None,
+            pause: None,
+            checkpoint: None,
+            start: RunSummary::default(),
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    pub fn with_realtime_factor(mut self, realtime_factor: f64) -> Self {
+        self.realtime_factor = realtime_factor;
+        self
+    }
+
+    pub fn quiet(mut self) -> Self {
+        self.print_reports = false;
+        self
+    }
+
+    // Records step counts, tick rate and step/report durations
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    // Ends the run after the current step once the flag is raised, e.g. from
+    // a Ctrl-C handler
+    pub fn with_stop_flag(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    // Waits between steps while `pause` holds the run
+    pub fn with_pause(mut self, pause: Arc<PauseControl>) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+
+    // Saves the simulation state every CHECKPOINT_INTERVAL steps and when
+    // the run ends
+    pub fn with_checkpoint(mut self, path: PathBuf) -> Self {
+        self.checkpoint = Some(path);
+        self
+    }
+
+    // Continues counting from a resumed checkpoint; `max_steps` includes
+    // the steps already run
+    pub fn resume_from(mut self, start: RunSummary) -> Self {
+        self.start = start;
+        self
+    }
+
+    fn stop_requested(&self) -> bool {
+        self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+
+    pub fn run<S: Simulation>(&self, simulation: &mut S) -> RunSummary {
+        self.run_with(simulation, |_, _| {})
+    }
+
+    // Like `run`, but hands the state after every step to `observer`
+    pub fn run_with<S, F>(&self, simulation: &mut S, mut observer: F) -> RunSummary
+    where
+        S: Simulation,
+        F: FnMut(S::State, &RunSummary),
+    {
+        let mut summary = self.start;
+
+        let started = Instant::now();
+        let mut clock = SimClock::new(self.realtime_factor).starting_at(summary.simulated_seconds);
+
+        while self.max_steps.is_none_or(|max| summary.steps < max) && !self.stop_requested() {
+            if self.wait_while_paused() {
+                if self.stop_requested() {
+                    break;
+                }
+                // Real time continues from here rather than catching up on the pause
+                clock = clock.starting_at(clock.now());
+            }
+            let tick = Instant::now();
+            simulation.step(self.dt);
+            let latency = tick.elapsed();
+            summary.steps += 1;
+            clock.advance(self.dt);
+            summary.simulated_seconds = clock.now();
+
+            if self.print_reports {
+                let report = match &self.metrics {
+                    Some(metrics) => metrics.time("report", || simulation.report()),
+                    None => simulation.report(),
+                };
+                sim_log::info("report", &report);
+            }
+            if let Some(metrics) = &self.metrics {
+                record_progress(metrics, &summary, started, latency);
+            }
+            observer(simulation.state(), &summary);
+
+            if simulation.is_finished() {
+                break;
+            }
+            if summary.steps.is_multiple_of(CHECKPOINT_INTERVAL) {
+                self.save_checkpoint(simulation, &summary);
+            }
+
+            if let Some(deadline) = clock.deadline() {
+                self.sleep_until(deadline);
+            }
+        }
+
+        self.save_checkpoint(simulation, &summary);
+        summary
+    }
+
+    fn save_checkpoint<S: Simulation>(&self, simulation: &S, summary: &RunSummary) {
+        let Some(path) = &self.checkpoint else {
+            return;
+        };
+        let Some(state) = simulation.checkpoint() else {
+            sim_log::warn("checkpoint", "This simulation does not support checkpoints");
+            return;
+        };
+
+        let snapshot = Snapshot {
+            steps: summary.steps,
+            simulated_seconds: summary.simulated_seconds,
+            state,
+        };
+        if let Err(e) = snapshot.save(path) {
+            sim_log::warn("checkpoint", &format!("Failed to save checkpoint {}: {}", path.display(), e));
+        }
+    }
+
+    // Returns whether the run was held, once it may take its next step
+    fn wait_while_paused(&self) -> bool {
+        let Some(pause) = &self.pause else {
+            return false;
+        };
+        let mut waited = false;
+        while pause.is_paused() && !pause.take_step() && !self.stop_requested() {
+            waited = true;
+            thread::sleep(Duration::from_millis(20));
+        }
+        waited
+    }
+
+    // Sleeps in short slices so a stop request does not wait for a long delay
+    fn sleep_until(&self, deadline: Instant) {
+        while !self.stop_requested() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            thread::sleep(remaining.min(Duration::from_millis(100)));
+        }
+    }
+}
+
+fn record_progress(metrics: &Metrics, summary: &RunSummary, started: Instant, latency: Duration) {
+    metrics.record_duration("step", latency);
+    metrics.set_gauge("sim_last_tick_latency_seconds", "Duration of the latest step", &[], latency.as_secs_f64());
+    metrics.inc_counter("sim_steps_total", "Simulation steps executed", &[], 1.0);
+    metrics.set_gauge("sim_time_seconds", "Simulated time", &[], summary.simulated_seconds);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64());
+    metrics.set_gauge("sim_last_tick_timestamp_seconds", "Unix time of the latest step", &[], now);
+
+    let elapsed = started.elapsed().as_secs_f64();
+    if elapsed > 0.0 {
+        metrics.set_gauge("sim_tick_rate", "Simulation steps per wall-clock second", &[], summary.steps as f64 / elapsed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Counts its steps and finishes at `finish_at`
+    #[derive(Default)]
+    struct Counter {
+        steps: u64,
+        time: f64,
+        finish_at: Option<u64>,
+    }
+
+    impl Simulation for Counter {
+        type State = u64;
+
+        fn step(&mut self, dt: f64) {
+            self.steps += 1;
+            self.time += dt;
+        }
+
+        fn state(&self) -> u64 {
+            self.steps
+        }
+
+        fn report(&self) -> String {
+            format!("step {}", self.steps)
+        }
+
+        fn is_finished(&self) -> bool {
+            self.finish_at.is_some_and(|finish_at| self.steps >= finish_at)
+        }
+
+        fn checkpoint(&self) -> Option<serde_json::Value> {
+            Some(serde_json::json!({ "steps": self.steps }))
+        }
+    }
+
+    #[test]
+    fn runs_the_bounded_number_of_steps_and_hands_every_state_on() {
+        let mut counter = Counter::default();
+        let mut seen = Vec::new();
+        let summary = FixedStepRunner::new(0.5)
+            .with_max_steps(4)
+            .quiet()
+            .run_with(&mut counter, |state, _| seen.push(state));
+        assert_eq!(summary.steps, 4);
+        assert_eq!(summary.simulated_seconds, 2.0);
+        assert_eq!(counter.time, 2.0);
+        assert_eq!(seen, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn a_finished_simulation_or_a_stop_request_ends_the_run() {
+        let mut counter = Counter {
+            finish_at: Some(3),
+            ..Counter::default()
+        };
+        assert_eq!(FixedStepRunner::new(1.0).with_max_steps(10).quiet().run(&mut counter).steps, 3);
+
+        let stop = Arc::new(AtomicBool::new(true));
+        let mut counter = Counter::default();
+        let summary = FixedStepRunner::new(1.0).with_stop_flag(stop).quiet().run(&mut counter);
+        assert_eq!(summary.steps, 0);
+    }
+
+    #[test]
+    fn a_resumed_run_counts_on_from_its_checkpoint() {
+        let path = std::env::temp_dir().join(format!("sim_runner_checkpoint_{}.json", std::process::id()));
+        let mut counter = Counter::default();
+        let start = RunSummary {
+            steps: 10,
+            simulated_seconds: 10.0,
+        };
+        let summary = FixedStepRunner::new(1.0)
+            .with_max_steps(12)
+            .resume_from(start)
+            .with_checkpoint(path.clone())
+            .quiet()
+            .run(&mut counter);
+        assert_eq!((summary.steps, summary.simulated_seconds), (12, 12.0));
+        assert_eq!(counter.steps, 2);
+
+        let snapshot = Snapshot::<serde_json::Value>::load(&path).unwrap();
+        assert_eq!(snapshot.steps, 12);
+        assert_eq!(snapshot.state["steps"], 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_paused_run_lets_single_steps_through() {
+        let pause = PauseControl::new();
+        assert!(!pause.take_step());
+        // The first request only pauses
+        pause.step_once();
+        assert!(pause.is_paused());
+        assert!(!pause.take_step());
+        pause.step_once();
+        pause.step_once();
+        assert!(pause.take_step());
+        assert!(pause.take_step());
+        assert!(!pause.take_step());
+        // Resuming forgets steps that were not taken
+        pause.step_once();
+        pause.toggle();
+        assert!(!pause.is_paused());
+        assert!(!pause.take_step());
+    }
+}
@@ -0,0 +1,95 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::RunSummary;
+use crate::storage;
+
+// Steps between two checkpoints written while a run is in progress
+pub const CHECKPOINT_INTERVAL: u64 = 60;
+
+// Checkpoint of a run: how far the runner got plus the simulation's own
+// state, written as JSON with `--save` and read back with `--resume`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot<T> {
+    pub steps: u64,
+    pub simulated_seconds: f64,
+    pub state: T,
+}
+
+impl<T> Snapshot<T> {
+    // Where a resumed runner continues counting from
+    pub fn summary(&self) -> RunSummary {
+        RunSummary {
+            steps: self.steps,
+            simulated_seconds: self.simulated_seconds,
+        }
+    }
+}
+
+impl<T: Serialize> Snapshot<T> {
+    // A crash never leaves half a checkpoint
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        storage::write_atomic(path, json)
+    }
+}
+
+impl<T: DeserializeOwned> Snapshot<T> {
+    pub fn load(path: &Path) -> io::Result<Snapshot<T>> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+// Uses `--save <path>` from the command line
+pub fn save_path_from_args() -> Option<PathBuf> {
+    path_from_args("--save")
+}
+
+// Uses `--resume <path>` from the command line
+pub fn resume_path_from_args() -> Option<PathBuf> {
+    path_from_args("--resume")
+}
+
+fn path_from_args(flag: &str) -> Option<PathBuf> {
+    let args: Vec<String> = env::args().collect();
+    let prefix = format!("{}=", flag);
+
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            Some(PathBuf::from(value))
+        } else if arg == flag {
+            args.get(i + 1).map(PathBuf::from)
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_saved_snapshot_loads_back_with_its_progress() {
+        let path = env::temp_dir().join(format!("sim_snapshot_{}.json", std::process::id()));
+        let snapshot = Snapshot {
+            steps: 120,
+            simulated_seconds: 60.0,
+            state: vec![1.5, 2.5],
+        };
+        snapshot.save(&path).unwrap();
+        let loaded = Snapshot::<Vec<f64>>::load(&path).unwrap();
+        assert_eq!(loaded.state, snapshot.state);
+        assert_eq!((loaded.summary().steps, loaded.summary().simulated_seconds), (120, 60.0));
+
+        fs::write(&path, "{").unwrap();
+        assert_eq!(Snapshot::<Vec<f64>>::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+// Crash-safe writes for the stores that outlive a run (mileage, trip
+// history, fault memory). A file is written next to its target and renamed
+// over it, so a reader sees either the old or the new contents. Files that
+// belong together go through a write-ahead journal first: after a power cut
+// the next start finds the journal and either finishes the job (when it was
+// written completely) or throws it away, leaving all of the old files.
+
+thread_local! {
+    // Bytes the storage may still write before the simulated power cut
+    static POWER_BUDGET: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// Fault injection: the power fails once `bytes` more bytes have been
+// written; everything after that fails like a dead ECU would
+pub fn cut_power_after(bytes: u64) {
+    POWER_BUDGET.with(|budget| budget.set(Some(bytes)));
+}
+
+pub fn restore_power() {
+    POWER_BUDGET.with(|budget| budget.set(None));
+}
+
+fn power_lost() -> io::Error {
+    io::Error::other("power lost")
+}
+
+fn check_power() -> io::Result<()> {
+    match POWER_BUDGET.with(Cell::get) {
+        Some(0) => Err(power_lost()),
+        _ => Ok(()),
+    }
+}
+
+// Writes as much of `data` as the power allows, and flushes it to the disk
+fn write_durably(path: &Path, data: &[u8]) -> io::Result<()> {
+    check_power()?;
+    let mut file = File::create(path)?;
+    let allowed = POWER_BUDGET.with(|budget| {
+        let allowed = budget.get().map_or(data.len(), |left| data.len().min(left as usize));
+        if let Some(left) = budget.get() {
+            budget.set(Some(left - allowed as u64));
+        }
+        allowed
+    });
+    file.write_all(&data[..allowed])?;
+    file.sync_all()?;
+    if allowed < data.len() {
+        return Err(power_lost());
+    }
+    Ok(())
+}
+
+// The rename itself is only durable once the directory is flushed
+fn sync_directory(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(directory)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+// Replaces `path` with `contents` in one step: a crash leaves either the old
+// or the new file, never half of one
+pub fn write_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = path.as_ref();
+    let temporary = temporary_path(path);
+    write_durably(&temporary, contents.as_ref())?;
+    check_power()?;
+    fs::rename(&temporary, path)?;
+    sync_directory(path)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct JournalEntry {
+    path: PathBuf,
+    contents: String,
+}
+
+// Last line of a complete journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct JournalCommit {
+    entries: usize,
+    checksum: u64,
+}
+
+// FNV-1a over the journaled files, so a torn or garbled journal is never
+// replayed
+fn checksum(entries: &[JournalEntry]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for entry in entries {
+        for byte in entry.path.to_string_lossy().bytes().chain([0]).chain(entry.contents.bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+// Files saved together: after a power cut all of them have either their old
+// or their new contents
+pub struct Transaction {
+    journal: PathBuf,
+    entries: Vec<JournalEntry>,
+}
+
+impl Transaction {
+    pub fn new(journal: impl Into<PathBuf>) -> Self {
+        Transaction {
+            journal: journal.into(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn write(&mut self, path: impl Into<PathBuf>, contents: String) {
+        self.entries.push(JournalEntry {
+            path: path.into(),
+            contents,
+        });
+    }
+
+    // Journal first, then the files, then the journal is done with
+    pub fn commit(self) -> io::Result<()> {
+        let mut journal = String::new();
+        for entry in &self.entries {
+            journal.push_str(&serde_json::to_string(entry)?);
+            journal.push('\n');
+        }
+        let commit = JournalCommit {
+            entries: self.entries.len(),
+            checksum: checksum(&self.entries),
+        };
+        journal.push_str(&serde_json::to_string(&commit)?);
+        journal.push('\n');
+        write_durably(&self.journal, journal.as_bytes())?;
+        sync_directory(&self.journal)?;
+
+        for entry in &self.entries {
+            write_atomic(&entry.path, &entry.contents)?;
+        }
+        check_power()?;
+        fs::remove_file(&self.journal)
+    }
+}
+
+// What the start-up found of an interrupted save
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Recovery {
+    Clean,
+    // The journal was complete; its files were written again
+    Replayed(usize),
+    // The power failed while the journal was written, the old files stand
+    Discarded,
+}
+
+// Finishes or rolls back a save interrupted by a power cut; call before
+// loading any file the journal covers
+pub fn recover(journal: impl AsRef<Path>) -> io::Result<Recovery> {
+    let journal = journal.as_ref();
+    let text = match fs::read_to_string(journal) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Recovery::Clean),
+        // Cut off in the middle of a character
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let mut lines: Vec<&str> = text.lines().collect();
+    let commit = text
+        .ends_with('\n')
+        .then(|| lines.pop())
+        .flatten()
+        .and_then(|line| serde_json::from_str::<JournalCommit>(line).ok());
+    let entries: Option<Vec<JournalEntry>> = lines.iter().map(|line| serde_json::from_str(line).ok()).collect();
+    let recovery = match (commit, entries) {
+        (Some(commit), Some(entries)) if commit.entries == entries.len() && commit.checksum == checksum(&entries) => {
+            for entry in &entries {
+                write_atomic(&entry.path, &entry.contents)?;
+            }
+            Recovery::Replayed(entries.len())
+        }
+        _ => Recovery::Discarded,
+    };
+    fs::remove_file(journal)?;
+    Ok(recovery)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("sim_storage_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn save(directory: &Path, mileage: &str, trips: &str) -> io::Result<()> {
+        let mut transaction = Transaction::new(directory.join("journal"));
+        transaction.write(directory.join("mileage"), mileage.to_string());
+        transaction.write(directory.join("trips"), trips.to_string());
+        transaction.commit()
+    }
+
+    fn read(directory: &Path) -> (String, String) {
+        (
+            fs::read_to_string(directory.join("mileage")).unwrap(),
+            fs::read_to_string(directory.join("trips")).unwrap(),
+        )
+    }
+
+    #[test]
+    fn a_power_cut_during_an_atomic_write_keeps_the_old_file() {
+        let directory = directory("atomic");
+        let path = directory.join("file");
+        write_atomic(&path, "old").unwrap();
+        cut_power_after(2);
+        assert!(write_atomic(&path, "new contents").is_err());
+        restore_power();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn a_torn_journal_is_discarded() {
+        let directory = directory("torn");
+        save(&directory, "1", "a").unwrap();
+        assert!(!directory.join("journal").exists());
+
+        cut_power_after(20);
+        assert!(save(&directory, "2", "b").is_err());
+        restore_power();
+        assert_eq!(recover(directory.join("journal")).unwrap(), Recovery::Discarded);
+        assert_eq!(read(&directory), ("1".to_string(), "a".to_string()));
+        assert_eq!(recover(directory.join("journal")).unwrap(), Recovery::Clean);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn a_complete_journal_is_replayed() {
+        let directory = directory("replay");
+        save(&directory, "1", "a").unwrap();
+
+        // Power lost after the journal was written, before any file was renamed
+        let journal_length = {
+            let entries = [
+                JournalEntry { path: directory.join("mileage"), contents: "2".to_string() },
+                JournalEntry { path: directory.join("trips"), contents: "b".to_string() },
+            ];
+            let commit = JournalCommit { entries: 2, checksum: checksum(&entries) };
+            entries.iter().map(|entry| serde_json::to_string(entry).unwrap().len() + 1).sum::<usize>()
+                + serde_json::to_string(&commit).unwrap().len()
+                + 1
+        };
+        cut_power_after(journal_length as u64 + 1);
+        assert!(save(&directory, "2", "b").is_err());
+        restore_power();
+        assert_eq!(read(&directory), ("1".to_string(), "a".to_string()));
+
+        assert_eq!(recover(directory.join("journal")).unwrap(), Recovery::Replayed(2));
+        assert_eq!(read(&directory), ("2".to_string(), "b".to_string()));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
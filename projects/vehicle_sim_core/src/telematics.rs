@@ -0,0 +1,522 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::locale;
+use crate::scenario::{self, Value};
+use crate::sim_log;
+
+// Named network scenarios for `--telematics`
+pub const NETWORK_SCENARIOS: &[&str] = &["always-on", "tunnel", "rural", "dead-zone"];
+
+// Records the modem sends per second once the network is back
+pub const DEFAULT_UPLINK_RATE: f64 = 5.0;
+pub const DEFAULT_CAPACITY: usize = 1000;
+// Above this share of the buffer only every THINNING-th record is kept, so
+// a long outage loses resolution before it loses whole stretches
+const HIGH_WATERMARK: f64 = 0.8;
+const THINNING: u64 = 2;
+// With compression every this many uploads all signals go out in full, so
+// the backend can pick the stream up again after a lost record
+const KEYFRAME_INTERVAL: u64 = 60;
+
+// The network is down from `start` for `duration`, in simulated seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outage {
+    pub start: f64,
+    pub duration: f64,
+}
+
+// When the (simulated) cellular network is unavailable. Periodic outages
+// repeat every `period` seconds for as long as the run goes on.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NetworkSchedule {
+    pub outages: Vec<Outage>,
+    pub period: Option<f64>,
+}
+
+impl NetworkSchedule {
+    // A named scenario, or outages as START+DURATION pairs separated by
+    // commas, e.g. "10min+2min,1h+15min"
+    pub fn parse(value: &str) -> Result<NetworkSchedule, String> {
+        let periodic = |start: f64, duration: f64, period: f64| NetworkSchedule {
+            outages: vec![Outage { start, duration }],
+            period: Some(period),
+        };
+        match value.trim() {
+            "always-on" => return Ok(NetworkSchedule::default()),
+            // A minute in a tunnel every ten minutes
+            "tunnel" => return Ok(periodic(300.0, 60.0, 600.0)),
+            // Half an hour without coverage every two hours
+            "rural" => return Ok(periodic(3600.0, 1800.0, 7200.0)),
+            // Parked in an underground garage for most of the day
+            "dead-zone" => return Ok(periodic(3600.0, 18.0 * 3600.0, 86400.0)),
+            _ => {}
+        }
+        let duration = |text: &str| scenario::parse_duration(&Value::String(text.to_string()));
+        let mut outages = Vec::new();
+        for pair in value.split(',') {
+            let (start, length) = pair.split_once('+').ok_or_else(|| {
+                format!(
+                    "expected {} or START+DURATION pairs, got '{}'",
+                    NETWORK_SCENARIOS.join(", "),
+                    pair.trim()
+                )
+            })?;
+            outages.push(Outage {
+                start: duration(start)?,
+                duration: duration(length)?,
+            });
+        }
+        outages.sort_by(|a, b| a.start.total_cmp(&b.start));
+        Ok(NetworkSchedule { outages, period: None })
+    }
+
+    pub fn is_online(&self, time: f64) -> bool {
+        let time = match self.period {
+            Some(period) => time.rem_euclid(period),
+            None => time,
+        };
+        !self
+            .outages
+            .iter()
+            .any(|outage| time >= outage.start && time < outage.start + outage.duration)
+    }
+}
+
+// One sample of signals, numbered so the backend can tell gaps from delays.
+// Compressed records carry only the signals that moved, as differences to
+// the values last sent.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TelemetryRecord {
+    pub sequence: u64,
+    pub time: f64, // s of simulated time
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub delta: bool,
+    pub values: Vec<(String, f64)>,
+}
+
+impl TelemetryRecord {
+    // Size on the wire, as JSON
+    pub fn encoded_size(&self) -> usize {
+        serde_json::to_vec(self).map_or(0, |encoded| encoded.len())
+    }
+}
+
+// Deadband compression of the uplink: a signal is only sent once it has
+// moved further than its deadband from the value last sent, and then as the
+// difference to it
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Compression {
+    pub default_deadband: f64,
+    pub deadbands: BTreeMap<String, f64>,
+    // What the backend holds, and uploads since the last keyframe
+    sent: BTreeMap<String, f64>,
+    since_keyframe: u64,
+}
+
+impl Compression {
+    // A deadband for every signal and/or SIGNAL=DEADBAND pairs, separated by
+    // commas, e.g. "0.5,speed=2,odometer=0.1"
+    pub fn parse(value: &str) -> Result<Compression, String> {
+        let mut compression = Compression::default();
+        for entry in value.split(',') {
+            let deadband = |text: &str| match text.trim().parse::<f64>() {
+                Ok(deadband) if deadband >= 0.0 => Ok(deadband),
+                _ => Err(format!("expected a deadband >= 0, got '{}'", text.trim())),
+            };
+            match entry.split_once('=') {
+                Some((signal, value)) => {
+                    compression.deadbands.insert(signal.trim().to_string(), deadband(value)?);
+                }
+                None => compression.default_deadband = deadband(entry)?,
+            }
+        }
+        Ok(compression)
+    }
+
+    pub fn deadband(&self, signal: &str) -> f64 {
+        self.deadbands.get(signal).copied().unwrap_or(self.default_deadband)
+    }
+
+    // The record as it goes on the wire, `None` if no signal left its
+    // deadband and the backend can keep the values it has
+    pub fn encode(&mut self, record: &TelemetryRecord) -> Option<TelemetryRecord> {
+        let new_signal = record.values.iter().any(|(name, _)| !self.sent.contains_key(name));
+        if self.since_keyframe == 0 || new_signal {
+            self.since_keyframe = 1;
+            for (name, value) in &record.values {
+                self.sent.insert(name.clone(), *value);
+            }
+            return Some(record.clone());
+        }
+        self.since_keyframe = (self.since_keyframe + 1) % KEYFRAME_INTERVAL;
+
+        let mut deltas = Vec::new();
+        for (name, value) in &record.values {
+            let deadband = self.deadband(name);
+            let sent = self.sent.get_mut(name).expect("keyframe holds every signal");
+            let delta = value - *sent;
+            if delta.abs() > deadband {
+                *sent += delta;
+                deltas.push((name.clone(), delta));
+            }
+        }
+        (!deltas.is_empty()).then_some(TelemetryRecord {
+            sequence: record.sequence,
+            time: record.time,
+            delta: true,
+            values: deltas,
+        })
+    }
+}
+
+// What became of the records a run produced
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TelematicsStats {
+    pub produced: u64,
+    pub uploaded: u64,
+    // Left out above the high watermark
+    pub thinned: u64,
+    // Oldest records pushed out of a full buffer
+    pub dropped: u64,
+    pub max_buffered: usize,
+    pub outages: u64,
+    pub offline_time: f64, // s
+    // Longest time a record waited before it was uploaded
+    pub max_delay: f64, // s
+    // Bytes the uploads would have taken in full, and took on the wire
+    pub raw_bytes: u64,
+    pub sent_bytes: u64,
+    // Uploads compression left out as nothing had moved
+    pub suppressed: u64,
+}
+
+impl TelematicsStats {
+    pub fn lost(&self) -> u64 {
+        self.thinned + self.dropped
+    }
+
+    pub fn saved_percent(&self) -> f64 {
+        if self.raw_bytes == 0 {
+            return 0.0;
+        }
+        100.0 * (1.0 - self.sent_bytes as f64 / self.raw_bytes as f64)
+    }
+}
+
+// The telematics control unit: signals recorded every step are uploaded in
+// order while the network is up and kept in a bounded buffer while it is
+// down. Back online, the backlog goes out at the modem's uplink rate, oldest
+// first, so nothing overtakes older data.
+pub struct TelematicsUnit {
+    schedule: NetworkSchedule,
+    capacity: usize,
+    uplink_rate: f64, // records per second
+    buffer: VecDeque<TelemetryRecord>,
+    next_sequence: u64,
+    online: bool,
+    // Records the uplink may still send this step
+    credit: f64,
+    uplink: Option<BufWriter<File>>,
+    compression: Option<Compression>,
+    pub stats: TelematicsStats,
+}
+
+impl TelematicsUnit {
+    pub fn new(schedule: NetworkSchedule, capacity: usize, uplink_rate: f64) -> Self {
+        TelematicsUnit {
+            schedule,
+            capacity: capacity.max(1),
+            uplink_rate,
+            buffer: VecDeque::new(),
+            next_sequence: 0,
+            online: true,
+            credit: 0.0,
+            uplink: None,
+            compression: None,
+            stats: TelematicsStats::default(),
+        }
+    }
+
+    // Uploaded records are written to this file as JSON lines, standing in
+    // for the backend
+    pub fn with_uplink(mut self, path: &Path) -> io::Result<Self> {
+        self.uplink = Some(BufWriter::new(File::create(path)?));
+        Ok(self)
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    // Queues a sample taken at `time`
+    pub fn record(&mut self, time: f64, values: &[(&str, f64)]) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.stats.produced += 1;
+
+        if self.buffer.len() as f64 >= self.capacity as f64 * HIGH_WATERMARK && !sequence.is_multiple_of(THINNING) {
+            self.stats.thinned += 1;
+            return;
+        }
+        if self.buffer.len() >= self.capacity {
+            self.buffer.pop_front();
+            self.stats.dropped += 1;
+        }
+        self.buffer.push_back(TelemetryRecord {
+            sequence,
+            time,
+            delta: false,
+            values: values.iter().map(|&(name, value)| (name.to_string(), value)).collect(),
+        });
+        self.stats.max_buffered = self.stats.max_buffered.max(self.buffer.len());
+    }
+
+    // Follows the network over the `dt` seconds up to `time` and uploads
+    // what the uplink rate allows; returns the records as they went on the
+    // wire
+    pub fn step(&mut self, time: f64, dt: f64) -> Vec<TelemetryRecord> {
+        let online = self.schedule.is_online(time);
+        if online != self.online {
+            self.online = online;
+            let locale = locale::current();
+            if online {
+                sim_log::info(
+                    "telematics",
+                    &format!("Network back at {} s, {} records to upload", locale.number(time, 0), self.buffer.len()),
+                );
+            } else {
+                self.stats.outages += 1;
+                sim_log::info("telematics", &format!("Network lost at {} s, buffering", locale.number(time, 0)));
+            }
+        }
+        if !online {
+            self.stats.offline_time += dt;
+            self.credit = 0.0;
+            return Vec::new();
+        }
+
+        self.credit += self.uplink_rate * dt;
+        let count = (self.credit.floor() as usize).min(self.buffer.len());
+        self.credit = if count == self.buffer.len() { 0.0 } else { self.credit - count as f64 };
+        let mut sent = Vec::with_capacity(count);
+        for record in self.buffer.drain(..count).collect::<Vec<_>>() {
+            self.stats.uploaded += 1;
+            self.stats.max_delay = self.stats.max_delay.max(time - record.time);
+            let raw_size = record.encoded_size() as u64;
+            self.stats.raw_bytes += raw_size;
+            let encoded = match &mut self.compression {
+                Some(compression) => compression.encode(&record),
+                None => Some(record),
+            };
+            let Some(encoded) = encoded else {
+                self.stats.suppressed += 1;
+                continue;
+            };
+            self.stats.sent_bytes += encoded.encoded_size() as u64;
+            self.write_uplink(&encoded);
+            sent.push(encoded);
+        }
+        sent
+    }
+
+    fn write_uplink(&mut self, record: &TelemetryRecord) {
+        let Some(uplink) = &mut self.uplink else {
+            return;
+        };
+        let written = serde_json::to_writer(&mut *uplink, record).map_err(io::Error::from).and_then(|_| writeln!(uplink));
+        if let Err(e) = written {
+            sim_log::warn("telematics", &format!("Cannot write the uplink, no more records: {}", e));
+            self.uplink = None;
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.uplink {
+            Some(uplink) => uplink.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn print_summary(&self) {
+        let locale = locale::current();
+        let stats = &self.stats;
+        println!(
+            "Telematics: {} records, {} uploaded, {} still buffered; {} outages, offline for {} min",
+            stats.produced,
+            stats.uploaded,
+            self.buffer.len(),
+            stats.outages,
+            locale.number(stats.offline_time / 60.0, 1)
+        );
+        println!(
+            "  lost {} ({} thinned above {}% of the buffer, {} dropped from a full buffer of {}), peak buffer {}, longest delay {} s",
+            stats.lost(),
+            stats.thinned,
+            locale.number(HIGH_WATERMARK * 100.0, 0),
+            stats.dropped,
+            self.capacity,
+            stats.max_buffered,
+            locale.number(stats.max_delay, 0)
+        );
+        if self.compression.is_some() {
+            println!(
+                "  compression: {} kB sent instead of {} kB ({}% saved), {} uploads left out as nothing moved",
+                locale.number(stats.sent_bytes as f64 / 1000.0, 1),
+                locale.number(stats.raw_bytes as f64 / 1000.0, 1),
+                locale.number(stats.saved_percent(), 1),
+                stats.suppressed
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedules_are_named_or_listed_outages() {
+        let tunnel = NetworkSchedule::parse("tunnel").unwrap();
+        assert!(tunnel.is_online(299.0));
+        assert!(!tunnel.is_online(330.0));
+        // Periodic outages come back every period
+        assert!(!tunnel.is_online(930.0));
+
+        let schedule = NetworkSchedule::parse("1h+15min, 10min+2min").unwrap();
+        assert_eq!(schedule.outages[0], Outage { start: 600.0, duration: 120.0 });
+        assert!(!schedule.is_online(3700.0));
+        assert!(schedule.is_online(3600.0 + 900.0));
+        assert!(NetworkSchedule::parse("10min").is_err());
+        assert!(NetworkSchedule::parse("10min+2parsecs").is_err());
+        assert!(NetworkSchedule::parse("always-on").unwrap().is_online(1e9));
+    }
+
+    #[test]
+    fn records_wait_out_an_outage_and_go_out_oldest_first() {
+        let schedule = NetworkSchedule::parse("0+10").unwrap();
+        let mut unit = TelematicsUnit::new(schedule, 100, 2.0);
+        for second in 0..10 {
+            unit.record(second as f64, &[("speed", second as f64)]);
+            assert!(unit.step(second as f64, 1.0).is_empty());
+        }
+        assert!(!unit.is_online());
+        assert_eq!(unit.buffered(), 10);
+
+        let sent = unit.step(10.0, 1.0);
+        assert_eq!(sent.iter().map(|record| record.sequence).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(unit.stats.max_delay, 10.0);
+        assert_eq!((unit.stats.outages, unit.stats.offline_time), (1, 10.0));
+    }
+
+    #[test]
+    fn a_filling_buffer_thins_then_drops_the_oldest() {
+        let schedule = NetworkSchedule::parse("0+1000").unwrap();
+        let mut unit = TelematicsUnit::new(schedule, 10, 1.0);
+        for second in 0..30 {
+            unit.record(second as f64, &[("speed", 1.0)]);
+        }
+        assert_eq!(unit.buffered(), 10);
+        assert_eq!(unit.stats.produced, 30);
+        assert!(unit.stats.thinned > 0 && unit.stats.dropped > 0);
+        assert_eq!(unit.stats.lost(), 30 - 10);
+    }
+
+    #[test]
+    fn compression_sends_only_what_left_its_deadband() {
+        let mut compression = Compression::parse("0.5,speed=2").unwrap();
+        assert_eq!((compression.deadband("speed"), compression.deadband("rpm")), (2.0, 0.5));
+        assert!(Compression::parse("speed=-1").is_err());
+
+        let record = |sequence, speed, rpm| TelemetryRecord {
+            sequence,
+            time: sequence as f64,
+            delta: false,
+            values: vec![("speed".to_string(), speed), ("rpm".to_string(), rpm)],
+        };
+        // The first upload is a keyframe
+        assert_eq!(compression.encode(&record(0, 50.0, 1000.0)), Some(record(0, 50.0, 1000.0)));
+        assert_eq!(compression.encode(&record(1, 51.0, 1000.2)), None);
+        let delta = compression.encode(&record(2, 53.0, 1000.2)).unwrap();
+        assert!(delta.delta);
+        assert_eq!(delta.values, vec![("speed".to_string(), 3.0)]);
+    }
+
+    #[test]
+    fn a_backlog_goes_out_in_order_once_the_network_is_back() {
+        // Online for ten minutes, then half an hour without a network
+        let mut unit = TelematicsUnit::new(NetworkSchedule::parse("10min+30min").unwrap(), 20, 1.0);
+        for minute in 1..40 {
+            let time = minute as f64 * 60.0;
+            unit.record(time, &[("speed", minute as f64)]);
+            unit.step(time, 60.0);
+        }
+        assert!(!unit.is_online());
+        assert_eq!(unit.stats.uploaded, 9);
+        // Past 16 buffered records only every other one is kept
+        assert_eq!(unit.buffered(), 20);
+        assert!(unit.stats.thinned > 0 && unit.stats.dropped > 0);
+
+        let uploaded = unit.step(2400.0, 60.0);
+        assert_eq!(uploaded.len(), 20);
+        assert!(uploaded.windows(2).all(|pair| pair[0].sequence < pair[1].sequence));
+        assert_eq!(unit.stats.uploaded + unit.stats.lost(), unit.stats.produced);
+    }
+
+    #[test]
+    fn the_backend_rebuilds_compressed_uploads_within_the_deadbands() {
+        let deadbands = "5,latitude=0.05,longitude=0.05";
+        // Offline throughout, the whole backlog goes out at the end
+        let upload = |compression: Option<&str>| {
+            let mut unit = TelematicsUnit::new(NetworkSchedule::parse("0s+1d").unwrap(), 200, 1.0);
+            if let Some(compression) = compression {
+                unit = unit.with_compression(Compression::parse(compression).unwrap());
+            }
+            for minute in 0..100 {
+                let time = minute as f64 * 60.0;
+                let speed = 60.0 + 20.0 * (time / 900.0).sin();
+                let latitude = 48.0 + minute as f64 * 0.01;
+                unit.record(time, &[("speed", speed), ("odometer", minute as f64), ("latitude", latitude), ("longitude", 11.5)]);
+            }
+            let records = unit.step(86400.0, 100.0);
+            (records, unit.stats)
+        };
+        let (raw, _) = upload(None);
+        let (compressed, stats) = upload(Some(deadbands));
+        assert_eq!(raw.len(), 100);
+        assert!(compressed.len() < raw.len() && stats.suppressed > 0);
+        assert!(compressed[0].values.len() == raw[0].values.len() && !compressed[0].delta);
+        assert!(stats.saved_percent() > 50.0);
+
+        let compression = Compression::parse(deadbands).unwrap();
+        let mut received = compressed.iter().peekable();
+        let mut backend: Vec<(String, f64)> = Vec::new();
+        for record in &raw {
+            if let Some(update) = received.next_if(|update| update.sequence == record.sequence) {
+                for (name, value) in &update.values {
+                    match backend.iter_mut().find(|(signal, _)| signal == name) {
+                        Some((_, held)) if update.delta => *held += value,
+                        Some((_, held)) => *held = *value,
+                        None => backend.push((name.clone(), *value)),
+                    }
+                }
+            }
+            for ((name, value), (_, held)) in record.values.iter().zip(&backend) {
+                assert!((value - held).abs() <= compression.deadband(name) + 1e-9, "{} at {}", name, record.sequence);
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Pressure, Temperature};
+
+// Tread of a new passenger car tire and the legal minimum, in mm
+pub const NEW_TREAD_DEPTH: f32 = 8.0;
+pub const LEGAL_TREAD_DEPTH: f32 = 1.6;
+// Cold inflation pressure of a passenger car tire, in kPa
+pub const NOMINAL_PRESSURE: f32 = 230.0;
+// Nominal pressures are cold pressures, specified at this temperature
+pub const REFERENCE_TEMPERATURE: Temperature = Temperature::from_celsius(20.0);
+// Load a passenger car tire is rated for at its nominal pressure (load index
+// 91), and the share of a mid-size car's weight it usually carries, in kg
+pub const RATED_LOAD: f32 = 615.0;
+pub const WHEEL_LOAD: f32 = 400.0;
+// Horne's rule: a smooth tire on deep water rides up at this many km/h per
+// square root of its pressure in kPa
+const HORNE_FACTOR: f32 = 6.36;
+// Water film the tread grooves carry away, in mm per mm of tread
+const GROOVE_DRAINAGE: f32 = 0.3;
+// Grip lost per share of missing inflation: the sidewalls give way and the
+// tread lifts off the road in its middle. Over-inflation shrinks the contact
+// patch, which costs less.
+const UNDERINFLATION_GRIP_LOSS: f32 = 0.5;
+const OVERINFLATION_GRIP_LOSS: f32 = 0.25;
+const MIN_GRIP_FACTOR: f32 = 0.5;
+
+// Ideal gas law on the absolute pressure: what `pressure` measured at
+// `temperature` becomes when cooled (or warmed) to the reference temperature
+pub fn cold_pressure(pressure: Pressure, temperature: Temperature) -> Pressure {
+    Pressure::from_absolute(pressure.absolute() * REFERENCE_TEMPERATURE.kelvin() / temperature.kelvin())
+}
+
+// A tire as the simulations see it: the TPMS watches its pressure and
+// temperature, the road monitor brakes and corners on it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tire {
+    pub pressure: Pressure,
+    pub temperature: Temperature,
+    pub nominal_pressure: Pressure,
+    pub tread_depth: f32, // mm
+    pub load: f32,        // kg
+}
+
+impl Default for Tire {
+    fn default() -> Self {
+        Tire::new(Pressure::from_kpa(NOMINAL_PRESSURE))
+    }
+}
+
+impl Tire {
+    // A new tire inflated to `nominal_pressure` at the reference temperature
+    pub fn new(nominal_pressure: Pressure) -> Self {
+        Tire {
+            pressure: nominal_pressure,
+            temperature: REFERENCE_TEMPERATURE,
+            nominal_pressure,
+            tread_depth: NEW_TREAD_DEPTH,
+            load: WHEEL_LOAD,
+        }
+    }
+
+    // Wears the tread down from new (0) to the legal minimum (1)
+    pub fn set_wear(&mut self, wear: f32) {
+        self.tread_depth = NEW_TREAD_DEPTH - wear.clamp(0.0, 1.0) * (NEW_TREAD_DEPTH - LEGAL_TREAD_DEPTH);
+    }
+
+    pub fn adjust_pressure(&mut self, delta: Pressure) {
+        self.pressure = (self.pressure + delta).max(Pressure::ZERO);
+    }
+
+    // The air in the tire heats up or cools down at constant volume
+    pub fn set_temperature(&mut self, temperature: Temperature) {
+        let absolute = self.pressure.absolute() * temperature.kelvin() / self.temperature.kelvin();
+        self.pressure = Pressure::from_absolute(absolute).max(Pressure::ZERO);
+        self.temperature = temperature;
+    }
+
+    pub fn cold_pressure(&self) -> Pressure {
+        cold_pressure(self.pressure, self.temperature)
+    }
+
+    // Cold pressure as a share of what the load needs: a tire carrying more
+    // than its rating deflects like an under-inflated one
+    pub fn inflation(&self) -> f32 {
+        let required = self.nominal_pressure * (self.load / RATED_LOAD).max(1.0);
+        if required <= Pressure::ZERO {
+            return 1.0;
+        }
+        (self.cold_pressure() / required).max(0.0)
+    }
+
+    // Share of its full grip the tire transfers for braking and driving;
+    // 1 when correctly inflated
+    pub fn grip_factor(&self) -> f32 {
+        let inflation = self.inflation();
+        let loss = if inflation < 1.0 {
+            (1.0 - inflation) * UNDERINFLATION_GRIP_LOSS
+        } else {
+            (inflation - 1.0) * OVERINFLATION_GRIP_LOSS
+        };
+        (1.0 - loss).max(MIN_GRIP_FACTOR)
+    }
+
+    // Speed in km/h above which the tire rides up on a water film `film` mm
+    // deep, None while the grooves drain it. Once the film is much deeper
+    // than they can drain, the onset falls to Horne's speed of a smooth tire.
+    pub fn aquaplaning_speed(&self, film: f32) -> Option<f32> {
+        let drained = self.tread_depth.max(0.0) * GROOVE_DRAINAGE;
+        if film <= drained {
+            return None;
+        }
+        let smooth = HORNE_FACTOR * self.pressure.kpa().max(0.0).sqrt();
+        Some(smooth * (1.0 + drained / (film - drained)).powf(0.25))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn pressure_follows_the_temperature_of_the_air() {
+        let mut tire = Tire::default();
+        tire.set_temperature(Temperature::from_celsius(60.0));
+        assert!(tire.pressure.kpa() > NOMINAL_PRESSURE);
+        assert!(close(tire.cold_pressure().kpa(), NOMINAL_PRESSURE));
+        tire.set_temperature(REFERENCE_TEMPERATURE);
+        assert!(close(tire.pressure.kpa(), NOMINAL_PRESSURE));
+
+        tire.adjust_pressure(Pressure::from_kpa(-500.0));
+        assert_eq!(tire.pressure, Pressure::ZERO);
+    }
+
+    #[test]
+    fn grip_falls_with_wrong_inflation_and_overload() {
+        let mut tire = Tire::default();
+        assert!(close(tire.grip_factor(), 1.0));
+        tire.adjust_pressure(Pressure::from_kpa(-NOMINAL_PRESSURE / 2.0));
+        assert!(close(tire.grip_factor(), 0.75));
+        tire.adjust_pressure(Pressure::from_kpa(NOMINAL_PRESSURE));
+        assert!(close(tire.grip_factor(), 0.875));
+        tire.pressure = Pressure::ZERO;
+        assert_eq!(tire.grip_factor(), MIN_GRIP_FACTOR);
+
+        let overloaded = Tire {
+            load: 2.0 * RATED_LOAD,
+            ..Tire::default()
+        };
+        assert!(close(overloaded.inflation(), 0.5));
+    }
+
+    #[test]
+    fn worn_tires_aquaplane_on_shallower_water() {
+        let mut tire = Tire::default();
+        assert_eq!(tire.aquaplaning_speed(2.0), None);
+        let new = tire.aquaplaning_speed(5.0).unwrap();
+        tire.set_wear(1.0);
+        assert!(close(tire.tread_depth, LEGAL_TREAD_DEPTH));
+        let worn = tire.aquaplaning_speed(5.0).unwrap();
+        assert!(worn < new);
+        // Deep water leaves the smooth tire's speed
+        let smooth = HORNE_FACTOR * NOMINAL_PRESSURE.sqrt();
+        assert!(tire.aquaplaning_speed(1000.0).unwrap() - smooth < 0.1);
+    }
+}
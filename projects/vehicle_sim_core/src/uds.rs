@@ -0,0 +1,557 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::can_bus::CanBus;
+use crate::isotp::IsoTpChannel;
+use crate::sim_log;
+use crate::vehicle::{Trim, VariantCoding, VehicleIdentity, Vin};
+
+// Services
+pub const DIAGNOSTIC_SESSION_CONTROL: u8 = 0x10;
+pub const CLEAR_DIAGNOSTIC_INFORMATION: u8 = 0x14;
+pub const READ_DTC_INFORMATION: u8 = 0x19;
+pub const READ_DATA_BY_IDENTIFIER: u8 = 0x22;
+pub const WRITE_DATA_BY_IDENTIFIER: u8 = 0x2E;
+
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+const POSITIVE_RESPONSE_OFFSET: u8 = 0x40;
+
+// Negative response codes
+pub const SERVICE_NOT_SUPPORTED: u8 = 0x11;
+pub const SUBFUNCTION_NOT_SUPPORTED: u8 = 0x12;
+pub const INCORRECT_MESSAGE_LENGTH: u8 = 0x13;
+pub const REQUEST_OUT_OF_RANGE: u8 = 0x31;
+pub const GENERAL_PROGRAMMING_FAILURE: u8 = 0x72;
+pub const SERVICE_NOT_SUPPORTED_IN_ACTIVE_SESSION: u8 = 0x7F;
+
+// ReadDTCInformation sub-functions
+pub const REPORT_NUMBER_OF_DTC_BY_STATUS_MASK: u8 = 0x01;
+pub const REPORT_DTC_BY_STATUS_MASK: u8 = 0x02;
+
+// DTC status bits (ISO 14229-1 Annex D)
+pub const TEST_FAILED: u8 = 0x01;
+pub const TEST_FAILED_THIS_OPERATION_CYCLE: u8 = 0x02;
+pub const PENDING_DTC: u8 = 0x04;
+pub const CONFIRMED_DTC: u8 = 0x08;
+pub const WARNING_INDICATOR_REQUESTED: u8 = 0x80;
+
+// Status bits the servers report, returned as the availability mask
+pub const STATUS_AVAILABILITY_MASK: u8 =
+    TEST_FAILED | TEST_FAILED_THIS_OPERATION_CYCLE | PENDING_DTC | CONFIRMED_DTC | WARNING_INDICATOR_REQUESTED;
+
+// Identification data every server with a vehicle identity answers: the
+// VIN as 17 ASCII characters, the coding as trim byte and coding word
+pub const VIN_DID: u16 = 0xF190;
+pub const VARIANT_CODING_DID: u16 = 0xF101;
+
+// ClearDiagnosticInformation group for every DTC
+pub const ALL_GROUPS: u32 = 0xFF_FFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Session {
+    Default = 0x01,
+    Programming = 0x02,
+    Extended = 0x03,
+}
+
+impl Session {
+    fn from_byte(byte: u8) -> Option<Session> {
+        match byte {
+            0x01 => Some(Session::Default),
+            0x02 => Some(Session::Programming),
+            0x03 => Some(Session::Extended),
+            _ => None,
+        }
+    }
+}
+
+// A DTC in the three-byte UDS form and its status bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DtcEntry {
+    pub dtc: u32,
+    pub status: u8,
+}
+
+// What an ECU exposes to its diagnostic server
+pub trait DiagnosticHandler {
+    fn dtcs(&self) -> Vec<DtcEntry>;
+    fn clear_dtcs(&mut self, group: u32);
+    // `None` when the identifier is not supported
+    fn read_data(&self, identifier: u16) -> Option<Vec<u8>>;
+}
+
+// Minimal UDS (ISO 14229) server on a request/response identifier pair.
+// The ECU calls `poll` once per step to answer whatever arrived.
+pub struct UdsServer {
+    channel: IsoTpChannel,
+    session: Session,
+    identity: Option<VehicleIdentity>,
+    // Where written identification data is stored
+    identity_path: Option<PathBuf>,
+}
+
+impl UdsServer {
+    pub fn new(bus: Arc<CanBus>, request_id: u32, response_id: u32) -> Self {
+        UdsServer {
+            channel: IsoTpChannel::open(bus, response_id, request_id),
+            session: Session::Default,
+            identity: None,
+            identity_path: None,
+        }
+    }
+
+    pub fn with_identity(mut self, identity: VehicleIdentity, path: Option<PathBuf>) -> Self {
+        self.identity = Some(identity);
+        self.identity_path = path;
+        self
+    }
+
+    pub fn session(&self) -> Session {
+        self.session
+    }
+
+    pub fn poll(&mut self, handler: &mut dyn DiagnosticHandler) {
+        while let Some(request) = self.channel.poll() {
+            if let Some(response) = self.handle(&request, handler) {
+                self.channel.send(&response);
+            }
+        }
+    }
+
+    fn handle(&mut self, request: &[u8], handler: &mut dyn DiagnosticHandler) -> Option<Vec<u8>> {
+        let &service = request.first()?;
+        let result = match service {
+            DIAGNOSTIC_SESSION_CONTROL => self.session_control(request),
+            CLEAR_DIAGNOSTIC_INFORMATION => self.clear_diagnostic_information(request, handler),
+            READ_DTC_INFORMATION => read_dtc_information(request, handler),
+            READ_DATA_BY_IDENTIFIER => self.read_data_by_identifier(request, handler),
+            WRITE_DATA_BY_IDENTIFIER => self.write_data_by_identifier(request),
+            _ => Err(SERVICE_NOT_SUPPORTED),
+        };
+        Some(match result {
+            Ok(mut data) => {
+                data.insert(0, service + POSITIVE_RESPONSE_OFFSET);
+                data
+            }
+            Err(code) => vec![NEGATIVE_RESPONSE, service, code],
+        })
+    }
+
+    fn session_control(&mut self, request: &[u8]) -> Result<Vec<u8>, u8> {
+        if request.len() != 2 {
+            return Err(INCORRECT_MESSAGE_LENGTH);
+        }
+        // Nothing to flash, so there is no programming session
+        match Session::from_byte(request[1]) {
+            Some(session @ (Session::Default | Session::Extended)) => self.session = session,
+            _ => return Err(SUBFUNCTION_NOT_SUPPORTED),
+        }
+        // P2 server 50 ms, P2* server 5000 ms (in 10 ms units)
+        Ok(vec![request[1], 0x00, 0x32, 0x01, 0xF4])
+    }
+
+    // Fault memory can only be cleared in the extended session, so a stray
+    // request in the default session can't wipe it
+    fn clear_diagnostic_information(&mut self, request: &[u8], handler: &mut dyn DiagnosticHandler) -> Result<Vec<u8>, u8> {
+        if request.len() != 4 {
+            return Err(INCORRECT_MESSAGE_LENGTH);
+        }
+        if self.session != Session::Extended {
+            return Err(SERVICE_NOT_SUPPORTED_IN_ACTIVE_SESSION);
+        }
+        let group = u32::from_be_bytes([0, request[1], request[2], request[3]]);
+        handler.clear_dtcs(group);
+        Ok(Vec::new())
+    }
+
+    fn read_data_by_identifier(&self, request: &[u8], handler: &dyn DiagnosticHandler) -> Result<Vec<u8>, u8> {
+        if request.len() < 3 || request.len().is_multiple_of(2) {
+            return Err(INCORRECT_MESSAGE_LENGTH);
+        }
+        let mut data = Vec::new();
+        for identifier in request[1..].chunks(2) {
+            let identifier = u16::from_be_bytes([identifier[0], identifier[1]]);
+            let value = self
+                .read_identity(identifier)
+                .or_else(|| handler.read_data(identifier))
+                .ok_or(REQUEST_OUT_OF_RANGE)?;
+            data.extend_from_slice(&identifier.to_be_bytes());
+            data.extend_from_slice(&value);
+        }
+        Ok(data)
+    }
+
+    fn read_identity(&self, identifier: u16) -> Option<Vec<u8>> {
+        let identity = self.identity.as_ref()?;
+        match identifier {
+            VIN_DID => Some(identity.vin.as_str().as_bytes().to_vec()),
+            VARIANT_CODING_DID => {
+                let [high, low] = identity.coding.0.to_be_bytes();
+                Some(vec![identity.trim.as_byte(), high, low])
+            }
+            _ => None,
+        }
+    }
+
+    // Only identification data is writable, in the extended session, and
+    // only values that pass the plausibility checks. ECUs read their coding
+    // at startup, so a new coding takes effect at the next start.
+    fn write_data_by_identifier(&mut self, request: &[u8]) -> Result<Vec<u8>, u8> {
+        if request.len() < 4 {
+            return Err(INCORRECT_MESSAGE_LENGTH);
+        }
+        if self.session != Session::Extended {
+            return Err(SERVICE_NOT_SUPPORTED_IN_ACTIVE_SESSION);
+        }
+        let identifier = u16::from_be_bytes([request[1], request[2]]);
+        let data = &request[3..];
+        let identity = self.identity.as_mut().ok_or(REQUEST_OUT_OF_RANGE)?;
+        match identifier {
+            VIN_DID => {
+                if data.len() != 17 {
+                    return Err(INCORRECT_MESSAGE_LENGTH);
+                }
+                let vin = std::str::from_utf8(data).map_err(|_| REQUEST_OUT_OF_RANGE)?;
+                identity.vin = Vin::parse(vin).map_err(|e| {
+                    sim_log::warn("uds", &format!("Rejected VIN {}: {}", vin, e));
+                    REQUEST_OUT_OF_RANGE
+                })?;
+                sim_log::info("uds", &format!("VIN written: {}", identity.vin));
+            }
+            VARIANT_CODING_DID => {
+                let [trim, high, low] = data else {
+                    return Err(INCORRECT_MESSAGE_LENGTH);
+                };
+                let trim = Trim::from_byte(*trim).ok_or(REQUEST_OUT_OF_RANGE)?;
+                let coding = VariantCoding(u16::from_be_bytes([*high, *low]));
+                coding.check(trim).map_err(|e| {
+                    sim_log::warn("uds", &format!("Rejected variant coding: {}", e));
+                    REQUEST_OUT_OF_RANGE
+                })?;
+                identity.trim = trim;
+                identity.coding = coding;
+                sim_log::info(
+                    "uds",
+                    &format!("Coded as {:?} trim with {}; takes effect at the next start", trim, coding.describe()),
+                );
+            }
+            _ => return Err(REQUEST_OUT_OF_RANGE),
+        }
+
+        if let Some(path) = &self.identity_path {
+            identity.save(path).map_err(|e| {
+                sim_log::warn("uds", &format!("Cannot store the vehicle identity in {}: {}", path.display(), e));
+                GENERAL_PROGRAMMING_FAILURE
+            })?;
+        }
+        Ok(identifier.to_be_bytes().to_vec())
+    }
+}
+
+fn read_dtc_information(request: &[u8], handler: &dyn DiagnosticHandler) -> Result<Vec<u8>, u8> {
+    if request.len() != 3 {
+        return Err(INCORRECT_MESSAGE_LENGTH);
+    }
+    let (report, mask) = (request[1], request[2]);
+    let matching: Vec<DtcEntry> = handler.dtcs().into_iter().filter(|entry| entry.status & mask != 0).collect();
+    match report {
+        REPORT_NUMBER_OF_DTC_BY_STATUS_MASK => {
+            // Format identifier 0x01: ISO 14229-1 DTC format
+            let count = matching.len().min(u16::MAX as usize) as u16;
+            let mut data = vec![report, STATUS_AVAILABILITY_MASK, 0x01];
+            data.extend_from_slice(&count.to_be_bytes());
+            Ok(data)
+        }
+        REPORT_DTC_BY_STATUS_MASK => {
+            let mut data = vec![report, STATUS_AVAILABILITY_MASK];
+            for entry in matching {
+                data.extend_from_slice(&entry.dtc.to_be_bytes()[1..]);
+                data.push(entry.status);
+            }
+            Ok(data)
+        }
+        _ => Err(SUBFUNCTION_NOT_SUPPORTED),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UdsError {
+    // The server answered 0x7F with this response code
+    Negative(u8),
+    NoResponse,
+    UnexpectedResponse(Vec<u8>),
+}
+
+impl fmt::Display for UdsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UdsError::Negative(code) => write!(f, "negative response 0x{:02X} ({})", code, response_code_name(*code)),
+            UdsError::NoResponse => write!(f, "no response"),
+            UdsError::UnexpectedResponse(data) => write!(f, "unexpected response {}", hex(data)),
+        }
+    }
+}
+
+pub fn response_code_name(code: u8) -> &'static str {
+    match code {
+        SERVICE_NOT_SUPPORTED => "serviceNotSupported",
+        SUBFUNCTION_NOT_SUPPORTED => "subFunctionNotSupported",
+        INCORRECT_MESSAGE_LENGTH => "incorrectMessageLengthOrInvalidFormat",
+        REQUEST_OUT_OF_RANGE => "requestOutOfRange",
+        GENERAL_PROGRAMMING_FAILURE => "generalProgrammingFailure",
+        SERVICE_NOT_SUPPORTED_IN_ACTIVE_SESSION => "serviceNotSupportedInActiveSession",
+        _ => "unknown",
+    }
+}
+
+// Tester side of a request/response identifier pair. The bus is in-process,
+// so a request is only answered once the server polls: `exchange` drives
+// both sides until the response is complete.
+pub struct UdsClient {
+    channel: IsoTpChannel,
+}
+
+impl UdsClient {
+    pub fn new(bus: Arc<CanBus>, request_id: u32, response_id: u32) -> Self {
+        UdsClient {
+            channel: IsoTpChannel::open(bus, request_id, response_id),
+        }
+    }
+
+    pub fn exchange(
+        &mut self,
+        server: &mut UdsServer,
+        handler: &mut dyn DiagnosticHandler,
+        request: &[u8],
+    ) -> Result<Vec<u8>, UdsError> {
+        self.channel.send(request);
+        // Request, flow control, consecutive frames, with room to spare
+        for _ in 0..4 {
+            server.poll(handler);
+            if let Some(response) = self.channel.poll() {
+                return check_response(request, response);
+            }
+        }
+        Err(UdsError::NoResponse)
+    }
+
+    pub fn session_control(
+        &mut self,
+        server: &mut UdsServer,
+        handler: &mut dyn DiagnosticHandler,
+        session: Session,
+    ) -> Result<(), UdsError> {
+        self.exchange(server, handler, &[DIAGNOSTIC_SESSION_CONTROL, session as u8])
+            .map(|_| ())
+    }
+
+    pub fn read_dtcs(
+        &mut self,
+        server: &mut UdsServer,
+        handler: &mut dyn DiagnosticHandler,
+        mask: u8,
+    ) -> Result<Vec<DtcEntry>, UdsError> {
+        let response = self.exchange(server, handler, &[READ_DTC_INFORMATION, REPORT_DTC_BY_STATUS_MASK, mask])?;
+        // Sub-function and status availability mask precede the records
+        let records = response.get(3..).unwrap_or_default();
+        Ok(records
+            .chunks_exact(4)
+            .map(|record| DtcEntry {
+                dtc: u32::from_be_bytes([0, record[0], record[1], record[2]]),
+                status: record[3],
+            })
+            .collect())
+    }
+
+    pub fn clear_dtcs(
+        &mut self,
+        server: &mut UdsServer,
+        handler: &mut dyn DiagnosticHandler,
+        group: u32,
+    ) -> Result<(), UdsError> {
+        let group = group.to_be_bytes();
+        self.exchange(server, handler, &[CLEAR_DIAGNOSTIC_INFORMATION, group[1], group[2], group[3]])
+            .map(|_| ())
+    }
+
+    pub fn read_data(
+        &mut self,
+        server: &mut UdsServer,
+        handler: &mut dyn DiagnosticHandler,
+        identifier: u16,
+    ) -> Result<Vec<u8>, UdsError> {
+        let [high, low] = identifier.to_be_bytes();
+        let response = self.exchange(server, handler, &[READ_DATA_BY_IDENTIFIER, high, low])?;
+        if response.get(1..3) != Some(&[high, low][..]) {
+            return Err(UdsError::UnexpectedResponse(response));
+        }
+        Ok(response[3..].to_vec())
+    }
+
+    pub fn write_data(
+        &mut self,
+        server: &mut UdsServer,
+        handler: &mut dyn DiagnosticHandler,
+        identifier: u16,
+        data: &[u8],
+    ) -> Result<(), UdsError> {
+        let mut request = vec![WRITE_DATA_BY_IDENTIFIER];
+        request.extend_from_slice(&identifier.to_be_bytes());
+        request.extend_from_slice(data);
+        self.exchange(server, handler, &request).map(|_| ())
+    }
+}
+
+fn check_response(request: &[u8], response: Vec<u8>) -> Result<Vec<u8>, UdsError> {
+    match response.as_slice() {
+        [NEGATIVE_RESPONSE, service, code] if Some(service) == request.first() => Err(UdsError::Negative(*code)),
+        [service, ..] if Some(&service.wrapping_sub(POSITIVE_RESPONSE_OFFSET)) == request.first() => Ok(response),
+        _ => Err(UdsError::UnexpectedResponse(response)),
+    }
+}
+
+// Converts an SAE J2012 code like `C0750` to the three-byte UDS form, with
+// a zero failure type byte
+pub fn dtc_from_code(code: &str) -> Option<u32> {
+    let mut chars = code.chars();
+    let system = match chars.next()? {
+        'P' => 0b00,
+        'C' => 0b01,
+        'B' => 0b10,
+        'U' => 0b11,
+        _ => return None,
+    };
+    let digits = chars.as_str();
+    if digits.len() != 4 {
+        return None;
+    }
+    let first = digits[..1].parse::<u32>().ok().filter(|digit| *digit <= 3)?;
+    let rest = u32::from_str_radix(&digits[1..], 16).ok()?;
+    Some(((system << 14 | first << 12 | rest) << 8) & 0xFF_FFFF)
+}
+
+// Inverse of `dtc_from_code`, ignoring the failure type byte
+pub fn code_from_dtc(dtc: u32) -> String {
+    let code = (dtc >> 8) & 0xFFFF;
+    let system = ['P', 'C', 'B', 'U'][(code >> 14) as usize];
+    format!("{}{}{:03X}", system, (code >> 12) & 0b11, code & 0xFFF)
+}
+
+pub fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SimRng;
+
+    const REQUEST_ID: u32 = 0x7E0;
+    const RESPONSE_ID: u32 = 0x7E8;
+
+    #[derive(Default)]
+    struct Ecu {
+        dtcs: Vec<DtcEntry>,
+        cleared: Option<u32>,
+    }
+
+    impl DiagnosticHandler for Ecu {
+        fn dtcs(&self) -> Vec<DtcEntry> {
+            self.dtcs.clone()
+        }
+
+        fn clear_dtcs(&mut self, group: u32) {
+            self.cleared = Some(group);
+            self.dtcs.clear();
+        }
+
+        fn read_data(&self, identifier: u16) -> Option<Vec<u8>> {
+            (identifier == 0x1234).then(|| vec![0xAB; 20])
+        }
+    }
+
+    fn connect() -> (UdsClient, UdsServer, Ecu) {
+        let bus = Arc::new(CanBus::new());
+        let identity = VehicleIdentity::generate(Trim::Comfort, &mut SimRng::from_seed(1));
+        let server = UdsServer::new(bus.clone(), REQUEST_ID, RESPONSE_ID).with_identity(identity, None);
+        let ecu = Ecu {
+            dtcs: vec![
+                DtcEntry { dtc: dtc_from_code("C0750").unwrap(), status: TEST_FAILED | CONFIRMED_DTC },
+                DtcEntry { dtc: dtc_from_code("B1A20").unwrap(), status: PENDING_DTC },
+            ],
+            cleared: None,
+        };
+        (UdsClient::new(bus, REQUEST_ID, RESPONSE_ID), server, ecu)
+    }
+
+    #[test]
+    fn dtc_codes_convert_to_the_three_byte_form_and_back() {
+        assert_eq!(dtc_from_code("P0420"), Some(0x042000));
+        assert_eq!(dtc_from_code("C0750"), Some(0x475000));
+        assert_eq!(dtc_from_code("U3FFF"), Some(0xFFFF00));
+        assert_eq!(code_from_dtc(0x475000), "C0750");
+        assert_eq!(code_from_dtc(dtc_from_code("B1A2F").unwrap()), "B1A2F");
+        assert_eq!(dtc_from_code("C4750"), None);
+        assert_eq!(dtc_from_code("X0750"), None);
+        assert_eq!(dtc_from_code("C075"), None);
+        assert_eq!(hex(&[0x19, 0x02, 0xFF]), "19 02 FF");
+    }
+
+    #[test]
+    fn reads_dtcs_by_status_mask_and_long_data() {
+        let (mut client, mut server, mut ecu) = connect();
+        let failed = client.read_dtcs(&mut server, &mut ecu, TEST_FAILED).unwrap();
+        assert_eq!(failed, vec![ecu.dtcs[0]]);
+        assert_eq!(client.read_dtcs(&mut server, &mut ecu, 0xFF).unwrap().len(), 2);
+
+        let response = client
+            .exchange(&mut server, &mut ecu, &[READ_DTC_INFORMATION, REPORT_NUMBER_OF_DTC_BY_STATUS_MASK, PENDING_DTC])
+            .unwrap();
+        assert_eq!(response, vec![0x59, 0x01, STATUS_AVAILABILITY_MASK, 0x01, 0x00, 0x01]);
+
+        // Twenty bytes take a segmented response
+        assert_eq!(client.read_data(&mut server, &mut ecu, 0x1234).unwrap(), vec![0xAB; 20]);
+        assert_eq!(client.read_data(&mut server, &mut ecu, VIN_DID).unwrap().len(), 17);
+        assert_eq!(client.read_data(&mut server, &mut ecu, 0x4321), Err(UdsError::Negative(REQUEST_OUT_OF_RANGE)));
+    }
+
+    #[test]
+    fn clearing_and_writing_need_the_extended_session() {
+        let (mut client, mut server, mut ecu) = connect();
+        assert_eq!(
+            client.clear_dtcs(&mut server, &mut ecu, ALL_GROUPS),
+            Err(UdsError::Negative(SERVICE_NOT_SUPPORTED_IN_ACTIVE_SESSION))
+        );
+        assert_eq!(
+            client.session_control(&mut server, &mut ecu, Session::Programming),
+            Err(UdsError::Negative(SUBFUNCTION_NOT_SUPPORTED))
+        );
+        client.session_control(&mut server, &mut ecu, Session::Extended).unwrap();
+        assert_eq!(server.session(), Session::Extended);
+        client.clear_dtcs(&mut server, &mut ecu, ALL_GROUPS).unwrap();
+        assert_eq!(ecu.cleared, Some(ALL_GROUPS));
+
+        // The base trim has no rear climate zone to code
+        let premium = [Trim::Base.as_byte(), 0x00, VariantCoding::REAR_CLIMATE_ZONE as u8];
+        assert_eq!(
+            client.write_data(&mut server, &mut ecu, VARIANT_CODING_DID, &premium),
+            Err(UdsError::Negative(REQUEST_OUT_OF_RANGE))
+        );
+        let coding = [Trim::Premium.as_byte(), 0x00, VariantCoding::ALL as u8];
+        client.write_data(&mut server, &mut ecu, VARIANT_CODING_DID, &coding).unwrap();
+        assert_eq!(client.read_data(&mut server, &mut ecu, VARIANT_CODING_DID).unwrap(), coding.to_vec());
+    }
+
+    #[test]
+    fn malformed_requests_get_negative_responses() {
+        let (mut client, mut server, mut ecu) = connect();
+        let negative = |client: &mut UdsClient, server: &mut UdsServer, ecu: &mut Ecu, request: &[u8]| {
+            client.exchange(server, ecu, request).unwrap_err()
+        };
+        assert_eq!(negative(&mut client, &mut server, &mut ecu, &[0x31, 0x01]), UdsError::Negative(SERVICE_NOT_SUPPORTED));
+        assert_eq!(negative(&mut client, &mut server, &mut ecu, &[0x22, 0xF1]), UdsError::Negative(INCORRECT_MESSAGE_LENGTH));
+        assert_eq!(negative(&mut client, &mut server, &mut ecu, &[0x19, 0x0A, 0xFF]), UdsError::Negative(SUBFUNCTION_NOT_SUPPORTED));
+        assert_eq!(response_code_name(INCORRECT_MESSAGE_LENGTH), "incorrectMessageLengthOrInvalidFormat");
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+// Plain aliases documenting which unit a value is in
+pub type Seconds = f64;
+pub type Hours = f64;
+pub type Kilometers = f64;
+pub type KilometersPerHour = f64;
+pub type MetersPerSecond = f64;
+pub type Liters = f64;
+pub type Psi = f32;
+pub type Celsius = f32;
+
+pub const GRAVITY: f64 = 9.81;
+pub const SECONDS_PER_HOUR: f64 = 3600.0;
+pub const ATMOSPHERIC_PRESSURE: Psi = 14.696;
+pub const KPA_PER_PSI: f32 = 6.894_757;
+
+pub fn kmh_to_ms(speed: KilometersPerHour) -> MetersPerSecond {
+    speed / 3.6
+}
+
+pub fn ms_to_kmh(speed: MetersPerSecond) -> KilometersPerHour {
+    speed * 3.6
+}
+
+pub fn seconds_to_hours(seconds: Seconds) -> Hours {
+    seconds / SECONDS_PER_HOUR
+}
+
+pub fn hours_to_seconds(hours: Hours) -> Seconds {
+    hours * SECONDS_PER_HOUR
+}
+
+pub fn celsius_to_kelvin(temperature: Celsius) -> f32 {
+    temperature + 273.15
+}
+
+// Newtype quantities for public APIs, so a pressure cannot be passed where a
+// temperature is expected. They serialize as the bare number, keeping
+// checkpoints and JSON output unchanged.
+macro_rules! quantity {
+    ($name:ident, $inner:ty, $from:ident, $value:ident, $unit:expr) => {
+        #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name($inner);
+
+        impl $name {
+            pub const ZERO: $name = $name(0.0);
+
+            pub const fn $from(value: $inner) -> Self {
+                $name(value)
+            }
+
+            pub const fn $value(self) -> $inner {
+                self.0
+            }
+
+            pub fn max(self, other: $name) -> $name {
+                $name(self.0.max(other.0))
+            }
+
+            pub fn min(self, other: $name) -> $name {
+                $name(self.0.min(other.0))
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                $name(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                $name(self.0 - other.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                self.0 += other.0;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name(-self.0)
+            }
+        }
+
+        impl Mul<$inner> for $name {
+            type Output = $name;
+
+            fn mul(self, factor: $inner) -> $name {
+                $name(self.0 * factor)
+            }
+        }
+
+        impl Div<$inner> for $name {
+            type Output = $name;
+
+            fn div(self, divisor: $inner) -> $name {
+                $name(self.0 / divisor)
+            }
+        }
+
+        // The ratio of two quantities of the same kind
+        impl Div for $name {
+            type Output = $inner;
+
+            fn div(self, other: $name) -> $inner {
+                self.0 / other.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match f.precision() {
+                    Some(precision) => write!(f, "{:.*} {}", precision, self.0, $unit),
+                    None => write!(f, "{} {}", self.0, $unit),
+                }
+            }
+        }
+    };
+}
+
+quantity!(Distance, f64, from_km, km, "km");
+quantity!(Speed, f64, from_kmh, kmh, "km/h");
+quantity!(Volume, f64, from_liters, liters, "l");
+quantity!(Pressure, f32, from_psi, psi, "PSI");
+quantity!(Temperature, f32, from_celsius, celsius, "°C");
+
+impl Speed {
+    pub fn from_meters_per_second(speed: MetersPerSecond) -> Speed {
+        Speed(ms_to_kmh(speed))
+    }
+
+    pub fn meters_per_second(self) -> MetersPerSecond {
+        kmh_to_ms(self.0)
+    }
+
+    // Distance covered at this speed in `hours`
+    pub fn distance(self, hours: Hours) -> Distance {
+        Distance(self.0 * hours)
+    }
+}
+
+impl Distance {
+    // Time needed at `speed`, None when standing still
+    pub fn hours_at(self, speed: Speed) -> Option<Hours> {
+        (speed.0 > 0.0).then(|| self.0 / speed.0)
+    }
+}
+
+impl Pressure {
+    pub fn from_kpa(kpa: f32) -> Pressure {
+        Pressure(kpa / KPA_PER_PSI)
+    }
+
+    pub fn kpa(self) -> f32 {
+        self.0 * KPA_PER_PSI
+    }
+
+    // Pressure above vacuum, for the gas laws
+    pub fn absolute(self) -> Psi {
+        self.0 + ATMOSPHERIC_PRESSURE
+    }
+
+    pub fn from_absolute(absolute: Psi) -> Pressure {
+        Pressure(absolute - ATMOSPHERIC_PRESSURE)
+    }
+}
+
+impl Temperature {
+    pub fn kelvin(self) -> f32 {
+        celsius_to_kelvin(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speeds_convert_between_km_per_hour_and_meters_per_second() {
+        assert!((kmh_to_ms(36.0) - 10.0).abs() < 1e-12);
+        assert!((ms_to_kmh(10.0) - 36.0).abs() < 1e-12);
+        assert_eq!(Speed::from_meters_per_second(25.0).kmh(), 90.0);
+        assert_eq!(Speed::from_kmh(80.0).distance(0.5), Distance::from_km(40.0));
+        assert_eq!(Distance::from_km(40.0).hours_at(Speed::from_kmh(80.0)), Some(0.5));
+        assert_eq!(Distance::from_km(40.0).hours_at(Speed::ZERO), None);
+        assert_eq!(hours_to_seconds(seconds_to_hours(90.0)), 90.0);
+    }
+
+    #[test]
+    fn pressures_and_temperatures_convert_to_absolute_scales() {
+        let pressure = Pressure::from_psi(32.0);
+        assert!((Pressure::from_kpa(pressure.kpa()).psi() - 32.0).abs() < 1e-4);
+        assert!((Pressure::from_absolute(pressure.absolute()).psi() - 32.0).abs() < 1e-4);
+        assert_eq!(Temperature::from_celsius(0.0).kelvin(), 273.15);
+    }
+
+    #[test]
+    fn quantities_do_arithmetic_and_serialize_as_the_bare_number() {
+        let mut distance = Distance::from_km(10.0) + Distance::from_km(5.0) - Distance::from_km(3.0);
+        distance += Distance::from_km(1.0);
+        distance -= Distance::from_km(2.0);
+        assert_eq!(distance, Distance::from_km(11.0));
+        assert_eq!(-distance * 2.0 / 11.0, Distance::from_km(-2.0));
+        assert_eq!(Distance::from_km(3.0) / Distance::from_km(12.0), 0.25);
+        assert_eq!(distance.max(Distance::ZERO).min(Distance::from_km(5.0)), Distance::from_km(5.0));
+
+        assert_eq!(format!("{:.1}", Speed::from_kmh(52.25)), "52.2 km/h");
+        assert_eq!(format!("{}", Temperature::from_celsius(21.5)), "21.5 °C");
+        assert_eq!(serde_json::to_string(&Pressure::from_psi(30.5)).unwrap(), "30.5");
+        assert_eq!(serde_json::from_str::<Volume>("4.5").unwrap(), Volume::from_liters(4.5));
+    }
+}
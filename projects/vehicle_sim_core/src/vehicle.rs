@@ -0,0 +1,365 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::rng::SimRng;
+use crate::storage;
+
+pub const VEHICLE_ENV_VAR: &str = "SIM_VEHICLE";
+pub const TRIM_ENV_VAR: &str = "SIM_TRIM";
+
+// World manufacturer identifier of the simulated vehicles
+const WMI: &str = "5SV";
+// Model year code of position 10 (2025)
+const MODEL_YEAR: char = 'S';
+const PLANT: char = 'A';
+
+// I, O and Q are never used, to avoid confusion with 1 and 0
+const VIN_CHARACTERS: &[u8] = b"0123456789ABCDEFGHJKLMNPRSTUVWXYZ";
+const CHECK_DIGIT_WEIGHTS: [u32; 17] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VinError {
+    Length(usize),
+    Character(char),
+    CheckDigit { expected: char, found: char },
+}
+
+impl fmt::Display for VinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VinError::Length(length) => write!(f, "a VIN has 17 characters, not {}", length),
+            VinError::Character(c) => write!(f, "'{}' is not allowed in a VIN", c),
+            VinError::CheckDigit { expected, found } => {
+                write!(f, "check digit is {} but should be {}", found, expected)
+            }
+        }
+    }
+}
+
+// A 17-character vehicle identification number with a valid check digit
+// at position 9 (ISO 3779 / 49 CFR 565)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Vin(String);
+
+impl Vin {
+    pub fn parse(vin: &str) -> Result<Vin, VinError> {
+        let vin = vin.trim().to_ascii_uppercase();
+        if vin.len() != 17 {
+            return Err(VinError::Length(vin.chars().count()));
+        }
+        if let Some(c) = vin.chars().find(|c| !c.is_ascii() || !VIN_CHARACTERS.contains(&(*c as u8))) {
+            return Err(VinError::Character(c));
+        }
+        let expected = check_digit(vin.as_bytes());
+        let found = vin.as_bytes()[8] as char;
+        if found != expected {
+            return Err(VinError::CheckDigit { expected, found });
+        }
+        Ok(Vin(vin))
+    }
+
+    // Random vehicle descriptor and serial number under the simulator's WMI
+    pub fn generate(rng: &mut impl Rng) -> Vin {
+        let mut vin: Vec<u8> = WMI.bytes().collect();
+        for _ in 0..5 {
+            vin.push(VIN_CHARACTERS[rng.gen_range(10..VIN_CHARACTERS.len())]);
+        }
+        vin.push(b'0');
+        vin.push(MODEL_YEAR as u8);
+        vin.push(PLANT as u8);
+        vin.extend(format!("{:06}", rng.gen_range(0..1_000_000)).bytes());
+        vin[8] = check_digit(&vin) as u8;
+        Vin(String::from_utf8(vin).unwrap())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Vin {
+    type Error = VinError;
+
+    fn try_from(vin: String) -> Result<Vin, VinError> {
+        Vin::parse(&vin)
+    }
+}
+
+impl From<Vin> for String {
+    fn from(vin: Vin) -> String {
+        vin.0
+    }
+}
+
+impl fmt::Display for Vin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn transliterate(c: u8) -> u32 {
+    match c {
+        b'0'..=b'9' => (c - b'0') as u32,
+        b'A'..=b'H' => (c - b'A') as u32 + 1,
+        b'J'..=b'R' => [1, 2, 3, 4, 5, 0, 7, 0, 9][(c - b'J') as usize],
+        b'S'..=b'Z' => (c - b'S') as u32 + 2,
+        _ => 0,
+    }
+}
+
+fn check_digit(vin: &[u8]) -> char {
+    let sum: u32 = vin.iter().zip(CHECK_DIGIT_WEIGHTS).map(|(&c, weight)| transliterate(c) * weight).sum();
+    match sum % 11 {
+        10 => 'X',
+        digit => char::from_digit(digit, 10).unwrap(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trim {
+    Base,
+    Comfort,
+    Premium,
+}
+
+impl Trim {
+    pub fn parse(value: &str) -> Option<Trim> {
+        match value.to_ascii_lowercase().as_str() {
+            "base" => Some(Trim::Base),
+            "comfort" => Some(Trim::Comfort),
+            "premium" => Some(Trim::Premium),
+            _ => None,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Trim> {
+        match byte {
+            0 => Some(Trim::Base),
+            1 => Some(Trim::Comfort),
+            2 => Some(Trim::Premium),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte(self) -> u8 {
+        self as u8
+    }
+
+    // Equipment the trim can be coded for
+    pub fn available(self) -> VariantCoding {
+        match self {
+            // No rear HVAC unit in the base model
+            Trim::Base => VariantCoding(VariantCoding::AUTO_DEFOG | VariantCoding::TPMS_TEMPERATURE_COMPENSATION),
+            Trim::Comfort | Trim::Premium => VariantCoding(VariantCoding::ALL),
+        }
+    }
+
+    // Coding the vehicle leaves the factory with
+    pub fn factory_coding(self) -> VariantCoding {
+        match self {
+            Trim::Base => VariantCoding(VariantCoding::TPMS_TEMPERATURE_COMPENSATION),
+            Trim::Comfort => VariantCoding(VariantCoding::AUTO_DEFOG | VariantCoding::TPMS_TEMPERATURE_COMPENSATION),
+            Trim::Premium => VariantCoding(VariantCoding::ALL),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodingError {
+    UnknownBits(u16),
+    NotAvailable { option: &'static str, trim: Trim },
+}
+
+impl fmt::Display for CodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodingError::UnknownBits(bits) => write!(f, "unknown coding bits 0x{:04X}", bits),
+            CodingError::NotAvailable { option, trim } => {
+                write!(f, "{} is not available on the {:?} trim", option, trim)
+            }
+        }
+    }
+}
+
+// Variant coding: which optional equipment and calibrations the ECUs of
+// this vehicle enable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VariantCoding(pub u16);
+
+impl VariantCoding {
+    // Climate control has a third zone for the rear seats
+    pub const REAR_CLIMATE_ZONE: u16 = 0x0001;
+    // Climate control defogs the windshield on its own
+    pub const AUTO_DEFOG: u16 = 0x0002;
+    // TPMS judges pressures corrected to the reference temperature
+    pub const TPMS_TEMPERATURE_COMPENSATION: u16 = 0x0004;
+
+    pub const ALL: u16 = Self::REAR_CLIMATE_ZONE | Self::AUTO_DEFOG | Self::TPMS_TEMPERATURE_COMPENSATION;
+
+    const NAMES: [(u16, &'static str); 3] = [
+        (Self::REAR_CLIMATE_ZONE, "rear climate zone"),
+        (Self::AUTO_DEFOG, "automatic defog"),
+        (Self::TPMS_TEMPERATURE_COMPENSATION, "TPMS temperature compensation"),
+    ];
+
+    pub fn has(self, option: u16) -> bool {
+        self.0 & option == option
+    }
+
+    // Plausibility check before coding is accepted
+    pub fn check(self, trim: Trim) -> Result<(), CodingError> {
+        if self.0 & !Self::ALL != 0 {
+            return Err(CodingError::UnknownBits(self.0 & !Self::ALL));
+        }
+        let available = trim.available();
+        match Self::NAMES.iter().find(|(bit, _)| self.has(*bit) && !available.has(*bit)) {
+            Some((_, option)) => Err(CodingError::NotAvailable { option, trim }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn describe(self) -> String {
+        let options: Vec<&str> = Self::NAMES
+            .iter()
+            .filter(|(bit, _)| self.has(*bit))
+            .map(|(_, name)| *name)
+            .collect();
+        if options.is_empty() {
+            "no options".to_string()
+        } else {
+            options.join(", ")
+        }
+    }
+}
+
+// Who the vehicle is and how its ECUs are coded, kept in a small JSON file
+// shared by all simulations of the same vehicle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VehicleIdentity {
+    pub vin: Vin,
+    pub trim: Trim,
+    pub coding: VariantCoding,
+}
+
+impl VehicleIdentity {
+    pub fn generate(trim: Trim, rng: &mut impl Rng) -> Self {
+        VehicleIdentity {
+            vin: Vin::generate(rng),
+            trim,
+            coding: trim.factory_coding(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let identity: VehicleIdentity = serde_json::from_str(&fs::read_to_string(path)?)?;
+        identity
+            .coding
+            .check(identity.trim)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        Ok(identity)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        storage::write_atomic(path, serde_json::to_string_pretty(self)?)
+    }
+
+    // Loads the vehicle file if there is one, otherwise builds a new vehicle
+    // of `trim` (and saves it when a path is given). The VIN only depends on
+    // the seed, so reproduced runs get the same vehicle.
+    pub fn load_or_create(path: Option<&Path>, trim: Trim, seed: u64) -> io::Result<Self> {
+        if let Some(path) = path.filter(|path| path.exists()) {
+            return VehicleIdentity::load(path);
+        }
+        let identity = VehicleIdentity::generate(trim, &mut SimRng::from_seed(seed));
+        if let Some(path) = path {
+            identity.save(path)?;
+        }
+        Ok(identity)
+    }
+}
+
+// Uses `--vehicle <path>` from the command line, then SIM_VEHICLE
+pub fn path_from_args() -> Option<PathBuf> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--vehicle")
+        .and_then(|i| args.get(i + 1).map(PathBuf::from))
+        .or_else(|| env::var(VEHICLE_ENV_VAR).ok().map(PathBuf::from))
+}
+
+// Trim of a new vehicle: `--trim <base|comfort|premium>`, then SIM_TRIM,
+// then premium. An existing vehicle file keeps its own trim.
+pub fn trim_from_args() -> Result<Trim, String> {
+    let args: Vec<String> = env::args().collect();
+    let value = args
+        .iter()
+        .position(|arg| arg == "--trim")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| env::var(TRIM_ENV_VAR).ok());
+
+    match value {
+        Some(value) => Trim::parse(&value).ok_or_else(|| format!("unknown trim {}, expected base, comfort or premium", value)),
+        None => Ok(Trim::Premium),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vins_need_seventeen_allowed_characters_and_their_check_digit() {
+        assert_eq!(Vin::parse(" 1m8gdm9axkp042788 ").unwrap().as_str(), "1M8GDM9AXKP042788");
+        assert_eq!(Vin::parse("1M8GDM9A"), Err(VinError::Length(8)));
+        assert_eq!(Vin::parse("1M8GDM9AXKP04278O"), Err(VinError::Character('O')));
+        assert_eq!(
+            Vin::parse("1M8GDM9A1KP042788"),
+            Err(VinError::CheckDigit {
+                expected: 'X',
+                found: '1'
+            })
+        );
+
+        let vin = Vin::generate(&mut SimRng::from_seed(5));
+        assert!(vin.as_str().starts_with(WMI));
+        assert_eq!(Vin::parse(vin.as_str()), Ok(vin.clone()));
+        assert_eq!(vin, Vin::generate(&mut SimRng::from_seed(5)));
+        assert!(serde_json::from_str::<Vin>("\"1M8GDM9A1KP042788\"").is_err());
+    }
+
+    #[test]
+    fn coding_is_checked_against_the_trim() {
+        assert_eq!(VariantCoding(0x0100).check(Trim::Premium), Err(CodingError::UnknownBits(0x0100)));
+        assert!(VariantCoding(VariantCoding::REAR_CLIMATE_ZONE).check(Trim::Base).is_err());
+        for trim in [Trim::Base, Trim::Comfort, Trim::Premium] {
+            assert_eq!(trim.factory_coding().check(trim), Ok(()));
+            assert_eq!(Trim::from_byte(trim.as_byte()), Some(trim));
+        }
+        assert_eq!(VariantCoding(0).describe(), "no options");
+        assert_eq!(
+            VariantCoding(VariantCoding::AUTO_DEFOG | VariantCoding::REAR_CLIMATE_ZONE).describe(),
+            "rear climate zone, automatic defog"
+        );
+        assert_eq!(Trim::parse("Comfort"), Some(Trim::Comfort));
+    }
+
+    #[test]
+    fn a_vehicle_file_is_created_once_and_then_kept() {
+        let path = std::env::temp_dir().join(format!("sim_vehicle_{}.json", std::process::id()));
+        let created = VehicleIdentity::load_or_create(Some(&path), Trim::Base, 1).unwrap();
+        let loaded = VehicleIdentity::load_or_create(Some(&path), Trim::Premium, 2).unwrap();
+        assert_eq!(loaded, created);
+        assert_eq!(loaded.trim, Trim::Base);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+use crate::can_bus::{CanBus, VEHICLE_MODE};
+use crate::events::Event;
+use crate::scenario::Scenario;
+use crate::sim_log;
+
+// Global state of the vehicle every ECU adapts its behavior to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VehicleMode {
+    Parked,
+    Driving,
+    Charging,
+    Service,
+}
+
+impl VehicleMode {
+    pub const ALL: [VehicleMode; 4] = [VehicleMode::Parked, VehicleMode::Driving, VehicleMode::Charging, VehicleMode::Service];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VehicleMode::Parked => "parked",
+            VehicleMode::Driving => "driving",
+            VehicleMode::Charging => "charging",
+            VehicleMode::Service => "service",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<VehicleMode> {
+        VehicleMode::ALL.into_iter().find(|mode| mode.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    // Value of the VehicleMode signal
+    pub fn code(self) -> u8 {
+        match self {
+            VehicleMode::Parked => 0,
+            VehicleMode::Driving => 1,
+            VehicleMode::Charging => 2,
+            VehicleMode::Service => 3,
+        }
+    }
+
+    // Every other mode is entered from and left to Parked: the vehicle
+    // stops before it is plugged in or handed to the workshop
+    pub fn can_change_to(self, to: VehicleMode) -> bool {
+        self == to || self == VehicleMode::Parked || to == VehicleMode::Parked
+    }
+}
+
+// A mode change `at` seconds into the scenario
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModeChange {
+    pub at: f64,
+    pub mode: VehicleMode,
+}
+
+// Holds the vehicle mode, switches it along a scenario timeline and
+// announces it on the bus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModeManager {
+    mode: VehicleMode,
+    timeline: Vec<ModeChange>,
+    // Timeline entries already applied
+    applied: usize,
+}
+
+// The simulations model a vehicle on the road unless told otherwise
+impl Default for ModeManager {
+    fn default() -> Self {
+        ModeManager::new(VehicleMode::Driving)
+    }
+}
+
+impl ModeManager {
+    pub fn new(mode: VehicleMode) -> Self {
+        ModeManager {
+            mode,
+            timeline: Vec::new(),
+            applied: 0,
+        }
+    }
+
+    pub fn with_timeline(mut self, timeline: &[ModeChange]) -> Self {
+        self.timeline = timeline.to_vec();
+        self
+    }
+
+    // Starts in the scenario's initial `vehicle_mode`, or `default`, and
+    // follows its `[[mode]]` changes
+    pub fn from_scenario(scenario: Option<&Scenario>, default: VehicleMode) -> Self {
+        let Some(scenario) = scenario else {
+            return ModeManager::new(default);
+        };
+        let mode = scenario.initial_str("vehicle_mode").map_or(default, |name| {
+            VehicleMode::parse(name).unwrap_or_else(|| {
+                sim_log::warn("scenario", &format!("Ignoring unknown initial vehicle_mode {}", name));
+                default
+            })
+        });
+        ModeManager::new(mode).with_timeline(&scenario.modes)
+    }
+
+    pub fn mode(&self) -> VehicleMode {
+        self.mode
+    }
+
+    // Switches to `mode` if allowed from the current one
+    pub fn request(&mut self, mode: VehicleMode) -> Result<(), String> {
+        if !self.mode.can_change_to(mode) {
+            return Err(format!("cannot switch from {} to {}, park first", self.mode.name(), mode.name()));
+        }
+        if mode != self.mode {
+            self.mode = mode;
+            sim_log::event(
+                None,
+                &Event::ModeChanged {
+                    component: "vehicle".to_string(),
+                    mode: mode.name().to_string(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    // Applies the timeline entries due by `seconds`; a change that is not
+    // allowed is skipped with a warning
+    pub fn update(&mut self, seconds: f64) {
+        while let Some(change) = self.timeline.get(self.applied).filter(|change| change.at <= seconds).copied() {
+            self.applied += 1;
+            if let Err(e) = self.request(change.mode) {
+                sim_log::warn("vehicle", &format!("Ignoring scenario mode change: {}", e));
+            }
+        }
+    }
+
+    pub fn transmit(&self, bus: &CanBus) {
+        bus.transmit(&VEHICLE_MODE, &[("VehicleMode", self.mode.code() as f64)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_change_only_through_parked() {
+        use VehicleMode::*;
+        for mode in VehicleMode::ALL {
+            assert!(Parked.can_change_to(mode) && mode.can_change_to(Parked) && mode.can_change_to(mode));
+        }
+        assert!(!Driving.can_change_to(Charging));
+        assert!(!Charging.can_change_to(Service));
+        assert!(!Service.can_change_to(Driving));
+        assert_eq!(VehicleMode::parse(" Charging "), Some(Charging));
+        assert_eq!(VehicleMode::parse("towing"), None);
+    }
+
+    #[test]
+    fn a_rejected_request_keeps_the_mode() {
+        let mut manager = ModeManager::new(VehicleMode::Driving);
+        let error = manager.request(VehicleMode::Charging).unwrap_err();
+        assert_eq!(error, "cannot switch from driving to charging, park first");
+        assert_eq!(manager.mode(), VehicleMode::Driving);
+
+        manager.request(VehicleMode::Parked).unwrap();
+        manager.request(VehicleMode::Charging).unwrap();
+        assert_eq!(manager.mode(), VehicleMode::Charging);
+    }
+
+    #[test]
+    fn the_timeline_skips_changes_that_are_not_allowed() {
+        let timeline = [
+            ModeChange { at: 10.0, mode: VehicleMode::Service },
+            ModeChange { at: 20.0, mode: VehicleMode::Parked },
+            ModeChange { at: 30.0, mode: VehicleMode::Charging },
+        ];
+        let mut manager = ModeManager::new(VehicleMode::Driving).with_timeline(&timeline);
+        manager.update(5.0);
+        assert_eq!(manager.mode(), VehicleMode::Driving);
+        // Straight from driving into the workshop is refused
+        manager.update(15.0);
+        assert_eq!(manager.mode(), VehicleMode::Driving);
+        manager.update(30.0);
+        assert_eq!(manager.mode(), VehicleMode::Charging);
+    }
+}
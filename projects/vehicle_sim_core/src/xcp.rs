@@ -0,0 +1,671 @@
+use std::env;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::can_bus::CanBus;
+use crate::signal_store::SignalStore;
+use crate::clock;
+use crate::description::Description;
+use crate::sim_log;
+
+pub const XCP_ENV_VAR: &str = "SIM_XCP";
+
+// Address extensions: measurements and characteristics are addressed by
+// their index in the description, one 32-bit float per address
+pub const MEASUREMENTS: u8 = 0;
+pub const CHARACTERISTICS: u8 = 1;
+
+// The only event channel: the end of every simulation step. A DAQ list's
+// prescaler samples it every n-th step.
+pub const STEP_EVENT: u16 = 0;
+
+const CONNECT: u8 = 0xFF;
+const DISCONNECT: u8 = 0xFE;
+const GET_STATUS: u8 = 0xFD;
+const SYNCH: u8 = 0xFC;
+const SET_MTA: u8 = 0xF6;
+const UPLOAD: u8 = 0xF5;
+const SHORT_UPLOAD: u8 = 0xF4;
+const DOWNLOAD: u8 = 0xF0;
+const SHORT_DOWNLOAD: u8 = 0xED;
+const SET_DAQ_PTR: u8 = 0xE2;
+const WRITE_DAQ: u8 = 0xE1;
+const SET_DAQ_LIST_MODE: u8 = 0xE0;
+const START_STOP_DAQ_LIST: u8 = 0xDE;
+const START_STOP_SYNCH: u8 = 0xDD;
+const GET_DAQ_PROCESSOR_INFO: u8 = 0xDA;
+const FREE_DAQ: u8 = 0xD6;
+const ALLOC_DAQ: u8 = 0xD5;
+const ALLOC_ODT: u8 = 0xD4;
+const ALLOC_ODT_ENTRY: u8 = 0xD3;
+
+const POSITIVE_RESPONSE: u8 = 0xFF;
+const ERROR_RESPONSE: u8 = 0xFE;
+
+pub const ERR_CMD_SYNCH: u8 = 0x00;
+pub const ERR_DAQ_ACTIVE: u8 = 0x11;
+pub const ERR_CMD_UNKNOWN: u8 = 0x20;
+pub const ERR_CMD_SYNTAX: u8 = 0x21;
+pub const ERR_OUT_OF_RANGE: u8 = 0x22;
+pub const ERR_WRITE_PROTECTED: u8 = 0x23;
+pub const ERR_SEQUENCE: u8 = 0x29;
+pub const ERR_DAQ_CONFIG: u8 = 0x2A;
+pub const ERR_MEMORY_OVERFLOW: u8 = 0x30;
+
+// Calibration and DAQ resources, Intel byte order, DWORD address granularity
+const RESOURCES: u8 = 0x01 | 0x04;
+const COMM_MODE_BASIC: u8 = 0x04;
+const MAX_CTO: u8 = 255;
+const MAX_DTO: u16 = 1024;
+const ELEMENT_SIZE: usize = 4;
+
+// DAQ list mode and session status bits
+const TIMESTAMP_MODE: u8 = 0x10;
+const DAQ_RUNNING: u8 = 0x40;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OdtEntry {
+    extension: u8,
+    address: u32,
+    elements: u8,
+}
+
+#[derive(Debug, Clone, Default)]
+struct DaqList {
+    odts: Vec<Vec<OdtEntry>>,
+    mode: u8,
+    prescaler: u8,
+    selected: bool,
+    running: bool,
+    // Steps since the list started, for the prescaler
+    cycle: u32,
+}
+
+struct Parameter {
+    name: String,
+    value: f64,
+    // Text parameters have no range and cannot be written
+    range: Option<(f64, f64)>,
+}
+
+#[derive(Default)]
+struct Session {
+    master: Option<SocketAddr>,
+    mta: (u8, u32),
+    daq_lists: Vec<DaqList>,
+    daq_pointer: Option<(usize, usize, usize)>,
+    counter: u16,
+}
+
+struct Memory {
+    measurements: Vec<(String, f64)>,
+    characteristics: Vec<Parameter>,
+    // Characteristics written since the simulation last took them
+    written: Vec<(String, f64)>,
+    bus: Option<Arc<SignalStore>>,
+}
+
+impl Memory {
+    fn read(&self, extension: u8, address: u32) -> Option<f32> {
+        let index = address as usize;
+        match extension {
+            MEASUREMENTS => self.measurements.get(index).map(|(_, value)| *value as f32),
+            CHARACTERISTICS => self.characteristics.get(index).map(|parameter| parameter.value as f32),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, extension: u8, address: u32, value: f32) -> Result<(), u8> {
+        if extension != CHARACTERISTICS {
+            return Err(ERR_WRITE_PROTECTED);
+        }
+        let parameter = self.characteristics.get_mut(address as usize).ok_or(ERR_OUT_OF_RANGE)?;
+        let (min, max) = parameter.range.ok_or(ERR_WRITE_PROTECTED)?;
+        // Through the shortest decimal form, so 0.97 does not arrive as 0.9700000286
+        let value: f64 = value.to_string().parse().unwrap_or(value as f64);
+        if !(min..=max).contains(&value) {
+            return Err(ERR_OUT_OF_RANGE);
+        }
+        parameter.value = value;
+        self.written.retain(|(name, _)| *name != parameter.name);
+        self.written.push((parameter.name.clone(), value));
+        Ok(())
+    }
+}
+
+// XCP on UDP slave, reduced to what measurement and calibration tools need:
+// polling with SHORT_UPLOAD, dynamic DAQ lists sent after every n-th step,
+// and writing characteristics with (SHORT_)DOWNLOAD. Each datagram holds one
+// packet behind the XCP on Ethernet header (length and counter, 16 bits each).
+// A DAQ packet is the absolute ODT number, the simulated time in ms when the
+// list has timestamps, then the entries as 32-bit floats.
+pub struct XcpServer {
+    socket: UdpSocket,
+    session: Mutex<Session>,
+    memory: Mutex<Memory>,
+}
+
+impl XcpServer {
+    // Serves the measurements and characteristics of `description` on the
+    // UDP `address`
+    pub fn start(address: &str, description: &Description) -> io::Result<Arc<XcpServer>> {
+        let socket = UdpSocket::bind(address)?;
+        let memory = Memory {
+            measurements: description.measurements.iter().map(|m| (m.name.clone(), f64::NAN)).collect(),
+            characteristics: description
+                .characteristics
+                .iter()
+                .map(|c| Parameter {
+                    name: c.name.clone(),
+                    value: c.value.parse().unwrap_or(f64::NAN),
+                    range: c.min.zip(c.max),
+                })
+                .collect(),
+            written: Vec::new(),
+            bus: None,
+        };
+        let server = Arc::new(XcpServer {
+            socket,
+            session: Mutex::new(Session::default()),
+            memory: Mutex::new(memory),
+        });
+
+        let served = Arc::clone(&server);
+        thread::spawn(move || {
+            let mut buffer = [0u8; 2048];
+            loop {
+                match served.socket.recv_from(&mut buffer) {
+                    Ok((length, from)) => served.receive(&buffer[..length], from),
+                    Err(e) => {
+                        sim_log::warn("xcp", &format!("XCP socket failed: {}", e));
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(server)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    // Keeps the measurements of bus signals up to date from the signals
+    // sent on `bus`
+    pub fn watch_bus(&self, bus: &CanBus) {
+        self.memory.lock().unwrap().bus = Some(bus.signals());
+    }
+
+    // Characteristics a tool wrote since the last call, for the simulation to
+    // apply between steps
+    pub fn take_writes(&self) -> Vec<(String, f64)> {
+        std::mem::take(&mut self.memory.lock().unwrap().written)
+    }
+
+    // Current value of a numeric characteristic, including writes
+    pub fn characteristic(&self, name: &str) -> Option<f64> {
+        let memory = self.memory.lock().unwrap();
+        memory
+            .characteristics
+            .iter()
+            .find(|parameter| parameter.name == name && parameter.range.is_some())
+            .map(|parameter| parameter.value)
+    }
+
+    // Updates measurements by name and sends the DAQ lists due this step;
+    // call once at the end of every step
+    pub fn publish(&self, values: &[(&str, f64)]) {
+        {
+            let mut memory = self.memory.lock().unwrap();
+            let snapshot = memory.bus.as_ref().map(|bus| bus.snapshot());
+            let mut updates: Vec<(&str, f64)> = snapshot.iter().flat_map(|snapshot| snapshot.iter()).collect();
+            updates.extend_from_slice(values);
+            for (name, value) in updates {
+                if let Some(measurement) = memory.measurements.iter_mut().find(|(measured, _)| measured == name) {
+                    measurement.1 = value;
+                }
+            }
+        }
+
+        // Locked in the same order as while answering commands
+        let mut session = self.session.lock().unwrap();
+        let Some(master) = session.master else {
+            return;
+        };
+        let memory = self.memory.lock().unwrap();
+        let timestamp = (clock::now() * 1000.0) as u32;
+        let mut packets = Vec::new();
+        let mut pid = 0usize;
+        for list in &mut session.daq_lists {
+            let due = list.running && {
+                list.cycle += 1;
+                list.cycle.is_multiple_of(list.prescaler.max(1) as u32)
+            };
+            for odt in &list.odts {
+                if due {
+                    let mut packet = vec![pid as u8];
+                    if list.mode & TIMESTAMP_MODE != 0 {
+                        packet.extend(timestamp.to_le_bytes());
+                    }
+                    for entry in odt {
+                        for i in 0..entry.elements as u32 {
+                            let value = memory.read(entry.extension, entry.address + i).unwrap_or(f32::NAN);
+                            packet.extend(value.to_le_bytes());
+                        }
+                    }
+                    packets.push(packet);
+                }
+                pid += 1;
+            }
+        }
+        drop(memory);
+        for packet in packets {
+            self.send(&mut session, master, &packet);
+        }
+    }
+
+    fn receive(&self, datagram: &[u8], from: SocketAddr) {
+        let Some((header, packet)) = datagram.split_at_checked(4) else {
+            return;
+        };
+        let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+        let Some(command) = packet.get(..length).filter(|command| !command.is_empty()) else {
+            return;
+        };
+
+        let mut session = self.session.lock().unwrap();
+        // Only a connected master gets answers, as on any XCP slave
+        if command[0] != CONNECT && session.master != Some(from) {
+            return;
+        }
+        let response = match self.handle(&mut session, command, from) {
+            Ok(mut data) => {
+                data.insert(0, POSITIVE_RESPONSE);
+                data
+            }
+            Err(code) => vec![ERROR_RESPONSE, code],
+        };
+        self.send(&mut session, from, &response);
+    }
+
+    fn send(&self, session: &mut Session, to: SocketAddr, packet: &[u8]) {
+        let mut datagram = Vec::with_capacity(packet.len() + 4);
+        datagram.extend((packet.len() as u16).to_le_bytes());
+        datagram.extend(session.counter.to_le_bytes());
+        datagram.extend(packet);
+        session.counter = session.counter.wrapping_add(1);
+        if let Err(e) = self.socket.send_to(&datagram, to) {
+            sim_log::debug("xcp", &format!("Cannot send to {}: {}", to, e));
+        }
+    }
+
+    fn handle(&self, session: &mut Session, command: &[u8], from: SocketAddr) -> Result<Vec<u8>, u8> {
+        let byte = |i: usize| command.get(i).copied().ok_or(ERR_CMD_SYNTAX);
+        let word = |i: usize| -> Result<u16, u8> { Ok(u16::from_le_bytes([byte(i)?, byte(i + 1)?])) };
+        let dword = |i: usize| -> Result<u32, u8> { Ok(u32::from_le_bytes([byte(i)?, byte(i + 1)?, byte(i + 2)?, byte(i + 3)?])) };
+
+        match command[0] {
+            CONNECT => {
+                if session.master != Some(from) {
+                    *session = Session::default();
+                    session.master = Some(from);
+                    sim_log::info("xcp", &format!("XCP master {} connected", from));
+                }
+                let mut data = vec![RESOURCES, COMM_MODE_BASIC, MAX_CTO];
+                data.extend(MAX_DTO.to_le_bytes());
+                data.extend([1, 1]);
+                Ok(data)
+            }
+            DISCONNECT => {
+                *session = Session {
+                    counter: session.counter,
+                    ..Session::default()
+                };
+                sim_log::info("xcp", &format!("XCP master {} disconnected", from));
+                Ok(Vec::new())
+            }
+            GET_STATUS => {
+                let running = if session.daq_lists.iter().any(|list| list.running) { DAQ_RUNNING } else { 0 };
+                Ok(vec![running, 0, 0, 0, 0])
+            }
+            SYNCH => Err(ERR_CMD_SYNCH),
+            SET_MTA => {
+                session.mta = (byte(3)?, dword(4)?);
+                Ok(Vec::new())
+            }
+            UPLOAD => {
+                let (extension, address) = session.mta;
+                let count = byte(1)?;
+                let data = self.upload(extension, address, count)?;
+                session.mta.1 = address + count as u32;
+                Ok(data)
+            }
+            SHORT_UPLOAD => self.upload(byte(3)?, dword(4)?, byte(1)?),
+            DOWNLOAD => {
+                let (extension, address) = session.mta;
+                let count = byte(1)?;
+                self.download(extension, address, count, command.get(4..).unwrap_or_default())?;
+                session.mta.1 = address + count as u32;
+                Ok(Vec::new())
+            }
+            SHORT_DOWNLOAD => {
+                self.download(byte(3)?, dword(4)?, byte(1)?, command.get(8..).unwrap_or_default())?;
+                Ok(Vec::new())
+            }
+            GET_DAQ_PROCESSOR_INFO => {
+                // Dynamic configuration, prescalers and timestamps supported
+                let mut data = vec![0x01 | 0x02 | 0x10];
+                data.extend((session.daq_lists.len() as u16).to_le_bytes());
+                data.extend(1u16.to_le_bytes());
+                data.extend([0, 0]);
+                Ok(data)
+            }
+            FREE_DAQ => {
+                session.daq_lists.clear();
+                session.daq_pointer = None;
+                Ok(Vec::new())
+            }
+            ALLOC_DAQ => {
+                if !session.daq_lists.is_empty() {
+                    return Err(ERR_SEQUENCE);
+                }
+                session.daq_lists = vec![DaqList::default(); word(2)? as usize];
+                Ok(Vec::new())
+            }
+            ALLOC_ODT => {
+                let (index, count) = (word(2)? as usize, byte(4)? as usize);
+                let allocated: usize = session.daq_lists.iter().map(|list| list.odts.len()).sum();
+                let list = session.daq_lists.get_mut(index).ok_or(ERR_OUT_OF_RANGE)?;
+                if !list.odts.is_empty() {
+                    return Err(ERR_SEQUENCE);
+                }
+                // Every ODT needs a packet identifier of its own
+                if allocated + count > u8::MAX as usize {
+                    return Err(ERR_MEMORY_OVERFLOW);
+                }
+                list.odts = vec![Vec::new(); count];
+                Ok(Vec::new())
+            }
+            ALLOC_ODT_ENTRY => {
+                let list = session.daq_lists.get_mut(word(2)? as usize).ok_or(ERR_OUT_OF_RANGE)?;
+                let odt = list.odts.get_mut(byte(4)? as usize).ok_or(ERR_OUT_OF_RANGE)?;
+                if !odt.is_empty() {
+                    return Err(ERR_SEQUENCE);
+                }
+                let unset = OdtEntry {
+                    extension: MEASUREMENTS,
+                    address: 0,
+                    elements: 0,
+                };
+                *odt = vec![unset; byte(5)? as usize];
+                Ok(Vec::new())
+            }
+            SET_DAQ_PTR => {
+                let (list, odt, entry) = (word(2)? as usize, byte(4)? as usize, byte(5)? as usize);
+                session
+                    .daq_lists
+                    .get(list)
+                    .and_then(|daq| daq.odts.get(odt))
+                    .and_then(|entries| entries.get(entry))
+                    .ok_or(ERR_OUT_OF_RANGE)?;
+                session.daq_pointer = Some((list, odt, entry));
+                Ok(Vec::new())
+            }
+            WRITE_DAQ => {
+                let entry = OdtEntry {
+                    extension: byte(3)?,
+                    address: dword(4)?,
+                    elements: byte(2)?,
+                };
+                self.write_daq(session, entry)?;
+                Ok(Vec::new())
+            }
+            SET_DAQ_LIST_MODE => {
+                let list = session.daq_lists.get_mut(word(2)? as usize).ok_or(ERR_OUT_OF_RANGE)?;
+                if list.running {
+                    return Err(ERR_DAQ_ACTIVE);
+                }
+                if word(4)? != STEP_EVENT {
+                    return Err(ERR_OUT_OF_RANGE);
+                }
+                list.mode = byte(1)?;
+                list.prescaler = byte(6)?.max(1);
+                Ok(Vec::new())
+            }
+            START_STOP_DAQ_LIST => {
+                let index = word(2)? as usize;
+                let first_pid: usize = session.daq_lists.iter().take(index).map(|list| list.odts.len()).sum();
+                let list = session.daq_lists.get_mut(index).ok_or(ERR_OUT_OF_RANGE)?;
+                match byte(1)? {
+                    0 => list.running = false,
+                    1 => {
+                        list.running = true;
+                        list.cycle = 0;
+                    }
+                    2 => list.selected = true,
+                    _ => return Err(ERR_OUT_OF_RANGE),
+                }
+                Ok(vec![first_pid as u8])
+            }
+            START_STOP_SYNCH => {
+                let mode = byte(1)?;
+                if mode > 2 {
+                    return Err(ERR_OUT_OF_RANGE);
+                }
+                for list in &mut session.daq_lists {
+                    if mode == 0 || (list.selected && mode == 2) {
+                        list.running = false;
+                    } else if list.selected && mode == 1 {
+                        list.running = true;
+                        list.cycle = 0;
+                    }
+                    list.selected = false;
+                }
+                Ok(Vec::new())
+            }
+            _ => Err(ERR_CMD_UNKNOWN),
+        }
+    }
+
+    fn upload(&self, extension: u8, address: u32, count: u8) -> Result<Vec<u8>, u8> {
+        if count as usize * ELEMENT_SIZE + 4 > MAX_CTO as usize {
+            return Err(ERR_OUT_OF_RANGE);
+        }
+        let memory = self.memory.lock().unwrap();
+        // Three alignment bytes keep the data on DWORD boundaries
+        let mut data = vec![0; 3];
+        for i in 0..count as u32 {
+            data.extend(memory.read(extension, address + i).ok_or(ERR_OUT_OF_RANGE)?.to_le_bytes());
+        }
+        Ok(data)
+    }
+
+    fn download(&self, extension: u8, address: u32, count: u8, data: &[u8]) -> Result<(), u8> {
+        if data.len() < count as usize * ELEMENT_SIZE {
+            return Err(ERR_CMD_SYNTAX);
+        }
+        let mut memory = self.memory.lock().unwrap();
+        for (i, bytes) in data.chunks_exact(ELEMENT_SIZE).take(count as usize).enumerate() {
+            let value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            memory.write(extension, address + i as u32, value)?;
+        }
+        Ok(())
+    }
+
+    fn write_daq(&self, session: &mut Session, entry: OdtEntry) -> Result<(), u8> {
+        let (list, odt, index) = session.daq_pointer.ok_or(ERR_SEQUENCE)?;
+        let daq = &mut session.daq_lists[list];
+        if daq.running {
+            return Err(ERR_DAQ_ACTIVE);
+        }
+        {
+            let memory = self.memory.lock().unwrap();
+            let readable = (0..entry.elements as u32).all(|i| memory.read(entry.extension, entry.address + i).is_some());
+            if entry.elements == 0 || !readable {
+                return Err(ERR_OUT_OF_RANGE);
+            }
+        }
+
+        let entries = &mut daq.odts[odt];
+        let elements: usize = entries.iter().map(|entry| entry.elements as usize).sum::<usize>() - entries[index].elements as usize
+            + entry.elements as usize;
+        if 5 + elements * ELEMENT_SIZE > MAX_DTO as usize {
+            return Err(ERR_DAQ_CONFIG);
+        }
+        entries[index] = entry;
+        // The pointer moves on to the next entry of the same ODT
+        session.daq_pointer = (index + 1 < entries.len()).then_some((list, odt, index + 1));
+        Ok(())
+    }
+}
+
+// Uses `--xcp <address>` from the command line, then SIM_XCP
+pub fn address_from_args() -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--xcp")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| env::var(XCP_ENV_VAR).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::description::{Address, Characteristic, DataType, Measurement};
+
+    fn server() -> Arc<XcpServer> {
+        let description = Description::new("test", "1.0")
+            .with_measurement(Measurement::new("speed", Address::ObdPid { pid: 0x0D }, DataType::Ubyte, 1.0, "km/h"))
+            .with_measurement(Measurement::new("rpm", Address::ObdPid { pid: 0x0C }, DataType::Uword, 0.25, "1/min"))
+            .with_characteristic(Characteristic::number("gain", 0.5, "", 0.0, 1.0))
+            .with_characteristic(Characteristic::text("variant", "EU"));
+        XcpServer::start("127.0.0.1:0", &description).unwrap()
+    }
+
+    fn master() -> SocketAddr {
+        "127.0.0.1:5555".parse().unwrap()
+    }
+
+    // Runs `commands` one after another, stopping at the first error
+    fn run(server: &XcpServer, session: &mut Session, commands: &[&[u8]]) -> Result<Vec<u8>, u8> {
+        let mut response = Vec::new();
+        for command in commands {
+            response = server.handle(session, command, master())?;
+        }
+        Ok(response)
+    }
+
+    #[test]
+    fn connect_over_udp_frames_the_response() {
+        let server = server();
+        let tool = UdpSocket::bind("127.0.0.1:0").unwrap();
+        tool.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        tool.send_to(&[1, 0, 0, 0, CONNECT, 0], server.local_addr().unwrap()).unwrap();
+        let mut buffer = [0u8; 64];
+        let length = tool.recv(&mut buffer).unwrap();
+        assert_eq!(
+            &buffer[..length],
+            &[8, 0, 0, 0, POSITIVE_RESPONSE, RESOURCES, COMM_MODE_BASIC, MAX_CTO, 0x00, 0x04, 1, 1]
+        );
+
+        // Commands from anyone but the connected master go unanswered
+        let stranger = UdpSocket::bind("127.0.0.1:0").unwrap();
+        stranger.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        stranger.send_to(&[1, 0, 0, 0, GET_STATUS], server.local_addr().unwrap()).unwrap();
+        assert!(stranger.recv(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn uploads_measurements_and_downloads_characteristics() {
+        let server = server();
+        let mut session = Session::default();
+        server.publish(&[("speed", 88.0), ("rpm", 2500.0)]);
+        let upload = run(&server, &mut session, &[&[CONNECT, 0], &[SHORT_UPLOAD, 2, 0, MEASUREMENTS, 0, 0, 0, 0]]).unwrap();
+        assert_eq!(&upload[3..7], &88f32.to_le_bytes());
+        assert_eq!(&upload[7..11], &2500f32.to_le_bytes());
+
+        let mut download = vec![SHORT_DOWNLOAD, 1, 0, CHARACTERISTICS, 0, 0, 0, 0];
+        download.extend(0.97f32.to_le_bytes());
+        run(&server, &mut session, &[&download]).unwrap();
+        assert_eq!(server.characteristic("gain"), Some(0.97));
+        assert_eq!(server.take_writes(), vec![("gain".to_string(), 0.97)]);
+
+        download[8..].copy_from_slice(&2f32.to_le_bytes());
+        assert_eq!(run(&server, &mut session, &[&download]), Err(ERR_OUT_OF_RANGE));
+        download[4] = 1;
+        assert_eq!(run(&server, &mut session, &[&download]), Err(ERR_WRITE_PROTECTED));
+        download[3] = MEASUREMENTS;
+        assert_eq!(run(&server, &mut session, &[&download]), Err(ERR_WRITE_PROTECTED));
+    }
+
+    #[test]
+    fn configures_a_daq_list() {
+        let server = server();
+        let mut session = Session::default();
+        run(
+            &server,
+            &mut session,
+            &[
+                &[CONNECT, 0],
+                &[ALLOC_DAQ, 0, 1, 0],
+                &[ALLOC_ODT, 0, 0, 0, 1],
+                &[ALLOC_ODT_ENTRY, 0, 0, 0, 0, 2],
+                &[SET_DAQ_PTR, 0, 0, 0, 0, 0],
+                &[WRITE_DAQ, 0, 1, MEASUREMENTS, 1, 0, 0, 0],
+                &[WRITE_DAQ, 0, 1, CHARACTERISTICS, 0, 0, 0, 0],
+                &[SET_DAQ_LIST_MODE, TIMESTAMP_MODE, 0, 0, 0, 0, 10, 0],
+            ],
+        )
+        .unwrap();
+        let list = &session.daq_lists[0];
+        assert_eq!(
+            list.odts,
+            vec![vec![
+                OdtEntry { extension: MEASUREMENTS, address: 1, elements: 1 },
+                OdtEntry { extension: CHARACTERISTICS, address: 0, elements: 1 },
+            ]]
+        );
+        assert_eq!((list.mode, list.prescaler), (TIMESTAMP_MODE, 10));
+        // The pointer ran past the last entry
+        assert_eq!(session.daq_pointer, None);
+
+        assert_eq!(run(&server, &mut session, &[&[START_STOP_DAQ_LIST, 1, 0, 0]]), Ok(vec![0]));
+        assert_eq!(run(&server, &mut session, &[&[GET_STATUS]]), Ok(vec![DAQ_RUNNING, 0, 0, 0, 0]));
+        assert_eq!(
+            run(&server, &mut session, &[&[SET_DAQ_LIST_MODE, 0, 0, 0, 0, 0, 1, 0]]),
+            Err(ERR_DAQ_ACTIVE)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_daq_configurations() {
+        let server = server();
+        let mut session = Session::default();
+        run(&server, &mut session, &[&[CONNECT, 0], &[ALLOC_DAQ, 0, 2, 0], &[ALLOC_ODT, 0, 0, 0, 200]]).unwrap();
+
+        // Too many ODTs in all leaves the list unallocated
+        assert_eq!(run(&server, &mut session, &[&[ALLOC_ODT, 0, 1, 0, 100]]), Err(ERR_MEMORY_OVERFLOW));
+        assert!(session.daq_lists[1].odts.is_empty());
+        assert_eq!(run(&server, &mut session, &[&[ALLOC_ODT, 0, 1, 0, 55]]), Ok(Vec::new()));
+        assert_eq!(run(&server, &mut session, &[&[ALLOC_ODT, 0, 1, 0, 1]]), Err(ERR_SEQUENCE));
+        assert_eq!(run(&server, &mut session, &[&[ALLOC_ODT, 0, 2, 0, 1]]), Err(ERR_OUT_OF_RANGE));
+        assert_eq!(run(&server, &mut session, &[&[ALLOC_DAQ, 0, 1, 0]]), Err(ERR_SEQUENCE));
+
+        assert_eq!(run(&server, &mut session, &[&[WRITE_DAQ, 0, 1, MEASUREMENTS, 0, 0, 0, 0]]), Err(ERR_SEQUENCE));
+        assert_eq!(run(&server, &mut session, &[&[SET_DAQ_PTR, 0, 0, 0, 0, 0]]), Err(ERR_OUT_OF_RANGE));
+        run(&server, &mut session, &[&[ALLOC_ODT_ENTRY, 0, 0, 0, 0, 1], &[SET_DAQ_PTR, 0, 0, 0, 0, 0]]).unwrap();
+        // Past the last measurement, and no elements at all
+        assert_eq!(run(&server, &mut session, &[&[WRITE_DAQ, 0, 1, MEASUREMENTS, 2, 0, 0, 0]]), Err(ERR_OUT_OF_RANGE));
+        assert_eq!(run(&server, &mut session, &[&[WRITE_DAQ, 0, 0, MEASUREMENTS, 0, 0, 0, 0]]), Err(ERR_OUT_OF_RANGE));
+
+        assert_eq!(run(&server, &mut session, &[&[SYNCH]]), Err(ERR_CMD_SYNCH));
+        assert_eq!(run(&server, &mut session, &[&[0xC0]]), Err(ERR_CMD_UNKNOWN));
+        assert_eq!(run(&server, &mut session, &[&[SHORT_UPLOAD, 1]]), Err(ERR_CMD_SYNTAX));
+        assert_eq!(run(&server, &mut session, &[&[SHORT_UPLOAD, 100, 0, MEASUREMENTS, 0, 0, 0, 0]]), Err(ERR_OUT_OF_RANGE));
+    }
+}